@@ -0,0 +1,100 @@
+//! Merging a finished command's cell back into its shell
+//!
+//! Ctrl+Shift+A (or Super+A) on a command cell whose command has exited
+//! writes the cell's output, under a header line naming the command, into the
+//! shell that launched it, just above the shell's prompt, and closes the cell.
+//! The output ends up in the shell's scrollback as it would in a classic
+//! terminal, for when a linear history is handier than separate cells. The
+//! cell can be brought back with undo.
+
+use crate::layout::calculate_terminal_render_height;
+use crate::state::TermStack;
+use crate::terminal_manager::{TerminalId, TerminalManager};
+use terminal::export::ExportFormat;
+
+/// Header line above the absorbed output (dim, with the exit code if it failed)
+fn header(title: &str, exit_code: Option<i32>) -> String {
+    match exit_code {
+        Some(code) if code != 0 => format!("\x1b[2m-- {title} (exit {code}) --\x1b[0m\n"),
+        _ => format!("\x1b[2m-- {title} --\x1b[0m\n"),
+    }
+}
+
+/// Absorb the focused cell into its parent shell, if asked to (once per frame)
+pub fn process(compositor: &mut TermStack, terminal_manager: &mut TerminalManager) {
+    if !std::mem::take(&mut compositor.absorb_requested) {
+        return;
+    }
+    let Some(child) = compositor.focused_index().and_then(|index| compositor.layout_nodes[index].cell.terminal_id())
+    else {
+        return;
+    };
+    let Some(parent) = write_into_parent(terminal_manager, child) else {
+        tracing::debug!(id = child.0, "focused cell isn't a finished command with a shell to absorb it");
+        return;
+    };
+
+    if let Some(index) = crate::terminal_output::find_terminal_window_index(compositor, child) {
+        compositor.close_terminal_cell(index, terminal_manager);
+        compositor.recalculate_layout();
+    }
+    tracing::info!(id = child.0, parent = parent.0, "absorbed command cell into its shell");
+
+    let Some(index) = fit_shell_cell(compositor, terminal_manager, parent) else {
+        return;
+    };
+    compositor.set_focus_by_index(index);
+    compositor.update_keyboard_focus_for_focused_window();
+    compositor.reveal_after_layout(index);
+    compositor.redraw_requested = true;
+}
+
+/// Write finished command terminal `id`'s output into its parent shell above
+/// the prompt, growing the shell to fit. Returns the shell, `None` if the
+/// command is still running or has no shell.
+pub fn write_into_parent(terminal_manager: &mut TerminalManager, id: TerminalId) -> Option<TerminalId> {
+    let (parent, text) = absorbable(terminal_manager, id)?;
+    let shell = terminal_manager.get_mut(parent)?;
+    shell.terminal.insert_above_cursor_line(&text);
+    shell.mark_dirty();
+    if !shell.manually_sized {
+        let rows = shell.content_rows().min(u16::MAX as u32) as u16;
+        terminal_manager.grow_terminal(parent, rows);
+    }
+    Some(parent)
+}
+
+/// Give shell `id`'s cell the height of its grown terminal, returning its index
+pub fn fit_shell_cell(compositor: &mut TermStack, terminal_manager: &TerminalManager, id: TerminalId) -> Option<usize> {
+    let index = crate::terminal_output::find_terminal_window_index(compositor, id)?;
+    if let Some(shell) = terminal_manager.get(id) {
+        let height = calculate_terminal_render_height(shell.height as i32, shell.show_title_bar, shell.is_visible());
+        compositor.request_window_height(index, height);
+    }
+    Some(index)
+}
+
+/// The parent shell of command terminal `id` and the text to give it, if the
+/// command has finished
+fn absorbable(terminal_manager: &TerminalManager, id: TerminalId) -> Option<(TerminalId, String)> {
+    let child = terminal_manager.get(id)?;
+    let parent = child.parent.filter(|parent| terminal_manager.get(*parent).is_some())?;
+    if !child.has_exited() {
+        return None;
+    }
+    let exit_code = child.exit_status().and_then(|status| status.code());
+    let mut text = header(&child.title, exit_code);
+    text.push_str(&child.terminal.export(ExportFormat::Ansi, ""));
+    Some((parent, text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_names_the_command_and_failures() {
+        assert_eq!(header("make", Some(0)), "\x1b[2m-- make --\x1b[0m\n");
+        assert_eq!(header("make", Some(2)), "\x1b[2m-- make (exit 2) --\x1b[0m\n");
+    }
+}
@@ -0,0 +1,392 @@
+//! Accessibility tree for screen readers
+//!
+//! The stack is exposed as a flat tree: one node per cell, in stack order,
+//! with its role, accessible name (the title bar text) and whether it is still
+//! running, plus the focused cell. Roles use AccessKit's names.
+//!
+//! On Linux the tree goes to screen readers such as Orca over AT-SPI (see
+//! `AtSpiBridge`), which announce focus changes themselves and can read the
+//! focused terminal's output.
+//!
+//! A client sends an `accessibility` IPC request and gets the tree back as one
+//! JSON line; with `watch` set a new line follows every change.
+//! `termstack a11y --watch` turns those into spoken-style announcements
+//! ("cargo test, terminal, 3 of 5"), one per line, ready to pipe into a speech
+//! synthesizer, and `termstack a11y read INDEX` reads a terminal cell's last
+//! lines of output.
+
+#[cfg(target_os = "linux")]
+use std::collections::HashMap;
+#[cfg(target_os = "linux")]
+use std::sync::{Arc, Mutex};
+
+#[cfg(target_os = "linux")]
+use accesskit::{ActionRequest, Node, NodeId, Tree, TreeUpdate};
+use serde::{Deserialize, Serialize};
+
+use crate::state::{StackWindow, TermStack};
+use crate::terminal_manager::TerminalManager;
+
+/// What a cell is, in AccessKit's terms
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Terminal,
+    Window,
+}
+
+impl Role {
+    fn spoken(self) -> &'static str {
+        match self {
+            Self::Terminal => "terminal",
+            Self::Window => "window",
+        }
+    }
+}
+
+/// One cell of the stack
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessNode {
+    /// `CellId::to_bits()`, stable while the cell exists
+    pub cell: u64,
+    pub role: Role,
+    /// Accessible name: the title bar text
+    pub name: String,
+    /// Whether the cell's command is still running
+    #[serde(default)]
+    pub running: bool,
+}
+
+/// The whole stack, top to bottom
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessTree {
+    pub cells: Vec<AccessNode>,
+    /// Cell with keyboard focus
+    #[serde(default)]
+    pub focused: Option<u64>,
+}
+
+impl AccessTree {
+    /// Build the tree for the current stack
+    pub fn build(compositor: &TermStack, terminal_manager: &TerminalManager) -> Self {
+        let cells = compositor
+            .layout_nodes
+            .iter()
+            .map(|node| match &node.cell {
+                StackWindow::Terminal(id) => {
+                    let terminal = terminal_manager.get(*id);
+                    AccessNode {
+                        cell: node.id.to_bits(),
+                        role: Role::Terminal,
+                        name: terminal.map(|t| t.display_title().to_string()).unwrap_or_default(),
+                        running: terminal.is_some_and(|t| !t.has_exited()),
+                    }
+                }
+                StackWindow::External(entry) => AccessNode {
+                    cell: node.id.to_bits(),
+                    role: Role::Window,
+                    name: entry.title_bar_text().into_owned(),
+                    running: true,
+                },
+            })
+            .collect();
+        let focused = compositor
+            .focused_index()
+            .and_then(|index| compositor.layout_nodes.get(index))
+            .map(|node| node.id.to_bits());
+        Self { cells, focused }
+    }
+
+    fn get(&self, cell: u64) -> Option<&AccessNode> {
+        self.cells.iter().find(|node| node.cell == cell)
+    }
+}
+
+/// Spoken-style lines describing the change from `old` to `new` (everything
+/// worth saying about `new` when there is no `old`)
+pub fn announcements(old: Option<&AccessTree>, new: &AccessTree) -> Vec<String> {
+    let mut lines = Vec::new();
+    if let Some(old) = old {
+        for node in &old.cells {
+            if new.get(node.cell).is_none() {
+                lines.push(format!("closed {}", node.name));
+            }
+        }
+        for node in &new.cells {
+            match old.get(node.cell) {
+                None => lines.push(format!("opened {}", node.name)),
+                Some(before) if before.running && !node.running => lines.push(format!("finished {}", node.name)),
+                Some(_) => {}
+            }
+        }
+    }
+
+    let focus_moved = old.map_or(true, |old| old.focused != new.focused);
+    if focus_moved {
+        let focused = new
+            .focused
+            .and_then(|cell| new.cells.iter().position(|node| node.cell == cell));
+        lines.push(match focused {
+            Some(index) => {
+                let node = &new.cells[index];
+                format!("{}, {}, {} of {}", node.name, node.role.spoken(), index + 1, new.cells.len())
+            }
+            None => "nothing focused".to_string(),
+        });
+    }
+    lines
+}
+
+/// Send the tree to AT-SPI and to waiting IPC clients
+pub fn publish(compositor: &mut TermStack, terminal_manager: &TerminalManager) {
+    let tree = AccessTree::build(compositor, terminal_manager);
+
+    #[cfg(target_os = "linux")]
+    {
+        let text = focused_text(compositor, terminal_manager);
+        compositor
+            .atspi
+            .get_or_insert_with(AtSpiBridge::new)
+            .update(&tree, text);
+    }
+
+    if !compositor.accessibility_feed.is_empty() {
+        compositor.accessibility_feed.publish(tree);
+    }
+}
+
+/// Lines of the focused terminal a screen reader can read
+#[cfg(target_os = "linux")]
+const READ_LINES: usize = 20;
+
+/// Last lines of the focused cell's output, if it is a terminal
+#[cfg(target_os = "linux")]
+fn focused_text(compositor: &TermStack, terminal_manager: &TerminalManager) -> Vec<String> {
+    let focused = compositor
+        .focused_index()
+        .and_then(|index| compositor.layout_nodes.get(index));
+    let Some(StackWindow::Terminal(id)) = focused.map(|node| &node.cell) else {
+        return Vec::new();
+    };
+    let Some(terminal) = terminal_manager.get(*id) else {
+        return Vec::new();
+    };
+    let mut lines = terminal.terminal.visible_content(READ_LINES);
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+/// The stack on the AT-SPI bus, where Orca and other screen readers find it
+///
+/// The output is a window whose children are the cells, and the focused cell
+/// has AT-SPI focus, so moving focus is announced. A focused terminal holds
+/// its last lines as text, one text run per line. The adapter talks D-Bus on
+/// its own thread and sends nothing until a screen reader asks for the tree.
+#[cfg(target_os = "linux")]
+pub struct AtSpiBridge {
+    adapter: accesskit_unix::Adapter,
+    /// Full tree for a screen reader that connects (see `InitialTree`)
+    latest: Arc<Mutex<Option<TreeUpdate>>>,
+    /// What `latest` was built from
+    built_from: Option<(AccessTree, Vec<String>)>,
+    numbers: NodeNumbers,
+}
+
+#[cfg(target_os = "linux")]
+impl AtSpiBridge {
+    pub fn new() -> Self {
+        let latest = Arc::new(Mutex::new(None));
+        let mut adapter =
+            accesskit_unix::Adapter::new(InitialTree(Arc::clone(&latest)), NoActions, NoActions);
+        // The compositor owns the screen, so its "window" always has focus
+        adapter.update_window_focus_state(true);
+        Self { adapter, latest, built_from: None, numbers: NodeNumbers::default() }
+    }
+
+    /// Send the stack to the screen reader if it changed
+    pub fn update(&mut self, tree: &AccessTree, text: Vec<String>) {
+        if self
+            .built_from
+            .as_ref()
+            .is_some_and(|(built_tree, built_text)| built_tree == tree && *built_text == text)
+        {
+            return;
+        }
+        self.numbers.assign(tree);
+        let update = tree_update(tree, &text, &self.numbers);
+        if let Ok(mut latest) = self.latest.lock() {
+            *latest = Some(update.clone());
+        }
+        self.adapter.update_if_active(|| update);
+        self.built_from = Some((tree.clone(), text));
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Default for AtSpiBridge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Hands the adapter's thread the latest tree when a screen reader connects
+#[cfg(target_os = "linux")]
+struct InitialTree(Arc<Mutex<Option<TreeUpdate>>>);
+
+#[cfg(target_os = "linux")]
+impl accesskit::ActivationHandler for InitialTree {
+    fn request_initial_tree(&mut self) -> Option<TreeUpdate> {
+        self.0.lock().ok()?.clone()
+    }
+}
+
+/// Screen reader actions aren't supported, cells are focused with the keyboard
+#[cfg(target_os = "linux")]
+struct NoActions;
+
+#[cfg(target_os = "linux")]
+impl accesskit::ActionHandler for NoActions {
+    fn do_action(&mut self, _request: ActionRequest) {}
+}
+
+#[cfg(target_os = "linux")]
+impl accesskit::DeactivationHandler for NoActions {
+    fn deactivate_accessibility(&mut self) {}
+}
+
+/// AccessKit node ids for cells
+///
+/// Each cell gets a number that is never reused, so a cell opened in a closed
+/// cell's slot gets new nodes instead of inheriting the old ones. A cell's
+/// node is its number shifted left 16 bits; its text runs fill the low bits.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Default)]
+struct NodeNumbers {
+    /// By `CellId::to_bits()`
+    numbers: HashMap<u64, u64>,
+    last: u64,
+}
+
+#[cfg(target_os = "linux")]
+impl NodeNumbers {
+    /// Number the cells of `tree`, forgetting closed ones
+    fn assign(&mut self, tree: &AccessTree) {
+        self.numbers.retain(|cell, _| tree.get(*cell).is_some());
+        for node in &tree.cells {
+            self.numbers.entry(node.cell).or_insert_with(|| {
+                self.last += 1;
+                self.last
+            });
+        }
+    }
+
+    fn cell(&self, cell: u64) -> NodeId {
+        NodeId(self.numbers[&cell] << 16)
+    }
+
+    fn line(&self, cell: u64, line: usize) -> NodeId {
+        NodeId(self.cell(cell).0 | (line as u64 + 1))
+    }
+}
+
+/// Full AccessKit tree for `tree`, with `text` under the focused cell
+#[cfg(target_os = "linux")]
+fn tree_update(tree: &AccessTree, text: &[String], numbers: &NodeNumbers) -> TreeUpdate {
+    let root = NodeId(0);
+    let mut nodes = Vec::with_capacity(tree.cells.len() + text.len() + 1);
+
+    for cell in &tree.cells {
+        let mut node = Node::new(match cell.role {
+            Role::Terminal => accesskit::Role::Terminal,
+            Role::Window => accesskit::Role::Window,
+        });
+        node.set_label(cell.name.as_str());
+        if !cell.running {
+            node.set_description("finished");
+        }
+        if tree.focused == Some(cell.cell) {
+            let lines = text.len();
+            for (index, line) in text.iter().enumerate() {
+                let mut run = Node::new(accesskit::Role::TextRun);
+                let value = if index + 1 < lines { format!("{line}\n") } else { line.clone() };
+                run.set_character_lengths(value.chars().map(|c| c.len_utf8() as u8).collect::<Vec<_>>());
+                run.set_value(value);
+                nodes.push((numbers.line(cell.cell, index), run));
+            }
+            node.set_children((0..lines).map(|index| numbers.line(cell.cell, index)).collect::<Vec<_>>());
+        }
+        nodes.push((numbers.cell(cell.cell), node));
+    }
+
+    let mut window = Node::new(accesskit::Role::Window);
+    window.set_label("termstack");
+    window.set_children(tree.cells.iter().map(|cell| numbers.cell(cell.cell)).collect::<Vec<_>>());
+    nodes.push((root, window));
+
+    let mut info = Tree::new(root);
+    info.app_name = Some("termstack".to_string());
+    info.toolkit_name = Some("termstack".to_string());
+    info.toolkit_version = Some(env!("CARGO_PKG_VERSION").to_string());
+
+    TreeUpdate {
+        nodes,
+        tree: Some(info),
+        focus: tree.focused.map_or(root, |cell| numbers.cell(cell)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(cell: u64, name: &str, running: bool) -> AccessNode {
+        AccessNode { cell, role: Role::Terminal, name: name.to_string(), running }
+    }
+
+    #[test]
+    fn first_tree_announces_focus() {
+        let tree = AccessTree { cells: vec![node(1, "fish", true), node(2, "> make", true)], focused: Some(2) };
+        assert_eq!(announcements(None, &tree), ["> make, terminal, 2 of 2"]);
+        assert_eq!(announcements(None, &AccessTree::default()), ["nothing focused"]);
+    }
+
+    #[test]
+    fn changes_are_announced() {
+        let old = AccessTree { cells: vec![node(1, "fish", true), node(2, "> make", true)], focused: Some(1) };
+        let new = AccessTree { cells: vec![node(2, "> make", false), node(3, "> ls", true)], focused: Some(3) };
+        assert_eq!(announcements(Some(&old), &new), [
+            "closed fish",
+            "finished > make",
+            "opened > ls",
+            "> ls, terminal, 2 of 2",
+        ]);
+        // Nothing changed, nothing said
+        assert!(announcements(Some(&new), &new).is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn atspi_tree_holds_focused_output() {
+        let mut numbers = NodeNumbers::default();
+        let old = AccessTree { cells: vec![node(1, "fish", true), node(2, "> make", false)], focused: Some(2) };
+        numbers.assign(&old);
+        let update = tree_update(&old, &["cc main.c".to_string(), "done".to_string()], &numbers);
+        assert_eq!(update.focus, numbers.cell(2));
+        let runs: Vec<_> = update
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.role() == accesskit::Role::TextRun)
+            .map(|(_, node)| node.value().unwrap())
+            .collect();
+        assert_eq!(runs, ["cc main.c\n", "done"]);
+
+        // A cell opened after one closed doesn't take over its nodes
+        let closed = numbers.cell(2);
+        let new = AccessTree { cells: vec![node(1, "fish", true), node(3, "> ls", true)], focused: Some(1) };
+        numbers.assign(&new);
+        assert_ne!(numbers.cell(3), closed);
+        assert_eq!(tree_update(&new, &[], &numbers).focus, numbers.cell(1));
+    }
+}
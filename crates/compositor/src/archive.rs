@@ -0,0 +1,255 @@
+//! Archive of closed command cells
+//!
+//! When a command cell closes (close button, absorb, `termstack all
+//! close-exited` or an ephemeral cell timing out) its final scrollback is
+//! kept here, up to `archive_size` cells for `archive_minutes`. Unlike undo,
+//! the process and terminal are gone; `termstack archive` lists what is left,
+//! `show` prints a cell's output and `restore` puts it back as a read-only
+//! cell above the launcher, like a builtin's entry.
+
+use std::collections::VecDeque;
+use std::os::unix::net::UnixStream;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ipc::BuiltinRequest;
+use crate::state::TermStack;
+use crate::terminal_manager::{ManagedTerminal, TerminalId};
+use terminal::export::ExportFormat;
+
+/// How many closed cells are kept
+pub const DEFAULT_ARCHIVE_SIZE: usize = 50;
+
+/// How long a closed cell is kept
+pub const DEFAULT_ARCHIVE_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// What to do with the archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveAction {
+    /// List the archived cells, newest first (answered with `Vec<ArchiveSummary>`)
+    List,
+    /// A cell's output as ANSI text (answered with `String`)
+    Show,
+    /// Bring a cell back read-only (answered with its title)
+    Restore,
+}
+
+/// An archived cell as listed over IPC
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveSummary {
+    pub id: u64,
+    pub title: String,
+    pub exit_code: Option<i32>,
+    pub lines: usize,
+    /// Seconds since the cell closed
+    pub age_secs: u64,
+}
+
+/// A closed command cell's final scrollback
+#[derive(Debug, Clone)]
+struct ArchivedCell {
+    id: u64,
+    /// The terminal it came from, so undo can drop the copy
+    terminal: TerminalId,
+    title: String,
+    exit_code: Option<i32>,
+    /// Output as ANSI text, `\n` line endings
+    text: String,
+    closed_at: Instant,
+}
+
+/// Archived cells, oldest first. Kept on `TermStack`.
+#[derive(Debug)]
+pub struct Archive {
+    cells: VecDeque<ArchivedCell>,
+    size: usize,
+    max_age: Duration,
+    next_id: u64,
+    /// Requests from IPC: the action, the cell id and the stream to answer on
+    pub pending: Vec<(ArchiveAction, Option<u64>, UnixStream)>,
+}
+
+impl Default for Archive {
+    fn default() -> Self {
+        Self::new(DEFAULT_ARCHIVE_SIZE, DEFAULT_ARCHIVE_AGE)
+    }
+}
+
+impl Archive {
+    pub fn new(size: usize, max_age: Duration) -> Self {
+        Self { cells: VecDeque::new(), size, max_age, next_id: 1, pending: Vec::new() }
+    }
+
+    /// Settings from the config (`archive_size = 0` turns the archive off)
+    pub fn from_config(config: &crate::config::Config) -> Self {
+        Self::new(config.archive_size, Duration::from_secs(config.archive_minutes * 60))
+    }
+
+    /// Keep the output of a closing terminal, if it ran a command
+    pub fn add(&mut self, terminal: &ManagedTerminal, now: Instant) {
+        if terminal.parent.is_none() {
+            return;
+        }
+        let exit_code = terminal.exit_status().and_then(|status| status.code());
+        let text = terminal.terminal.export(ExportFormat::Ansi, "");
+        self.insert(terminal.id, &terminal.title, exit_code, text, now);
+    }
+
+    fn insert(&mut self, terminal: TerminalId, title: &str, exit_code: Option<i32>, text: String, now: Instant) {
+        if self.size == 0 {
+            return;
+        }
+        self.cells.push_back(ArchivedCell {
+            id: self.next_id,
+            terminal,
+            title: title.to_string(),
+            exit_code,
+            text,
+            closed_at: now,
+        });
+        self.next_id += 1;
+        while self.cells.len() > self.size {
+            self.cells.pop_front();
+        }
+    }
+
+    /// Drop the copy of a terminal that came back with undo
+    pub fn forget(&mut self, terminal: TerminalId) {
+        self.cells.retain(|cell| cell.terminal != terminal);
+    }
+
+    /// Drop cells older than the maximum age
+    pub fn expire(&mut self, now: Instant) {
+        // Oldest first, so the stale ones are at the front
+        let stale = self
+            .cells
+            .iter()
+            .take_while(|cell| now.saturating_duration_since(cell.closed_at) > self.max_age)
+            .count();
+        self.cells.drain(..stale);
+    }
+
+    /// The archived cells, newest first
+    pub fn list(&self, now: Instant) -> Vec<ArchiveSummary> {
+        self.cells
+            .iter()
+            .rev()
+            .map(|cell| ArchiveSummary {
+                id: cell.id,
+                title: cell.title.clone(),
+                exit_code: cell.exit_code,
+                lines: cell.text.lines().count(),
+                age_secs: now.saturating_duration_since(cell.closed_at).as_secs(),
+            })
+            .collect()
+    }
+
+    fn get(&self, id: u64) -> Option<&ArchivedCell> {
+        self.cells.iter().find(|cell| cell.id == id)
+    }
+
+    fn take(&mut self, id: u64) -> Option<ArchivedCell> {
+        let index = self.cells.iter().position(|cell| cell.id == id)?;
+        self.cells.remove(index)
+    }
+}
+
+impl ArchivedCell {
+    /// Entry for a read-only cell showing the archived output
+    fn builtin_request(&self) -> BuiltinRequest {
+        let mut result = String::new();
+        for line in self.text.lines() {
+            result.push_str(line);
+            result.push_str("\x1b[0m\r\n");
+        }
+        BuiltinRequest {
+            prompt: String::new(),
+            command: self.title.clone(),
+            result,
+            success: true,
+        }
+    }
+}
+
+/// Drop expired cells and answer archive requests from IPC (once per frame,
+/// before builtin entries are inserted)
+pub fn process(compositor: &mut TermStack) {
+    let now = Instant::now();
+    compositor.archive.expire(now);
+
+    for (action, id, stream) in std::mem::take(&mut compositor.archive.pending) {
+        let missing = || match id {
+            Some(id) => format!("no archived cell {id}"),
+            None => "no archived cell id given".to_string(),
+        };
+        let sent = match action {
+            ArchiveAction::List => {
+                crate::ipc::send_json_response(stream, &Ok::<_, String>(compositor.archive.list(now)))
+            }
+            ArchiveAction::Show => {
+                let result = id.and_then(|id| compositor.archive.get(id)).map(|cell| cell.text.clone()).ok_or_else(missing);
+                crate::ipc::send_json_response(stream, &result)
+            }
+            ArchiveAction::Restore => {
+                let result = id.and_then(|id| compositor.archive.take(id)).ok_or_else(missing).map(|cell| {
+                    tracing::info!(id = cell.id, title = %cell.title, "restoring archived cell");
+                    compositor.pending_builtin_requests.push(cell.builtin_request());
+                    compositor.redraw_requested = true;
+                    cell.title
+                });
+                crate::ipc::send_json_response(stream, &result)
+            }
+        };
+        if let Err(e) = sent {
+            tracing::warn!(error = ?e, ?action, "Failed to send archive response");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive_of(size: usize) -> (Archive, Instant) {
+        let now = Instant::now();
+        let mut archive = Archive::new(size, Duration::from_secs(60));
+        archive.insert(TerminalId(1), "make", Some(2), "error: oops\n".to_string(), now);
+        archive.insert(TerminalId(2), "ls", Some(0), "a\nb\n".to_string(), now + Duration::from_secs(30));
+        (archive, now)
+    }
+
+    #[test]
+    fn lists_newest_first_and_drops_the_oldest_when_full() {
+        let (archive, now) = archive_of(5);
+        let list = archive.list(now + Duration::from_secs(40));
+        assert_eq!(list.iter().map(|cell| cell.title.as_str()).collect::<Vec<_>>(), ["ls", "make"]);
+        assert_eq!((list[0].lines, list[0].age_secs), (2, 10));
+        assert_eq!(list[1].exit_code, Some(2));
+
+        let (archive, now) = archive_of(1);
+        assert_eq!(archive.list(now).len(), 1);
+        assert!(Archive::new(0, DEFAULT_ARCHIVE_AGE).list(now).is_empty());
+    }
+
+    #[test]
+    fn old_cells_expire_and_undone_ones_are_forgotten() {
+        let (mut archive, now) = archive_of(5);
+        archive.expire(now + Duration::from_secs(61));
+        assert_eq!(archive.list(now).len(), 1);
+        archive.forget(TerminalId(2));
+        assert!(archive.list(now).is_empty());
+    }
+
+    #[test]
+    fn restored_cells_leave_the_archive() {
+        let (mut archive, _) = archive_of(5);
+        let cell = archive.take(2).unwrap();
+        let request = cell.builtin_request();
+        assert_eq!(request.command, "ls");
+        assert_eq!(request.result, "a\x1b[0m\r\nb\x1b[0m\r\n");
+        assert!(archive.get(2).is_none());
+        assert!(archive.get(1).is_some());
+    }
+}
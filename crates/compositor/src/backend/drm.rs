@@ -0,0 +1,280 @@
+//! DRM/KMS backend: termstack as the session's compositor
+//!
+//! [`DrmHost`] runs termstack straight from a virtual terminal, without a
+//! display server. A libseat session (logind or seatd) hands out the GPU and
+//! the input devices without root, the seat's primary GPU drives the first
+//! connected monitor in its preferred mode, and libinput reads keyboards,
+//! mice and touchpads.
+//!
+//! Ctrl+Alt+F<n> switches virtual terminals. While another VT is shown the
+//! session is paused: the devices are released and nothing is drawn until
+//! termstack's VT is active again, when the monitor is redrawn in full.
+//!
+//! There is no host to show the pointer, so the compositor draws it. The
+//! monitor connected at startup stays the output for the whole session;
+//! hotplug isn't followed.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use smithay::backend::allocator::dmabuf::Dmabuf;
+use smithay::backend::allocator::gbm::{GbmAllocator, GbmBufferFlags, GbmDevice};
+use smithay::backend::allocator::Fourcc;
+use smithay::backend::drm::{DrmDevice, DrmDeviceFd, DrmEvent, GbmBufferedSurface};
+use smithay::backend::egl::{EGLContext, EGLDisplay};
+use smithay::backend::input::InputEvent;
+use smithay::backend::libinput::{LibinputInputBackend, LibinputSessionInterface};
+use smithay::backend::renderer::gles::{GlesRenderer, GlesTarget};
+use smithay::backend::renderer::Bind;
+use smithay::backend::session::libseat::LibSeatSession;
+use smithay::backend::session::{Event as SessionEvent, Session};
+use smithay::backend::udev;
+use smithay::reexports::calloop::LoopHandle;
+use smithay::reexports::drm::control::{connector, Mode as DrmMode, ModeFlags, ModeTypeFlags};
+use smithay::reexports::input::Libinput;
+use smithay::reexports::rustix::fs::OFlags;
+use smithay::reexports::wayland_protocols::wp::presentation_time::server::wp_presentation_feedback;
+use smithay::utils::{DeviceFd, Physical, Size};
+use smithay_drm_extras::drm_scanner::{DrmScanEvent, DrmScanner};
+
+use super::HostWindow;
+use crate::state::TermStack;
+
+/// Buffer formats tried for scanout, in order
+const COLOR_FORMATS: [Fourcc; 2] = [Fourcc::Argb8888, Fourcc::Xrgb8888];
+
+/// Swapchain of scanout buffers on the monitor's CRTC
+type ScanoutSurface = GbmBufferedSurface<GbmAllocator<DrmDeviceFd>, ()>;
+
+/// The monitor termstack drives directly, with the seat's input devices
+pub struct DrmHost {
+    session: LibSeatSession,
+    /// Shared with the vblank and session sources, which finish flips and
+    /// reset the swapchain
+    surface: Rc<RefCell<ScanoutSurface>>,
+    renderer: GlesRenderer,
+    /// Buffer being drawn, between `bind` and `submit`
+    buffer: Option<Dmabuf>,
+    size: Size<i32, Physical>,
+    refresh: Option<i32>,
+    input: Rc<RefCell<Vec<InputEvent<LibinputInputBackend>>>>,
+}
+
+impl DrmHost {
+    /// Take the seat, set up the first connected monitor and add the DRM,
+    /// input and session sources to the loop (`_title` has nowhere to go)
+    pub fn new(handle: &LoopHandle<'_, TermStack>, _title: &str) -> anyhow::Result<Self> {
+        let (mut session, session_notifier) = LibSeatSession::new()
+            .map_err(|e| anyhow::anyhow!("Failed to open a seat session (is logind or seatd running?): {e:?}"))?;
+        let seat = session.seat();
+
+        // The seat's primary GPU (the one the firmware booted with), else its first
+        let gpu = udev::primary_gpu(&seat)
+            .ok()
+            .flatten()
+            .or_else(|| udev::all_gpus(&seat).ok()?.into_iter().next())
+            .ok_or_else(|| anyhow::anyhow!("No GPU found on seat {seat}"))?;
+        tracing::info!(gpu = %gpu.display(), seat, "using GPU");
+
+        let fd = session
+            .open(&gpu, OFlags::RDWR | OFlags::CLOEXEC | OFlags::NOCTTY | OFlags::NONBLOCK)
+            .map_err(|e| anyhow::anyhow!("Failed to open {}: {e:?}", gpu.display()))?;
+        let fd = DrmDeviceFd::new(DeviceFd::from(fd));
+        let (mut drm, drm_notifier) = DrmDevice::new(fd.clone(), true)
+            .map_err(|e| anyhow::anyhow!("Failed to open DRM device: {e:?}"))?;
+        let gbm_device = GbmDevice::new(fd)
+            .map_err(|e| anyhow::anyhow!("Failed to create GBM device: {e:?}"))?;
+
+        // Create EGL display and context for OpenGL rendering
+        let egl_display = unsafe { EGLDisplay::new(gbm_device.clone()) }
+            .map_err(|e| anyhow::anyhow!("Failed to create EGL display: {e:?}"))?;
+        let egl_context = EGLContext::new(&egl_display)
+            .map_err(|e| anyhow::anyhow!("Failed to create EGL context: {e:?}"))?;
+        let render_formats = egl_context.dmabuf_render_formats().clone();
+        let renderer = unsafe { GlesRenderer::new(egl_context) }
+            .map_err(|e| anyhow::anyhow!("Failed to create GLES renderer: {e:?}"))?;
+
+        // The first connected monitor with a CRTC to drive it, in its preferred mode
+        let mut scanner: DrmScanner = DrmScanner::new();
+        let (connector, crtc) = scanner
+            .scan_connectors(&drm)
+            .map_err(|e| anyhow::anyhow!("Failed to scan connectors: {e}"))?
+            .into_iter()
+            .find_map(|event| match event {
+                DrmScanEvent::Connected { connector, crtc: Some(crtc) } => Some((connector, crtc)),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow::anyhow!("No connected monitor on {}", gpu.display()))?;
+        let mode = preferred_mode(&connector)
+            .ok_or_else(|| anyhow::anyhow!("Monitor on {:?} has no modes", connector.interface()))?;
+        let (width, height) = mode.size();
+        let refresh = mode_refresh(&mode);
+        tracing::info!(connector = ?connector.interface(), width, height, ?refresh, "using monitor");
+
+        let drm_surface = drm
+            .create_surface(crtc, mode, &[connector.handle()])
+            .map_err(|e| anyhow::anyhow!("Failed to create DRM surface: {e:?}"))?;
+        let allocator = GbmAllocator::new(gbm_device, GbmBufferFlags::RENDERING | GbmBufferFlags::SCANOUT);
+        let surface = GbmBufferedSurface::new(drm_surface, allocator, &COLOR_FORMATS, render_formats)
+            .map_err(|e| anyhow::anyhow!("Failed to create scanout swapchain: {e:?}"))?;
+        let surface = Rc::new(RefCell::new(surface));
+
+        // A flip finished: the next buffer can be queued and the frame is on screen
+        let vblank_surface = surface.clone();
+        handle.insert_source(drm_notifier, move |event, _, state| match event {
+            DrmEvent::VBlank(_) => {
+                if let Err(e) = vblank_surface.borrow_mut().frame_submitted() {
+                    tracing::warn!(error = ?e, "failed to finish page flip");
+                }
+                state.presentation.presented(state.refresh, wp_presentation_feedback::Kind::Vsync);
+            }
+            DrmEvent::Error(e) => {
+                tracing::warn!(error = ?e, "DRM device error");
+            }
+        }).map_err(|e| anyhow::anyhow!("Failed to insert DRM source: {e:?}"))?;
+
+        // Input devices of the seat, opened through the session. Events are
+        // queued for the main loop, like the X11 backend's
+        let mut libinput = Libinput::new_with_udev(LibinputSessionInterface::from(session.clone()));
+        libinput
+            .udev_assign_seat(&seat)
+            .map_err(|()| anyhow::anyhow!("Failed to assign seat {seat} to libinput"))?;
+        let input: Rc<RefCell<Vec<InputEvent<LibinputInputBackend>>>> = Rc::default();
+        let input_source = input.clone();
+        handle.insert_source(LibinputInputBackend::new(libinput.clone()), move |event, _, state| {
+            state.redraw_requested = true;
+            if let InputEvent::DeviceAdded { mut device } = event {
+                // Touchpads click on tap, as on most desktops
+                if device.config_tap_finger_count() > 0 {
+                    let _ = device.config_tap_set_enabled(true);
+                }
+                tracing::info!(name = device.name(), "input device added");
+                return;
+            }
+            input_source.borrow_mut().push(event);
+        }).map_err(|e| anyhow::anyhow!("Failed to insert libinput source: {e:?}"))?;
+
+        // VT switches pause the session: let go of the devices until it's back
+        let session_surface = surface.clone();
+        handle.insert_source(session_notifier, move |event, _, state| match event {
+            SessionEvent::PauseSession => {
+                tracing::info!("session paused");
+                libinput.suspend();
+                drm.pause();
+            }
+            SessionEvent::ActivateSession => {
+                tracing::info!("session activated");
+                if libinput.resume().is_err() {
+                    tracing::warn!("failed to resume libinput");
+                }
+                if let Err(e) = drm.activate(false) {
+                    tracing::warn!(error = ?e, "failed to activate DRM device");
+                }
+                // A flip pending when the session paused never completes, and
+                // another VT drew over the buffers
+                let mut surface = session_surface.borrow_mut();
+                if let Err(e) = surface.frame_submitted() {
+                    tracing::warn!(error = ?e, "failed to drop pending page flip");
+                }
+                surface.reset_buffers();
+                state.redraw_requested = true;
+            }
+        }).map_err(|e| anyhow::anyhow!("Failed to insert session source: {e:?}"))?;
+
+        Ok(Self {
+            session,
+            surface,
+            renderer,
+            buffer: None,
+            size: (i32::from(width), i32::from(height)).into(),
+            refresh,
+            input,
+        })
+    }
+}
+
+impl HostWindow for DrmHost {
+    type Input = LibinputInputBackend;
+
+    const OUTPUT_NAME: &'static str = "drm";
+
+    fn size(&self) -> Size<i32, Physical> {
+        self.size
+    }
+
+    fn renderer(&mut self) -> &mut GlesRenderer {
+        &mut self.renderer
+    }
+
+    fn take_input(&mut self) -> Vec<InputEvent<LibinputInputBackend>> {
+        std::mem::take(&mut *self.input.borrow_mut())
+    }
+
+    fn refresh(&self) -> Option<i32> {
+        self.refresh
+    }
+
+    fn reports_presents(&self) -> bool {
+        true
+    }
+
+    fn shows_cursor(&self) -> bool {
+        false
+    }
+
+    fn set_resize_cursor(&mut self, _resize: bool) {
+        // The drawn pointer is always the arrow
+    }
+
+    fn is_active(&self) -> bool {
+        self.session.is_active()
+    }
+
+    fn owns_vt(&self) -> bool {
+        true
+    }
+
+    fn switch_vt(&mut self, vt: i32) {
+        tracing::info!(vt, "switching virtual terminal");
+        if let Err(e) = self.session.change_vt(vt) {
+            tracing::warn!(error = ?e, vt, "failed to switch virtual terminal");
+        }
+    }
+
+    fn bind(&mut self) -> anyhow::Result<(&mut GlesRenderer, GlesTarget<'_>, u8)> {
+        let (buffer, age) = self.surface.borrow_mut().next_buffer()
+            .map_err(|e| anyhow::anyhow!("Failed to get scanout buffer: {e:?}"))?;
+        let buffer = self.buffer.insert(buffer);
+        let target = self.renderer.bind(buffer)
+            .map_err(|e| anyhow::anyhow!("Failed to bind buffer: {e:?}"))?;
+        Ok((&mut self.renderer, target, age))
+    }
+
+    fn submit(&mut self) -> anyhow::Result<()> {
+        self.buffer = None;
+        self.surface.borrow_mut().queue_buffer(None, None, ())
+            .map_err(|e| anyhow::anyhow!("Failed to queue scanout buffer: {e:?}"))
+    }
+}
+
+/// The connector's preferred mode, or its first one
+fn preferred_mode(connector: &connector::Info) -> Option<DrmMode> {
+    let modes = connector.modes();
+    modes
+        .iter()
+        .find(|mode| mode.mode_type().contains(ModeTypeFlags::PREFERRED))
+        .or_else(|| modes.first())
+        .copied()
+}
+
+/// Refresh rate of a DRM mode in mHz (its clock is in kHz)
+fn mode_refresh(mode: &DrmMode) -> Option<i32> {
+    let flags = mode.flags();
+    crate::refresh::mode_refresh(
+        mode.clock() * 1000,
+        mode.hsync().2,
+        mode.vsync().2,
+        flags.contains(ModeFlags::DBLSCAN),
+        flags.contains(ModeFlags::INTERLACE),
+    )
+}
@@ -8,6 +8,13 @@
 //! - **X11Backend** (feature: `x11-backend`): GPU-accelerated rendering using OpenGL/GLES
 //!   via DRM/GBM/EGL. Used for normal compositor operation.
 //!
+//! - **WaylandHost** (feature: `x11-backend`): the same GLES rendering into a
+//!   window of a host Wayland compositor (GNOME, Sway), without XWayland.
+//!
+//! - **DrmHost** (feature: `x11-backend`): the same GLES rendering straight to
+//!   a monitor through DRM/KMS, started from a virtual terminal, with
+//!   libinput for input and a libseat session for device access.
+//!
 //! - **HeadlessBackend** (feature: `headless-backend`): CPU-based software rendering
 //!   for headless E2E testing. No display required.
 //!
@@ -22,14 +29,22 @@
 //! - [`CompositorBackend`]: Main trait for backend lifecycle and access
 //! - [`BackendEvent`]: Unified event type from any backend
 //! - [`BackendConfig`]: Configuration for backend initialization
+//! - [`HostWindow`]: Window on a host display that the GPU backends draw into
 
 #[cfg(feature = "x11-backend")]
 pub mod x11;
 
+#[cfg(feature = "x11-backend")]
+pub mod wayland;
+
+#[cfg(feature = "x11-backend")]
+pub mod drm;
+
 #[cfg(feature = "headless-backend")]
 pub mod headless;
 
 use smithay::backend::input::{InputBackend, InputEvent};
+use smithay::backend::renderer::gles::{GlesRenderer, GlesTarget};
 use smithay::backend::renderer::{ImportMem, Renderer, Texture};
 use smithay::utils::{Physical, Size};
 
@@ -173,11 +188,80 @@ pub trait CompositorSurface {
     fn submit(&mut self) -> anyhow::Result<()>;
 }
 
+/// Window on a host display that the compositor draws its output into
+///
+/// Implemented by the GPU backends ([`x11::X11Host`], [`wayland::WaylandHost`],
+/// [`drm::DrmHost`]); the main loop is generic over it. The host's event
+/// sources are already in the event loop: they queue input, set
+/// `compositor_window_resize_pending` on resize and clear `running` on close.
+pub trait HostWindow {
+    /// Input backend of the events the window delivers
+    type Input: InputBackend;
+
+    /// Name of the output the window stands for
+    const OUTPUT_NAME: &'static str;
+
+    /// Current window size in physical pixels
+    fn size(&self) -> Size<i32, Physical>;
+
+    /// The renderer, for setting up shaders and textures outside a frame
+    fn renderer(&mut self) -> &mut GlesRenderer;
+
+    /// Input events received since the last call
+    fn take_input(&mut self) -> Vec<InputEvent<Self::Input>>;
+
+    /// Refresh rate (mHz) of the monitor the window is on, if known
+    fn refresh(&self) -> Option<i32>;
+
+    /// Whether the host tells when a frame is on screen (`presentation.presented`)
+    fn reports_presents(&self) -> bool;
+
+    /// Whether the host shows the pointer; if not, the compositor draws it
+    fn shows_cursor(&self) -> bool {
+        true
+    }
+
+    /// Show the resize cursor or the default one
+    fn set_resize_cursor(&mut self, resize: bool);
+
+    /// Whether the host can be drawn to now (false while another virtual
+    /// terminal is shown)
+    fn is_active(&self) -> bool {
+        true
+    }
+
+    /// Whether the host runs on a virtual terminal that `switch_vt` can
+    /// leave
+    fn owns_vt(&self) -> bool {
+        false
+    }
+
+    /// Switch to virtual terminal `vt`, if the host owns one
+    fn switch_vt(&mut self, _vt: i32) {}
+
+    /// Bind the next buffer for drawing, with the number of frames since
+    /// it was last drawn (0: contents unknown)
+    fn bind(&mut self) -> anyhow::Result<(&mut GlesRenderer, GlesTarget<'_>, u8)>;
+
+    /// Present the buffer drawn since the last `bind`
+    fn submit(&mut self) -> anyhow::Result<()>;
+}
+
 /// Backend selection based on environment variable
 pub fn select_backend() -> BackendType {
     match std::env::var("TERMSTACK_BACKEND").as_deref() {
         Ok("headless") => BackendType::Headless,
         Ok("x11") => BackendType::X11,
+        Ok("wayland") => BackendType::Wayland,
+        Ok("drm") => BackendType::Drm,
+        // Without a display server to open a window on, drive the monitor
+        _ if std::env::var_os("DISPLAY").is_none() && std::env::var_os("WAYLAND_DISPLAY").is_none() => {
+            tracing::warn!(
+                "neither DISPLAY nor WAYLAND_DISPLAY is set: taking over the monitor with the DRM backend \
+                 (set TERMSTACK_BACKEND to choose another)"
+            );
+            BackendType::Drm
+        }
         // Default to X11 for normal operation
         _ => BackendType::X11,
     }
@@ -188,6 +272,10 @@ pub fn select_backend() -> BackendType {
 pub enum BackendType {
     /// X11 backend with GPU acceleration
     X11,
+    /// GPU-accelerated window nested in a host Wayland compositor
+    Wayland,
+    /// GPU-accelerated rendering straight to a monitor, from a virtual terminal
+    Drm,
     /// Headless backend with software rendering
     Headless,
 }
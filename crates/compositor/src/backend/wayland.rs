@@ -0,0 +1,127 @@
+//! Nested Wayland backend
+//!
+//! [`WaylandHost`] opens the compositor's window as a client of the host
+//! Wayland compositor (GNOME, Sway) through Smithay's winit backend, so
+//! termstack runs nested without going through XWayland. Rendering is the
+//! same GLES pipeline as the X11 backend, into the window's EGL surface.
+//!
+//! The host doesn't report when a frame reaches the screen, so a frame counts
+//! as presented once it is submitted and frames are paced by the refresh
+//! rate alone.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use smithay::backend::input::InputEvent;
+use smithay::backend::renderer::gles::{GlesRenderer, GlesTarget};
+use smithay::backend::winit::{self, WinitEvent, WinitGraphicsBackend, WinitInput};
+use smithay::reexports::calloop::LoopHandle;
+use smithay::reexports::winit::dpi::LogicalSize;
+use smithay::reexports::winit::window::{CursorIcon, Window as WinitWindow};
+use smithay::utils::{Physical, Size};
+
+use super::HostWindow;
+use crate::state::TermStack;
+
+/// The compositor's window on the host Wayland compositor
+pub struct WaylandHost {
+    backend: WinitGraphicsBackend<GlesRenderer>,
+    input: Rc<RefCell<Vec<InputEvent<WinitInput>>>>,
+    resize_cursor: bool,
+}
+
+impl WaylandHost {
+    /// Open a window titled `title` and add its event source to the loop
+    ///
+    /// Fails when `WAYLAND_DISPLAY` is unset: winit would fall back to X11,
+    /// which the X11 backend does better.
+    pub fn new(handle: &LoopHandle<'_, TermStack>, title: &str) -> anyhow::Result<Self> {
+        if std::env::var_os("WAYLAND_DISPLAY").is_none() {
+            anyhow::bail!("Wayland backend requested but WAYLAND_DISPLAY is not set");
+        }
+
+        let attributes = WinitWindow::default_attributes()
+            .with_title(title)
+            .with_inner_size(LogicalSize::new(1280.0, 800.0));
+        let (backend, winit_loop) = winit::init_from_attributes::<GlesRenderer>(attributes)
+            .map_err(|e| anyhow::anyhow!("Wayland backend init error: {e:?}"))?;
+
+        // Input is queued for the main loop, like the X11 backend's
+        let input: Rc<RefCell<Vec<InputEvent<WinitInput>>>> = Rc::default();
+        let input_source = input.clone();
+
+        handle.insert_source(winit_loop, move |event, _, state| {
+            state.redraw_requested = true;
+            match event {
+                WinitEvent::Input(input_event) => {
+                    input_source.borrow_mut().push(input_event);
+                }
+                WinitEvent::Resized { size, .. } => {
+                    state.compositor_window_resize_pending = Some((
+                        size.w.clamp(1, u16::MAX as i32) as u16,
+                        size.h.clamp(1, u16::MAX as i32) as u16,
+                    ));
+                }
+                WinitEvent::CloseRequested => {
+                    state.running = false;
+                }
+                WinitEvent::Focus(focused) => {
+                    tracing::info!("Wayland window focus changed: {}", focused);
+                }
+                WinitEvent::Redraw => {
+                    // Window needs redraw - will happen in main loop anyway
+                }
+            }
+        }).map_err(|e| anyhow::anyhow!("Failed to insert Wayland backend source: {e:?}"))?;
+
+        Ok(Self { backend, input, resize_cursor: false })
+    }
+}
+
+impl HostWindow for WaylandHost {
+    type Input = WinitInput;
+
+    const OUTPUT_NAME: &'static str = "wayland";
+
+    fn size(&self) -> Size<i32, Physical> {
+        self.backend.window_size()
+    }
+
+    fn renderer(&mut self) -> &mut GlesRenderer {
+        self.backend.renderer()
+    }
+
+    fn take_input(&mut self) -> Vec<InputEvent<WinitInput>> {
+        std::mem::take(&mut *self.input.borrow_mut())
+    }
+
+    fn refresh(&self) -> Option<i32> {
+        self.backend.window().current_monitor()?.refresh_rate_millihertz().map(|mhz| mhz as i32)
+    }
+
+    fn reports_presents(&self) -> bool {
+        false
+    }
+
+    fn set_resize_cursor(&mut self, resize: bool) {
+        if self.resize_cursor == resize {
+            return;
+        }
+        self.resize_cursor = resize;
+        let icon = if resize { CursorIcon::NsResize } else { CursorIcon::Default };
+        self.backend.window().set_cursor(icon);
+    }
+
+    fn bind(&mut self) -> anyhow::Result<(&mut GlesRenderer, GlesTarget<'_>, u8)> {
+        // The age of the buffer the next bind makes current
+        let age = self.backend.buffer_age().unwrap_or(0).min(u8::MAX as usize) as u8;
+        let (renderer, target) = self.backend.bind()
+            .map_err(|e| anyhow::anyhow!("Failed to bind window surface: {e:?}"))?;
+        Ok((renderer, target, age))
+    }
+
+    fn submit(&mut self) -> anyhow::Result<()> {
+        self.backend.submit(None)
+            .map_err(|e| anyhow::anyhow!("Failed to submit window surface: {e:?}"))
+    }
+}
@@ -1,17 +1,214 @@
 //! X11 backend implementation
 //!
-//! This module provides utilities for the X11 backend.
-//! The actual X11 initialization remains in compositor_main.rs for now,
-//! as the Smithay API is tightly coupled to specific renderer types.
+//! [`X11Host`] opens the compositor's window on the host X server and
+//! renders into it with GLES on dmabufs from the X server's GPU. The main
+//! loop in compositor_main.rs drives it through [`HostWindow`].
 //!
 //! # Future Work
 //!
 //! As the backend abstraction matures, more X11-specific code can be
 //! moved here to provide a cleaner separation.
 
-use smithay::backend::x11::{X11Event, X11Input};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+use std::sync::Arc;
 
-use super::BackendEvent;
+use smithay::backend::allocator::dmabuf::{Dmabuf, DmabufAllocator};
+use smithay::backend::allocator::gbm::{GbmAllocator, GbmBufferFlags, GbmDevice};
+use smithay::backend::egl::{EGLContext, EGLDisplay};
+use smithay::backend::input::InputEvent;
+use smithay::backend::renderer::gles::{GlesRenderer, GlesTarget};
+use smithay::backend::renderer::Bind;
+use smithay::backend::x11::{Window, WindowBuilder, X11Backend, X11Event, X11Input, X11Surface};
+use smithay::reexports::calloop::LoopHandle;
+use smithay::reexports::wayland_protocols::wp::presentation_time::server::wp_presentation_feedback;
+use smithay::utils::{DeviceFd, Physical, Size};
+use x11rb::rust_connection::RustConnection;
+
+use super::{BackendEvent, HostWindow};
+use crate::cursor::CursorManager;
+use crate::icon::{set_window_class, set_window_icon};
+use crate::state::TermStack;
+
+/// The compositor's window on the host X server
+pub struct X11Host {
+    window: Window,
+    surface: X11Surface,
+    renderer: GlesRenderer,
+    /// Buffer being drawn, between `bind` and `submit`
+    buffer: Option<Dmabuf>,
+    connection: Arc<RustConnection>,
+    cursor_manager: Option<CursorManager>,
+    input: Rc<RefCell<Vec<InputEvent<X11Input>>>>,
+}
+
+impl X11Host {
+    /// Open and map a window titled `title` and add its X11 event source to the loop
+    pub fn new(handle: &LoopHandle<'_, TermStack>, title: &str) -> anyhow::Result<Self> {
+        let x11_backend = X11Backend::new()
+            .map_err(|e| anyhow::anyhow!("X11 backend init error: {e:?}"))?;
+        let x11_handle = x11_backend.handle();
+
+        // Create window
+        let window = WindowBuilder::new()
+            .title(title)
+            .size((1280u16, 800u16).into())
+            .build(&x11_handle)
+            .map_err(|e| anyhow::anyhow!("X11 window creation error: {e:?}"))?;
+
+        // Get DRM node for GPU rendering
+        let (_drm_node, fd) = x11_handle.drm_node()
+            .map_err(|e| anyhow::anyhow!("Failed to get DRM node: {e:?}"))?;
+
+        // Create GBM device for buffer allocation
+        let gbm_device = GbmDevice::new(DeviceFd::from(fd))
+            .map_err(|e| anyhow::anyhow!("Failed to create GBM device: {e:?}"))?;
+
+        // Create EGL display and context for OpenGL rendering
+        let egl_display = unsafe { EGLDisplay::new(gbm_device.clone()) }
+            .map_err(|e| anyhow::anyhow!("Failed to create EGL display: {e:?}"))?;
+        let egl_context = EGLContext::new(&egl_display)
+            .map_err(|e| anyhow::anyhow!("Failed to create EGL context: {e:?}"))?;
+
+        // Get supported modifiers for buffer allocation
+        let modifiers: HashSet<_> = egl_context
+            .dmabuf_render_formats()
+            .iter()
+            .map(|format| format.modifier)
+            .collect();
+
+        // Create X11 surface for presenting buffers
+        let surface = x11_handle.create_surface(
+            &window,
+            DmabufAllocator(GbmAllocator::new(gbm_device, GbmBufferFlags::RENDERING)),
+            modifiers.into_iter(),
+        ).map_err(|e| anyhow::anyhow!("Failed to create X11 surface: {e:?}"))?;
+
+        // Create GLES renderer from EGL context
+        let renderer = unsafe { GlesRenderer::new(egl_context) }
+            .map_err(|e| anyhow::anyhow!("Failed to create GLES renderer: {e:?}"))?;
+
+        // Set window icon and class BEFORE mapping (GNOME queries these on map)
+        let connection = x11_handle.connection();
+        if let Err(e) = set_window_icon(&connection, window.id()) {
+            tracing::warn!(?e, "failed to set window icon");
+        }
+        if let Err(e) = set_window_class(&connection, window.id()) {
+            tracing::warn!(?e, "failed to set window class");
+        }
+
+        // Map the window to make it visible
+        window.map();
+
+        // Create cursor manager for resize cursor feedback
+        let cursor_manager = match CursorManager::new(
+            connection.clone(),
+            x11_handle.screen(),
+            window.id(),
+        ) {
+            Ok(cm) => Some(cm),
+            Err(e) => {
+                tracing::warn!(?e, "failed to create cursor manager, cursor changes disabled");
+                None
+            }
+        };
+
+        // Unbounded buffer for X11 input events. The X11 source callback moves
+        // events here right away (processing needs terminal_manager, which the
+        // main loop owns), so Smithay's bounded internal channel never backs up
+        // and no drain/poll workaround is needed.
+        let input: Rc<RefCell<Vec<InputEvent<X11Input>>>> = Rc::default();
+        let input_source = input.clone();
+
+        // Insert X11 backend into event loop
+        handle.insert_source(x11_backend, move |event, _, state| {
+            state.redraw_requested = true;
+            match event {
+                X11Event::Input { event: input_event, .. } => {
+                    // Log that we received an X11 input event (helps debug freezes)
+                    tracing::debug!("X11 input event received");
+                    input_source.borrow_mut().push(input_event);
+                }
+                X11Event::Resized { new_size, .. } => {
+                    state.compositor_window_resize_pending = Some((new_size.w, new_size.h));
+                }
+                X11Event::CloseRequested { .. } => {
+                    state.running = false;
+                }
+                X11Event::Focus { focused, .. } => {
+                    tracing::info!("X11 window focus changed: {}", focused);
+                }
+                X11Event::Refresh { .. } => {
+                    // Window needs redraw - will happen in main loop anyway
+                }
+                X11Event::PresentCompleted { .. } => {
+                    // Buffer presentation complete - ready for next frame
+                    state.presentation.presented(state.refresh, wp_presentation_feedback::Kind::Vsync);
+                }
+            }
+        }).map_err(|e| anyhow::anyhow!("Failed to insert X11 backend source: {e:?}"))?;
+
+        Ok(Self {
+            window,
+            surface,
+            renderer,
+            buffer: None,
+            connection,
+            cursor_manager,
+            input,
+        })
+    }
+}
+
+impl HostWindow for X11Host {
+    type Input = X11Input;
+
+    const OUTPUT_NAME: &'static str = "x11";
+
+    fn size(&self) -> Size<i32, Physical> {
+        let size = self.window.size();
+        (size.w as i32, size.h as i32).into()
+    }
+
+    fn renderer(&mut self) -> &mut GlesRenderer {
+        &mut self.renderer
+    }
+
+    fn take_input(&mut self) -> Vec<InputEvent<X11Input>> {
+        std::mem::take(&mut *self.input.borrow_mut())
+    }
+
+    fn refresh(&self) -> Option<i32> {
+        // The window may have been moved to another monitor since the last call
+        crate::refresh::x11_refresh(&self.connection, self.window.id())
+    }
+
+    fn reports_presents(&self) -> bool {
+        true
+    }
+
+    fn set_resize_cursor(&mut self, resize: bool) {
+        if let Some(ref mut cm) = self.cursor_manager {
+            cm.set_resize_cursor(resize);
+        }
+    }
+
+    fn bind(&mut self) -> anyhow::Result<(&mut GlesRenderer, GlesTarget<'_>, u8)> {
+        let (buffer, age) = self.surface.buffer()
+            .map_err(|e| anyhow::anyhow!("Failed to get X11 surface buffer: {e:?}"))?;
+        let buffer = self.buffer.insert(buffer);
+        let target = self.renderer.bind(buffer)
+            .map_err(|e| anyhow::anyhow!("Failed to bind buffer: {e:?}"))?;
+        Ok((&mut self.renderer, target, age))
+    }
+
+    fn submit(&mut self) -> anyhow::Result<()> {
+        self.buffer = None;
+        self.surface.submit()
+            .map_err(|e| anyhow::anyhow!("Failed to submit X11 surface: {e:?}"))
+    }
+}
 
 /// Convert X11Event to BackendEvent
 pub fn convert_x11_event(event: X11Event) -> Option<BackendEvent<X11Input>> {
@@ -0,0 +1,160 @@
+//! Background image or gradient behind the stack
+//!
+//! The configured image is decoded once at startup. Pixels for the output are
+//! rendered on the CPU, in BGRA byte order (`Argb8888` textures and
+//! softbuffer both use it), and rendered again whenever the output size
+//! changes; the backends upload or blit the result and draw it instead of
+//! clearing to `background_color`.
+
+use std::path::PathBuf;
+
+use image::imageops::{self, FilterType};
+use image::RgbaImage;
+
+use crate::config::{BackgroundConfig, BackgroundMode};
+use crate::home::{expand_home, home_dir};
+
+/// Errors loading the background image
+#[derive(Debug, thiserror::Error)]
+pub enum BackgroundError {
+    #[error("failed to load background image {path}: {source}")]
+    Image {
+        path: PathBuf,
+        #[source]
+        source: image::ImageError,
+    },
+}
+
+/// A loaded background, ready to render at any size
+#[derive(Debug)]
+pub enum Background {
+    Image { image: RgbaImage, mode: BackgroundMode },
+    Gradient { top: [f32; 4], bottom: [f32; 4] },
+}
+
+impl Background {
+    /// Load the configured background; `None` means a flat `background_color`
+    pub fn load(config: &BackgroundConfig) -> Result<Option<Self>, BackgroundError> {
+        if let Some(path) = &config.image {
+            let path = match path.to_str() {
+                Some(text) => expand_home(text, home_dir().as_deref()),
+                None => path.clone(),
+            };
+            let image = image::open(&path)
+                .map_err(|source| BackgroundError::Image { path: path.clone(), source })?
+                .into_rgba8();
+            tracing::info!(?path, width = image.width(), height = image.height(), "loaded background image");
+            return Ok(Some(Self::Image { image, mode: config.mode }));
+        }
+        Ok(config.gradient.map(|[top, bottom]| Self::Gradient { top, bottom }))
+    }
+
+    /// Pixels covering a `width` x `height` output, in BGRA order
+    pub fn render(&self, width: u32, height: u32) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        match self {
+            Self::Image { image, mode: BackgroundMode::Scaled } => {
+                let scaled = cover(image, width, height);
+                pixels.extend(scaled.pixels().flat_map(|p| bgra(p.0)));
+            }
+            Self::Image { image, mode: BackgroundMode::Tiled } => {
+                for y in 0..height {
+                    for x in 0..width {
+                        let p = image.get_pixel(x % image.width(), y % image.height());
+                        pixels.extend(bgra(p.0));
+                    }
+                }
+            }
+            Self::Gradient { top, bottom } => {
+                for y in 0..height {
+                    let t = if height > 1 { y as f32 / (height - 1) as f32 } else { 0.0 };
+                    let row = gradient_color(*top, *bottom, t);
+                    for _ in 0..width {
+                        pixels.extend(row);
+                    }
+                }
+            }
+        }
+        pixels
+    }
+}
+
+/// `image` scaled to cover `width` x `height` keeping its aspect ratio,
+/// with the overflow cropped equally from both sides
+fn cover(image: &RgbaImage, width: u32, height: u32) -> RgbaImage {
+    let (crop_w, crop_h) = cover_crop(image.width(), image.height(), width, height);
+    let x = (image.width() - crop_w) / 2;
+    let y = (image.height() - crop_h) / 2;
+    let cropped = imageops::crop_imm(image, x, y, crop_w, crop_h).to_image();
+    imageops::resize(&cropped, width, height, FilterType::Triangle)
+}
+
+/// Size of the centered part of an image that, scaled, exactly covers the output
+fn cover_crop(image_w: u32, image_h: u32, width: u32, height: u32) -> (u32, u32) {
+    if width == 0 || height == 0 {
+        return (image_w, image_h);
+    }
+    // Compare aspect ratios without floats: image_w/image_h vs width/height
+    if image_w as u64 * height as u64 > width as u64 * image_h as u64 {
+        // Image is wider: crop the sides
+        let crop_w = (image_h as u64 * width as u64 / height as u64).max(1) as u32;
+        (crop_w, image_h)
+    } else {
+        let crop_h = (image_w as u64 * height as u64 / width as u64).max(1) as u32;
+        (image_w, crop_h)
+    }
+}
+
+/// Color `t` of the way from `top` to `bottom`, as BGRA bytes
+fn gradient_color(top: [f32; 4], bottom: [f32; 4], t: f32) -> [u8; 4] {
+    let channel = |i: usize| ((top[i] + (bottom[i] - top[i]) * t).clamp(0.0, 1.0) * 255.0).round() as u8;
+    [channel(2), channel(1), channel(0), channel(3)]
+}
+
+fn bgra([r, g, b, a]: [u8; 4]) -> [u8; 4] {
+    [b, g, r, a]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cover_crops_the_longer_side() {
+        // 2:1 image on a 1:1 output: keep the middle square
+        assert_eq!(cover_crop(200, 100, 50, 50), (100, 100));
+        // 1:2 image on a 2:1 output
+        assert_eq!(cover_crop(100, 200, 200, 100), (100, 50));
+        assert_eq!(cover_crop(160, 90, 1920, 1080), (160, 90));
+    }
+
+    #[test]
+    fn gradient_runs_top_to_bottom() {
+        let background = Background::Gradient { top: [1.0, 0.0, 0.0, 1.0], bottom: [0.0, 0.0, 1.0, 1.0] };
+        let pixels = background.render(2, 3);
+        assert_eq!(pixels.len(), 2 * 3 * 4);
+        // BGRA: red at the top, blue at the bottom
+        assert_eq!(&pixels[..4], &[0, 0, 255, 255]);
+        assert_eq!(&pixels[pixels.len() - 4..], &[255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn tiles_repeat_the_image() {
+        let mut image = RgbaImage::new(2, 1);
+        image.put_pixel(0, 0, image::Rgba([10, 20, 30, 255]));
+        image.put_pixel(1, 0, image::Rgba([40, 50, 60, 255]));
+        let background = Background::Image { image, mode: BackgroundMode::Tiled };
+        let pixels = background.render(3, 1);
+        assert_eq!(pixels, vec![30, 20, 10, 255, 60, 50, 40, 255, 30, 20, 10, 255]);
+    }
+
+    #[test]
+    fn missing_image_is_an_error() {
+        let config = BackgroundConfig {
+            image: Some(PathBuf::from("/nonexistent/wallpaper.png")),
+            ..Default::default()
+        };
+        assert!(Background::load(&config).is_err());
+        assert!(Background::load(&BackgroundConfig::default()).unwrap().is_none());
+    }
+}
@@ -0,0 +1,197 @@
+//! Actions over the whole stack
+//!
+//! `termstack all close-exited|collapse|kill|undo` asks for one of these over
+//! IPC. Closing finished command cells and collapsing every cell but the
+//! focused one go on the undo stack (`crate::undo`) and can be taken back as
+//! a whole with `undo`; killing running commands can't.
+
+use std::os::unix::net::UnixStream;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cell_id::CellId;
+use crate::state::{StackWindow, TermStack};
+use crate::terminal_manager::TerminalManager;
+use crate::undo::{ClosedCell, UndoEntry};
+
+/// A stack-wide action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkAction {
+    /// Close every command cell whose command has exited
+    CloseExited,
+    /// Hide every cell except the focused one
+    Collapse,
+    /// Hang up every running command (shells are left alone)
+    KillChildren,
+    /// Take back the last close or collapse
+    Undo,
+}
+
+/// Answer to a bulk action request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BulkResult {
+    /// Cells closed, collapsed, signalled or brought back
+    pub affected: usize,
+}
+
+/// Bulk action state, kept on `TermStack`
+#[derive(Default)]
+pub struct BulkActions {
+    /// Requests from IPC with the stream to answer on
+    pub pending: Vec<(BulkAction, UnixStream)>,
+    /// Cells hidden by `Collapse`
+    collapsed: Vec<CellId>,
+}
+
+impl BulkActions {
+    /// Whether `Collapse` hides `cell`
+    pub fn hides(&self, cell: CellId) -> bool {
+        self.collapsed.contains(&cell)
+    }
+
+    /// Go back to the cells collapsed before the last `Collapse`, returning
+    /// how many that one hid
+    pub fn restore_collapsed(&mut self, previous: Vec<CellId>) -> usize {
+        std::mem::replace(&mut self.collapsed, previous).len()
+    }
+}
+
+impl TermStack {
+    /// Whether `cell` is hidden by do-not-disturb focus mode, a collapse or
+    /// the filter view
+    pub fn hides_cell(&self, cell: CellId) -> bool {
+        self.do_not_disturb.hides(cell) || self.bulk_actions.hides(cell) || self.filter_view.hides(cell)
+    }
+}
+
+/// Run bulk actions requested over IPC and keep collapsed cells in line
+/// with focus (once per frame)
+pub fn process(compositor: &mut TermStack, terminal_manager: &mut TerminalManager) {
+    // A collapsed cell that gets focused opens up again
+    if let Some(focused) = compositor.focused_index().and_then(|index| compositor.layout_nodes.get(index)) {
+        let focused = focused.id;
+        if compositor.bulk_actions.hides(focused) {
+            compositor.bulk_actions.collapsed.retain(|cell| *cell != focused);
+            compositor.redraw_requested = true;
+        }
+    }
+
+    for (action, stream) in std::mem::take(&mut compositor.bulk_actions.pending) {
+        let affected = apply(compositor, terminal_manager, action);
+        tracing::info!(?action, affected, "bulk action applied");
+        compositor.redraw_requested = true;
+        if let Err(e) = crate::ipc::send_json_response(stream, &BulkResult { affected }) {
+            tracing::warn!(error = ?e, "Failed to send bulk action response");
+        }
+    }
+}
+
+/// Apply `action`, returning how many cells it touched
+pub fn apply(compositor: &mut TermStack, terminal_manager: &mut TerminalManager, action: BulkAction) -> usize {
+    match action {
+        BulkAction::CloseExited => close_exited(compositor, terminal_manager),
+        BulkAction::Collapse => collapse(compositor),
+        BulkAction::KillChildren => kill_children(compositor, terminal_manager),
+        BulkAction::Undo => crate::undo::undo(compositor, terminal_manager),
+    }
+}
+
+fn close_exited(compositor: &mut TermStack, terminal_manager: &mut TerminalManager) -> usize {
+    // Output cells of open GUI windows stay; the window still refers to them
+    let linked: Vec<_> = compositor
+        .layout_nodes
+        .iter()
+        .filter_map(|node| match &node.cell {
+            StackWindow::External(entry) => entry.output_terminal,
+            StackWindow::Terminal(_) => None,
+        })
+        .collect();
+    let exited: Vec<_> = compositor
+        .layout_nodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, node)| {
+            let id = node.cell.terminal_id()?;
+            let terminal = terminal_manager.get(id)?;
+            (terminal.parent.is_some() && terminal.has_exited() && !linked.contains(&id)).then_some(index)
+        })
+        .collect();
+    if exited.is_empty() {
+        return 0;
+    }
+
+    let focused = compositor.focused_index();
+    let now = Instant::now();
+    // From the bottom up, so the indices of the rest stay valid
+    let mut closed = Vec::with_capacity(exited.len());
+    for &index in exited.iter().rev() {
+        let node = compositor.remove_cell(index);
+        if let Some(terminal) = node.cell.terminal_id().and_then(|id| terminal_manager.remove(id)) {
+            compositor.archive.add(&terminal, now);
+            closed.push(ClosedCell { index, terminal, height: node.height });
+        }
+    }
+    closed.reverse();
+    compositor.invalidate_focused_index_cache();
+    if let Some(index) = focused.filter(|index| exited.contains(index)) {
+        let above = exited.iter().filter(|other| **other < index).count();
+        compositor.update_focus_after_removal(index - above);
+    }
+    compositor.recalculate_layout();
+
+    let count = closed.len();
+    compositor.undo_stack.push(UndoEntry::Closed(closed), now);
+    count
+}
+
+fn collapse(compositor: &mut TermStack) -> usize {
+    let Some(focused) = compositor.focused_index().and_then(|index| compositor.layout_nodes.get(index)) else {
+        return 0;
+    };
+    let focused = focused.id;
+    let collapsed: Vec<_> = compositor
+        .layout_nodes
+        .iter()
+        .map(|node| node.id)
+        .filter(|id| *id != focused)
+        .collect();
+    let count = collapsed.len();
+    let previous = std::mem::replace(&mut compositor.bulk_actions.collapsed, collapsed);
+    compositor.undo_stack.push(UndoEntry::Collapsed(previous), Instant::now());
+    count
+}
+
+fn kill_children(compositor: &TermStack, terminal_manager: &TerminalManager) -> usize {
+    compositor
+        .layout_nodes
+        .iter()
+        .filter_map(|node| terminal_manager.get(node.cell.terminal_id()?))
+        .filter(|terminal| terminal.parent.is_some() && !terminal.has_exited())
+        .filter(|terminal| terminal.terminal.hang_up())
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn actions_parse_from_snake_case() {
+        let action: BulkAction = serde_json::from_str(r#""close_exited""#).unwrap();
+        assert_eq!(action, BulkAction::CloseExited);
+        let action: BulkAction = serde_json::from_str(r#""kill_children""#).unwrap();
+        assert_eq!(action, BulkAction::KillChildren);
+    }
+
+    #[test]
+    fn collapsed_cells_are_hidden() {
+        let (kept, collapsed) = (CellId::from_bits(1), CellId::from_bits(2));
+        let mut bulk = BulkActions::default();
+        assert!(!bulk.hides(collapsed));
+        bulk.collapsed = vec![collapsed];
+        assert!(bulk.hides(collapsed));
+        assert!(!bulk.hides(kept));
+    }
+}
@@ -0,0 +1,426 @@
+//! Calculator for the prompt
+//!
+//! The shell integration hands command lines that look like arithmetic to
+//! `termstack --calc`, which answers them in a short-lived stack entry
+//! instead of spawning a shell. Anything that doesn't evaluate here runs
+//! as a normal command.
+//!
+//! - Arithmetic: `+ - * / % ^`, parentheses, `pi`, `e` and
+//!   `sqrt abs ln log floor ceil round` (`2^10`, `(3 + 4) * 1.5`)
+//! - Unit conversions: `VALUE UNIT to|in UNIT` (`5 km to mi`, `100 F in C`,
+//!   `3 GiB to MB`) for length, mass, time, volume, data and temperature
+
+/// Errors evaluating a calculation
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum CalcError {
+    #[error("not a calculation")]
+    NotACalculation,
+
+    #[error("unexpected '{0}'")]
+    UnexpectedChar(char),
+
+    #[error("unexpected end of expression")]
+    UnexpectedEnd,
+
+    #[error("unexpected {0}")]
+    UnexpectedToken(String),
+
+    #[error("unknown name '{0}'")]
+    UnknownName(String),
+
+    #[error("unknown unit '{0}'")]
+    UnknownUnit(String),
+
+    #[error("cannot convert {from} to {to}")]
+    Incompatible { from: String, to: String },
+
+    #[error("result is not a finite number")]
+    NotFinite,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Op(char),
+    Open,
+    Close,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, CalcError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut number = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit() || **c == '.' || **c == '_') {
+                if c != '_' {
+                    number.push(c);
+                }
+                chars.next();
+            }
+            // Exponent, only when digits follow (`2e3`, not `2 e`)
+            let mut ahead = chars.clone();
+            if ahead.next().is_some_and(|c| c == 'e' || c == 'E') {
+                let sign = ahead.peek().copied().filter(|c| *c == '+' || *c == '-');
+                if sign.is_some() {
+                    ahead.next();
+                }
+                if ahead.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    number.push('e');
+                    number.extend(sign);
+                    chars = ahead;
+                    while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                        number.push(c);
+                        chars.next();
+                    }
+                }
+            }
+            let value = number.parse().map_err(|_| CalcError::UnexpectedToken(number.clone()))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_alphabetic() {
+            let mut name = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric()) {
+                name.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Name(name));
+        } else {
+            chars.next();
+            tokens.push(match c {
+                '+' | '-' | '*' | '/' | '%' | '^' => Token::Op(c),
+                '×' => Token::Op('*'),
+                '÷' => Token::Op('/'),
+                '(' => Token::Open,
+                ')' => Token::Close,
+                c => return Err(CalcError::UnexpectedChar(c)),
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over the tokens of an arithmetic expression
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn finish(mut self) -> Result<(), CalcError> {
+        match self.next() {
+            None => Ok(()),
+            Some(token) => Err(unexpected(token)),
+        }
+    }
+
+    /// `term (+|- term)*`
+    fn expr(&mut self) -> Result<f64, CalcError> {
+        let mut value = self.term()?;
+        while let Some(Token::Op(op @ ('+' | '-'))) = self.peek() {
+            let op = *op;
+            self.pos += 1;
+            let rhs = self.term()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Ok(value)
+    }
+
+    /// `unary (*|/|% unary)*`
+    fn term(&mut self) -> Result<f64, CalcError> {
+        let mut value = self.unary()?;
+        while let Some(Token::Op(op @ ('*' | '/' | '%'))) = self.peek() {
+            let op = *op;
+            self.pos += 1;
+            let rhs = self.unary()?;
+            value = match op {
+                '*' => value * rhs,
+                '/' => value / rhs,
+                _ => value % rhs,
+            };
+        }
+        Ok(value)
+    }
+
+    /// Sign binds looser than `^`, so `-2^2` is -4
+    fn unary(&mut self) -> Result<f64, CalcError> {
+        match self.peek() {
+            Some(Token::Op('-')) => {
+                self.pos += 1;
+                Ok(-self.unary()?)
+            }
+            Some(Token::Op('+')) => {
+                self.pos += 1;
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    /// `primary (^ unary)?`, right-associative
+    fn power(&mut self) -> Result<f64, CalcError> {
+        let base = self.primary()?;
+        if let Some(Token::Op('^')) = self.peek() {
+            self.pos += 1;
+            return Ok(base.powf(self.unary()?));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<f64, CalcError> {
+        match self.next().cloned() {
+            Some(Token::Number(value)) => Ok(value),
+            Some(Token::Open) => {
+                let value = self.expr()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(value),
+                    Some(token) => Err(unexpected(token)),
+                    None => Err(CalcError::UnexpectedEnd),
+                }
+            }
+            Some(Token::Name(name)) => match name.as_str() {
+                "pi" => Ok(std::f64::consts::PI),
+                "e" => Ok(std::f64::consts::E),
+                _ => {
+                    let function = function(&name).ok_or(CalcError::UnknownName(name))?;
+                    if self.peek() != Some(&Token::Open) {
+                        return Err(self.peek().map_or(CalcError::UnexpectedEnd, unexpected));
+                    }
+                    Ok(function(self.primary()?))
+                }
+            },
+            Some(token) => Err(unexpected(&token)),
+            None => Err(CalcError::UnexpectedEnd),
+        }
+    }
+}
+
+fn unexpected(token: &Token) -> CalcError {
+    CalcError::UnexpectedToken(match token {
+        Token::Number(value) => format_number(*value),
+        Token::Name(name) => format!("'{}'", name),
+        Token::Op(op) => format!("'{}'", op),
+        Token::Open => "'('".to_string(),
+        Token::Close => "')'".to_string(),
+    })
+}
+
+fn function(name: &str) -> Option<fn(f64) -> f64> {
+    Some(match name {
+        "sqrt" => f64::sqrt,
+        "abs" => f64::abs,
+        "ln" => f64::ln,
+        "log" => f64::log10,
+        "floor" => f64::floor,
+        "ceil" => f64::ceil,
+        "round" => f64::round,
+        _ => return None,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dimension {
+    Length,
+    Mass,
+    Time,
+    Volume,
+    Data,
+    Temperature,
+}
+
+/// Unit names, what they measure and their size in the dimension's base unit
+/// (meters, kilograms, seconds, liters, bytes; kelvin steps for temperature)
+const UNITS: &[(&[&str], Dimension, f64)] = &[
+    (&["mm", "millimeter", "millimeters"], Dimension::Length, 0.001),
+    (&["cm", "centimeter", "centimeters"], Dimension::Length, 0.01),
+    (&["m", "meter", "meters", "metre", "metres"], Dimension::Length, 1.0),
+    (&["km", "kilometer", "kilometers"], Dimension::Length, 1000.0),
+    (&["in", "inch", "inches"], Dimension::Length, 0.0254),
+    (&["ft", "foot", "feet"], Dimension::Length, 0.3048),
+    (&["yd", "yard", "yards"], Dimension::Length, 0.9144),
+    (&["mi", "mile", "miles"], Dimension::Length, 1609.344),
+    (&["nmi"], Dimension::Length, 1852.0),
+    (&["mg", "milligram", "milligrams"], Dimension::Mass, 1e-6),
+    (&["g", "gram", "grams"], Dimension::Mass, 0.001),
+    (&["kg", "kilogram", "kilograms"], Dimension::Mass, 1.0),
+    (&["t", "tonne", "tonnes"], Dimension::Mass, 1000.0),
+    (&["oz", "ounce", "ounces"], Dimension::Mass, 0.028349523125),
+    (&["lb", "lbs", "pound", "pounds"], Dimension::Mass, 0.45359237),
+    (&["st", "stone"], Dimension::Mass, 6.35029318),
+    (&["ms", "millisecond", "milliseconds"], Dimension::Time, 0.001),
+    (&["s", "sec", "second", "seconds"], Dimension::Time, 1.0),
+    (&["min", "minute", "minutes"], Dimension::Time, 60.0),
+    (&["h", "hr", "hour", "hours"], Dimension::Time, 3600.0),
+    (&["d", "day", "days"], Dimension::Time, 86400.0),
+    (&["week", "weeks"], Dimension::Time, 604800.0),
+    (&["year", "years"], Dimension::Time, 31557600.0),
+    (&["ml", "milliliter", "milliliters"], Dimension::Volume, 0.001),
+    (&["l", "liter", "liters", "litre", "litres"], Dimension::Volume, 1.0),
+    (&["cup", "cups"], Dimension::Volume, 0.2365882365),
+    (&["pt", "pint", "pints"], Dimension::Volume, 0.473176473),
+    (&["qt", "quart", "quarts"], Dimension::Volume, 0.946352946),
+    (&["gal", "gallon", "gallons"], Dimension::Volume, 3.785411784),
+    (&["bit", "bits"], Dimension::Data, 0.125),
+    (&["B", "byte", "bytes"], Dimension::Data, 1.0),
+    (&["KB", "kB"], Dimension::Data, 1e3),
+    (&["MB"], Dimension::Data, 1e6),
+    (&["GB"], Dimension::Data, 1e9),
+    (&["TB"], Dimension::Data, 1e12),
+    (&["KiB"], Dimension::Data, 1024.0),
+    (&["MiB"], Dimension::Data, 1048576.0),
+    (&["GiB"], Dimension::Data, 1073741824.0),
+    (&["TiB"], Dimension::Data, 1099511627776.0),
+    (&["K", "kelvin"], Dimension::Temperature, 1.0),
+    (&["C", "celsius"], Dimension::Temperature, 1.0),
+    (&["F", "fahrenheit"], Dimension::Temperature, 5.0 / 9.0),
+];
+
+#[derive(Debug, Clone, Copy)]
+struct Unit {
+    name: &'static str,
+    dimension: Dimension,
+    factor: f64,
+}
+
+/// Look up a unit, exactly first (`MB` vs `mb`), then ignoring case
+fn unit(name: &str) -> Option<Unit> {
+    let find = |matches: &dyn Fn(&str) -> bool| {
+        UNITS.iter().find_map(|(names, dimension, factor)| {
+            names.iter().find(|n| matches(n)).map(|_| Unit { name: names[0], dimension: *dimension, factor: *factor })
+        })
+    };
+    find(&|n| n == name).or_else(|| find(&|n| n.eq_ignore_ascii_case(name)))
+}
+
+/// Offset of a temperature scale's zero from absolute zero, in its own degrees
+fn temperature_offset(unit: &Unit) -> f64 {
+    match unit.name {
+        "C" => 273.15,
+        "F" => 459.67,
+        _ => 0.0,
+    }
+}
+
+fn convert(value: f64, from: &Unit, to: &Unit) -> f64 {
+    if from.dimension == Dimension::Temperature {
+        let kelvin = (value + temperature_offset(from)) * from.factor;
+        kelvin / to.factor - temperature_offset(to)
+    } else {
+        value * from.factor / to.factor
+    }
+}
+
+/// Evaluate `input`, returning the formatted answer
+///
+/// Plain numbers and anything that isn't arithmetic or a unit conversion
+/// are `NotACalculation`, so they can be run as commands instead.
+pub fn evaluate(input: &str) -> Result<String, CalcError> {
+    let tokens = tokenize(input)?;
+    if !tokens.iter().any(|t| matches!(t, Token::Op(_) | Token::Name(_) | Token::Open)) {
+        return Err(CalcError::NotACalculation);
+    }
+
+    // `VALUE UNIT to|in UNIT`
+    let keyword = tokens.iter().rposition(|t| matches!(t, Token::Name(n) if n == "to" || n == "in"));
+    if let Some(keyword) = keyword.filter(|&k| k >= 2 && k + 2 == tokens.len()) {
+        if let (Token::Name(from), Token::Name(to)) = (&tokens[keyword - 1], &tokens[keyword + 1]) {
+            let from_unit = unit(from).ok_or_else(|| CalcError::UnknownUnit(from.clone()))?;
+            let to_unit = unit(to).ok_or_else(|| CalcError::UnknownUnit(to.clone()))?;
+            if from_unit.dimension != to_unit.dimension {
+                return Err(CalcError::Incompatible { from: from.clone(), to: to.clone() });
+            }
+            let mut parser = Parser { tokens: &tokens[..keyword - 1], pos: 0 };
+            let value = parser.expr()?;
+            parser.finish()?;
+            let result = finite(convert(value, &from_unit, &to_unit))?;
+            return Ok(format!("{} {}", format_number(result), to));
+        }
+    }
+
+    let mut parser = Parser { tokens: &tokens, pos: 0 };
+    let value = parser.expr()?;
+    parser.finish()?;
+    Ok(format_number(finite(value)?))
+}
+
+fn finite(value: f64) -> Result<f64, CalcError> {
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(CalcError::NotFinite)
+    }
+}
+
+/// Up to 10 significant digits, without trailing zeros
+fn format_number(value: f64) -> String {
+    if value == 0.0 {
+        return "0".to_string();
+    }
+    let magnitude = value.abs().log10().floor() as i32;
+    if !(-6..15).contains(&magnitude) {
+        return format!("{:e}", value);
+    }
+    let decimals = (9 - magnitude).max(0) as usize;
+    let text = format!("{:.*}", decimals, value);
+    let text = if text.contains('.') { text.trim_end_matches('0').trim_end_matches('.') } else { &text };
+    if text == "-0" { "0".to_string() } else { text.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(evaluate("2 + 2").unwrap(), "4");
+        assert_eq!(evaluate("(3 + 4) * 1.5").unwrap(), "10.5");
+        assert_eq!(evaluate("2^10").unwrap(), "1024");
+        assert_eq!(evaluate("-2^2").unwrap(), "-4");
+        assert_eq!(evaluate("2^3^2").unwrap(), "512");
+        assert_eq!(evaluate("10 / 4 - 1").unwrap(), "1.5");
+        assert_eq!(evaluate("17 % 5").unwrap(), "2");
+        assert_eq!(evaluate("1/3").unwrap(), "0.3333333333");
+        assert_eq!(evaluate("sqrt(16) + abs(-2)").unwrap(), "6");
+        assert_eq!(evaluate("2 * pi").unwrap(), "6.283185307");
+        assert_eq!(evaluate("1.5e3 * 2").unwrap(), "3000");
+        assert_eq!(evaluate("0.1 + 0.2").unwrap(), "0.3");
+    }
+
+    #[test]
+    fn unit_conversions() {
+        assert_eq!(evaluate("5 km to mi").unwrap(), "3.106855961 mi");
+        assert_eq!(evaluate("100 F in C").unwrap(), "37.77777778 C");
+        assert_eq!(evaluate("0 C to K").unwrap(), "273.15 K");
+        assert_eq!(evaluate("3 GiB to MB").unwrap(), "3221.225472 MB");
+        assert_eq!(evaluate("12 in to cm").unwrap(), "30.48 cm");
+        assert_eq!(evaluate("2 * 45 min to hours").unwrap(), "1.5 hours");
+        assert_eq!(
+            evaluate("5 kg to km"),
+            Err(CalcError::Incompatible { from: "kg".into(), to: "km".into() })
+        );
+        assert_eq!(evaluate("5 parsec to km"), Err(CalcError::UnknownUnit("parsec".into())));
+    }
+
+    #[test]
+    fn commands_are_not_calculations() {
+        assert_eq!(evaluate("42"), Err(CalcError::NotACalculation));
+        assert_eq!(evaluate("7z x archive.7z"), Err(CalcError::UnexpectedToken("'z'".into())));
+        assert!(evaluate("2 +").is_err());
+        assert!(evaluate("(1 + 2").is_err());
+        assert_eq!(evaluate("1 / 0"), Err(CalcError::NotFinite));
+        assert_eq!(evaluate("ls -la"), Err(CalcError::UnknownName("ls".into())));
+        assert_eq!(evaluate("echo $HOME"), Err(CalcError::UnexpectedChar('$')));
+    }
+}
@@ -0,0 +1,326 @@
+//! Filter view: show only the cells of one tag or command
+//!
+//! `termstack filter cargo` (or Super+F and typing `cargo` at the prompt)
+//! hides every cell whose title and command don't contain `cargo`, ignoring
+//! case; `termstack filter --tag red` (`#red` at the prompt) keeps the cells
+//! with that color tag instead. The shells that launched the matching cells
+//! and the focused cell stay visible too, and cells started while the filter
+//! is on join it if they match. A banner at the top says what is filtered;
+//! Super+F again or `termstack filter --clear` shows all cells.
+//!
+//! Hidden cells take no space, like those hidden by do-not-disturb focus mode
+//! (see `TermStack::hides_cell`).
+
+use std::os::unix::net::UnixStream;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cell_id::CellId;
+use crate::color_tags::ColorTag;
+use crate::state::{StackWindow, TermStack};
+use crate::terminal_manager::TerminalManager;
+
+/// Longest text the prompt takes (characters)
+const MAX_PROMPT_CHARS: usize = 200;
+
+/// What the filter keeps
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CellFilter {
+    /// Cells with this color tag
+    Tag(ColorTag),
+    /// Cells whose title or command contains this text (ignoring case)
+    Command(String),
+}
+
+impl CellFilter {
+    /// Filter typed at the prompt: `#<color>` for a tag, anything else a
+    /// command substring, `None` if empty
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+        match text.strip_prefix('#').and_then(ColorTag::from_name) {
+            Some(tag) => Some(CellFilter::Tag(tag)),
+            None => Some(CellFilter::Command(text.to_string())),
+        }
+    }
+
+    /// Whether a cell tagged `tag` with these `titles` (title bar text,
+    /// command) matches
+    fn matches(&self, tag: Option<ColorTag>, titles: &[&str]) -> bool {
+        match self {
+            CellFilter::Tag(wanted) => tag == Some(*wanted),
+            CellFilter::Command(text) => {
+                let text = text.to_lowercase();
+                titles.iter().any(|title| title.to_lowercase().contains(&text))
+            }
+        }
+    }
+
+    /// Short form for the banner, as typed at the prompt
+    pub fn describe(&self) -> String {
+        match self {
+            CellFilter::Tag(tag) => format!("#{}", tag.name()),
+            CellFilter::Command(text) => format!("{text:?}"),
+        }
+    }
+}
+
+/// Filter state, kept on `TermStack`
+#[derive(Default)]
+pub struct FilterView {
+    filter: Option<CellFilter>,
+    /// Cells shown while filtering: the matching ones and their shells
+    shown: Vec<CellId>,
+    /// How many cells matched when `shown` was last worked out
+    matched: usize,
+    /// Text typed at the prompt, while it is open
+    prompt: Option<String>,
+    /// Set when Enter is pressed at the prompt, applied with the next frame
+    submitted: Option<CellFilter>,
+    /// Set by the keybinding to show all cells again with the next frame
+    pending_clear: bool,
+    /// Requests from IPC (`None` clears the filter) with the stream to answer on
+    pub pending: Vec<(Option<CellFilter>, UnixStream)>,
+}
+
+impl FilterView {
+    pub fn is_active(&self) -> bool {
+        self.filter.is_some()
+    }
+
+    /// Whether the filter hides `cell`
+    pub fn hides(&self, cell: CellId) -> bool {
+        self.filter.is_some() && !self.shown.contains(&cell)
+    }
+
+    pub fn is_prompting(&self) -> bool {
+        self.prompt.is_some()
+    }
+
+    pub fn open_prompt(&mut self) {
+        self.prompt = Some(String::new());
+    }
+
+    /// Add a typed character to the prompt
+    pub fn type_char(&mut self, c: char) {
+        if let Some(text) = &mut self.prompt {
+            if !c.is_control() && text.chars().count() < MAX_PROMPT_CHARS {
+                text.push(c);
+            }
+        }
+    }
+
+    /// Remove the last character from the prompt
+    pub fn backspace(&mut self) {
+        if let Some(text) = &mut self.prompt {
+            text.pop();
+        }
+    }
+
+    /// Close the prompt, applying what was typed if `apply`
+    /// (an empty prompt leaves the filter as it is)
+    pub fn close_prompt(&mut self, apply: bool) {
+        let text = self.prompt.take().unwrap_or_default();
+        if apply {
+            self.submitted = CellFilter::parse(&text);
+        }
+    }
+
+    /// Prompt panel text, while the prompt is open
+    pub fn prompt_lines(&self) -> Option<Vec<String>> {
+        let text = self.prompt.as_ref()?;
+        Some(vec![
+            "Show only cells matching:".to_string(),
+            format!("> {text}_"),
+            String::new(),
+            "A command, or #red, #blue, ... for a color tag".to_string(),
+            "Enter applies, Esc cancels".to_string(),
+        ])
+    }
+
+    /// Banner text while filtering, out of `total` cells
+    pub fn banner(&self, total: usize) -> Option<String> {
+        let filter = self.filter.as_ref()?;
+        Some(format!(
+            "Filter {}: {} of {} cells - Super+F shows all",
+            filter.describe(),
+            self.matched,
+            total
+        ))
+    }
+}
+
+impl TermStack {
+    /// Start filtering with `filter`, or show all cells again with `None`.
+    /// Returns how many cells match; a filter nothing matches is not applied.
+    pub fn set_cell_filter(
+        &mut self,
+        filter: Option<CellFilter>,
+        terminal_manager: &TerminalManager,
+    ) -> Result<usize, String> {
+        let Some(filter) = filter else {
+            if self.filter_view.filter.take().is_some() {
+                tracing::info!("cell filter cleared");
+                self.redraw_requested = true;
+            }
+            self.filter_view.shown.clear();
+            return Ok(0);
+        };
+
+        let matching = matching_cells(self, terminal_manager, &filter);
+        let Some(&last) = matching.last() else {
+            return Err(format!("no cells match {}", filter.describe()));
+        };
+        tracing::info!(?filter, matched = matching.len(), "cell filter set");
+
+        // Focus moves into the filtered view if it was on a hidden cell
+        let focused = self.focused_index().and_then(|index| self.layout_nodes.get(index)).map(|node| node.id);
+        if !focused.is_some_and(|cell| matching.contains(&cell)) {
+            if let Some(index) = self.cell_index(last) {
+                self.set_focus_by_index(index);
+            }
+        }
+
+        self.filter_view.filter = Some(filter);
+        update_shown(self, terminal_manager);
+        // Scroll the focused cell into view once hidden cells have collapsed
+        if let Some(index) = self.focused_index() {
+            self.reveal_after_layout(index);
+        }
+        self.redraw_requested = true;
+        Ok(self.filter_view.matched)
+    }
+
+    /// Open the filter prompt, or show all cells if filtering already
+    pub fn toggle_cell_filter(&mut self) {
+        if self.filter_view.is_active() {
+            // Applied with the next frame, which has the terminal manager
+            self.filter_view.pending_clear = true;
+        } else {
+            self.filter_view.open_prompt();
+        }
+        self.redraw_requested = true;
+    }
+}
+
+/// Cells matching `filter`, top to bottom
+fn matching_cells(compositor: &TermStack, terminal_manager: &TerminalManager, filter: &CellFilter) -> Vec<CellId> {
+    compositor
+        .layout_nodes
+        .iter()
+        .filter(|node| match &node.cell {
+            StackWindow::Terminal(id) => terminal_manager
+                .get(*id)
+                .is_some_and(|t| filter.matches(node.tag, &[t.display_title(), &t.title])),
+            StackWindow::External(entry) => filter.matches(node.tag, &[&entry.title_bar_text(), &entry.command]),
+        })
+        .map(|node| node.id)
+        .collect()
+}
+
+/// Work out which cells the filter shows: the matching ones, the shells that
+/// launched them and the focused one
+fn update_shown(compositor: &mut TermStack, terminal_manager: &TerminalManager) {
+    let Some(filter) = &compositor.filter_view.filter else {
+        return;
+    };
+    let matching = matching_cells(compositor, terminal_manager, filter);
+    let parents: Vec<CellId> = matching
+        .iter()
+        .filter_map(|&cell| {
+            let node = compositor.layout_nodes.get(compositor.cell_index(cell)?)?;
+            let parent = match &node.cell {
+                StackWindow::Terminal(id) => terminal_manager.get(*id)?.parent?,
+                StackWindow::External(entry) => entry.output_terminal?,
+            };
+            compositor.layout_nodes.iter().find(|other| other.cell.terminal_id() == Some(parent)).map(|other| other.id)
+        })
+        .collect();
+    let focused = compositor.focused_index().and_then(|index| compositor.layout_nodes.get(index)).map(|node| node.id);
+
+    let matched = matching.len();
+    let mut shown = matching;
+    for cell in parents.into_iter().chain(focused) {
+        if !shown.contains(&cell) {
+            shown.push(cell);
+        }
+    }
+    if shown != compositor.filter_view.shown || matched != compositor.filter_view.matched {
+        compositor.filter_view.shown = shown;
+        compositor.filter_view.matched = matched;
+        compositor.redraw_requested = true;
+    }
+}
+
+/// Apply filters from the prompt and IPC, and keep the filtered view in line
+/// with new cells and focus (once per frame)
+pub fn process(compositor: &mut TermStack, terminal_manager: &TerminalManager) {
+    if std::mem::take(&mut compositor.filter_view.pending_clear) {
+        let _ = compositor.set_cell_filter(None, terminal_manager);
+    }
+    if let Some(filter) = compositor.filter_view.submitted.take() {
+        if let Err(e) = compositor.set_cell_filter(Some(filter), terminal_manager) {
+            tracing::info!(error = %e, "filter from prompt not applied");
+        }
+    }
+    for (filter, stream) in std::mem::take(&mut compositor.filter_view.pending) {
+        let result = compositor.set_cell_filter(filter, terminal_manager);
+        if let Err(e) = crate::ipc::send_json_response(stream, &result) {
+            tracing::warn!(error = ?e, "Failed to send filter response");
+        }
+    }
+    update_shown(compositor, terminal_manager);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prompt_text_parses_to_tag_or_command() {
+        assert_eq!(CellFilter::parse(" #Red "), Some(CellFilter::Tag(ColorTag::Red)));
+        assert_eq!(CellFilter::parse("cargo"), Some(CellFilter::Command("cargo".to_string())));
+        assert_eq!(CellFilter::parse("#teal"), Some(CellFilter::Command("#teal".to_string())));
+        assert_eq!(CellFilter::parse("  "), None);
+    }
+
+    #[test]
+    fn commands_match_ignoring_case() {
+        let filter = CellFilter::Command("Cargo".to_string());
+        assert!(filter.matches(None, &["build", "~/code> cargo build"]));
+        assert!(!filter.matches(Some(ColorTag::Red), &["make"]));
+        let filter = CellFilter::Tag(ColorTag::Red);
+        assert!(filter.matches(Some(ColorTag::Red), &["make"]));
+        assert!(!filter.matches(None, &["red"]));
+    }
+
+    #[test]
+    fn prompt_applies_on_enter_only() {
+        let mut view = FilterView::default();
+        view.open_prompt();
+        for c in "git\u{7}".chars() {
+            view.type_char(c);
+        }
+        view.backspace();
+        assert_eq!(view.prompt_lines().unwrap()[1], "> gi_");
+        view.close_prompt(false);
+        assert!(!view.is_prompting() && view.submitted.is_none());
+
+        view.open_prompt();
+        view.type_char('x');
+        view.close_prompt(true);
+        assert_eq!(view.submitted, Some(CellFilter::Command("x".to_string())));
+    }
+
+    #[test]
+    fn only_shown_cells_are_kept_while_filtering() {
+        let (kept, other) = (CellId::from_bits(1), CellId::from_bits(2));
+        let mut view = FilterView { shown: vec![kept], ..FilterView::default() };
+        assert!(!view.hides(other));
+        view.filter = Some(CellFilter::Tag(ColorTag::Blue));
+        assert!(view.hides(other) && !view.hides(kept));
+    }
+}
@@ -0,0 +1,155 @@
+//! Stable cell identifiers
+//!
+//! Cells live in `TermStack::layout_nodes`, so a cell's Vec index changes
+//! whenever a cell above it is inserted or removed. State that refers to a
+//! cell across events (an active resize drag, a new window waiting to be
+//! scrolled into view) holds a `CellId` instead and resolves it to the current
+//! index through the `CellRegistry`.
+//!
+//! The registry is a small generational slot map: slots of removed cells are
+//! reused with a new generation, so a stale id resolves to `None` rather than
+//! to whichever cell took over its slot.
+
+/// Stable identifier of a cell in the layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CellId {
+    slot: u32,
+    generation: u32,
+}
+
+impl CellId {
+    /// Pack into an integer, for handing ids to other processes
+    pub fn to_bits(self) -> u64 {
+        (u64::from(self.generation) << 32) | u64::from(self.slot)
+    }
+
+    /// Unpack an id from `to_bits()`
+    pub fn from_bits(bits: u64) -> Self {
+        Self {
+            slot: bits as u32,
+            generation: (bits >> 32) as u32,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Slot {
+    generation: u32,
+    /// Current index in `layout_nodes`, `None` for a free slot
+    index: Option<usize>,
+}
+
+/// Maps cell ids to their current index in `layout_nodes`
+#[derive(Debug, Default)]
+pub struct CellRegistry {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+}
+
+impl CellRegistry {
+    /// Allocate an id for a cell at `index`
+    pub fn insert(&mut self, index: usize) -> CellId {
+        if let Some(slot) = self.free.pop() {
+            let entry = &mut self.slots[slot as usize];
+            entry.index = Some(index);
+            return CellId { slot, generation: entry.generation };
+        }
+        let slot = self.slots.len() as u32;
+        self.slots.push(Slot { generation: 0, index: Some(index) });
+        CellId { slot, generation: 0 }
+    }
+
+    /// Release an id; it (and every copy of it) resolves to `None` afterwards
+    pub fn remove(&mut self, id: CellId) -> Option<usize> {
+        let entry = self.live_slot_mut(id)?;
+        let index = entry.index.take();
+        entry.generation = entry.generation.wrapping_add(1);
+        self.free.push(id.slot);
+        index
+    }
+
+    /// Current index of a cell, or `None` if it was removed
+    pub fn index_of(&self, id: CellId) -> Option<usize> {
+        self.slots
+            .get(id.slot as usize)
+            .filter(|entry| entry.generation == id.generation)
+            .and_then(|entry| entry.index)
+    }
+
+    /// Record that a cell moved to `index`
+    pub fn set_index(&mut self, id: CellId, index: usize) {
+        if let Some(entry) = self.live_slot_mut(id) {
+            entry.index = Some(index);
+        }
+    }
+
+    /// Whether `id` refers to a cell that still exists
+    pub fn contains(&self, id: CellId) -> bool {
+        self.index_of(id).is_some()
+    }
+
+    /// Number of live cells
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn live_slot_mut(&mut self, id: CellId) -> Option<&mut Slot> {
+        self.slots
+            .get_mut(id.slot as usize)
+            .filter(|entry| entry.generation == id.generation && entry.index.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_resolve_to_current_index() {
+        let mut cells = CellRegistry::default();
+        let a = cells.insert(0);
+        let b = cells.insert(1);
+        assert_eq!(cells.index_of(a), Some(0));
+        assert_eq!(cells.index_of(b), Some(1));
+
+        // A cell inserted above shifts b down
+        cells.set_index(b, 2);
+        assert_eq!(cells.index_of(b), Some(2));
+        assert_eq!(cells.len(), 2);
+    }
+
+    #[test]
+    fn removed_ids_go_stale() {
+        let mut cells = CellRegistry::default();
+        let a = cells.insert(0);
+        assert_eq!(cells.remove(a), Some(0));
+        assert!(!cells.contains(a));
+        assert_eq!(cells.remove(a), None);
+
+        // The slot is reused, but the old id doesn't resolve to the new cell
+        let b = cells.insert(0);
+        assert_ne!(a, b);
+        assert_eq!(cells.index_of(a), None);
+        assert_eq!(cells.index_of(b), Some(0));
+
+        // Stale ids can't move or free the new cell
+        cells.set_index(a, 5);
+        assert_eq!(cells.remove(a), None);
+        assert_eq!(cells.index_of(b), Some(0));
+        assert_eq!(cells.len(), 1);
+    }
+
+    #[test]
+    fn bits_round_trip() {
+        let mut cells = CellRegistry::default();
+        let a = cells.insert(0);
+        cells.remove(a);
+        let b = cells.insert(0);
+        assert_eq!(CellId::from_bits(b.to_bits()), b);
+        assert_ne!(a.to_bits(), b.to_bits());
+    }
+}
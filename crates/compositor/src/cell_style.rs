@@ -0,0 +1,373 @@
+//! Rounded corners and drop shadows for cells
+//!
+//! Both are drawn by one pixel shader after all cells are rendered, using the
+//! signed distance to each cell's rounded rectangle:
+//!
+//! - Corners: the part of a cell outside its rounded rectangle is painted with
+//!   the background color.
+//! - Shadows: a dark halo fading out over `SHADOW_SIZE` pixels around the
+//!   cell. Cells are stacked without gaps, so each cell's shadow falls on its
+//!   neighbours' edges, which is what separates them.
+//!
+//! Only the corner squares and the shadow ring are drawn (passed as damage),
+//! so the shader doesn't run over cell content. Both effects are off unless
+//! `corner_radius` / `shadow_intensity` are set in the config.
+//!
+//! The winit backend draws in software and gets the same effects from
+//! `CellStyle::paint`, which evaluates the shader's formulas per pixel over
+//! the same corner squares and shadow ring.
+
+#[cfg(target_os = "linux")]
+use smithay::backend::renderer::gles::{
+    GlesError, GlesFrame, GlesPixelProgram, GlesRenderer, Uniform, UniformName, UniformType,
+};
+use smithay::utils::{Physical, Rectangle, Size};
+
+use crate::config::Config;
+
+/// How far shadows reach beyond a cell (pixels)
+pub const SHADOW_SIZE: i32 = 12;
+
+#[cfg(target_os = "linux")]
+const SHADER: &str = r#"
+precision mediump float;
+uniform float alpha;
+#if defined(DEBUG_FLAGS)
+uniform float tint;
+#endif
+uniform vec2 size;
+varying vec2 v_coords;
+
+uniform vec4 color;
+uniform float radius;
+// Distance from the quad's edges to the cell (0 for corners)
+uniform float spread;
+
+// Signed distance from `p` to a rounded box centered at the origin
+float rounded_box(vec2 p, vec2 half_size, float r) {
+    vec2 q = abs(p) - half_size + r;
+    return length(max(q, 0.0)) + min(max(q.x, q.y), 0.0) - r;
+}
+
+void main() {
+    vec2 p = v_coords * size - size * 0.5;
+    float d = rounded_box(p, size * 0.5 - spread, radius);
+    float a;
+    if (spread > 0.0) {
+        a = d > 0.0 ? 1.0 - smoothstep(0.0, spread, d) : 0.0;
+    } else {
+        a = clamp(d + 0.5, 0.0, 1.0);
+    }
+    gl_FragColor = color * a * alpha;
+#if defined(DEBUG_FLAGS)
+    if (tint == 1.0)
+        gl_FragColor = vec4(0.0, 0.2, 0.0, 0.2) + gl_FragColor * 0.8;
+#endif
+}
+"#;
+
+/// Corner radius and shadow strength from the config
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellStyle {
+    pub corner_radius: u32,
+    pub shadow_intensity: f32,
+    /// Background color corners are painted with (RGBA)
+    pub background: [f32; 4],
+}
+
+impl CellStyle {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            corner_radius: config.corner_radius,
+            shadow_intensity: config.shadow_intensity,
+            background: config.background_color,
+        }
+    }
+
+    /// Whether there's anything to draw
+    pub fn is_enabled(&self) -> bool {
+        self.corner_radius > 0 || self.shadow_intensity > 0.0
+    }
+
+    /// Corner radius used for `cell` (at most half its width or height)
+    fn radius(&self, cell: Rectangle<i32, Physical>) -> i32 {
+        (self.corner_radius as i32).min(cell.size.h / 2).min(cell.size.w / 2)
+    }
+
+    /// Round the corners of and cast shadows around `cells` in a software
+    /// framebuffer of 0x00RRGGBB pixels, painting corners with `background`
+    pub fn paint(&self, buffer: &mut [u32], width: u32, height: u32, cells: &[Rectangle<i32, Physical>], background: u32) {
+        let mut canvas = Canvas { buffer, width: width as i32, height: height as i32 };
+        for &cell in cells {
+            let radius = self.radius(cell);
+            if radius <= 0 {
+                continue;
+            }
+            for corner in corner_squares(cell.size, radius) {
+                canvas.each_pixel(cell.loc, corner, |x, y| {
+                    let coverage = (rounded_box(x, y, cell, radius) + 0.5).clamp(0.0, 1.0);
+                    (background, coverage)
+                });
+            }
+        }
+
+        if self.shadow_intensity <= 0.0 {
+            return;
+        }
+        for &cell in cells {
+            let radius = self.radius(cell);
+            let quad = Rectangle::new(
+                (cell.loc.x - SHADOW_SIZE, cell.loc.y - SHADOW_SIZE).into(),
+                (cell.size.w + 2 * SHADOW_SIZE, cell.size.h + 2 * SHADOW_SIZE).into(),
+            );
+            for band in shadow_ring(quad.size, SHADOW_SIZE, radius) {
+                canvas.each_pixel(quad.loc, band, |x, y| {
+                    let distance = rounded_box(x, y, cell, radius);
+                    let alpha = if distance > 0.0 { 1.0 - smoothstep(SHADOW_SIZE as f32, distance) } else { 0.0 };
+                    (0, alpha * self.shadow_intensity)
+                });
+            }
+        }
+    }
+}
+
+/// A software framebuffer being painted on
+struct Canvas<'a> {
+    buffer: &'a mut [u32],
+    width: i32,
+    height: i32,
+}
+
+impl Canvas<'_> {
+    /// Blend the color `paint` gives for each pixel center of `region`
+    /// (relative to `origin`) into the pixel, with the alpha it gives
+    fn each_pixel(
+        &mut self,
+        origin: smithay::utils::Point<i32, Physical>,
+        region: Rectangle<i32, Physical>,
+        paint: impl Fn(f32, f32) -> (u32, f32),
+    ) {
+        let top = (origin.y + region.loc.y).max(0);
+        let bottom = (origin.y + region.loc.y + region.size.h).min(self.height);
+        let left = (origin.x + region.loc.x).max(0);
+        let right = (origin.x + region.loc.x + region.size.w).min(self.width);
+        for y in top..bottom {
+            for x in left..right {
+                let (color, alpha) = paint(x as f32 + 0.5, y as f32 + 0.5);
+                if alpha > 0.0 {
+                    let pixel = &mut self.buffer[(y * self.width + x) as usize];
+                    *pixel = blend(*pixel, color, alpha);
+                }
+            }
+        }
+    }
+}
+
+/// `color` over `pixel` (both 0x00RRGGBB) with `alpha`
+fn blend(pixel: u32, color: u32, alpha: f32) -> u32 {
+    let alpha = alpha.min(1.0);
+    [16, 8, 0].iter().fold(0, |blended, shift| {
+        let under = ((pixel >> shift) & 0xFF) as f32;
+        let over = ((color >> shift) & 0xFF) as f32;
+        blended | (((over * alpha + under * (1.0 - alpha)).round() as u32) << shift)
+    })
+}
+
+/// Signed distance from (`x`, `y`) to `cell` with its corners rounded by
+/// `radius` (negative inside), as `rounded_box` in the shader
+fn rounded_box(x: f32, y: f32, cell: Rectangle<i32, Physical>, radius: i32) -> f32 {
+    let half_width = cell.size.w as f32 / 2.0;
+    let half_height = cell.size.h as f32 / 2.0;
+    let radius = radius as f32;
+    let qx = (x - cell.loc.x as f32 - half_width).abs() - half_width + radius;
+    let qy = (y - cell.loc.y as f32 - half_height).abs() - half_height + radius;
+    qx.max(0.0).hypot(qy.max(0.0)) + qx.max(qy).min(0.0) - radius
+}
+
+/// GLSL `smoothstep(0.0, edge, x)`
+fn smoothstep(edge: f32, x: f32) -> f32 {
+    let t = (x / edge).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Compiled shader plus the style it draws
+#[cfg(target_os = "linux")]
+pub struct CellStyleRenderer {
+    program: GlesPixelProgram,
+    style: CellStyle,
+}
+
+#[cfg(target_os = "linux")]
+impl CellStyleRenderer {
+    /// Compile the shader, or `None` if the style is disabled
+    pub fn new(renderer: &mut GlesRenderer, style: CellStyle) -> Result<Option<Self>, GlesError> {
+        if !style.is_enabled() {
+            return Ok(None);
+        }
+        let program = renderer.compile_custom_pixel_shader(
+            SHADER,
+            &[
+                UniformName::new("color", UniformType::_4f),
+                UniformName::new("radius", UniformType::_1f),
+                UniformName::new("spread", UniformType::_1f),
+            ],
+        )?;
+        Ok(Some(Self { program, style }))
+    }
+
+    /// Replace the style (the shader stays compiled)
+    pub fn set_style(&mut self, style: CellStyle) {
+        self.style = style;
+    }
+
+    /// Round the corners of and cast shadows around `cells` (render
+    /// coordinates), within the frame's `damage`
+    /// (shadows are translucent, so drawing one twice would darken it)
+    pub fn render(
+        &self,
+        frame: &mut GlesFrame<'_, '_>,
+        cells: &[Rectangle<i32, Physical>],
+        damage: Rectangle<i32, Physical>,
+    ) {
+        for &cell in cells {
+            let radius = self.style.radius(cell);
+            if radius > 0 {
+                let background = self.style.background;
+                let corners = clip(cell, &corner_squares(cell.size, radius), damage);
+            self.draw(frame, cell, &corners, background, radius, 0);
+            }
+        }
+
+        if self.style.shadow_intensity <= 0.0 {
+            return;
+        }
+        let shadow = [0.0, 0.0, 0.0, self.style.shadow_intensity];
+        for &cell in cells {
+            let radius = self.style.radius(cell);
+            let quad = Rectangle::new(
+                (cell.loc.x - SHADOW_SIZE, cell.loc.y - SHADOW_SIZE).into(),
+                (cell.size.w + 2 * SHADOW_SIZE, cell.size.h + 2 * SHADOW_SIZE).into(),
+            );
+            let ring = shadow_ring(quad.size, SHADOW_SIZE, radius);
+            let ring = clip(quad, &ring, damage);
+            self.draw(frame, quad, &ring, shadow, radius, SHADOW_SIZE);
+        }
+    }
+
+    fn draw(
+        &self,
+        frame: &mut GlesFrame<'_, '_>,
+        dest: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        color: [f32; 4],
+        radius: i32,
+        spread: i32,
+    ) {
+        if damage.is_empty() {
+            return;
+        }
+        let size = Size::from((dest.size.w, dest.size.h));
+        let result = frame.render_pixel_shader_to(
+            &self.program,
+            Rectangle::from_size(size).to_f64(),
+            dest,
+            size,
+            Some(damage),
+            1.0,
+            &[
+                Uniform::new("color", color),
+                Uniform::new("radius", radius as f32),
+                Uniform::new("spread", spread as f32),
+            ],
+        );
+        if let Err(e) = result {
+            tracing::warn!(error = ?e, "failed to draw cell style");
+        }
+    }
+}
+
+/// The parts of `regions` (relative to `dest`) inside the frame's `damage`
+#[cfg(target_os = "linux")]
+fn clip(
+    dest: Rectangle<i32, Physical>,
+    regions: &[Rectangle<i32, Physical>],
+    damage: Rectangle<i32, Physical>,
+) -> Vec<Rectangle<i32, Physical>> {
+    let Some(damage) = crate::render::local_damage(dest, damage) else {
+        return Vec::new();
+    };
+    regions.iter().filter_map(|region| region.intersection(damage)).collect()
+}
+
+/// The four `radius`-sized corner squares of a rectangle of `size`, in its
+/// own coordinates
+fn corner_squares(size: Size<i32, Physical>, radius: i32) -> [Rectangle<i32, Physical>; 4] {
+    let square = Size::from((radius, radius));
+    let right = size.w - radius;
+    let bottom = size.h - radius;
+    [
+        Rectangle::new((0, 0).into(), square),
+        Rectangle::new((right, 0).into(), square),
+        Rectangle::new((0, bottom).into(), square),
+        Rectangle::new((right, bottom).into(), square),
+    ]
+}
+
+/// The band of a shadow quad of `size` that lies outside the cell (plus the
+/// cell's corners, which are outside its rounded rectangle), in quad coordinates
+fn shadow_ring(size: Size<i32, Physical>, spread: i32, radius: i32) -> [Rectangle<i32, Physical>; 4] {
+    // Bands of short quads would overlap (and darken twice)
+    let edge = (spread + radius).min(size.w / 2).min(size.h / 2);
+    [
+        // Top and bottom bands span the full width
+        Rectangle::new((0, 0).into(), (size.w, edge).into()),
+        Rectangle::new((0, size.h - edge).into(), (size.w, edge).into()),
+        // Left and right bands between them
+        Rectangle::new((0, edge).into(), (edge, size.h - 2 * edge).into()),
+        Rectangle::new((size.w - edge, edge).into(), (edge, size.h - 2 * edge).into()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn style_is_off_by_default() {
+        assert!(!CellStyle::from_config(&Config::default()).is_enabled());
+    }
+
+    #[test]
+    fn shadow_ring_covers_the_edges_only() {
+        let ring = shadow_ring(Size::from((124, 74)), 12, 8);
+        let area: i32 = ring.iter().map(|r| r.size.w * r.size.h).sum();
+        // Everything but the cell's inner rectangle (inset by the radius)
+        assert_eq!(area, 124 * 74 - (124 - 40) * (74 - 40));
+        assert!(ring.iter().all(|r| r.size.w >= 0 && r.size.h >= 0));
+    }
+
+    #[test]
+    fn corner_squares_sit_in_the_corners() {
+        let corners = corner_squares(Size::from((100, 50)), 8);
+        assert_eq!(corners[3], Rectangle::new((92, 42).into(), (8, 8).into()));
+    }
+
+    #[test]
+    fn software_paint_rounds_corners_and_shades_edges() {
+        let style = CellStyle { corner_radius: 8, shadow_intensity: 0.5, background: [0.0; 4] };
+        let (width, height) = (60, 60);
+        let mut buffer = vec![0x00FF_FFFF; (width * height) as usize];
+        let cell = Rectangle::new((20, 20).into(), (20, 20).into());
+        style.paint(&mut buffer, width, height, &[cell], 0x0000_00FF);
+        let at = |x: u32, y: u32| buffer[(y * width + x) as usize];
+
+        // The corner pixel is outside the rounded rectangle (painted, then
+        // shaded like the GPU does), the middle inside
+        assert_eq!(at(20, 20) & 0x00FF_FF00, 0);
+        assert!(at(20, 20) > 0);
+        assert_eq!(at(30, 30), 0x00FF_FFFF);
+        // Just outside an edge is darkened, further out less, past the shadow not at all
+        assert!(at(30, 18) < at(30, 12));
+        assert_eq!(at(30, 2), 0x00FF_FFFF);
+    }
+}
@@ -0,0 +1,90 @@
+//! Custom cell titles
+//!
+//! Users name a cell with `termstack title <index> <text>` and programs name
+//! their own with `OSC 777 ; termstack-title ; <text> ST` (picked out of the
+//! output by the terminal crate). The name is shown instead of the command in
+//! title bars, `termstack ls`, the window switcher and the accessibility
+//! tree, and comes back with a session recovered after a crash. An empty
+//! name goes back to the command.
+
+use std::os::unix::net::UnixStream;
+
+use crate::state::{StackWindow, TermStack};
+use crate::terminal_manager::TerminalManager;
+
+/// Longest title kept, in characters
+pub const MAX_TITLE_CHARS: usize = 200;
+
+/// A title as shown: one line without control characters, at most
+/// `MAX_TITLE_CHARS` long, `None` if nothing is left
+fn clean_title(title: &str) -> Option<String> {
+    let title: String = title
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .take(MAX_TITLE_CHARS)
+        .collect();
+    let title = title.trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+impl TermStack {
+    /// Name the cell at `index`, or clear its name with an empty `title`.
+    /// Returns false if there is no such cell.
+    pub fn set_cell_title(&mut self, index: usize, title: &str, terminal_manager: &mut TerminalManager) -> bool {
+        let title = clean_title(title);
+        let Some(node) = self.layout_nodes.get_mut(index) else {
+            return false;
+        };
+        match &mut node.cell {
+            StackWindow::Terminal(id) => match terminal_manager.get_mut(*id) {
+                Some(terminal) => terminal.custom_title = title.clone(),
+                None => return false,
+            },
+            StackWindow::External(entry) => entry.custom_title = title.clone(),
+        }
+        tracing::info!(index, ?title, "cell title set");
+        self.redraw_requested = true;
+        true
+    }
+}
+
+/// Apply titles programs set with the title escape sequence since the last frame
+pub fn apply_title_escapes(compositor: &mut TermStack, terminal_manager: &mut TerminalManager) {
+    for (id, terminal) in terminal_manager.iter_mut() {
+        let Some(title) = terminal.terminal.take_title_request() else {
+            continue;
+        };
+        tracing::debug!(id = id.0, %title, "title escape sequence");
+        terminal.custom_title = clean_title(&title);
+        compositor.redraw_requested = true;
+    }
+}
+
+/// Answer `termstack title` requests with `Result<(), String>`
+pub fn handle_ipc_title_requests(compositor: &mut TermStack, terminal_manager: &mut TerminalManager) {
+    let requests: Vec<(usize, String, UnixStream)> = std::mem::take(&mut compositor.pending_title_requests);
+    for (index, title, stream) in requests {
+        let result = if compositor.set_cell_title(index, &title, terminal_manager) {
+            Ok(())
+        } else {
+            Err(format!("no cell {index}"))
+        };
+        if let Err(e) = crate::ipc::send_json_response(stream, &result) {
+            tracing::warn!(error = ?e, "Failed to send title response");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn titles_are_one_trimmed_line() {
+        assert_eq!(clean_title("  prod logs \n").as_deref(), Some("prod logs"));
+        assert_eq!(clean_title("db\tshell").as_deref(), Some("db shell"));
+        assert_eq!(clean_title(" \x1b "), None);
+        assert_eq!(clean_title(""), None);
+        assert_eq!(clean_title(&"x".repeat(500)).map(|t| t.len()), Some(MAX_TITLE_CHARS));
+    }
+}
@@ -0,0 +1,197 @@
+//! Child process reaping
+//!
+//! The compositor owns several kinds of child processes: terminal shells,
+//! command terminals (including `termstack gui` launches, which run inside a
+//! command terminal), and xwayland-satellite. Instead of each owner polling
+//! `try_wait` on its own schedule, a SIGCHLD handler wakes the event loop and
+//! `reap_children()` collects every exit status in one place, handing it back
+//! to the owner so exit codes are available for later use.
+//!
+//! Children are reaped by PID with `waitpid(pid, WNOHANG)` rather than
+//! `waitpid(-1)`, so processes spawned by other code (Smithay's XWayland,
+//! one-shot helpers run via `Command::status`) are left to their owners.
+
+use std::io::Read;
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+
+use rustix::io::Errno;
+use rustix::process::{Pid, WaitOptions};
+use smithay::reexports::calloop::generic::Generic;
+use smithay::reexports::calloop::{Interest, LoopHandle, Mode, PostAction};
+
+use crate::state::TermStack;
+use crate::terminal_manager::{TerminalId, TerminalManager};
+
+/// Which owner a reaped child belonged to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChildKind {
+    /// Shell or command process of a terminal
+    Terminal(TerminalId),
+    /// The xwayland-satellite X11 window manager
+    XWaylandSatellite,
+}
+
+/// A child process that has exited and been reaped
+#[derive(Debug, Clone, Copy)]
+pub struct ChildExit {
+    pub pid: u32,
+    pub kind: ChildKind,
+    pub status: ExitStatus,
+}
+
+/// SIGCHLD bookkeeping shared between the signal source and the main loop
+#[derive(Debug)]
+pub struct ChildReaper {
+    /// Set when SIGCHLD has been received and children have not been reaped since
+    sigchld_pending: bool,
+}
+
+impl Default for ChildReaper {
+    fn default() -> Self {
+        // Start pending so children that exit before the signal source is
+        // registered are still collected on the first frame
+        Self { sigchld_pending: true }
+    }
+}
+
+impl ChildReaper {
+    /// Note that SIGCHLD was received
+    pub fn notify_sigchld(&mut self) {
+        self.sigchld_pending = true;
+    }
+
+    /// Consume the pending flag, returning whether children should be reaped
+    pub fn take_pending(&mut self) -> bool {
+        std::mem::take(&mut self.sigchld_pending)
+    }
+}
+
+/// Register a calloop source that wakes the event loop on SIGCHLD.
+///
+/// The signal handler only writes to a self-pipe; reaping itself happens in
+/// `reap_children()` on the main thread.
+pub fn setup_sigchld_source(calloop_handle: &LoopHandle<'static, TermStack>) -> anyhow::Result<()> {
+    let (read, write) = UnixStream::pair()?;
+    read.set_nonblocking(true)?;
+    write.set_nonblocking(true)?;
+
+    signal_hook::low_level::pipe::register(signal_hook::consts::SIGCHLD, write)?;
+
+    calloop_handle
+        .insert_source(
+            Generic::new(read, Interest::READ, Mode::Level),
+            |_, stream, state| {
+                // Drain the pipe; several signals may have coalesced into one wakeup
+                let mut buf = [0u8; 64];
+                let mut stream: &UnixStream = stream.as_ref();
+                while matches!(stream.read(&mut buf), Ok(n) if n > 0) {}
+                state.child_reaper.notify_sigchld();
+                state.redraw_requested = true;
+                Ok(PostAction::Continue)
+            },
+        )
+        .map_err(|e| anyhow::anyhow!("Failed to insert SIGCHLD source: {e}"))?;
+
+    tracing::info!("SIGCHLD reaper registered");
+    Ok(())
+}
+
+/// Reap every exited child the compositor owns and hand back its exit status.
+///
+/// Terminal exit statuses are recorded on the terminal (picked up by the
+/// regular dead-terminal cleanup), and xwayland-satellite's status is stored
+/// on its monitor for the crash-recovery logic.
+pub fn reap_children(
+    compositor: &mut TermStack,
+    terminal_manager: &mut TerminalManager,
+) -> Vec<ChildExit> {
+    if !compositor.child_reaper.take_pending() {
+        return Vec::new();
+    }
+
+    let mut exits = Vec::new();
+
+    for (id, terminal) in terminal_manager.iter_mut() {
+        if terminal.exit_status().is_some() {
+            continue;
+        }
+        let pid = terminal.pid();
+        if let Some(status) = try_reap(pid) {
+            terminal.record_exit(status);
+            exits.push(ChildExit { pid, kind: ChildKind::Terminal(*id), status });
+        }
+    }
+
+    if let Some(monitor) = compositor.xwayland.satellite.as_mut() {
+        if monitor.exit_status.is_none() {
+            let pid = monitor.child.id();
+            if let Some(status) = try_reap(pid) {
+                monitor.exit_status = Some(status);
+                exits.push(ChildExit { pid, kind: ChildKind::XWaylandSatellite, status });
+            }
+        }
+    }
+
+    for exit in &exits {
+        tracing::debug!(pid = exit.pid, kind = ?exit.kind, status = ?exit.status, "reaped child");
+    }
+
+    exits
+}
+
+/// Reap a single child without blocking.
+///
+/// Returns `None` if the child is still running, or if it was already reaped
+/// elsewhere (its owner then has the status via `Child::try_wait`).
+fn try_reap(pid: u32) -> Option<ExitStatus> {
+    // A zero PID would turn this into waitpid(-1) and steal other owners' children
+    let pid_arg = Pid::from_raw(pid as i32)?;
+    match rustix::process::waitpid(Some(pid_arg), WaitOptions::NOHANG) {
+        Ok(Some(status)) => Some(ExitStatus::from_raw(status.as_raw() as i32)),
+        Ok(None) => None,
+        Err(Errno::CHILD) => None,
+        Err(e) => {
+            tracing::debug!(pid, ?e, "waitpid failed");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reaper_starts_pending() {
+        let mut reaper = ChildReaper::default();
+        assert!(reaper.take_pending());
+        assert!(!reaper.take_pending());
+        reaper.notify_sigchld();
+        assert!(reaper.take_pending());
+    }
+
+    #[test]
+    fn try_reap_collects_exit_code() {
+        let mut child = std::process::Command::new("sh")
+            .args(["-c", "exit 3"])
+            .spawn()
+            .expect("spawn sh");
+        let pid = child.id();
+
+        let start = std::time::Instant::now();
+        let status = loop {
+            if let Some(status) = try_reap(pid) {
+                break status;
+            }
+            assert!(start.elapsed() < std::time::Duration::from_secs(5), "child never exited");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        };
+        assert_eq!(status.code(), Some(3));
+
+        // Already reaped: neither we nor the owner can wait on it again
+        assert!(try_reap(pid).is_none());
+        assert!(child.try_wait().is_err());
+    }
+}
@@ -0,0 +1,163 @@
+//! Color tags for grouping cells
+//!
+//! A cell can carry one of a few colors, drawn as a thin stripe along the
+//! right edge of the cell (the left edge has the focus and running
+//! indicators). Super+G cycles the focused cell's tag, right-clicking a
+//! terminal's title bar cycles that cell's, and `termstack tag <index>
+//! [color]` sets one over IPC. The window switcher and `termstack ls --tag`
+//! can show only the cells with a given tag.
+
+use std::os::unix::net::UnixStream;
+
+use serde::{Deserialize, Serialize};
+use smithay::backend::renderer::gles::GlesFrame;
+use smithay::backend::renderer::{Color32F, Frame};
+use smithay::utils::{Physical, Rectangle};
+
+use crate::state::TermStack;
+
+/// Width of the stripe drawn along a tagged cell's right edge (pixels)
+pub const TAG_STRIPE_WIDTH: i32 = 4;
+
+/// Color a cell is tagged with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorTag {
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+}
+
+impl ColorTag {
+    /// All tags, in cycling order (the window switcher's number keys follow it)
+    pub const ALL: [ColorTag; 6] = [
+        ColorTag::Red,
+        ColorTag::Orange,
+        ColorTag::Yellow,
+        ColorTag::Green,
+        ColorTag::Blue,
+        ColorTag::Purple,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorTag::Red => "red",
+            ColorTag::Orange => "orange",
+            ColorTag::Yellow => "yellow",
+            ColorTag::Green => "green",
+            ColorTag::Blue => "blue",
+            ColorTag::Purple => "purple",
+        }
+    }
+
+    /// Tag called `name` (as in `name()`), case-insensitive
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|tag| tag.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Stripe color
+    pub fn color(self) -> Color32F {
+        match self {
+            ColorTag::Red => Color32F::new(0.90, 0.30, 0.30, 1.0),
+            ColorTag::Orange => Color32F::new(0.95, 0.60, 0.20, 1.0),
+            ColorTag::Yellow => Color32F::new(0.95, 0.85, 0.30, 1.0),
+            ColorTag::Green => Color32F::new(0.40, 0.80, 0.40, 1.0),
+            ColorTag::Blue => Color32F::new(0.35, 0.50, 0.95, 1.0),
+            ColorTag::Purple => Color32F::new(0.70, 0.45, 0.90, 1.0),
+        }
+    }
+
+    /// The tag after `tag` when cycling; no tag follows the last one
+    pub fn cycle(tag: Option<ColorTag>) -> Option<ColorTag> {
+        match tag {
+            None => Some(Self::ALL[0]),
+            Some(tag) => {
+                let index = Self::ALL.iter().position(|&t| t == tag).unwrap_or(0);
+                Self::ALL.get(index + 1).copied()
+            }
+        }
+    }
+}
+
+impl TermStack {
+    /// Tag the cell at `index` (`None` removes its tag).
+    /// Returns false if there is no such cell.
+    pub fn set_cell_tag(&mut self, index: usize, tag: Option<ColorTag>) -> bool {
+        let Some(node) = self.layout_nodes.get_mut(index) else {
+            return false;
+        };
+        node.tag = tag;
+        tracing::info!(index, ?tag, "cell color tag set");
+        self.redraw_requested = true;
+        true
+    }
+
+    /// Give the cell at `index` the next tag (see `ColorTag::cycle`)
+    pub fn cycle_cell_tag(&mut self, index: usize) {
+        if let Some(node) = self.layout_nodes.get(index) {
+            let tag = ColorTag::cycle(node.tag);
+            self.set_cell_tag(index, tag);
+        }
+    }
+}
+
+/// Draw the tag stripes of `cells` (cell bounds in render coordinates),
+/// within the frame's `damage`
+pub fn draw_tag_stripes(
+    frame: &mut GlesFrame<'_, '_>,
+    cells: &[(Rectangle<i32, Physical>, ColorTag)],
+    damage: Rectangle<i32, Physical>,
+) {
+    for &(cell, tag) in cells {
+        let stripe = Rectangle::new(
+            (cell.loc.x + cell.size.w - TAG_STRIPE_WIDTH, cell.loc.y).into(),
+            (TAG_STRIPE_WIDTH, cell.size.h).into(),
+        );
+        frame.clear(tag.color(), stripe.intersection(damage).as_slice()).ok();
+    }
+}
+
+/// Answer `termstack tag` requests with `Result<(), String>`
+pub fn handle_ipc_tag_requests(compositor: &mut TermStack) {
+    let requests: Vec<(usize, Option<ColorTag>, UnixStream)> = std::mem::take(&mut compositor.pending_tag_requests);
+    for (index, tag, stream) in requests {
+        let result = if compositor.set_cell_tag(index, tag) {
+            Ok(())
+        } else {
+            Err(format!("no cell {index}"))
+        };
+        if let Err(e) = crate::ipc::send_json_response(stream, &result) {
+            tracing::warn!(error = ?e, "Failed to send tag response");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycling_goes_through_all_tags_and_back_to_none() {
+        let mut tag = None;
+        let mut seen = Vec::new();
+        for _ in 0..ColorTag::ALL.len() {
+            tag = ColorTag::cycle(tag);
+            seen.extend(tag);
+        }
+        assert_eq!(seen, ColorTag::ALL);
+        assert_eq!(ColorTag::cycle(tag), None);
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for tag in ColorTag::ALL {
+            assert_eq!(ColorTag::from_name(tag.name()), Some(tag));
+            assert_eq!(serde_json::to_string(&tag).unwrap(), format!("\"{}\"", tag.name()));
+        }
+        assert_eq!(ColorTag::from_name("Blue"), Some(ColorTag::Blue));
+        assert_eq!(ColorTag::from_name("teal"), None);
+    }
+}
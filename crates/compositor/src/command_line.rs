@@ -0,0 +1,419 @@
+//! Shell command lines, as far as spawning needs to understand them
+//!
+//! Commands still run verbatim with `$SHELL -c`, in the directory the shell
+//! was in when Enter was pressed. [`CommandLine`] splits one into pipelines
+//! and their stages and picks out the redirections, so spawning can tell:
+//!
+//! - whether the output ends in an interactive pager (`git log | less`),
+//!   which then keeps the user's pager settings instead of `PAGER=cat`
+//! - which files the output went to (`make > build.log`), so a cell that
+//!   shows nothing can say where its output is
+//!
+//! The lexer knows quotes, backslash escapes, `$(...)` and fish's `(...)`
+//! substitutions, and the redirection syntax POSIX shells and fish share.
+//! Anything else ends up in a word, which is harmless for both uses.
+
+use std::path::{Path, PathBuf};
+
+use crate::home::expand_home;
+
+/// Programs that page their input interactively
+const PAGERS: &[&str] = &["less", "more", "most", "pg", "lv"];
+
+/// Words that run the program after them (`env LESS= less`)
+const PREFIXES: &[&str] = &["command", "exec", "env", "nohup", "builtin"];
+
+/// Which file descriptor a redirection replaces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdin,
+    Stdout,
+    Stderr,
+    /// Stdout and stderr (`&>`, `>&`)
+    Both,
+    Other(u32),
+}
+
+/// A redirection to or from a file (`> log`, `2>> err`, `&> all`, `< in`)
+///
+/// Duplications like `2>&1` name no file and aren't recorded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    pub stream: Stream,
+    pub target: String,
+    pub append: bool,
+}
+
+/// One program of a pipeline: its words and redirections
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stage {
+    pub words: Vec<String>,
+    pub redirects: Vec<Redirect>,
+}
+
+impl Stage {
+    /// Name of the program the stage runs, past variable assignments and
+    /// prefixes like `command`, without its directory
+    pub fn program(&self) -> Option<&str> {
+        let mut words = self.words.iter().map(String::as_str).filter(|word| !is_assignment(word));
+        let mut program = words.next()?;
+        while PREFIXES.contains(&program) {
+            program = words.next()?;
+        }
+        program.rsplit('/').next()
+    }
+}
+
+/// Programs joined with `|`; output of each goes into the next
+pub type Pipeline = Vec<Stage>;
+
+/// A parsed command line: pipelines separated by `;`, `&&`, `||` or `&`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandLine {
+    pub pipelines: Vec<Pipeline>,
+}
+
+impl CommandLine {
+    /// Split `line` into pipelines, stages, words and redirections
+    pub fn parse(line: &str) -> Self {
+        let mut parser = Parser::default();
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\'' => {
+                    parser.started = true;
+                    for c in chars.by_ref() {
+                        if c == '\'' {
+                            break;
+                        }
+                        parser.word.push(c);
+                    }
+                }
+                '"' => {
+                    parser.started = true;
+                    while let Some(c) = chars.next() {
+                        match c {
+                            '"' => break,
+                            '\\' if matches!(chars.peek(), Some('"' | '\\' | '$' | '`')) => {
+                                parser.word.extend(chars.next());
+                            }
+                            c => parser.word.push(c),
+                        }
+                    }
+                }
+                '\\' => {
+                    parser.started = true;
+                    // A backslash before a newline continues the line
+                    if let Some(c) = chars.next().filter(|c| *c != '\n') {
+                        parser.word.push(c);
+                    }
+                }
+                '$' if chars.next_if_eq(&'(').is_some() => {
+                    parser.word.push('$');
+                    parser.substitution(&mut chars);
+                }
+                '(' => parser.substitution(&mut chars),
+                '#' if !parser.started => {
+                    // A comment runs to the end of the line
+                    for c in chars.by_ref() {
+                        if c == '\n' {
+                            parser.end_pipeline();
+                            break;
+                        }
+                    }
+                }
+                ';' | '\n' => parser.end_pipeline(),
+                '|' => match chars.peek() {
+                    Some('|') => {
+                        chars.next();
+                        parser.end_pipeline();
+                    }
+                    Some('&') => {
+                        // bash's `|&` pipes stderr too
+                        chars.next();
+                        parser.end_stage();
+                    }
+                    _ => parser.end_stage(),
+                },
+                '&' => match chars.peek() {
+                    Some('>') => {
+                        chars.next();
+                        parser.end_word();
+                        let append = chars.next_if_eq(&'>').is_some();
+                        parser.pending = Some((Stream::Both, append));
+                    }
+                    Some('&') => {
+                        chars.next();
+                        parser.end_pipeline();
+                    }
+                    Some('|') => {
+                        // fish's `&|` pipes stderr too
+                        chars.next();
+                        parser.end_stage();
+                    }
+                    _ => parser.end_pipeline(),
+                },
+                '>' | '<' => {
+                    // Digits right before the operator name the descriptor
+                    let fd = (!parser.word.is_empty() && parser.word.chars().all(|c| c.is_ascii_digit()))
+                        .then(|| std::mem::take(&mut parser.word))
+                        .and_then(|digits| digits.parse::<u32>().ok());
+                    if fd.is_some() {
+                        parser.started = false;
+                    }
+                    parser.end_word();
+
+                    let mut stream = match fd {
+                        None if c == '<' => Stream::Stdin,
+                        None => Stream::Stdout,
+                        Some(0) => Stream::Stdin,
+                        Some(1) => Stream::Stdout,
+                        Some(2) => Stream::Stderr,
+                        Some(fd) => Stream::Other(fd),
+                    };
+                    let mut append = false;
+                    if c == '<' {
+                        // Here-documents and here-strings
+                        while chars.next_if_eq(&'<').is_some() {}
+                    } else if chars.next_if_eq(&'>').is_some() {
+                        append = true;
+                    } else {
+                        // bash's `>|` and fish's `>?` only change clobbering
+                        chars.next_if(|c| matches!(c, '|' | '?'));
+                    }
+                    if chars.next_if_eq(&'&').is_some() {
+                        // `2>&1` and `>&-` duplicate or close a descriptor
+                        if chars.peek().is_some_and(|c| c.is_ascii_digit() || *c == '-') {
+                            while chars.next_if(|c| c.is_ascii_digit() || *c == '-').is_some() {}
+                            continue;
+                        }
+                        // `>& file` is `&> file`
+                        stream = Stream::Both;
+                    }
+                    parser.pending = Some((stream, append));
+                }
+                c if c.is_whitespace() => parser.end_word(),
+                c => {
+                    parser.started = true;
+                    parser.word.push(c);
+                }
+            }
+        }
+        parser.end_pipeline();
+        parser.line
+    }
+
+    /// Whether the last pipeline ends in an interactive pager the user piped
+    /// into, as in `git log | less`
+    pub fn pipes_into_pager(&self) -> bool {
+        self.pipelines.last().is_some_and(|pipeline| {
+            pipeline.len() > 1
+                && pipeline.last().and_then(Stage::program).is_some_and(|program| PAGERS.contains(&program))
+        })
+    }
+
+    /// Files standard output is redirected to, with relative paths resolved
+    /// against `cwd` and `~` against `home`. Devices like `/dev/null` aren't
+    /// files anyone would look for output in and are left out, and so are
+    /// `~user/...` targets (and `~` without a `home`), which the shell
+    /// resolves from the password database.
+    pub fn output_files(&self, cwd: &Path, home: Option<&Path>) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        let redirects = self.pipelines.iter().flatten().flat_map(|stage| &stage.redirects);
+        for redirect in redirects.filter(|redirect| matches!(redirect.stream, Stream::Stdout | Stream::Both)) {
+            if redirect.target.starts_with("/dev/") {
+                continue;
+            }
+            let Some(path) = resolve(&redirect.target, cwd, home) else { continue };
+            if !files.contains(&path) {
+                files.push(path);
+            }
+        }
+        files
+    }
+}
+
+/// Collects words into stages and stages into pipelines as the line is read
+#[derive(Default)]
+struct Parser {
+    line: CommandLine,
+    pipeline: Pipeline,
+    stage: Stage,
+    word: String,
+    /// Whether a word is being read, even an empty one (`''`)
+    started: bool,
+    /// Redirection whose target is the next word
+    pending: Option<(Stream, bool)>,
+}
+
+impl Parser {
+    fn end_word(&mut self) {
+        if !self.started {
+            return;
+        }
+        self.started = false;
+        let word = std::mem::take(&mut self.word);
+        match self.pending.take() {
+            Some((stream, append)) => self.stage.redirects.push(Redirect { stream, target: word, append }),
+            None => self.stage.words.push(word),
+        }
+    }
+
+    fn end_stage(&mut self) {
+        self.end_word();
+        self.pending = None;
+        let stage = std::mem::take(&mut self.stage);
+        if !stage.words.is_empty() || !stage.redirects.is_empty() {
+            self.pipeline.push(stage);
+        }
+    }
+
+    fn end_pipeline(&mut self) {
+        self.end_stage();
+        let pipeline = std::mem::take(&mut self.pipeline);
+        if !pipeline.is_empty() {
+            self.line.pipelines.push(pipeline);
+        }
+    }
+
+    /// Read a parenthesized substitution into the current word, as written
+    fn substitution(&mut self, chars: &mut std::iter::Peekable<std::str::Chars<'_>>) {
+        self.started = true;
+        self.word.push('(');
+        let mut depth = 1;
+        let mut quote = None;
+        while let Some(c) = chars.next() {
+            self.word.push(c);
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some('"'), '\\') => self.word.extend(chars.next()),
+                (Some(_), _) => {}
+                (None, '\'' | '"') => quote = Some(c),
+                (None, '\\') => self.word.extend(chars.next()),
+                (None, '(') => depth += 1,
+                (None, ')') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                (None, _) => {}
+            }
+        }
+    }
+}
+
+/// Whether `word` sets a variable (`LESS=-R`) rather than naming a program
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// `target` as an absolute path, if it doesn't start with a `~` that
+/// `expand_home` leaves as is
+fn resolve(target: &str, cwd: &Path, home: Option<&Path>) -> Option<PathBuf> {
+    let path = expand_home(target, home);
+    if path.to_string_lossy().starts_with('~') {
+        return None;
+    }
+    // Joining an absolute path replaces `cwd`; collecting drops `.`
+    // components (`./build.log`)
+    Some(cwd.join(path).components().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn programs(line: &str) -> Vec<Vec<String>> {
+        CommandLine::parse(line)
+            .pipelines
+            .iter()
+            .map(|pipeline| pipeline.iter().map(|stage| stage.program().unwrap_or_default().to_string()).collect())
+            .collect()
+    }
+
+    fn redirects(line: &str) -> Vec<(Stream, String, bool)> {
+        CommandLine::parse(line)
+            .pipelines
+            .into_iter()
+            .flatten()
+            .flat_map(|stage| stage.redirects)
+            .map(|redirect| (redirect.stream, redirect.target, redirect.append))
+            .collect()
+    }
+
+    #[test]
+    fn pipelines_and_lists() {
+        assert_eq!(programs("make 2>&1 | tee log"), vec![vec!["make", "tee"]]);
+        assert_eq!(programs("cd src && make; git status || true &"), vec![
+            vec!["cd"],
+            vec!["make"],
+            vec!["git"],
+            vec!["true"],
+        ]);
+        assert_eq!(programs("make |& less"), vec![vec!["make", "less"]]);
+        assert_eq!(programs("make &| less"), vec![vec!["make", "less"]]);
+        assert_eq!(programs("LESS=-R command /usr/bin/less -S x"), vec![vec!["less"]]);
+        assert!(programs("# only a comment").is_empty());
+    }
+
+    #[test]
+    fn quotes_and_substitutions_stay_in_words() {
+        let parsed = CommandLine::parse(r#"echo 'a | b' "c > d" e\|f $(ls | wc -l) (pwd; echo ")") ''"#);
+        assert_eq!(parsed.pipelines.len(), 1);
+        let stage = &parsed.pipelines[0][0];
+        assert_eq!(stage.words, ["echo", "a | b", "c > d", "e|f", "$(ls | wc -l)", "(pwd; echo \")\")", ""]);
+        assert!(stage.redirects.is_empty());
+    }
+
+    #[test]
+    fn redirections() {
+        assert_eq!(redirects("cmd > out"), vec![(Stream::Stdout, "out".into(), false)]);
+        assert_eq!(redirects("cmd>>out 2>err"), vec![
+            (Stream::Stdout, "out".into(), true),
+            (Stream::Stderr, "err".into(), false),
+        ]);
+        assert_eq!(redirects("cmd &> all"), vec![(Stream::Both, "all".into(), false)]);
+        assert_eq!(redirects("cmd >& all"), vec![(Stream::Both, "all".into(), false)]);
+        assert_eq!(redirects("cmd >| out 1>? out2"), vec![
+            (Stream::Stdout, "out".into(), false),
+            (Stream::Stdout, "out2".into(), false),
+        ]);
+        assert_eq!(redirects("sort < in 3> x"), vec![
+            (Stream::Stdin, "in".into(), false),
+            (Stream::Other(3), "x".into(), false),
+        ]);
+        assert_eq!(redirects("make 2>&1 >&2 3>&-"), vec![]);
+        assert_eq!(redirects("echo '>' file2>x"), vec![(Stream::Stdout, "x".into(), false)]);
+    }
+
+    #[test]
+    fn pipes_into_pager() {
+        assert!(CommandLine::parse("git log | less").pipes_into_pager());
+        assert!(CommandLine::parse("make 2>&1 | LESS=-S less").pipes_into_pager());
+        assert!(CommandLine::parse("cd repo; git log |& more").pipes_into_pager());
+        assert!(!CommandLine::parse("less README").pipes_into_pager());
+        assert!(!CommandLine::parse("git log | less; echo done").pipes_into_pager());
+        assert!(!CommandLine::parse("git log | grep fix").pipes_into_pager());
+        assert!(!CommandLine::parse("echo '| less'").pipes_into_pager());
+    }
+
+    #[test]
+    fn output_files_resolve_against_cwd() {
+        let cwd = Path::new("/home/me/src");
+        let home = Some(Path::new("/home/me"));
+        let files = |line: &str| CommandLine::parse(line).output_files(cwd, home);
+
+        assert_eq!(files("make > build.log"), vec![PathBuf::from("/home/me/src/build.log")]);
+        assert_eq!(files("make >> ./out/build.log 2> err"), vec![PathBuf::from("/home/me/src/out/build.log")]);
+        assert_eq!(files("cmd &> ~/all.log"), vec![PathBuf::from("/home/me/all.log")]);
+        assert_eq!(files("a > /tmp/x; b > /tmp/x"), vec![PathBuf::from("/tmp/x")]);
+        assert_eq!(files("cmd > /dev/null"), Vec::<PathBuf>::new());
+        assert_eq!(files("cmd > ~root/x.log"), Vec::<PathBuf>::new());
+        assert_eq!(CommandLine::parse("cmd > ~/x.log").output_files(cwd, None), Vec::<PathBuf>::new());
+        assert_eq!(files("make 2>&1 | tee log"), Vec::<PathBuf>::new());
+    }
+}
@@ -0,0 +1,143 @@
+//! Named command templates
+//!
+//! The `[commands]` config table maps names to command lines with
+//! placeholders, so long commands can be started as `termstack tpl NAME ARGS`:
+//!
+//! ```toml
+//! [commands]
+//! deploy = "kubectl --context {1} apply -f k8s/"
+//! logs = "journalctl -fu {1:nginx} {args}"
+//! ```
+//!
+//! - `{1}`, `{2}`, ...: positional arguments
+//! - `{1:nginx}`: positional argument with a default
+//! - `{args}`: all arguments not consumed by a positional placeholder
+//! - `{{` and `}}`: literal braces
+//!
+//! Arguments are shell-quoted where needed, so they stay single words.
+
+/// Errors expanding a command template
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum TemplateError {
+    #[error("missing argument {{{0}}}")]
+    MissingArgument(usize),
+
+    #[error("unclosed '{{' in template")]
+    Unclosed,
+
+    #[error("unknown placeholder {{{0}}} (expected a number or 'args')")]
+    UnknownPlaceholder(String),
+
+    #[error("too many arguments: {given} given, template takes {used}")]
+    TooManyArguments { given: usize, used: usize },
+}
+
+/// Expand `template` with `args`
+pub fn expand(template: &str, args: &[String]) -> Result<String, TemplateError> {
+    let mut out = String::with_capacity(template.len());
+    let mut highest = 0;
+    // `{args}` is filled in after all positionals are known
+    let mut rest_marks = Vec::new();
+
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(TemplateError::Unclosed),
+                    }
+                }
+                if name == "args" {
+                    rest_marks.push(out.len());
+                    continue;
+                }
+                let (index, default) = match name.split_once(':') {
+                    Some((index, default)) => (index, Some(default)),
+                    None => (name.as_str(), None),
+                };
+                let index: usize = index
+                    .parse()
+                    .ok()
+                    .filter(|&i| i > 0)
+                    .ok_or_else(|| TemplateError::UnknownPlaceholder(name.clone()))?;
+                highest = highest.max(index);
+                match (args.get(index - 1), default) {
+                    (Some(arg), _) => out.push_str(&shell_quote(arg)),
+                    (None, Some(default)) => out.push_str(default),
+                    (None, None) => return Err(TemplateError::MissingArgument(index)),
+                }
+            }
+            c => out.push(c),
+        }
+    }
+
+    let rest = args.get(highest..).unwrap_or_default();
+    if rest_marks.is_empty() {
+        if !rest.is_empty() {
+            return Err(TemplateError::TooManyArguments { given: args.len(), used: highest });
+        }
+        return Ok(out);
+    }
+    let rest = rest.iter().map(|arg| shell_quote(arg)).collect::<Vec<_>>().join(" ");
+    for mark in rest_marks.into_iter().rev() {
+        out.insert_str(mark, &rest);
+    }
+    Ok(out.trim_end().to_string())
+}
+
+/// `arg` as a single shell word (single-quoted unless it's plainly safe)
+pub fn shell_quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_-./=:,@+%".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn positionals_and_defaults() {
+        assert_eq!(expand("journalctl -fu {1:nginx}", &[]).unwrap(), "journalctl -fu nginx");
+        assert_eq!(expand("journalctl -fu {1:nginx}", &args(&["sshd"])).unwrap(), "journalctl -fu sshd");
+        assert_eq!(expand("cp {2} {1}", &args(&["a", "b"])).unwrap(), "cp b a");
+        assert_eq!(expand("deploy {1}", &[]), Err(TemplateError::MissingArgument(1)));
+        assert_eq!(
+            expand("deploy {1}", &args(&["a", "b"])),
+            Err(TemplateError::TooManyArguments { given: 2, used: 1 })
+        );
+    }
+
+    #[test]
+    fn rest_arguments_follow_the_positionals() {
+        assert_eq!(expand("rg {1} {args}", &args(&["foo", "src", "tests"])).unwrap(), "rg foo src tests");
+        assert_eq!(expand("make {args}", &[]).unwrap(), "make");
+    }
+
+    #[test]
+    fn arguments_are_quoted_and_braces_escaped() {
+        assert_eq!(expand("echo {1}", &args(&["it's here"])).unwrap(), r"echo 'it'\''s here'");
+        assert_eq!(expand("awk '{{print}}' {1}", &args(&["f.txt"])).unwrap(), "awk '{print}' f.txt");
+        assert_eq!(expand("echo {name}", &[]), Err(TemplateError::UnknownPlaceholder("name".into())));
+        assert_eq!(expand("echo {1", &[]), Err(TemplateError::Unclosed));
+    }
+}
@@ -4,7 +4,8 @@
 //! and a single dispatch function. Both Linux and macOS backends parse their
 //! native key events into `CompositorAction` and call `apply_compositor_action`.
 
-use crate::state::TermStack;
+use crate::input_handler::ColumnRequest;
+use crate::state::{ColumnSide, TermStack};
 
 /// Scroll amount per key press (pixels)
 pub const SCROLL_STEP: f64 = 50.0;
@@ -22,10 +23,72 @@ pub enum CompositorAction {
     ScrollToBottom,
     PageDown,
     PageUp,
+    ScrollbackPageDown,
+    ScrollbackPageUp,
     Copy,
     Paste,
     FontSizeUp,
     FontSizeDown,
+    ToggleHud,
+    ToggleHelp,
+    ToggleDecorations,
+    ToggleQuickTerminal,
+    ToggleDoNotDisturb,
+    ResizeMode,
+    FocusBack,
+    FocusParent,
+    FocusNextChild,
+    FocusColumnLeft,
+    FocusColumnRight,
+    MoveToColumnLeft,
+    MoveToColumnRight,
+    Absorb,
+    Undo,
+    CycleColorTag,
+    ToggleFilter,
+    ToggleSmartView,
+}
+
+impl CompositorAction {
+    /// What the action does, as shown in the keybinding help overlay
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Quit => "Quit termstack (press twice)",
+            Self::SpawnTerminal => "New terminal",
+            Self::FocusNext => "Focus next cell",
+            Self::FocusPrev => "Focus previous cell",
+            Self::ScrollDown => "Scroll down",
+            Self::ScrollUp => "Scroll up",
+            Self::ScrollToTop => "Scroll to top",
+            Self::ScrollToBottom => "Scroll to bottom",
+            Self::PageDown => "Scroll down a page",
+            Self::PageUp => "Scroll up a page",
+            Self::ScrollbackPageDown => "Page down in the focused terminal's scrollback",
+            Self::ScrollbackPageUp => "Page up in the focused terminal's scrollback",
+            Self::Copy => "Copy selection",
+            Self::Paste => "Paste",
+            Self::FontSizeUp => "Larger font",
+            Self::FontSizeDown => "Smaller font",
+            Self::ToggleHud => "Frame-time HUD",
+            Self::ToggleHelp => "This help",
+            Self::ToggleDecorations => "Title bar or app decorations for the focused app",
+            Self::ToggleQuickTerminal => "Quick terminal",
+            Self::ToggleDoNotDisturb => "Do-not-disturb",
+            Self::ResizeMode => "Resize the focused cell with the keyboard",
+            Self::FocusBack => "Focus the previously focused cell",
+            Self::FocusParent => "Focus the shell that launched the focused cell",
+            Self::FocusNextChild => "Focus the focused shell's command cells in turn",
+            Self::FocusColumnLeft => "Focus the column to the left",
+            Self::FocusColumnRight => "Focus the column to the right",
+            Self::MoveToColumnLeft => "Move the focused cell to the column on the left (or a new one)",
+            Self::MoveToColumnRight => "Move the focused cell to the column on the right (or a new one)",
+            Self::Absorb => "Merge a finished command cell into its shell",
+            Self::Undo => "Bring back the last closed cells",
+            Self::CycleColorTag => "Cycle the focused cell's color tag",
+            Self::ToggleFilter => "Show only cells matching a command or #tag (again: all cells)",
+            Self::ToggleSmartView => "View the focused cell's JSON or table output formatted (again: raw)",
+        }
+    }
 }
 
 /// Apply a compositor action to the compositor state.
@@ -34,8 +97,13 @@ pub enum CompositorAction {
 pub fn apply_compositor_action(compositor: &mut TermStack, action: CompositorAction) {
     match action {
         CompositorAction::Quit => {
-            tracing::info!("quit requested");
-            compositor.running = false;
+            if compositor.quit_confirm.press(std::time::Instant::now()) {
+                tracing::info!("quit confirmed");
+                compositor.running = false;
+            } else {
+                tracing::info!("quit requested, waiting for confirmation");
+            }
+            compositor.redraw_requested = true;
         }
         CompositorAction::SpawnTerminal => {
             tracing::debug!("spawn terminal binding triggered");
@@ -62,10 +130,16 @@ pub fn apply_compositor_action(compositor: &mut TermStack, action: CompositorAct
             compositor.scroll_to_bottom();
         }
         CompositorAction::PageDown => {
-            compositor.pending_scroll_delta += compositor.output_size.h as f64 * 0.9;
+            compositor.pending_scroll_delta += compositor.viewport_height() as f64 * 0.9;
         }
         CompositorAction::PageUp => {
-            compositor.pending_scroll_delta += -(compositor.output_size.h as f64 * 0.9);
+            compositor.pending_scroll_delta += -(compositor.viewport_height() as f64 * 0.9);
+        }
+        CompositorAction::ScrollbackPageDown => {
+            compositor.pending_scrollback_pages -= 1;
+        }
+        CompositorAction::ScrollbackPageUp => {
+            compositor.pending_scrollback_pages += 1;
         }
         CompositorAction::Copy => {
             tracing::debug!("copy to clipboard requested");
@@ -83,5 +157,79 @@ pub fn apply_compositor_action(compositor: &mut TermStack, action: CompositorAct
             tracing::debug!("font size decrease requested");
             compositor.pending_font_size_delta -= 1.0;
         }
+        CompositorAction::ToggleHud => {
+            compositor.hud_visible = !compositor.hud_visible;
+            tracing::info!(visible = compositor.hud_visible, "frame-time HUD toggled");
+        }
+        CompositorAction::ToggleHelp => {
+            compositor.help_visible = !compositor.help_visible;
+            compositor.redraw_requested = true;
+            tracing::debug!(visible = compositor.help_visible, "keybinding help toggled");
+        }
+        CompositorAction::ToggleDecorations => {
+            if let Some(index) = compositor.focused_index() {
+                compositor.toggle_decorations(index);
+            }
+        }
+        CompositorAction::ToggleQuickTerminal => {
+            tracing::debug!("quick terminal toggle requested");
+            compositor.quick_terminal.request_toggle();
+        }
+        CompositorAction::ToggleDoNotDisturb => {
+            compositor.do_not_disturb.set(crate::do_not_disturb::DndMode::Toggle, None);
+            compositor.redraw_requested = true;
+        }
+        CompositorAction::FocusParent => {
+            tracing::debug!("focus parent requested");
+            compositor.family_focus_requested = Some(crate::input_handler::FamilyFocus::Parent);
+        }
+        CompositorAction::FocusNextChild => {
+            tracing::debug!("focus next child requested");
+            compositor.family_focus_requested = Some(crate::input_handler::FamilyFocus::NextChild);
+        }
+        CompositorAction::FocusColumnLeft => {
+            compositor.column_requested = Some(ColumnRequest::Focus(ColumnSide::Left));
+        }
+        CompositorAction::FocusColumnRight => {
+            compositor.column_requested = Some(ColumnRequest::Focus(ColumnSide::Right));
+        }
+        CompositorAction::MoveToColumnLeft => {
+            compositor.column_requested = Some(ColumnRequest::MoveCell(ColumnSide::Left));
+        }
+        CompositorAction::MoveToColumnRight => {
+            compositor.column_requested = Some(ColumnRequest::MoveCell(ColumnSide::Right));
+        }
+        CompositorAction::FocusBack => {
+            if compositor.focus_back() {
+                compositor.redraw_requested = true;
+            } else {
+                tracing::debug!("no previously focused cell to go back to");
+            }
+        }
+        CompositorAction::ResizeMode => {
+            tracing::debug!("keyboard resize mode requested");
+            compositor.keyboard_resize.request_toggle();
+        }
+        CompositorAction::Absorb => {
+            tracing::debug!("absorb into parent shell requested");
+            compositor.absorb_requested = true;
+        }
+        CompositorAction::Undo => {
+            tracing::debug!("undo requested");
+            compositor.undo_stack.requested = true;
+        }
+        CompositorAction::ToggleFilter => {
+            tracing::debug!("cell filter toggle requested");
+            compositor.toggle_cell_filter();
+        }
+        CompositorAction::ToggleSmartView => {
+            tracing::debug!("formatted view toggle requested");
+            compositor.smart_view_toggle_requested = true;
+        }
+        CompositorAction::CycleColorTag => {
+            if let Some(index) = compositor.focused_index() {
+                compositor.cycle_cell_tag(index);
+            }
+        }
     }
 }
@@ -7,38 +7,48 @@
 //!
 //! The compositor supports multiple rendering backends:
 //! - **X11** (default): GPU-accelerated rendering using OpenGL/GLES
+//! - **Wayland**: the same rendering in a window of a host Wayland compositor
+//! - **DRM**: the same rendering straight to a monitor, from a virtual terminal
 //! - **Headless** (feature): CPU-based software rendering for testing
 //!
 //! Backend selection is controlled by the `TERMSTACK_BACKEND` environment variable.
 
 use std::os::unix::net::UnixListener;
-use std::sync::mpsc;
 use std::time::{Duration, Instant};
 
-use smithay::backend::input::InputEvent;
 use smithay::backend::renderer::gles::GlesRenderer;
 use smithay::backend::renderer::element::surface::render_elements_from_surface_tree;
 use smithay::backend::renderer::element::{Element, Kind, RenderElement};
 use smithay::backend::renderer::element::surface::WaylandSurfaceRenderElement;
-use smithay::backend::renderer::{Color32F, Frame, Renderer, Bind};
-use smithay::backend::x11::{X11Event, X11Input};
+use smithay::backend::renderer::{Color32F, Frame, Renderer};
 use smithay::desktop::PopupKind;
 use smithay::desktop::PopupManager;
 use smithay::output::{Mode, Output, PhysicalProperties, Subpixel};
 use smithay::utils::Point;
-use smithay::reexports::calloop::{EventLoop, generic::Generic, Interest, Mode as CalloopMode};
+use smithay::reexports::calloop::{EventLoop, LoopHandle, generic::Generic, Interest, Mode as CalloopMode};
 use smithay::reexports::wayland_server::{Display, Resource};
 use smithay::utils::{Physical, Rectangle, Scale, Size, Transform};
 use smithay::wayland::socket::ListeningSocketSource;
 
-use crate::backend::{BackendType, select_backend};
+use crate::backend::{BackendType, HostWindow, select_backend};
+use crate::background::Background;
+use crate::cell_style::{CellStyle, CellStyleRenderer};
+use crate::color_tags::{draw_tag_stripes, ColorTag};
 use crate::config::Config;
+use crate::damage::DamageTracker;
+use crate::do_not_disturb::DoNotDisturb;
+use crate::frame_layout::FrameLayout;
+use crate::frame_stats::{FramePhase, FrameStats};
+use crate::input_log::InputReplay;
+use crate::layout::CULL_MARGIN;
+use crate::quick_terminal::QuickTerminal;
 use crate::render::{
     CellRenderData, prerender_terminals, prerender_title_bars,
-    collect_window_data, build_render_data, log_frame_state, render_terminal, render_external,
-    render_title_bar_selection, TitleBarCache,
+    apply_display_scale, collect_window_data, build_render_data, log_frame_state, render_terminal, render_external,
+    render_title_bar_selection, prerender_hud, render_hud, render_quick_terminal, render_centered_panel, collect_switcher_thumbnails, render_switcher_thumbnails, render_banner, render_plugin_overlays, prerender_background, render_background, collect_layer_elements, render_layer_surfaces, prerender_cursor, render_cursor,
+    cell_frames, local_damage, TitleBarTextureCache,
 };
-use crate::state::{ClientState, StackWindow, TermStack};
+use crate::state::{ClientState, StackWindow, TermStack, LAYERS_ABOVE_CELLS, LAYERS_BELOW_CELLS};
 use crate::xwayland_lifecycle;
 use crate::terminal_manager::TerminalManager;
 use crate::title_bar::{TitleBarRenderer, TITLE_BAR_HEIGHT};
@@ -51,10 +61,15 @@ type PopupRenderData = Vec<(i32, i32, i32, i32, Vec<WaylandSurfaceRenderElement<
 /// Selects and runs the appropriate backend based on the `TERMSTACK_BACKEND`
 /// environment variable:
 /// - `x11` (default): GPU-accelerated X11 backend
+/// - `wayland`: GPU-accelerated window nested in the host Wayland compositor
+/// - `drm` (default without `DISPLAY` or `WAYLAND_DISPLAY`): straight to the
+///   monitor, from a virtual terminal
 /// - `headless`: CPU-based software rendering (requires `headless-backend` feature)
 pub fn run_compositor() -> anyhow::Result<()> {
     match select_backend() {
         BackendType::X11 => run_compositor_x11(),
+        BackendType::Wayland => run_compositor_wayland(),
+        BackendType::Drm => run_compositor_drm(),
         BackendType::Headless => {
             #[cfg(feature = "headless-backend")]
             {
@@ -74,6 +89,7 @@ fn run_compositor_headless() -> anyhow::Result<()> {
 
     // Load configuration
     let config = Config::load();
+    crate::crash::install_panic_hook(&config);
 
     // Create event loop
     let mut event_loop: EventLoop<TermStack> = EventLoop::try_new()?;
@@ -82,7 +98,7 @@ fn run_compositor_headless() -> anyhow::Result<()> {
     let display: Display<TermStack> = Display::new()?;
 
     // Create output
-    let (output, _mode, output_size) = crate::setup::create_output("headless", 1280, 800);
+    let (output, _mode, output_size) = crate::setup::create_output("headless", 1280, 800, crate::refresh::DEFAULT_REFRESH);
 
     // Create compositor state (no renderer needed for headless)
     let (mut compositor, mut display) = TermStack::new(
@@ -92,6 +108,21 @@ fn run_compositor_headless() -> anyhow::Result<()> {
         config.csd_apps.clone(),
         config.max_gui_windows,
     );
+    compositor.gui_env = config.gui_env.clone();
+    compositor.hide_gui_launcher = config.hide_gui_launcher;
+    compositor.decoration_overrides = config.decorations.clone();
+    compositor.window_rules = config.window_rules.clone();
+    compositor.max_window_height = config.max_window_height;
+    compositor.quick_terminal = QuickTerminal::new(config.quick_terminal_height);
+    compositor.quick_terminal.set_reduce_motion(config.reduce_motion);
+    compositor.ephemeral_cells = crate::ephemeral::EphemeralCells::from_config(&config);
+    compositor.do_not_disturb = DoNotDisturb::new(config.dnd_focus_mode);
+    compositor.copy_options = crate::selection::CopyOptions::from_config(&config);
+    compositor.word_chars = config.word_chars.clone();
+    compositor.spawn_dedup = crate::spawn_handler::SpawnDedup::from_config(&config);
+    compositor.undo_stack = crate::undo::UndoStack::from_config(&config);
+    compositor.archive = crate::archive::Archive::from_config(&config);
+    compositor.respawn_last_shell = config.respawn_last_shell;
 
     // Add output to compositor
     compositor.space.map_output(&output, (0, 0));
@@ -100,6 +131,7 @@ fn run_compositor_headless() -> anyhow::Result<()> {
     // Set up Wayland socket, IPC socket, and toolkit env vars
     crate::setup::setup_wayland_socket(&event_loop.handle())?;
     crate::setup::setup_ipc_socket(&event_loop.handle())?;
+    crate::child_reaper::setup_sigchld_source(&event_loop.handle())?;
     crate::setup::set_toolkit_env_vars();
 
     // Spawn initial terminal immediately in headless mode
@@ -113,6 +145,19 @@ fn run_compositor_headless() -> anyhow::Result<()> {
         output_size.h as u32,
     );
 
+    // Recover the stack if the previous run crashed
+    if let Some(snapshot) = crate::crash::take_saved_session() {
+        crate::crash::restore_session(&mut compositor, &mut terminal_manager, &snapshot);
+    }
+
+    // Load the plugins enabled in the config
+    if let Some(dir) = Config::plugin_dir() {
+        compositor.plugins.load(&dir, &config.plugins);
+    }
+
+    // Logged input to feed back in (TERMSTACK_REPLAY_INPUT)
+    let mut input_replay = InputReplay::from_env(output_size.w, output_size.h);
+
     tracing::info!("headless compositor entering main loop");
 
     // Main event loop (no rendering, just protocol dispatch)
@@ -120,6 +165,7 @@ fn run_compositor_headless() -> anyhow::Result<()> {
         // Spawn initial terminal if requested
         if compositor.spawn_initial_terminal {
             compositor.spawn_initial_terminal = false;
+            crate::welcome::show_if_first_run(&mut compositor, &mut terminal_manager);
             match terminal_manager.spawn() {
                 Ok(id) => {
                     compositor.add_terminal(id);
@@ -138,16 +184,17 @@ fn run_compositor_headless() -> anyhow::Result<()> {
             &mut display,
             &mut terminal_manager,
             &output,
-            crate::window_height::calculate_window_heights,
         );
         if frame_result.all_terminals_exited || !compositor.running {
             break;
         }
 
-        // Dispatch calloop events with ~60fps timing
+        // Dispatch calloop events, one frame at a time
         event_loop
-            .dispatch(Some(Duration::from_millis(16)), &mut compositor)
+            .dispatch(Some(compositor.frame_time()), &mut compositor)
             .map_err(|e| anyhow::anyhow!("event loop error: {e}"))?;
+
+        replay_input(&mut input_replay, &mut compositor, &mut terminal_manager);
     }
 
     tracing::info!("headless compositor shutting down");
@@ -155,21 +202,57 @@ fn run_compositor_headless() -> anyhow::Result<()> {
     Ok(())
 }
 
-/// Run the compositor with the X11 backend
-fn run_compositor_x11() -> anyhow::Result<()> {
-    use smithay::backend::x11::{X11Backend, WindowBuilder};
-    use smithay::backend::allocator::dmabuf::DmabufAllocator;
-    use smithay::backend::allocator::gbm::{GbmAllocator, GbmBufferFlags, GbmDevice};
-    use smithay::backend::egl::{EGLContext, EGLDisplay};
-    use smithay::utils::DeviceFd;
-    use std::collections::HashSet;
-    use crate::cursor::CursorManager;
-    use crate::icon::{set_window_class, set_window_icon};
+/// Feed logged input events whose time has come through the input handlers
+fn replay_input(replay: &mut Option<InputReplay>, compositor: &mut TermStack, terminal_manager: &mut TerminalManager) {
+    let Some(active) = replay else {
+        return;
+    };
+    #[cfg(feature = "headless-backend")]
+    {
+        for entry in active.due(Instant::now()) {
+            let event = entry.to_input_event(compositor.output_size);
+            compositor.process_input_event_with_terminals(event, terminal_manager);
+        }
+        if active.is_finished() {
+            tracing::info!("input replay finished");
+            *replay = None;
+        }
+    }
+    #[cfg(not(feature = "headless-backend"))]
+    {
+        let _ = (active, compositor, terminal_manager);
+        tracing::warn!("input replay needs the `headless-backend` feature, ignoring TERMSTACK_REPLAY_INPUT");
+        *replay = None;
+    }
+}
 
+/// Run the compositor in a window on the host X server
+fn run_compositor_x11() -> anyhow::Result<()> {
     tracing::info!("starting termstack with X11 backend");
+    run_hosted(crate::backend::x11::X11Host::new)
+}
 
-    // Load configuration
-    let config = Config::load();
+/// Run the compositor in a window nested in the host Wayland compositor
+fn run_compositor_wayland() -> anyhow::Result<()> {
+    tracing::info!("starting termstack with nested Wayland backend");
+    run_hosted(crate::backend::wayland::WaylandHost::new)
+}
+
+/// Run the compositor on the monitor directly, from a virtual terminal
+fn run_compositor_drm() -> anyhow::Result<()> {
+    tracing::info!("starting termstack with DRM backend");
+    run_hosted(crate::backend::drm::DrmHost::new)
+}
+
+/// Run the compositor in the host window `open` creates from the event
+/// loop and a title
+fn run_hosted<H: HostWindow>(
+    open: impl FnOnce(&LoopHandle<'_, TermStack>, &str) -> anyhow::Result<H>,
+) -> anyhow::Result<()> {
+    // Load configuration (the theme may change at runtime with the desktop's)
+    let mut config = Config::load();
+    let configured_theme = config.theme;
+    crate::crash::install_panic_hook(&config);
 
     // Create event loop
     let mut event_loop: EventLoop<TermStack> = EventLoop::try_new()?;
@@ -177,82 +260,18 @@ fn run_compositor_x11() -> anyhow::Result<()> {
     // Create Wayland display
     let display: Display<TermStack> = Display::new()?;
 
-    // Initialize X11 backend
+    // Open the host window (before our own WAYLAND_DISPLAY and DISPLAY are set)
     let window_title = match config.theme {
         crate::config::Theme::Light => "Column Compositor (Light)",
         crate::config::Theme::Dark => "Column Compositor (Dark)",
     };
+    let mut host = open(&event_loop.handle(), window_title)?;
 
-    let x11_backend = X11Backend::new()
-        .map_err(|e| anyhow::anyhow!("X11 backend init error: {e:?}"))?;
-    let x11_handle = x11_backend.handle();
-
-    // Create window
-    let x11_window = WindowBuilder::new()
-        .title(window_title)
-        .size((1280u16, 800u16).into())
-        .build(&x11_handle)
-        .map_err(|e| anyhow::anyhow!("X11 window creation error: {e:?}"))?;
-
-    // Get DRM node for GPU rendering
-    let (_drm_node, fd) = x11_handle.drm_node()
-        .map_err(|e| anyhow::anyhow!("Failed to get DRM node: {e:?}"))?;
-
-    // Create GBM device for buffer allocation
-    let gbm_device = GbmDevice::new(DeviceFd::from(fd))
-        .map_err(|e| anyhow::anyhow!("Failed to create GBM device: {e:?}"))?;
-
-    // Create EGL display and context for OpenGL rendering
-    let egl_display = unsafe { EGLDisplay::new(gbm_device.clone()) }
-        .map_err(|e| anyhow::anyhow!("Failed to create EGL display: {e:?}"))?;
-    let egl_context = EGLContext::new(&egl_display)
-        .map_err(|e| anyhow::anyhow!("Failed to create EGL context: {e:?}"))?;
-
-    // Get supported modifiers for buffer allocation
-    let modifiers: HashSet<_> = egl_context
-        .dmabuf_render_formats()
-        .iter()
-        .map(|format| format.modifier)
-        .collect();
-
-    // Create X11 surface for presenting buffers
-    let mut x11_surface = x11_handle.create_surface(
-        &x11_window,
-        DmabufAllocator(GbmAllocator::new(gbm_device, GbmBufferFlags::RENDERING)),
-        modifiers.into_iter(),
-    ).map_err(|e| anyhow::anyhow!("Failed to create X11 surface: {e:?}"))?;
-
-    // Create GLES renderer from EGL context
-    let mut renderer = unsafe { GlesRenderer::new(egl_context) }
-        .map_err(|e| anyhow::anyhow!("Failed to create GLES renderer: {e:?}"))?;
-
-    // Set window icon and class BEFORE mapping (GNOME queries these on map)
-    if let Err(e) = set_window_icon(&x11_handle.connection(), x11_window.id()) {
-        tracing::warn!(?e, "failed to set window icon");
-    }
-    if let Err(e) = set_window_class(&x11_handle.connection(), x11_window.id()) {
-        tracing::warn!(?e, "failed to set window class");
-    }
-
-    // Map the window to make it visible
-    x11_window.map();
-
-    // Create cursor manager for resize cursor feedback
-    let mut cursor_manager = match CursorManager::new(
-        x11_handle.connection(),
-        x11_handle.screen(),
-        x11_window.id(),
-    ) {
-        Ok(cm) => Some(cm),
-        Err(e) => {
-            tracing::warn!(?e, "failed to create cursor manager, cursor changes disabled");
-            None
-        }
-    };
-
-    let initial_size = x11_window.size();
+    let initial_size = host.size();
+    let refresh = host.refresh().unwrap_or(crate::refresh::DEFAULT_REFRESH);
+    tracing::info!(refresh, "output refresh rate (mHz)");
     let (output, _mode, output_size) =
-        crate::setup::create_output("x11", initial_size.w as i32, initial_size.h as i32);
+        crate::setup::create_output(H::OUTPUT_NAME, initial_size.w, initial_size.h, refresh);
 
     // Track current window size for resize events
     let mut current_size = initial_size;
@@ -265,6 +284,24 @@ fn run_compositor_x11() -> anyhow::Result<()> {
         config.csd_apps.clone(),
         config.max_gui_windows,
     );
+    compositor.refresh = refresh;
+    compositor.presentation.reports_presents = host.reports_presents();
+    compositor.presentation.allow_tearing = config.allow_tearing;
+    compositor.gui_env = config.gui_env.clone();
+    compositor.hide_gui_launcher = config.hide_gui_launcher;
+    compositor.decoration_overrides = config.decorations.clone();
+    compositor.window_rules = config.window_rules.clone();
+    compositor.max_window_height = config.max_window_height;
+    compositor.quick_terminal = QuickTerminal::new(config.quick_terminal_height);
+    compositor.quick_terminal.set_reduce_motion(config.reduce_motion);
+    compositor.ephemeral_cells = crate::ephemeral::EphemeralCells::from_config(&config);
+    compositor.do_not_disturb = DoNotDisturb::new(config.dnd_focus_mode);
+    compositor.copy_options = crate::selection::CopyOptions::from_config(&config);
+    compositor.word_chars = config.word_chars.clone();
+    compositor.spawn_dedup = crate::spawn_handler::SpawnDedup::from_config(&config);
+    compositor.undo_stack = crate::undo::UndoStack::from_config(&config);
+    compositor.archive = crate::archive::Archive::from_config(&config);
+    compositor.respawn_last_shell = config.respawn_last_shell;
 
     // Add output to compositor
     compositor.space.map_output(&output, (0, 0));
@@ -289,50 +326,29 @@ fn run_compositor_x11() -> anyhow::Result<()> {
     // Set up Wayland socket, IPC socket, and toolkit env vars
     crate::setup::setup_wayland_socket(&event_loop.handle())?;
     crate::setup::setup_ipc_socket(&event_loop.handle())?;
+    crate::child_reaper::setup_sigchld_source(&event_loop.handle())?;
     crate::setup::set_toolkit_env_vars();
 
+    // Follow the desktop's light/dark preference (stops when dropped)
+    let _system_theme = if config.follow_system_theme {
+        Some(crate::system_theme::setup_system_theme_source(&event_loop.handle())?)
+    } else {
+        None
+    };
+
     // Unset DISPLAY so X11 apps spawned from terminals use our XWayland, not the host.
     // XWayland will set DISPLAY when it's ready.
     std::env::remove_var("DISPLAY");
 
-    // Create channel for X11 input events
-    // The X11 backend callback will send events, main loop will receive them
-    let (x11_event_tx, x11_event_rx) = mpsc::channel::<InputEvent<X11Input>>();
-
-    // Insert X11 backend into event loop
-    event_loop.handle().insert_source(x11_backend, move |event, _, state| {
-        match event {
-            X11Event::Input { event: input_event, .. } => {
-                // Log that we received an X11 input event (helps debug freezes)
-                tracing::debug!("X11 input event received");
-                // Send input events through channel to be processed in main loop
-                // where we have access to terminal_manager
-                let _ = x11_event_tx.send(input_event);
-            }
-            X11Event::Resized { new_size, .. } => {
-                state.compositor_window_resize_pending = Some((new_size.w, new_size.h));
-            }
-            X11Event::CloseRequested { .. } => {
-                state.running = false;
-            }
-            X11Event::Focus { focused, .. } => {
-                tracing::info!("X11 window focus changed: {}", focused);
-            }
-            X11Event::Refresh { .. } => {
-                // Window needs redraw - will happen in main loop anyway
-            }
-            X11Event::PresentCompleted { .. } => {
-                // Buffer presentation complete - ready for next frame
-            }
-        }
-    }).map_err(|e| anyhow::anyhow!("Failed to insert X11 backend source: {e:?}"))?;
+    // Wake the loop when Wayland clients send requests
+    crate::event_sources::setup_display_source(&event_loop.handle(), display.backend().poll_fd())?;
 
     // Initialize XWayland support for X11 apps
     xwayland_lifecycle::initialize_xwayland(&mut compositor, &mut display, event_loop.handle());
 
     tracing::info!("entering main loop");
 
-    let bg_color = Color32F::new(
+    let mut bg_color = Color32F::new(
         config.background_color[0],
         config.background_color[1],
         config.background_color[2],
@@ -346,6 +362,16 @@ fn run_compositor_x11() -> anyhow::Result<()> {
         output_size.h as u32,
     );
 
+    // Recover the stack if the previous run crashed
+    if let Some(snapshot) = crate::crash::take_saved_session() {
+        crate::crash::restore_session(&mut compositor, &mut terminal_manager, &snapshot);
+    }
+
+    // Load the plugins enabled in the config
+    if let Some(dir) = Config::plugin_dir() {
+        compositor.plugins.load(&dir, &config.plugins);
+    }
+
     // Create title bar renderer for external windows
     let terminal_theme = config.theme.to_terminal_theme();
     let mut title_bar_renderer = TitleBarRenderer::new(terminal_theme);
@@ -354,15 +380,75 @@ fn run_compositor_x11() -> anyhow::Result<()> {
     }
 
     // Cache for title bar textures to avoid re-rendering every frame
-    let mut title_bar_cache: TitleBarCache = TitleBarCache::new();
+    let mut title_bar_cache = TitleBarTextureCache::new();
+
+    // Rounded corners and shadows (None when disabled in the config)
+    let mut cell_style = match CellStyleRenderer::new(host.renderer(), CellStyle::from_config(&config)) {
+        Ok(cell_style) => cell_style,
+        Err(e) => {
+            tracing::warn!(error = ?e, "failed to compile cell style shader, drawing square cells");
+            None
+        }
+    };
+
+    // Background image or gradient, re-uploaded when the output size changes
+    let background = match Background::load(&config.background) {
+        Ok(background) => background,
+        Err(e) => {
+            tracing::warn!(error = %e, "using flat background color");
+            None
+        }
+    };
+    let mut background_texture = None;
+    let mut background_size = Size::<i32, Physical>::default();
+
+    // Frame timing statistics and the texture for the HUD overlay (Super+F12)
+    let mut frame_stats = FrameStats::new();
+    let mut hud_texture = None;
+
+    // Logged input to feed back in (TERMSTACK_REPLAY_INPUT)
+    let mut input_replay = InputReplay::from_env(output_size.w, output_size.h);
+
+    // Window switcher panel (Alt+Tab) and the lines it was rendered from
+    let mut switcher_texture = None;
+    let mut switcher_lines = Vec::new();
+
+    // Keybinding help panel (Super+?) and the lines it was rendered from
+    let mut help_texture = None;
+    let mut help_lines = Vec::new();
+
+    // Filter prompt panel (Super+F), and the banner shown while filtering
+    // with the text it was rendered from
+    let mut filter_prompt_texture = None;
+    let mut filter_prompt_lines = Vec::new();
+    let mut filter_banner_texture = None;
+    let mut filter_banner = None;
+
+    // Quit prompt panel after the first Super+Q
+    let mut quit_texture = None;
+
+    // Plugin overlay panels and the overlays they were rendered from
+    let mut plugin_overlay_textures = Vec::new();
+    let mut plugin_overlays = Vec::new();
+
+    // Pointer drawn by the compositor when the host shows none (DRM), and
+    // where it was last drawn
+    let cursor_texture = if host.shows_cursor() { None } else { prerender_cursor(host.renderer()) };
+    compositor.owns_vt = host.owns_vt();
+    let mut drawn_cursor_position = compositor.pointer_position;
+
+    // What changed since each swapchain buffer was drawn; `full_damage` is set
+    // when something outside the cells changes, like the background color
+    let mut damage_tracker = DamageTracker::new(crate::cell_style::SHADOW_SIZE);
+    let mut full_damage = true;
 
     // Initial terminal will be spawned after XWayland is ready (in main loop)
     // This ensures DISPLAY is set correctly for X11 app support
 
     // Frame timing for render rate limiting
     // Skip renders when behind to avoid backlog, but always process events
+    // at most one frame per refresh of the monitor
     let mut last_render_time = Instant::now();
-    const MIN_FRAME_TIME: Duration = Duration::from_millis(8); // ~120fps max
 
     // Periodic resource usage logging
     let mut last_resource_log = Instant::now();
@@ -373,6 +459,7 @@ fn run_compositor_x11() -> anyhow::Result<()> {
         // Spawn initial terminal once XWayland is ready
         if compositor.spawn_initial_terminal {
             compositor.spawn_initial_terminal = false;
+            crate::welcome::show_if_first_run(&mut compositor, &mut terminal_manager);
             match terminal_manager.spawn() {
                 Ok(id) => {
                     compositor.add_terminal(id);
@@ -388,56 +475,47 @@ fn run_compositor_x11() -> anyhow::Result<()> {
         // Monitor xwayland-satellite health and auto-restart on crash with backoff
         xwayland_lifecycle::monitor_xwayland_satellite_health(&mut compositor);
 
-        // Update cell heights for input event processing
-        let window_heights = crate::window_height::calculate_window_heights(&compositor, &terminal_manager);
-        compositor.update_layout_heights(window_heights);
-
         // Update Space positions to match current terminal height and scroll
         // This ensures Space.element_under works correctly for click detection
         compositor.recalculate_layout();
 
-        // Drain all pending X11 input events before rendering.
-        //
-        // WORKAROUND: Smithay's X11Source uses a bounded sync_channel(5) which causes
-        // scroll event backlog during fast touchpad scrolling. Events buffer in
-        // RustConnection while the channel is full, then trickle through 5 at a time.
-        // See: smithay/src/utils/x11rb.rs line 53
-        //
-        // CLEAN FIX: Fork Smithay and change sync_channel(5) to channel() (unbounded)
-        // in src/utils/x11rb.rs. This allows all buffered events to drain immediately.
-        //
-        // This workaround uses small timeouts between drain attempts to give the event
-        // thread time to refill the channel from RustConnection's buffer.
-        let drain_start = Instant::now();
-        let drain_max = Duration::from_millis(8);
-        let mut consecutive_empty = 0;
-
-        loop {
-            // Small timeout lets event thread refill channel from RustConnection buffer
-            event_loop
-                .dispatch(Some(Duration::from_micros(200)), &mut compositor)
-                .expect("event loop dispatch failed");
-
-            let mut batch_count = 0;
-            while let Ok(input_event) = x11_event_rx.try_recv() {
-                batch_count += 1;
-                compositor.process_input_event_with_terminals(input_event, &mut terminal_manager);
-            }
+        // Sleep until an event source fires, a key repeat is due, or the
+        // frame rate limit / housekeeping tick expires. While the last frame
+        // isn't presented yet, its PresentCompleted event wakes the loop.
+        let now = Instant::now();
+        let timeout = crate::event_sources::dispatch_timeout(
+            now,
+            (compositor.redraw_requested || compositor.hud_visible || compositor.quick_terminal.is_animating())
+                && !compositor.presentation.waiting_for_present(now)
+                && host.is_active(),
+            last_render_time,
+            compositor.frame_time(),
+            compositor
+                .key_repeat
+                .as_ref()
+                .map(|(_, at)| *at)
+                .into_iter()
+                .chain(input_replay.as_ref().and_then(InputReplay::next_due))
+                .chain(compositor.presentation.present_deadline(now))
+                .chain(compositor.xwayland.restart_deadline())
+                .chain(compositor.plugins.next_timer())
+                .min(),
+        );
+        event_loop
+            .dispatch(Some(timeout), &mut compositor)
+            .map_err(|e| anyhow::anyhow!("event loop error: {e}"))?;
 
-            if batch_count == 0 {
-                consecutive_empty += 1;
-                // Break after 3 consecutive empty checks (~600µs of no events)
-                if consecutive_empty >= 3 {
-                    break;
-                }
-            } else {
-                consecutive_empty = 0;
-            }
+        let drain_phase = frame_stats.phase(FramePhase::InputDrain);
+        let input_events = host.take_input();
+        for input_event in input_events {
+            compositor.process_input_event_with_terminals(input_event, &mut terminal_manager);
+        }
+        replay_input(&mut input_replay, &mut compositor, &mut terminal_manager);
+        drop(drain_phase);
 
-            // Hard timeout safety
-            if drain_start.elapsed() >= drain_max {
-                break;
-            }
+        // Ctrl+Alt+F<n>
+        if let Some(vt) = compositor.pending_vt_switch.take() {
+            host.switch_vt(vt);
         }
 
         // Periodic resource usage logging
@@ -448,7 +526,7 @@ fn run_compositor_x11() -> anyhow::Result<()> {
             let active_terminals = terminal_manager.count_active();
             let dead_terminals = terminal_manager.count_dead();
             let gui_windows = compositor.count_gui_spawned_windows();
-            let has_pending_gui = compositor.pending_window_set_at.is_some();
+            let has_pending_gui = !compositor.pending_launches.is_empty();
             tracing::info!(
                 window_count,
                 terminal_count,
@@ -460,14 +538,21 @@ fn run_compositor_x11() -> anyhow::Result<()> {
             );
         }
 
-        // Handle X11 resize events
+        // Handle host window resize events
         if let Some((new_w, new_h)) = compositor.compositor_window_resize_pending.take() {
             let new_size: Size<i32, Physical> = (new_w as i32, new_h as i32).into();
+            // The window may have been moved to another monitor too
+            if let Some(refresh) = host.refresh() {
+                if refresh != compositor.refresh {
+                    tracing::info!(refresh, "output refresh rate changed (mHz)");
+                    compositor.refresh = refresh;
+                }
+            }
             // Update Smithay output mode
             output.change_current_state(
                 Some(Mode {
                     size: new_size,
-                    refresh: 60_000,
+                    refresh: compositor.refresh,
                 }),
                 None,
                 None,
@@ -479,18 +564,38 @@ fn run_compositor_x11() -> anyhow::Result<()> {
                 &mut terminal_manager,
                 Size::from((new_size.w, new_size.h)),
             );
-            current_size = (new_w, new_h).into();
+            current_size = new_size;
         }
 
-        // Update cursor icon based on whether pointer is on a resize handle
-        if let Some(ref mut cm) = cursor_manager {
-            cm.set_resize_cursor(compositor.cursor_on_resize_handle);
+        // Switch themes with the desktop (no preference: the configured theme)
+        if let Some(scheme) = compositor.pending_color_scheme.take() {
+            let theme = scheme.resolve(configured_theme);
+            if theme != config.theme {
+                tracing::info!(?theme, "switching theme");
+                config.switch_theme(theme);
+                let [r, g, b, a] = config.background_color;
+                bg_color = Color32F::new(r, g, b, a);
+                if let Some(ref mut tb_renderer) = title_bar_renderer {
+                    tb_renderer.set_theme(theme.to_terminal_theme());
+                }
+                if let Some(ref mut cell_style) = cell_style {
+                    cell_style.set_style(CellStyle::from_config(&config));
+                }
+                terminal_manager.set_theme(theme.to_terminal_theme());
+                compositor.redraw_requested = true;
+                full_damage = true;
+            }
         }
 
+        // Update cursor icon based on whether pointer is on a resize handle
+        host.set_resize_cursor(compositor.cursor_on_resize_handle);
+
         if !compositor.running {
             break;
         }
 
+        let layout_phase = frame_stats.phase(FramePhase::Layout);
+
         // Dispatch Wayland client requests
         display.dispatch_clients(&mut compositor)
             .expect("failed to dispatch clients");
@@ -499,147 +604,115 @@ fn run_compositor_x11() -> anyhow::Result<()> {
         let frame_result = crate::frame::process_frame(
             &mut compositor,
             &mut terminal_manager,
-            crate::window_height::calculate_window_heights,
         );
+        drop(layout_phase);
         if frame_result.all_terminals_exited {
             break;
         }
 
-        // Frame rate limiting: wait until it's time to render
-        // This prevents busy-looping while still processing events at a steady rate
+        // Re-arm PTY sources now that their output has been read
+        crate::event_sources::sync_pty_sources(&mut compositor, &terminal_manager);
+
+        // Render only when something may have changed, at most once per refresh
+        // (sooner for the echo of a key) and, unless tearing is allowed, once
+        // the last frame is on screen; never while another VT is shown
         let now = Instant::now();
-        let elapsed = now.duration_since(last_render_time);
-        if elapsed < MIN_FRAME_TIME {
-            // Wait for remaining time, then process any events that arrived
-            let remaining = MIN_FRAME_TIME - elapsed;
-            event_loop
-                .dispatch(Some(remaining), &mut compositor)
-                .expect("event loop dispatch failed");
-            // Process events that arrived during the wait
-            while let Ok(input_event) = x11_event_rx.try_recv() {
-                compositor.process_input_event_with_terminals(input_event, &mut terminal_manager);
-            }
-            compositor.apply_pending_scroll();
+        let redraw = compositor.redraw_requested || compositor.hud_visible;
+        let echo = compositor.typing_echo.due(now, &terminal_manager);
+        if !redraw
+            || (now.duration_since(last_render_time) < compositor.frame_time() && echo.is_none())
+            || compositor.presentation.waiting_for_present(now)
+            || !host.is_active()
+        {
+            compositor.display_handle.flush_clients()?;
             continue;
         }
+        compositor.redraw_requested = false;
+        if echo.is_some() {
+            compositor.typing_echo.drawn();
+        }
 
         // Get window size for rendering
-        let physical_size: Size<i32, Physical> = Size::from((current_size.w as i32, current_size.h as i32));
+        let physical_size = current_size;
 
-        #[allow(deprecated)]
-        let damage: Rectangle<i32, Physical> = Rectangle::from_loc_and_size(
-            (0, 0),
-            (current_size.w as i32, current_size.h as i32),
-        );
+        // Region redrawn this frame, once the cells are known
+        let damage;
 
-        // Get buffer from X11 surface for rendering
-        let (mut buffer, _buffer_age) = match x11_surface.buffer() {
-            Ok(buf) => buf,
-            Err(e) => {
-                tracing::warn!(error = ?e, "Failed to get X11 surface buffer");
-                continue;
-            }
-        };
-
-        // Render frame - bind the buffer to the renderer
+        // Render frame - bind the next buffer, with how many frames old its contents are
         {
-            let mut framebuffer = match renderer.bind(&mut buffer) {
-                Ok(fb) => fb,
+            let (renderer, mut framebuffer, buffer_age) = match host.bind() {
+                Ok(bound) => bound,
                 Err(e) => {
-                    tracing::warn!(error = ?e, "Failed to bind buffer");
+                    tracing::warn!(error = ?e, "Failed to bind host window buffer");
                     continue;
                 }
             };
 
             let scale = Scale::from(1.0);
 
-            // Pre-render all terminal textures
-            prerender_terminals(&mut terminal_manager, &mut renderer);
+            let prerender_phase = frame_stats.phase(FramePhase::Prerender);
 
-            // Pre-render title bar textures for all cells with SSD
-            let title_bar_textures = prerender_title_bars(
+            // Cells far outside the viewport skip texture and element work
+            let near_viewport = compositor.cells_near_viewport(compositor.viewport_height(), CULL_MARGIN);
+
+            // Pre-render terminal textures, just the echoing one on the fast path
+            if prerender_terminals(
                 &compositor.layout_nodes,
-                &mut title_bar_renderer,
-                &terminal_manager,
-                &mut renderer,
-                physical_size.w,
-                &mut title_bar_cache,
-                &mut compositor.title_bar_char_info,
-            );
+                &near_viewport,
+                compositor.quick_terminal.visible().map(|(id, _)| id),
+                echo,
+                &mut terminal_manager,
+                renderer,
+            ) {
+                compositor.redraw_requested = true;
+            }
 
             // Collect actual heights and external window elements
-            let (actual_heights, mut external_elements) = collect_window_data(
+            let (measured_heights, mut external_elements) = collect_window_data(
                 &compositor.layout_nodes,
+                &near_viewport,
                 &terminal_manager,
-                &mut renderer,
+                renderer,
                 scale,
             );
 
-            // Build heights for positioning:
-            // - Terminals being resized: use node.height for instant visual feedback
-            // - Terminals NOT resizing: use actual_heights (includes title bar from collect_window_data)
-            // - External windows being resized: use drag target height
-            // - External windows NOT resizing: use committed height from WindowState
-            let layout_heights: Vec<i32> = compositor.layout_nodes
-                .iter()
-                .enumerate()
-                .map(|(i, node)| {
-                    // Check if this window is being resized
-                    let is_resizing = compositor.resizing
-                        .as_ref()
-                        .map(|drag| drag.window_index == i)
-                        .unwrap_or(false);
-
-                    match &node.cell {
-                        StackWindow::Terminal(_) => {
-                            if is_resizing {
-                                // Being resized: use node.height for instant visual feedback
-                                // (avoids lag from texture rendering)
-                                node.height
-                            } else {
-                                // Not resizing: use actual_heights which includes title bar
-                                actual_heights[i]
-                            }
-                        }
-                        StackWindow::External(_) => {
-                            if is_resizing {
-                                if let Some(drag) = &compositor.resizing {
-                                    // Being resized: use drag target for visual feedback
-                                    return drag.target_height;
-                                }
-                            }
-                            // Not being resized: use actual_heights from element geometry
-                            // This handles both new windows (before first commit) and
-                            // post-commit windows correctly.
-                            actual_heights[i]
-                        }
-                    }
-                })
-                .collect();
+            // Lay the frame out from the measured heights and apply it (auto-scrolling
+            // if needed); everything below draws from this one snapshot
+            let frame_layout = FrameLayout::compute(&compositor, &measured_heights);
+            let frame_layout = compositor.commit_frame_layout(frame_layout);
+            let origins = frame_layout.origins();
+            let tops = frame_layout.tops();
+            let hidden = frame_layout.hidden();
+            let column_width = frame_layout.column_width();
+
+            // Pre-render title bar textures for on-screen cells with SSD
+            let title_bar_textures = prerender_title_bars(
+                &compositor.layout_nodes,
+                &tops,
+                physical_size.h,
+                compositor.focused_index(),
+                &mut title_bar_renderer,
+                &terminal_manager,
+                &compositor.plugins,
+                renderer,
+                column_width,
+                &mut title_bar_cache,
+                &mut compositor.title_bar_char_info,
+            );
 
             // Build render data with computed Y positions
             let render_data = build_render_data(
                 &compositor.layout_nodes,
-                &layout_heights,
+                frame_layout.heights(),
+                origins,
                 &mut external_elements,
                 &title_bar_textures,
-                compositor.scroll_offset,
                 physical_size.h,
                 &terminal_manager,
             );
 
             // Debug logging for external windows
-            log_frame_state(
-                &compositor.layout_nodes,
-                &render_data,
-                &terminal_manager,
-                compositor.scroll_offset,
-                compositor.focused_index(),
-                physical_size.h,
-            );
-
-            // Check height changes and auto-scroll if needed
-            crate::window_height::check_and_handle_height_changes(&mut compositor, actual_heights);
+            log_frame_state(&compositor, &terminal_manager);
 
             // Collect popup elements BEFORE starting the frame (need renderer access)
             // Store: (popup_x, popup_top, geo_offset_x, geo_offset_y, elements)
@@ -647,7 +720,10 @@ fn run_compositor_x11() -> anyhow::Result<()> {
             let mut popup_render_data: PopupRenderData = Vec::new();
 
             for (window_idx, data) in render_data.iter().enumerate() {
-                if let CellRenderData::External { y, .. } = data {
+                if hidden[window_idx] {
+                    continue;
+                }
+                if let CellRenderData::External { x, y, .. } = data {
                     if let Some(node) = compositor.layout_nodes.get(window_idx) {
                         if let StackWindow::External(entry) = &node.cell {
                             // Get parent window geometry for proper popup positioning
@@ -681,12 +757,12 @@ fn run_compositor_x11() -> anyhow::Result<()> {
                                 };
 
                                 // Parent window's client area top in render coords
-                                let title_bar_offset = if entry.uses_csd { 0 } else { TITLE_BAR_HEIGHT as i32 };
+                                let title_bar_offset = if entry.has_title_bar() { TITLE_BAR_HEIGHT as i32 } else { 0 };
                                 let client_area_top = *y + node.height - title_bar_offset;
 
                                 // Calculate popup CONTENT position in screen coords
                                 // popup_position is relative to parent surface, so add parent's screen offset
-                                let popup_content_x = popup_position.x + parent_window_geo.loc.x + crate::render::FOCUS_INDICATOR_WIDTH;
+                                let popup_content_x = popup_position.x + parent_window_geo.loc.x + *x + crate::render::FOCUS_INDICATOR_WIDTH;
                                 let popup_content_top = client_area_top - popup_position.y - parent_window_geo.loc.y;
 
                                 // Popup SURFACE position = content position minus window geometry offset
@@ -704,7 +780,7 @@ fn run_compositor_x11() -> anyhow::Result<()> {
 
                                 let popup_elements: Vec<WaylandSurfaceRenderElement<GlesRenderer>> =
                                     render_elements_from_surface_tree(
-                                        &mut renderer,
+                                        renderer,
                                         wl_surface,
                                         Point::from((0i32, 0i32)),
                                         scale,
@@ -722,20 +798,164 @@ fn run_compositor_x11() -> anyhow::Result<()> {
                 }
             }
 
+            // Panels and other layer surfaces, under and over the cells
+            let layers_below = collect_layer_elements(&compositor, &LAYERS_BELOW_CELLS, renderer, scale);
+            let layers_above = collect_layer_elements(&compositor, &LAYERS_ABOVE_CELLS, renderer, scale);
+
+            drop(prerender_phase);
+
+            // Regenerate the HUD texture a few times per second while it is shown
+            if compositor.hud_visible {
+                if frame_stats.hud_refresh_due(now) {
+                    hud_texture = prerender_hud(
+                        &frame_stats.hud_lines(),
+                        &mut title_bar_renderer,
+                        renderer,
+                    );
+                }
+            } else if hud_texture.take().is_some() {
+                frame_stats.invalidate_hud();
+            }
+
+            let switcher_thumbnails = if compositor.window_switcher.is_open() {
+                let lines = compositor.window_switcher.lines(&compositor, &terminal_manager);
+                if switcher_texture.is_none() || lines != switcher_lines {
+                    switcher_texture = prerender_hud(&lines, &mut title_bar_renderer, renderer);
+                    switcher_lines = lines;
+                }
+                collect_switcher_thumbnails(&compositor, renderer, scale, physical_size.w)
+            } else {
+                switcher_texture = None;
+                Vec::new()
+            };
+
+            if compositor.help_visible {
+                let lines = crate::help_overlay::help_lines(&compositor.plugins);
+                if help_texture.is_none() || lines != help_lines {
+                    help_texture = prerender_hud(&lines, &mut title_bar_renderer, renderer);
+                    help_lines = lines;
+                }
+            } else {
+                help_texture = None;
+            }
+
+            match compositor.filter_view.prompt_lines() {
+                Some(lines) => {
+                    if filter_prompt_texture.is_none() || lines != filter_prompt_lines {
+                        filter_prompt_texture = prerender_hud(&lines, &mut title_bar_renderer, renderer);
+                        filter_prompt_lines = lines;
+                    }
+                }
+                None => filter_prompt_texture = None,
+            }
+
+            // The banner stays up while filtering, so it is redrawn within the
+            // damage like a cell rather than forcing full damage every frame
+            let banner = compositor.filter_view.banner(compositor.layout_nodes.len());
+            if banner != filter_banner {
+                filter_banner_texture = banner
+                    .as_ref()
+                    .and_then(|text| prerender_hud(std::slice::from_ref(text), &mut title_bar_renderer, &mut *renderer));
+                filter_banner = banner;
+                full_damage = true;
+            }
+
+            if compositor.quit_confirm.is_pending(Instant::now()) {
+                if quit_texture.is_none() {
+                    quit_texture = prerender_hud(&crate::quit::QuitConfirm::lines(), &mut title_bar_renderer, renderer);
+                }
+            } else {
+                quit_texture = None;
+            }
+
+            let overlays: Vec<_> = compositor.plugins.overlays().cloned().collect();
+            if overlays != plugin_overlays {
+                plugin_overlay_textures = overlays
+                    .iter()
+                    .filter_map(|overlay| {
+                        let texture = prerender_hud(&overlay.lines, &mut title_bar_renderer, &mut *renderer)?;
+                        Some((overlay.anchor, texture))
+                    })
+                    .collect();
+                plugin_overlays = overlays;
+            }
+
+            if let Some(ref background) = background {
+                if background_texture.is_none() || background_size != physical_size {
+                    background_texture = prerender_background(background, physical_size, renderer);
+                    background_size = physical_size;
+                }
+            }
+
+            // Redraw only what changed since this buffer was last drawn into;
+            // overlays and popups aren't tracked and take the whole output
+            let overlays = compositor.quick_terminal.visible().is_some()
+                || switcher_texture.is_some()
+                || help_texture.is_some()
+                || filter_prompt_texture.is_some()
+                || quit_texture.is_some()
+                || !plugin_overlay_textures.is_empty()
+                || hud_texture.is_some()
+                || !popup_render_data.is_empty()
+                || compositor.cross_selection.is_some()
+                || compositor.layer_shell.take_damage();
+            let cell_frames = cell_frames(
+                &render_data,
+                &compositor.layout_nodes,
+                hidden,
+                compositor.focused_index(),
+                &terminal_manager,
+                scale,
+            );
+            // A drawn pointer isn't damage-tracked: redraw in full when it moved
+            let cursor_moved = cursor_texture.is_some() && compositor.pointer_position != drawn_cursor_position;
+            drawn_cursor_position = compositor.pointer_position;
+            damage = damage_tracker.frame(
+                physical_size,
+                cell_frames,
+                std::mem::take(&mut full_damage) || overlays || cursor_moved,
+                buffer_age,
+            );
+
+            let _render_phase = frame_stats.phase(FramePhase::Render);
+
             // Begin actual rendering
-            // X11 backend needs Flipped180 because OpenGL Y=0 is at bottom but X11 Y=0 is at top
+            // Flipped180 because OpenGL Y=0 is at bottom but the host window's Y=0 is at top
             let mut frame = renderer.render(&mut framebuffer, physical_size, Transform::Flipped180)
                 .map_err(|e| anyhow::anyhow!("render error: {e:?}"))?;
 
-            frame.clear(bg_color, &[damage])
+            frame.clear(bg_color, local_damage(Rectangle::from_size(physical_size), damage).as_slice())
                 .map_err(|e| anyhow::anyhow!("clear error: {e:?}"))?;
+            if let Some(ref texture) = background_texture {
+                render_background(&mut frame, texture, damage);
+            }
+            render_layer_surfaces(&mut frame, &layers_below, physical_size.h, damage, scale);
+
+            let cell_bounds: Vec<Rectangle<i32, Physical>> = render_data
+                .iter()
+                .zip(hidden)
+                .filter(|(_, &hidden)| !hidden)
+                .map(|(data, _)| match data {
+                    CellRenderData::Terminal { x, y, height, .. } | CellRenderData::External { x, y, height, .. } => {
+                        Rectangle::new((*x, *y).into(), (column_width, *height).into())
+                    }
+                })
+                .collect();
+            let tag_stripes: Vec<(Rectangle<i32, Physical>, ColorTag)> = cell_bounds
+                .iter()
+                .zip(compositor.layout_nodes.iter().zip(hidden).filter(|(_, &hidden)| !hidden))
+                .filter_map(|(&bounds, (node, _))| Some((bounds, node.tag?)))
+                .collect();
 
             // Render all cells
             for (window_idx, data) in render_data.into_iter().enumerate() {
+                if hidden[window_idx] {
+                    continue;
+                }
                 let is_focused = compositor.focused_index() == Some(window_idx);
 
                 match data {
-                    CellRenderData::Terminal { id, y, height, title_bar_texture } => {
+                    CellRenderData::Terminal { id, x, y, height, title_bar_texture } => {
                         // Check if terminal is still running (for indicator)
                         let is_running = terminal_manager.get(id)
                             .map(|t| !t.has_exited())
@@ -745,6 +965,7 @@ fn run_compositor_x11() -> anyhow::Result<()> {
                             &mut frame,
                             &terminal_manager,
                             id,
+                            x,
                             y,
                             height,
                             title_bar_texture,
@@ -760,17 +981,19 @@ fn run_compositor_x11() -> anyhow::Result<()> {
                             render_title_bar_selection(
                                 &mut frame,
                                 window_idx,
+                                x,
                                 title_bar_y,
-                                physical_size.w,
+                                column_width,
                                 compositor.cross_selection.as_ref(),
                                 &compositor.title_bar_char_info,
                                 damage,
                             );
                         }
                     }
-                    CellRenderData::External { y, height, elements, title_bar_texture, uses_csd } => {
+                    CellRenderData::External { x, y, height, elements, title_bar_texture, uses_csd, display_scale } => {
                         render_external(
                             &mut frame,
+                            x,
                             y,
                             height,
                             elements,
@@ -778,7 +1001,7 @@ fn run_compositor_x11() -> anyhow::Result<()> {
                             is_focused,
                             physical_size,
                             damage,
-                            scale,
+                            apply_display_scale(scale, display_scale),
                             uses_csd,
                         );
 
@@ -788,8 +1011,9 @@ fn run_compositor_x11() -> anyhow::Result<()> {
                             render_title_bar_selection(
                                 &mut frame,
                                 window_idx,
+                                x,
                                 title_bar_y,
-                                physical_size.w,
+                                column_width,
                                 compositor.cross_selection.as_ref(),
                                 &compositor.title_bar_char_info,
                                 damage,
@@ -799,7 +1023,14 @@ fn run_compositor_x11() -> anyhow::Result<()> {
                 }
             }
 
-            // Render popups on top of all cells (using pre-collected elements)
+            draw_tag_stripes(&mut frame, &tag_stripes, damage);
+            if let Some(ref cell_style) = cell_style {
+                cell_style.render(&mut frame, &cell_bounds, damage);
+            }
+
+            render_layer_surfaces(&mut frame, &layers_above, physical_size.h, damage, scale);
+
+            // Render popups on top of all cells and panels (using pre-collected elements)
             // popup_render_data contains (popup_surface_x, popup_surface_top, _, _, elements)
             // popup_surface_x/top is where the popup SURFACE origin should render (already adjusted for window geometry)
             for (popup_surface_x, popup_surface_top, _, _, popup_elements) in popup_render_data {
@@ -818,35 +1049,71 @@ fn run_compositor_x11() -> anyhow::Result<()> {
                     );
 
                     // Use source rectangle directly - Smithay handles coordinate systems
-                    element.draw(&mut frame, src, dest, &[damage], &[]).ok();
+                    element.draw(&mut frame, src, dest, local_damage(dest, damage).as_slice(), &[]).ok();
                 }
             }
+
+            // Quick terminal slides over the cells and popups
+            if let Some((id, progress)) = compositor.quick_terminal.visible() {
+                render_quick_terminal(&mut frame, &terminal_manager, id, progress, bg_color, physical_size, damage);
+            }
+
+            if let Some(ref texture) = switcher_texture {
+                render_centered_panel(&mut frame, texture, physical_size, damage);
+                render_switcher_thumbnails(&mut frame, &terminal_manager, &switcher_thumbnails, texture, physical_size, damage);
+            }
+
+            if let Some(ref texture) = help_texture {
+                render_centered_panel(&mut frame, texture, physical_size, damage);
+            }
+
+            if let Some(ref texture) = filter_banner_texture {
+                render_banner(&mut frame, texture, physical_size, damage);
+            }
+
+            if let Some(ref texture) = filter_prompt_texture {
+                render_centered_panel(&mut frame, texture, physical_size, damage);
+            }
+
+            if let Some(ref texture) = quit_texture {
+                render_centered_panel(&mut frame, texture, physical_size, damage);
+            }
+
+            render_plugin_overlays(&mut frame, &plugin_overlay_textures, physical_size, damage);
+
+            // Frame-time HUD goes on top of everything but the pointer
+            if let Some(ref texture) = hud_texture {
+                render_hud(&mut frame, texture, physical_size, damage);
+            }
+
+            if let Some(ref texture) = cursor_texture {
+                render_cursor(&mut frame, texture, compositor.pointer_position, damage);
+            }
         }
 
-        // Submit the rendered buffer to X11
-        if let Err(e) = x11_surface.submit() {
-            tracing::warn!(error = ?e, "Failed to submit X11 surface");
+        // Submit the rendered buffer to the host window
+        {
+            let _submit_phase = frame_stats.phase(FramePhase::Submit);
+            if let Err(e) = host.submit() {
+                tracing::warn!(error = ?e, "Failed to submit host window buffer");
+            }
         }
+        frame_stats.end_frame(now, damage.size.w as i64 * damage.size.h as i64);
 
         // Update render timestamp for frame rate limiting
         last_render_time = now;
 
-        // Send frame callbacks to all toplevel surfaces and their popups
-        crate::frame::send_all_frame_callbacks(&compositor, &output);
+        // Presentation feedback for the submitted frame, then frame callbacks
+        // to all toplevel surfaces and their popups
+        crate::presentation::frame_submitted(&mut compositor, &output);
+        crate::presentation::send_frame_callbacks(&mut compositor, &output, true);
 
         // Flush clients
         compositor.display_handle.flush_clients()?;
-
-        // Dispatch calloop events
-        // With X11 backend, calloop properly handles all events including X11 input
-        // Use 16ms timeout for ~60fps frame rate
-        event_loop
-            .dispatch(Some(Duration::from_millis(16)), &mut compositor)
-            .map_err(|e| anyhow::anyhow!("event loop error: {e}"))?;
     }
 
     // Terminate xwayland-satellite on compositor shutdown
-    if let Some(mut monitor) = compositor.xwayland_satellite.take() {
+    if let Some(mut monitor) = compositor.xwayland.satellite.take().filter(|m| m.exit_status.is_none()) {
         if let Err(e) = monitor.child.kill() {
             tracing::warn!(?e, "Failed to kill xwayland-satellite");
         }
@@ -1,5 +1,7 @@
 //! Runtime configuration
 
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 /// Color theme for the terminal (config file format)
@@ -29,6 +31,10 @@ impl Theme {
     }
 }
 
+/// Word characters of double-click selection, as in GNOME Terminal: enough
+/// for paths, URLs and email addresses to select in one go
+pub const DEFAULT_WORD_CHARS: &str = "-#%&+,./:=?@_~";
+
 /// Compositor configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -36,12 +42,19 @@ pub struct Config {
     /// Color theme (light or dark)
     pub theme: Theme,
 
+    /// Switch between light and dark with the desktop's color scheme
+    /// (`theme` is used when the desktop has no preference)
+    pub follow_system_theme: bool,
+
     /// Font size in pixels (default: 14.0)
     pub font_size: f32,
 
     /// Background color (ARGB) - overrides theme default if set
     pub background_color: [f32; 4],
 
+    /// Image or gradient drawn behind the stack instead of `background_color`
+    pub background: BackgroundConfig,
+
     /// Gap between windows (pixels)
     /// TODO: not yet applied in layout calculation
     pub window_gap: u32,
@@ -49,16 +62,122 @@ pub struct Config {
     /// Minimum window height (pixels)
     pub min_window_height: u32,
 
-    /// Maximum window height (pixels, 0 = unlimited)
-    /// TODO: not yet enforced in layout/render
+    /// Maximum external window height (pixels, 0 = unlimited)
+    /// Taller windows are shown scaled down to this height until clicked
     pub max_window_height: u32,
 
+    /// Rows unfocused terminal cells grow to at most until focused (0 = no cap)
+    pub background_max_rows: u16,
+
+    /// Radius of the cells' rounded corners (pixels, 0 = square)
+    pub corner_radius: u32,
+
+    /// Opacity of the shadow around cells (0.0 = none, 1.0 = black)
+    pub shadow_intensity: f32,
+
     /// Scroll speed multiplier
     pub scroll_speed: f64,
 
     /// Auto-scroll when focused window grows
     pub auto_scroll: bool,
 
+    /// Collapse runs of identical output lines in command cells to `line ×N`
+    pub fold_repeated_lines: bool,
+
+    /// Record command cells' output for `termstack replay` (see `recording`)
+    pub record_sessions: bool,
+
+    /// Share of the screen height the drop-down quick terminal covers
+    pub quick_terminal_height: f32,
+
+    /// Whether the do-not-disturb keybinding also hides all but the focused cell
+    pub dnd_focus_mode: bool,
+
+    /// Pure black and white terminal colors, with text held to WCAG AA contrast
+    pub high_contrast: bool,
+
+    /// Minimum contrast ratio of terminal text against its background
+    /// (1.0 = leave palettes alone, 4.5 = WCAG AA, 7.0 = AAA)
+    pub minimum_contrast: f32,
+
+    /// Join `->`, `!=`, `===` and the like into ligatures in terminals, if the
+    /// terminal font has them (Fira Code, JetBrains Mono, ...)
+    pub ligatures: bool,
+
+    /// Turn off animations (the quick terminal appears and disappears at once)
+    pub reduce_motion: bool,
+
+    /// Copy text to PRIMARY as soon as it is selected with the mouse
+    pub copy_on_select: bool,
+
+    /// Copy mouse selections to CLIPBOARD too, so Ctrl+V pastes them
+    pub copy_on_select_clipboard: bool,
+
+    /// Trim trailing whitespace from each line of copied selections
+    pub trim_selection_whitespace: bool,
+
+    /// Punctuation that double-click selection counts as part of a word
+    /// (letters and digits always are)
+    pub word_chars: String,
+
+    /// Close the cells of commands that succeed within this many milliseconds
+    /// (and print fewer than `ephemeral_max_lines` lines) after a moment (0 = never)
+    pub ephemeral_command_ms: u64,
+
+    /// Output lines a command must stay under for its cell to close by itself
+    pub ephemeral_max_lines: u32,
+
+    /// Write the output of cells closed by `ephemeral_command_ms` into the
+    /// shell that ran the command, above its prompt
+    pub ephemeral_merge: bool,
+
+    /// Drop a spawn of the same command from the same terminal within this many
+    /// milliseconds of the last one, e.g. from a double Enter (0 = never)
+    pub spawn_dedup_ms: u64,
+
+    /// Command lines starting with this run in the shell they were typed in
+    /// instead of a new cell, e.g. `"!"` or `" "` (empty = always a new cell)
+    pub inline_prefix: String,
+
+    /// Offer a tree or table view of finished commands' JSON or CSV output
+    pub smart_output: bool,
+
+    /// Hide the launching terminal while a foreground `gui` app runs
+    /// (`gui -b` keeps it visible for a single launch)
+    pub hide_gui_launcher: bool,
+
+    /// Seconds a closed cell can be brought back with the undo keybinding
+    pub undo_seconds: u64,
+
+    /// Release the GPU textures of cells off-screen and unchanged for this many
+    /// seconds; they are redrawn when scrolled back into view (0 = never)
+    pub texture_evict_secs: u64,
+
+    /// Compress the scrollback of cells whose command exited and that have
+    /// been left alone for this many seconds (0 = never)
+    pub compress_idle_secs: u64,
+
+    /// Open a fresh shell when the last cell exits instead of quitting
+    /// (quit with Super+Q twice or `termstack quit`)
+    pub respawn_last_shell: bool,
+
+    /// Draw frames without waiting for the previous one to reach the screen
+    /// (X11 backend): lower latency, but frames may be dropped
+    pub allow_tearing: bool,
+
+    /// Closes kept for undo (0 = closing is final)
+    pub undo_depth: usize,
+
+    /// Closed command cells whose output `termstack archive` keeps (0 = none)
+    pub archive_size: usize,
+
+    /// Minutes a closed command cell's output stays in the archive
+    pub archive_minutes: u64,
+
+    /// Lua plugins to load from `plugin_dir()`, by file name without `.lua`
+    /// (plugins not listed here never run)
+    pub plugins: Vec<String>,
+
     /// Keyboard configuration
     pub keyboard: KeyboardConfig,
 
@@ -66,6 +185,24 @@ pub struct Config {
     /// Supports prefix matching with "*" (e.g., "org.gnome.*")
     pub csd_apps: Vec<String>,
 
+    /// Extra environment for GUI apps launched with `gui`, applied in order
+    pub gui_env: Vec<GuiEnvOverride>,
+
+    /// Placement, size and decorations for new windows, applied in order
+    pub window_rules: Vec<WindowRule>,
+
+    /// Per-app decoration choice by app_id, overriding both xdg-decoration
+    /// negotiation and `csd_apps` (written when toggled from the title bar)
+    pub decorations: BTreeMap<String, Decorations>,
+
+    /// Named command templates for `termstack tpl NAME ARGS`
+    /// (see `command_templates` for the placeholders)
+    pub commands: BTreeMap<String, String>,
+
+    /// TUI sizing by program name ("*" at either end matches anything),
+    /// overriding the alternate screen detection
+    pub tui: BTreeMap<String, TuiMode>,
+
     /// Maximum number of terminals allowed (default: 100)
     /// Prevents file descriptor exhaustion
     pub max_terminals: usize,
@@ -88,15 +225,53 @@ impl Default for Config {
         let theme = Theme::default();
         Self {
             background_color: theme.background_color(),
+            background: BackgroundConfig::default(),
             theme,
+            follow_system_theme: false,
             font_size: 14.0,
             window_gap: 0,
             min_window_height: 50,
             max_window_height: 0,
+            background_max_rows: 0,
+            corner_radius: 0,
+            shadow_intensity: 0.0,
             scroll_speed: 1.0,
             auto_scroll: true,
+            fold_repeated_lines: true,
+            record_sessions: false,
+            quick_terminal_height: 0.4,
+            dnd_focus_mode: false,
+            high_contrast: false,
+            minimum_contrast: 1.0,
+            ligatures: false,
+            reduce_motion: false,
+            copy_on_select: true,
+            copy_on_select_clipboard: false,
+            trim_selection_whitespace: true,
+            word_chars: DEFAULT_WORD_CHARS.to_string(),
+            ephemeral_command_ms: 0,
+            ephemeral_max_lines: 5,
+            ephemeral_merge: false,
+            spawn_dedup_ms: 500,
+            inline_prefix: String::new(),
+            smart_output: true,
+            hide_gui_launcher: true,
+            texture_evict_secs: 60,
+            compress_idle_secs: 120,
+            respawn_last_shell: false,
+            allow_tearing: false,
+            undo_seconds: 30,
+            undo_depth: 10,
+            archive_size: 50,
+            archive_minutes: 60,
+            plugins: Vec::new(),
             keyboard: KeyboardConfig::default(),
             csd_apps: Vec::new(),
+            gui_env: Vec::new(),
+            window_rules: Vec::new(),
+            decorations: BTreeMap::new(),
+            commands: BTreeMap::new(),
+            tui: BTreeMap::new(),
             max_terminals: 100,
             max_gui_windows: 50,
             max_dead_terminals: 20,
@@ -105,6 +280,174 @@ impl Default for Config {
     }
 }
 
+/// How a command's cell is sized, whatever the screen it draws on
+///
+/// ```toml
+/// [tui]
+/// dialog = "always"  # curses without the alternate screen
+/// "tig*" = "never"   # stays sized to its output
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TuiMode {
+    /// Full height from the start, like a TUI in the alternate screen
+    Always,
+    /// Sized to its output even in the alternate screen
+    Never,
+}
+
+/// Environment overrides for one GUI app
+///
+/// ```toml
+/// [[gui_env]]
+/// app = "gimp"
+/// env = { GDK_SCALE = "2" }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuiEnvOverride {
+    /// Program name to match, with prefix matching via "*" suffix (e.g., "org.gnome.*")
+    pub app: String,
+    /// Variables to set; an empty value removes the variable
+    pub env: BTreeMap<String, String>,
+}
+
+/// Background behind the stack
+///
+/// ```toml
+/// [background]
+/// image = "~/Pictures/wallpaper.jpg"
+/// mode = "tiled"  # or "scaled" (default)
+///
+/// # or, without an image, a vertical gradient from top to bottom
+/// gradient = [[0.10, 0.10, 0.18, 1.0], [0.02, 0.02, 0.05, 1.0]]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackgroundConfig {
+    /// PNG or JPEG file; a leading `~/` is the home directory
+    pub image: Option<std::path::PathBuf>,
+    /// How the image covers the output
+    pub mode: BackgroundMode,
+    /// Top and bottom colors (RGBA, like `background_color`), used without an image
+    pub gradient: Option<[[f32; 4]; 2]>,
+}
+
+/// How a background image covers the output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackgroundMode {
+    /// Scaled to cover the output, keeping its aspect ratio (edges are cropped)
+    #[default]
+    Scaled,
+    /// Repeated at its own size from the top left
+    Tiled,
+}
+
+/// Who draws a window's title bar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decorations {
+    /// The app draws its own header (no compositor title bar)
+    Client,
+    /// The compositor draws the title bar
+    Server,
+}
+
+impl Decorations {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Client => "client",
+            Self::Server => "server",
+        }
+    }
+}
+
+/// Where a new window goes in the stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowPosition {
+    /// Above the cell it was launched from (its output terminal, else the focused cell)
+    AboveParent,
+    /// Below the cell it was launched from
+    BelowParent,
+    /// First cell of the stack
+    Top,
+    /// Last cell of the stack
+    Bottom,
+}
+
+/// Rule for new external windows
+///
+/// All conditions that are set must match; a rule without conditions matches
+/// every window. When several rules match, later ones override earlier ones.
+///
+/// ```toml
+/// [[window_rules]]
+/// app_id = "org.gnome.Calculator"
+/// position = "bottom"
+/// height = 400
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowRule {
+    /// app_id pattern, with "*" at either end for prefix/suffix matching
+    pub app_id: Option<String>,
+    /// Title pattern
+    pub title: Option<String>,
+    /// Program name pattern of the `gui` command that launched the window
+    pub command: Option<String>,
+    /// Where to insert the window (default: above its parent)
+    pub position: Option<WindowPosition>,
+    /// Initial height in pixels, including the title bar
+    pub height: Option<u32>,
+    /// Who draws the title bar (a choice made from the title bar still wins)
+    pub decorations: Option<Decorations>,
+}
+
+impl WindowRule {
+    /// Whether the rule applies to a window; unknown properties never match
+    pub fn matches(&self, app_id: Option<&str>, title: Option<&str>, command: &str) -> bool {
+        let matches = |pattern: &Option<String>, value: Option<&str>| {
+            pattern
+                .as_deref()
+                .map_or(true, |pattern| value.is_some_and(|value| matches_app_pattern(pattern, value)))
+        };
+        matches(&self.app_id, app_id)
+            && matches(&self.title, title)
+            && matches(&self.command, command_program(command))
+    }
+
+    /// Whether the rule looks at properties clients set after creating the window
+    pub fn needs_client_properties(&self) -> bool {
+        self.app_id.is_some() || self.title.is_some()
+    }
+
+    /// Actions of `later` on top of these
+    fn merge(&mut self, later: &WindowRule) {
+        self.position = later.position.or(self.position);
+        self.height = later.height.or(self.height);
+        self.decorations = later.decorations.or(self.decorations);
+    }
+}
+
+/// Combined actions of the rules matching a window, `None` if none match
+pub fn resolve_window_rules(
+    rules: &[WindowRule],
+    app_id: Option<&str>,
+    title: Option<&str>,
+    command: &str,
+) -> Option<WindowRule> {
+    rules
+        .iter()
+        .filter(|rule| rule.matches(app_id, title, command))
+        .fold(None, |merged: Option<WindowRule>, rule| {
+            let mut merged = merged.unwrap_or_default();
+            merged.merge(rule);
+            Some(merged)
+        })
+}
+
 /// Keyboard configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -141,6 +484,40 @@ impl Default for KeyboardConfig {
     }
 }
 
+/// Errors writing settings back to the config file
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigWriteError {
+    #[error("no config directory (neither XDG_CONFIG_HOME nor HOME is set)")]
+    NoConfigDir,
+    #[error("failed to access config file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("config file is not valid TOML: {0}")]
+    Parse(#[from] toml_edit::TomlError),
+    #[error("failed to serialize config: {0}")]
+    Serialize(#[from] toml::ser::Error),
+    #[error("{} already exists (use --force to overwrite)", .0.display())]
+    Exists(std::path::PathBuf),
+}
+
+/// Comment at the top of a config file written by `write_default`
+const DEFAULT_CONFIG_HEADER: &str = "\
+# termstack configuration
+#
+# These are the defaults; change what you need and delete the rest.
+# termstack reads this file at startup.
+";
+
+/// Errors reading one config file
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigFileError {
+    #[error("failed to read config file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("config file is not valid: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("invalid config value: {0}")]
+    Invalid(#[from] ConfigValidationError),
+}
+
 /// Configuration validation errors
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigValidationError {
@@ -150,8 +527,18 @@ pub enum ConfigValidationError {
     InvalidMinHeight(u32),
     #[error("max_window_height ({max}) is less than min_window_height ({min})")]
     MaxLessThanMin { min: u32, max: u32 },
+    #[error("corner_radius {0} out of range (must be 0..=64)")]
+    InvalidCornerRadius(u32),
+    #[error("shadow_intensity {0} out of range (must be 0.0..=1.0)")]
+    InvalidShadowIntensity(f32),
     #[error("scroll_speed {0} out of range (must be 0.1..=10.0)")]
     InvalidScrollSpeed(f64),
+    #[error("quick_terminal_height {0} out of range (must be 0.1..=1.0)")]
+    InvalidQuickTerminalHeight(f32),
+    #[error("minimum_contrast {0} out of range (must be 1.0..=21.0)")]
+    InvalidMinimumContrast(f32),
+    #[error("ephemeral_max_lines {0} out of range (must be 1..=1000)")]
+    InvalidEphemeralMaxLines(u32),
     #[error("max_terminals {0} out of range (must be 1..=1000)")]
     InvalidMaxTerminals(usize),
     #[error("max_gui_windows {0} out of range (must be 1..=1000)")]
@@ -164,6 +551,8 @@ pub enum ConfigValidationError {
     InvalidRepeatDelay(u32),
     #[error("repeat_rate {0} out of range (must be 1..=100)")]
     InvalidRepeatRate(u32),
+    #[error("window_rules height {0} out of range (must be 20..=10000)")]
+    InvalidRuleHeight(u32),
 }
 
 impl Config {
@@ -181,9 +570,24 @@ impl Config {
                 max: self.max_window_height,
             });
         }
+        if self.corner_radius > 64 {
+            return Err(ConfigValidationError::InvalidCornerRadius(self.corner_radius));
+        }
+        if !(0.0..=1.0).contains(&self.shadow_intensity) {
+            return Err(ConfigValidationError::InvalidShadowIntensity(self.shadow_intensity));
+        }
         if !(0.1..=10.0).contains(&self.scroll_speed) {
             return Err(ConfigValidationError::InvalidScrollSpeed(self.scroll_speed));
         }
+        if !(0.1..=1.0).contains(&self.quick_terminal_height) {
+            return Err(ConfigValidationError::InvalidQuickTerminalHeight(self.quick_terminal_height));
+        }
+        if !(1.0..=21.0).contains(&self.minimum_contrast) {
+            return Err(ConfigValidationError::InvalidMinimumContrast(self.minimum_contrast));
+        }
+        if !(1..=1000).contains(&self.ephemeral_max_lines) {
+            return Err(ConfigValidationError::InvalidEphemeralMaxLines(self.ephemeral_max_lines));
+        }
         if !(1..=1000).contains(&self.max_terminals) {
             return Err(ConfigValidationError::InvalidMaxTerminals(self.max_terminals));
         }
@@ -202,38 +606,103 @@ impl Config {
         if !(1..=100).contains(&self.keyboard.repeat_rate) {
             return Err(ConfigValidationError::InvalidRepeatRate(self.keyboard.repeat_rate));
         }
+        if let Some(height) = self
+            .window_rules
+            .iter()
+            .filter_map(|rule| rule.height)
+            .find(|height| !(20..=10000).contains(height))
+        {
+            return Err(ConfigValidationError::InvalidRuleHeight(height));
+        }
         Ok(())
     }
 
-    /// Load configuration from file, falling back to defaults
-    pub fn load() -> Self {
-        let config_paths = [
+    /// Config file locations, in order of precedence
+    pub fn config_paths() -> [Option<std::path::PathBuf>; 2] {
+        [
             dirs::config_dir().map(|p| p.join("termstack/config.toml")),
             Some(std::path::PathBuf::from("/etc/termstack/config.toml")),
-        ];
+        ]
+    }
+
+    /// The user's config file, where settings are written back to
+    pub fn user_config_path() -> Option<std::path::PathBuf> {
+        dirs::config_dir().map(|p| p.join("termstack/config.toml"))
+    }
+
+    /// Whether no config file exists yet (termstack has not been set up)
+    pub fn is_first_run() -> bool {
+        Self::config_paths().into_iter().flatten().all(|path| !path.exists())
+    }
+
+    /// Write the default configuration to `path`, refusing to overwrite
+    /// an existing file unless `force` is set
+    pub fn write_default(path: &std::path::Path, force: bool) -> Result<(), ConfigWriteError> {
+        if path.exists() && !force {
+            return Err(ConfigWriteError::Exists(path.to_path_buf()));
+        }
+        let body = toml::to_string_pretty(&Self::default())?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, format!("{DEFAULT_CONFIG_HEADER}\n{body}"))?;
+        tracing::info!(?path, "wrote default configuration");
+        Ok(())
+    }
+
+    /// Remember the decoration choice for an app in the user's config file
+    ///
+    /// Only the `[decorations]` entry is touched; comments and formatting of
+    /// the rest of the file are preserved.
+    pub fn save_decoration(app_id: &str, decorations: Decorations) -> Result<(), ConfigWriteError> {
+        let path = Self::user_config_path().ok_or(ConfigWriteError::NoConfigDir)?;
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let updated = set_decoration(&content, app_id, decorations)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, updated)?;
+        tracing::info!(?path, app_id, ?decorations, "saved decoration override");
+        Ok(())
+    }
+
+    /// Directory of the Lua plugins enabled with `plugins`
+    pub fn plugin_dir() -> Option<std::path::PathBuf> {
+        dirs::config_dir().map(|p| p.join("termstack/plugins"))
+    }
+
+    /// Read, parse and validate one config file
+    pub fn from_file(path: &std::path::Path) -> Result<Self, ConfigFileError> {
+        let content = std::fs::read_to_string(path)?;
+        let mut config: Config = toml::from_str(&content)?;
+        config.validate()?;
+        // Apply theme-based background if not explicitly set
+        // (check if it's still the dark default when theme is light)
+        config.apply_theme_defaults();
+        Ok(config)
+    }
+
+    /// Load configuration from file, falling back to defaults
+    pub fn load() -> Self {
+        let config_paths = Self::config_paths();
 
         for path in config_paths.into_iter().flatten() {
             if path.exists() {
-                match std::fs::read_to_string(&path) {
-                    Ok(content) => match toml::from_str::<Config>(&content) {
-                        Ok(mut config) => {
-                            // Validate before using
-                            if let Err(e) = config.validate() {
-                                tracing::error!(?path, error = %e, "invalid config value, using defaults");
-                                return Self::default();
-                            }
-                            // Apply theme-based background if not explicitly set
-                            // (check if it's still the dark default when theme is light)
-                            config.apply_theme_defaults();
-                            tracing::info!(?path, ?config.theme, csd_apps = ?config.csd_apps, "loaded configuration");
-                            return config;
-                        }
-                        Err(e) => {
-                            tracing::warn!(?path, error = %e, "failed to parse config");
-                        }
-                    },
+                match Self::from_file(&path) {
+                    Ok(config) => {
+                        tracing::info!(?path, ?config.theme, csd_apps = ?config.csd_apps, "loaded configuration");
+                        return config;
+                    }
+                    Err(e @ ConfigFileError::Invalid(_)) => {
+                        tracing::error!(?path, error = %e, "invalid config value, using defaults");
+                        return Self::default();
+                    }
                     Err(e) => {
-                        tracing::warn!(?path, error = %e, "failed to read config");
+                        tracing::warn!(?path, error = %e, "failed to load config");
                     }
                 }
             }
@@ -253,16 +722,93 @@ impl Config {
         }
     }
 
+    /// Switch to `theme` at runtime
+    ///
+    /// The background color follows unless it was customized.
+    pub fn switch_theme(&mut self, theme: Theme) {
+        if self.background_color == self.theme.background_color() {
+            self.background_color = theme.background_color();
+        }
+        self.theme = theme;
+    }
+
     /// Check if an app_id matches the CSD apps patterns
     /// Supports exact match and prefix match with "*" suffix (e.g., "org.gnome.*")
     pub fn is_csd_app(&self, app_id: &str) -> bool {
-        self.csd_apps.iter().any(|pattern| {
-            if let Some(prefix) = pattern.strip_suffix('*') {
-                app_id.starts_with(prefix)
+        self.csd_apps.iter().any(|pattern| matches_app_pattern(pattern, app_id))
+    }
+}
+
+/// Exact match; a "*" at the end matches any suffix, at the start any prefix
+fn matches_app_pattern(pattern: &str, name: &str) -> bool {
+    match (pattern.strip_prefix('*'), pattern.strip_suffix('*')) {
+        (Some(_), Some(_)) if pattern.len() > 1 => name.contains(&pattern[1..pattern.len() - 1]),
+        (Some(suffix), None) => name.ends_with(suffix),
+        (_, Some(prefix)) => name.starts_with(prefix),
+        (None, None) => name == pattern,
+    }
+}
+
+/// `content` with `decorations.<app_id>` set, keeping everything else as is
+fn set_decoration(content: &str, app_id: &str, decorations: Decorations) -> Result<String, toml_edit::TomlError> {
+    let mut document: toml_edit::DocumentMut = content.parse()?;
+    let table = document
+        .entry("decorations")
+        .or_insert_with(toml_edit::table)
+        .as_table_like_mut();
+    match table {
+        Some(table) => {
+            table.insert(app_id, toml_edit::value(decorations.as_str()));
+        }
+        // `decorations` exists but isn't a table; replace it
+        None => {
+            let mut table = toml_edit::Table::new();
+            table.insert(app_id, toml_edit::value(decorations.as_str()));
+            document.insert("decorations", toml_edit::Item::Table(table));
+        }
+    }
+    Ok(document.to_string())
+}
+
+/// Program name of a shell command line: the first word that isn't a
+/// `VAR=value` assignment or `env`, without its directory
+pub fn command_program(command: &str) -> Option<&str> {
+    command
+        .split_whitespace()
+        .find(|word| !word.contains('=') && *word != "env")
+        .map(|word| word.rsplit('/').next().unwrap_or(word))
+}
+
+/// The TUI override for `command`'s program: an exact name, else the
+/// longest matching pattern
+pub fn tui_mode(overrides: &BTreeMap<String, TuiMode>, command: &str) -> Option<TuiMode> {
+    let program = command_program(command)?;
+    overrides.get(program).or_else(|| {
+        overrides
+            .iter()
+            .filter(|(pattern, _)| pattern.contains('*') && matches_app_pattern(pattern, program))
+            .max_by_key(|(pattern, _)| pattern.len())
+            .map(|(_, mode)| mode)
+    }).copied()
+}
+
+/// Apply the overrides matching `command` to a GUI app's environment
+pub fn apply_gui_env(
+    overrides: &[GuiEnvOverride],
+    command: &str,
+    env: &mut std::collections::HashMap<String, String>,
+) {
+    let Some(program) = command_program(command) else {
+        return;
+    };
+    for entry in overrides.iter().filter(|entry| matches_app_pattern(&entry.app, program)) {
+        for (key, value) in &entry.env {
+            if value.is_empty() {
+                env.remove(key);
             } else {
-                app_id == pattern
+                env.insert(key.clone(), value.clone());
             }
-        })
+        }
     }
 }
 
@@ -314,9 +860,23 @@ mod tests {
         assert_eq!(config.window_gap, 0);
         assert_eq!(config.min_window_height, 50);
         assert_eq!(config.max_window_height, 0); // 0 = unlimited
+        assert_eq!(config.background_max_rows, 0); // 0 = no cap
         assert!((config.scroll_speed - 1.0).abs() < 0.01);
         assert!(config.auto_scroll);
+        assert!(config.fold_repeated_lines);
+        assert!(config.copy_on_select && !config.copy_on_select_clipboard);
+        assert!(config.trim_selection_whitespace);
+        assert!(config.word_chars.contains('/') && !config.word_chars.contains(' '));
+        assert_eq!(config.spawn_dedup_ms, 500);
+        assert!(config.inline_prefix.is_empty());
+        assert!(config.smart_output);
+        assert!(config.hide_gui_launcher);
+        assert_eq!((config.texture_evict_secs, config.compress_idle_secs), (60, 120));
+        assert!(!config.respawn_last_shell && !config.allow_tearing);
+        assert_eq!((config.undo_seconds, config.undo_depth), (30, 10));
+        assert_eq!((config.archive_size, config.archive_minutes), (50, 60));
         assert!(config.csd_apps.is_empty());
+        assert!(config.plugins.is_empty());
     }
 
     #[test]
@@ -386,6 +946,27 @@ mod tests {
         assert!(config.is_csd_app("anything"));
     }
 
+    // ========== Default config file tests ==========
+
+    #[test]
+    fn written_default_config_loads_and_is_not_overwritten() {
+        let dir = std::env::temp_dir().join(format!("termstack-config-test-{}", std::process::id()));
+        let path = dir.join("termstack/config.toml");
+
+        Config::write_default(&path, false).unwrap();
+        let loaded = Config::from_file(&path).unwrap();
+        assert_eq!(loaded.font_size, Config::default().font_size);
+        assert_eq!(loaded.theme, Config::default().theme);
+
+        std::fs::write(&path, "font_size = 18.0\n").unwrap();
+        assert!(matches!(Config::write_default(&path, false), Err(ConfigWriteError::Exists(_))));
+        assert!((Config::from_file(&path).unwrap().font_size - 18.0).abs() < 0.01);
+        Config::write_default(&path, true).unwrap();
+        assert_eq!(Config::from_file(&path).unwrap().font_size, Config::default().font_size);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     // ========== TOML roundtrip tests ==========
 
     #[test]
@@ -432,6 +1013,114 @@ mod tests {
         assert!(parsed.csd_apps.is_empty());
     }
 
+    #[test]
+    fn gui_env_overrides_apply_in_order() {
+        let parsed: Config = toml::from_str(r#"
+            [[gui_env]]
+            app = "gimp*"
+            env = { GDK_BACKEND = "wayland", GDK_SCALE = "2" }
+
+            [[gui_env]]
+            app = "gimp-2.10"
+            env = { GDK_SCALE = "1", http_proxy = "" }
+        "#).expect("Failed to parse gui_env");
+
+        let mut env = std::collections::HashMap::from([
+            ("GDK_BACKEND".to_string(), "x11".to_string()),
+            ("http_proxy".to_string(), "http://proxy:3128".to_string()),
+        ]);
+        apply_gui_env(&parsed.gui_env, "LANG=C /usr/bin/gimp-2.10 image.png", &mut env);
+
+        assert_eq!(env.get("GDK_BACKEND").map(String::as_str), Some("wayland"));
+        assert_eq!(env.get("GDK_SCALE").map(String::as_str), Some("1"));
+        assert!(!env.contains_key("http_proxy"));
+
+        // Other apps are left alone
+        let mut env = std::collections::HashMap::new();
+        apply_gui_env(&parsed.gui_env, "env FOO=1 firefox", &mut env);
+        assert!(env.is_empty());
+    }
+
+    #[test]
+    fn saved_decoration_keeps_rest_of_file() {
+        let original = "# my settings\ntheme = \"light\" # bright\n";
+        let updated = set_decoration(original, "org.gnome.Nautilus", Decorations::Client).unwrap();
+        assert!(updated.starts_with(original));
+
+        let updated = set_decoration(&updated, "org.gnome.Nautilus", Decorations::Server).unwrap();
+        let updated = set_decoration(&updated, "gimp", Decorations::Client).unwrap();
+        let parsed: Config = toml::from_str(&updated).unwrap();
+        assert_eq!(parsed.theme, Theme::Light);
+        assert_eq!(parsed.decorations.get("org.gnome.Nautilus"), Some(&Decorations::Server));
+        assert_eq!(parsed.decorations.get("gimp"), Some(&Decorations::Client));
+
+        assert!(set_decoration("not [valid", "gimp", Decorations::Client).is_err());
+    }
+
+    #[test]
+    fn window_rules_merge_in_order() {
+        let parsed: Config = toml::from_str(r#"
+            [[window_rules]]
+            command = "gimp*"
+            position = "bottom"
+            height = 600
+
+            [[window_rules]]
+            title = "*Preferences*"
+            position = "below_parent"
+            decorations = "client"
+        "#).expect("Failed to parse window_rules");
+        assert!(parsed.validate().is_ok());
+
+        let rule = resolve_window_rules(&parsed.window_rules, None, Some("GIMP Preferences"), "gimp").unwrap();
+        assert_eq!(rule.position, Some(WindowPosition::BelowParent));
+        assert_eq!(rule.height, Some(600));
+        assert_eq!(rule.decorations, Some(Decorations::Client));
+
+        // A title condition can't match before the client sets a title
+        let rule = resolve_window_rules(&parsed.window_rules, None, None, "gimp").unwrap();
+        assert_eq!(rule.position, Some(WindowPosition::Bottom));
+        assert!(resolve_window_rules(&parsed.window_rules, Some("firefox"), None, "firefox").is_none());
+        assert!(parsed.window_rules[1].needs_client_properties());
+    }
+
+    #[test]
+    fn window_rule_height_is_validated() {
+        let mut config = Config::default();
+        config.window_rules.push(WindowRule { height: Some(5), ..Default::default() });
+        assert!(matches!(config.validate(), Err(ConfigValidationError::InvalidRuleHeight(5))));
+    }
+
+    #[test]
+    fn app_patterns_match_either_end() {
+        assert!(matches_app_pattern("org.gnome.*", "org.gnome.Maps"));
+        assert!(matches_app_pattern("*Preferences", "GIMP Preferences"));
+        assert!(matches_app_pattern("*Pref*", "GIMP Preferences"));
+        assert!(matches_app_pattern("*", "anything"));
+        assert!(!matches_app_pattern("firefox", "firefox-esr"));
+    }
+
+    #[test]
+    fn command_program_skips_assignments() {
+        assert_eq!(command_program("gimp"), Some("gimp"));
+        assert_eq!(command_program("env A=1 B=2 /opt/bin/app --flag"), Some("app"));
+        assert_eq!(command_program("  "), None);
+    }
+
+    #[test]
+    fn tui_overrides_match_the_program() {
+        let config: Config = toml::from_str(
+            "[tui]\ndialog = \"always\"\n\"tig*\" = \"never\"\n\"*\" = \"always\"\ntigger = \"always\"\n",
+        ).unwrap();
+        assert_eq!(tui_mode(&config.tui, "dialog --menu x 10 40 3"), Some(TuiMode::Always));
+        assert_eq!(tui_mode(&config.tui, "TERM=xterm /usr/bin/tig log"), Some(TuiMode::Never));
+        // An exact name wins over patterns, a longer pattern over a shorter one
+        assert_eq!(tui_mode(&config.tui, "htop"), Some(TuiMode::Always));
+        assert_eq!(tui_mode(&config.tui, "tigger"), Some(TuiMode::Always));
+        assert_eq!(tui_mode(&Config::default().tui, "vim"), None);
+        assert!(toml::from_str::<Config>("[tui]\nvim = \"sometimes\"\n").is_err());
+    }
+
     #[test]
     fn config_invalid_toml_returns_error() {
         let invalid_toml = "this is not valid { toml [";
@@ -457,6 +1146,18 @@ mod tests {
         assert_eq!(config.background_color, expected);
     }
 
+    #[test]
+    fn switch_theme_keeps_custom_background() {
+        let mut config = Config::default();
+        config.switch_theme(Theme::Light);
+        assert_eq!(config.background_color, Theme::Light.background_color());
+
+        let mut config = Config { background_color: [0.2, 0.0, 0.2, 1.0], ..Config::default() };
+        config.switch_theme(Theme::Light);
+        assert_eq!(config.theme, Theme::Light);
+        assert_eq!(config.background_color, [0.2, 0.0, 0.2, 1.0]);
+    }
+
     // ========== Config::validate tests ==========
 
     #[test]
@@ -501,6 +1202,15 @@ mod tests {
         assert!(config.validate().is_ok());
     }
 
+    #[test]
+    fn validate_rejects_shadow_intensity_above_one() {
+        let mut config = Config { shadow_intensity: 1.5, ..Config::default() };
+        assert!(matches!(config.validate(), Err(ConfigValidationError::InvalidShadowIntensity(_))));
+        config.shadow_intensity = 0.3;
+        config.corner_radius = 100;
+        assert!(matches!(config.validate(), Err(ConfigValidationError::InvalidCornerRadius(100))));
+    }
+
     #[test]
     fn validate_rejects_scroll_speed_too_low() {
         let mut config = Config::default();
@@ -508,6 +1218,28 @@ mod tests {
         assert!(matches!(config.validate(), Err(ConfigValidationError::InvalidScrollSpeed(_))));
     }
 
+    #[test]
+    fn validate_rejects_quick_terminal_height_out_of_range() {
+        let mut config = Config { quick_terminal_height: 0.0, ..Config::default() };
+        assert!(matches!(config.validate(), Err(ConfigValidationError::InvalidQuickTerminalHeight(_))));
+        config.quick_terminal_height = 1.0;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_minimum_contrast_out_of_range() {
+        let mut config = Config { minimum_contrast: 0.5, ..Config::default() };
+        assert!(matches!(config.validate(), Err(ConfigValidationError::InvalidMinimumContrast(_))));
+        config.minimum_contrast = 7.0;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_zero_ephemeral_max_lines() {
+        let config = Config { ephemeral_max_lines: 0, ..Config::default() };
+        assert!(matches!(config.validate(), Err(ConfigValidationError::InvalidEphemeralMaxLines(0))));
+    }
+
     #[test]
     fn validate_rejects_zero_max_terminals() {
         let mut config = Config::default();
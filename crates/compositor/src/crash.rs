@@ -0,0 +1,415 @@
+//! Crash reporting and best-effort session recovery
+//!
+//! `install_panic_hook()` replaces the panic hook with one that writes a crash
+//! report to `$XDG_STATE_HOME/termstack/crashes/` before the default hook runs
+//! and the process goes down. The report contains the panic message, a
+//! backtrace, the recent in-memory log lines, a summary of the stack and the
+//! active configuration.
+//!
+//! The panic hook can't reach the compositor state (it's owned by the main
+//! loop), so the main loop publishes a `SessionSnapshot` every
+//! `SNAPSHOT_INTERVAL` via `update_snapshot()`. On panic the snapshot is also
+//! saved as `session.json`; the next launch picks it up with
+//! `take_saved_session()` and `restore_session()` recreates the stack: the
+//! shell's working directory is restored and earlier command cells come back
+//! as static entries (commands are never re-run).
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::state::{LayoutNode, StackWindow, TermStack};
+use crate::terminal_manager::TerminalManager;
+
+/// How often the main loop refreshes the snapshot used by the panic hook
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Session file name inside `state_dir()`
+const SESSION_FILE: &str = "session.json";
+
+/// What kind of cell a `CellSummary` describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CellKind {
+    /// Interactive shell (no title bar)
+    Shell,
+    /// Command or builtin output terminal
+    Command,
+    /// External Wayland/X11 window
+    Window,
+}
+
+/// One cell of the stack, as recorded for crash reports and recovery
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CellSummary {
+    pub kind: CellKind,
+    /// Terminal title or the command that launched the window
+    pub title: String,
+    pub height: i32,
+    /// Name given with `termstack title` or the title escape sequence
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_title: Option<String>,
+    /// Working directory of the shell process (shells only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+}
+
+/// Summary of the stack, cheap enough to refresh periodically
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionSnapshot {
+    pub cells: Vec<CellSummary>,
+    pub focused_index: Option<usize>,
+    pub scroll_offset: f64,
+}
+
+impl SessionSnapshot {
+    /// Build a snapshot of the current stack
+    pub fn capture(compositor: &TermStack, terminal_manager: &TerminalManager) -> Self {
+        let cells = compositor
+            .layout_nodes
+            .iter()
+            .map(|node| summarize_cell(node, terminal_manager))
+            .collect();
+
+        Self {
+            cells,
+            focused_index: compositor.focused_index(),
+            scroll_offset: compositor.scroll_offset,
+        }
+    }
+}
+
+fn summarize_cell(node: &LayoutNode, terminal_manager: &TerminalManager) -> CellSummary {
+    match &node.cell {
+        StackWindow::Terminal(id) => {
+            let terminal = terminal_manager.get(*id);
+            let is_shell = terminal.is_some_and(|t| !t.show_title_bar);
+            CellSummary {
+                kind: if is_shell { CellKind::Shell } else { CellKind::Command },
+                title: terminal.map(|t| t.title.clone()).unwrap_or_default(),
+                height: node.height,
+                custom_title: terminal.and_then(|t| t.custom_title.clone()),
+                cwd: terminal
+                    .filter(|_| is_shell)
+                    .and_then(|t| process_cwd(t.pid())),
+            }
+        }
+        StackWindow::External(entry) => CellSummary {
+            kind: CellKind::Window,
+            title: entry.command.clone(),
+            height: node.height,
+            custom_title: entry.custom_title.clone(),
+            cwd: None,
+        },
+    }
+}
+
+/// Current working directory of a process
+#[cfg(target_os = "linux")]
+pub(crate) fn process_cwd(pid: u32) -> Option<PathBuf> {
+    std::fs::read_link(format!("/proc/{}/cwd", pid)).ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn process_cwd(_pid: u32) -> Option<PathBuf> {
+    None
+}
+
+/// State shared with the panic hook
+struct CrashContext {
+    snapshot: Option<SessionSnapshot>,
+    last_update: Option<Instant>,
+    config_toml: Option<String>,
+}
+
+static CRASH_CONTEXT: Mutex<CrashContext> = Mutex::new(CrashContext {
+    snapshot: None,
+    last_update: None,
+    config_toml: None,
+});
+
+/// Directory crash reports are written to (`$XDG_STATE_HOME/termstack/crashes`)
+pub fn crash_dir() -> Option<PathBuf> {
+    crate::logging::state_dir().map(|p| p.join("crashes"))
+}
+
+/// Install the crash-reporting panic hook (chains to the previous hook)
+pub fn install_panic_hook(config: &Config) {
+    if let Ok(mut context) = CRASH_CONTEXT.lock() {
+        context.config_toml = toml::to_string_pretty(config).ok();
+    }
+
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        // Only the first panic gets a report; a panic while writing it would recurse
+        static REPORTED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+        if !REPORTED.swap(true, std::sync::atomic::Ordering::SeqCst) {
+            match write_crash_report(&info.to_string()) {
+                Some(path) => eprintln!("termstack crashed; report written to {}", path.display()),
+                None => eprintln!("termstack crashed; failed to write crash report"),
+            }
+        }
+        previous(info);
+    }));
+}
+
+/// Publish a fresh snapshot for the panic hook, at most once per `SNAPSHOT_INTERVAL`
+pub fn update_snapshot(compositor: &TermStack, terminal_manager: &TerminalManager) {
+    let Ok(mut context) = CRASH_CONTEXT.try_lock() else {
+        return;
+    };
+    let due = context
+        .last_update
+        .map_or(true, |t| t.elapsed() >= SNAPSHOT_INTERVAL);
+    if due {
+        context.snapshot = Some(SessionSnapshot::capture(compositor, terminal_manager));
+        context.last_update = Some(Instant::now());
+    }
+}
+
+/// Write the crash report and session file, returning the report path
+fn write_crash_report(panic_message: &str) -> Option<PathBuf> {
+    // Never block in the panic hook: the panic may have happened while the lock was held
+    let (snapshot, config_toml) = match CRASH_CONTEXT.try_lock() {
+        Ok(context) => (context.snapshot.clone(), context.config_toml.clone()),
+        Err(_) => (None, None),
+    };
+
+    let dir = crash_dir()?;
+    std::fs::create_dir_all(&dir).ok()?;
+
+    if let (Some(snapshot), Some(state_dir)) = (&snapshot, crate::logging::state_dir()) {
+        if let Err(e) = save_session(&state_dir, snapshot) {
+            eprintln!("failed to save session for recovery: {}", e);
+        }
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let path = dir.join(format!("crash-{}.txt", timestamp));
+    let report = format_report(
+        panic_message,
+        &std::backtrace::Backtrace::force_capture().to_string(),
+        &crate::logging::recent_log_lines(),
+        snapshot.as_ref(),
+        config_toml.as_deref(),
+    );
+    std::fs::write(&path, report).ok()?;
+    Some(path)
+}
+
+/// Assemble the crash report text
+fn format_report(
+    panic_message: &str,
+    backtrace: &str,
+    recent_logs: &[String],
+    snapshot: Option<&SessionSnapshot>,
+    config_toml: Option<&str>,
+) -> String {
+    let mut report = String::new();
+    let thread = std::thread::current();
+    let _ = writeln!(report, "termstack {} crash report", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(report, "thread: {}", thread.name().unwrap_or("<unnamed>"));
+    let _ = writeln!(report, "\n== panic ==\n{}", panic_message);
+    let _ = writeln!(report, "\n== backtrace ==\n{}", backtrace);
+
+    let _ = writeln!(report, "\n== stack ==");
+    match snapshot {
+        Some(snapshot) => {
+            let _ = writeln!(
+                report,
+                "focused: {:?}  scroll_offset: {:.1}",
+                snapshot.focused_index, snapshot.scroll_offset
+            );
+            for (i, cell) in snapshot.cells.iter().enumerate() {
+                let _ = writeln!(
+                    report,
+                    "[{}] {:?} height={} title={:?}{}{}",
+                    i,
+                    cell.kind,
+                    cell.height,
+                    cell.title,
+                    cell.custom_title
+                        .as_ref()
+                        .map(|name| format!(" name={:?}", name))
+                        .unwrap_or_default(),
+                    cell.cwd
+                        .as_ref()
+                        .map(|cwd| format!(" cwd={}", cwd.display()))
+                        .unwrap_or_default()
+                );
+            }
+        }
+        None => {
+            let _ = writeln!(report, "(no snapshot available)");
+        }
+    }
+
+    let _ = writeln!(report, "\n== recent log ==");
+    for line in recent_logs {
+        let _ = writeln!(report, "{}", line);
+    }
+
+    let _ = writeln!(report, "\n== config ==\n{}", config_toml.unwrap_or("(unavailable)"));
+    report
+}
+
+fn save_session(state_dir: &Path, snapshot: &SessionSnapshot) -> std::io::Result<()> {
+    std::fs::create_dir_all(state_dir)?;
+    let json = serde_json::to_string_pretty(snapshot)?;
+    std::fs::write(state_dir.join(SESSION_FILE), json)
+}
+
+/// Load and remove the session saved by a crash, if any
+pub fn take_saved_session() -> Option<SessionSnapshot> {
+    take_session_from(&crate::logging::state_dir()?)
+}
+
+fn take_session_from(state_dir: &Path) -> Option<SessionSnapshot> {
+    let path = state_dir.join(SESSION_FILE);
+    let content = std::fs::read_to_string(&path).ok()?;
+    // Remove first so a session that crashes us again isn't restored forever
+    let _ = std::fs::remove_file(&path);
+    match serde_json::from_str(&content) {
+        Ok(snapshot) => Some(snapshot),
+        Err(e) => {
+            tracing::warn!(?path, error = %e, "ignoring unreadable saved session");
+            None
+        }
+    }
+}
+
+/// Recreate the stack from a session saved by a crash
+///
+/// Must run before the initial terminal is spawned: that shell starts in the
+/// last shell's working directory (the compositor's own is left alone).
+/// Command cells come back as static entries showing only their title
+/// (and their name, if they had one).
+pub fn restore_session(
+    compositor: &mut TermStack,
+    terminal_manager: &mut TerminalManager,
+    snapshot: &SessionSnapshot,
+) {
+    let mut restored = 0;
+    for cell in &snapshot.cells {
+        match cell.kind {
+            CellKind::Command => {
+                match terminal_manager.create_builtin_terminal(&cell.title, "", "", true) {
+                    Ok(id) => {
+                        if let Some(terminal) = terminal_manager.get_mut(id) {
+                            terminal.custom_title = cell.custom_title.clone();
+                        }
+                        compositor.push_cell(StackWindow::Terminal(id), 0);
+                        restored += 1;
+                    }
+                    Err(e) => {
+                        tracing::warn!(title = %cell.title, error = ?e, "failed to restore cell");
+                    }
+                }
+            }
+            CellKind::Window => {
+                tracing::info!(command = %cell.title, "not relaunching GUI window from previous session");
+            }
+            CellKind::Shell => {}
+        }
+    }
+    compositor.invalidate_focused_index_cache();
+
+    let shell_cwd = snapshot
+        .cells
+        .iter()
+        .rev()
+        .find(|c| c.kind == CellKind::Shell)
+        .and_then(|c| c.cwd.as_ref());
+    if let Some(cwd) = shell_cwd {
+        if cwd.is_dir() {
+            terminal_manager.start_next_shell_in(cwd.clone());
+        } else {
+            tracing::warn!(?cwd, "shell working directory is gone, not restoring it");
+        }
+    }
+
+    tracing::info!(restored, cwd = ?shell_cwd, "restored session after crash");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_snapshot() -> SessionSnapshot {
+        SessionSnapshot {
+            cells: vec![
+                CellSummary {
+                    kind: CellKind::Command,
+                    title: "~/code> cargo build".to_string(),
+                    height: 200,
+                    custom_title: Some("release build".to_string()),
+                    cwd: None,
+                },
+                CellSummary {
+                    kind: CellKind::Window,
+                    title: "pqiv image.png".to_string(),
+                    height: 400,
+                    custom_title: None,
+                    cwd: None,
+                },
+                CellSummary {
+                    kind: CellKind::Shell,
+                    title: "Terminal".to_string(),
+                    height: 100,
+                    custom_title: None,
+                    cwd: Some(PathBuf::from("/home/user/code")),
+                },
+            ],
+            focused_index: Some(2),
+            scroll_offset: 42.0,
+        }
+    }
+
+    #[test]
+    fn report_contains_all_sections() {
+        let snapshot = sample_snapshot();
+        let report = format_report(
+            "panicked at src/foo.rs:1:1:\nboom",
+            "0: main",
+            &["INFO something happened".to_string()],
+            Some(&snapshot),
+            Some("theme = \"dark\""),
+        );
+
+        assert!(report.contains("boom"));
+        assert!(report.contains("0: main"));
+        assert!(report.contains("INFO something happened"));
+        assert!(report.contains("cargo build"));
+        assert!(report.contains("name=\"release build\""));
+        assert!(report.contains("cwd=/home/user/code"));
+        assert!(report.contains("theme = \"dark\""));
+    }
+
+    #[test]
+    fn report_without_snapshot() {
+        let report = format_report("boom", "", &[], None, None);
+        assert!(report.contains("(no snapshot available)"));
+        assert!(report.contains("(unavailable)"));
+    }
+
+    #[test]
+    fn saved_session_is_taken_once() {
+        let dir = std::env::temp_dir()
+            .join(format!("termstack-crash-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let snapshot = sample_snapshot();
+        save_session(&dir, &snapshot).unwrap();
+
+        assert_eq!(take_session_from(&dir), Some(snapshot));
+        assert_eq!(take_session_from(&dir), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,142 @@
+//! Damage tracking for the X11 backend
+//!
+//! The X11 swapchain hands back buffers that still hold the frame drawn into
+//! them a few frames ago; `x11_surface.buffer()` says how many (the buffer
+//! age). Rather than redrawing the whole window each frame, the render loop
+//! compares every cell with the last frame and redraws only the region that
+//! changed since the buffer was last used. Anything harder to follow
+//! (overlays, popups, a cross-window selection, a resize of the output)
+//! damages the whole output.
+
+use std::collections::VecDeque;
+
+use smithay::utils::{Physical, Rectangle, Size};
+
+use crate::cell_id::CellId;
+
+/// How many frames of damage are kept; older buffers are redrawn in full
+const MAX_BUFFER_AGE: usize = 4;
+
+/// A cell as drawn in one frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CellFrame {
+    pub id: CellId,
+    /// Bottom edge in render coordinates (Y=0 at the bottom)
+    pub y: i32,
+    pub height: i32,
+    /// Hash of everything else about the cell that shows: its texture or
+    /// surface contents, title bar, focus and running indicator
+    pub content: u64,
+}
+
+/// Recent frames' damage, kept by the X11 render loop
+#[derive(Debug)]
+pub struct DamageTracker {
+    size: Size<i32, Physical>,
+    /// How far drawing reaches beyond a cell (shadows)
+    margin: i32,
+    cells: Vec<CellFrame>,
+    /// Damage of recent frames, newest first (`None`: nothing changed)
+    history: VecDeque<Option<Rectangle<i32, Physical>>>,
+}
+
+impl DamageTracker {
+    pub fn new(margin: i32) -> Self {
+        Self { size: Size::default(), margin, cells: Vec::new(), history: VecDeque::new() }
+    }
+
+    /// Record this frame's cells and return the region to redraw into a buffer
+    /// of `age` (0: contents unknown). The region is empty when nothing the
+    /// buffer shows has changed.
+    pub fn frame(
+        &mut self,
+        size: Size<i32, Physical>,
+        cells: Vec<CellFrame>,
+        full: bool,
+        age: u8,
+    ) -> Rectangle<i32, Physical> {
+        let output = Rectangle::from_size(size);
+        let same_cells = self.cells.len() == cells.len()
+            && self.cells.iter().zip(&cells).all(|(old, new)| old.id == new.id);
+        let current = if full || size != self.size || !same_cells {
+            Some(output)
+        } else {
+            self.cells
+                .iter()
+                .zip(&cells)
+                .filter(|(old, new)| old != new)
+                .flat_map(|(old, new)| [self.cell_rect(old), self.cell_rect(new)])
+                .reduce(Rectangle::merge)
+        };
+        self.size = size;
+        self.cells = cells;
+        self.history.push_front(current);
+        self.history.truncate(MAX_BUFFER_AGE);
+
+        let age = usize::from(age);
+        if age == 0 || age > self.history.len() {
+            return output;
+        }
+        self.history
+            .iter()
+            .take(age)
+            .flatten()
+            .copied()
+            .reduce(Rectangle::merge)
+            .and_then(|damage| damage.intersection(output))
+            .unwrap_or_default()
+    }
+
+    fn cell_rect(&self, cell: &CellFrame) -> Rectangle<i32, Physical> {
+        Rectangle::new(
+            (-self.margin, cell.y - self.margin).into(),
+            (self.size.w + 2 * self.margin, cell.height + 2 * self.margin).into(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: (i32, i32) = (800, 600);
+
+    fn cells(contents: &[u64]) -> Vec<CellFrame> {
+        contents
+            .iter()
+            .enumerate()
+            .map(|(i, &content)| CellFrame {
+                id: CellId::from_bits(i as u64),
+                y: 600 - 100 * (i as i32 + 1),
+                height: 100,
+                content,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn only_changed_cells_are_redrawn() {
+        let mut damage = DamageTracker::new(0);
+        let output = Rectangle::from_size(SIZE.into());
+        assert_eq!(damage.frame(SIZE.into(), cells(&[1, 1, 1]), false, 0), output);
+        assert!(damage.frame(SIZE.into(), cells(&[1, 1, 1]), false, 1).is_empty());
+
+        let changed = damage.frame(SIZE.into(), cells(&[1, 2, 1]), false, 1);
+        assert_eq!(changed, Rectangle::new((0, 400).into(), (800, 100).into()));
+
+        assert_eq!(damage.frame(SIZE.into(), cells(&[1, 2, 1]), true, 1), output);
+    }
+
+    #[test]
+    fn older_buffers_get_the_damage_since_they_were_drawn() {
+        let mut damage = DamageTracker::new(0);
+        damage.frame(SIZE.into(), cells(&[1, 1, 1]), false, 0);
+        damage.frame(SIZE.into(), cells(&[2, 1, 1]), false, 1);
+        let since = damage.frame(SIZE.into(), cells(&[2, 1, 3]), false, 2);
+        assert_eq!(since, Rectangle::new((0, 300).into(), (800, 300).into()));
+
+        // Older than the history: everything
+        let output = Rectangle::from_size(SIZE.into());
+        assert_eq!(damage.frame(SIZE.into(), cells(&[2, 1, 3]), false, 5), output);
+    }
+}
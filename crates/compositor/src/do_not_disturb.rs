@@ -0,0 +1,145 @@
+//! Do-not-disturb and focus mode
+//!
+//! Toggled with Ctrl+Shift+N / Super+N or `termstack dnd [on|off|toggle]`.
+//! While it is on, plugin badges are hidden and plugins subscribed to
+//! `do_not_disturb` are told, so they can hold back their own notifications.
+//! With focus mode (`--focus`, or `dnd_focus_mode` in the config for the
+//! keybinding) every cell except the focused one and its parent is hidden
+//! until it is turned off again.
+//!
+//! Status bars see the state in `termstack status`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::cell_id::CellId;
+use crate::state::{StackWindow, TermStack};
+use crate::terminal_manager::TerminalManager;
+
+/// How a request changes the state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DndMode {
+    On,
+    Off,
+    Toggle,
+}
+
+/// State reported back to `termstack dnd`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DndStatus {
+    pub enabled: bool,
+    pub focus_mode: bool,
+}
+
+/// Do-not-disturb state, kept on `TermStack`
+#[derive(Debug, Default)]
+pub struct DoNotDisturb {
+    enabled: bool,
+    focus_mode: bool,
+    /// Whether the keybinding turns on focus mode too
+    focus_by_default: bool,
+    /// Cells shown in focus mode: the focused one and its parent
+    kept: Vec<CellId>,
+}
+
+impl DoNotDisturb {
+    pub fn new(focus_by_default: bool) -> Self {
+        Self { focus_by_default, ..Self::default() }
+    }
+
+    /// Apply `mode`; `focus` picks focus mode when turning on (default from config)
+    pub fn set(&mut self, mode: DndMode, focus: Option<bool>) {
+        self.enabled = match mode {
+            DndMode::On => true,
+            DndMode::Off => false,
+            DndMode::Toggle => !self.enabled,
+        };
+        self.focus_mode = self.enabled && focus.unwrap_or(self.focus_by_default);
+        tracing::info!(enabled = self.enabled, focus_mode = self.focus_mode, "do-not-disturb changed");
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn status(&self) -> DndStatus {
+        DndStatus { enabled: self.enabled, focus_mode: self.focus_mode }
+    }
+
+    /// Whether focus mode hides `cell`
+    pub fn hides(&self, cell: CellId) -> bool {
+        self.focus_mode && !self.kept.is_empty() && !self.kept.contains(&cell)
+    }
+}
+
+/// Follow focus in focus mode and keep plugins informed (once per frame)
+pub fn update(compositor: &mut TermStack, terminal_manager: &TerminalManager) {
+    let enabled = compositor.do_not_disturb.enabled;
+    if compositor.plugins.set_do_not_disturb(enabled) {
+        compositor.redraw_requested = true;
+    }
+
+    let kept = if compositor.do_not_disturb.focus_mode {
+        focused_with_parent(compositor, terminal_manager)
+    } else {
+        Vec::new()
+    };
+    if kept != compositor.do_not_disturb.kept {
+        compositor.do_not_disturb.kept = kept;
+        compositor.redraw_requested = true;
+    }
+}
+
+/// The focused cell, and the terminal that launched it (a command's shell,
+/// or a GUI window's output cell)
+fn focused_with_parent(compositor: &TermStack, terminal_manager: &TerminalManager) -> Vec<CellId> {
+    let Some(node) = compositor.focused_index().and_then(|index| compositor.layout_nodes.get(index)) else {
+        return Vec::new();
+    };
+    let parent = match &node.cell {
+        StackWindow::Terminal(id) => terminal_manager.get(*id).and_then(|t| t.parent),
+        StackWindow::External(entry) => entry.output_terminal,
+    };
+    let parent = parent.and_then(|id| {
+        compositor
+            .layout_nodes
+            .iter()
+            .find(|other| other.cell.terminal_id() == Some(id))
+            .map(|other| other.id)
+    });
+    std::iter::once(node.id).chain(parent).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggling_and_focus_mode() {
+        let mut dnd = DoNotDisturb::new(false);
+        dnd.set(DndMode::Toggle, None);
+        assert_eq!(dnd.status(), DndStatus { enabled: true, focus_mode: false });
+        dnd.set(DndMode::On, Some(true));
+        assert_eq!(dnd.status(), DndStatus { enabled: true, focus_mode: true });
+        dnd.set(DndMode::Toggle, Some(true));
+        assert_eq!(dnd.status(), DndStatus { enabled: false, focus_mode: false });
+
+        let mut dnd = DoNotDisturb::new(true);
+        dnd.set(DndMode::On, None);
+        assert!(dnd.status().focus_mode);
+    }
+
+    #[test]
+    fn focus_mode_hides_other_cells() {
+        let (focused, parent, other) = (CellId::from_bits(1), CellId::from_bits(2), CellId::from_bits(3));
+        let mut dnd = DoNotDisturb::new(true);
+        dnd.kept = vec![focused, parent];
+        assert!(!dnd.hides(other));
+        dnd.set(DndMode::On, None);
+        assert!(dnd.hides(other));
+        assert!(!dnd.hides(focused) && !dnd.hides(parent));
+        // Nothing focused: nothing hidden
+        dnd.kept.clear();
+        assert!(!dnd.hides(other));
+    }
+}
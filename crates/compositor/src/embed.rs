@@ -0,0 +1,367 @@
+//! Embedding a termstack column in another program
+//!
+//! The `termstack` binary owns its event loop, display and backend. Programs
+//! that already have those (another Smithay compositor, an application shell)
+//! can instead build a termstack column on top of their own pieces and drive
+//! it once per frame:
+//!
+//! ```no_run
+//! use compositor::embed::{CellEvent, TermStackBuilder};
+//! use compositor::state::TermStack;
+//! use smithay::reexports::calloop::EventLoop;
+//! use smithay::reexports::wayland_server::Display;
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let mut event_loop: EventLoop<TermStack> = EventLoop::try_new()?;
+//! let display: Display<TermStack> = Display::new()?;
+//!
+//! let mut column = TermStackBuilder::new()
+//!     .size(800, 600)
+//!     .on_cell_event(|event| {
+//!         if let CellEvent::Added { cell, .. } = event {
+//!             println!("new cell {cell:?}");
+//!         }
+//!     })
+//!     .build(display, event_loop.handle())?;
+//!
+//! column.spawn_shell()?;
+//! while !column.frame().all_terminals_exited {
+//!     event_loop.dispatch(Some(column.state().frame_time()), column.state_mut())?;
+//!     // Draw `column.state().layout_nodes` with the embedder's renderer
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Rendering stays with the embedder: cells are laid out in
+//! `TermStack::layout_nodes` (offsets in `TermStack::height_index`), terminal
+//! content comes from the `TerminalManager` and external windows are regular
+//! Smithay `Window`s in `TermStack::space`.
+
+use smithay::output::{Mode, Output};
+use smithay::reexports::calloop::LoopHandle;
+use smithay::reexports::wayland_server::Display;
+use smithay::utils::{Physical, Size};
+
+use crate::cell_id::CellId;
+use crate::config::Config;
+use crate::frame::FrameResult;
+use crate::state::{LayoutNode, TermStack};
+use crate::terminal_manager::{SpawnError, TerminalId, TerminalManager};
+
+/// Something that happened to a cell during a frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CellEvent {
+    /// A cell was added to the column; `terminal` is `None` for external windows
+    Added { cell: CellId, terminal: Option<TerminalId> },
+    /// A cell was removed from the column
+    Removed { cell: CellId },
+    /// Focus moved to another cell (or to none)
+    FocusChanged { cell: Option<CellId> },
+}
+
+type CellCallback = Box<dyn FnMut(&CellEvent)>;
+
+/// Turns layout changes between frames into `CellEvent`s
+#[derive(Debug, Default)]
+pub struct CellEventTracker {
+    known_cells: Vec<CellId>,
+    known_focus: Option<CellId>,
+}
+
+impl CellEventTracker {
+    /// Cells and focus as of the last `update()`
+    pub fn known(&self) -> (&[CellId], Option<CellId>) {
+        (&self.known_cells, self.known_focus)
+    }
+
+    /// Events since the last call
+    pub fn update(&mut self, layout_nodes: &[LayoutNode], focused_index: Option<usize>) -> Vec<CellEvent> {
+        let cells: Vec<CellId> = layout_nodes.iter().map(|node| node.id).collect();
+        let focus = focused_index.and_then(|index| layout_nodes.get(index)).map(|node| node.id);
+
+        let mut events = diff_cells(&self.known_cells, &cells, |cell| {
+            layout_nodes
+                .iter()
+                .find(|node| node.id == cell)
+                .and_then(|node| node.cell.terminal_id())
+        });
+        if focus != self.known_focus {
+            events.push(CellEvent::FocusChanged { cell: focus });
+        }
+
+        self.known_cells = cells;
+        self.known_focus = focus;
+        events
+    }
+}
+
+/// Builder for an embedded termstack column
+pub struct TermStackBuilder {
+    config: Option<Config>,
+    output_name: String,
+    size: (i32, i32),
+    refresh: i32,
+    wayland_socket: bool,
+    ipc_socket: bool,
+    plugins: bool,
+    callbacks: Vec<CellCallback>,
+}
+
+impl Default for TermStackBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TermStackBuilder {
+    /// Start from the defaults: user config, 1280x800, no sockets or plugins
+    pub fn new() -> Self {
+        Self {
+            config: None,
+            output_name: "embedded".to_string(),
+            size: (1280, 800),
+            refresh: crate::refresh::DEFAULT_REFRESH,
+            wayland_socket: false,
+            ipc_socket: false,
+            plugins: false,
+            callbacks: Vec::new(),
+        }
+    }
+
+    /// Use this config instead of loading `~/.config/termstack/config.toml`
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Name of the Smithay output the column is mapped to
+    pub fn output_name(mut self, name: impl Into<String>) -> Self {
+        self.output_name = name.into();
+        self
+    }
+
+    /// Initial size of the column in physical pixels
+    pub fn size(mut self, width: i32, height: i32) -> Self {
+        self.size = (width, height);
+        self
+    }
+
+    /// Refresh rate of the embedder's display in mHz (default 60 Hz)
+    pub fn refresh(mut self, refresh: i32) -> Self {
+        self.refresh = refresh;
+        self
+    }
+
+    /// Listen on a Wayland socket (and set `WAYLAND_DISPLAY`) so GUI apps
+    /// launched from terminals open as cells
+    pub fn wayland_socket(mut self, enabled: bool) -> Self {
+        self.wayland_socket = enabled;
+        self
+    }
+
+    /// Listen on the IPC socket used by the `termstack` CLI and shell integration
+    pub fn ipc_socket(mut self, enabled: bool) -> Self {
+        self.ipc_socket = enabled;
+        self
+    }
+
+    /// Load the plugins enabled in the user's config (see `crate::plugins`)
+    pub fn plugins(mut self, enabled: bool) -> Self {
+        self.plugins = enabled;
+        self
+    }
+
+    /// Call `callback` for every cell event, after the frame that caused it
+    pub fn on_cell_event(mut self, callback: impl FnMut(&CellEvent) + 'static) -> Self {
+        self.callbacks.push(Box::new(callback));
+        self
+    }
+
+    /// Create the column on the embedder's display and event loop
+    pub fn build(
+        self,
+        display: Display<TermStack>,
+        loop_handle: LoopHandle<'static, TermStack>,
+    ) -> anyhow::Result<EmbeddedTermStack> {
+        let config = self.config.unwrap_or_else(Config::load);
+        let (width, height) = self.size;
+        let (output, _mode, output_size) =
+            crate::setup::create_output(&self.output_name, width, height, self.refresh);
+
+        let (mut compositor, display) = TermStack::new(
+            display,
+            loop_handle.clone(),
+            output_size,
+            config.csd_apps.clone(),
+            config.max_gui_windows,
+        );
+        compositor.gui_env = config.gui_env.clone();
+        compositor.refresh = self.refresh;
+        compositor.decoration_overrides = config.decorations.clone();
+        compositor.window_rules = config.window_rules.clone();
+        compositor.max_window_height = config.max_window_height;
+        compositor.space.map_output(&output, (0, 0));
+
+        if self.wayland_socket {
+            output.create_global::<TermStack>(&compositor.display_handle);
+            crate::setup::setup_wayland_socket(&loop_handle)?;
+            crate::setup::set_toolkit_env_vars();
+        }
+        if self.ipc_socket {
+            crate::setup::setup_ipc_socket(&loop_handle)?;
+        }
+        crate::child_reaper::setup_sigchld_source(&loop_handle)?;
+        if let Some(dir) = Config::plugin_dir().filter(|_| self.plugins) {
+            compositor.plugins.load(&dir, &config.plugins);
+        }
+
+        let terminal_manager =
+            crate::setup::create_terminal_manager(&config, width as u32, height as u32);
+
+        tracing::info!(width, height, output = %self.output_name, "embedded termstack created");
+
+        Ok(EmbeddedTermStack {
+            compositor,
+            display,
+            terminal_manager,
+            output,
+            tracker: CellEventTracker::default(),
+            callbacks: self.callbacks,
+        })
+    }
+}
+
+/// A termstack column driven by an embedding program
+pub struct EmbeddedTermStack {
+    compositor: TermStack,
+    display: Display<TermStack>,
+    terminal_manager: TerminalManager,
+    output: Output,
+    tracker: CellEventTracker,
+    callbacks: Vec<CellCallback>,
+}
+
+impl EmbeddedTermStack {
+    /// Compositor state (layout, focus, scroll)
+    pub fn state(&self) -> &TermStack {
+        &self.compositor
+    }
+
+    /// Mutable compositor state, also what the event loop dispatches to
+    pub fn state_mut(&mut self) -> &mut TermStack {
+        &mut self.compositor
+    }
+
+    /// Terminals shown in the column
+    pub fn terminals(&self) -> &TerminalManager {
+        &self.terminal_manager
+    }
+
+    pub fn terminals_mut(&mut self) -> &mut TerminalManager {
+        &mut self.terminal_manager
+    }
+
+    /// The output the column is mapped to
+    pub fn output(&self) -> &Output {
+        &self.output
+    }
+
+    /// Add a shell terminal above the focused cell
+    pub fn spawn_shell(&mut self) -> Result<TerminalId, SpawnError> {
+        let id = self.terminal_manager.spawn()?;
+        self.compositor.add_terminal(id);
+        self.compositor.enforce_terminal_limit(&mut self.terminal_manager);
+        Ok(id)
+    }
+
+    /// Resize the column
+    pub fn resize(&mut self, width: i32, height: i32) {
+        let size: Size<i32, Physical> = (width, height).into();
+        self.output.change_current_state(
+            Some(Mode { size, refresh: self.compositor.refresh }),
+            None,
+            None,
+            None,
+        );
+        crate::window_height::handle_compositor_resize(
+            &mut self.compositor,
+            &mut self.terminal_manager,
+            size,
+        );
+    }
+
+    /// Run one frame: dispatch Wayland clients, process terminal output and
+    /// window events, update the layout, then report cell events
+    ///
+    /// Does not dispatch the event loop; the embedder does that with its own
+    /// timeout, passing `state_mut()`.
+    pub fn frame(&mut self) -> FrameResult {
+        let result = crate::frame::run_frame_body(
+            &mut self.compositor,
+            &mut self.display,
+            &mut self.terminal_manager,
+            &self.output,
+        );
+        self.emit_cell_events();
+        result
+    }
+
+    /// Tell clients that the frame drawn from the current state is on screen
+    /// (presentation feedback for video players and the like)
+    pub fn frame_presented(&mut self) {
+        crate::presentation::frame_submitted(&mut self.compositor, &self.output);
+    }
+
+    fn emit_cell_events(&mut self) {
+        let focused = self.compositor.focused_index();
+        let events = self.tracker.update(&self.compositor.layout_nodes, focused);
+        for event in &events {
+            for callback in &mut self.callbacks {
+                callback(event);
+            }
+        }
+    }
+}
+
+/// Events for going from cells `before` to cells `after`, removals first
+fn diff_cells(
+    before: &[CellId],
+    after: &[CellId],
+    terminal_of: impl Fn(CellId) -> Option<TerminalId>,
+) -> Vec<CellEvent> {
+    let removed = before
+        .iter()
+        .filter(|cell| !after.contains(cell))
+        .map(|&cell| CellEvent::Removed { cell });
+    let added = after
+        .iter()
+        .filter(|cell| !before.contains(cell))
+        .map(|&cell| CellEvent::Added { cell, terminal: terminal_of(cell) });
+    removed.chain(added).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_id::CellRegistry;
+
+    #[test]
+    fn diff_reports_removals_then_additions() {
+        let mut cells = CellRegistry::default();
+        let a = cells.insert(0);
+        let b = cells.insert(1);
+        let c = cells.insert(2);
+
+        let events = diff_cells(&[a, b], &[c, a], |cell| (cell == c).then_some(TerminalId(7)));
+        assert_eq!(
+            events,
+            vec![
+                CellEvent::Removed { cell: b },
+                CellEvent::Added { cell: c, terminal: Some(TerminalId(7)) },
+            ]
+        );
+        // Reordering alone is not an event
+        assert!(diff_cells(&[a, c], &[c, a], |_| None).is_empty());
+    }
+}
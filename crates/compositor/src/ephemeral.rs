@@ -0,0 +1,122 @@
+//! Auto-closing cells of trivially short commands
+//!
+//! With `ephemeral_command_ms` set in the config, the cell of a command that
+//! succeeds within that many milliseconds and prints fewer than
+//! `ephemeral_max_lines` lines stays for `EPHEMERAL_LINGER` and then closes
+//! by itself, so `ls`-class commands don't pile up in the stack. Failed,
+//! slow or chatty commands keep their cells. Closed cells' output goes to
+//! the archive (`crate::archive`), and with `ephemeral_merge` also into the
+//! shell that ran the command, above its prompt, the way the absorb action
+//! (`crate::absorb`) does it.
+
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use crate::state::TermStack;
+use crate::terminal_manager::{TerminalId, TerminalManager};
+
+/// How long a trivial command's cell stays before closing
+pub const EPHEMERAL_LINGER: Duration = Duration::from_secs(3);
+
+/// What makes a command trivial
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EphemeralLimits {
+    /// Longest run time
+    pub max_duration: Duration,
+    /// Output lines must be fewer than this
+    pub max_lines: u32,
+}
+
+impl EphemeralLimits {
+    /// Whether a command that ran for `duration` and printed `lines` lines is trivial
+    pub fn is_trivial(&self, success: bool, duration: Duration, lines: u32) -> bool {
+        success && duration <= self.max_duration && lines < self.max_lines
+    }
+}
+
+/// Ephemeral cell state, kept on `TermStack`
+#[derive(Debug, Default)]
+pub struct EphemeralCells {
+    /// `None` when the feature is off
+    limits: Option<EphemeralLimits>,
+    /// Write closing cells' output into their shells
+    merge: bool,
+    /// Exited command terminals already looked at
+    judged: HashSet<TerminalId>,
+    /// Trivial commands' cells and when they close
+    closing: Vec<(TerminalId, Instant)>,
+}
+
+impl EphemeralCells {
+    /// Settings from the config (off unless `ephemeral_command_ms` is set)
+    pub fn from_config(config: &crate::config::Config) -> Self {
+        let limits = (config.ephemeral_command_ms > 0).then(|| EphemeralLimits {
+            max_duration: Duration::from_millis(config.ephemeral_command_ms),
+            max_lines: config.ephemeral_max_lines,
+        });
+        Self { limits, merge: config.ephemeral_merge, ..Self::default() }
+    }
+}
+
+/// Schedule trivial commands' cells for closing and close those whose time
+/// is up (once per frame, after exit statuses are reaped)
+pub fn process(compositor: &mut TermStack, terminal_manager: &mut TerminalManager) {
+    let ephemeral = &mut compositor.ephemeral_cells;
+    let Some(limits) = ephemeral.limits else {
+        return;
+    };
+    let now = Instant::now();
+
+    ephemeral.judged.retain(|id| terminal_manager.get(*id).is_some());
+    for (id, terminal) in terminal_manager.iter() {
+        if terminal.parent.is_none() || !terminal.has_exited() || ephemeral.judged.contains(id) {
+            continue;
+        }
+        // Wait for the child reaper to collect the exit status
+        let Some(status) = terminal.exit_status() else {
+            continue;
+        };
+        ephemeral.judged.insert(*id);
+        let duration = terminal
+            .finished_at
+            .and_then(|finished| finished.duration_since(terminal.started_at).ok())
+            .unwrap_or(Duration::MAX);
+        if limits.is_trivial(status.success(), duration, terminal.content_rows()) {
+            tracing::debug!(id = id.0, ?duration, "closing trivial command's cell soon");
+            ephemeral.closing.push((*id, now + EPHEMERAL_LINGER));
+        }
+    }
+
+    let (expired, kept): (Vec<_>, Vec<_>) = ephemeral.closing.iter().partition(|(_, until)| *until <= now);
+    if expired.is_empty() {
+        return;
+    }
+    ephemeral.closing = kept;
+    let merge = ephemeral.merge;
+    for (id, _) in expired {
+        let shell = if merge { crate::absorb::write_into_parent(terminal_manager, id) } else { None };
+        if let Some(terminal) = terminal_manager.remove(id) {
+            compositor.archive.add(&terminal, now);
+        }
+        compositor.remove_terminal(id);
+        if let Some(shell) = shell {
+            crate::absorb::fit_shell_cell(compositor, terminal_manager, shell);
+        }
+        tracing::info!(id = id.0, merged = shell.is_some(), "closed trivial command's cell");
+    }
+    compositor.redraw_requested = true;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_quick_quiet_successes_are_trivial() {
+        let limits = EphemeralLimits { max_duration: Duration::from_millis(500), max_lines: 5 };
+        assert!(limits.is_trivial(true, Duration::from_millis(20), 3));
+        assert!(!limits.is_trivial(false, Duration::from_millis(20), 3));
+        assert!(!limits.is_trivial(true, Duration::from_secs(2), 3));
+        assert!(!limits.is_trivial(true, Duration::from_millis(20), 5));
+    }
+}
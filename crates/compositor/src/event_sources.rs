@@ -0,0 +1,175 @@
+//! Event-driven main loop support
+//!
+//! The X11 main loop blocks in `EventLoop::dispatch` until a source fires
+//! instead of polling on a fixed timeout. Everything that can change what is
+//! on screen is a calloop source that sets `TermStack::redraw_requested`:
+//! X11 events, Wayland client requests (the display's poll fd), PTY output,
+//! IPC connections and SIGCHLD. Between events the loop only wakes for key
+//! repeat and a slow housekeeping tick (timeouts, watchdog, xwayland health).
+//!
+//! PTY sources are oneshot: the callback disables its source so a PTY that
+//! stays readable (output not yet consumed, or hung up) can't spin the loop.
+//! `sync_pty_sources()` re-arms them after the frame has read the output.
+
+use std::collections::HashMap;
+use std::os::fd::{BorrowedFd, OwnedFd};
+use std::time::{Duration, Instant};
+
+use smithay::reexports::calloop::generic::Generic;
+use smithay::reexports::calloop::{Interest, LoopHandle, Mode, PostAction, RegistrationToken};
+
+use crate::state::TermStack;
+use crate::terminal_manager::{TerminalId, TerminalManager};
+
+/// Longest the loop sleeps without an event (periodic housekeeping)
+pub const IDLE_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Calloop registrations for terminal PTY fds
+#[derive(Debug, Default)]
+pub struct PtySources {
+    /// Registered sources by terminal
+    tokens: HashMap<TerminalId, RegistrationToken>,
+    /// Terminals whose source fired (and disabled itself) since the last sync
+    fired: Vec<TerminalId>,
+}
+
+/// Duplicate a borrowed fd so a calloop source can own it
+fn dup_fd(fd: BorrowedFd<'_>) -> std::io::Result<OwnedFd> {
+    Ok(rustix::io::fcntl_dupfd_cloexec(fd, 0)?)
+}
+
+/// Register the Wayland display's poll fd so client requests wake the loop.
+///
+/// The callback only requests a redraw; the main loop dispatches clients on
+/// every iteration, which clears the fd's readiness.
+pub fn setup_display_source(
+    calloop_handle: &LoopHandle<'static, TermStack>,
+    poll_fd: BorrowedFd<'_>,
+) -> anyhow::Result<()> {
+    calloop_handle
+        .insert_source(
+            Generic::new(dup_fd(poll_fd)?, Interest::READ, Mode::Level),
+            |_, _, state| {
+                state.redraw_requested = true;
+                Ok(PostAction::Continue)
+            },
+        )
+        .map_err(|e| anyhow::anyhow!("Failed to insert Wayland display source: {e}"))?;
+    Ok(())
+}
+
+/// Bring PTY sources in line with the terminal manager.
+///
+/// Registers terminals that appeared, re-arms sources that fired (their
+/// output has been read by now), and drops sources of removed or exited
+/// terminals. Exited terminals are picked up by the housekeeping tick if a
+/// leftover background process still writes to them.
+pub fn sync_pty_sources(compositor: &mut TermStack, terminal_manager: &TerminalManager) {
+    let handle = compositor.loop_handle.clone();
+    let sources = &mut compositor.pty_sources;
+
+    sources.tokens.retain(|id, token| {
+        let live = terminal_manager.get(*id).is_some_and(|t| !t.has_exited());
+        if !live {
+            handle.remove(*token);
+        }
+        live
+    });
+
+    for id in sources.fired.drain(..) {
+        if let Some(token) = sources.tokens.get(&id) {
+            if let Err(e) = handle.enable(token) {
+                tracing::warn!(id = id.0, ?e, "failed to re-arm PTY source");
+            }
+        }
+    }
+
+    for (id, fd) in terminal_manager.pty_fds() {
+        if sources.tokens.contains_key(&id)
+            || terminal_manager.get(id).map_or(true, |t| t.has_exited())
+        {
+            continue;
+        }
+        // SAFETY: the fd belongs to a terminal that is alive for this call; we
+        // only borrow it long enough to duplicate it.
+        let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+        let registered = dup_fd(fd).map_err(anyhow::Error::from).and_then(|fd| {
+            handle
+                .insert_source(
+                    Generic::new(fd, Interest::READ, Mode::Level),
+                    move |_, _, state| {
+                        state.redraw_requested = true;
+                        state.pty_sources.fired.push(id);
+                        Ok(PostAction::Disable)
+                    },
+                )
+                .map_err(|e| anyhow::anyhow!("{e}"))
+        });
+        match registered {
+            Ok(token) => {
+                sources.tokens.insert(id, token);
+            }
+            Err(e) => tracing::warn!(id = id.0, error = %e, "failed to register PTY source"),
+        }
+    }
+}
+
+/// How long the next `dispatch` may block
+///
+/// A pending redraw waits only for the frame rate limit; otherwise the loop
+/// sleeps until the next key repeat, replayed input event or housekeeping
+/// tick.
+pub fn dispatch_timeout(
+    now: Instant,
+    redraw_pending: bool,
+    last_render: Instant,
+    min_frame_time: Duration,
+    next_timer: Option<Instant>,
+) -> Duration {
+    let mut timeout = if redraw_pending {
+        min_frame_time.saturating_sub(now.duration_since(last_render))
+    } else {
+        IDLE_TIMEOUT
+    };
+    if let Some(at) = next_timer {
+        timeout = timeout.min(at.saturating_duration_since(now));
+    }
+    timeout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(8);
+
+    #[test]
+    fn idle_loop_sleeps_until_housekeeping() {
+        let now = Instant::now();
+        assert_eq!(dispatch_timeout(now, false, now, FRAME, None), IDLE_TIMEOUT);
+    }
+
+    #[test]
+    fn pending_redraw_waits_for_frame_limit() {
+        let now = Instant::now();
+        let last = now - Duration::from_millis(3);
+        assert_eq!(dispatch_timeout(now, true, last, FRAME, None), Duration::from_millis(5));
+
+        // Overdue frames don't block at all
+        let last = now - Duration::from_millis(20);
+        assert_eq!(dispatch_timeout(now, true, last, FRAME, None), Duration::ZERO);
+    }
+
+    #[test]
+    fn key_repeat_shortens_timeout() {
+        let now = Instant::now();
+        let repeat = now + Duration::from_millis(30);
+        assert_eq!(
+            dispatch_timeout(now, false, now, FRAME, Some(repeat)),
+            Duration::from_millis(30)
+        );
+        // A repeat that is already due fires immediately
+        let past = now - Duration::from_millis(1);
+        assert_eq!(dispatch_timeout(now, false, now, FRAME, Some(past)), Duration::ZERO);
+    }
+}
@@ -3,14 +3,9 @@
 //! All backends call `process_frame()` once per iteration to run the shared
 //! compositor logic (spawn handling, terminal output, window lifecycle, layout).
 //!
-//! Higher-level helpers (`send_all_frame_callbacks`, `run_frame_body`) reduce
-//! duplication across backends for the common dispatch → process → callbacks →
-//! flush cycle.
+//! `run_frame_body` reduces duplication across backends for the common
+//! dispatch → process → callbacks → flush cycle.
 
-use std::time::Duration;
-
-use smithay::desktop::utils::send_frames_surface_tree;
-use smithay::desktop::PopupManager;
 use smithay::output::Output;
 use smithay::reexports::wayland_server::Display;
 
@@ -30,7 +25,6 @@ pub struct FrameResult {
 pub fn process_frame(
     compositor: &mut TermStack,
     terminal_manager: &mut TerminalManager,
-    height_calculator: fn(&TermStack, &TerminalManager) -> Vec<i32>,
 ) -> FrameResult {
     // 1. Clear stale drag state if no pointer buttons are pressed
     //    (handles lost release events when window loses focus mid-drag)
@@ -39,6 +33,9 @@ pub fn process_frame(
     // 2. Cancel pending resizes from unresponsive clients
     compositor.cancel_stale_pending_resizes();
 
+    // 2b. Send the window being resized its latest drag target, if it's ready
+    compositor.send_drag_configure();
+
     // 3. Cleanup popup internal resources
     compositor.popup_manager.cleanup();
 
@@ -47,23 +44,26 @@ pub fn process_frame(
 
     // 5. Handle focus change requests from input
     crate::input_handler::handle_focus_change_requests(compositor, terminal_manager);
+    crate::input_handler::handle_family_focus_requests(compositor, terminal_manager);
+    crate::input_handler::handle_column_requests(compositor);
+
+    // 5a. Answer archive requests (restored cells go in with the builtins below)
+    crate::archive::process(compositor);
+
+    // 6–8. Handle spawn requests from IPC (and expire calculator answers)
+    crate::spawn_handler::handle_ipc_spawn_requests(compositor, terminal_manager);
+    crate::spawn_handler::handle_gui_spawn_requests(compositor, terminal_manager);
+    crate::spawn_handler::handle_builtin_requests(compositor, terminal_manager);
+    crate::spawn_handler::dismiss_calc_results(compositor, terminal_manager);
+
+    // 8a. Add the cells of imported layout files and answer layout exports
+    crate::layout_file::handle_ipc_layout_file_requests(compositor, terminal_manager);
 
-    // 6–8. Handle spawn requests from IPC
-    crate::spawn_handler::handle_ipc_spawn_requests(
-        compositor,
-        terminal_manager,
-        height_calculator,
-    );
-    crate::spawn_handler::handle_gui_spawn_requests(
-        compositor,
-        terminal_manager,
-        height_calculator,
-    );
-    crate::spawn_handler::handle_builtin_requests(
-        compositor,
-        terminal_manager,
-        height_calculator,
-    );
+    // 8b. Apply stack-wide actions from IPC
+    crate::bulk_actions::process(compositor, terminal_manager);
+
+    // 8c. Apply cell filters from the prompt and IPC, keeping new cells in the filtered view
+    crate::cell_filter::process(compositor, terminal_manager);
 
     // 9. Handle resize requests from IPC
     crate::terminal_output::handle_ipc_resize_request(compositor, terminal_manager);
@@ -71,9 +71,27 @@ pub fn process_frame(
     // 10. Handle key repeat for terminals
     crate::input_handler::handle_key_repeat(compositor, terminal_manager);
 
+    // 10a. Step keyboard resize mode
+    crate::keyboard_resize::process(compositor, terminal_manager);
+
+    // 10b. Scroll a terminal while a selection is dragged past its edge
+    crate::selection::autoscroll_selection(compositor, terminal_manager);
+
     // 11. Process terminal PTY output and handle sizing actions
     crate::terminal_output::process_terminal_output(compositor, terminal_manager);
 
+    // 11a. Spawn, size and slide the quick terminal (after output may have resized it)
+    crate::quick_terminal::process(compositor, terminal_manager);
+
+    // 11b. Answer cell exports, layout snapshots and hit tests from IPC (after new output is in the grid),
+    // naming and tagging cells first so snapshots show the new titles and tags
+    crate::cell_title::apply_title_escapes(compositor, terminal_manager);
+    crate::cell_title::handle_ipc_title_requests(compositor, terminal_manager);
+    crate::color_tags::handle_ipc_tag_requests(compositor);
+    crate::terminal_output::handle_ipc_export_requests(compositor, terminal_manager);
+    crate::layout_dump::handle_ipc_layout_requests(compositor, terminal_manager);
+    crate::hit_test::handle_ipc_hit_test_requests(compositor, terminal_manager);
+
     // 12. Promote output terminals that have content
     crate::terminal_output::promote_output_terminals(compositor, terminal_manager);
 
@@ -83,16 +101,33 @@ pub fn process_frame(
     // 14. Handle restoration of launchers when output terminals are already gone
     crate::window_lifecycle::handle_launcher_restoration(compositor, terminal_manager);
 
-    // 15. Cleanup dead terminals and check if all have exited
+    // 15. Reap exited child processes, cleanup dead terminals and check if all have exited
+    crate::child_reaper::reap_children(compositor, terminal_manager);
     let all_terminals_exited =
         crate::window_lifecycle::cleanup_and_sync_focus(compositor, terminal_manager);
 
+    // 15a. Restore launchers whose foreground GUI died unnoticed
+    crate::window_lifecycle::restore_orphaned_gui_launchers(compositor, terminal_manager);
+
+    // 15b. Close the cells of trivially short commands
+    crate::ephemeral::process(compositor, terminal_manager);
+
+    // 15c. Merge the focused command cell into its shell, if asked to
+    crate::absorb::process(compositor, terminal_manager);
+
+    // 15d. Drop expired closes and bring back the last one, if asked to
+    crate::undo::process(compositor, terminal_manager);
+
+    // 15e. Take down the quit prompt once its time is up
+    if compositor.quit_confirm.expire(std::time::Instant::now()) {
+        compositor.redraw_requested = true;
+    }
+
+    // 15f. Compress the scrollback of finished cells left alone for a while
+    terminal_manager.compress_idle_scrollback(std::time::Instant::now());
+
     // 16. Handle terminal spawn requests (Ctrl+Shift+Enter)
-    crate::window_lifecycle::handle_terminal_spawn(
-        compositor,
-        terminal_manager,
-        height_calculator,
-    );
+    crate::window_lifecycle::handle_terminal_spawn(compositor, terminal_manager);
 
     // 17. Handle font size changes
     if compositor.pending_font_size_delta != 0.0 {
@@ -102,21 +137,60 @@ pub fn process_frame(
         let new_font_size = (terminal_manager.font_size() + delta).clamp(6.0, 72.0);
         terminal_manager.set_font_size(
             new_font_size,
-            compositor.output_size.w as u32,
-            compositor.output_size.h as u32,
+            compositor.column_width() as u32,
+            compositor.viewport_height() as u32,
         );
     }
 
+    // 17a. Offer formatted views of finished JSON and table output, and
+    //      switch the focused cell to or from its view (Super+E)
+    crate::smart_output::detect_finished(terminal_manager);
+    crate::smart_output::handle_toggle_request(compositor, terminal_manager);
+
+    // 17b. Page through the focused terminal's scrollback (Shift+Page Up/Down)
+    if compositor.pending_scrollback_pages != 0 {
+        let pages = std::mem::take(&mut compositor.pending_scrollback_pages);
+        if let Some(term) = terminal_manager.get_focused_mut(compositor.keyboard_input_window().as_ref()) {
+            term.terminal.scroll_display_pages(pages);
+            term.mark_dirty();
+            tracing::debug!(pages, offset = term.terminal.display_offset(), "terminal scrollback (Shift+Page)");
+        }
+    }
+
+    // 17c. Fit cells to the column width after columns came or went
+    crate::window_height::fit_cells_to_columns(compositor, terminal_manager);
+
     // 18. Apply accumulated scroll delta
     compositor.apply_pending_scroll();
 
-    // 19. Calculate and update window heights, auto-scroll if needed
-    let window_heights = height_calculator(compositor, terminal_manager);
-    crate::window_height::check_and_handle_height_changes(compositor, window_heights);
+    // 19. Lay out the frame from the estimated heights, auto-scroll if needed
+    //     (backends that render commit again with measured heights; heights
+    //     requested before this point hold for both layouts)
+    compositor.height_requests.begin_frame();
+    let estimated = crate::frame_layout::estimate_heights(compositor, terminal_manager);
+    let layout = crate::frame_layout::FrameLayout::compute(compositor, &estimated);
+    compositor.commit_frame_layout(layout);
 
     // 20. Recalculate layout positions
     compositor.recalculate_layout();
 
+    // 20a. Keep a fullscreen window covering the output
+    compositor.pin_fullscreen();
+
+    // 20b. Move keyboard focus along with any cell focus change above
+    compositor.sync_keyboard_focus();
+
+    // 20c. Follow focus in do-not-disturb focus mode, apply plugin requests
+    //      and notify plugins of cell changes
+    crate::do_not_disturb::update(compositor, terminal_manager);
+    crate::plugins::process(compositor);
+
+    // 20d. Tell status bar clients what the focused cell is running
+    crate::now_running::publish(compositor, terminal_manager);
+
+    // 20e. Tell screen reader clients about changes to the stack
+    crate::accessibility::publish(compositor, terminal_manager);
+
     // 21. Process pending PRIMARY selection paste (from middle-click)
     compositor.process_primary_selection_paste(terminal_manager);
 
@@ -128,34 +202,14 @@ pub fn process_frame(
     #[cfg(debug_assertions)]
     compositor.validate_state(terminal_manager);
 
-    FrameResult {
-        all_terminals_exited,
-    }
-}
+    // 25. Detect frozen clients and hung terminals
+    crate::watchdog::check_liveness(compositor, terminal_manager);
 
-/// Send frame callbacks to all toplevel surfaces and their popups.
-///
-/// Each backend calls this after rendering (or on a timer for headless)
-/// so Wayland clients know they can draw another frame.
-pub fn send_all_frame_callbacks(compositor: &TermStack, output: &Output) {
-    for surface in compositor.xdg_shell_state.toplevel_surfaces() {
-        send_frames_surface_tree(
-            surface.wl_surface(),
-            output,
-            Duration::ZERO,
-            Some(Duration::ZERO),
-            |_, _| Some(output.clone()),
-        );
+    // 26. Refresh the stack snapshot used by crash reports
+    crate::crash::update_snapshot(compositor, terminal_manager);
 
-        for (popup, _) in PopupManager::popups_for_surface(surface.wl_surface()) {
-            send_frames_surface_tree(
-                popup.wl_surface(),
-                output,
-                Duration::ZERO,
-                Some(Duration::ZERO),
-                |_, _| Some(output.clone()),
-            );
-        }
+    FrameResult {
+        all_terminals_exited,
     }
 }
 
@@ -169,15 +223,15 @@ pub fn run_frame_body(
     display: &mut Display<TermStack>,
     terminal_manager: &mut TerminalManager,
     output: &Output,
-    height_calculator: fn(&TermStack, &TerminalManager) -> Vec<i32>,
 ) -> FrameResult {
     display
         .dispatch_clients(compositor)
         .expect("failed to dispatch clients");
 
-    let result = process_frame(compositor, terminal_manager, height_calculator);
+    let result = process_frame(compositor, terminal_manager);
 
-    send_all_frame_callbacks(compositor, output);
+    // Clients waiting on a frame callback get one once per refresh
+    crate::presentation::send_frame_callbacks(compositor, output, false);
 
     if let Err(e) = compositor.display_handle.flush_clients() {
         tracing::warn!(error = ?e, "failed to flush Wayland clients");
@@ -0,0 +1,316 @@
+//! Per-frame layout: every cell's height and position, worked out once
+//!
+//! Cell heights come from two measurements: between frames, an estimate from
+//! the cached heights and terminal sizes (`estimate_heights`); when a frame is
+//! rendered, the textures and surfaces actually drawn (`render::collect_window_data`).
+//! `FrameLayout::compute` is the one place turning either into the heights the
+//! stack is laid out with: cells hidden by a filter, focus mode or a collapse
+//! get 0, the cell being resized by a drag gets its drag height, and heights
+//! requested since the last frame (`TermStack::request_window_height`, for
+//! terminal resizes and window commits) win over the measurements. A request
+//! holds for both layouts of its frame (see `HeightRequests`).
+//!
+//! `TermStack::commit_frame_layout` then applies the result in one step (cached
+//! heights, height index, auto-scroll, `TermStack::reveal_after_layout`) and
+//! keeps it as an immutable snapshot in `TermStack::frame_layout`. Rendering
+//! draws from the snapshot it committed, and clicks and hit tests look points
+//! up in it, so nothing changes a height between layout and drawing.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::cell_id::CellId;
+use crate::layout::{calculate_terminal_render_height, heights_changed_significantly};
+use crate::state::{StackWindow, TermStack};
+use crate::terminal_manager::TerminalManager;
+use crate::terminal_output::is_window_bottom_visible;
+use crate::title_bar::TITLE_BAR_HEIGHT;
+use crate::window_height::initial_surface_height;
+
+/// Default terminal height in pixels (fallback when terminal doesn't exist)
+const DEFAULT_TERMINAL_HEIGHT: i32 = 200;
+
+/// Heights and screen positions of all cells for one frame
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameLayout {
+    /// Cell of each entry, in `layout_nodes` order
+    cells: Vec<CellId>,
+    /// Visual heights (including title bars)
+    heights: Vec<i32>,
+    /// Column x and screen top (Y=0 at top) of each cell
+    origins: Vec<(i32, i32)>,
+    /// Cells that take no space and aren't drawn
+    hidden: Vec<bool>,
+    column_width: i32,
+}
+
+/// Heights given to cells between frames, inputs to `FrameLayout::compute`
+///
+/// A frame is laid out twice when it is rendered: from estimates in
+/// `process_frame`, then from the measured heights. Requests made before the
+/// frame hold for both, so a measurement of the old size can't undo them.
+#[derive(Debug, Default)]
+pub struct HeightRequests {
+    /// Made since the current frame was laid out
+    pending: HashMap<CellId, i32>,
+    /// Applied to the current frame
+    frame: HashMap<CellId, i32>,
+}
+
+impl HeightRequests {
+    pub fn request(&mut self, cell: CellId, height: i32) {
+        self.pending.insert(cell, height);
+    }
+
+    /// Start a frame with the requests made since the last one
+    pub fn begin_frame(&mut self) {
+        self.frame = std::mem::take(&mut self.pending);
+    }
+
+    pub fn get(&self, cell: CellId) -> Option<i32> {
+        self.pending.get(&cell).or_else(|| self.frame.get(&cell)).copied()
+    }
+}
+
+/// Height of a cell that is neither hidden nor being dragged: a requested
+/// height wins over a measured one, which wins over the cached one
+fn settled_height(requested: Option<i32>, measured: Option<i32>, cached: i32) -> i32 {
+    requested.or(measured).unwrap_or(cached)
+}
+
+impl FrameLayout {
+    /// Lay out the stack with `measured` heights (one per cell; missing
+    /// entries keep the cached height) and the requested ones
+    pub fn compute(compositor: &TermStack, measured: &[i32]) -> Self {
+        let hidden: Vec<bool> = compositor.layout_nodes.iter().map(|node| compositor.hides_cell(node.id)).collect();
+        let heights: Vec<i32> = compositor
+            .layout_nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| {
+                let requested = compositor.height_requests.get(node.id);
+                match &compositor.resizing {
+                    // Being resized: terminals follow the drag at once, external
+                    // windows are placed at the target height while their content
+                    // still renders at the committed size
+                    Some(drag) if drag.cell == node.id => match node.cell {
+                        StackWindow::Terminal(_) => requested.unwrap_or(node.height),
+                        StackWindow::External(_) => drag.target_height,
+                    },
+                    _ if hidden[i] => 0,
+                    _ => settled_height(requested, measured.get(i).copied(), node.height),
+                }
+            })
+            .collect();
+        Self {
+            cells: compositor.layout_nodes.iter().map(|node| node.id).collect(),
+            origins: compositor.cell_origins(&heights),
+            heights,
+            hidden,
+            column_width: compositor.column_width(),
+        }
+    }
+
+    pub fn heights(&self) -> &[i32] {
+        &self.heights
+    }
+
+    pub fn origins(&self) -> &[(i32, i32)] {
+        &self.origins
+    }
+
+    /// Screen top of each cell
+    pub fn tops(&self) -> Vec<i32> {
+        self.origins.iter().map(|&(_, top)| top).collect()
+    }
+
+    pub fn hidden(&self) -> &[bool] {
+        &self.hidden
+    }
+
+    pub fn column_width(&self) -> i32 {
+        self.column_width
+    }
+
+    /// Screen top and bottom (Y=0 at top) of `cell`, if it was laid out
+    pub fn screen_bounds(&self, cell: CellId) -> Option<(i32, i32)> {
+        let i = self.cells.iter().position(|&laid_out| laid_out == cell)?;
+        let top = self.origins[i].1;
+        Some((top, top + self.heights[i]))
+    }
+
+    /// Cell drawn at screen point (`x`, `y`), Y=0 at top
+    pub fn cell_at(&self, x: f64, y: f64) -> Option<CellId> {
+        (0..self.cells.len())
+            .filter(|&i| !self.hidden[i])
+            .find(|&i| {
+                let (left, top) = self.origins[i];
+                x >= left as f64
+                    && x < (left + self.column_width) as f64
+                    && y >= top as f64
+                    && y < (top + self.heights[i]) as f64
+            })
+            .map(|i| self.cells[i])
+    }
+}
+
+/// Estimate cell heights without rendering.
+///
+/// All cells store visual height in node.height (including title bar for SSD windows).
+/// This is set by configure_notify (X11), configure_ack (Wayland), or terminal resize.
+///
+/// For terminals: hidden terminals always get 0 height; otherwise uses cached height
+/// if available, falls back to terminal.height for new cells (already includes title bar).
+///
+/// For external windows: uses cached visual height if available, otherwise computes
+/// from window state (which stores content height, so we add title bar for SSD).
+pub fn estimate_heights(compositor: &TermStack, terminal_manager: &TerminalManager) -> Vec<i32> {
+    compositor.layout_nodes.iter().map(|node| {
+        match &node.cell {
+            StackWindow::Terminal(tid) => {
+                // Hidden terminals always get 0 height
+                if !terminal_manager.is_terminal_visible(*tid) {
+                    return 0;
+                }
+                // Use cached visual height if available
+                if node.height > 0 {
+                    return node.height;
+                }
+                // Fallback for new cells: use centralized height calculation
+                terminal_manager.get(*tid)
+                    .map(|t| calculate_terminal_render_height(t.height as i32, t.show_title_bar, true))
+                    .unwrap_or(DEFAULT_TERMINAL_HEIGHT)
+            }
+            StackWindow::External(entry) => {
+                // Use cached visual height if available
+                if node.height > 0 {
+                    return node.height;
+                }
+                // Fallback for new cells: try window state first, then window.geometry()
+                let mut content_height = entry.state.current_height() as i32;
+
+                // If state hasn't been updated yet, try to get size from window geometry
+                // This handles the initial commit case where the client has drawn
+                // but handle_commit hasn't processed the size yet
+                if content_height == 0 {
+                    content_height = initial_surface_height(
+                        entry.window.geometry().size.h,
+                        crate::state::toplevel_min_height(entry.surface.wl_surface()),
+                    );
+                }
+
+                let cell_height = if !entry.has_title_bar() {
+                    content_height
+                } else {
+                    // Add title bar for SSD windows to get visual height
+                    content_height + TITLE_BAR_HEIGHT as i32
+                };
+                entry.displayed_height(cell_height.max(0) as u32) as i32
+            }
+        }
+    }).collect()
+}
+
+impl TermStack {
+    /// Apply a frame's layout: cache its heights and, if cells above the
+    /// focused one changed size while its bottom was on screen, scroll to
+    /// keep that bottom in view (not during a resize drag), then reveal the
+    /// cell asked for with `reveal_after_layout()`. Returns the snapshot,
+    /// placed at the resulting scroll and also kept in `frame_layout`.
+    pub fn commit_frame_layout(&mut self, mut layout: FrameLayout) -> Rc<FrameLayout> {
+        let current_heights: Vec<i32> = self.layout_nodes.iter().map(|node| node.height).collect();
+        let heights_changed = heights_changed_significantly(&current_heights, layout.heights(), self.focused_index());
+
+        // Skip autoscroll during resize to avoid disrupting drag
+        let should_autoscroll = heights_changed
+            && self.resizing.is_none()
+            && self.focused_index().is_some_and(|focused| is_window_bottom_visible(self, focused));
+
+        self.update_layout_heights(layout.heights.clone());
+        let mut scrolled = false;
+
+        // This allows users to scroll up while content continues to flow in
+        if should_autoscroll {
+            if let Some(focused_idx) = self.focused_index() {
+                if let Some(new_scroll) = self.scroll_to_show_window_bottom(focused_idx) {
+                    tracing::info!(
+                        focused_idx,
+                        new_scroll,
+                        "scroll adjusted due to actual height change (bottom was visible)"
+                    );
+                    scrolled = true;
+                }
+            }
+        }
+
+        if let Some(index) = self.pending_reveal.take().and_then(|cell| self.cell_index(cell)) {
+            if let Some(new_scroll) = self.scroll_to_show_window_bottom(index) {
+                tracing::debug!(index, new_scroll, "scrolled to reveal cell after layout");
+                scrolled = true;
+            }
+        }
+
+        if scrolled {
+            layout.origins = self.cell_origins(&layout.heights);
+        }
+        let layout = Rc::new(layout);
+        self.frame_layout = Rc::clone(&layout);
+        layout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_are_found_inside_their_drawn_bounds_only() {
+        let (first, second) = (CellId::from_bits(1), CellId::from_bits(2));
+        let layout = FrameLayout {
+            cells: vec![first, second],
+            heights: vec![30, 50],
+            origins: vec![(100, 50), (100, 80)],
+            hidden: vec![false, false],
+            column_width: 200,
+        };
+        assert_eq!(layout.cell_at(100.0, 50.0), Some(first));
+        assert_eq!(layout.cell_at(299.5, 79.5), Some(first));
+        assert_eq!(layout.cell_at(150.0, 80.0), Some(second));
+        assert_eq!(layout.cell_at(300.0, 60.0), None);
+        assert_eq!(layout.cell_at(99.9, 60.0), None);
+        assert_eq!(layout.cell_at(150.0, 130.0), None);
+        assert_eq!(layout.screen_bounds(second), Some((80, 130)));
+        assert_eq!(layout.screen_bounds(CellId::from_bits(3)), None);
+    }
+
+    #[test]
+    fn requests_win_over_measurements_for_their_frame() {
+        let cell = CellId::from_bits(1);
+        let mut requests = HeightRequests::default();
+        requests.request(cell, 300);
+        requests.begin_frame();
+
+        // Estimated layout in `process_frame`, then the measured one at render
+        // time, which still sees the old size
+        assert_eq!(settled_height(requests.get(cell), Some(200), 200), 300);
+        assert_eq!(settled_height(requests.get(cell), Some(200), 300), 300);
+
+        // The next frame goes by the measurement again
+        requests.begin_frame();
+        assert_eq!(settled_height(requests.get(cell), Some(280), 300), 280);
+    }
+
+    #[test]
+    fn hidden_cells_are_not_found() {
+        let cell = CellId::from_bits(1);
+        let layout = FrameLayout {
+            cells: vec![cell],
+            heights: vec![40],
+            origins: vec![(0, 0)],
+            hidden: vec![true],
+            column_width: 100,
+        };
+        assert_eq!(layout.cell_at(10.0, 10.0), None);
+        assert_eq!(layout.tops(), vec![0]);
+    }
+}
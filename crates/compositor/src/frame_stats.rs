@@ -0,0 +1,264 @@
+//! Frame timing statistics for the performance HUD
+//!
+//! The main loop wraps each phase of a frame in a `PhaseGuard`, which both
+//! enters a tracing span (visible with `RUST_LOG=compositor=debug` or any
+//! span-aware subscriber) and records the phase duration. `FrameStats` keeps
+//! a short rolling history so the HUD (toggled with Super+F12 / Ctrl+Shift+F12)
+//! can show frame time, FPS, damage area and per-phase timings while
+//! diagnosing stutter.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Number of frames averaged for frame time / FPS
+const HISTORY_LEN: usize = 60;
+
+/// How often the HUD text is regenerated (avoids re-rasterizing every frame)
+pub const HUD_REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Phases of a rendered frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramePhase {
+    /// Draining backend input events
+    InputDrain,
+    /// Wayland dispatch, shared frame logic and layout
+    Layout,
+    /// Texture uploads and render data collection
+    Prerender,
+    /// Drawing into the frame buffer
+    Render,
+    /// Presenting the buffer
+    Submit,
+}
+
+impl FramePhase {
+    pub const ALL: [FramePhase; 5] = [
+        FramePhase::InputDrain,
+        FramePhase::Layout,
+        FramePhase::Prerender,
+        FramePhase::Render,
+        FramePhase::Submit,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FramePhase::InputDrain => "input_drain",
+            FramePhase::Layout => "layout",
+            FramePhase::Prerender => "prerender",
+            FramePhase::Render => "render",
+            FramePhase::Submit => "submit",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Rolling frame timing statistics
+#[derive(Debug)]
+pub struct FrameStats {
+    /// Durations of each phase in the current (or most recent) frame
+    phases: [Duration; FramePhase::ALL.len()],
+    /// Intervals between presented frames, newest last
+    frame_intervals: VecDeque<Duration>,
+    /// When the last frame was presented
+    last_frame: Option<Instant>,
+    /// Damaged area of the last frame in pixels
+    damage_area: i64,
+    /// When the HUD text was last regenerated
+    last_hud_refresh: Option<Instant>,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameStats {
+    pub fn new() -> Self {
+        Self {
+            phases: [Duration::ZERO; FramePhase::ALL.len()],
+            frame_intervals: VecDeque::with_capacity(HISTORY_LEN),
+            last_frame: None,
+            damage_area: 0,
+            last_hud_refresh: None,
+        }
+    }
+
+    /// Start timing a phase; the duration is recorded when the guard drops
+    pub fn phase(&mut self, phase: FramePhase) -> PhaseGuard<'_> {
+        let span = tracing::debug_span!("frame_phase", phase = phase.name()).entered();
+        PhaseGuard {
+            stats: self,
+            phase,
+            start: Instant::now(),
+            _span: span,
+        }
+    }
+
+    /// Record a phase duration directly
+    pub fn record_phase(&mut self, phase: FramePhase, duration: Duration) {
+        self.phases[phase.index()] = duration;
+    }
+
+    /// Mark a frame as presented with the given damaged area (pixels)
+    pub fn end_frame(&mut self, now: Instant, damage_area: i64) {
+        if let Some(last) = self.last_frame {
+            if self.frame_intervals.len() == HISTORY_LEN {
+                self.frame_intervals.pop_front();
+            }
+            self.frame_intervals.push_back(now.duration_since(last));
+        }
+        self.last_frame = Some(now);
+        self.damage_area = damage_area;
+    }
+
+    /// Duration of a phase in the most recent frame
+    pub fn phase_duration(&self, phase: FramePhase) -> Duration {
+        self.phases[phase.index()]
+    }
+
+    /// Average interval between presented frames
+    pub fn average_frame_time(&self) -> Option<Duration> {
+        if self.frame_intervals.is_empty() {
+            return None;
+        }
+        let total: Duration = self.frame_intervals.iter().sum();
+        Some(total / self.frame_intervals.len() as u32)
+    }
+
+    /// Longest interval between presented frames in the history window
+    pub fn max_frame_time(&self) -> Option<Duration> {
+        self.frame_intervals.iter().max().copied()
+    }
+
+    /// Frames per second derived from the average frame time
+    pub fn fps(&self) -> Option<f64> {
+        self.average_frame_time()
+            .filter(|d| !d.is_zero())
+            .map(|d| 1.0 / d.as_secs_f64())
+    }
+
+    /// Whether the HUD text should be regenerated, marking it refreshed if so
+    pub fn hud_refresh_due(&mut self, now: Instant) -> bool {
+        let due = self
+            .last_hud_refresh
+            .map_or(true, |t| now.duration_since(t) >= HUD_REFRESH_INTERVAL);
+        if due {
+            self.last_hud_refresh = Some(now);
+        }
+        due
+    }
+
+    /// Force the HUD text to be regenerated on the next check
+    pub fn invalidate_hud(&mut self) {
+        self.last_hud_refresh = None;
+    }
+
+    /// Text lines shown in the HUD overlay
+    pub fn hud_lines(&self) -> Vec<String> {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+
+        let mut lines = vec![
+            match (self.average_frame_time(), self.max_frame_time(), self.fps()) {
+                (Some(avg), Some(max), Some(fps)) => format!(
+                    "frame {:5.1} ms  max {:5.1} ms  {:5.1} fps",
+                    ms(avg),
+                    ms(max),
+                    fps
+                ),
+                _ => "frame   --".to_string(),
+            },
+            format!("damage {} px", self.damage_area),
+        ];
+        for phase in FramePhase::ALL {
+            lines.push(format!(
+                "{:<12}{:6.2} ms",
+                phase.name(),
+                ms(self.phase_duration(phase))
+            ));
+        }
+        lines
+    }
+}
+
+/// Times a frame phase and keeps its tracing span entered while alive
+pub struct PhaseGuard<'a> {
+    stats: &'a mut FrameStats,
+    phase: FramePhase,
+    start: Instant,
+    _span: tracing::span::EnteredSpan,
+}
+
+impl Drop for PhaseGuard<'_> {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        self.stats.record_phase(self.phase, elapsed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phase_guard_records_duration() {
+        let mut stats = FrameStats::new();
+        {
+            let _guard = stats.phase(FramePhase::Render);
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        assert!(stats.phase_duration(FramePhase::Render) >= Duration::from_millis(2));
+        assert_eq!(stats.phase_duration(FramePhase::Submit), Duration::ZERO);
+    }
+
+    #[test]
+    fn fps_from_frame_intervals() {
+        let mut stats = FrameStats::new();
+        assert!(stats.fps().is_none());
+
+        let start = Instant::now();
+        for i in 0..=10 {
+            stats.end_frame(start + Duration::from_millis(20 * i), 100);
+        }
+        assert_eq!(stats.average_frame_time(), Some(Duration::from_millis(20)));
+        assert!((stats.fps().unwrap() - 50.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut stats = FrameStats::new();
+        let start = Instant::now();
+        // One slow frame followed by a full window of fast ones
+        stats.end_frame(start, 0);
+        stats.end_frame(start + Duration::from_millis(500), 0);
+        let mut t = start + Duration::from_millis(500);
+        for _ in 0..HISTORY_LEN {
+            t += Duration::from_millis(10);
+            stats.end_frame(t, 0);
+        }
+        assert_eq!(stats.max_frame_time(), Some(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn hud_refresh_is_rate_limited() {
+        let mut stats = FrameStats::new();
+        let now = Instant::now();
+        assert!(stats.hud_refresh_due(now));
+        assert!(!stats.hud_refresh_due(now + Duration::from_millis(10)));
+        assert!(stats.hud_refresh_due(now + HUD_REFRESH_INTERVAL));
+        stats.invalidate_hud();
+        assert!(stats.hud_refresh_due(now + HUD_REFRESH_INTERVAL));
+    }
+
+    #[test]
+    fn hud_lines_list_every_phase() {
+        let stats = FrameStats::new();
+        let lines = stats.hud_lines();
+        for phase in FramePhase::ALL {
+            assert!(lines.iter().any(|l| l.starts_with(phase.name())));
+        }
+    }
+}
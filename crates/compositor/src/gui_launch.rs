@@ -0,0 +1,322 @@
+//! Matching GUI windows to the commands that launched them
+//!
+//! When a toplevel appears, the PID of its client's socket peer is looked up
+//! in `/proc` and its ancestry walked: a launch whose command process is an
+//! ancestor owns the window, so two apps started back-to-back each land above
+//! their own output terminal. Processes are identified by PID and start time,
+//! so a recycled PID doesn't match.
+//!
+//! Apps that detach from their launcher (double fork, D-Bus activation) are
+//! found by the launch token in `TERMSTACK_LAUNCH_TOKEN`, read from the
+//! client's environment. Clients with neither (X11 apps behind
+//! xwayland-satellite) take the oldest launch that has no window yet.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::terminal_manager::TerminalId;
+
+/// Environment variable carrying the launch token to spawned commands
+pub const LAUNCH_TOKEN_ENV: &str = "TERMSTACK_LAUNCH_TOKEN";
+
+/// How long a launch waits for windows before it is forgotten
+pub const PENDING_LAUNCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A process, told apart from later ones reusing its PID by its start time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessId {
+    pub pid: i32,
+    /// Clock ticks after boot (field 22 of `/proc/<pid>/stat`)
+    pub start_time: u64,
+}
+
+impl ProcessId {
+    /// Identify a running process
+    pub fn of(pid: i32) -> Option<Self> {
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        let (_, start_time) = parse_stat(&stat)?;
+        Some(Self { pid, start_time })
+    }
+}
+
+/// The process and its ancestors, nearest first, up to (not including) init
+pub fn ancestry(pid: i32) -> Vec<ProcessId> {
+    // Bounded in case /proc changes under us and forms a cycle
+    const MAX_DEPTH: usize = 64;
+
+    let mut chain = Vec::new();
+    let mut pid = pid;
+    while pid > 1 && chain.len() < MAX_DEPTH {
+        let Ok(stat) = std::fs::read_to_string(format!("/proc/{}/stat", pid)) else {
+            break;
+        };
+        let Some((ppid, start_time)) = parse_stat(&stat) else {
+            break;
+        };
+        chain.push(ProcessId { pid, start_time });
+        pid = ppid;
+    }
+    chain
+}
+
+/// Parse `(ppid, start_time)` from the contents of `/proc/<pid>/stat`
+///
+/// The command name (field 2) may contain spaces or parentheses, so fields
+/// are read after the last `)`.
+fn parse_stat(stat: &str) -> Option<(i32, u64)> {
+    let rest = &stat[stat.rfind(')')? + 1..];
+    let mut fields = rest.split_whitespace();
+    let ppid = fields.nth(1)?.parse().ok()?;
+    // Field 22 overall; state and ppid (fields 3 and 4) are already consumed
+    let start_time = fields.nth(17)?.parse().ok()?;
+    Some((ppid, start_time))
+}
+
+/// Where a new window's client comes from
+#[derive(Debug, Clone, Default)]
+pub struct ClientOrigin {
+    /// The client process and its ancestors
+    pub ancestry: Vec<ProcessId>,
+    /// `LAUNCH_TOKEN_ENV` in the client's environment
+    pub token: Option<String>,
+}
+
+impl ClientOrigin {
+    /// Look up the client with socket peer `pid` in `/proc`
+    pub fn of(pid: i32) -> Self {
+        Self {
+            ancestry: ancestry(pid),
+            token: process_launch_token(pid),
+        }
+    }
+}
+
+/// A command that may still open windows
+#[derive(Debug, Clone)]
+pub struct PendingLaunch {
+    /// Value of `LAUNCH_TOKEN_ENV` in the command's environment
+    pub token: String,
+    /// The command's process, an ancestor of the windows it opens
+    pub process: Option<ProcessId>,
+    /// Terminal showing the command's output, windows go above it
+    pub output_terminal: TerminalId,
+    /// Command string for the windows' title bars
+    pub command: String,
+    /// Whether the first window takes focus (foreground `gui` launch)
+    pub foreground: bool,
+    /// When the command was spawned
+    pub set_at: Instant,
+    /// Whether a window has been linked to this launch
+    pub linked: bool,
+}
+
+/// What a new window inherits from its launch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaunchMatch {
+    pub output_terminal: TerminalId,
+    pub command: String,
+    /// Only true for the first window of a foreground launch
+    pub foreground: bool,
+}
+
+/// Launches waiting for their windows, oldest first. Kept on `TermStack`.
+#[derive(Debug, Default)]
+pub struct PendingLaunches {
+    queue: VecDeque<PendingLaunch>,
+    next_token: u64,
+}
+
+impl PendingLaunches {
+    /// A fresh token to put in a command's environment
+    pub fn next_token(&mut self) -> String {
+        self.next_token += 1;
+        format!("{}-{}", std::process::id(), self.next_token)
+    }
+
+    /// Remember a spawned command whose windows should link to `output_terminal`
+    pub fn push(
+        &mut self,
+        token: String,
+        process: Option<ProcessId>,
+        output_terminal: TerminalId,
+        command: &str,
+        foreground: bool,
+    ) {
+        self.queue.push_back(PendingLaunch {
+            token,
+            process,
+            output_terminal,
+            command: command.to_string(),
+            foreground,
+            set_at: Instant::now(),
+            linked: false,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PendingLaunch> {
+        self.queue.iter()
+    }
+
+    /// Link a new window to its launch
+    ///
+    /// The launch whose process is the nearest ancestor of the client wins,
+    /// then one with the client's token. Failing both, the oldest launch that
+    /// has no window yet is used, and after that the latest linked one, so
+    /// further windows of a multi-window app stay with their first.
+    pub fn claim(&mut self, origin: &ClientOrigin) -> Option<LaunchMatch> {
+        let by_ancestry = origin.ancestry.iter().find_map(|process| {
+            self.queue.iter().position(|launch| launch.process == Some(*process))
+        });
+        let index = by_ancestry
+            .or_else(|| {
+                let token = origin.token.as_deref()?;
+                self.queue.iter().position(|launch| launch.token == token)
+            })
+            .or_else(|| self.queue.iter().position(|launch| !launch.linked))
+            .or_else(|| self.queue.len().checked_sub(1))?;
+        let launch = &mut self.queue[index];
+        launch.linked = true;
+        Some(LaunchMatch {
+            output_terminal: launch.output_terminal,
+            command: launch.command.clone(),
+            foreground: std::mem::take(&mut launch.foreground),
+        })
+    }
+
+    /// Drop launches older than `PENDING_LAUNCH_TIMEOUT`, returning them
+    pub fn expire(&mut self, now: Instant) -> Vec<PendingLaunch> {
+        // Oldest first, so the stale ones are at the front
+        let stale = self
+            .queue
+            .iter()
+            .take_while(|launch| now.saturating_duration_since(launch.set_at) > PENDING_LAUNCH_TIMEOUT)
+            .count();
+        self.queue.drain(..stale).collect()
+    }
+}
+
+/// The launch token in a process's environment, if it has one
+pub fn process_launch_token(pid: i32) -> Option<String> {
+    let environ = std::fs::read(format!("/proc/{}/environ", pid)).ok()?;
+    launch_token_in_environ(&environ)
+}
+
+/// Find `LAUNCH_TOKEN_ENV` in NUL-separated `KEY=value` pairs
+fn launch_token_in_environ(environ: &[u8]) -> Option<String> {
+    let prefix = format!("{}=", LAUNCH_TOKEN_ENV);
+    environ
+        .split(|&b| b == 0)
+        .find_map(|var| var.strip_prefix(prefix.as_bytes()))
+        .map(|value| String::from_utf8_lossy(value).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: i32) -> ProcessId {
+        ProcessId { pid, start_time: 1000 + pid as u64 }
+    }
+
+    fn launches() -> (PendingLaunches, String, String) {
+        let mut launches = PendingLaunches::default();
+        let first = launches.next_token();
+        launches.push(first.clone(), Some(process(100)), TerminalId(1), "gimp", true);
+        let second = launches.next_token();
+        launches.push(second.clone(), Some(process(200)), TerminalId(2), "inkscape", true);
+        (launches, first, second)
+    }
+
+    fn token(token: &str) -> ClientOrigin {
+        ClientOrigin { ancestry: Vec::new(), token: Some(token.to_string()) }
+    }
+
+    fn child_of(launcher: ProcessId) -> ClientOrigin {
+        ClientOrigin { ancestry: vec![process(900), launcher, process(1)], token: None }
+    }
+
+    #[test]
+    fn ancestry_links_windows_to_their_launch() {
+        let (mut launches, first, _) = launches();
+
+        // The second app maps first
+        let window = launches.claim(&child_of(process(200))).unwrap();
+        assert_eq!(window.output_terminal, TerminalId(2));
+        assert!(window.foreground);
+
+        // Ancestry wins over an inherited token
+        let origin = ClientOrigin { token: Some(first), ..child_of(process(200)) };
+        assert_eq!(launches.claim(&origin).unwrap().output_terminal, TerminalId(2));
+
+        // A recycled PID is a different process
+        let recycled = ProcessId { pid: 100, start_time: 5 };
+        assert_eq!(launches.claim(&child_of(recycled)).unwrap().output_terminal, TerminalId(1));
+    }
+
+    #[test]
+    fn tokens_link_windows_to_their_launch() {
+        let (mut launches, first, second) = launches();
+        assert_ne!(first, second);
+
+        // The second app maps first
+        let window = launches.claim(&token(&second)).unwrap();
+        assert_eq!(window.output_terminal, TerminalId(2));
+        assert_eq!(window.command, "inkscape");
+        assert!(window.foreground);
+
+        let window = launches.claim(&token(&first)).unwrap();
+        assert_eq!(window.output_terminal, TerminalId(1));
+
+        // A second window of the same app doesn't take focus again
+        let window = launches.claim(&token(&second)).unwrap();
+        assert_eq!(window.output_terminal, TerminalId(2));
+        assert!(!window.foreground);
+    }
+
+    #[test]
+    fn windows_without_token_take_launches_in_order() {
+        let (mut launches, _, _) = launches();
+        assert_eq!(launches.claim(&ClientOrigin::default()).unwrap().output_terminal, TerminalId(1));
+        assert_eq!(launches.claim(&token("unknown")).unwrap().output_terminal, TerminalId(2));
+        // All linked: further windows join the latest launch
+        assert_eq!(launches.claim(&ClientOrigin::default()).unwrap().output_terminal, TerminalId(2));
+        assert!(PendingLaunches::default().claim(&ClientOrigin::default()).is_none());
+    }
+
+    #[test]
+    fn stale_launches_expire() {
+        let (mut launches, _, _) = launches();
+        assert!(launches.expire(Instant::now()).is_empty());
+        let expired = launches.expire(Instant::now() + PENDING_LAUNCH_TIMEOUT + Duration::from_secs(1));
+        assert_eq!(expired.len(), 2);
+        assert!(launches.is_empty());
+    }
+
+    #[test]
+    fn stat_fields_after_the_command_name() {
+        let stat = "4242 (my (odd) app) S 17 4242 4242 0 -1 4194560 1 0 0 0 0 0 0 0 20 0 1 0 987654 0 0";
+        assert_eq!(parse_stat(stat), Some((17, 987654)));
+    }
+
+    #[test]
+    fn own_process_is_in_its_ancestry() {
+        let pid = std::process::id() as i32;
+        let chain = ancestry(pid);
+        assert_eq!(chain.first().copied(), ProcessId::of(pid));
+    }
+
+    #[test]
+    fn token_is_read_from_environ() {
+        let environ = b"HOME=/home/me\0TERMSTACK_LAUNCH_TOKEN=42-3\0PATH=/bin\0";
+        assert_eq!(launch_token_in_environ(environ).as_deref(), Some("42-3"));
+        assert_eq!(launch_token_in_environ(b"HOME=/home/me\0"), None);
+    }
+}
@@ -0,0 +1,59 @@
+//! Keybinding help overlay (Super+? or Ctrl+Shift+?)
+//!
+//! A panel in the middle of the screen listing every compositor binding in
+//! `input::KEYBINDINGS` next to what it does, plus the window switcher keys
+//! and the bindings plugins registered, so nobody has to look chords up in the
+//! README. Esc or the binding again closes it.
+
+use crate::compositor_actions::CompositorAction;
+use crate::input::KEYBINDINGS;
+use crate::plugins::PluginHost;
+
+/// Overlay text: a heading, then one line per action with all its chords
+pub fn help_lines(plugins: &PluginHost) -> Vec<String> {
+    let mut entries: Vec<(CompositorAction, Vec<String>)> = Vec::new();
+    for binding in KEYBINDINGS {
+        let chord = format!("{}{}", binding.mods.label(), binding.label);
+        match entries.iter_mut().find(|(action, _)| *action == binding.action) {
+            Some((_, chords)) if chords.contains(&chord) => {}
+            Some((_, chords)) => chords.push(chord),
+            None => entries.push((binding.action, vec![chord])),
+        }
+    }
+
+    let mut rows: Vec<(String, String)> = entries
+        .into_iter()
+        .map(|(action, chords)| (chords.join(" / "), action.description().to_string()))
+        .collect();
+    rows.push(("Alt+Tab / Super+Tab".to_string(), "Switch cells (Shift+Tab goes back)".to_string()));
+    rows.extend(plugins.bindings().map(|(chord, name)| (chord, format!("{name} (plugin)"))));
+
+    let width = rows.iter().map(|(chord, _)| chord.chars().count()).max().unwrap_or(0);
+    let mut lines = vec!["Keybindings (Esc closes)".to_string(), String::new()];
+    lines.extend(rows.iter().map(|(chord, description)| format!("{chord:<width$}  {description}")));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_bound_action_is_listed_once() {
+        let lines = help_lines(&PluginHost::default());
+        for binding in KEYBINDINGS {
+            let description = binding.action.description();
+            let count = lines.iter().filter(|line| line.ends_with(description)).count();
+            assert_eq!(count, 1, "{description:?} listed {count} times:\n{}", lines.join("\n"));
+        }
+    }
+
+    #[test]
+    fn chords_of_one_action_share_a_line() {
+        let lines = help_lines(&PluginHost::default());
+        let help = lines.iter().find(|line| line.ends_with(CompositorAction::ToggleHelp.description())).unwrap();
+        assert!(help.starts_with("Super+? / Ctrl+Shift+?"), "{help}");
+        let new_terminal = lines.iter().find(|line| line.ends_with("New terminal")).unwrap();
+        assert!(new_terminal.contains("Super+Return"), "{new_terminal}");
+    }
+}
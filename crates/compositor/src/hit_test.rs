@@ -0,0 +1,108 @@
+//! Hit-test reports: what the compositor thinks is under a point
+//!
+//! Clicks find their cell in the last committed `FrameLayout`
+//! (`TermStack::window_at`), the one the renderer placed cells by; the two
+//! disagreeing has broken clicks more than once. `termstack hit-test X Y`
+//! reports the cell, title bar or terminal row and column a click at that
+//! screen point would reach, the cell whose bottom edge a press there would
+//! drag, and the cell the frame layout drew there. Harness tests sweep a
+//! grid of points over a stack and require the answers to match.
+
+use serde::{Deserialize, Serialize};
+
+use crate::coords::ScreenY;
+use crate::state::{StackWindow, TermStack, WindowPosition};
+use crate::terminal_manager::TerminalManager;
+use crate::title_bar::TITLE_BAR_HEIGHT;
+
+/// Part of a cell a point is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "part", rename_all = "snake_case")]
+pub enum HitPart {
+    TitleBar,
+    /// Terminal content, at this grid row (counted from the first line of
+    /// the scrollback) and column
+    Terminal { row: usize, col: usize },
+    /// An external window's surface
+    Window,
+}
+
+/// Answer for one point
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HitTest {
+    /// The point, in screen coordinates (Y=0 at top)
+    pub x: f64,
+    pub y: f64,
+    /// Column of the stack under `x`, counted from 0 on the left
+    pub column: usize,
+    /// Index of the cell a click would land on
+    pub cell: Option<usize>,
+    pub part: Option<HitPart>,
+    /// Index of the cell whose bottom edge a press here would start resizing
+    pub resize_handle: Option<usize>,
+    /// Index of the cell drawn under the point by the last frame layout
+    pub drawn: Option<usize>,
+}
+
+impl TermStack {
+    /// What is under the screen point (`x`, `y`), as a click and the last
+    /// frame see it
+    pub fn hit_test(&self, x: f64, y: f64, terminal_manager: &TerminalManager) -> HitTest {
+        let render_y = ScreenY::new(y).to_render(self.output_size.h);
+        let hit = crate::selection::position_at_point(self, terminal_manager, x, render_y);
+        let cell = self.window_at(x, render_y);
+
+        let part = cell.and_then(|index| {
+            let node = self.layout_nodes.get(index)?;
+            match &node.cell {
+                StackWindow::Terminal(_) => match hit? {
+                    (_, WindowPosition::TitleBar { .. }) => Some(HitPart::TitleBar),
+                    (_, WindowPosition::Content { col, row }) => Some(HitPart::Terminal { row, col }),
+                },
+                StackWindow::External(entry) => {
+                    let (top, _) = self.get_window_screen_bounds(index)?;
+                    let on_title_bar = entry.has_title_bar() && y < (top + TITLE_BAR_HEIGHT as i32) as f64;
+                    Some(if on_title_bar { HitPart::TitleBar } else { HitPart::Window })
+                }
+            }
+        });
+
+        let drawn = self.frame_layout.cell_at(x, y).and_then(|cell| self.cell_index(cell));
+
+        HitTest {
+            x,
+            y,
+            column: self.columns.position(self.column_at_x(x)).unwrap_or(0),
+            cell,
+            part,
+            resize_handle: self.find_resize_handle_at(x, ScreenY::new(y)),
+            drawn,
+        }
+    }
+}
+
+/// Answer `termstack hit-test` requests with one `HitTest` per point
+pub fn handle_ipc_hit_test_requests(compositor: &mut TermStack, terminal_manager: &TerminalManager) {
+    for (points, stream) in std::mem::take(&mut compositor.pending_hit_tests) {
+        let hits: Vec<HitTest> = points
+            .iter()
+            .map(|&(x, y)| compositor.hit_test(x, y, terminal_manager))
+            .collect();
+        if let Err(e) = crate::ipc::send_json_response(stream, &hits) {
+            tracing::warn!(error = ?e, "Failed to send hit test response");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parts_serialize_with_a_tag() {
+        let json = serde_json::to_string(&HitPart::Terminal { row: 3, col: 7 }).unwrap();
+        assert_eq!(json, r#"{"part":"terminal","row":3,"col":7}"#);
+        let json = serde_json::to_string(&HitPart::TitleBar).unwrap();
+        assert_eq!(serde_json::from_str::<HitPart>(&json).unwrap(), HitPart::TitleBar);
+    }
+}
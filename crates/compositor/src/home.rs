@@ -0,0 +1,49 @@
+//! The home directory in paths
+//!
+//! Config values, saved layouts and shell redirections write the home
+//! directory as `~` (only the current user's: `~bob` is left alone).
+
+use std::path::{Path, PathBuf};
+
+/// `$HOME`, if set
+pub fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(PathBuf::from)
+}
+
+/// `path` with a leading `~` replaced by `home`
+pub fn expand_home(path: &str, home: Option<&Path>) -> PathBuf {
+    match (path.strip_prefix('~'), home) {
+        (Some(""), Some(home)) => home.to_path_buf(),
+        (Some(rest), Some(home)) if rest.starts_with('/') => home.join(&rest[1..]),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// `path` with a leading `home` written as `~`
+pub fn shorten_home(path: &Path, home: Option<&Path>) -> String {
+    match home.and_then(|home| path.strip_prefix(home).ok()) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn home_directory_is_written_as_tilde() {
+        let home = Some(Path::new("/home/karl"));
+        assert_eq!(shorten_home(Path::new("/home/karl/code"), home), "~/code");
+        assert_eq!(shorten_home(Path::new("/home/karl"), home), "~");
+        assert_eq!(shorten_home(Path::new("/home/karlb"), home), "/home/karlb");
+        assert_eq!(shorten_home(Path::new("/tmp"), None), "/tmp");
+
+        let home = Some(Path::new("/home/ana"));
+        assert_eq!(expand_home("~/code", home), PathBuf::from("/home/ana/code"));
+        assert_eq!(expand_home("~", home), PathBuf::from("/home/ana"));
+        assert_eq!(expand_home("~bob/x", home), PathBuf::from("~bob/x"));
+        assert_eq!(expand_home("/srv", home), PathBuf::from("/srv"));
+    }
+}
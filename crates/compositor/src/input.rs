@@ -19,16 +19,19 @@ use smithay::backend::input::{
     AbsolutePositionEvent, Axis, AxisSource, ButtonState, Event, InputBackend, InputEvent,
     KeyState, KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent,
 };
-use smithay::input::keyboard::{FilterResult, Keysym, ModifiersState};
+use smithay::input::keyboard::{xkb, FilterResult, Keysym, ModifiersState};
 use smithay::input::pointer::{AxisFrame, ButtonEvent, MotionEvent};
 use smithay::reexports::wayland_server::Resource;
 use smithay::utils::{Logical, Point, SERIAL_COUNTER};
 
+use crate::color_tags::ColorTag;
 use crate::compositor_actions::{CompositorAction, apply_compositor_action, SCROLL_STEP};
 use crate::coords::{RenderY, ScreenY};
+use crate::keyboard_resize::ResizeKey;
 use crate::render::FOCUS_INDICATOR_WIDTH;
+use crate::plugins::KeyMods;
 use crate::selection;
-use crate::state::{FocusedWindow, StackWindow, TermStack, ResizeDrag, SurfaceKind, MIN_WINDOW_HEIGHT};
+use crate::state::{StackWindow, TermStack, ResizeDrag, SurfaceKind, MIN_WINDOW_HEIGHT};
 use crate::terminal_manager::{TerminalId, TerminalManager};
 use crate::title_bar::{CLOSE_BUTTON_WIDTH, TITLE_BAR_HEIGHT};
 
@@ -37,6 +40,9 @@ use terminal::Side;
 /// Left mouse button code (BTN_LEFT in evdev)
 const BTN_LEFT: u32 = 0x110;
 
+/// Right mouse button code (BTN_RIGHT in evdev)
+const BTN_RIGHT: u32 = 0x111;
+
 /// Middle mouse button code (BTN_MIDDLE in evdev)
 const BTN_MIDDLE: u32 = 0x112;
 
@@ -96,6 +102,9 @@ fn copy_to_primary_selection(text: &str) {
 /// - `window_height`: The terminal cell's height in pixels
 /// - `char_width`, `char_height`: Character cell dimensions from the font
 /// - `title_bar_height`: Height of the title bar in pixels (0 if no title bar)
+///
+/// Columns are plain cells: the right half of a double-width character maps
+/// to its spacer column, which the terminal's selection moves onto the character.
 fn render_to_grid_coords(
     render_x: f64,
     render_y: f64,
@@ -171,50 +180,139 @@ fn update_terminal_selection(
     }
 }
 
-/// Parse compositor keybindings from modifiers and keysym
-fn parse_compositor_keybinding(modifiers: &ModifiersState, keysym: Keysym) -> Option<CompositorAction> {
-    // Ctrl+Shift bindings (work when Super is grabbed by parent compositor)
-    if modifiers.ctrl && modifiers.shift && !modifiers.logo && !modifiers.alt {
-        return match keysym {
-            Keysym::q | Keysym::Q => Some(CompositorAction::Quit),
-            Keysym::Return | Keysym::t | Keysym::T => Some(CompositorAction::SpawnTerminal),
-            Keysym::j | Keysym::J | Keysym::Down => Some(CompositorAction::FocusNext),
-            Keysym::k | Keysym::K | Keysym::Up => Some(CompositorAction::FocusPrev),
-            Keysym::Page_Down => Some(CompositorAction::PageDown),
-            Keysym::Page_Up => Some(CompositorAction::PageUp),
-            Keysym::v | Keysym::V => Some(CompositorAction::Paste),
-            Keysym::c | Keysym::C => Some(CompositorAction::Copy),
-            Keysym::plus | Keysym::equal => Some(CompositorAction::FontSizeUp),
-            Keysym::minus | Keysym::underscore => Some(CompositorAction::FontSizeDown),
-            _ => None,
-        };
-    }
+/// Modifiers a compositor binding is held with (exactly these, no others)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BindingMods {
+    /// Ctrl+Shift, for when Super is grabbed by a parent compositor
+    CtrlShift,
+    /// Super (Mod4)
+    Super,
+    /// Super+Shift, for keys only reachable with Shift (like `?`)
+    SuperShift,
+    /// Shift alone, for the usual terminal scrollback keys
+    Shift,
+    /// No modifiers
+    None,
+}
 
-    // Super (Mod4) bindings
-    if modifiers.logo && !modifiers.ctrl && !modifiers.shift && !modifiers.alt {
-        return match keysym {
-            Keysym::q | Keysym::Q => Some(CompositorAction::Quit),
-            Keysym::Return | Keysym::t | Keysym::T => Some(CompositorAction::SpawnTerminal),
-            Keysym::j | Keysym::J => Some(CompositorAction::FocusNext),
-            Keysym::k | Keysym::K => Some(CompositorAction::FocusPrev),
-            Keysym::Down => Some(CompositorAction::ScrollDown),
-            Keysym::Up => Some(CompositorAction::ScrollUp),
-            Keysym::Home => Some(CompositorAction::ScrollToTop),
-            Keysym::End => Some(CompositorAction::ScrollToBottom),
+impl BindingMods {
+    fn of(modifiers: &ModifiersState) -> Option<Self> {
+        if modifiers.alt {
+            return None;
+        }
+        match (modifiers.ctrl, modifiers.shift, modifiers.logo) {
+            (true, true, false) => Some(Self::CtrlShift),
+            (false, false, true) => Some(Self::Super),
+            (false, true, true) => Some(Self::SuperShift),
+            (false, true, false) => Some(Self::Shift),
+            (false, false, false) => Some(Self::None),
             _ => None,
-        };
+        }
     }
 
-    // Page Up/Down without modifiers
-    if !modifiers.ctrl && !modifiers.shift && !modifiers.logo && !modifiers.alt {
-        return match keysym {
-            Keysym::Page_Up => Some(CompositorAction::PageUp),
-            Keysym::Page_Down => Some(CompositorAction::PageDown),
-            _ => None,
-        };
+    /// Prefix for the chord as shown to users
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Self::CtrlShift => "Ctrl+Shift+",
+            Self::Super => "Super+",
+            Self::SuperShift => "Super+",
+            Self::Shift => "Shift+",
+            Self::None => "",
+        }
     }
+}
+
+/// A compositor keybinding: any of `keys` held with `mods` triggers `action`
+pub(crate) struct Keybinding {
+    pub mods: BindingMods,
+    pub keys: &'static [Keysym],
+    /// The key as shown to users
+    pub label: &'static str,
+    pub action: CompositorAction,
+}
+
+const fn bind(
+    mods: BindingMods,
+    keys: &'static [Keysym],
+    label: &'static str,
+    action: CompositorAction,
+) -> Keybinding {
+    Keybinding { mods, keys, label, action }
+}
+
+/// Every compositor keybinding, in the order the help overlay lists them
+pub(crate) const KEYBINDINGS: &[Keybinding] = {
+    use BindingMods::{CtrlShift, Super, SuperShift};
+    use CompositorAction as A;
+    &[
+        bind(Super, &[Keysym::t, Keysym::T], "T", A::SpawnTerminal),
+        bind(Super, &[Keysym::Return], "Return", A::SpawnTerminal),
+        bind(CtrlShift, &[Keysym::Return, Keysym::t, Keysym::T], "T", A::SpawnTerminal),
+        bind(Super, &[Keysym::j, Keysym::J], "J", A::FocusNext),
+        bind(CtrlShift, &[Keysym::j, Keysym::J, Keysym::Down], "J", A::FocusNext),
+        bind(Super, &[Keysym::k, Keysym::K], "K", A::FocusPrev),
+        bind(CtrlShift, &[Keysym::k, Keysym::K, Keysym::Up], "K", A::FocusPrev),
+        bind(Super, &[Keysym::o, Keysym::O], "O", A::FocusBack),
+        bind(CtrlShift, &[Keysym::o, Keysym::O], "O", A::FocusBack),
+        bind(Super, &[Keysym::Left], "Left", A::FocusParent),
+        bind(CtrlShift, &[Keysym::Left], "Left", A::FocusParent),
+        bind(Super, &[Keysym::Right], "Right", A::FocusNextChild),
+        bind(CtrlShift, &[Keysym::Right], "Right", A::FocusNextChild),
+        bind(Super, &[Keysym::h, Keysym::H], "H", A::FocusColumnLeft),
+        bind(CtrlShift, &[Keysym::h, Keysym::H], "H", A::FocusColumnLeft),
+        bind(Super, &[Keysym::l, Keysym::L], "L", A::FocusColumnRight),
+        bind(CtrlShift, &[Keysym::l, Keysym::L], "L", A::FocusColumnRight),
+        bind(SuperShift, &[Keysym::h, Keysym::H], "H", A::MoveToColumnLeft),
+        bind(SuperShift, &[Keysym::l, Keysym::L], "L", A::MoveToColumnRight),
+        bind(Super, &[Keysym::Down], "Down", A::ScrollDown),
+        bind(Super, &[Keysym::Up], "Up", A::ScrollUp),
+        bind(Super, &[Keysym::Home], "Home", A::ScrollToTop),
+        bind(Super, &[Keysym::End], "End", A::ScrollToBottom),
+        bind(BindingMods::None, &[Keysym::Page_Down], "Page Down", A::PageDown),
+        bind(CtrlShift, &[Keysym::Page_Down], "Page Down", A::PageDown),
+        bind(BindingMods::None, &[Keysym::Page_Up], "Page Up", A::PageUp),
+        bind(CtrlShift, &[Keysym::Page_Up], "Page Up", A::PageUp),
+        bind(BindingMods::Shift, &[Keysym::Page_Up], "Page Up", A::ScrollbackPageUp),
+        bind(BindingMods::Shift, &[Keysym::Page_Down], "Page Down", A::ScrollbackPageDown),
+        bind(CtrlShift, &[Keysym::c, Keysym::C], "C", A::Copy),
+        bind(CtrlShift, &[Keysym::v, Keysym::V], "V", A::Paste),
+        bind(CtrlShift, &[Keysym::plus, Keysym::equal], "+", A::FontSizeUp),
+        bind(CtrlShift, &[Keysym::minus, Keysym::underscore], "-", A::FontSizeDown),
+        bind(Super, &[Keysym::r, Keysym::R], "R", A::ResizeMode),
+        bind(CtrlShift, &[Keysym::r, Keysym::R], "R", A::ResizeMode),
+        bind(Super, &[Keysym::a, Keysym::A], "A", A::Absorb),
+        bind(CtrlShift, &[Keysym::a, Keysym::A], "A", A::Absorb),
+        bind(SuperShift, &[Keysym::z, Keysym::Z], "Z", A::Undo),
+        bind(CtrlShift, &[Keysym::z, Keysym::Z], "Z", A::Undo),
+        bind(Super, &[Keysym::g, Keysym::G], "G", A::CycleColorTag),
+        bind(CtrlShift, &[Keysym::g, Keysym::G], "G", A::CycleColorTag),
+        bind(Super, &[Keysym::f, Keysym::F], "F", A::ToggleFilter),
+        bind(CtrlShift, &[Keysym::f, Keysym::F], "F", A::ToggleFilter),
+        bind(Super, &[Keysym::e, Keysym::E], "E", A::ToggleSmartView),
+        bind(CtrlShift, &[Keysym::e, Keysym::E], "E", A::ToggleSmartView),
+        bind(Super, &[Keysym::grave], "`", A::ToggleQuickTerminal),
+        // Shift turns ` into ~ on most layouts
+        bind(CtrlShift, &[Keysym::grave, Keysym::asciitilde], "`", A::ToggleQuickTerminal),
+        bind(Super, &[Keysym::n, Keysym::N], "N", A::ToggleDoNotDisturb),
+        bind(CtrlShift, &[Keysym::n, Keysym::N], "N", A::ToggleDoNotDisturb),
+        bind(CtrlShift, &[Keysym::d, Keysym::D], "D", A::ToggleDecorations),
+        bind(Super, &[Keysym::F12], "F12", A::ToggleHud),
+        bind(CtrlShift, &[Keysym::F12], "F12", A::ToggleHud),
+        bind(SuperShift, &[Keysym::question], "?", A::ToggleHelp),
+        // Shift turns / into ? on most layouts
+        bind(CtrlShift, &[Keysym::question, Keysym::slash], "?", A::ToggleHelp),
+        bind(Super, &[Keysym::q, Keysym::Q], "Q", A::Quit),
+        bind(CtrlShift, &[Keysym::q, Keysym::Q], "Q", A::Quit),
+    ]
+};
 
-    None
+/// Parse compositor keybindings from modifiers and keysym
+fn parse_compositor_keybinding(modifiers: &ModifiersState, keysym: Keysym) -> Option<CompositorAction> {
+    let mods = BindingMods::of(modifiers)?;
+    KEYBINDINGS
+        .iter()
+        .find(|binding| binding.mods == mods && binding.keys.contains(&keysym))
+        .map(|binding| binding.action)
 }
 
 impl TermStack {
@@ -224,9 +322,12 @@ impl TermStack {
         event: InputEvent<I>,
         terminals: &mut TerminalManager,
     ) {
+        if let Some(recorder) = &mut self.input_recorder {
+            recorder.record(&event, self.output_size.w, self.output_size.h);
+        }
         match event {
             InputEvent::Keyboard { event } => self.handle_keyboard_event(event, Some(terminals)),
-            InputEvent::PointerMotion { event } => self.handle_pointer_motion(event),
+            InputEvent::PointerMotion { event } => self.handle_pointer_motion(event, terminals),
             InputEvent::PointerMotionAbsolute { event } => {
                 self.handle_pointer_motion_absolute(event, terminals)
             }
@@ -257,7 +358,9 @@ impl TermStack {
 
         // If an external Wayland window has focus, forward events via Wayland protocol
         // Note: When a popup grab is active, events are routed through PopupKeyboardGrab
-        if self.is_external_focused() || keyboard.is_grabbed() {
+        // The quick terminal takes the keyboard while shown, whatever has focus
+        let quick_terminal_shown = self.quick_terminal.is_shown();
+        if (self.is_external_focused() || keyboard.is_grabbed()) && !quick_terminal_shown {
             // Check keyboard grab state
             let has_keyboard_grab = keyboard.is_grabbed();
             let has_pointer_grab = self.seat.get_pointer().map(|p| p.is_grabbed()).unwrap_or(false);
@@ -286,7 +389,15 @@ impl TermStack {
                 time,
                 |state, modifiers, keysym| {
                     let sym = keysym.modified_sym();
-                    if state.handle_global_compositor_binding(modifiers, sym, key_state) {
+                    if state.handle_vt_switch_key(sym, key_state)
+                        || state.handle_filter_prompt_key(modifiers, sym, key_state)
+                        || state.handle_help_key(sym, key_state)
+                        || state.handle_window_switcher_key(modifiers, sym, key_state)
+                        || state.handle_keyboard_resize_key(modifiers, sym, key_state)
+                        || state.handle_global_compositor_binding(modifiers, sym, key_state)
+                        // Escape closes the popup (menu) holding the keyboard grab
+                        || (key_state == KeyState::Pressed && sym == Keysym::Escape && state.dismiss_grabbed_popup())
+                    {
                         FilterResult::Intercept(true)
                     } else {
                         // Forward to the focused Wayland surface (or popup via grab)
@@ -304,6 +415,16 @@ impl TermStack {
             return;
         }
 
+        // Keys that aren't for a terminal go to the focused Wayland client,
+        // unless the quick terminal covers it
+        let unhandled = || {
+            if quick_terminal_shown {
+                FilterResult::Intercept((false, None))
+            } else {
+                FilterResult::Forward
+            }
+        };
+
         // Process through keyboard for modifier tracking
         let result = keyboard.input::<(bool, Option<Vec<u8>>), _>(
             self,
@@ -315,7 +436,12 @@ impl TermStack {
                 let sym = keysym.modified_sym();
 
                 // Handle compositor keybindings
-                if state.handle_compositor_binding_with_terminals(modifiers, sym, key_state)
+                if state.handle_vt_switch_key(sym, key_state)
+                    || state.handle_filter_prompt_key(modifiers, sym, key_state)
+                    || state.handle_help_key(sym, key_state)
+                    || state.handle_window_switcher_key(modifiers, sym, key_state)
+                    || state.handle_keyboard_resize_key(modifiers, sym, key_state)
+                    || state.handle_compositor_binding_with_terminals(modifiers, sym, key_state)
                 {
                     FilterResult::Intercept((true, None))
                 } else if key_state == KeyState::Pressed {
@@ -324,10 +450,10 @@ impl TermStack {
                     if !bytes.is_empty() {
                         FilterResult::Intercept((false, Some(bytes)))
                     } else {
-                        FilterResult::Forward
+                        unhandled()
                     }
                 } else {
-                    FilterResult::Forward
+                    unhandled()
                 }
             },
         );
@@ -342,15 +468,22 @@ impl TermStack {
             // Forward to focused terminal if we got bytes
             if let Some((handled, Some(bytes))) = result {
                 if !handled {
-                    if let Some(terminal) = terminals.get_focused_mut(self.focused_window.as_ref()) {
+                    if let Some(terminal) = terminals.get_focused_mut(self.keyboard_input_window().as_ref()) {
                         // Only write if terminal process is still running
                         if terminal.has_exited() {
                             tracing::debug!("ignoring input to exited terminal");
                         } else if let Err(e) = terminal.write(&bytes) {
                             tracing::error!(?e, "failed to write to terminal");
                         } else {
+                            let now = std::time::Instant::now();
+                            self.typing_echo.key_sent(terminal.id, now);
+                            // Typing goes back to live output, like other terminals
+                            if terminal.terminal.display_offset() > 0 {
+                                terminal.terminal.scroll_to_bottom();
+                                terminal.mark_dirty();
+                            }
                             // Set up key repeat for this key
-                            let repeat_time = std::time::Instant::now()
+                            let repeat_time = now
                                 + std::time::Duration::from_millis(self.repeat_delay_ms);
                             self.key_repeat = Some((bytes, repeat_time));
                         }
@@ -410,7 +543,7 @@ impl TermStack {
                     Ok(text) => {
                         self.clipboard_receiver = None;
                         self.clipboard_read_started_at = None;
-                        if let Some(terminal) = terminals.get_focused_mut(self.focused_window.as_ref()) {
+                        if let Some(terminal) = terminals.get_focused_mut(self.keyboard_input_window().as_ref()) {
                             // Only paste if terminal process is still running
                             if terminal.has_exited() {
                                 tracing::debug!("ignoring paste to exited terminal");
@@ -436,12 +569,13 @@ impl TermStack {
             // Copy selected text to clipboard (or entire content if no selection)
             if self.pending_copy {
                 self.pending_copy = false;
+                let target = self.keyboard_input_window();
                 if let Some(ref mut clipboard) = self.clipboard {
-                    if let Some(terminal) = terminals.get_focused_mut(self.focused_window.as_ref()) {
+                    if let Some(terminal) = terminals.get_focused_mut(target.as_ref()) {
                         // Prefer selection text, fall back to entire grid content
                         let text = if let Some(selected) = terminal.terminal.selection_text() {
                             tracing::debug!(len = selected.len(), "copying selection to clipboard");
-                            selected
+                            self.copy_options.prepare(&selected)
                         } else {
                             let lines = terminal.terminal.grid_content();
                             let text = lines.join("\n");
@@ -458,6 +592,25 @@ impl TermStack {
         }
     }
 
+    /// Copy the text of a mouse selection that just ended (select-to-copy)
+    fn copy_finished_selection(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let text = self.copy_options.prepare(text);
+        if self.copy_options.primary {
+            copy_to_primary_selection(&text);
+        }
+        if self.copy_options.clipboard {
+            if let Some(ref mut clipboard) = self.clipboard {
+                if let Err(e) = clipboard.set_text(text.as_str()) {
+                    tracing::error!(?e, "failed to copy selection to clipboard");
+                }
+            }
+        }
+        tracing::debug!(len = text.len(), "selection copied");
+    }
+
     /// Handle compositor-level keybindings with terminal spawning
     fn handle_compositor_binding_with_terminals(
         &mut self,
@@ -468,6 +621,9 @@ impl TermStack {
         if state != KeyState::Pressed {
             return false;
         }
+        if self.handle_plugin_binding(modifiers, keysym) {
+            return true;
+        }
 
         let Some(action) = parse_compositor_keybinding(modifiers, keysym) else {
             return false;
@@ -477,8 +633,134 @@ impl TermStack {
         true
     }
 
+    /// Send key presses bound by a plugin to that plugin.
+    /// Returns true if the key was bound.
+    fn handle_plugin_binding(&mut self, modifiers: &ModifiersState, keysym: Keysym) -> bool {
+        if self.plugins.is_empty() {
+            return false;
+        }
+        let mods = KeyMods {
+            ctrl: modifiers.ctrl,
+            shift: modifiers.shift,
+            alt: modifiers.alt,
+            logo: modifiers.logo,
+        };
+        self.plugins.handle_key(mods, &xkb::keysym_get_name(keysym))
+    }
+
+    /// Cancel a pending quit or close the keybinding help overlay on Esc.
+    /// Returns true if the key was taken.
+    fn handle_help_key(&mut self, keysym: Keysym, state: KeyState) -> bool {
+        if state != KeyState::Pressed || keysym != Keysym::Escape {
+            return false;
+        }
+        if self.quit_confirm.cancel() {
+            tracing::info!("quit cancelled");
+            self.redraw_requested = true;
+            return true;
+        }
+        if !self.help_visible {
+            return false;
+        }
+        self.help_visible = false;
+        self.redraw_requested = true;
+        true
+    }
+
+    /// Take all keys while the filter prompt is open: typed text goes into
+    /// the prompt, Enter applies it, Esc cancels.
+    /// Returns true if the key was taken.
+    fn handle_filter_prompt_key(&mut self, modifiers: &ModifiersState, keysym: Keysym, state: KeyState) -> bool {
+        if !self.filter_view.is_prompting() {
+            return false;
+        }
+        if state == KeyState::Pressed {
+            match keysym {
+                Keysym::Return | Keysym::KP_Enter => self.filter_view.close_prompt(true),
+                Keysym::Escape => self.filter_view.close_prompt(false),
+                Keysym::BackSpace => self.filter_view.backspace(),
+                _ if modifiers.ctrl || modifiers.logo => {}
+                _ => {
+                    if let Some(c) = keysym.key_char() {
+                        self.filter_view.type_char(c);
+                    }
+                }
+            }
+            self.redraw_requested = true;
+        }
+        true
+    }
+
+    /// Open the window switcher on Alt+Tab / Super+Tab and take all keys
+    /// while it is open; releasing the modifier switches.
+    /// Returns true if the key was taken.
+    fn handle_window_switcher_key(&mut self, modifiers: &ModifiersState, keysym: Keysym, state: KeyState) -> bool {
+        let held = (modifiers.alt || modifiers.logo) && !modifiers.ctrl;
+        if !self.window_switcher.is_open() {
+            // Shift+Tab is ISO_Left_Tab on most layouts
+            let tab = matches!(keysym, Keysym::Tab | Keysym::ISO_Left_Tab);
+            if !(held && tab && state == KeyState::Pressed) {
+                return false;
+            }
+            self.open_window_switcher(if modifiers.shift { -1 } else { 1 });
+            return true;
+        }
+
+        if !held {
+            self.close_window_switcher(true);
+            return true;
+        }
+        if state == KeyState::Pressed {
+            match keysym {
+                Keysym::Tab | Keysym::Down => self.step_window_switcher(1),
+                Keysym::ISO_Left_Tab | Keysym::Up => self.step_window_switcher(-1),
+                Keysym::Return | Keysym::KP_Enter => self.close_window_switcher(true),
+                Keysym::Escape => self.close_window_switcher(false),
+                Keysym::_0 => self.filter_window_switcher(None),
+                _ if (Keysym::_1.raw()..=Keysym::_6.raw()).contains(&keysym.raw()) => {
+                    let tag = ColorTag::ALL[(keysym.raw() - Keysym::_1.raw()) as usize];
+                    self.filter_window_switcher(Some(tag));
+                }
+                _ => {}
+            }
+        }
+        true
+    }
+
+    /// Take key presses while keyboard resize mode is on (all of them, except
+    /// the resize binding itself, which leaves the mode).
+    /// Returns true if the key was taken.
+    fn handle_keyboard_resize_key(&mut self, modifiers: &ModifiersState, keysym: Keysym, state: KeyState) -> bool {
+        if !self.keyboard_resize.is_active() || state != KeyState::Pressed {
+            return false;
+        }
+        let key = match keysym {
+            Keysym::Down | Keysym::j => ResizeKey::Grow,
+            Keysym::Up | Keysym::k => ResizeKey::Shrink,
+            Keysym::Return | Keysym::KP_Enter => ResizeKey::Commit,
+            Keysym::Escape => ResizeKey::Cancel,
+            _ => return parse_compositor_keybinding(modifiers, keysym) != Some(CompositorAction::ResizeMode),
+        };
+        self.keyboard_resize.press(key);
+        true
+    }
+
+    /// Ctrl+Alt+F<n>, which the keymap turns into XF86Switch_VT_<n>: leave
+    /// the switch to the main loop (it needs the session). Nested in a
+    /// host's window there is no VT of ours, so the keys go to the client.
+    fn handle_vt_switch_key(&mut self, keysym: Keysym, state: KeyState) -> bool {
+        let first = Keysym::XF86_Switch_VT_1.raw();
+        if !self.owns_vt || !(first..=Keysym::XF86_Switch_VT_12.raw()).contains(&keysym.raw()) {
+            return false;
+        }
+        if state == KeyState::Pressed {
+            self.pending_vt_switch = Some((keysym.raw() - first + 1) as i32);
+        }
+        true
+    }
+
     /// Handle global compositor bindings that work regardless of focused window type.
-    /// These are: quit, focus switch, spawn terminal.
+    /// These are: quit, focus switch, spawn terminal, quick terminal.
     /// Returns true if the binding was handled.
     fn handle_global_compositor_binding(
         &mut self,
@@ -489,6 +771,9 @@ impl TermStack {
         if state != KeyState::Pressed {
             return false;
         }
+        if self.handle_plugin_binding(modifiers, keysym) {
+            return true;
+        }
 
         let Some(action) = parse_compositor_keybinding(modifiers, keysym) else {
             return false;
@@ -498,7 +783,20 @@ impl TermStack {
             CompositorAction::Quit
             | CompositorAction::SpawnTerminal
             | CompositorAction::FocusNext
-            | CompositorAction::FocusPrev => {
+            | CompositorAction::FocusPrev
+            | CompositorAction::ToggleQuickTerminal
+            | CompositorAction::ToggleDoNotDisturb
+            | CompositorAction::ResizeMode
+            | CompositorAction::FocusBack
+            | CompositorAction::FocusParent
+            | CompositorAction::FocusNextChild
+            | CompositorAction::FocusColumnLeft
+            | CompositorAction::FocusColumnRight
+            | CompositorAction::MoveToColumnLeft
+            | CompositorAction::MoveToColumnRight
+            | CompositorAction::CycleColorTag
+            | CompositorAction::ToggleFilter
+            | CompositorAction::ToggleHelp => {
                 apply_compositor_action(self, action);
             }
             // Other actions not used in global bindings
@@ -508,9 +806,19 @@ impl TermStack {
         true
     }
 
-    fn handle_pointer_motion<I: InputBackend>(&mut self, _event: impl smithay::backend::input::PointerMotionEvent<I>) {
-        // Relative motion handling (for mouse movement)
-        // Not critical for initial implementation
+    fn handle_pointer_motion<I: InputBackend>(
+        &mut self,
+        event: impl smithay::backend::input::PointerMotionEvent<I>,
+        terminals: &mut TerminalManager,
+    ) {
+        // Relative motion (mice on the DRM backend) moves the pointer from
+        // where it is, staying on the output
+        let output_size = self.output_size;
+        let delta = event.delta();
+        let screen_x = (self.pointer_position.x + delta.x).clamp(0.0, (output_size.w - 1).max(0) as f64);
+        let screen_y = (output_size.h as f64 - self.pointer_position.y + delta.y)
+            .clamp(0.0, (output_size.h - 1).max(0) as f64);
+        self.handle_pointer_moved_to(screen_x, ScreenY::new(screen_y), event.time_msec(), terminals);
     }
 
     fn handle_pointer_motion_absolute<I: InputBackend>(
@@ -525,6 +833,19 @@ impl TermStack {
         // The Y-flip formula is: render_y = screen_height - screen_y
         let screen_x = event.x_transformed(output_size.w);
         let screen_y = ScreenY::new(event.y_transformed(output_size.h));
+        self.handle_pointer_moved_to(screen_x, screen_y, event.time_msec(), terminals);
+    }
+
+    /// Move the pointer to a screen position: resize drags, selections,
+    /// the resize cursor and pointer focus follow it
+    fn handle_pointer_moved_to(
+        &mut self,
+        screen_x: f64,
+        screen_y: ScreenY,
+        time: u32,
+        terminals: &mut TerminalManager,
+    ) {
+        let output_size = self.output_size;
 
         // Convert to render coordinates (Y=0 at bottom) for hit detection
         let render_y = screen_y.to_render(output_size.h).value();
@@ -534,16 +855,19 @@ impl TermStack {
 
         // Check if pointer is on a resize handle (for cursor change)
         // Do this before checking for active resize drag
-        let on_resize_handle = self.find_resize_handle_at(screen_y).is_some();
-        self.cursor_on_resize_handle = on_resize_handle || self.resizing.is_some();
+        let on_resize_handle = self.find_resize_handle_at(screen_x, screen_y).is_some();
+        let mouse_drag = self.resizing.as_ref().filter(|drag| !drag.keyboard);
+        self.cursor_on_resize_handle = on_resize_handle || mouse_drag.is_some();
 
         // Handle resize drag if active
         if self.resizing.is_some() {
-            // Validate identity before proceeding (window may have been removed/shifted)
+            // The dragged window may have been removed since the drag started
             self.clear_stale_resize_drag();
         }
-        if let Some(drag) = &self.resizing {
-            let window_index = drag.window_index;
+        if let Some(drag) = self.resizing.as_ref().filter(|drag| !drag.keyboard) {
+            let Some(window_index) = self.cell_index(drag.cell) else {
+                return;
+            };
             let delta = screen_y.value() as i32 - drag.start_screen_y;
             let new_height = (drag.start_height + delta).max(MIN_WINDOW_HEIGHT);
 
@@ -566,7 +890,7 @@ impl TermStack {
             match window_type {
                 Some(id) => {
                     // Terminal - snap to full rows during drag
-                    let char_height = terminals.cell_height;
+                    let (_, char_height, _) = terminals.cell_metrics(id);
                     if let Some(terminal) = terminals.get_mut(id) {
                         let title_bar = if terminal.show_title_bar { TITLE_BAR_HEIGHT } else { 0 };
 
@@ -578,28 +902,20 @@ impl TermStack {
 
                         terminal.resize_to_height(snapped_content, char_height);
 
-                        // Update cached height with snapped value
-                        if let Some(node) = self.layout_nodes.get_mut(window_index) {
-                            node.height = snapped_total;
-                        }
+                        // Lay the cell out at the snapped height from the next frame
+                        self.request_window_height(window_index, snapped_total);
                     }
                 }
                 None => {
-                    // External window - Niri-style approach: no configures during drag
-                    // Only send configure when drag ends to prevent overwhelming window
-                    // But DO update layout positions for visual feedback
-
-                    // Update drag target for final resize
+                    // External window - no configure from motion events; the frame
+                    // loop sends the latest target at most once per frame, and the
+                    // final one goes out when the drag ends. The frame layout
+                    // places the window at the drag target for visual feedback
+                    // while it renders at its committed size
                     if let Some(drag) = &mut self.resizing {
                         drag.target_height = new_height;
                     }
 
-                    // Update cached height for layout positioning (visual feedback)
-                    // Window will render at committed size but be positioned at target size
-                    if let Some(node) = self.layout_nodes.get_mut(window_index) {
-                        node.height = new_height;
-                    }
-
                     tracing::trace!(
                         window_index,
                         new_height,
@@ -607,8 +923,8 @@ impl TermStack {
                     );
                 }
             }
-            // Don't call recalculate_layout() here - main loop handles it
-            // Just update node.height and let render use it directly
+            // Don't call recalculate_layout() here - the next frame layout
+            // picks up the new height
             return;
         }
 
@@ -619,12 +935,12 @@ impl TermStack {
         }
         // Legacy: Update single-terminal selection if we're in a drag operation
         else if let Some((term_id, window_render_y, window_height, start_col, start_row, last_col, last_row, last_update_time)) = self.selecting {
-            // Throttle at input level: Only process motion events every 16ms (~60 FPS)
+            // Throttle at input level: Only process motion events once per frame
             // This prevents backlog by skipping motion events entirely if we're behind
             let now = std::time::Instant::now();
             let elapsed = now.duration_since(last_update_time);
 
-            if elapsed >= std::time::Duration::from_millis(16) {
+            if elapsed >= self.frame_time() {
                 let title_bar_height = terminals.get(term_id)
                     .map(|t| if t.show_title_bar { TITLE_BAR_HEIGHT } else { 0 })
                     .unwrap_or(0);
@@ -696,7 +1012,7 @@ impl TermStack {
             &MotionEvent {
                 location: screen_position.into(),
                 serial,
-                time: event.time_msec(),
+                time,
             },
         );
 
@@ -723,9 +1039,12 @@ impl TermStack {
 
         // Handle left mouse button release
         if button == BTN_LEFT && state == ButtonState::Released {
-            // End resize drag
-            if let Some(drag) = self.resizing.take() {
-                let window_index = drag.window_index;
+            // End resize drag (keyboard resizes end with Enter or Esc)
+            let mouse_drag = self.resizing.as_ref().is_some_and(|drag| !drag.keyboard);
+            if let Some(drag) = mouse_drag.then(|| self.resizing.take()).flatten() {
+                let Some(window_index) = self.cell_index(drag.cell) else {
+                    return;
+                };
                 let final_target = drag.target_height as u32;
 
                 if let Some(node) = self.layout_nodes.get(window_index) {
@@ -753,10 +1072,7 @@ impl TermStack {
             if self.cross_selection.is_some() {
                 if let Some(ref mut tm) = terminals {
                     if let Some(selected_text) = selection::end_cross_selection(self, tm) {
-                        if !selected_text.is_empty() {
-                            copy_to_primary_selection(&selected_text);
-                            tracing::debug!(len = selected_text.len(), "cross-selection copied to PRIMARY");
-                        }
+                        self.copy_finished_selection(&selected_text);
                     }
                 }
             }
@@ -765,17 +1081,15 @@ impl TermStack {
                 if let Some(ref mut tm) = terminals {
                     if let Some(managed) = tm.get_mut(term_id) {
                         if let Some(selected_text) = managed.terminal.selection_text() {
-                            if !selected_text.is_empty() {
-                                copy_to_primary_selection(&selected_text);
-                            }
+                            self.copy_finished_selection(&selected_text);
                         }
                     }
                 }
             }
         }
 
-        // Focus window on click
-        if state == ButtonState::Pressed {
+        // Focus window on click (clicks on panels only go to the panel)
+        if state == ButtonState::Pressed && !self.is_on_panel(self.pointer_position) {
             // Use self.pointer_position which is updated on every motion event
             // pointer.current_location() can be stale if no motion happened since last button press
             let screen_x = self.pointer_position.x;
@@ -783,11 +1097,25 @@ impl TermStack {
 
             // Convert render Y back to screen Y for resize handle detection
             let screen_y = RenderY::new(render_y).to_screen(self.output_size.h);
+
+            // Clicks on the quick terminal don't reach the cells under it
+            if self.quick_terminal.covers(screen_y.value()) {
+                return;
+            }
             let render_y_wrapped = RenderY::new(render_y);
 
+            // A click outside a window and its popups dismisses the popups
+            // (before any early return below, so title bar clicks count too)
+            let on_popup = self
+                .surface_under(self.pointer_position)
+                .is_some_and(|(surface, _)| self.popup_manager.find_popup(&surface).is_some());
+            if !on_popup {
+                self.dismiss_popups_except(self.window_at(screen_x, render_y_wrapped));
+            }
+
             // Check for resize handle before normal cell hit detection
             if button == BTN_LEFT {
-                if let Some(window_index) = self.find_resize_handle_at(screen_y) {
+                if let Some(window_index) = self.find_resize_handle_at(screen_x, screen_y) {
                     // Start resize drag
                     let raw_height = self.get_window_height(window_index).unwrap_or(100);
 
@@ -795,7 +1123,7 @@ impl TermStack {
                     let start_height = if let Some(node) = self.layout_nodes.get(window_index) {
                         if let StackWindow::Terminal(id) = &node.cell {
                             if let Some(ref mut tm) = terminals {
-                                let char_height = tm.cell_height;
+                                let (_, char_height, _) = tm.cell_metrics(*id);
                                 if let Some(term) = tm.get_mut(*id) {
                                     let title_bar = if term.show_title_bar { TITLE_BAR_HEIGHT } else { 0 };
                                     let content = (raw_height as u32).saturating_sub(title_bar);
@@ -822,34 +1150,24 @@ impl TermStack {
                         raw_height
                     };
 
-                    // Extract window identity for stale index detection
-                    let window_identity = match &self.layout_nodes[window_index].cell {
-                        StackWindow::Terminal(id) => FocusedWindow::Terminal(*id),
-                        StackWindow::External(entry) => {
-                            FocusedWindow::External(entry.surface.wl_surface().id())
-                        }
-                    };
-
+                    let cell = self.layout_nodes[window_index].id;
                     self.resizing = Some(ResizeDrag {
-                        window_index,
-                        window_identity,
+                        cell,
                         start_screen_y: screen_y.value() as i32,
                         start_height,
-                        last_configure_time: std::time::Instant::now(),
                         target_height: start_height,
                         last_sent_height: None,
+                        keyboard: false,
                     });
 
-                    // Update node.height immediately if we snapped (for terminals)
+                    // Lay the cell out at the snapped height (for terminals)
                     if start_height != raw_height {
-                        if let Some(node) = self.layout_nodes.get_mut(window_index) {
-                            node.height = start_height;
-                        }
+                        self.request_window_height(window_index, start_height);
                     }
 
                     // Clear any pending external_window_resized for this cell
                     // to prevent stale resize events from overwriting our drag updates
-                    if self.external_window_resized.as_ref().map(|(idx, _)| *idx) == Some(window_index) {
+                    if self.external_window_resized.as_ref().map(|(id, _)| *id) == Some(cell) {
                         self.external_window_resized = None;
                         tracing::debug!(window_index, "cleared pending external_window_resized on drag start");
                     }
@@ -857,18 +1175,15 @@ impl TermStack {
                 }
             }
 
-            if let Some(index) = self.window_at(render_y_wrapped) {
+            if let Some(index) = self.window_at(screen_x, render_y_wrapped) {
                 // Clicked on a cell - focus it
                 self.set_focus_by_index(index);
 
                 // Calculate window's screen-coord top for close button detection
-                let window_screen_top = {
-                    let mut content_y = -self.scroll_offset;
-                    for node in self.layout_nodes.iter().take(index) {
-                        content_y += node.height as f64;
-                    }
-                    content_y as i32
-                };
+                let window_screen_top = self.get_window_screen_bounds(index).map_or(0, |(top, _)| top);
+                // The close button sits at the right edge of the cell's column
+                let column_x = screen_x - self.cell_x(index) as f64;
+                let column_width = self.column_width();
 
                 // Extract cell info before doing mutable operations
                 // For terminals, check if they have a title bar
@@ -892,6 +1207,8 @@ impl TermStack {
                     External {
                         surface: &'a SurfaceKind,
                         has_ssd: bool,
+                        not_responding: bool,
+                        scaled_down: bool,
                     },
                     Terminal {
                         id: TerminalId,
@@ -903,7 +1220,9 @@ impl TermStack {
                     StackWindow::External(entry) => {
                         CellClickInfo::External {
                             surface: &entry.surface,
-                            has_ssd: !entry.uses_csd,
+                            has_ssd: entry.has_title_bar(),
+                            not_responding: entry.liveness.is_not_responding(),
+                            scaled_down: entry.display_scale.is_some(),
                         }
                     }
                     StackWindow::Terminal(id) => {
@@ -915,22 +1234,48 @@ impl TermStack {
                 };
 
                 match window_info {
-                    CellClickInfo::External { surface, has_ssd } => {
+                    CellClickInfo::External { surface, has_ssd, not_responding, scaled_down } => {
                         // Check if click is on close button in title bar
                         if button == BTN_LEFT && crate::mouse_actions::is_click_on_close_button(
-                            screen_x,
+                            column_x,
                             screen_y.value(),
                             window_screen_top,
-                            self.output_size.w,
+                            column_width,
                             TITLE_BAR_HEIGHT as i32,
                             CLOSE_BUTTON_WIDTH as i32,
                             has_ssd,
                         ) {
-                            tracing::debug!(index, "close button clicked, sending close");
-                            surface.send_close();
+                            if not_responding {
+                                // A frozen client would never handle the close request
+                                crate::watchdog::force_close(self, surface);
+                            } else {
+                                tracing::debug!(index, "close button clicked, sending close");
+                                surface.send_close();
+                            }
                             return; // Don't process further
                         }
 
+                        // Right-click on our title bar hands decorations to the app
+                        // (Ctrl+Shift+D on the focused window switches back)
+                        if button == BTN_RIGHT
+                            && has_ssd
+                            && (screen_y.value() as i32) < window_screen_top + TITLE_BAR_HEIGHT as i32
+                        {
+                            self.toggle_decorations(index);
+                            return;
+                        }
+
+                        // Clicking a scaled-down window's content expands it
+                        // (the click was aimed at the thumbnail, so the client doesn't get it)
+                        let title_bar_h = if has_ssd { TITLE_BAR_HEIGHT as i32 } else { 0 };
+                        if button == BTN_LEFT
+                            && scaled_down
+                            && (screen_y.value() as i32) >= window_screen_top + title_bar_h
+                        {
+                            self.expand_window(index);
+                            return;
+                        }
+
                         // Start cross-window selection on left button press (title bar only for external)
                         if button == BTN_LEFT && has_ssd {
                             // Only start selection if clicking on title bar area
@@ -942,6 +1287,7 @@ impl TermStack {
                                         terminals,
                                         screen_x,
                                         render_y_wrapped,
+                                        false,
                                     );
                                 }
                             }
@@ -953,10 +1299,10 @@ impl TermStack {
                     CellClickInfo::Terminal { id, has_ssd } => {
                         // Check if click is on close button in title bar (for terminals with title bars)
                         if button == BTN_LEFT && crate::mouse_actions::is_click_on_close_button(
-                            screen_x,
+                            column_x,
                             screen_y.value(),
                             window_screen_top,
-                            self.output_size.w,
+                            column_width,
                             TITLE_BAR_HEIGHT as i32,
                             CLOSE_BUTTON_WIDTH as i32,
                             has_ssd,
@@ -989,34 +1335,69 @@ impl TermStack {
                             }
 
                             tracing::debug!(index, terminal_id = ?id, "close button clicked on terminal, removing");
-                            // Remove the terminal from the layout
-                            self.layout_nodes.remove(index);
-                            // Invalidate cache since layout_nodes changed
-                            self.invalidate_focused_index_cache();
-                            // Remove from terminal manager
+                            // Remove the terminal, keeping it for undo
                             if let Some(terminals) = terminals {
-                                terminals.remove(id);
+                                self.close_terminal_cell(index, terminals);
+                            } else {
+                                self.remove_cell(index);
+                                self.invalidate_focused_index_cache();
+                                self.update_focus_after_removal(index);
                             }
-                            // Update focus if the focused cell was removed
-                            self.update_focus_after_removal(index);
                             return; // Don't process further
                         }
 
-                        if let Some(keyboard) = self.seat.get_keyboard() {
-                            keyboard.set_focus(self, None, serial);
+                        // Right-click on a title bar cycles the cell's color tag
+                        if button == BTN_RIGHT
+                            && has_ssd
+                            && (screen_y.value() as i32) < window_screen_top + TITLE_BAR_HEIGHT as i32
+                        {
+                            self.cycle_cell_tag(index);
+                            return;
+                        }
+
+                        // Take keyboard focus from external windows when focusing terminal
+                        self.update_keyboard_focus_for_focused_window();
+
+                        // Clicking a folded `line ×N` row expands it, clicking a
+                        // `▾`/`▸` row of a JSON tree collapses or expands it
+                        if button == BTN_LEFT {
+                            if let Some(tm) = &mut terminals {
+                                let title_bar_h = if has_ssd { TITLE_BAR_HEIGHT as f64 } else { 0.0 };
+                                let local_y = screen_y.value() - window_screen_top as f64 - title_bar_h;
+                                let char_height = tm.cell_metrics(id).1 as f64;
+                                if local_y >= 0.0
+                                    && crate::smart_output::toggle_node_at_row(tm, id, (local_y / char_height) as usize)
+                                {
+                                    return;
+                                }
+                                if let Some(term) = tm.get_mut(id) {
+                                    if local_y >= 0.0
+                                        && term.terminal.expand_fold_at_row((local_y / char_height) as usize)
+                                    {
+                                        term.mark_dirty();
+                                        return;
+                                    }
+                                }
+                            }
                         }
-                        // Deactivate all external windows when focusing terminal
-                        self.deactivate_all_toplevels();
 
                         // Start cross-window selection on left button press
+                        // (Alt+drag selects a rectangular block instead, double-click a word)
                         if button == BTN_LEFT {
+                            let alt_held = self.seat.get_keyboard().is_some_and(|kb| kb.modifier_state().alt);
                             if let Some(terminals) = &mut terminals {
-                                selection::start_cross_selection(
-                                    self,
-                                    terminals,
-                                    screen_x,
-                                    render_y_wrapped,
-                                );
+                                let double_click = selection::register_click(self, screen_x, render_y_wrapped);
+                                if !(double_click
+                                    && selection::select_word_at(self, terminals, screen_x, render_y_wrapped))
+                                {
+                                    selection::start_cross_selection(
+                                        self,
+                                        terminals,
+                                        screen_x,
+                                        render_y_wrapped,
+                                        alt_held,
+                                    );
+                                }
                             }
                         }
 
@@ -1027,23 +1408,22 @@ impl TermStack {
                     }
                 }
             } else {
-                // Click not on any cell - check if it's below all cells
-                // If so, focus the last cell
-                if !self.layout_nodes.is_empty() {
+                // Click not on any cell - check if it's below all cells of
+                // the column under the pointer. If so, focus its last cell
+                let column = self.column_at_x(screen_x);
+                let range = self.column_range(column);
+                if !range.is_empty() {
                     // Calculate the bottom edge of the last cell in render coords
                     let screen_height = self.output_size.h as f64;
-                    let mut content_y = -self.scroll_offset;
-
-                    for node in &self.layout_nodes {
-                        content_y += node.height as f64;
-                    }
+                    let content_y =
+                        (self.column_top() + self.column_height(column)) as f64 - self.column_scroll(column);
 
                     // Last cell's bottom in render coords: screen_height - content_y
                     let last_window_bottom = screen_height - content_y;
 
                     // If click is below the last cell's bottom, focus the last cell
                     if render_y < last_window_bottom {
-                        let last_index = self.layout_nodes.len() - 1;
+                        let last_index = range.end - 1;
                         self.set_focus_by_index(last_index);
 
                         // Middle-click in empty area pastes to focused terminal
@@ -1111,11 +1491,30 @@ impl TermStack {
         );
 
         // Get current modifier state directly from keyboard
-        let shift_held = self.seat.get_keyboard()
-            .map(|kb| kb.modifier_state().shift)
-            .unwrap_or(false);
-
-        if shift_held {
+        let (shift_held, ctrl_held) = self.seat.get_keyboard()
+            .map(|kb| kb.modifier_state())
+            .map_or((false, false), |mods| (mods.shift, mods.ctrl));
+
+        if ctrl_held {
+            // Ctrl+Scroll: zoom the terminal under the pointer
+            let steps = match source {
+                AxisSource::Wheel | AxisSource::WheelTilt => (amount_v120.unwrap_or(0.0) / 120.0).round(),
+                // Touchpad/continuous: one step per ~20 pixels
+                _ => (amount.unwrap_or(0.0) / 20.0).round(),
+            };
+            if steps == 0.0 {
+                return;
+            }
+            if let Some(terminals) = terminals {
+                if let Some(window_idx) = self.window_at(self.pointer_position.x, RenderY::new(self.pointer_position.y)) {
+                    if let Some(StackWindow::Terminal(term_id)) = self.layout_nodes.get(window_idx).map(|n| &n.cell) {
+                        // Wheel up (negative) = larger text
+                        terminals.zoom_terminal(*term_id, -steps as f32);
+                    }
+                }
+            }
+            return;
+        } else if shift_held {
             // Shift+Scroll: Terminal scrollback navigation
             // Calculate lines to scroll (using terminal-specific sensitivity)
             let lines = match source {
@@ -1136,7 +1535,7 @@ impl TermStack {
             // Scroll the terminal under the pointer, not the focused one
             if let Some(terminals) = terminals {
                 // Find which cell is under the pointer
-                if let Some(window_idx) = self.window_at(RenderY::new(self.pointer_position.y)) {
+                if let Some(window_idx) = self.window_at(self.pointer_position.x, RenderY::new(self.pointer_position.y)) {
                     if let Some(StackWindow::Terminal(term_id)) = self.layout_nodes.get(window_idx).map(|n| &n.cell) {
                         if let Some(term) = terminals.get_mut(*term_id) {
                             // Positive lines = wheel down = scroll toward newer output
@@ -1171,6 +1570,9 @@ impl TermStack {
                 return;
             }
 
+            // The wheel scrolls the column under the pointer
+            self.activate_column(self.column_at_x(self.pointer_position.x));
+
             // Accumulate delta - applied once per frame to avoid repeated layout recalc
             // Positive vertical = wheel down = scroll content down (increase offset)
             let before = self.pending_scroll_delta;
@@ -1219,26 +1621,21 @@ impl TermStack {
         pointer.frame(self);
     }
 
-    /// Find the surface under a point (only for external windows)
+    /// Find the surface under a point (external windows and panels)
     ///
     /// `point` is in RENDER coordinates (Y=0 at bottom, for OpenGL).
     fn surface_under(
         &self,
         point: Point<f64, Logical>,
     ) -> Option<(smithay::reexports::wayland_server::protocol::wl_surface::WlSurface, Point<f64, Logical>)> {
-        // First check all popups (they're on top of windows)
+        // First check all popups (they're on top of windows and panels)
         // We need to check popups for ALL external windows, not just the one under the point
         for (idx, node) in self.layout_nodes.iter().enumerate() {
             if let crate::state::StackWindow::External(entry) = &node.cell {
                 // Calculate window position
                 let output_height = self.output_size.h as f64;
-                let mut content_y = -self.scroll_offset;
-                for (i, n) in self.layout_nodes.iter().enumerate() {
-                    if i == idx {
-                        break;
-                    }
-                    content_y += n.height as f64;
-                }
+                let content_y =
+                    (self.column_top() + self.column_content_y(idx)) as f64 - self.column_scroll(node.column);
                 let window_render_top = output_height - content_y;
 
                 // Check popups for this window
@@ -1255,8 +1652,8 @@ impl TermStack {
                     // Popup offset is relative to the client area
                     // For CSD apps, client area is the whole window
                     // For SSD apps, client area is below our title bar
-                    let popup_render_x = (popup_offset.x + FOCUS_INDICATOR_WIDTH) as f64;
-                    let title_bar_offset = if entry.uses_csd { 0.0 } else { TITLE_BAR_HEIGHT as f64 };
+                    let popup_render_x = (self.cell_x(idx) + popup_offset.x + FOCUS_INDICATOR_WIDTH) as f64;
+                    let title_bar_offset = if entry.has_title_bar() { TITLE_BAR_HEIGHT as f64 } else { 0.0 };
                     let client_area_top = window_render_top - title_bar_offset;
                     let popup_render_y = client_area_top - popup_offset.y as f64 - popup_geo.size.h as f64;
                     let popup_w = popup_geo.size.w as f64;
@@ -1286,8 +1683,15 @@ impl TermStack {
             }
         }
 
-        // No popup hit, check main window
-        let index = self.window_at(RenderY::new(point.y))?;
+        // No popup hit, check panels over the cells
+        if let Some(found) = self.layer_surface_under(point, &crate::state::LAYERS_ABOVE_CELLS) {
+            return Some(found);
+        }
+
+        // Then the main window, then what is drawn under the cells
+        let Some(index) = self.window_at(point.x, RenderY::new(point.y)) else {
+            return self.layer_surface_under(point, &crate::state::LAYERS_BELOW_CELLS);
+        };
         debug_assert!(
             index < self.layout_nodes.len(),
             "BUG: window_at returned invalid index {} for {} layout_nodes",
@@ -1299,6 +1703,11 @@ impl TermStack {
             return None;
         };
 
+        // A scaled-down window takes no input until it's expanded
+        if entry.display_scale.is_some() {
+            return None;
+        }
+
         tracing::debug!(
             render_point = ?point,
             scroll_offset = self.scroll_offset,
@@ -1310,13 +1719,9 @@ impl TermStack {
         let output_height = self.output_size.h as f64;
 
         // Calculate the cell's content_y position (Y from top in content space)
-        let mut content_y = -self.scroll_offset;
-        for (i, node) in self.layout_nodes.iter().enumerate() {
-            if i == index {
-                break;
-            }
-            content_y += node.height as f64;
-        }
+        let content_y = (self.column_top() + self.column_content_y(index)) as f64
+            - self.column_scroll(self.layout_nodes[index].column);
+        let column_x = self.cell_x(index) as f64;
 
         let window_height = self.get_window_height(index).unwrap_or(0) as f64;
 
@@ -1329,9 +1734,9 @@ impl TermStack {
         // - client_local_y = render_end - point.y = (output_height - content_y) - point.y
         // - For SSD windows, subtract title bar height since surface starts below it
         let render_end = output_height - content_y;
-        let title_bar_offset = if entry.uses_csd { 0.0 } else { TITLE_BAR_HEIGHT as f64 };
+        let title_bar_offset = if entry.has_title_bar() { TITLE_BAR_HEIGHT as f64 } else { 0.0 };
         // Subtract focus indicator width from X (content is offset from left edge)
-        let relative_x = (point.x - FOCUS_INDICATOR_WIDTH as f64).max(0.0);
+        let relative_x = (point.x - column_x - FOCUS_INDICATOR_WIDTH as f64).max(0.0);
         let relative_y = render_end - point.y - title_bar_offset;
         let relative_point: Point<f64, Logical> = Point::from((relative_x, relative_y));
 
@@ -1360,8 +1765,8 @@ impl TermStack {
         //
         // The cell's top in screen coords = content_y
         // For SSD windows, the surface starts BELOW our title bar, so add title_bar_offset
-        // The X position is FOCUS_INDICATOR_WIDTH (content is offset from left edge)
-        let screen_surface_x = FOCUS_INDICATOR_WIDTH as f64;
+        // The X position is FOCUS_INDICATOR_WIDTH (content is offset from the column's left edge)
+        let screen_surface_x = column_x + FOCUS_INDICATOR_WIDTH as f64;
         let screen_surface_y = content_y + title_bar_offset;
 
         result.map(|(surface, _pt)| (surface, Point::from((screen_surface_x, screen_surface_y))))
@@ -1374,6 +1779,32 @@ mod tests {
 
     // Close button tests live in mouse_actions::tests.
 
+    // ========== keybinding tests ==========
+
+    fn mods(ctrl: bool, shift: bool, logo: bool) -> ModifiersState {
+        ModifiersState { ctrl, shift, logo, ..Default::default() }
+    }
+
+    #[test]
+    fn keybindings_need_exactly_their_modifiers() {
+        let ctrl_shift = mods(true, true, false);
+        let super_only = mods(false, false, true);
+        let none = mods(false, false, false);
+        assert_eq!(parse_compositor_keybinding(&ctrl_shift, Keysym::J), Some(CompositorAction::FocusNext));
+        assert_eq!(parse_compositor_keybinding(&ctrl_shift, Keysym::Down), Some(CompositorAction::FocusNext));
+        assert_eq!(parse_compositor_keybinding(&super_only, Keysym::Down), Some(CompositorAction::ScrollDown));
+        assert_eq!(parse_compositor_keybinding(&none, Keysym::Page_Down), Some(CompositorAction::PageDown));
+        assert_eq!(parse_compositor_keybinding(&mods(false, true, true), Keysym::question), Some(CompositorAction::ToggleHelp));
+        assert_eq!(parse_compositor_keybinding(&mods(false, true, true), Keysym::J), None);
+        assert_eq!(parse_compositor_keybinding(&super_only, Keysym::h), Some(CompositorAction::FocusColumnLeft));
+        assert_eq!(parse_compositor_keybinding(&mods(false, true, true), Keysym::L), Some(CompositorAction::MoveToColumnRight));
+        assert_eq!(parse_compositor_keybinding(&none, Keysym::j), None);
+        assert_eq!(parse_compositor_keybinding(&mods(false, true, false), Keysym::Page_Up), Some(CompositorAction::ScrollbackPageUp));
+        assert_eq!(parse_compositor_keybinding(&mods(false, true, false), Keysym::J), None);
+        let ctrl_alt_shift = ModifiersState { alt: true, ..ctrl_shift };
+        assert_eq!(parse_compositor_keybinding(&ctrl_alt_shift, Keysym::Q), None);
+    }
+
     // ========== render_to_grid_coords tests ==========
 
     #[test]
@@ -3,9 +3,27 @@
 //! Handles key repeat for terminal input and processes focus change
 //! requests from the input handler.
 
-use crate::state::TermStack;
+use crate::state::{ColumnSide, StackWindow, TermStack};
 use crate::terminal_manager::TerminalManager;
 
+/// Focus move along parent/child relationships between cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FamilyFocus {
+    /// From a command's cell to the shell that ran it
+    Parent,
+    /// From a shell to its commands' cells, cycling through them
+    NextChild,
+}
+
+/// Focus or cell move between side-by-side columns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnRequest {
+    /// Focus the cell last focused in the neighbouring column
+    Focus(ColumnSide),
+    /// Move the focused cell to the neighbouring column, or a new one
+    MoveCell(ColumnSide),
+}
+
 /// Handle key repeat for terminal input.
 ///
 /// When a key is held down, this sends repeat events at regular intervals.
@@ -25,12 +43,13 @@ pub fn handle_key_repeat(
     // Time to send a repeat event
     let bytes_to_send = bytes.clone();
 
-    if let Some(terminal) = terminal_manager.get_focused_mut(compositor.focused_window.as_ref()) {
+    if let Some(terminal) = terminal_manager.get_focused_mut(compositor.keyboard_input_window().as_ref()) {
         if let Err(e) = terminal.write(&bytes_to_send) {
             tracing::error!(?e, "failed to write repeat to terminal");
             compositor.key_repeat = None;
             return;
         }
+        compositor.typing_echo.key_sent(terminal.id, now);
     } else {
         // No focused terminal, stop repeating
         compositor.key_repeat = None;
@@ -74,3 +93,126 @@ pub fn handle_focus_change_requests(
         compositor.scroll_to_show_window_bottom(focused_idx);
     }
 }
+
+/// Handle parent/child focus requests from input handlers.
+///
+/// A command's cell (or a GUI window) has the shell cell that launched it as
+/// its parent; a GUI window's output cell has the window. `NextChild` on a
+/// shell focuses its first child, and on a child the next of its siblings.
+pub fn handle_family_focus_requests(
+    compositor: &mut TermStack,
+    terminal_manager: &mut TerminalManager,
+) {
+    let Some(request) = compositor.family_focus_requested.take() else {
+        return;
+    };
+    let Some(focused) = compositor.focused_index() else {
+        return;
+    };
+
+    let target = match request {
+        FamilyFocus::Parent => parent_index(compositor, terminal_manager, focused),
+        FamilyFocus::NextChild => {
+            let mut children = child_indices(compositor, terminal_manager, focused);
+            if children.is_empty() {
+                if let Some(parent) = parent_index(compositor, terminal_manager, focused) {
+                    children = child_indices(compositor, terminal_manager, parent);
+                }
+            }
+            next_in_cycle(&children, focused)
+        }
+    };
+    let Some(target) = target else {
+        tracing::debug!(?request, focused, "no related cell to focus");
+        return;
+    };
+    tracing::debug!(?request, from = focused, to = target, "family focus change");
+
+    compositor.set_focus_by_index(target);
+    compositor.update_keyboard_focus_for_focused_window();
+    compositor.scroll_to_show_window_bottom(target);
+}
+
+/// Handle column focus and cell move requests from input handlers.
+pub fn handle_column_requests(compositor: &mut TermStack) {
+    let Some(request) = compositor.column_requested.take() else {
+        return;
+    };
+    let changed = match request {
+        ColumnRequest::Focus(side) => compositor.focus_column(side),
+        ColumnRequest::MoveCell(side) => compositor.move_focused_cell_to_column(side),
+    };
+    if !changed {
+        tracing::debug!(?request, "no column there");
+        return;
+    }
+    compositor.update_keyboard_focus_for_focused_window();
+    compositor.redraw_requested = true;
+}
+
+/// Index of the visible cell that launched the cell at `index`
+fn parent_index(compositor: &TermStack, terminal_manager: &TerminalManager, index: usize) -> Option<usize> {
+    let node = compositor.layout_nodes.get(index)?;
+    match &node.cell {
+        StackWindow::Terminal(id) => match terminal_manager.get(*id)?.parent {
+            Some(parent) => terminal_index(compositor, terminal_manager, parent),
+            // A GUI window's output cell
+            None => compositor.layout_nodes.iter().position(|other| {
+                matches!(&other.cell, StackWindow::External(entry) if entry.output_terminal == Some(*id))
+            }),
+        },
+        StackWindow::External(entry) => terminal_index(compositor, terminal_manager, entry.launcher_terminal?),
+    }
+}
+
+/// Indices of the visible cells whose parent is the cell at `index`, in stack order
+fn child_indices(compositor: &TermStack, terminal_manager: &TerminalManager, index: usize) -> Vec<usize> {
+    (0..compositor.layout_nodes.len())
+        .filter(|&i| i != index && is_visible(compositor, terminal_manager, i))
+        .filter(|&i| parent_index(compositor, terminal_manager, i) == Some(index))
+        .collect()
+}
+
+/// Index of terminal `id`'s cell, if it is shown
+fn terminal_index(
+    compositor: &TermStack,
+    terminal_manager: &TerminalManager,
+    id: crate::terminal_manager::TerminalId,
+) -> Option<usize> {
+    compositor
+        .layout_nodes
+        .iter()
+        .position(|node| node.cell.terminal_id() == Some(id))
+        .filter(|_| terminal_manager.is_terminal_visible(id))
+}
+
+fn is_visible(compositor: &TermStack, terminal_manager: &TerminalManager, index: usize) -> bool {
+    match compositor.layout_nodes[index].cell {
+        StackWindow::Terminal(id) => terminal_manager.is_terminal_visible(id),
+        StackWindow::External(_) => true,
+    }
+}
+
+/// The entry after `current` in `cells`, wrapping around (the first one if
+/// `current` isn't among them)
+fn next_in_cycle(cells: &[usize], current: usize) -> Option<usize> {
+    match cells.iter().position(|&cell| cell == current) {
+        Some(i) => cells.get((i + 1) % cells.len()).copied(),
+        None => cells.first().copied(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn children_cycle() {
+        let children = [2, 5, 7];
+        // From the parent: the first child
+        assert_eq!(next_in_cycle(&children, 1), Some(2));
+        assert_eq!(next_in_cycle(&children, 5), Some(7));
+        assert_eq!(next_in_cycle(&children, 7), Some(2));
+        assert_eq!(next_in_cycle(&[], 1), None);
+    }
+}
@@ -0,0 +1,345 @@
+//! Input event logs for reproducing bugs
+//!
+//! With `TERMSTACK_RECORD_INPUT=<file>`, every input event the compositor
+//! handles is appended to `<file>`: a JSON header line with the output size,
+//! then one `{"time_ms": .., "type": .., ..}` line per event. Lines are
+//! flushed as they are written, so the log survives a crash.
+//!
+//! With `TERMSTACK_REPLAY_INPUT=<file>`, the headless and X11 backends feed
+//! the logged events back through `process_input_event_with_terminals` at
+//! their original pace, so a focus or selection bug a user captured can be
+//! replayed by a developer. Replay needs the `headless-backend` feature, whose
+//! input types carry the events.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use smithay::backend::input::{
+    AbsolutePositionEvent, Axis, AxisSource, ButtonState, InputBackend, InputEvent, KeyState,
+    KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent,
+};
+use thiserror::Error;
+
+/// Log input events to this file
+pub const RECORD_ENV: &str = "TERMSTACK_RECORD_INPUT";
+/// Replay input events from this file
+pub const REPLAY_ENV: &str = "TERMSTACK_REPLAY_INPUT";
+
+const LOG_VERSION: u32 = 1;
+
+/// Errors reading an input log
+#[derive(Debug, Error)]
+pub enum InputLogError {
+    #[error("failed to read {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("{path}:{line}: {source}")]
+    Parse { path: PathBuf, line: usize, source: serde_json::Error },
+
+    #[error("{0}: unsupported input log version")]
+    Version(PathBuf),
+}
+
+/// First line of an input log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputLogHeader {
+    pub version: u32,
+    pub output_width: i32,
+    pub output_height: i32,
+}
+
+/// Scroll source, as far as the scroll handler cares
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrollSource {
+    Wheel,
+    Finger,
+    Continuous,
+}
+
+/// One input event, independent of the backend that produced it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RecordedInput {
+    /// Key code as the backend reported it
+    Key { key: u32, pressed: bool },
+    /// Absolute pointer position as a fraction of the output (0.0-1.0)
+    PointerMotion { x: f64, y: f64 },
+    PointerButton { button: u32, pressed: bool },
+    /// Notches for wheels, pixels for finger and continuous scrolling
+    Scroll { source: ScrollSource, horizontal: f64, vertical: f64 },
+}
+
+impl RecordedInput {
+    /// The loggable part of `event`, if it is a kind the compositor handles
+    pub fn from_event<I: InputBackend>(event: &InputEvent<I>, output_width: i32, output_height: i32) -> Option<Self> {
+        Some(match event {
+            InputEvent::Keyboard { event } => RecordedInput::Key {
+                key: event.key_code().raw(),
+                pressed: event.state() == KeyState::Pressed,
+            },
+            InputEvent::PointerMotionAbsolute { event } => RecordedInput::PointerMotion {
+                x: event.x_transformed(output_width) / output_width.max(1) as f64,
+                y: event.y_transformed(output_height) / output_height.max(1) as f64,
+            },
+            InputEvent::PointerButton { event } => RecordedInput::PointerButton {
+                button: event.button_code(),
+                pressed: event.state() == ButtonState::Pressed,
+            },
+            InputEvent::PointerAxis { event } => {
+                let source = match event.source() {
+                    AxisSource::Wheel | AxisSource::WheelTilt => ScrollSource::Wheel,
+                    AxisSource::Finger => ScrollSource::Finger,
+                    AxisSource::Continuous => ScrollSource::Continuous,
+                };
+                let amount = |axis| match source {
+                    ScrollSource::Wheel => event.amount_v120(axis).unwrap_or(0.0) / 120.0,
+                    ScrollSource::Finger | ScrollSource::Continuous => event.amount(axis).unwrap_or(0.0),
+                };
+                RecordedInput::Scroll {
+                    source,
+                    horizontal: amount(Axis::Horizontal),
+                    vertical: amount(Axis::Vertical),
+                }
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// One line of an input log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputLogEntry {
+    /// Milliseconds since recording started
+    pub time_ms: u64,
+    #[serde(flatten)]
+    pub input: RecordedInput,
+}
+
+/// Appends input events to a log, kept on `TermStack`
+#[derive(Debug)]
+pub struct InputRecorder {
+    file: BufWriter<File>,
+    started: Instant,
+}
+
+impl InputRecorder {
+    /// Create the log at `path` and write its header
+    pub fn create(path: &Path, output_width: i32, output_height: i32) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let header = InputLogHeader { version: LOG_VERSION, output_width, output_height };
+        serde_json::to_writer(&mut file, &header)?;
+        file.write_all(b"\n")?;
+        file.flush()?;
+        Ok(Self { file, started: Instant::now() })
+    }
+
+    /// Recorder for the file named by `TERMSTACK_RECORD_INPUT`, if set
+    pub fn from_env(output_width: i32, output_height: i32) -> Option<Self> {
+        let path = PathBuf::from(std::env::var_os(RECORD_ENV)?);
+        match Self::create(&path, output_width, output_height) {
+            Ok(recorder) => {
+                tracing::info!(path = %path.display(), "recording input events");
+                Some(recorder)
+            }
+            Err(e) => {
+                tracing::warn!(path = %path.display(), error = %e, "failed to create input log");
+                None
+            }
+        }
+    }
+
+    /// Log an event (events the compositor ignores are skipped)
+    pub fn record<I: InputBackend>(&mut self, event: &InputEvent<I>, output_width: i32, output_height: i32) {
+        let Some(input) = RecordedInput::from_event(event, output_width, output_height) else {
+            return;
+        };
+        let entry = InputLogEntry { time_ms: self.started.elapsed().as_millis() as u64, input };
+        let written = serde_json::to_writer(&mut self.file, &entry)
+            .map_err(io::Error::from)
+            .and_then(|()| self.file.write_all(b"\n"))
+            .and_then(|()| self.file.flush());
+        if let Err(e) = written {
+            tracing::warn!(error = %e, "failed to write input log");
+        }
+    }
+}
+
+/// Read an input log
+pub fn read_input_log(path: &Path) -> Result<(InputLogHeader, Vec<InputLogEntry>), InputLogError> {
+    let io_err = |source| InputLogError::Io { path: path.to_path_buf(), source };
+    let parse_err = |line, source| InputLogError::Parse { path: path.to_path_buf(), line, source };
+
+    let mut lines = BufReader::new(File::open(path).map_err(io_err)?).lines();
+    let first = lines.next().transpose().map_err(io_err)?.unwrap_or_default();
+    let header: InputLogHeader = serde_json::from_str(&first).map_err(|e| parse_err(1, e))?;
+    if header.version != LOG_VERSION {
+        return Err(InputLogError::Version(path.to_path_buf()));
+    }
+
+    let mut entries = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let line = line.map_err(io_err)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(serde_json::from_str(&line).map_err(|e| parse_err(i + 2, e))?);
+    }
+    Ok((header, entries))
+}
+
+/// Logged events waiting to be replayed
+#[derive(Debug)]
+pub struct InputReplay {
+    pending: VecDeque<InputLogEntry>,
+    started: Instant,
+}
+
+impl InputReplay {
+    /// Replay `entries`, starting now
+    pub fn new(entries: Vec<InputLogEntry>) -> Self {
+        Self { pending: entries.into(), started: Instant::now() }
+    }
+
+    /// Replay of the log named by `TERMSTACK_REPLAY_INPUT`, if set
+    pub fn from_env(output_width: i32, output_height: i32) -> Option<Self> {
+        let path = PathBuf::from(std::env::var_os(REPLAY_ENV)?);
+        match read_input_log(&path) {
+            Ok((header, entries)) => {
+                if (header.output_width, header.output_height) != (output_width, output_height) {
+                    tracing::warn!(
+                        recorded = ?(header.output_width, header.output_height),
+                        current = ?(output_width, output_height),
+                        "input log was recorded at a different output size"
+                    );
+                }
+                tracing::info!(path = %path.display(), events = entries.len(), "replaying input events");
+                Some(Self::new(entries))
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to read input log");
+                None
+            }
+        }
+    }
+
+    /// Events whose time has come, oldest first
+    pub fn due(&mut self, now: Instant) -> Vec<InputLogEntry> {
+        let elapsed = now.saturating_duration_since(self.started);
+        let count = self
+            .pending
+            .iter()
+            .take_while(|entry| Duration::from_millis(entry.time_ms) <= elapsed)
+            .count();
+        self.pending.drain(..count).collect()
+    }
+
+    /// When the next event is due
+    pub fn next_due(&self) -> Option<Instant> {
+        self.pending.front().map(|entry| self.started + Duration::from_millis(entry.time_ms))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "headless-backend"))]
+mod replay {
+    use smithay::backend::input::{AxisSource, ButtonState, InputEvent, KeyState};
+    use smithay::utils::{Physical, Size};
+
+    use super::{InputLogEntry, RecordedInput, ScrollSource};
+    use crate::backend::headless::{
+        HeadlessInputBackend, HeadlessKeyboardEvent, HeadlessPointerAxisEvent, HeadlessPointerButtonEvent,
+        HeadlessPointerMotionEvent,
+    };
+
+    impl InputLogEntry {
+        /// The logged event as the headless backend would deliver it
+        pub fn to_input_event(&self, output_size: Size<i32, Physical>) -> InputEvent<HeadlessInputBackend> {
+            let time = self.time_ms as u32;
+            match self.input {
+                RecordedInput::Key { key, pressed } => InputEvent::Keyboard {
+                    event: HeadlessKeyboardEvent {
+                        time,
+                        key,
+                        state: if pressed { KeyState::Pressed } else { KeyState::Released },
+                    },
+                },
+                RecordedInput::PointerMotion { x, y } => InputEvent::PointerMotionAbsolute {
+                    event: HeadlessPointerMotionEvent { time, x, y, output_size },
+                },
+                RecordedInput::PointerButton { button, pressed } => InputEvent::PointerButton {
+                    event: HeadlessPointerButtonEvent {
+                        time,
+                        button,
+                        state: if pressed { ButtonState::Pressed } else { ButtonState::Released },
+                    },
+                },
+                RecordedInput::Scroll { source, horizontal, vertical } => InputEvent::PointerAxis {
+                    event: HeadlessPointerAxisEvent {
+                        time,
+                        horizontal,
+                        vertical,
+                        source: match source {
+                            ScrollSource::Wheel => AxisSource::Wheel,
+                            ScrollSource::Finger => AxisSource::Finger,
+                            ScrollSource::Continuous => AxisSource::Continuous,
+                        },
+                    },
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logs_round_trip() {
+        let path = std::env::temp_dir().join(format!("termstack-input-log-test-{}.jsonl", std::process::id()));
+        let entries = vec![
+            InputLogEntry { time_ms: 0, input: RecordedInput::PointerMotion { x: 0.5, y: 0.25 } },
+            InputLogEntry { time_ms: 12, input: RecordedInput::PointerButton { button: 0x110, pressed: true } },
+            InputLogEntry {
+                time_ms: 40,
+                input: RecordedInput::Scroll { source: ScrollSource::Wheel, horizontal: 0.0, vertical: -1.0 },
+            },
+            InputLogEntry { time_ms: 41, input: RecordedInput::Key { key: 38, pressed: true } },
+        ];
+        let mut file = InputRecorder::create(&path, 1280, 800).unwrap().file;
+        for entry in &entries {
+            serde_json::to_writer(&mut file, entry).unwrap();
+            file.write_all(b"\n").unwrap();
+        }
+        drop(file);
+
+        let (header, read) = read_input_log(&path).unwrap();
+        assert_eq!((header.output_width, header.output_height), (1280, 800));
+        assert_eq!(read, entries);
+
+        std::fs::write(&path, "{\"version\":9,\"output_width\":1,\"output_height\":1}\n").unwrap();
+        assert!(matches!(read_input_log(&path), Err(InputLogError::Version(_))));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn replay_releases_events_on_schedule() {
+        let entry = |time_ms| InputLogEntry { time_ms, input: RecordedInput::Key { key: 1, pressed: true } };
+        let mut replay = InputReplay::new(vec![entry(0), entry(10), entry(500)]);
+        let start = replay.started;
+
+        assert_eq!(replay.due(start + Duration::from_millis(20)).len(), 2);
+        assert_eq!(replay.next_due(), Some(start + Duration::from_millis(500)));
+        assert!(replay.due(start + Duration::from_millis(499)).is_empty());
+        assert_eq!(replay.due(start + Duration::from_secs(1)).len(), 1);
+        assert!(replay.is_finished());
+    }
+}
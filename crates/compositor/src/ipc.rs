@@ -2,20 +2,34 @@
 //!
 //! Handles requests from the `termstack` CLI tool.
 //! The compositor listens on a Unix socket and accepts JSON messages
-//! to spawn terminals or resize the focused terminal.
+//! to spawn terminals, resize the focused terminal, export, name or tag a
+//! cell, filter the cells shown, save or load a layout file, report what is
+//! under a point or change do-not-disturb.
 
 use std::collections::HashMap;
 use std::io::{self, BufRead, BufReader};
 use std::os::unix::net::UnixStream;
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 use serde::{Deserialize, Serialize};
 use std::io::Write;
 use thiserror::Error;
 
+use crate::archive::ArchiveAction;
+use crate::bulk_actions::BulkAction;
+use crate::cell_filter::CellFilter;
+use crate::color_tags::ColorTag;
+use crate::do_not_disturb::DndMode;
+use crate::layout_file::LayoutFile;
+
 /// Maximum IPC message size (1 MB)
 const MAX_IPC_MESSAGE_SIZE: usize = 1024 * 1024;
 
+/// How long a client may take to send its whole request line. Requests are
+/// read on the event loop, so a client trickling bytes must not hold it longer.
+const IPC_READ_DEADLINE: Duration = Duration::from_secs(2);
+
 /// Maximum number of environment variables in a spawn request
 const MAX_ENV_VARS: usize = 1000;
 
@@ -44,10 +58,14 @@ pub enum IpcError {
     #[error("empty message received")]
     EmptyMessage,
 
-    /// Message too large
+    /// Message too large (`size` counts the bytes read before giving up)
     #[error("message too large: {size} bytes (max {max})")]
     MessageTooLarge { size: usize, max: usize },
 
+    /// Message is not UTF-8
+    #[error("message is not valid UTF-8")]
+    InvalidUtf8,
+
     /// Validation error
     #[error("validation error: {0}")]
     ValidationError(String),
@@ -63,6 +81,28 @@ pub enum ResizeMode {
     Content,
 }
 
+/// Output format of `termstack export`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Standalone HTML page with colors preserved
+    Html,
+    /// Text with ANSI color escapes
+    Ansi,
+    /// Plain text
+    Txt,
+}
+
+impl From<ExportFormat> for terminal::export::ExportFormat {
+    fn from(format: ExportFormat) -> Self {
+        match format {
+            ExportFormat::Html => Self::Html,
+            ExportFormat::Ansi => Self::Ansi,
+            ExportFormat::Txt => Self::Text,
+        }
+    }
+}
+
 /// Message from termstack to compositor
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
@@ -82,6 +122,12 @@ pub enum IpcMessage {
         /// GUI mode: None = terminal spawn, Some(true) = foreground GUI, Some(false) = background GUI
         #[serde(skip_serializing_if = "Option::is_none")]
         foreground: Option<bool>,
+        /// Shell for this cell instead of the compositor's `$SHELL` (absolute path)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        shell: Option<String>,
+        /// Variables set on top of `env` after the compositor's own adjustments
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        env_overrides: HashMap<String, String>,
     },
     /// Resize the focused terminal
     #[serde(rename = "resize")]
@@ -101,9 +147,112 @@ pub enum IpcMessage {
         /// Whether the command succeeded
         success: bool,
     },
+    /// Answer to a calculation typed at the prompt (see `calc`), shown
+    /// in an entry that goes away by itself
+    #[serde(rename = "calc")]
+    Calc {
+        /// The shell prompt at command entry time
+        prompt: String,
+        /// The expression as typed
+        command: String,
+        /// The formatted answer
+        result: String,
+    },
     /// Query current window state (for testing/debugging)
     #[serde(rename = "query_windows")]
     QueryWindows,
+    /// Report what the focused cell is running (see `now_running`)
+    #[serde(rename = "status")]
+    Status {
+        /// Keep the connection open and report every change
+        #[serde(default)]
+        watch: bool,
+    },
+    /// Export a terminal cell's content (answered with `Result<String, String>`)
+    #[serde(rename = "export")]
+    Export {
+        /// Index in the stack, as in `query_windows`
+        index: usize,
+        format: ExportFormat,
+    },
+    /// Name a cell, empty `title` to clear the name (answered with
+    /// `Result<(), String>`, see `cell_title`)
+    #[serde(rename = "set_title")]
+    SetTitle {
+        /// Index in the stack, as in `query_windows`
+        index: usize,
+        title: String,
+    },
+    /// Tag a cell with a color, no `tag` to remove it (answered with
+    /// `Result<(), String>`, see `color_tags`)
+    #[serde(rename = "set_tag")]
+    SetTag {
+        /// Index in the stack, as in `query_windows`
+        index: usize,
+        #[serde(default)]
+        tag: Option<ColorTag>,
+    },
+    /// Report the accessibility tree (see `accessibility`)
+    #[serde(rename = "accessibility")]
+    Accessibility {
+        /// Keep the connection open and report every change
+        #[serde(default)]
+        watch: bool,
+    },
+    /// Turn do-not-disturb on or off (answered with a `DndStatus`)
+    #[serde(rename = "dnd")]
+    Dnd {
+        mode: DndMode,
+        /// Hide all but the focused cell; `None` uses the `dnd_focus_mode` config
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        focus: Option<bool>,
+    },
+    /// Focus the previously focused cell (answered with whether there was one)
+    #[serde(rename = "focus_back")]
+    FocusBack,
+    /// Report the layout (answered with a `LayoutDump`)
+    #[serde(rename = "layout")]
+    Layout,
+    /// Describe the stack for a layout file (answered with a `LayoutFile`)
+    #[serde(rename = "layout_export")]
+    LayoutExport,
+    /// Add the cells of a layout file to the stack (answered with
+    /// `Result<usize, String>`, the number of cells added, see `layout_file`)
+    #[serde(rename = "layout_import")]
+    LayoutImport { layout: LayoutFile },
+    /// Show only the cells matching `filter`, all cells without one
+    /// (answered with `Result<usize, String>`, the number of matching cells,
+    /// see `cell_filter`)
+    #[serde(rename = "filter")]
+    Filter {
+        #[serde(default)]
+        filter: Option<CellFilter>,
+    },
+    /// What a click at each screen point (Y=0 at top) would reach, and what
+    /// the last frame drew there (answered with a `Vec<HitTest>`, see `hit_test`)
+    #[serde(rename = "hit_test")]
+    HitTest { points: Vec<(f64, f64)> },
+    /// Act on the whole stack (answered with a `BulkResult`)
+    #[serde(rename = "bulk")]
+    Bulk { action: BulkAction },
+    /// Browse or restore closed command cells (answered with `Result<_, String>`,
+    /// see `ArchiveAction`)
+    #[serde(rename = "archive")]
+    Archive {
+        action: ArchiveAction,
+        #[serde(default)]
+        id: Option<u64>,
+    },
+    /// Report X11 support (answered with an `XWaylandStatus`)
+    #[serde(rename = "xwayland")]
+    XWayland,
+    /// Variables X11 apps need, for shells started before XWayland was ready
+    /// (answered with an `EnvUpdate`)
+    #[serde(rename = "env")]
+    Env,
+    /// Shut termstack down (answered with `true`)
+    #[serde(rename = "quit")]
+    Quit,
 }
 
 /// Information about a window in the compositor (for IPC responses)
@@ -130,8 +279,44 @@ pub enum IpcRequest {
     Resize(ResizeMode),
     /// Shell builtin executed (creates persistent entry in stack)
     Builtin(BuiltinRequest),
+    /// Calculation answered (creates a short-lived entry in stack)
+    Calc(BuiltinRequest),
     /// Query current window state (for testing/debugging)
     QueryWindows,
+    /// Report what the focused cell is running, optionally streaming changes
+    Status { watch: bool },
+    /// Export the content of the cell at `index`
+    Export { index: usize, format: ExportFormat },
+    /// Name the cell at `index` (empty `title` clears the name)
+    SetTitle { index: usize, title: String },
+    /// Tag the cell at `index` with a color (`None` removes the tag)
+    SetTag { index: usize, tag: Option<ColorTag> },
+    /// Report the accessibility tree, optionally streaming changes
+    Accessibility { watch: bool },
+    /// Change do-not-disturb and report the new state
+    Dnd { mode: DndMode, focus: Option<bool> },
+    /// Focus the previously focused cell and report whether there was one
+    FocusBack,
+    /// Report a snapshot of the layout
+    Layout,
+    /// Report the stack as a layout file
+    LayoutExport,
+    /// Add the cells of a layout file and report how many were added
+    LayoutImport(LayoutFile),
+    /// Filter the cells shown (`None` shows all) and report how many match
+    Filter(Option<CellFilter>),
+    /// Report what is under screen points
+    HitTest(Vec<(f64, f64)>),
+    /// Close, collapse or kill across the stack, or undo the last of these
+    Bulk(BulkAction),
+    /// List, show or restore archived cells
+    Archive { action: ArchiveAction, id: Option<u64> },
+    /// Report the state of XWayland and xwayland-satellite
+    XWayland,
+    /// Report the variables X11 apps need
+    Env,
+    /// Shut down
+    Quit,
 }
 
 /// Builtin command request ready for processing by the compositor
@@ -160,6 +345,10 @@ pub struct SpawnRequest {
     pub env: HashMap<String, String>,
     /// GUI mode: None = terminal spawn, Some(true) = foreground GUI, Some(false) = background GUI
     pub foreground: Option<bool>,
+    /// Shell override (absolute path), `None` for the compositor's `$SHELL`
+    pub shell: Option<String>,
+    /// Variables that win over the compositor's environment adjustments
+    pub env_overrides: HashMap<String, String>,
 }
 
 /// Read a request from a Unix stream
@@ -169,49 +358,24 @@ pub struct SpawnRequest {
 ///
 /// # Errors
 ///
-/// Returns `IpcError::Timeout` if the read times out.
+/// Returns `IpcError::Timeout` if the whole line doesn't arrive within `IPC_READ_DEADLINE`.
 /// Returns `IpcError::Io` for other IO errors.
+/// Returns `IpcError::MessageTooLarge` as soon as the line exceeds `MAX_IPC_MESSAGE_SIZE`.
+/// Returns `IpcError::InvalidUtf8` if the line isn't UTF-8.
 /// Returns `IpcError::ParseError` if JSON parsing fails.
 /// Returns `IpcError::EmptyMessage` if an empty line is received.
 pub fn read_ipc_request(stream: UnixStream) -> Result<(IpcRequest, UnixStream), IpcError> {
-    // Set a timeout to avoid blocking the compositor indefinitely on hung clients.
-    // Must be generous enough for large messages (env vars JSON can exceed the
-    // macOS ~8KB Unix socket buffer, requiring multiple read/write rounds).
-    // On macOS, set_read_timeout returns EINVAL on socket pairs when the peer
-    // has already disconnected — ignore that since reads will return EOF anyway.
-    if let Err(e) = stream.set_read_timeout(Some(std::time::Duration::from_secs(2))) {
-        match e.kind() {
-            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => return Err(IpcError::Timeout),
-            io::ErrorKind::InvalidInput => {} // macOS: peer already gone, reads won't block
-            _ => return Err(IpcError::Io(e)),
-        }
-    }
+    read_ipc_request_within(stream, IPC_READ_DEADLINE)
+}
 
+fn read_ipc_request_within(stream: UnixStream, deadline: Duration) -> Result<(IpcRequest, UnixStream), IpcError> {
     let mut reader = BufReader::new(stream);
-
-    // Read first line (JSON message)
-    let mut line = String::new();
-    match reader.read_line(&mut line) {
-        Ok(0) => return Err(IpcError::EmptyMessage),
-        Ok(_) => {}
-        Err(e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {
-            return Err(IpcError::Timeout);
-        }
-        Err(e) => return Err(IpcError::Io(e)),
-    }
+    let line = read_request_line(&mut reader, Instant::now() + deadline)?;
 
     if line.trim().is_empty() {
         return Err(IpcError::EmptyMessage);
     }
 
-    // Check message size before parsing
-    if line.len() > MAX_IPC_MESSAGE_SIZE {
-        return Err(IpcError::MessageTooLarge {
-            size: line.len(),
-            max: MAX_IPC_MESSAGE_SIZE,
-        });
-    }
-
     tracing::debug!(message = %line.trim(), "received IPC message");
 
     // Parse JSON
@@ -221,19 +385,19 @@ pub fn read_ipc_request(stream: UnixStream) -> Result<(IpcRequest, UnixStream),
     let stream = reader.into_inner();
 
     match message {
-        IpcMessage::Spawn { prompt, command, cwd, env, foreground } => {
+        IpcMessage::Spawn { prompt, command, cwd, env, foreground, shell, env_overrides } => {
             // Validate spawn request fields
             if command.len() > MAX_COMMAND_SIZE {
                 return Err(IpcError::ValidationError(format!(
                     "command too large: {} bytes (max {})", command.len(), MAX_COMMAND_SIZE
                 )));
             }
-            if env.len() > MAX_ENV_VARS {
+            if env.len() + env_overrides.len() > MAX_ENV_VARS {
                 return Err(IpcError::ValidationError(format!(
                     "too many environment variables: {} (max {})", env.len(), MAX_ENV_VARS
                 )));
             }
-            for (key, value) in &env {
+            for (key, value) in env.iter().chain(&env_overrides) {
                 let total = key.len() + value.len();
                 if total > MAX_ENV_VAR_SIZE {
                     return Err(IpcError::ValidationError(format!(
@@ -243,6 +407,14 @@ pub fn read_ipc_request(stream: UnixStream) -> Result<(IpcRequest, UnixStream),
                 }
             }
 
+            if let Some(shell) = &shell {
+                if !shell.starts_with('/') || shell.len() > MAX_COMMAND_SIZE {
+                    return Err(IpcError::ValidationError(format!(
+                        "shell must be an absolute path: {}", &shell[..shell.len().min(64)]
+                    )));
+                }
+            }
+
             let spawn_type = match foreground {
                 None => "terminal",
                 Some(true) => "gui (foreground)",
@@ -255,6 +427,8 @@ pub fn read_ipc_request(stream: UnixStream) -> Result<(IpcRequest, UnixStream),
                 cwd: PathBuf::from(cwd),
                 env,
                 foreground,
+                shell,
+                env_overrides,
             }), stream))
         }
         IpcMessage::Resize { mode } => {
@@ -270,11 +444,159 @@ pub fn read_ipc_request(stream: UnixStream) -> Result<(IpcRequest, UnixStream),
                 success,
             }), stream))
         }
+        IpcMessage::Calc { prompt, command, result } => {
+            tracing::info!(command = %command, "calc request received");
+            Ok((IpcRequest::Calc(BuiltinRequest {
+                prompt,
+                command,
+                result,
+                success: true,
+            }), stream))
+        }
         IpcMessage::QueryWindows => {
             tracing::info!("query_windows request received");
             Ok((IpcRequest::QueryWindows, stream))
         }
+        IpcMessage::Status { watch } => {
+            tracing::info!(watch, "status request received");
+            Ok((IpcRequest::Status { watch }, stream))
+        }
+        IpcMessage::Export { index, format } => {
+            tracing::info!(index, ?format, "export request received");
+            Ok((IpcRequest::Export { index, format }, stream))
+        }
+        IpcMessage::SetTitle { index, title } => {
+            if title.len() > MAX_COMMAND_SIZE {
+                return Err(IpcError::ValidationError(format!(
+                    "title too large: {} bytes (max {})", title.len(), MAX_COMMAND_SIZE
+                )));
+            }
+            tracing::info!(index, %title, "set title request received");
+            Ok((IpcRequest::SetTitle { index, title }, stream))
+        }
+        IpcMessage::SetTag { index, tag } => {
+            tracing::info!(index, ?tag, "set tag request received");
+            Ok((IpcRequest::SetTag { index, tag }, stream))
+        }
+        IpcMessage::Accessibility { watch } => {
+            tracing::info!(watch, "accessibility request received");
+            Ok((IpcRequest::Accessibility { watch }, stream))
+        }
+        IpcMessage::Dnd { mode, focus } => {
+            tracing::info!(?mode, ?focus, "dnd request received");
+            Ok((IpcRequest::Dnd { mode, focus }, stream))
+        }
+        IpcMessage::FocusBack => {
+            tracing::info!("focus_back request received");
+            Ok((IpcRequest::FocusBack, stream))
+        }
+        IpcMessage::Layout => {
+            tracing::info!("layout request received");
+            Ok((IpcRequest::Layout, stream))
+        }
+        IpcMessage::LayoutExport => {
+            tracing::info!("layout export request received");
+            Ok((IpcRequest::LayoutExport, stream))
+        }
+        IpcMessage::LayoutImport { layout } => {
+            let too_long = layout.cells.iter().find(|cell| cell.command.len() > MAX_COMMAND_SIZE);
+            if let Some(cell) = too_long {
+                return Err(IpcError::ValidationError(format!(
+                    "layout command too large: {} bytes (max {})", cell.command.len(), MAX_COMMAND_SIZE
+                )));
+            }
+            tracing::info!(cells = layout.cells.len(), "layout import request received");
+            Ok((IpcRequest::LayoutImport(layout), stream))
+        }
+        IpcMessage::Filter { filter } => {
+            if let Some(CellFilter::Command(text)) = &filter {
+                if text.len() > MAX_COMMAND_SIZE {
+                    return Err(IpcError::ValidationError(format!(
+                        "filter too large: {} bytes (max {})", text.len(), MAX_COMMAND_SIZE
+                    )));
+                }
+            }
+            tracing::info!(?filter, "filter request received");
+            Ok((IpcRequest::Filter(filter), stream))
+        }
+        IpcMessage::HitTest { points } => {
+            tracing::debug!(points = points.len(), "hit test request received");
+            Ok((IpcRequest::HitTest(points), stream))
+        }
+        IpcMessage::Bulk { action } => {
+            tracing::info!(?action, "bulk action request received");
+            Ok((IpcRequest::Bulk(action), stream))
+        }
+        IpcMessage::Archive { action, id } => {
+            tracing::info!(?action, ?id, "archive request received");
+            Ok((IpcRequest::Archive { action, id }, stream))
+        }
+        IpcMessage::XWayland => {
+            tracing::info!("xwayland request received");
+            Ok((IpcRequest::XWayland, stream))
+        }
+        IpcMessage::Env => {
+            tracing::info!("env request received");
+            Ok((IpcRequest::Env, stream))
+        }
+        IpcMessage::Quit => {
+            tracing::info!("quit request received");
+            Ok((IpcRequest::Quit, stream))
+        }
+    }
+}
+
+/// Read the first line (the JSON message), never buffering more than
+/// `MAX_IPC_MESSAGE_SIZE` bytes of it and giving up at `deadline`
+fn read_request_line(reader: &mut BufReader<UnixStream>, deadline: Instant) -> Result<String, IpcError> {
+    let mut line = Vec::new();
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(IpcError::Timeout);
+        }
+        // Each read may block only until the deadline. On macOS,
+        // set_read_timeout returns EINVAL on socket pairs when the peer has
+        // already disconnected — ignore that since reads will return EOF anyway.
+        if let Err(e) = reader.get_ref().set_read_timeout(Some(remaining)) {
+            match e.kind() {
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => return Err(IpcError::Timeout),
+                io::ErrorKind::InvalidInput => {} // macOS: peer already gone, reads won't block
+                _ => return Err(IpcError::Io(e)),
+            }
+        }
+
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {
+                return Err(IpcError::Timeout);
+            }
+            Err(e) => return Err(IpcError::Io(e)),
+        };
+        // EOF: a line without a newline is still a message
+        if available.is_empty() {
+            break;
+        }
+
+        let newline = available.iter().position(|&b| b == b'\n');
+        let chunk = &available[..newline.map_or(available.len(), |i| i + 1)];
+        let size = line.len() + chunk.len();
+        if size > MAX_IPC_MESSAGE_SIZE {
+            return Err(IpcError::MessageTooLarge { size, max: MAX_IPC_MESSAGE_SIZE });
+        }
+        line.extend_from_slice(chunk);
+        let consumed = chunk.len();
+        reader.consume(consumed);
+        if newline.is_some() {
+            break;
+        }
+    }
+
+    if line.is_empty() {
+        return Err(IpcError::EmptyMessage);
     }
+    String::from_utf8(line).map_err(|_| IpcError::InvalidUtf8)
 }
 
 /// Send acknowledgement on a stream (for synchronous operations like resize)
@@ -393,6 +715,18 @@ mod tests {
         assert!(matches!(req, IpcRequest::Builtin(_)));
     }
 
+    #[test]
+    fn parse_valid_calc_request() {
+        let msg = r#"{"type":"calc","prompt":"$ ","command":"2 + 2","result":"4"}"#;
+        match send_and_read(msg).unwrap() {
+            IpcRequest::Calc(calc) => {
+                assert_eq!(calc.result, "4");
+                assert!(calc.success);
+            }
+            _ => panic!("expected Calc request"),
+        }
+    }
+
     #[test]
     fn parse_valid_query_windows_request() {
         let msg = r#"{"type":"query_windows"}"#;
@@ -400,6 +734,115 @@ mod tests {
         assert!(matches!(req, IpcRequest::QueryWindows));
     }
 
+    #[test]
+    fn parse_valid_export_request() {
+        let msg = r#"{"type":"export","index":2,"format":"html"}"#;
+        let req = send_and_read(msg).unwrap();
+        assert!(matches!(req, IpcRequest::Export { index: 2, format: ExportFormat::Html }));
+        assert!(send_and_read(r#"{"type":"export","index":2,"format":"pdf"}"#).is_err());
+    }
+
+    #[test]
+    fn parse_valid_set_title_request() {
+        let req = send_and_read(r#"{"type":"set_title","index":1,"title":"prod logs"}"#).unwrap();
+        assert!(matches!(req, IpcRequest::SetTitle { index: 1, ref title } if title == "prod logs"));
+        let too_long = format!(r#"{{"type":"set_title","index":1,"title":"{}"}}"#, "x".repeat(MAX_COMMAND_SIZE + 1));
+        assert!(matches!(send_and_read(&too_long), Err(IpcError::ValidationError(_))));
+    }
+
+    #[test]
+    fn parse_valid_set_tag_request() {
+        let req = send_and_read(r#"{"type":"set_tag","index":3,"tag":"green"}"#).unwrap();
+        assert!(matches!(req, IpcRequest::SetTag { index: 3, tag: Some(ColorTag::Green) }));
+        let req = send_and_read(r#"{"type":"set_tag","index":3}"#).unwrap();
+        assert!(matches!(req, IpcRequest::SetTag { index: 3, tag: None }));
+        assert!(send_and_read(r#"{"type":"set_tag","index":3,"tag":"teal"}"#).is_err());
+    }
+
+    #[test]
+    fn parse_valid_accessibility_request() {
+        let req = send_and_read(r#"{"type":"accessibility","watch":true}"#).unwrap();
+        assert!(matches!(req, IpcRequest::Accessibility { watch: true }));
+    }
+
+    #[test]
+    fn parse_valid_dnd_request() {
+        let req = send_and_read(r#"{"type":"dnd","mode":"toggle"}"#).unwrap();
+        assert!(matches!(req, IpcRequest::Dnd { mode: DndMode::Toggle, focus: None }));
+        let req = send_and_read(r#"{"type":"dnd","mode":"on","focus":true}"#).unwrap();
+        assert!(matches!(req, IpcRequest::Dnd { mode: DndMode::On, focus: Some(true) }));
+    }
+
+    #[test]
+    fn parse_valid_bulk_request() {
+        let req = send_and_read(r#"{"type":"bulk","action":"close_exited"}"#).unwrap();
+        assert!(matches!(req, IpcRequest::Bulk(BulkAction::CloseExited)));
+        assert!(send_and_read(r#"{"type":"bulk","action":"close_everything"}"#).is_err());
+    }
+
+    #[test]
+    fn parse_valid_archive_request() {
+        let req = send_and_read(r#"{"type":"archive","action":"list"}"#).unwrap();
+        assert!(matches!(req, IpcRequest::Archive { action: ArchiveAction::List, id: None }));
+        let req = send_and_read(r#"{"type":"archive","action":"restore","id":3}"#).unwrap();
+        assert!(matches!(req, IpcRequest::Archive { action: ArchiveAction::Restore, id: Some(3) }));
+    }
+
+    #[test]
+    fn parse_valid_quit_request() {
+        let req = send_and_read(r#"{"type":"quit"}"#).unwrap();
+        assert!(matches!(req, IpcRequest::Quit));
+    }
+
+    #[test]
+    fn parse_valid_focus_back_request() {
+        let req = send_and_read(r#"{"type":"focus_back"}"#).unwrap();
+        assert!(matches!(req, IpcRequest::FocusBack));
+    }
+
+    #[test]
+    fn parse_valid_layout_request() {
+        let req = send_and_read(r#"{"type":"layout"}"#).unwrap();
+        assert!(matches!(req, IpcRequest::Layout));
+    }
+
+    #[test]
+    fn parse_valid_layout_file_requests() {
+        let req = send_and_read(r#"{"type":"layout_export"}"#).unwrap();
+        assert!(matches!(req, IpcRequest::LayoutExport));
+        let json = r#"{"type":"layout_import","layout":{"version":1,"cell":[{"kind":"command","command":"make","column":1}]}}"#;
+        let req = send_and_read(json).unwrap();
+        assert!(matches!(req, IpcRequest::LayoutImport(ref layout) if layout.cells[0].command == "make" && layout.cells[0].column == 1));
+    }
+
+    #[test]
+    fn parse_valid_hit_test_request() {
+        let req = send_and_read(r#"{"type":"hit_test","points":[[10.0,20.5],[0,799]]}"#).unwrap();
+        assert!(matches!(req, IpcRequest::HitTest(ref points) if points == &[(10.0, 20.5), (0.0, 799.0)]));
+    }
+
+    #[test]
+    fn parse_valid_filter_request() {
+        let req = send_and_read(r#"{"type":"filter","filter":{"command":"cargo"}}"#).unwrap();
+        assert!(matches!(req, IpcRequest::Filter(Some(CellFilter::Command(ref text))) if text == "cargo"));
+        let req = send_and_read(r#"{"type":"filter","filter":{"tag":"red"}}"#).unwrap();
+        assert!(matches!(req, IpcRequest::Filter(Some(CellFilter::Tag(ColorTag::Red)))));
+        let req = send_and_read(r#"{"type":"filter"}"#).unwrap();
+        assert!(matches!(req, IpcRequest::Filter(None)));
+    }
+
+    #[test]
+    fn parse_valid_xwayland_request() {
+        let req = send_and_read(r#"{"type":"xwayland"}"#).unwrap();
+        assert!(matches!(req, IpcRequest::XWayland));
+    }
+
+    #[test]
+    fn parse_valid_env_request() {
+        let req = send_and_read(r#"{"type":"env"}"#).unwrap();
+        assert!(matches!(req, IpcRequest::Env));
+    }
+
     #[test]
     fn reject_empty_message() {
         let result = send_and_read("");
@@ -425,6 +868,68 @@ mod tests {
         assert!(matches!(result, Err(IpcError::MessageTooLarge { .. })));
     }
 
+    #[test]
+    fn giant_message_is_rejected_without_reading_it_all() {
+        let (client, server) = ipc_pair();
+        // A client that would send 64 MB without a newline
+        std::thread::spawn(move || {
+            let mut client = client;
+            let chunk = vec![b'x'; 64 * 1024];
+            for _ in 0..1024 {
+                if client.write_all(&chunk).is_err() {
+                    break;
+                }
+            }
+        });
+        match read_ipc_request(server) {
+            Err(IpcError::MessageTooLarge { size, max }) => assert!(size <= max + 64 * 1024),
+            other => panic!("expected MessageTooLarge, got {:?}", other.map(|(req, _)| req)),
+        }
+    }
+
+    #[test]
+    fn reject_truncated_json() {
+        let (client, server) = ipc_pair();
+        std::thread::spawn(move || {
+            let mut client = client;
+            // The client goes away mid-message, without a newline
+            let _ = client.write_all(br#"{"type":"spawn","prompt":"$ ","comm"#);
+        });
+        let result = read_ipc_request(server);
+        assert!(matches!(result, Err(IpcError::ParseError(_))));
+    }
+
+    #[test]
+    fn reject_invalid_utf8() {
+        let (client, server) = ipc_pair();
+        std::thread::spawn(move || {
+            let mut client = client;
+            let _ = client.write_all(b"{\"type\":\"status\xff\xfe\"}\n");
+        });
+        let result = read_ipc_request(server);
+        assert!(matches!(result, Err(IpcError::InvalidUtf8)));
+    }
+
+    #[test]
+    fn slow_client_times_out_at_the_deadline() {
+        let (client, server) = ipc_pair();
+        // Each byte arrives well within a single read's timeout, but the
+        // line never ends
+        std::thread::spawn(move || {
+            let mut client = client;
+            for _ in 0..100 {
+                if client.write_all(b" ").is_err() {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        });
+        let start = Instant::now();
+        let result = read_ipc_request_within(server, Duration::from_millis(200));
+        assert!(matches!(result, Err(IpcError::Timeout)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
     #[test]
     fn reject_command_too_large() {
         let big_command = "x".repeat(MAX_COMMAND_SIZE + 1);
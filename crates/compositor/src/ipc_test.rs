@@ -47,6 +47,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn spawn_overrides_are_optional() {
+        let parsed: IpcMessage = serde_json::from_value(serde_json::json!({
+            "type": "spawn",
+            "command": "make",
+            "cwd": "/src",
+            "env": {},
+            "shell": "/usr/bin/zsh",
+            "env_overrides": { "PAGER": "less" },
+        }))
+        .unwrap();
+        match parsed {
+            IpcMessage::Spawn { shell, env_overrides, .. } => {
+                assert_eq!(shell.as_deref(), Some("/usr/bin/zsh"));
+                assert_eq!(env_overrides.get("PAGER").map(String::as_str), Some("less"));
+            }
+            _ => panic!("expected Spawn"),
+        }
+
+        // Older clients don't send them
+        let parsed: IpcMessage = serde_json::from_value(serde_json::json!({
+            "type": "spawn",
+            "command": "make",
+            "cwd": "/src",
+            "env": {},
+        }))
+        .unwrap();
+        assert!(matches!(parsed, IpcMessage::Spawn { shell: None, ref env_overrides, .. } if env_overrides.is_empty()));
+    }
+
     #[test]
     fn terminal_starts_small_and_grows() {
         // This test documents the expected behavior:
@@ -467,6 +497,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn status_message_defaults_to_one_shot() {
+        let parsed: IpcMessage = serde_json::from_value(serde_json::json!({
+            "type": "status",
+        }))
+        .unwrap();
+        assert!(matches!(parsed, IpcMessage::Status { watch: false }));
+
+        let parsed: IpcMessage = serde_json::from_value(serde_json::json!({
+            "type": "status",
+            "watch": true,
+        }))
+        .unwrap();
+        assert!(matches!(parsed, IpcMessage::Status { watch: true }));
+    }
+
     #[test]
     fn gui_spawn_flow_spawns_command_terminal() {
         // Test the full flow: spawn with foreground should spawn a terminal to run the command.
@@ -0,0 +1,177 @@
+//! Keyboard-only resize mode
+//!
+//! Ctrl+Shift+R (or Super+R) starts resizing the focused cell without the
+//! mouse: Down/j grows it by a row, Up/k shrinks it by a row, Enter keeps the
+//! new height and Esc puts the old one back. While the mode is on, every other
+//! key press is swallowed.
+//!
+//! It drives the same `ResizeDrag` as dragging a resize handle, as if the
+//! pointer moved one row per key press, so terminals snap to whole rows and
+//! external windows get a single configure when the resize is committed.
+
+use crate::mouse_actions;
+use crate::state::{ResizeDrag, StackWindow, TermStack, MIN_WINDOW_HEIGHT};
+use crate::terminal_manager::TerminalManager;
+use crate::title_bar::TITLE_BAR_HEIGHT;
+
+/// Key press in resize mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeKey {
+    Grow,
+    Shrink,
+    Commit,
+    Cancel,
+}
+
+/// Resize mode state, kept on `TermStack`
+#[derive(Debug, Default)]
+pub struct KeyboardResize {
+    /// Whether resize mode is on (key presses are ours)
+    active: bool,
+    start_requested: bool,
+    /// Keys pressed since the last frame
+    pending: Vec<ResizeKey>,
+    /// Rows grown (negative: shrunk) since the mode started
+    rows: i32,
+}
+
+impl KeyboardResize {
+    /// Start resize mode on the next frame (or leave it, keeping the new height)
+    pub fn request_toggle(&mut self) {
+        self.start_requested = true;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Queue a key press for the next frame
+    pub fn press(&mut self, key: ResizeKey) {
+        self.pending.push(key);
+    }
+
+    /// Apply queued grow/shrink presses, never going below `min_rows`;
+    /// returns how the mode ends, if it does
+    fn take_keys(&mut self, min_rows: i32) -> Option<ResizeKey> {
+        for key in std::mem::take(&mut self.pending) {
+            match key {
+                ResizeKey::Grow => self.rows += 1,
+                ResizeKey::Shrink => self.rows = (self.rows - 1).max(min_rows.min(self.rows)),
+                ResizeKey::Commit | ResizeKey::Cancel => return Some(key),
+            }
+        }
+        None
+    }
+
+    fn reset(&mut self) {
+        self.active = false;
+        self.pending.clear();
+        self.rows = 0;
+    }
+}
+
+/// Start, step and end keyboard resizes (once per frame)
+pub fn process(compositor: &mut TermStack, terminal_manager: &mut TerminalManager) {
+    if std::mem::take(&mut compositor.keyboard_resize.start_requested) {
+        if compositor.keyboard_resize.active {
+            compositor.keyboard_resize.press(ResizeKey::Commit);
+        } else {
+            start(compositor);
+        }
+    }
+    if !compositor.keyboard_resize.active {
+        return;
+    }
+
+    // A mouse drag took over, or the cell went away
+    let drag = compositor.resizing.as_ref().filter(|drag| drag.keyboard);
+    let Some((cell, start_height)) = drag.map(|drag| (drag.cell, drag.start_height)) else {
+        tracing::debug!("keyboard resize interrupted");
+        compositor.keyboard_resize.reset();
+        return;
+    };
+    let Some(index) = compositor.cell_index(cell) else {
+        compositor.resizing = None;
+        compositor.keyboard_resize.reset();
+        return;
+    };
+
+    let row_height = match compositor.layout_nodes[index].cell {
+        StackWindow::Terminal(id) => terminal_manager.cell_metrics(id).1,
+        StackWindow::External(_) => terminal_manager.cell_height,
+    }
+    .max(1) as i32;
+    let min_rows = -((start_height - MIN_WINDOW_HEIGHT).max(0) / row_height);
+
+    let before = compositor.keyboard_resize.rows;
+    let end = compositor.keyboard_resize.take_keys(min_rows);
+    let rows = match end {
+        Some(ResizeKey::Cancel) => 0,
+        _ => compositor.keyboard_resize.rows,
+    };
+    if rows != before || end == Some(ResizeKey::Cancel) {
+        // The drag started at a virtual pointer position of 0
+        mouse_actions::update_resize_drag(compositor, terminal_manager, rows * row_height, TITLE_BAR_HEIGHT as i32);
+        compositor.redraw_requested = true;
+    }
+
+    if let Some(end) = end {
+        tracing::info!(?end, rows, "keyboard resize finished");
+        mouse_actions::end_resize_drag(compositor, terminal_manager);
+        compositor.keyboard_resize.reset();
+        compositor.redraw_requested = true;
+    }
+}
+
+/// Begin resizing the focused cell
+fn start(compositor: &mut TermStack) {
+    if compositor.resizing.is_some() {
+        return;
+    }
+    let Some(node) = compositor.focused_index().and_then(|index| compositor.layout_nodes.get(index)) else {
+        return;
+    };
+    tracing::info!(cell = ?node.id, height = node.height, "keyboard resize started");
+    compositor.resizing = Some(ResizeDrag {
+        cell: node.id,
+        start_screen_y: 0,
+        start_height: node.height,
+        target_height: node.height,
+        last_sent_height: None,
+        keyboard: true,
+    });
+    compositor.keyboard_resize.active = true;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_move_by_rows_and_stop_at_the_minimum() {
+        let mut resize = KeyboardResize::default();
+        resize.press(ResizeKey::Grow);
+        resize.press(ResizeKey::Grow);
+        resize.press(ResizeKey::Shrink);
+        assert_eq!(resize.take_keys(-2), None);
+        assert_eq!(resize.rows, 1);
+
+        for _ in 0..5 {
+            resize.press(ResizeKey::Shrink);
+        }
+        resize.take_keys(-2);
+        assert_eq!(resize.rows, -2);
+    }
+
+    #[test]
+    fn commit_and_cancel_end_the_mode() {
+        let mut resize = KeyboardResize::default();
+        resize.press(ResizeKey::Grow);
+        resize.press(ResizeKey::Cancel);
+        resize.press(ResizeKey::Grow);
+        assert_eq!(resize.take_keys(0), Some(ResizeKey::Cancel));
+        // Presses after the end are dropped
+        assert_eq!(resize.rows, 1);
+        assert!(resize.pending.is_empty());
+    }
+}
@@ -9,6 +9,9 @@
 //! - Visibility detection (which windows are in viewport)
 //! - Scroll offset calculations
 //! - Total height computation
+//! - Incremental cell offsets (`HeightIndex`)
+//! - Horizontal split of the output between columns (`column_span`)
+//! - Strips of the output kept free for panels (`ReservedArea`)
 //!
 //! # Design Contract
 //!
@@ -29,6 +32,57 @@ use crate::title_bar::TITLE_BAR_HEIGHT;
 /// Focus indicator width in pixels (also used as left margin for content)
 pub const FOCUS_INDICATOR_WIDTH: i32 = 2;
 
+/// Identity of a column of cells, stable while the column exists
+///
+/// Cells of a column are contiguous in `layout_nodes`, and columns follow
+/// each other left to right in the same order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ColumnId(pub u32);
+
+/// Horizontal span `(x, width)` of the column at `position` (0 = leftmost)
+/// when `count` columns share the output equally
+///
+/// All columns get the same width so terminals in different columns have
+/// the same number of columns; a remainder of less than `count` pixels is
+/// left unused at the right edge.
+pub fn column_span(output_width: i32, count: usize, position: usize) -> (i32, i32) {
+    let count = count.max(1) as i32;
+    let width = output_width / count;
+    (width * position as i32, width)
+}
+
+/// Strips at the top and bottom edge of the output reserved by panels
+/// (layer-shell exclusive zones); the column is laid out between them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReservedArea {
+    pub top: i32,
+    pub bottom: i32,
+}
+
+impl ReservedArea {
+    /// Strips of an output `output_height` tall whose area left for windows
+    /// starts at `zone_top` and is `zone_height` tall
+    pub fn from_zone(output_height: i32, zone_top: i32, zone_height: i32) -> Self {
+        let top = zone_top.clamp(0, output_height);
+        let bottom = (output_height - top - zone_height.max(0)).max(0);
+        Self { top, bottom }
+    }
+
+    /// Height left for the column (never less than one pixel)
+    pub fn viewport_height(&self, output_height: i32) -> i32 {
+        (output_height - self.top - self.bottom).max(1)
+    }
+
+    /// Whether screen Y (0 at top) falls in one of the strips
+    pub fn covers(&self, screen_y: f64, output_height: i32) -> bool {
+        screen_y < self.top as f64 || screen_y >= (output_height - self.bottom) as f64
+    }
+}
+
+/// Distance beyond the viewport within which off-screen cells are still
+/// prepared for rendering, so cells scrolled into view aren't a frame late
+pub const CULL_MARGIN: i32 = 256;
+
 /// Calculate the visual/render height for a terminal.
 ///
 /// This is the total height including the title bar (if shown).
@@ -75,6 +129,134 @@ pub fn heights_changed_significantly(
         })
 }
 
+/// Which cells are within the viewport, extended by `margin` on both sides
+///
+/// Used to skip per-frame work (texture uploads, surface element collection)
+/// for cells far outside the viewport. Cells with unknown height (0) are
+/// always included so their real size gets measured.
+pub fn cells_near_viewport(
+    heights: impl IntoIterator<Item = i32>,
+    scroll_offset: f64,
+    viewport_height: i32,
+    margin: i32,
+) -> Vec<bool> {
+    let mut top = -(scroll_offset as i32);
+    heights
+        .into_iter()
+        .map(|height| {
+            let near = height <= 0 || (top < viewport_height + margin && top + height > -margin);
+            top += height;
+            near
+        })
+        .collect()
+}
+
+/// Cached cumulative cell heights (prefix sums)
+///
+/// Lets cell lookups by Y, cell offsets and the total height run in
+/// O(log n) / O(1) instead of walking every cell. `sync()` compares against
+/// the current heights and only recomputes offsets from the first cell whose
+/// height changed; `set()` updates a single cell.
+#[derive(Debug, Clone)]
+pub struct HeightIndex {
+    /// Cell heights as of the last sync
+    heights: Vec<i32>,
+    /// `offsets[i]` is the content Y where cell `i` starts;
+    /// `offsets[len]` is the total height
+    offsets: Vec<i32>,
+}
+
+impl Default for HeightIndex {
+    fn default() -> Self {
+        Self {
+            heights: Vec::new(),
+            offsets: vec![0],
+        }
+    }
+}
+
+impl HeightIndex {
+    /// Bring the index in line with `heights`
+    ///
+    /// Returns the first cell whose offset or height changed, or `None` if
+    /// nothing did.
+    pub fn sync(&mut self, heights: impl IntoIterator<Item = i32>) -> Option<usize> {
+        let mut first_changed = None;
+        let mut len = 0;
+        for (i, height) in heights.into_iter().enumerate() {
+            len = i + 1;
+            match self.heights.get_mut(i) {
+                Some(old) if *old == height => {}
+                Some(old) => {
+                    *old = height;
+                    first_changed.get_or_insert(i);
+                }
+                None => {
+                    self.heights.push(height);
+                    first_changed.get_or_insert(i);
+                }
+            }
+        }
+        if len < self.heights.len() {
+            self.heights.truncate(len);
+            first_changed.get_or_insert(len);
+        }
+
+        let start = first_changed?;
+        self.offsets.truncate(start + 1);
+        for i in start..self.heights.len() {
+            self.offsets.push(self.offsets[i] + self.heights[i]);
+        }
+        Some(start)
+    }
+
+    /// Change the height of one cell
+    pub fn set(&mut self, index: usize, height: i32) {
+        let Some(old) = self.heights.get_mut(index) else {
+            return;
+        };
+        let delta = height - *old;
+        *old = height;
+        for offset in &mut self.offsets[index + 1..] {
+            *offset += delta;
+        }
+    }
+
+    /// Number of cells
+    pub fn len(&self) -> usize {
+        self.heights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heights.is_empty()
+    }
+
+    /// Content Y where cell `index` starts (the total height for `index == len`)
+    pub fn offset(&self, index: usize) -> i32 {
+        self.offsets[index.min(self.heights.len())]
+    }
+
+    /// Sum of all cell heights
+    pub fn total(&self) -> i32 {
+        self.offset(self.heights.len())
+    }
+
+    /// Cell containing content Y `y` (`start <= y < end`)
+    pub fn index_at(&self, y: i32) -> Option<usize> {
+        let index = self.offsets[1..].partition_point(|&end| end <= y);
+        (index < self.heights.len() && self.offsets[index] <= y).then_some(index)
+    }
+
+    /// First cell whose end is at or below content Y `y` (`start < y <= end`)
+    ///
+    /// Matches hit-testing in render coordinates, where cell edges are
+    /// inclusive at the bottom of the screen.
+    pub fn index_ending_at_or_after(&self, y: f64) -> Option<usize> {
+        let index = self.offsets[1..].partition_point(|&end| (end as f64) < y);
+        (index < self.heights.len() && (self.offsets[index] as f64) < y).then_some(index)
+    }
+}
+
 /// Calculated layout for all windows
 #[derive(Debug, Clone)]
 pub struct ColumnLayout {
@@ -424,4 +606,113 @@ mod tests {
         let visible: Vec<_> = layout.visible_windows().collect();
         assert_eq!(visible, vec![2, 3]);
     }
+
+    fn index(heights: &[i32]) -> HeightIndex {
+        let mut index = HeightIndex::default();
+        index.sync(heights.iter().copied());
+        index
+    }
+
+    #[test]
+    fn height_index_offsets_are_prefix_sums() {
+        let index = index(&[100, 200, 0, 50]);
+        assert_eq!(index.len(), 4);
+        assert_eq!(
+            (0..=4).map(|i| index.offset(i)).collect::<Vec<_>>(),
+            vec![0, 100, 300, 300, 350]
+        );
+        assert_eq!(index.total(), 350);
+        assert_eq!(HeightIndex::default().total(), 0);
+    }
+
+    #[test]
+    fn height_index_sync_reports_first_change() {
+        let mut index = index(&[100, 200, 300]);
+        assert_eq!(index.sync([100, 200, 300]), None);
+        assert_eq!(index.sync([100, 250, 300]), Some(1));
+        assert_eq!(index.total(), 650);
+
+        // Appending and removing cells
+        assert_eq!(index.sync([100, 250, 300, 10]), Some(3));
+        assert_eq!(index.total(), 660);
+        assert_eq!(index.sync([100]), Some(1));
+        assert_eq!(index.total(), 100);
+        assert_eq!(index.sync([]), Some(0));
+        assert!(index.is_empty());
+    }
+
+    #[test]
+    fn height_index_set_shifts_later_offsets() {
+        let mut index = index(&[100, 200, 300]);
+        index.set(0, 150);
+        assert_eq!(index.offset(1), 150);
+        assert_eq!(index.offset(2), 350);
+        assert_eq!(index.total(), 650);
+        assert_eq!(index.sync([150, 200, 300]), None);
+    }
+
+    #[test]
+    fn height_index_lookup_skips_empty_cells() {
+        let index = index(&[100, 0, 200]);
+        assert_eq!(index.index_at(-1), None);
+        assert_eq!(index.index_at(0), Some(0));
+        assert_eq!(index.index_at(99), Some(0));
+        assert_eq!(index.index_at(100), Some(2));
+        assert_eq!(index.index_at(299), Some(2));
+        assert_eq!(index.index_at(300), None);
+
+        assert_eq!(index.index_ending_at_or_after(0.0), None);
+        assert_eq!(index.index_ending_at_or_after(0.5), Some(0));
+        assert_eq!(index.index_ending_at_or_after(100.0), Some(0));
+        assert_eq!(index.index_ending_at_or_after(100.5), Some(2));
+        assert_eq!(index.index_ending_at_or_after(300.0), Some(2));
+        assert_eq!(index.index_ending_at_or_after(300.5), None);
+    }
+
+    #[test]
+    fn columns_split_the_output_equally() {
+        assert_eq!(column_span(1000, 1, 0), (0, 1000));
+        assert_eq!(column_span(1000, 0, 0), (0, 1000));
+        assert_eq!(column_span(1001, 2, 0), (0, 500));
+        assert_eq!(column_span(1001, 2, 1), (500, 500));
+        assert_eq!(column_span(900, 3, 2), (600, 300));
+    }
+
+    #[test]
+    fn reserved_area_is_what_the_zone_leaves_free() {
+        let reserved = ReservedArea::from_zone(720, 30, 660);
+        assert_eq!(reserved, ReservedArea { top: 30, bottom: 30 });
+        assert_eq!(reserved.viewport_height(720), 660);
+        assert!(reserved.covers(29.5, 720));
+        assert!(!reserved.covers(30.0, 720));
+        assert!(!reserved.covers(689.5, 720));
+        assert!(reserved.covers(690.0, 720));
+
+        assert_eq!(ReservedArea::from_zone(720, 0, 720), ReservedArea::default());
+        assert_eq!(ReservedArea::default().viewport_height(720), 720);
+    }
+
+    #[test]
+    fn panels_never_take_the_whole_viewport() {
+        let reserved = ReservedArea::from_zone(720, 400, 0);
+        assert_eq!(reserved, ReservedArea { top: 400, bottom: 320 });
+        assert_eq!(reserved.viewport_height(720), 1);
+    }
+
+    #[test]
+    fn cells_near_viewport_include_margin() {
+        // Cells at y=-400, -200, 0, 200, ..., 1000 with a 600px viewport
+        let heights = [200; 8];
+        let near = cells_near_viewport(heights, 400.0, 600, 100);
+        assert_eq!(near, vec![false, true, true, true, true, true, false, false]);
+
+        let exact = cells_near_viewport(heights, 400.0, 600, 0);
+        assert_eq!(exact, vec![false, false, true, true, true, false, false, false]);
+    }
+
+    #[test]
+    fn cells_with_unknown_height_are_near_viewport() {
+        let near = cells_near_viewport([5000, 0, 100], 0.0, 600, 0);
+        assert_eq!(near, vec![true, true, false]);
+    }
 }
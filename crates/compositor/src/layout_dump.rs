@@ -0,0 +1,158 @@
+//! Serializable snapshot of the stack's layout
+//!
+//! `TermStack::dump_layout()` captures what the layout works with: each cell's
+//! kind, title, tag, position and height, which cells are hidden, the scroll offset
+//! and the focused cell. Golden tests compare its text form
+//! (`test_harness::assertions::assert_layout_matches`), the `FRAME STATE` debug
+//! log prints it, and `termstack ls --json` fetches it over IPC.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::color_tags::ColorTag;
+use crate::state::{StackWindow, TermStack};
+use crate::terminal_manager::TerminalManager;
+
+/// What a cell holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CellKind {
+    Terminal,
+    External,
+}
+
+/// One cell of the stack
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CellLayout {
+    /// `CellId::to_bits()`, stable while the cell exists
+    pub cell: u64,
+    pub kind: CellKind,
+    /// Title bar text
+    pub title: String,
+    /// Content-space Y of the cell's top edge
+    pub y: i32,
+    pub height: i32,
+    /// Hidden terminals take no space (e.g. the launcher of a foreground GUI app)
+    pub hidden: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<ColorTag>,
+}
+
+/// The whole stack, top to bottom
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LayoutDump {
+    pub cells: Vec<CellLayout>,
+    pub scroll_offset: f64,
+    /// Index of the focused cell
+    pub focused: Option<usize>,
+    pub output_height: i32,
+}
+
+impl TermStack {
+    /// Snapshot of the current layout
+    pub fn dump_layout(&self, terminal_manager: &TerminalManager) -> LayoutDump {
+        let mut y = 0;
+        let cells = self
+            .layout_nodes
+            .iter()
+            .map(|node| {
+                let (kind, title, hidden) = match &node.cell {
+                    StackWindow::Terminal(id) => (
+                        CellKind::Terminal,
+                        terminal_manager.get(*id).map(|t| t.display_title().to_string()).unwrap_or_default(),
+                        !terminal_manager.is_terminal_visible(*id),
+                    ),
+                    StackWindow::External(entry) => (CellKind::External, entry.title_bar_text().into_owned(), false),
+                };
+                let cell = CellLayout {
+                    cell: node.id.to_bits(),
+                    kind,
+                    title,
+                    y,
+                    height: node.height,
+                    hidden,
+                    tag: node.tag,
+                };
+                y += node.height;
+                cell
+            })
+            .collect();
+        LayoutDump {
+            cells,
+            scroll_offset: self.scroll_offset,
+            focused: self.focused_index(),
+            output_height: self.output_size.h,
+        }
+    }
+}
+
+/// One line for the stack, then one per cell, leaving out the cell ids so
+/// golden text doesn't depend on allocation order
+impl fmt::Display for LayoutDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let focused = self.focused.map_or("-".to_string(), |index| index.to_string());
+        write!(f, "scroll={} focused={focused} output_h={}", self.scroll_offset, self.output_height)?;
+        for (index, cell) in self.cells.iter().enumerate() {
+            let kind = match cell.kind {
+                CellKind::Terminal => "term",
+                CellKind::External => "ext",
+            };
+            write!(f, "\n[{index}] {kind} y={} h={}", cell.y, cell.height)?;
+            if cell.hidden {
+                write!(f, " hidden")?;
+            }
+            if let Some(tag) = cell.tag {
+                write!(f, " tag={}", tag.name())?;
+            }
+            if !cell.title.is_empty() {
+                write!(f, " {:?}", cell.title)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Answer layout requests from IPC (termstack ls)
+pub fn handle_ipc_layout_requests(compositor: &mut TermStack, terminal_manager: &TerminalManager) {
+    if compositor.pending_layout_requests.is_empty() {
+        return;
+    }
+    let dump = compositor.dump_layout(terminal_manager);
+    for stream in std::mem::take(&mut compositor.pending_layout_requests) {
+        if let Err(e) = crate::ipc::send_json_response(stream, &dump) {
+            tracing::warn!(error = ?e, "Failed to send layout response");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_form_lists_cells() {
+        let cell = |kind, title: &str, y, height, hidden| CellLayout {
+            cell: 0,
+            kind,
+            title: title.to_string(),
+            y,
+            height,
+            hidden,
+            tag: None,
+        };
+        let dump = LayoutDump {
+            cells: vec![
+                cell(CellKind::Terminal, "fish", 0, 0, true),
+                CellLayout { tag: Some(ColorTag::Blue), ..cell(CellKind::External, "", 0, 400, false) },
+            ],
+            scroll_offset: 12.5,
+            focused: Some(1),
+            output_height: 720,
+        };
+        assert_eq!(
+            dump.to_string(),
+            "scroll=12.5 focused=1 output_h=720\n[0] term y=0 h=0 hidden \"fish\"\n[1] ext y=0 h=400 tag=blue"
+        );
+    }
+}
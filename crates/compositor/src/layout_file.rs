@@ -0,0 +1,355 @@
+//! Layout files: share how a stack is set up
+//!
+//! `termstack layout export dev.toml` writes the cells of the stack to a
+//! TOML file, one `[[cell]]` table each, top to bottom: the shells with
+//! their working directories, the commands with where they were started,
+//! the GUI apps, which column each cell is in, the height of cells sized by
+//! hand and their names and color tags. Scrollback isn't included, and paths
+//! in the home directory are written with `~` so the file works for others.
+//!
+//! `termstack layout import dev.toml` adds the cells to the bottom of the
+//! stack in the same order and columns: shells start in their directories,
+//! commands run again and GUI apps are launched again (their windows land
+//! where new windows do, without their names and tags).
+
+use std::collections::HashMap;
+use std::io;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::color_tags::ColorTag;
+use crate::crash::CellKind;
+use crate::home::{expand_home, home_dir, shorten_home};
+use crate::ipc::SpawnRequest;
+use crate::state::{StackWindow, TermStack};
+use crate::terminal_manager::{TerminalId, TerminalManager};
+
+/// Version written to new layout files
+pub const LAYOUT_FILE_VERSION: u32 = 1;
+
+/// Errors reading or writing a layout file
+#[derive(Debug, Error)]
+pub enum LayoutFileError {
+    #[error("failed to access {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("{path}: {source}")]
+    Parse { path: PathBuf, source: toml::de::Error },
+
+    #[error("failed to write layout: {0}")]
+    Serialize(#[from] toml::ser::Error),
+
+    #[error("{0}: unsupported layout file version")]
+    Version(PathBuf),
+}
+
+/// A stack's cells, as saved in a layout file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutFile {
+    pub version: u32,
+    /// Top to bottom, column by column
+    #[serde(default, rename = "cell")]
+    pub cells: Vec<LayoutCell>,
+}
+
+/// One cell of a layout file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutCell {
+    pub kind: CellKind,
+    /// Command line of a command or GUI app (empty for shells)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub command: String,
+    /// Working directory, starting with `~` inside the home directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// Column, counted from 0 on the left
+    #[serde(default, skip_serializing_if = "is_first_column")]
+    pub column: usize,
+    /// Rows of a terminal sized by hand (others grow with their output)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows: Option<u16>,
+    /// Name given with `termstack title`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<ColorTag>,
+}
+
+fn is_first_column(column: &usize) -> bool {
+    *column == 0
+}
+
+impl LayoutFile {
+    /// Describe the current stack
+    pub fn capture(compositor: &TermStack, terminal_manager: &TerminalManager) -> Self {
+        let home = home_dir();
+        // Output terminals of GUI apps come back with the app
+        let output_terminals: Vec<TerminalId> = compositor
+            .layout_nodes
+            .iter()
+            .filter_map(|node| match &node.cell {
+                StackWindow::External(entry) => entry.output_terminal,
+                StackWindow::Terminal(_) => None,
+            })
+            .collect();
+
+        let mut cells = Vec::new();
+        for node in &compositor.layout_nodes {
+            let column = compositor.columns.position(node.column).unwrap_or(0);
+            let cell = match &node.cell {
+                StackWindow::Terminal(id) => {
+                    let Some(terminal) = terminal_manager.get(*id) else {
+                        continue;
+                    };
+                    let is_shell = !terminal.show_title_bar;
+                    let cwd = if is_shell {
+                        crate::crash::process_cwd(terminal.pid()).or_else(|| terminal.cwd.clone())
+                    } else if output_terminals.contains(id) {
+                        continue;
+                    } else {
+                        // Builtin results and calculator answers ran nothing
+                        let Some(cwd) = terminal.cwd.clone() else {
+                            continue;
+                        };
+                        Some(cwd)
+                    };
+                    let rows = terminal
+                        .manually_sized
+                        .then(|| (terminal.height / terminal.cell_size().1.max(1)) as u16);
+                    LayoutCell {
+                        kind: if is_shell { CellKind::Shell } else { CellKind::Command },
+                        command: if is_shell { String::new() } else { terminal.command.clone() },
+                        cwd: cwd.map(|cwd| shorten_home(&cwd, home.as_deref())),
+                        column,
+                        rows,
+                        title: terminal.custom_title.clone(),
+                        tag: node.tag,
+                    }
+                }
+                StackWindow::External(entry) => LayoutCell {
+                    kind: CellKind::Window,
+                    command: entry.command.clone(),
+                    cwd: None,
+                    column,
+                    rows: None,
+                    title: entry.custom_title.clone(),
+                    tag: node.tag,
+                },
+            };
+            cells.push(cell);
+        }
+
+        Self { version: LAYOUT_FILE_VERSION, cells }
+    }
+
+    /// Parse a layout file's contents (`path` is for error messages)
+    pub fn from_toml(text: &str, path: &Path) -> Result<Self, LayoutFileError> {
+        let layout: Self =
+            toml::from_str(text).map_err(|source| LayoutFileError::Parse { path: path.to_path_buf(), source })?;
+        if layout.version == 0 || layout.version > LAYOUT_FILE_VERSION {
+            return Err(LayoutFileError::Version(path.to_path_buf()));
+        }
+        Ok(layout)
+    }
+
+    pub fn to_toml(&self) -> Result<String, LayoutFileError> {
+        Ok(toml::to_string(self)?)
+    }
+
+    /// Read a layout file
+    pub fn load(path: &Path) -> Result<Self, LayoutFileError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|source| LayoutFileError::Io { path: path.to_path_buf(), source })?;
+        Self::from_toml(&text, path)
+    }
+
+    /// Write a layout file
+    pub fn save(&self, path: &Path) -> Result<(), LayoutFileError> {
+        std::fs::write(path, self.to_toml()?)
+            .map_err(|source| LayoutFileError::Io { path: path.to_path_buf(), source })
+    }
+}
+
+/// Add the cells of `layout` to the bottom of the stack, returning how many
+/// were added. GUI apps are queued and launched with the next frame.
+pub fn import_layout(
+    compositor: &mut TermStack,
+    terminal_manager: &mut TerminalManager,
+    layout: &LayoutFile,
+) -> Result<usize, String> {
+    if layout.cells.is_empty() {
+        return Err("the layout has no cells".to_string());
+    }
+    let home = home_dir();
+    let fallback_dir = home.clone().or_else(|| std::env::current_dir().ok()).unwrap_or_else(|| PathBuf::from("/"));
+    let dir_of = |cell: &LayoutCell| {
+        let dir = cell.cwd.as_deref().map(|cwd| expand_home(cwd, home.as_deref()));
+        match dir {
+            Some(dir) if dir.is_dir() => dir,
+            Some(dir) => {
+                tracing::warn!(?dir, "layout directory missing, starting in the home directory");
+                fallback_dir.clone()
+            }
+            None => fallback_dir.clone(),
+        }
+    };
+
+    // Shells first, so commands can be started from the shell of their column
+    let mut terminals: Vec<Option<TerminalId>> = vec![None; layout.cells.len()];
+    for (slot, cell) in terminals.iter_mut().zip(&layout.cells) {
+        if cell.kind != CellKind::Shell {
+            continue;
+        }
+        match terminal_manager.spawn_in(&dir_of(cell)) {
+            Ok(id) => *slot = Some(id),
+            Err(e) => tracing::warn!(error = ?e, "failed to start layout shell"),
+        }
+    }
+
+    let base_env: HashMap<String, String> = std::env::vars().collect();
+    let mut windows = Vec::new();
+    for (index, cell) in layout.cells.iter().enumerate() {
+        let request = SpawnRequest {
+            prompt: String::new(),
+            command: cell.command.clone(),
+            cwd: dir_of(cell),
+            env: base_env.clone(),
+            foreground: None,
+            shell: None,
+            env_overrides: HashMap::new(),
+        };
+        match cell.kind {
+            CellKind::Shell => {}
+            CellKind::Command if cell.command.trim().is_empty() => {}
+            CellKind::Command => {
+                // Commands usually sit above the shell that started them
+                let column_shell = |i: &usize| {
+                    let other = &layout.cells[*i];
+                    (other.kind == CellKind::Shell && other.column == cell.column).then_some(terminals[*i]).flatten()
+                };
+                let parent = (index + 1..layout.cells.len())
+                    .find_map(|i| column_shell(&i))
+                    .or_else(|| (0..index).rev().find_map(|i| column_shell(&i)));
+                let env = crate::spawn_handler::command_env(compositor, &request);
+                match terminal_manager.spawn_command("", &cell.command, &request.cwd, &env, parent) {
+                    Ok(id) => terminals[index] = Some(id),
+                    Err(e) => tracing::warn!(command = %cell.command, error = ?e, "failed to run layout command"),
+                }
+            }
+            CellKind::Window => windows.push(SpawnRequest { foreground: Some(false), ..request }),
+        }
+    }
+
+    let mut added = 0;
+    let mut last = None;
+    for (cell, terminal) in layout.cells.iter().zip(&terminals) {
+        let Some(id) = *terminal else {
+            continue;
+        };
+        if let Some(term) = terminal_manager.get_mut(id) {
+            term.custom_title = cell.title.clone();
+            if let Some(rows) = cell.rows {
+                let cell_height = term.cell_size().1;
+                term.resize_to_height(u32::from(rows.max(1)) * cell_height, cell_height);
+            }
+        }
+        let cell_id = compositor.push_cell_to_column(StackWindow::Terminal(id), cell.column);
+        if let Some(index) = compositor.cell_index(cell_id) {
+            compositor.layout_nodes[index].tag = cell.tag;
+        }
+        last = Some(cell_id);
+        added += 1;
+    }
+    compositor.enforce_terminal_limit(terminal_manager);
+
+    // Launched last to first, as the queue is taken from the back
+    added += windows.len();
+    compositor.pending_gui_spawn_requests.extend(windows.into_iter().rev());
+
+    if let Some(index) = last.and_then(|cell| compositor.cell_index(cell)) {
+        compositor.set_focus_by_index(index);
+    }
+    compositor.recalculate_layout();
+    if let Some(index) = compositor.focused_index() {
+        compositor.scroll_to_show_window_bottom(index);
+    }
+    tracing::info!(added, cells = layout.cells.len(), "imported layout");
+    Ok(added)
+}
+
+/// Answer `termstack layout export` and `import` requests
+pub fn handle_ipc_layout_file_requests(compositor: &mut TermStack, terminal_manager: &mut TerminalManager) {
+    for stream in std::mem::take(&mut compositor.pending_layout_exports) {
+        let layout = LayoutFile::capture(compositor, terminal_manager);
+        if let Err(e) = crate::ipc::send_json_response(stream, &layout) {
+            tracing::warn!(error = ?e, "Failed to send layout file");
+        }
+    }
+    let imports: Vec<(LayoutFile, UnixStream)> = std::mem::take(&mut compositor.pending_layout_imports);
+    for (layout, stream) in imports {
+        let result = import_layout(compositor, terminal_manager, &layout);
+        if let Err(e) = crate::ipc::send_json_response(stream, &result) {
+            tracing::warn!(error = ?e, "Failed to send layout import response");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> LayoutFile {
+        LayoutFile {
+            version: LAYOUT_FILE_VERSION,
+            cells: vec![
+                LayoutCell {
+                    kind: CellKind::Command,
+                    command: "cargo watch -x test".to_string(),
+                    cwd: Some("~/code/app".to_string()),
+                    column: 0,
+                    rows: Some(12),
+                    title: Some("tests".to_string()),
+                    tag: Some(ColorTag::Green),
+                },
+                LayoutCell {
+                    kind: CellKind::Shell,
+                    command: String::new(),
+                    cwd: Some("~/code/app".to_string()),
+                    column: 0,
+                    rows: None,
+                    title: None,
+                    tag: None,
+                },
+                LayoutCell {
+                    kind: CellKind::Window,
+                    command: "pqiv diagram.png".to_string(),
+                    cwd: None,
+                    column: 1,
+                    rows: None,
+                    title: None,
+                    tag: None,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn toml_round_trips() {
+        let layout = sample();
+        let text = layout.to_toml().unwrap();
+        assert!(text.contains("[[cell]]\nkind = \"shell\"\ncwd = \"~/code/app\"\n"), "{text}");
+        assert_eq!(LayoutFile::from_toml(&text, Path::new("dev.toml")).unwrap(), layout);
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        let path = Path::new("dev.toml");
+        assert!(matches!(LayoutFile::from_toml("version = 2", path), Err(LayoutFileError::Version(_))));
+        assert!(matches!(LayoutFile::from_toml("cell = 1", path), Err(LayoutFileError::Parse { .. })));
+        let layout = LayoutFile::from_toml("version = 1\n[[cell]]\nkind = \"shell\"\n", path).unwrap();
+        assert_eq!(layout.cells[0].column, 0);
+    }
+}
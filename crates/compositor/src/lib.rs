@@ -1,28 +1,75 @@
 //! Column Compositor library
 //!
 //! This library exposes the compositor modules for testing and the main
-//! compositor entry point for the unified binary.
+//! compositor entry point for the unified binary. Programs that want a
+//! termstack column inside their own compositor or shell use `embed`.
 
 // Cross-platform modules (no Smithay backend/renderer dependencies)
+pub mod background;
+pub mod calc;
+pub mod cell_id;
+pub mod command_line;
+pub mod command_templates;
 pub mod compositor_actions;
 pub mod config;
 pub mod coords;
+pub mod frame_stats;
+pub mod home;
 pub mod ipc;
 pub mod layout;
+pub mod logging;
 pub mod mouse_actions;
+pub mod plugins;
+pub mod recording;
 pub mod terminal_keys;
+pub mod text_shaping;
 pub mod title_bar;
 
 // Cross-platform compositor modules (Smithay wayland_frontend + desktop features)
+pub mod absorb;
+pub mod accessibility;
+pub mod archive;
+pub mod bulk_actions;
+pub mod cell_filter;
+pub mod cell_style;
+pub mod cell_title;
+pub mod color_tags;
+pub mod child_reaper;
+pub mod crash;
+pub mod damage;
+pub mod do_not_disturb;
+pub mod embed;
+pub mod ephemeral;
+pub mod event_sources;
 pub mod frame;
+pub mod frame_layout;
+pub mod gui_launch;
+pub mod input_log;
+pub mod keyboard_resize;
+pub mod hit_test;
+pub mod layout_dump;
+pub mod layout_file;
+pub mod now_running;
+pub mod presentation;
+pub mod quick_terminal;
+pub mod quit;
+pub mod refresh;
 pub mod selection;
 pub mod setup;
+pub mod smart_output;
 pub mod spawn_handler;
 pub mod state;
+pub mod system_theme;
 pub mod terminal_manager;
 pub mod terminal_output;
+pub mod typing_echo;
+pub mod undo;
+pub mod watchdog;
+pub mod welcome;
 pub mod window_height;
 pub mod window_lifecycle;
+pub mod window_switcher;
+pub mod xwayland_supervisor;
 
 // Cross-platform input handling (no Smithay backend dependencies)
 pub mod input_handler;
@@ -33,11 +80,15 @@ pub mod backend;
 #[cfg(target_os = "linux")]
 pub mod cursor;
 #[cfg(target_os = "linux")]
+pub mod help_overlay;
+#[cfg(target_os = "linux")]
 pub mod icon;
 #[cfg(target_os = "linux")]
 pub mod input;
 #[cfg(target_os = "linux")]
 pub mod render;
+#[cfg(all(feature = "x11-backend", target_os = "linux"))]
+pub mod texture_pool;
 #[cfg(target_os = "linux")]
 pub mod xwayland_lifecycle;
 
@@ -52,25 +103,7 @@ mod winit_backend;
 pub use winit_backend::run_compositor_winit;
 
 // setup_logging is cross-platform (only uses tracing_subscriber)
-pub fn setup_logging() {
-    use tracing_subscriber::{fmt, prelude::*, EnvFilter};
-
-    let filter = EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| EnvFilter::new("info,smithay=warn"));
-
-    // Respect NO_COLOR environment variable for testing
-    let use_ansi = std::env::var("NO_COLOR").is_err();
-
-    tracing_subscriber::registry()
-        .with(
-            fmt::layer()
-                .with_target(true)
-                .with_line_number(true)
-                .with_ansi(use_ansi),
-        )
-        .with(filter)
-        .init();
-}
+pub use logging::setup_logging;
 
 #[cfg(all(test, target_os = "linux"))]
 mod ipc_test;
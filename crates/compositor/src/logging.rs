@@ -0,0 +1,301 @@
+//! Logging setup
+//!
+//! Logs always go to stderr. Unless `TERMSTACK_LOG_FILE=0` is set, they are
+//! also written to `$XDG_STATE_HOME/termstack/log/termstack.log` so there is
+//! something to attach to bug reports (see `termstack debug-bundle`).
+//!
+//! The log file is rotated by size: when it would grow past `MAX_LOG_SIZE`,
+//! `termstack.log` becomes `termstack.log.1`, `.1` becomes `.2`, and so on,
+//! keeping at most `MAX_LOG_FILES` files in total.
+//!
+//! The most recent lines are additionally kept in memory so a crash report
+//! can include them (see `crash`).
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, TryLockError};
+
+/// Rotate the log file once it would grow past this size (bytes)
+pub const MAX_LOG_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Total number of log files kept, including the active one
+pub const MAX_LOG_FILES: usize = 3;
+
+/// Name of the active log file inside `log_dir()`
+const LOG_FILE_NAME: &str = "termstack.log";
+
+/// Number of recent log lines kept in memory for crash reports
+const RECENT_LOG_LINES: usize = 200;
+
+/// Ring buffer of the most recent formatted log lines
+static RECENT_LOGS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+thread_local! {
+    /// Bytes written on this thread since its last complete log line
+    static PARTIAL_LOG_LINE: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Per-user state directory (`$XDG_STATE_HOME/termstack`)
+pub fn state_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/state")))
+        .map(|p| p.join("termstack"))
+}
+
+/// Directory log files are written to (`$XDG_STATE_HOME/termstack/log`)
+pub fn log_dir() -> Option<PathBuf> {
+    state_dir().map(|p| p.join("log"))
+}
+
+/// The most recent log lines, oldest first
+///
+/// Never blocks: called from the panic hook, which may run while the
+/// panicking thread is itself in the middle of logging.
+pub fn recent_log_lines() -> Vec<String> {
+    match RECENT_LOGS.try_lock() {
+        Ok(logs) => logs.iter().cloned().collect(),
+        Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner().iter().cloned().collect(),
+        Err(TryLockError::WouldBlock) => Vec::new(),
+    }
+}
+
+/// Writer that appends each complete formatted line to `RECENT_LOGS`
+///
+/// A line may arrive in several writes (`writeln!` writes the newline on
+/// its own), so text is collected per thread until its newline.
+struct RecentLogWriter;
+
+impl RecentLogWriter {
+    fn push_line(line: &[u8]) {
+        if let Ok(mut logs) = RECENT_LOGS.lock() {
+            if logs.len() == RECENT_LOG_LINES {
+                logs.pop_front();
+            }
+            logs.push_back(String::from_utf8_lossy(line).trim_end().to_string());
+        }
+    }
+}
+
+impl Write for RecentLogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        PARTIAL_LOG_LINE.with(|partial| {
+            let mut partial = partial.borrow_mut();
+            partial.extend_from_slice(buf);
+            while let Some(end) = partial.iter().position(|&byte| byte == b'\n') {
+                let line: Vec<u8> = partial.drain(..=end).collect();
+                Self::push_line(&line);
+            }
+        });
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Existing log files in `dir`, newest first
+pub fn log_files(dir: &Path) -> Vec<PathBuf> {
+    (0..MAX_LOG_FILES)
+        .map(|i| rotated_path(dir, i))
+        .filter(|p| p.exists())
+        .collect()
+}
+
+/// Path of the log file at rotation `index` (0 is the active file)
+fn rotated_path(dir: &Path, index: usize) -> PathBuf {
+    if index == 0 {
+        dir.join(LOG_FILE_NAME)
+    } else {
+        dir.join(format!("{}.{}", LOG_FILE_NAME, index))
+    }
+}
+
+/// Log file writer with size-based rotation
+pub struct RotatingLogFile {
+    dir: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+}
+
+impl RotatingLogFile {
+    /// Open (or create) the active log file in `dir`, appending to it
+    pub fn open(dir: &Path, max_size: u64) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let file = Self::open_active(dir)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            file,
+            size,
+            max_size,
+        })
+    }
+
+    fn open_active(dir: &Path) -> io::Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(rotated_path(dir, 0))
+    }
+
+    /// Shift every log file up one index and start a fresh active file
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        // The oldest file falls off the end
+        let _ = fs::remove_file(rotated_path(&self.dir, MAX_LOG_FILES - 1));
+        for index in (0..MAX_LOG_FILES - 1).rev() {
+            let from = rotated_path(&self.dir, index);
+            if from.exists() {
+                fs::rename(&from, rotated_path(&self.dir, index + 1))?;
+            }
+        }
+        self.file = Self::open_active(&self.dir)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingLogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Never rotate an empty file, or a single oversized line would rotate forever
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Whether file logging is enabled (disable with `TERMSTACK_LOG_FILE=0`)
+fn file_logging_enabled() -> bool {
+    !matches!(std::env::var("TERMSTACK_LOG_FILE").as_deref(), Ok("0") | Ok("false"))
+}
+
+/// Install the global tracing subscriber
+pub fn setup_logging() {
+    use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new("info,smithay=warn"));
+
+    // Respect NO_COLOR environment variable for testing
+    let use_ansi = std::env::var("NO_COLOR").is_err();
+
+    // Failing to open the log file must never prevent startup
+    let mut file_error = None;
+    let log_file = if file_logging_enabled() {
+        log_dir().and_then(|dir| match RotatingLogFile::open(&dir, MAX_LOG_SIZE) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                file_error = Some((dir, e));
+                None
+            }
+        })
+    } else {
+        None
+    };
+
+    let file_layer = log_file.map(|file| {
+        fmt::layer()
+            .with_target(true)
+            .with_line_number(true)
+            .with_ansi(false)
+            .with_writer(Mutex::new(file))
+    });
+
+    tracing_subscriber::registry()
+        .with(
+            fmt::layer()
+                .with_target(true)
+                .with_line_number(true)
+                .with_ansi(use_ansi),
+        )
+        .with(file_layer)
+        .with(
+            fmt::layer()
+                .with_target(true)
+                .with_ansi(false)
+                .with_writer(|| RecentLogWriter),
+        )
+        .with(filter)
+        .init();
+
+    if let Some((dir, e)) = file_error {
+        tracing::warn!(?dir, error = %e, "failed to open log file, logging to stderr only");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_log_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("termstack-log-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn appends_to_existing_log() {
+        let dir = temp_log_dir("append");
+        {
+            let mut log = RotatingLogFile::open(&dir, 1024).unwrap();
+            log.write_all(b"first\n").unwrap();
+        }
+        let mut log = RotatingLogFile::open(&dir, 1024).unwrap();
+        log.write_all(b"second\n").unwrap();
+
+        let content = fs::read_to_string(rotated_path(&dir, 0)).unwrap();
+        assert_eq!(content, "first\nsecond\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rotates_when_size_exceeded() {
+        let dir = temp_log_dir("rotate");
+        let mut log = RotatingLogFile::open(&dir, 10).unwrap();
+        for line in ["aaaaaaaa\n", "bbbbbbbb\n", "cccccccc\n", "dddddddd\n"] {
+            log.write_all(line.as_bytes()).unwrap();
+        }
+
+        // Only MAX_LOG_FILES are kept; the oldest line has been dropped
+        let files = log_files(&dir);
+        assert_eq!(files.len(), MAX_LOG_FILES);
+        assert_eq!(fs::read_to_string(&files[0]).unwrap(), "dddddddd\n");
+        assert_eq!(fs::read_to_string(&files[1]).unwrap(), "cccccccc\n");
+        assert_eq!(fs::read_to_string(&files[2]).unwrap(), "bbbbbbbb\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recent_logs_are_bounded() {
+        for i in 0..RECENT_LOG_LINES + 5 {
+            writeln!(RecentLogWriter, "line {}", i).unwrap();
+        }
+        let lines = recent_log_lines();
+        assert_eq!(lines.len(), RECENT_LOG_LINES);
+        assert_eq!(lines.last().unwrap(), &format!("line {}", RECENT_LOG_LINES + 4));
+    }
+
+    #[test]
+    fn oversized_write_does_not_rotate_empty_file() {
+        let dir = temp_log_dir("oversized");
+        let mut log = RotatingLogFile::open(&dir, 4).unwrap();
+        log.write_all(b"much longer than four bytes\n").unwrap();
+
+        assert_eq!(log_files(&dir).len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
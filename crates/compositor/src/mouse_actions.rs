@@ -5,10 +5,8 @@
 //! cross-platform implementations that each backend calls with its native
 //! coordinate types converted to `ScreenY`.
 
-use smithay::reexports::wayland_server::Resource;
-
 use crate::coords::ScreenY;
-use crate::state::{FocusedWindow, ResizeDrag, StackWindow, TermStack, MIN_WINDOW_HEIGHT};
+use crate::state::{ResizeDrag, StackWindow, TermStack, MIN_WINDOW_HEIGHT};
 use crate::terminal_manager::TerminalManager;
 
 /// Result of processing a left mouse button press.
@@ -19,6 +17,8 @@ pub enum ClickResult {
     CloseButtonClicked { index: usize },
     /// A window was clicked and focused.
     WindowClicked { index: usize },
+    /// A scaled-down window's content was clicked; it was focused and expanded.
+    WindowExpanded { index: usize },
     /// Click was not on any window.
     NoHit,
 }
@@ -60,50 +60,39 @@ pub fn process_left_click(
     close_button_width: i32,
 ) -> ClickResult {
     // 1. Check for resize handle
-    if let Some(handle_idx) = compositor.find_resize_handle_at(screen_y) {
+    if let Some(handle_idx) = compositor.find_resize_handle_at(screen_x, screen_y) {
         let node = &compositor.layout_nodes[handle_idx];
-        let identity = match &node.cell {
-            StackWindow::Terminal(id) => FocusedWindow::Terminal(*id),
-            StackWindow::External(e) => {
-                FocusedWindow::External(e.surface.wl_surface().id())
-            }
-        };
         compositor.resizing = Some(ResizeDrag {
-            window_index: handle_idx,
-            window_identity: identity,
+            cell: node.id,
             start_screen_y: screen_y.value() as i32,
             start_height: node.height,
             target_height: node.height,
-            last_configure_time: std::time::Instant::now(),
             last_sent_height: None,
+            keyboard: false,
         });
         return ClickResult::ResizeDragStarted;
     }
 
     // 2. Find window at click position
-    let Some(index) = compositor.window_at_screen_y(screen_y) else {
+    let Some(index) = compositor.window_at_screen_y(screen_x, screen_y) else {
         return ClickResult::NoHit;
     };
 
-    // 3. Check for close button in title bar
-    let window_screen_top: i32 = compositor.layout_nodes[..index]
-        .iter()
-        .map(|n| n.height)
-        .sum::<i32>()
-        - compositor.scroll_offset as i32;
+    // 3. Check for close button in title bar (at the right edge of the cell's column)
+    let window_screen_top = compositor.get_window_screen_bounds(index).map_or(0, |(top, _)| top);
 
     let has_ssd = match &compositor.layout_nodes[index].cell {
         StackWindow::Terminal(id) => {
             terminal_manager.get(*id).is_some_and(|t| t.show_title_bar)
         }
-        StackWindow::External(entry) => !entry.uses_csd,
+        StackWindow::External(entry) => entry.has_title_bar(),
     };
 
     if is_click_on_close_button(
-        screen_x,
+        screen_x - compositor.cell_x(index) as f64,
         screen_y.value(),
         window_screen_top,
-        compositor.output_size.w,
+        compositor.column_width(),
         title_bar_height,
         close_button_width,
         has_ssd,
@@ -114,6 +103,14 @@ pub fn process_left_click(
     // 4. Focus the window
     compositor.set_focus_by_index(index);
 
+    // 5. Clicking a scaled-down window's content expands it
+    let scaled_down = matches!(&compositor.layout_nodes[index].cell, StackWindow::External(entry) if entry.display_scale.is_some());
+    let title_bar_h = if has_ssd { title_bar_height } else { 0 };
+    if scaled_down && screen_y.value() as i32 >= window_screen_top + title_bar_h {
+        compositor.expand_window(index);
+        return ClickResult::WindowExpanded { index };
+    }
+
     ClickResult::WindowClicked { index }
 }
 
@@ -125,23 +122,9 @@ pub fn process_left_release(
     compositor: &mut TermStack,
     terminal_manager: &mut TerminalManager,
 ) -> Option<String> {
-    // End resize drag
-    if let Some(drag) = compositor.resizing.take() {
-        if let Some(node) = compositor.layout_nodes.get(drag.window_index) {
-            match &node.cell {
-                StackWindow::Terminal(id) => {
-                    if let Some(term) = terminal_manager.get_mut(*id) {
-                        term.mark_dirty();
-                    }
-                }
-                StackWindow::External(_) => {
-                    let final_target = drag.target_height as u32;
-                    if final_target > 0 {
-                        compositor.request_resize(drag.window_index, final_target);
-                    }
-                }
-            }
-        }
+    // End resize drag (keyboard resizes end with Enter or Esc)
+    if compositor.resizing.as_ref().is_some_and(|drag| !drag.keyboard) {
+        end_resize_drag(compositor, terminal_manager);
         return None;
     }
 
@@ -149,6 +132,32 @@ pub fn process_left_release(
     crate::selection::end_cross_selection(compositor, terminal_manager)
 }
 
+/// Finish the resize drag at its current height: re-render a terminal at its
+/// final size, or send an external window its final configure.
+pub fn end_resize_drag(compositor: &mut TermStack, terminal_manager: &mut TerminalManager) {
+    let Some(drag) = compositor.resizing.take() else {
+        return;
+    };
+    let Some(index) = compositor.cell_index(drag.cell) else {
+        return;
+    };
+    if let Some(node) = compositor.layout_nodes.get(index) {
+        match &node.cell {
+            StackWindow::Terminal(id) => {
+                if let Some(term) = terminal_manager.get_mut(*id) {
+                    term.mark_dirty();
+                }
+            }
+            StackWindow::External(_) => {
+                let final_target = drag.target_height as u32;
+                if final_target > 0 {
+                    compositor.request_resize(index, final_target);
+                }
+            }
+        }
+    }
+}
+
 /// Update resize drag during pointer motion.
 ///
 /// Includes row-snapping for terminals so the resize visually aligns to
@@ -166,25 +175,29 @@ pub fn update_resize_drag(
     let new_height = (drag.start_height + delta).max(MIN_WINDOW_HEIGHT);
     drag.target_height = new_height;
 
-    let window_index = drag.window_index;
-    if let Some(node) = compositor.layout_nodes.get_mut(window_index) {
-        node.height = new_height;
+    let Some(window_index) = compositor.cells.index_of(drag.cell) else {
+        return;
+    };
+    let Some(node) = compositor.layout_nodes.get(window_index) else {
+        return;
+    };
+    let mut height = new_height;
 
-        // Resize terminal with row-snapping
-        if let StackWindow::Terminal(tid) = node.cell {
-            let cell_height = terminal_manager.cell_height;
-            if let Some(term) = terminal_manager.get_mut(tid) {
-                let tb_h = if term.show_title_bar { title_bar_height as u32 } else { 0 };
-                let content_height = (new_height as u32).saturating_sub(tb_h);
-                let rows = (content_height / cell_height).max(1);
-                let snapped_content = rows * cell_height;
-                let snapped_total = (snapped_content + tb_h) as i32;
+    // Resize terminal with row-snapping
+    if let StackWindow::Terminal(tid) = node.cell {
+        let (_, cell_height, _) = terminal_manager.cell_metrics(tid);
+        if let Some(term) = terminal_manager.get_mut(tid) {
+            let tb_h = if term.show_title_bar { title_bar_height as u32 } else { 0 };
+            let content_height = (new_height as u32).saturating_sub(tb_h);
+            let rows = (content_height / cell_height).max(1);
+            let snapped_content = rows * cell_height;
+            let snapped_total = (snapped_content + tb_h) as i32;
 
-                term.resize_to_height(snapped_content, cell_height);
-                node.height = snapped_total;
-            }
+            term.resize_to_height(snapped_content, cell_height);
+            height = snapped_total;
         }
     }
+    compositor.request_window_height(window_index, height);
 }
 
 /// Handle scroll input (compositor column scroll or terminal scrollback).
@@ -197,6 +210,7 @@ pub fn handle_scroll(
     terminal_manager: &mut TerminalManager,
     pixel_delta: f64,
     shift_held: bool,
+    pointer_screen_x: f64,
     pointer_screen_y: ScreenY,
     scrollback_lines: Option<i32>,
 ) {
@@ -206,7 +220,7 @@ pub fn handle_scroll(
         if lines == 0 {
             return;
         }
-        if let Some(index) = compositor.window_at_screen_y(pointer_screen_y) {
+        if let Some(index) = compositor.window_at_screen_y(pointer_screen_x, pointer_screen_y) {
             if let StackWindow::Terminal(tid) = compositor.layout_nodes[index].cell {
                 if let Some(term) = terminal_manager.get_mut(tid) {
                     term.terminal.scroll_display(lines);
@@ -219,6 +233,8 @@ pub fn handle_scroll(
         if pixel_delta == 0.0 {
             return;
         }
+        // The wheel scrolls the column under the pointer
+        compositor.activate_column(compositor.column_at_x(pointer_screen_x));
         compositor.pending_scroll_delta += pixel_delta;
 
         // Clamp so scroll debt doesn't accumulate at boundaries
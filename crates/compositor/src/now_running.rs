@@ -0,0 +1,289 @@
+//! "Now running" status of the focused cell
+//!
+//! Status bars want to show what the focused cell is doing ("cargo test
+//! (2m13s)") without polling `termstack query-windows`. A client sends a
+//! `status` IPC request and gets the focused cell's `NowRunning` back as one
+//! JSON line (`null` when nothing is focused). With `watch` set the connection
+//! stays open and a new line is written whenever focus moves or the focused
+//! command exits.
+//!
+//! The runtime itself is not streamed: lines carry start and finish times and
+//! clients tick the clock themselves, as `termstack status --watch` does.
+
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::state::{StackWindow, TermStack};
+use crate::terminal_manager::{ManagedTerminal, TerminalManager};
+
+/// What the focused cell is running
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NowRunning {
+    /// Command line (the shell name for shell terminals)
+    pub command: String,
+    /// Working directory the command started in, if known
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Start time in seconds since the Unix epoch (unknown for external windows)
+    #[serde(default)]
+    pub started_at: Option<u64>,
+    /// Exit time in seconds since the Unix epoch, `None` while running
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+    /// Exit code, once the command has exited normally
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    /// Whether the cell is an external (Wayland client) window
+    #[serde(default)]
+    pub is_external: bool,
+    /// Whether do-not-disturb is on (see `do_not_disturb`)
+    #[serde(default)]
+    pub do_not_disturb: bool,
+    /// Average progress of all running cells that report one (OSC 9;4)
+    #[serde(default)]
+    pub progress: Option<u8>,
+}
+
+impl NowRunning {
+    /// Status of a terminal cell
+    pub fn from_terminal(terminal: &ManagedTerminal) -> Self {
+        Self {
+            command: terminal.command.clone(),
+            cwd: terminal.cwd.as_ref().map(|cwd| cwd.display().to_string()),
+            started_at: Some(unix_secs(terminal.started_at)),
+            finished_at: terminal.finished_at.map(unix_secs),
+            exit_code: terminal.exit_status().and_then(|status| status.code()),
+            is_external: false,
+            do_not_disturb: false,
+            progress: None,
+        }
+    }
+
+    /// Status of an external window
+    pub fn external(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            cwd: None,
+            started_at: None,
+            finished_at: None,
+            exit_code: None,
+            is_external: true,
+            do_not_disturb: false,
+            progress: None,
+        }
+    }
+
+    /// How long the command has been (or was) running
+    pub fn runtime(&self, now: SystemTime) -> Option<Duration> {
+        let end = self.finished_at.unwrap_or_else(|| unix_secs(now));
+        self.started_at.map(|start| Duration::from_secs(end.saturating_sub(start)))
+    }
+
+    /// One-line summary for status bars, e.g. `cargo test (2m13s)`, prefixed
+    /// with `[DND]` while do-not-disturb is on and with the progress of the
+    /// running cells (`[45%]`) while they report one
+    pub fn summary(&self, now: SystemTime) -> String {
+        let mut prefix = if self.do_not_disturb { "[DND] " } else { "" }.to_string();
+        if let Some(percent) = self.progress {
+            prefix.push_str(&format!("[{}%] ", percent));
+        }
+        let Some(runtime) = self.runtime(now).map(format_runtime) else {
+            return format!("{}{}", prefix, self.command);
+        };
+        match (self.finished_at, self.exit_code) {
+            (None, _) => format!("{}{} ({})", prefix, self.command, runtime),
+            (Some(_), Some(0)) => format!("{}{} (done in {})", prefix, self.command, runtime),
+            (Some(_), Some(code)) => format!("{}{} (exit {} after {})", prefix, self.command, code, runtime),
+            (Some(_), None) => format!("{}{} (killed after {})", prefix, self.command, runtime),
+        }
+    }
+}
+
+/// Compact duration: `13s`, `2m13s`, `1h02m`
+pub fn format_runtime(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// Average progress of the running terminals that report how far along they
+/// are, `None` when none does
+pub fn aggregate_progress(terminal_manager: &TerminalManager) -> Option<u8> {
+    let percents: Vec<u32> = terminal_manager
+        .iter()
+        .filter(|(_, terminal)| !terminal.has_exited())
+        .filter_map(|(_, terminal)| terminal.terminal.progress()?.percent())
+        .map(u32::from)
+        .collect();
+    if percents.is_empty() {
+        return None;
+    }
+    Some((percents.iter().sum::<u32>() / percents.len() as u32) as u8)
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+#[derive(Debug)]
+struct Listener {
+    stream: UnixStream,
+    /// Keep the connection open for updates
+    watch: bool,
+}
+
+/// IPC clients waiting for the focused cell's status (or another JSON
+/// value that changes from frame to frame, see `accessibility`)
+#[derive(Debug, Default)]
+pub struct StatusFeed<T = Option<NowRunning>> {
+    /// Watchers that already have the current status
+    watchers: Vec<Listener>,
+    /// Clients that connected since the last publish
+    pending: Vec<Listener>,
+    last: T,
+}
+
+impl<T: Serialize + PartialEq> StatusFeed<T> {
+    /// Answer `stream` on the next frame, and keep updating it if `watch`
+    pub fn add(&mut self, stream: UnixStream, watch: bool) {
+        // A client that stops reading is dropped rather than stalling the frame
+        if let Err(e) = stream.set_nonblocking(true) {
+            tracing::warn!(error = ?e, "failed to make status stream non-blocking");
+            return;
+        }
+        self.pending.push(Listener { stream, watch });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watchers.is_empty() && self.pending.is_empty()
+    }
+
+    /// Send `status` to new clients, and to watchers if it changed
+    pub fn publish(&mut self, status: T) {
+        let changed = status != self.last;
+        self.last = status;
+
+        let mut listeners = std::mem::take(&mut self.pending);
+        if changed {
+            listeners.append(&mut self.watchers);
+        }
+        if listeners.is_empty() {
+            return;
+        }
+
+        let line = match serde_json::to_string(&self.last) {
+            Ok(json) => json + "\n",
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to serialize status");
+                return;
+            }
+        };
+        for mut listener in listeners {
+            if let Err(e) = listener.stream.write_all(line.as_bytes()) {
+                tracing::debug!(error = ?e, "dropping status client");
+                continue;
+            }
+            if listener.watch {
+                self.watchers.push(listener);
+            }
+        }
+    }
+}
+
+/// Send the focused cell's status to waiting IPC clients
+pub fn publish(compositor: &mut TermStack, terminal_manager: &TerminalManager) {
+    if compositor.status_feed.is_empty() {
+        return;
+    }
+
+    let status = compositor
+        .focused_index()
+        .and_then(|index| compositor.layout_nodes.get(index))
+        .and_then(|node| match &node.cell {
+            StackWindow::Terminal(id) => terminal_manager.get(*id).map(NowRunning::from_terminal),
+            StackWindow::External(entry) => Some(NowRunning::external(&entry.command)),
+        })
+        .map(|status| NowRunning {
+            do_not_disturb: compositor.do_not_disturb.is_enabled(),
+            progress: aggregate_progress(terminal_manager),
+            ..status
+        });
+    compositor.status_feed.publish(status);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+
+    fn running(started_at: u64) -> NowRunning {
+        NowRunning {
+            command: "cargo test".to_string(),
+            cwd: Some("/src".to_string()),
+            started_at: Some(started_at),
+            finished_at: None,
+            exit_code: None,
+            is_external: false,
+            do_not_disturb: false,
+            progress: None,
+        }
+    }
+
+    #[test]
+    fn runtime_is_formatted_compactly() {
+        assert_eq!(format_runtime(Duration::from_secs(13)), "13s");
+        assert_eq!(format_runtime(Duration::from_secs(133)), "2m13s");
+        assert_eq!(format_runtime(Duration::from_secs(3720)), "1h02m");
+    }
+
+    #[test]
+    fn summary_shows_runtime_and_outcome() {
+        let now = UNIX_EPOCH + Duration::from_secs(1133);
+        let mut status = running(1000);
+        assert_eq!(status.summary(now), "cargo test (2m13s)");
+
+        // A finished command's runtime stops at its exit
+        status.finished_at = Some(1010);
+        status.exit_code = Some(101);
+        assert_eq!(status.summary(now), "cargo test (exit 101 after 10s)");
+        status.exit_code = Some(0);
+        assert_eq!(status.summary(now), "cargo test (done in 10s)");
+
+        assert_eq!(NowRunning::external("firefox").summary(now), "firefox");
+        status.do_not_disturb = true;
+        assert_eq!(status.summary(now), "[DND] cargo test (done in 10s)");
+        status.progress = Some(45);
+        assert_eq!(status.summary(now), "[DND] [45%] cargo test (done in 10s)");
+    }
+
+    #[test]
+    fn watchers_get_changes_and_one_shot_clients_one_line() {
+        let (watch_server, watch_client) = UnixStream::pair().unwrap();
+        let (once_server, once_client) = UnixStream::pair().unwrap();
+        let mut feed = StatusFeed::default();
+        feed.add(watch_server, true);
+        feed.add(once_server, false);
+
+        feed.publish(Some(running(1000)));
+        // Unchanged status isn't sent again
+        feed.publish(Some(running(1000)));
+        feed.publish(None);
+        assert!(!feed.is_empty());
+        drop(feed);
+
+        let lines: Vec<String> = BufReader::new(watch_client).lines().map(Result::unwrap).collect();
+        assert_eq!(lines.len(), 2);
+        let first: Option<NowRunning> = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(first, Some(running(1000)));
+        assert_eq!(lines[1], "null");
+
+        let lines: Vec<String> = BufReader::new(once_client).lines().map(Result::unwrap).collect();
+        assert_eq!(lines.len(), 1);
+    }
+}
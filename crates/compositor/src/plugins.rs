@@ -0,0 +1,729 @@
+//! Lua plugins
+//!
+//! Plugins are Lua 5.4 scripts in `~/.config/termstack/plugins/`, run inside
+//! the compositor. Only the ones named in the `plugins` config list are
+//! loaded (`plugins = ["clock"]` loads `clock.lua`). Each plugin gets its own
+//! Lua state with a `termstack` table:
+//!
+//! - `termstack.on(event, fn)`: call `fn(event)` for every event of that type
+//! - `termstack.bind("Super+M", "mark", fn)`: pressing the key calls
+//!   `fn(cell)` with the focused cell instead of reaching it
+//! - `termstack.set_badge(cell, "build ok")`: shown in the cell's title bar
+//! - `termstack.clear_badge(cell)`
+//! - `termstack.overlay("clock", {lines = {"12:00"}, anchor = "top_left"})`:
+//!   a text panel drawn over the cells (`top_left`, `top_right`,
+//!   `bottom_left`, `bottom_right` or `center`); calling it again replaces it
+//! - `termstack.clear_overlay("clock")`
+//! - `termstack.every(seconds, fn)`: call `fn()` repeatedly
+//! - `termstack.log(text)`
+//!
+//! Events are tables with a `type` field:
+//!
+//! - `{type = "cell_added", cell = 1, terminal = 3}` (`terminal` is nil for windows)
+//! - `{type = "cell_removed", cell = 1}`
+//! - `{type = "focus_changed", cell = 1}`
+//! - `{type = "do_not_disturb", enabled = true}`: notifier plugins should hold
+//!   back while it is on (badges are hidden by the compositor meanwhile)
+//! - `{type = "xwayland_ready", display = ":1"}`: X11 apps can start on
+//!   `display` (again after xwayland-satellite was restarted)
+//!
+//! Cells are identified by `CellId::to_bits()`. A new subscription first gets
+//! `cell_added` / `focus_changed` events for the cells that already exist
+//! (and `xwayland_ready` if X11 is up). A callback that runs longer than
+//! `CALLBACK_LIMIT` is stopped with an error; errors are logged and the
+//! plugin keeps its other callbacks.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use mlua::{Function, HookTriggers, Lua, RegistryKey, Table};
+
+use crate::cell_id::CellId;
+use crate::embed::{CellEvent, CellEventTracker};
+use crate::state::TermStack;
+
+/// Longest a plugin's script or callback may run per call
+const CALLBACK_LIMIT: Duration = Duration::from_millis(100);
+
+/// Lua instructions between checks of `CALLBACK_LIMIT`
+const HOOK_INSTRUCTIONS: u32 = 10_000;
+
+/// Shortest interval `termstack.every` accepts
+const MIN_TIMER_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Event types plugins can subscribe to with `termstack.on`
+const EVENTS: [&str; 5] = ["cell_added", "cell_removed", "focus_changed", "do_not_disturb", "xwayland_ready"];
+
+/// Requests from a plugin's `termstack` calls, applied by the host
+type RequestQueue = Rc<RefCell<Vec<PluginRequest>>>;
+
+/// Request from a plugin
+#[derive(Debug)]
+enum PluginRequest {
+    Subscribe { event: String, handler: RegistryKey },
+    RegisterAction { name: String, key: KeyBinding, handler: RegistryKey },
+    SetBadge { cell: u64, text: String },
+    ClearBadge { cell: u64 },
+    SetOverlay { name: String, overlay: Overlay },
+    ClearOverlay { name: String },
+    Every { interval: Duration, handler: RegistryKey },
+}
+
+/// Event passed to a plugin's `termstack.on` callbacks
+#[derive(Debug, Clone, PartialEq)]
+enum PluginEvent {
+    CellAdded { cell: u64, terminal: Option<u32> },
+    CellRemoved { cell: u64 },
+    FocusChanged { cell: Option<u64> },
+    DoNotDisturb { enabled: bool },
+    XWaylandReady { display: String },
+}
+
+impl PluginEvent {
+    fn from_cell_event(event: &CellEvent) -> Self {
+        match event {
+            CellEvent::Added { cell, terminal } => Self::CellAdded {
+                cell: cell.to_bits(),
+                terminal: terminal.map(|id| id.0),
+            },
+            CellEvent::Removed { cell } => Self::CellRemoved { cell: cell.to_bits() },
+            CellEvent::FocusChanged { cell } => Self::FocusChanged {
+                cell: cell.map(CellId::to_bits),
+            },
+        }
+    }
+
+    /// Name used with `termstack.on`
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::CellAdded { .. } => "cell_added",
+            Self::CellRemoved { .. } => "cell_removed",
+            Self::FocusChanged { .. } => "focus_changed",
+            Self::DoNotDisturb { .. } => "do_not_disturb",
+            Self::XWaylandReady { .. } => "xwayland_ready",
+        }
+    }
+
+    fn to_table<'lua>(&self, lua: &'lua Lua) -> mlua::Result<Table<'lua>> {
+        let table = lua.create_table()?;
+        table.set("type", self.kind())?;
+        match self {
+            Self::CellAdded { cell, terminal } => {
+                table.set("cell", *cell)?;
+                table.set("terminal", *terminal)?;
+            }
+            Self::CellRemoved { cell } => table.set("cell", *cell)?,
+            Self::FocusChanged { cell } => table.set("cell", *cell)?,
+            Self::DoNotDisturb { enabled } => table.set("enabled", *enabled)?,
+            Self::XWaylandReady { display } => table.set("display", display.as_str())?,
+        }
+        Ok(table)
+    }
+}
+
+/// Modifier state of a key press
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyMods {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
+
+/// A key combination like `Super+M` or `Ctrl+Shift+F5`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct KeyBinding {
+    mods: KeyMods,
+    /// Keysym name, compared case-insensitively
+    key: String,
+}
+
+impl KeyBinding {
+    fn parse(spec: &str) -> Option<Self> {
+        let mut mods = KeyMods::default();
+        let mut parts: Vec<&str> = spec.split('+').map(str::trim).collect();
+        let key = parts.pop().filter(|key| !key.is_empty())?;
+        for part in parts {
+            match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => mods.ctrl = true,
+                "shift" => mods.shift = true,
+                "alt" => mods.alt = true,
+                "super" | "logo" | "mod4" => mods.logo = true,
+                _ => return None,
+            }
+        }
+        Some(Self { mods, key: key.to_string() })
+    }
+
+    fn matches(&self, mods: KeyMods, key_name: &str) -> bool {
+        self.mods == mods && self.key.eq_ignore_ascii_case(key_name)
+    }
+}
+
+impl std::fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (held, name) in [
+            (self.mods.ctrl, "Ctrl+"),
+            (self.mods.shift, "Shift+"),
+            (self.mods.alt, "Alt+"),
+            (self.mods.logo, "Super+"),
+        ] {
+            if held {
+                f.write_str(name)?;
+            }
+        }
+        f.write_str(&self.key)
+    }
+}
+
+/// Screen corner (or the center) an overlay is drawn at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OverlayAnchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+
+impl OverlayAnchor {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "top_left" => Some(Self::TopLeft),
+            "top_right" => Some(Self::TopRight),
+            "bottom_left" => Some(Self::BottomLeft),
+            "bottom_right" => Some(Self::BottomRight),
+            "center" => Some(Self::Center),
+            _ => None,
+        }
+    }
+}
+
+/// Text panel a plugin draws over the cells
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overlay {
+    pub anchor: OverlayAnchor,
+    pub lines: Vec<String>,
+}
+
+impl Overlay {
+    fn from_table(spec: &Table) -> mlua::Result<Self> {
+        let anchor = match spec.get::<_, Option<String>>("anchor")? {
+            Some(name) => OverlayAnchor::parse(&name)
+                .ok_or_else(|| mlua::Error::runtime(format!("unknown overlay anchor {name:?}")))?,
+            None => OverlayAnchor::TopRight,
+        };
+        let lines = spec.get::<_, Vec<String>>("lines")?;
+        Ok(Self { anchor, lines })
+    }
+}
+
+/// Argument a plugin callback is called with
+enum CallArg<'a> {
+    None,
+    Event(&'a PluginEvent),
+    Cell(Option<u64>),
+}
+
+struct Timer {
+    interval: Duration,
+    next: Instant,
+    handler: RegistryKey,
+}
+
+struct Plugin {
+    name: String,
+    lua: Lua,
+    requests: RequestQueue,
+    /// `termstack.on` callbacks by event type
+    handlers: HashMap<String, Vec<RegistryKey>>,
+    timers: Vec<Timer>,
+}
+
+impl Plugin {
+    /// Run `source` as the plugin called `name`
+    fn load(name: &str, source: &str) -> mlua::Result<Self> {
+        let lua = Lua::new();
+        let requests = RequestQueue::default();
+        lua.globals().set("termstack", api(&lua, name, &requests)?)?;
+        let plugin = Self {
+            name: name.to_string(),
+            lua,
+            requests,
+            handlers: HashMap::new(),
+            timers: Vec::new(),
+        };
+        plugin.run(|lua| lua.load(source).set_name(name).exec())?;
+        Ok(plugin)
+    }
+
+    /// Run Lua code of this plugin, stopping it after `CALLBACK_LIMIT`
+    fn run<R>(&self, f: impl FnOnce(&Lua) -> mlua::Result<R>) -> mlua::Result<R> {
+        let deadline = Instant::now() + CALLBACK_LIMIT;
+        self.lua.set_hook(
+            HookTriggers::new().every_nth_instruction(HOOK_INSTRUCTIONS),
+            move |_, _| match Instant::now() > deadline {
+                true => Err(mlua::Error::runtime("plugin ran too long")),
+                false => Ok(()),
+            },
+        );
+        let result = f(&self.lua);
+        self.lua.remove_hook();
+        result
+    }
+
+    /// Call a registered callback, logging its errors
+    fn call(&self, handler: &RegistryKey, arg: CallArg) {
+        let result = self.run(|lua| {
+            let function: Function = lua.registry_value(handler)?;
+            match arg {
+                CallArg::None => function.call::<_, ()>(()),
+                CallArg::Event(event) => function.call::<_, ()>(event.to_table(lua)?),
+                CallArg::Cell(cell) => function.call::<_, ()>(cell),
+            }
+        });
+        if let Err(e) = result {
+            tracing::warn!(plugin = %self.name, error = %e, "plugin callback failed");
+        }
+    }
+}
+
+/// The `termstack` table of a plugin's Lua state
+fn api<'lua>(lua: &'lua Lua, name: &str, requests: &RequestQueue) -> mlua::Result<Table<'lua>> {
+    let api = lua.create_table()?;
+
+    let queue = requests.clone();
+    api.set(
+        "on",
+        lua.create_function(move |lua, (event, handler): (String, Function)| {
+            if !EVENTS.contains(&event.as_str()) {
+                return Err(mlua::Error::runtime(format!("unknown event {event:?}")));
+            }
+            let handler = lua.create_registry_value(handler)?;
+            queue.borrow_mut().push(PluginRequest::Subscribe { event, handler });
+            Ok(())
+        })?,
+    )?;
+
+    let queue = requests.clone();
+    api.set(
+        "bind",
+        lua.create_function(move |lua, (key, name, handler): (String, String, Function)| {
+            let key = KeyBinding::parse(&key)
+                .ok_or_else(|| mlua::Error::runtime(format!("invalid key {key:?}")))?;
+            let handler = lua.create_registry_value(handler)?;
+            queue.borrow_mut().push(PluginRequest::RegisterAction { name, key, handler });
+            Ok(())
+        })?,
+    )?;
+
+    let queue = requests.clone();
+    api.set(
+        "set_badge",
+        lua.create_function(move |_, (cell, text): (u64, String)| {
+            queue.borrow_mut().push(PluginRequest::SetBadge { cell, text });
+            Ok(())
+        })?,
+    )?;
+
+    let queue = requests.clone();
+    api.set(
+        "clear_badge",
+        lua.create_function(move |_, cell: u64| {
+            queue.borrow_mut().push(PluginRequest::ClearBadge { cell });
+            Ok(())
+        })?,
+    )?;
+
+    let queue = requests.clone();
+    api.set(
+        "overlay",
+        lua.create_function(move |_, (name, spec): (String, Table)| {
+            let overlay = Overlay::from_table(&spec)?;
+            queue.borrow_mut().push(PluginRequest::SetOverlay { name, overlay });
+            Ok(())
+        })?,
+    )?;
+
+    let queue = requests.clone();
+    api.set(
+        "clear_overlay",
+        lua.create_function(move |_, name: String| {
+            queue.borrow_mut().push(PluginRequest::ClearOverlay { name });
+            Ok(())
+        })?,
+    )?;
+
+    let queue = requests.clone();
+    api.set(
+        "every",
+        lua.create_function(move |lua, (seconds, handler): (f64, Function)| {
+            if !(seconds.is_finite() && seconds > 0.0) {
+                return Err(mlua::Error::runtime(format!("invalid interval {seconds}")));
+            }
+            let interval = Duration::from_secs_f64(seconds).max(MIN_TIMER_INTERVAL);
+            let handler = lua.create_registry_value(handler)?;
+            queue.borrow_mut().push(PluginRequest::Every { interval, handler });
+            Ok(())
+        })?,
+    )?;
+
+    let plugin = name.to_string();
+    api.set(
+        "log",
+        lua.create_function(move |_, text: String| {
+            tracing::info!(plugin = %plugin, "{text}");
+            Ok(())
+        })?,
+    )?;
+
+    Ok(api)
+}
+
+struct Action {
+    plugin: usize,
+    name: String,
+    key: KeyBinding,
+    handler: RegistryKey,
+}
+
+/// Loaded plugins and the state they registered
+#[derive(Default)]
+pub struct PluginHost {
+    plugins: Vec<Plugin>,
+    actions: Vec<Action>,
+    /// Actions triggered by key presses since the last `process()`
+    triggered: Vec<usize>,
+    /// Callbacks added since the last `process()`, as (plugin, event type, index)
+    new_subscribers: Vec<(usize, String, usize)>,
+    badges: HashMap<CellId, String>,
+    /// Overlays by plugin and name, in the order they are drawn
+    overlays: BTreeMap<(usize, String), Overlay>,
+    /// Badges are hidden while do-not-disturb is on
+    do_not_disturb: bool,
+    /// `DISPLAY` of the last `xwayland_ready`, for new subscribers
+    x11_display: Option<String>,
+    tracker: CellEventTracker,
+}
+
+impl std::fmt::Debug for PluginHost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PluginHost")
+            .field("plugins", &self.plugins.iter().map(|p| &p.name).collect::<Vec<_>>())
+            .field("badges", &self.badges)
+            .field("overlays", &self.overlays)
+            .finish()
+    }
+}
+
+impl PluginHost {
+    /// Load the plugins named in `enabled` from `dir` (`name` is `dir/name.lua`)
+    pub fn load(&mut self, dir: &Path, enabled: &[String]) {
+        for name in enabled {
+            let path = dir.join(format!("{name}.lua"));
+            let result = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|source| self.load_source(name, &source).map_err(|e| e.to_string()));
+            match result {
+                Ok(()) => tracing::info!(plugin = %name, "loaded plugin"),
+                Err(e) => tracing::warn!(?path, error = %e, "failed to load plugin"),
+            }
+        }
+    }
+
+    /// Load a plugin from its Lua source
+    pub fn load_source(&mut self, name: &str, source: &str) -> mlua::Result<()> {
+        self.plugins.push(Plugin::load(name, source)?);
+        Ok(())
+    }
+
+    /// Whether any plugin is loaded
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    /// Key bindings registered by plugins, as (chord, action name)
+    pub fn bindings(&self) -> impl Iterator<Item = (String, &str)> + '_ {
+        self.actions.iter().map(|action| (action.key.to_string(), action.name.as_str()))
+    }
+
+    /// Handle a key press bound by a plugin, returning whether it was consumed
+    pub fn handle_key(&mut self, mods: KeyMods, key_name: &str) -> bool {
+        let Some(index) = self.actions.iter().position(|action| action.key.matches(mods, key_name)) else {
+            return false;
+        };
+        self.triggered.push(index);
+        true
+    }
+
+    /// Badge a plugin set on a cell (none while do-not-disturb is on)
+    pub fn badge(&self, cell: CellId) -> Option<&str> {
+        if self.do_not_disturb {
+            return None;
+        }
+        self.badges.get(&cell).map(String::as_str)
+    }
+
+    /// Overlays to draw, in order
+    pub fn overlays(&self) -> impl Iterator<Item = &Overlay> {
+        self.overlays.values()
+    }
+
+    /// When the next plugin timer is due
+    pub fn next_timer(&self) -> Option<Instant> {
+        self.plugins.iter().flat_map(|plugin| &plugin.timers).map(|timer| timer.next).min()
+    }
+
+    /// Tell plugins do-not-disturb changed, returning whether titles need a redraw
+    pub fn set_do_not_disturb(&mut self, enabled: bool) -> bool {
+        if self.do_not_disturb == enabled {
+            return false;
+        }
+        self.do_not_disturb = enabled;
+        for plugin in 0..self.plugins.len() {
+            self.send(plugin, &PluginEvent::DoNotDisturb { enabled });
+        }
+        !self.badges.is_empty()
+    }
+
+    /// A cell title with its plugin badge appended
+    pub fn decorate_title<'a>(&self, cell: CellId, title: Cow<'a, str>) -> Cow<'a, str> {
+        match self.badge(cell) {
+            Some(badge) => format!("{title} [{badge}]").into(),
+            None => title,
+        }
+    }
+
+    fn send(&self, plugin: usize, event: &PluginEvent) {
+        let Some(target) = self.plugins.get(plugin) else {
+            return;
+        };
+        for handler in target.handlers.get(event.kind()).into_iter().flatten() {
+            target.call(handler, CallArg::Event(event));
+        }
+    }
+
+    /// Apply the requests plugins made since the last call, returning whether
+    /// anything on screen changed
+    fn apply_requests(&mut self) -> bool {
+        let mut redraw = false;
+        for plugin in 0..self.plugins.len() {
+            let requests = std::mem::take(&mut *self.plugins[plugin].requests.borrow_mut());
+            for request in requests {
+                redraw |= self.handle_request(plugin, request);
+            }
+        }
+        redraw
+    }
+
+    fn handle_request(&mut self, plugin: usize, request: PluginRequest) -> bool {
+        match request {
+            PluginRequest::Subscribe { event, handler } => {
+                let handlers = self.plugins[plugin].handlers.entry(event.clone()).or_default();
+                handlers.push(handler);
+                self.new_subscribers.push((plugin, event, handlers.len() - 1));
+                false
+            }
+            PluginRequest::RegisterAction { name, key, handler } => {
+                self.actions.retain(|action| action.key != key);
+                self.actions.push(Action { plugin, name, key, handler });
+                false
+            }
+            PluginRequest::SetBadge { cell, text } => {
+                self.badges.insert(CellId::from_bits(cell), text);
+                true
+            }
+            PluginRequest::ClearBadge { cell } => self.badges.remove(&CellId::from_bits(cell)).is_some(),
+            PluginRequest::SetOverlay { name, overlay } => {
+                self.overlays.insert((plugin, name), overlay);
+                true
+            }
+            PluginRequest::ClearOverlay { name } => self.overlays.remove(&(plugin, name)).is_some(),
+            PluginRequest::Every { interval, handler } => {
+                let next = Instant::now() + interval;
+                self.plugins[plugin].timers.push(Timer { interval, next, handler });
+                false
+            }
+        }
+    }
+
+    /// Call the callbacks of actions triggered since the last call
+    fn run_actions(&mut self, focused_cell: Option<u64>) {
+        for index in std::mem::take(&mut self.triggered) {
+            let Some(action) = self.actions.get(index) else { continue };
+            self.plugins[action.plugin].call(&action.handler, CallArg::Cell(focused_cell));
+        }
+    }
+
+    /// Call the callbacks of due timers
+    fn run_timers(&mut self, now: Instant) {
+        for plugin in &mut self.plugins {
+            for index in 0..plugin.timers.len() {
+                if plugin.timers[index].next > now {
+                    continue;
+                }
+                let timer = &mut plugin.timers[index];
+                timer.next = now + timer.interval;
+                plugin.call(&plugin.timers[index].handler, CallArg::None);
+            }
+        }
+    }
+}
+
+/// Apply plugin requests, and run plugin callbacks for this frame's events,
+/// key presses and timers
+pub fn process(compositor: &mut TermStack) {
+    if compositor.plugins.is_empty() {
+        return;
+    }
+
+    let focused = compositor.focused_index();
+    let focused_cell = focused.and_then(|index| compositor.layout_nodes.get(index)).map(|node| node.id.to_bits());
+    compositor.plugins.run_actions(focused_cell);
+    compositor.plugins.run_timers(Instant::now());
+    let mut redraw = compositor.plugins.apply_requests();
+
+    // Catch new subscribers up on the cells they missed
+    if !compositor.plugins.new_subscribers.is_empty() {
+        let (known_cells, known_focus) = compositor.plugins.tracker.known();
+        let mut snapshot: Vec<PluginEvent> = known_cells
+            .iter()
+            .map(|&cell| {
+                let terminal = compositor
+                    .cell_index(cell)
+                    .and_then(|index| compositor.layout_nodes[index].cell.terminal_id());
+                PluginEvent::from_cell_event(&CellEvent::Added { cell, terminal })
+            })
+            .collect();
+        snapshot.push(PluginEvent::FocusChanged { cell: known_focus.map(CellId::to_bits) });
+        if compositor.plugins.do_not_disturb {
+            snapshot.push(PluginEvent::DoNotDisturb { enabled: true });
+        }
+        if let Some(display) = compositor.plugins.x11_display.clone() {
+            snapshot.push(PluginEvent::XWaylandReady { display });
+        }
+        let new_subscribers = std::mem::take(&mut compositor.plugins.new_subscribers);
+        for (plugin, kind, index) in new_subscribers {
+            let target = &compositor.plugins.plugins[plugin];
+            let handler = &target.handlers[&kind][index];
+            for event in snapshot.iter().filter(|event| event.kind() == kind) {
+                target.call(handler, CallArg::Event(event));
+            }
+        }
+    }
+
+    let mut events: Vec<PluginEvent> = Vec::new();
+    if let Some(display) = compositor.xwayland.take_ready_event() {
+        compositor.plugins.x11_display = Some(display.clone());
+        events.push(PluginEvent::XWaylandReady { display });
+    }
+    for event in compositor.plugins.tracker.update(&compositor.layout_nodes, focused) {
+        if let CellEvent::Removed { cell } = event {
+            redraw |= compositor.plugins.badges.remove(&cell).is_some();
+        }
+        events.push(PluginEvent::from_cell_event(&event));
+    }
+
+    let host = &mut compositor.plugins;
+    for event in &events {
+        for plugin in 0..host.plugins.len() {
+            host.send(plugin, event);
+        }
+    }
+
+    // Requests made by this frame's callbacks
+    redraw |= host.apply_requests();
+    if redraw {
+        compositor.redraw_requested = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_key_bindings() {
+        let binding = KeyBinding::parse("Ctrl+Shift+b").unwrap();
+        assert!(binding.matches(KeyMods { ctrl: true, shift: true, ..Default::default() }, "B"));
+        assert!(!binding.matches(KeyMods { ctrl: true, ..Default::default() }, "b"));
+
+        let binding = KeyBinding::parse("Super+F5").unwrap();
+        assert!(binding.matches(KeyMods { logo: true, ..Default::default() }, "F5"));
+        assert_eq!(binding.to_string(), "Super+F5");
+
+        assert_eq!(KeyBinding::parse("Hyper+x"), None);
+        assert_eq!(KeyBinding::parse("Ctrl+"), None);
+    }
+
+    #[test]
+    fn events_reach_subscribed_callbacks() {
+        let mut host = PluginHost::default();
+        host.load_source(
+            "badger",
+            r#"
+            termstack.on("cell_added", function(event)
+                termstack.set_badge(event.cell, "new " .. tostring(event.terminal))
+            end)
+            "#,
+        )
+        .unwrap();
+        host.apply_requests();
+
+        host.send(0, &PluginEvent::CellAdded { cell: 7, terminal: Some(3) });
+        host.send(0, &PluginEvent::CellRemoved { cell: 7 });
+        assert!(host.apply_requests());
+        assert_eq!(host.badge(CellId::from_bits(7)), Some("new 3"));
+    }
+
+    #[test]
+    fn overlays_and_actions() {
+        let mut host = PluginHost::default();
+        host.load_source(
+            "clock",
+            r#"
+            termstack.overlay("time", {lines = {"12:00"}, anchor = "bottom_left"})
+            termstack.bind("Super+C", "hide clock", function(cell)
+                termstack.clear_overlay("time")
+            end)
+            "#,
+        )
+        .unwrap();
+        assert!(host.apply_requests());
+        let overlay = Overlay { anchor: OverlayAnchor::BottomLeft, lines: vec!["12:00".into()] };
+        assert_eq!(host.overlays().collect::<Vec<_>>(), [&overlay]);
+        assert_eq!(host.bindings().collect::<Vec<_>>(), [("Super+C".to_string(), "hide clock")]);
+
+        assert!(host.handle_key(KeyMods { logo: true, ..Default::default() }, "c"));
+        host.run_actions(None);
+        assert!(host.apply_requests());
+        assert_eq!(host.overlays().count(), 0);
+    }
+
+    #[test]
+    fn invalid_calls_and_runaway_scripts_fail_to_load() {
+        let mut host = PluginHost::default();
+        assert!(host.load_source("bad_anchor", r#"termstack.overlay("x", {lines = {}, anchor = "up"})"#).is_err());
+        assert!(host.load_source("bad_event", r#"termstack.on("keypress", print)"#).is_err());
+        assert!(host.load_source("spin", "while true do end").is_err());
+        assert!(host.is_empty());
+    }
+
+    #[test]
+    fn only_enabled_plugins_are_loaded() {
+        let dir = std::env::temp_dir().join(format!("termstack-plugins-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["clock", "other"] {
+            std::fs::write(dir.join(format!("{name}.lua")), "termstack.log('hi')").unwrap();
+        }
+
+        let mut host = PluginHost::default();
+        host.load(&dir, &["clock".to_string()]);
+        assert_eq!(host.plugins.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), ["clock"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,168 @@
+//! Frame timing for clients
+//!
+//! Frame callbacks carry the monotonic time of the frame and go out at most
+//! once per refresh of the output, and `wp_presentation` feedback tells
+//! clients (video players, mostly) when their content actually reached the
+//! screen and how long a refresh is, so they can keep audio and video in
+//! sync. The X11 and DRM backends report a frame presented when the X
+//! server or the vblank says so; the other backends (nested Wayland,
+//! headless) count a frame as presented once it is drawn.
+//!
+//! Those two also wait for the last frame to be presented before they draw
+//! the next one, so frames don't queue up behind vblank. With
+//! `allow_tearing` they don't wait, trading dropped frames for latency.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use smithay::desktop::utils::{
+    send_frames_surface_tree, take_presentation_feedback_surface_tree, OutputPresentationFeedback,
+};
+use smithay::desktop::PopupManager;
+use smithay::output::Output;
+use smithay::reexports::wayland_protocols::wp::presentation_time::server::wp_presentation_feedback::Kind;
+use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+use smithay::reexports::wayland_server::DisplayHandle;
+use smithay::utils::{Clock, Monotonic};
+use smithay::wayland::presentation::{PresentationState, Refresh};
+
+use crate::state::TermStack;
+
+/// Longest wait for the X server to report a frame presented, in case the
+/// event never comes
+const PRESENT_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Presentation clock and feedback, kept on `TermStack`
+pub struct Presentation {
+    /// Keeps the `wp_presentation` global alive
+    _state: PresentationState,
+    clock: Clock<Monotonic>,
+    /// Feedback of submitted frames not presented yet, oldest first
+    submitted: VecDeque<OutputPresentationFeedback>,
+    /// Frames presented so far, the `seq` of feedback events
+    sequence: u64,
+    /// When frame callbacks last went out
+    last_callbacks: Option<Instant>,
+    /// When the frame waiting to be presented was submitted
+    in_flight: Option<Instant>,
+    /// The backend calls `presented` when a frame is on screen (X11);
+    /// otherwise frames count as presented once drawn
+    pub reports_presents: bool,
+    /// Draw the next frame without waiting for the last one to be presented
+    pub allow_tearing: bool,
+}
+
+impl Presentation {
+    pub fn new(display: &DisplayHandle) -> Self {
+        let clock = Clock::<Monotonic>::new();
+        Self {
+            _state: PresentationState::new::<TermStack>(display, clock.id() as u32),
+            clock,
+            submitted: VecDeque::new(),
+            sequence: 0,
+            last_callbacks: None,
+            in_flight: None,
+            reports_presents: false,
+            allow_tearing: false,
+        }
+    }
+
+    /// Whether a frame's callbacks are due `frame_time` after the last ones
+    pub fn callbacks_due(&self, now: Instant, frame_time: Duration) -> bool {
+        is_due(self.last_callbacks, now, frame_time)
+    }
+
+    /// When to stop waiting for the frame in flight to be presented, while
+    /// the next frame has to wait for it
+    pub fn present_deadline(&self, now: Instant) -> Option<Instant> {
+        if self.allow_tearing {
+            return None;
+        }
+        self.in_flight.map(|submitted| submitted + PRESENT_TIMEOUT).filter(|deadline| now < *deadline)
+    }
+
+    /// Whether the next frame has to wait for the last one to be presented
+    pub fn waiting_for_present(&self, now: Instant) -> bool {
+        self.present_deadline(now).is_some()
+    }
+
+    /// Mark the frame in flight as presented now
+    pub fn presented(&mut self, refresh: i32, flags: Kind) {
+        self.in_flight = None;
+        let Some(mut feedback) = self.submitted.pop_front() else {
+            return;
+        };
+        self.sequence += 1;
+        let refresh = Refresh::fixed(crate::refresh::frame_time(refresh));
+        feedback.presented(self.clock.now(), refresh, self.sequence, flags);
+    }
+}
+
+/// Whether something done at `last` every `interval` is due again at `now`
+fn is_due(last: Option<Instant>, now: Instant, interval: Duration) -> bool {
+    last.map_or(true, |last| now.saturating_duration_since(last) >= interval)
+}
+
+/// Toplevel surfaces of Wayland clients and their popups, and layer surfaces
+fn client_surfaces(compositor: &TermStack) -> Vec<WlSurface> {
+    let mut surfaces = compositor.layer_surfaces();
+    for toplevel in compositor.xdg_shell_state.toplevel_surfaces() {
+        surfaces.push(toplevel.wl_surface().clone());
+        surfaces.extend(
+            PopupManager::popups_for_surface(toplevel.wl_surface()).map(|(popup, _)| popup.wl_surface().clone()),
+        );
+    }
+    surfaces
+}
+
+/// Collect the presentation feedback of the frame just drawn
+///
+/// Without a backend that reports presents, the frame counts as presented
+/// right away.
+pub fn frame_submitted(compositor: &mut TermStack, output: &Output) {
+    let mut feedback = OutputPresentationFeedback::new(output);
+    for surface in client_surfaces(compositor) {
+        take_presentation_feedback_surface_tree(
+            &surface,
+            &mut feedback,
+            |_, _| Some(output.clone()),
+            |_, _| Kind::empty(),
+        );
+    }
+    let presentation = &mut compositor.presentation;
+    presentation.submitted.push_back(feedback);
+    if presentation.reports_presents {
+        presentation.in_flight = Some(Instant::now());
+    } else {
+        presentation.presented(compositor.refresh, Kind::empty());
+    }
+}
+
+/// Send frame callbacks to all toplevel surfaces and their popups, at most
+/// once per refresh unless `force`d (right after a frame was drawn)
+pub fn send_frame_callbacks(compositor: &mut TermStack, output: &Output, force: bool) {
+    let now = Instant::now();
+    let frame_time = compositor.frame_time();
+    if !force && !compositor.presentation.callbacks_due(now, frame_time) {
+        return;
+    }
+    compositor.presentation.last_callbacks = Some(now);
+    let time = compositor.presentation.clock.now();
+    for surface in client_surfaces(compositor) {
+        send_frames_surface_tree(&surface, output, time, Some(frame_time), |_, _| Some(output.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn callbacks_wait_for_the_next_refresh() {
+        let now = Instant::now();
+        let frame = Duration::from_millis(16);
+        assert!(is_due(None, now, frame));
+        assert!(!is_due(Some(now), now + Duration::from_millis(10), frame));
+        assert!(is_due(Some(now), now + frame, frame));
+    }
+}
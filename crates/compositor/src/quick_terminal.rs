@@ -0,0 +1,246 @@
+//! Drop-down quick terminal
+//!
+//! A scratch shell that slides down from the top of the screen over the stack
+//! when toggled (Ctrl+Shift+` or Super+`), like a quake console. It is not a
+//! cell: it never enters the layout, takes the keyboard while shown and keeps
+//! its content while hidden. If its shell exits, the next toggle starts a new one.
+
+use std::time::{Duration, Instant};
+
+use crate::state::{FocusedWindow, TermStack};
+use crate::terminal_manager::{TerminalId, TerminalManager};
+
+/// Time to slide fully in or out
+pub const SLIDE_DURATION: Duration = Duration::from_millis(150);
+
+/// Height of the line under the quick terminal (pixels)
+pub const BORDER_HEIGHT: i32 = 2;
+
+/// Quick terminal state, kept on `TermStack`
+#[derive(Debug)]
+pub struct QuickTerminal {
+    /// The shell, spawned on first toggle
+    id: Option<TerminalId>,
+    /// Whether it is shown (or sliding in)
+    shown: bool,
+    /// How far it has slid down (0.0 = hidden, 1.0 = fully shown)
+    progress: f32,
+    last_step: Option<Instant>,
+    toggle_requested: bool,
+    /// Fraction of the output height it covers when shown
+    height_fraction: f32,
+    /// Pixels from the top of the screen currently covered, border included
+    covered: i32,
+    /// Appear and disappear at once instead of sliding (`reduce_motion`)
+    reduce_motion: bool,
+}
+
+impl Default for QuickTerminal {
+    fn default() -> Self {
+        Self::new(0.4)
+    }
+}
+
+impl QuickTerminal {
+    pub fn new(height_fraction: f32) -> Self {
+        Self {
+            id: None,
+            shown: false,
+            progress: 0.0,
+            last_step: None,
+            toggle_requested: false,
+            height_fraction,
+            covered: 0,
+            reduce_motion: false,
+        }
+    }
+
+    /// Skip the slide animation
+    pub fn set_reduce_motion(&mut self, reduce_motion: bool) {
+        self.reduce_motion = reduce_motion;
+    }
+
+    /// Show or hide on the next frame
+    pub fn request_toggle(&mut self) {
+        self.toggle_requested = true;
+    }
+
+    /// Whether keyboard input goes to the quick terminal
+    pub fn is_shown(&self) -> bool {
+        self.shown && self.id.is_some()
+    }
+
+    /// Terminal to draw and how far it has slid down, while any of it is on screen
+    pub fn visible(&self) -> Option<(TerminalId, f32)> {
+        self.id.filter(|_| self.progress > 0.0).map(|id| (id, self.progress))
+    }
+
+    /// Whether it is sliding in or out (frames must keep coming)
+    pub fn is_animating(&self) -> bool {
+        if self.shown {
+            self.progress < 1.0
+        } else {
+            self.progress > 0.0
+        }
+    }
+
+    /// Whether the screen row `screen_y` (Y=0 at top) is covered
+    pub fn covers(&self, screen_y: f64) -> bool {
+        screen_y < self.covered as f64
+    }
+
+    /// Content height for an output `output_height` pixels tall, in whole rows
+    fn content_height(&self, output_height: i32, cell_height: u32) -> u32 {
+        let cell_height = cell_height.max(1);
+        let rows = (output_height as f32 * self.height_fraction) as u32 / cell_height;
+        rows.max(1) * cell_height
+    }
+
+    /// Move the slide along to `now`; returns true if it moved
+    fn advance(&mut self, now: Instant) -> bool {
+        let last = self.last_step.replace(now);
+        if !self.is_animating() {
+            return false;
+        }
+        let elapsed = last.map_or(Duration::ZERO, |last| now.duration_since(last));
+        let step = elapsed.as_secs_f32() / SLIDE_DURATION.as_secs_f32();
+        // At least a little each frame, so a stalled clock still finishes
+        let step = if self.reduce_motion { 1.0 } else { step.max(0.05) };
+        self.progress = if self.shown {
+            (self.progress + step).min(1.0)
+        } else {
+            (self.progress - step).max(0.0)
+        };
+        true
+    }
+
+    /// Forget an exited shell so the next toggle starts a new one
+    fn forget(&mut self) {
+        self.id = None;
+        self.shown = false;
+        self.progress = 0.0;
+        self.covered = 0;
+    }
+}
+
+impl TermStack {
+    /// Window that keyboard input is written to: the quick terminal while
+    /// it is shown, otherwise the focused cell
+    pub fn keyboard_input_window(&self) -> Option<FocusedWindow> {
+        match self.quick_terminal.id {
+            Some(id) if self.quick_terminal.is_shown() => Some(FocusedWindow::Terminal(id)),
+            _ => self.focused_window().cloned(),
+        }
+    }
+}
+
+/// Spawn, size and slide the quick terminal (once per frame)
+pub fn process(compositor: &mut TermStack, terminal_manager: &mut TerminalManager) {
+    let output_height = compositor.output_size.h;
+    let quick = &mut compositor.quick_terminal;
+
+    if let Some(id) = quick.id {
+        if terminal_manager.get(id).map_or(true, |t| t.has_exited()) {
+            tracing::info!(id = id.0, "quick terminal shell exited");
+            terminal_manager.remove(id);
+            quick.forget();
+            compositor.redraw_requested = true;
+        }
+    }
+
+    if std::mem::take(&mut quick.toggle_requested) {
+        if quick.id.is_none() {
+            match terminal_manager.spawn() {
+                Ok(id) => {
+                    tracing::info!(id = id.0, "spawned quick terminal");
+                    quick.id = Some(id);
+                }
+                Err(e) => {
+                    tracing::error!(?e, "failed to spawn quick terminal");
+                    return;
+                }
+            }
+        }
+        quick.shown = !quick.shown;
+        quick.last_step = None;
+        tracing::debug!(shown = quick.shown, "quick terminal toggled");
+    }
+
+    let Some(id) = quick.id else { return };
+
+    // Keep the shell at its share of the output (font size changes and
+    // alternate screen programs resize terminals)
+    let cell_height = terminal_manager.cell_height;
+    let height = quick.content_height(output_height, cell_height);
+    if let Some(terminal) = terminal_manager.get_mut(id) {
+        if terminal.height != height {
+            terminal.resize_to_height(height, cell_height);
+            terminal.mark_dirty();
+        }
+    }
+
+    if quick.advance(Instant::now()) {
+        compositor.redraw_requested = true;
+    }
+    quick.covered = ((height as i32 + BORDER_HEIGHT) as f32 * quick.progress).round() as i32;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shown() -> QuickTerminal {
+        let mut quick = QuickTerminal::new(0.5);
+        quick.id = Some(TerminalId(1));
+        quick.shown = true;
+        quick
+    }
+
+    #[test]
+    fn slides_in_and_out() {
+        let mut quick = shown();
+        let start = Instant::now();
+        assert!(quick.advance(start));
+        assert!(quick.is_animating());
+        assert!(quick.advance(start + SLIDE_DURATION));
+        assert_eq!(quick.progress, 1.0);
+        assert!(!quick.is_animating());
+        assert!(!quick.advance(start + SLIDE_DURATION * 2));
+
+        quick.shown = false;
+        assert!(quick.is_animating());
+        assert!(quick.visible().is_some());
+        quick.advance(start + SLIDE_DURATION * 4);
+        assert_eq!(quick.progress, 0.0);
+        assert!(quick.visible().is_none());
+    }
+
+    #[test]
+    fn reduced_motion_skips_the_slide() {
+        let mut quick = shown();
+        quick.set_reduce_motion(true);
+        assert!(quick.advance(Instant::now()));
+        assert_eq!(quick.progress, 1.0);
+        quick.shown = false;
+        quick.advance(Instant::now());
+        assert_eq!(quick.progress, 0.0);
+    }
+
+    #[test]
+    fn height_is_whole_rows() {
+        let quick = QuickTerminal::new(0.4);
+        assert_eq!(quick.content_height(1000, 17), 391);
+        assert_eq!(quick.content_height(10, 17), 17);
+    }
+
+    #[test]
+    fn exited_shell_is_forgotten() {
+        let mut quick = shown();
+        quick.progress = 1.0;
+        quick.covered = 300;
+        assert!(quick.covers(100.0));
+        quick.forget();
+        assert!(!quick.is_shown());
+        assert!(!quick.covers(100.0));
+    }
+}
@@ -0,0 +1,89 @@
+//! Quitting on purpose
+//!
+//! With `respawn_last_shell` set, exiting the last shell opens a fresh one
+//! instead of shutting termstack down, so a stray `exit` or Ctrl+D doesn't
+//! lose the session. Quitting is then explicit: Super+Q (or Ctrl+Shift+Q)
+//! shows a prompt and quits on a second press within `QUIT_CONFIRM_WINDOW`
+//! (Esc cancels), and `termstack quit` asks on the command line.
+
+use std::time::{Duration, Instant};
+
+/// How long the quit prompt waits for the second press
+pub const QUIT_CONFIRM_WINDOW: Duration = Duration::from_secs(3);
+
+/// Pending quit confirmation, kept on `TermStack`
+#[derive(Debug, Default)]
+pub struct QuitConfirm {
+    /// When the prompt from the first press goes away
+    armed_until: Option<Instant>,
+}
+
+impl QuitConfirm {
+    /// Handle a press of the quit binding; true when it confirms an earlier one
+    pub fn press(&mut self, now: Instant) -> bool {
+        if self.is_pending(now) {
+            self.armed_until = None;
+            return true;
+        }
+        self.armed_until = Some(now + QUIT_CONFIRM_WINDOW);
+        false
+    }
+
+    /// Whether the prompt is showing
+    pub fn is_pending(&self, now: Instant) -> bool {
+        self.armed_until.is_some_and(|until| now < until)
+    }
+
+    /// Dismiss the prompt; false if it wasn't showing
+    pub fn cancel(&mut self) -> bool {
+        self.armed_until.take().is_some()
+    }
+
+    /// Drop a prompt whose time is up; true if it just went away
+    pub fn expire(&mut self, now: Instant) -> bool {
+        if self.armed_until.is_some() && !self.is_pending(now) {
+            self.armed_until = None;
+            return true;
+        }
+        false
+    }
+
+    /// Prompt panel text
+    pub fn lines() -> Vec<String> {
+        vec![
+            "Quit termstack?".to_string(),
+            String::new(),
+            "Press Super+Q again to quit, Esc cancels".to_string(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_press_within_the_window_confirms() {
+        let now = Instant::now();
+        let mut quit = QuitConfirm::default();
+        assert!(!quit.press(now));
+        assert!(quit.is_pending(now));
+        assert!(quit.press(now + Duration::from_secs(1)));
+        assert!(!quit.is_pending(now));
+
+        // Too late: the second press only asks again
+        assert!(!quit.press(now));
+        assert!(quit.expire(now + QUIT_CONFIRM_WINDOW));
+        assert!(!quit.press(now + QUIT_CONFIRM_WINDOW));
+    }
+
+    #[test]
+    fn escape_cancels_the_prompt() {
+        let now = Instant::now();
+        let mut quit = QuitConfirm::default();
+        assert!(!quit.cancel());
+        quit.press(now);
+        assert!(quit.cancel());
+        assert!(!quit.press(now));
+    }
+}
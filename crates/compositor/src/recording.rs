@@ -0,0 +1,223 @@
+//! Session recording and replay
+//!
+//! With `record_sessions` set, every command cell's output is written to
+//! `$XDG_STATE_HOME/termstack/recordings/<start time>/<cell>.cast` in the
+//! asciicast v2 format: a JSON header line followed by one
+//! `[seconds, "o", data]` line per chunk of output.
+//!
+//! `termstack replay [DIR]` reads a session directory back, recreates its
+//! cells in the order they were started and plays each recording with
+//! `termstack play`, optionally sped up with `--speed`.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Errors reading a recording
+#[derive(Debug, Error)]
+pub enum RecordingError {
+    #[error("failed to read {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("{path}:{line}: {source}")]
+    Parse { path: PathBuf, line: usize, source: serde_json::Error },
+
+    #[error("{0}: not an asciicast v2 recording")]
+    Version(PathBuf),
+}
+
+/// First line of a recording
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CastHeader {
+    pub version: u32,
+    pub width: u16,
+    pub height: u16,
+    /// Start of the recording (seconds since the Unix epoch)
+    pub timestamp: u64,
+    /// Title bar text of the cell
+    #[serde(default)]
+    pub title: String,
+    /// Command the cell ran
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub command: String,
+}
+
+/// One chunk of recorded output
+#[derive(Debug, Clone, PartialEq)]
+pub struct CastEvent {
+    /// Seconds since the start of the recording
+    pub time: f64,
+    pub data: String,
+}
+
+/// Appends a cell's output to its recording
+#[derive(Debug)]
+pub struct CastWriter {
+    file: BufWriter<File>,
+    started: Instant,
+    /// Tail of an UTF-8 sequence split across reads
+    partial: Vec<u8>,
+}
+
+impl CastWriter {
+    /// Create the recording at `path` and write its header
+    pub fn create(path: &Path, header: &CastHeader) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut file, header)?;
+        file.write_all(b"\n")?;
+        file.flush()?;
+        Ok(Self { file, started: Instant::now(), partial: Vec::new() })
+    }
+
+    /// Record a chunk of PTY output
+    pub fn write_output(&mut self, data: &[u8]) -> io::Result<()> {
+        self.partial.extend_from_slice(data);
+        // Keep an incomplete trailing sequence for the next chunk
+        let complete = match std::str::from_utf8(&self.partial) {
+            Ok(_) => self.partial.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.partial.len(),
+        };
+        if complete == 0 {
+            return Ok(());
+        }
+        let text = String::from_utf8_lossy(&self.partial[..complete]).into_owned();
+        self.partial.drain(..complete);
+
+        let time = self.started.elapsed().as_secs_f64();
+        serde_json::to_writer(&mut self.file, &(time, "o", text))?;
+        self.file.write_all(b"\n")?;
+        self.file.flush()
+    }
+}
+
+/// Seconds since the Unix epoch
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Directory all sessions are recorded to
+pub fn recordings_dir() -> Option<PathBuf> {
+    crate::logging::state_dir().map(|p| p.join("recordings"))
+}
+
+/// Create the directory for a new session's recordings
+pub fn create_session_dir() -> io::Result<PathBuf> {
+    let base = recordings_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no state directory"))?;
+    let dir = base.join(unix_time().to_string());
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Most recently started recorded session
+pub fn latest_session() -> Option<PathBuf> {
+    std::fs::read_dir(recordings_dir()?)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .max_by_key(|path| path.file_name().and_then(|n| n.to_str()).and_then(|n| n.parse::<u64>().ok()))
+}
+
+/// New recording for a cell, with the current time as its start
+pub fn header(width: u16, height: u16, title: &str, command: &str) -> CastHeader {
+    CastHeader {
+        version: 2,
+        width,
+        height,
+        timestamp: unix_time(),
+        title: title.to_string(),
+        command: command.to_string(),
+    }
+}
+
+/// Read a recording
+pub fn read_cast(path: &Path) -> Result<(CastHeader, Vec<CastEvent>), RecordingError> {
+    let io_err = |source| RecordingError::Io { path: path.to_path_buf(), source };
+    let parse_err = |line, source| RecordingError::Parse { path: path.to_path_buf(), line, source };
+
+    let mut lines = BufReader::new(File::open(path).map_err(io_err)?).lines();
+    let first = lines.next().transpose().map_err(io_err)?.unwrap_or_default();
+    let header: CastHeader = serde_json::from_str(&first).map_err(|e| parse_err(1, e))?;
+    if header.version != 2 {
+        return Err(RecordingError::Version(path.to_path_buf()));
+    }
+
+    let mut events = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let line = line.map_err(io_err)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (time, kind, data): (f64, String, String) = serde_json::from_str(&line).map_err(|e| parse_err(i + 2, e))?;
+        if kind == "o" {
+            events.push(CastEvent { time, data });
+        }
+    }
+    Ok((header, events))
+}
+
+/// Recordings of a session, in the order their cells were started
+pub fn session_casts(dir: &Path) -> Result<Vec<(PathBuf, CastHeader)>, RecordingError> {
+    let entries = std::fs::read_dir(dir).map_err(|source| RecordingError::Io { path: dir.to_path_buf(), source })?;
+    let mut casts = Vec::new();
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.extension().is_some_and(|ext| ext == "cast") {
+            let (header, _) = read_cast(&path)?;
+            casts.push((path, header));
+        }
+    }
+    // File names are cell ids, so ties in the (one second) timestamp keep spawn order
+    casts.sort_by_key(|(path, header)| {
+        let id = path.file_stem().and_then(|s| s.to_str()).and_then(|s| s.parse::<u32>().ok());
+        (header.timestamp, id)
+    });
+    Ok(casts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recordings_round_trip() {
+        let dir = std::env::temp_dir()
+            .join(format!("termstack-recording-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("3.cast");
+        let mut writer = CastWriter::create(&path, &header(80, 24, "> make", "make")).unwrap();
+        writer.write_output(b"building\r\n").unwrap();
+        // "é" split across two reads
+        writer.write_output(b"caf\xc3").unwrap();
+        writer.write_output(b"\xa9 \x1b[32mok\x1b[0m\r\n").unwrap();
+        drop(writer);
+
+        let (header, events) = read_cast(&path).unwrap();
+        assert_eq!(header.title, "> make");
+        assert_eq!(header.command, "make");
+        let data: Vec<&str> = events.iter().map(|e| e.data.as_str()).collect();
+        assert_eq!(data, ["building\r\n", "caf", "\u{e9} \x1b[32mok\x1b[0m\r\n"]);
+        assert!(events.windows(2).all(|w| w[0].time <= w[1].time));
+
+        let casts = session_casts(&dir).unwrap();
+        assert_eq!(casts.len(), 1);
+        assert_eq!(casts[0].0, path);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn other_formats_are_rejected() {
+        let path = std::env::temp_dir()
+            .join(format!("termstack-recording-test-{}.cast", std::process::id()));
+        std::fs::write(&path, "{\"version\": 1, \"width\": 80, \"height\": 24, \"timestamp\": 0}\n").unwrap();
+        assert!(matches!(read_cast(&path), Err(RecordingError::Version(_))));
+        std::fs::write(&path, "not json\n").unwrap();
+        assert!(matches!(read_cast(&path), Err(RecordingError::Parse { line: 1, .. })));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,112 @@
+//! Output refresh rate
+//!
+//! Frames are paced to the refresh rate of the monitor termstack runs on
+//! (RandR on X11, the current monitor on winit, the mode on DRM) instead of
+//! a fixed rate, so 120/144 Hz displays scroll smoothly and 60 Hz ones don't
+//! render frames nobody sees. The same rate is advertised in the output's `Mode` and
+//! throttles selection drags. Rates are in mHz, like `Mode::refresh`.
+
+use std::time::Duration;
+
+use crate::state::TermStack;
+
+/// Rate used when the monitor's rate can't be found out
+pub const DEFAULT_REFRESH: i32 = 60_000;
+
+/// Slowest and fastest rates frames are paced to
+const REFRESH_RANGE: (i32, i32) = (24_000, 500_000);
+
+/// Time between frames at `refresh` mHz
+pub fn frame_time(refresh: i32) -> Duration {
+    let refresh = refresh.clamp(REFRESH_RANGE.0, REFRESH_RANGE.1);
+    Duration::from_nanos(1_000_000_000_000 / refresh as u64)
+}
+
+/// Refresh rate of a video mode in mHz, from its timings
+pub fn mode_refresh(dot_clock: u32, htotal: u16, vtotal: u16, double_scan: bool, interlace: bool) -> Option<i32> {
+    let mut lines = u64::from(vtotal);
+    if double_scan {
+        lines *= 2;
+    }
+    if interlace {
+        lines /= 2;
+    }
+    let pixels = u64::from(htotal) * lines;
+    if pixels == 0 {
+        return None;
+    }
+    i32::try_from(u64::from(dot_clock) * 1000 / pixels).ok().filter(|refresh| *refresh > 0)
+}
+
+/// Refresh rate of the monitor showing `window`, or of the fastest one if
+/// the window isn't on any
+#[cfg(all(feature = "x11-backend", target_os = "linux"))]
+pub fn x11_refresh(connection: &std::sync::Arc<x11rb::rust_connection::RustConnection>, window: u32) -> Option<i32> {
+    use x11rb::protocol::randr::{ConnectionExt as _, ModeFlag};
+    use x11rb::protocol::xproto::ConnectionExt as _;
+
+    let root = connection.get_geometry(window).ok()?.reply().ok()?.root;
+    let position = connection.translate_coordinates(window, root, 0, 0).ok()?.reply().ok()?;
+    let (x, y) = (i32::from(position.dst_x), i32::from(position.dst_y));
+    let resources = connection.randr_get_screen_resources_current(root).ok()?.reply().ok()?;
+
+    let mut fastest = None;
+    for crtc in resources.crtcs {
+        let Some(info) = connection
+            .randr_get_crtc_info(crtc, resources.config_timestamp)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+        else {
+            continue;
+        };
+        let Some(mode) = resources.modes.iter().find(|mode| info.mode != 0 && mode.id == info.mode) else {
+            continue;
+        };
+        let Some(refresh) = mode_refresh(
+            mode.dot_clock,
+            mode.htotal,
+            mode.vtotal,
+            mode.mode_flags.contains(ModeFlag::DOUBLE_SCAN),
+            mode.mode_flags.contains(ModeFlag::INTERLACE),
+        ) else {
+            continue;
+        };
+        let (left, top) = (i32::from(info.x), i32::from(info.y));
+        if (left..left + i32::from(info.width)).contains(&x) && (top..top + i32::from(info.height)).contains(&y) {
+            return Some(refresh);
+        }
+        fastest = fastest.max(Some(refresh));
+    }
+    fastest
+}
+
+impl TermStack {
+    /// Time between frames at the output's refresh rate
+    pub fn frame_time(&self) -> Duration {
+        frame_time(self.refresh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_time_follows_the_refresh_rate() {
+        assert_eq!(frame_time(60_000), Duration::from_nanos(16_666_666));
+        assert_eq!(frame_time(144_000), Duration::from_nanos(6_944_444));
+        // Nonsense rates are clamped
+        assert_eq!(frame_time(0), frame_time(24_000));
+        assert_eq!(frame_time(i32::MAX), Duration::from_millis(2));
+    }
+
+    #[test]
+    fn mode_refresh_from_timings() {
+        // 1920x1080@60 (CEA-861) and 2560x1440@144
+        assert_eq!(mode_refresh(148_500_000, 2200, 1125, false, false), Some(60_000));
+        assert_eq!(mode_refresh(586_586_000, 2720, 1497, false, false), Some(144_059));
+        assert_eq!(mode_refresh(25_175_000, 800, 525, true, false), Some(29_970));
+        assert_eq!(mode_refresh(74_250_000, 2200, 1125, false, true), Some(60_053));
+        assert_eq!(mode_refresh(148_500_000, 0, 1125, false, false), None);
+    }
+}
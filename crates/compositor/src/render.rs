@@ -18,181 +18,325 @@
 //! - Terminal content (uses `terminal_manager/` textures)
 //! - Coordinate transforms (uses `coords.rs` types)
 
-use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::OnceLock;
 
+use rayon::prelude::*;
 use smithay::backend::renderer::element::surface::{WaylandSurfaceRenderElement, WaylandSurfaceTexture, render_elements_from_surface_tree};
 use smithay::backend::renderer::element::{Element, Kind};
 use smithay::backend::renderer::gles::{GlesFrame, GlesRenderer, GlesTexture};
+use smithay::backend::renderer::utils::CommitCounter;
 use smithay::backend::renderer::{Color32F, Frame, ImportMem, Texture};
+use smithay::desktop::layer_map_for_output;
 use smithay::utils::{Physical, Point, Rectangle, Scale, Size, Transform};
-
-use crate::state::{CrossSelection, StackWindow, LayoutNode, WindowPosition};
-use crate::terminal_manager::{TerminalId, TerminalManager};
-use crate::title_bar::{TitleBarRenderer, TITLE_BAR_HEIGHT, TITLE_BAR_PADDING};
-
-/// Cache for title bar textures, keyed by (title, width)
-pub type TitleBarCache = HashMap<(String, u32), GlesTexture>;
+use smithay::wayland::shell::wlr_layer::Layer;
+
+use crate::background::Background;
+use crate::damage::CellFrame;
+use crate::plugins::{OverlayAnchor, PluginHost};
+use crate::quick_terminal::BORDER_HEIGHT;
+use crate::state::{CrossSelection, StackWindow, LayoutNode, TermStack, WindowPosition};
+use crate::terminal_manager::{RasterJob, TerminalId, TerminalManager};
+use crate::title_bar::{
+    title_bar_visible, TitleBarCache, TitleBarCharInfo, TitleBarKey, TitleBarRenderer,
+    TITLE_BAR_HEIGHT, TITLE_BAR_PADDING,
+};
+
+/// Title bar textures (and their character positions) kept across frames
+pub type TitleBarTextureCache = TitleBarCache<(GlesTexture, TitleBarCharInfo)>;
 
 // Re-export from layout.rs for backwards compatibility
 pub use crate::layout::{FOCUS_INDICATOR_WIDTH, calculate_terminal_render_height, heights_changed_significantly};
 
+/// The part of `damage` (output coordinates) that falls on `dest`, relative
+/// to `dest` as smithay's draw calls take it
+pub fn local_damage(dest: Rectangle<i32, Physical>, damage: Rectangle<i32, Physical>) -> Option<Rectangle<i32, Physical>> {
+    if damage.is_empty() {
+        return None;
+    }
+    damage.intersection(dest).map(|overlap| Rectangle::new(overlap.loc - dest.loc, overlap.size))
+}
+
+/// Where a texture drawn at `pos` (scale 1) lands
+fn texture_rect(texture: &GlesTexture, pos: Point<i32, Physical>) -> Rectangle<i32, Physical> {
+    let size = texture.size();
+    Rectangle::new(pos, Size::from((size.w, size.h)))
+}
+
 /// Draw focus indicator on left side of cell
-fn draw_focus_indicator(frame: &mut GlesFrame<'_, '_>, y: i32, height: i32) {
+fn draw_focus_indicator(frame: &mut GlesFrame<'_, '_>, x: i32, y: i32, height: i32, damage: Rectangle<i32, Physical>) {
     let focus_rect = Rectangle::new(
-        (0, y).into(),
+        (x, y).into(),
         (FOCUS_INDICATOR_WIDTH, height).into(),
     );
-    frame.clear(Color32F::new(0.0, 0.8, 0.0, 1.0), &[focus_rect]).ok();
+    frame.clear(Color32F::new(0.0, 0.8, 0.0, 1.0), focus_rect.intersection(damage).as_slice()).ok();
 }
 
 /// Draw running indicator on left side of cell (light blue)
-fn draw_running_indicator(frame: &mut GlesFrame<'_, '_>, y: i32, height: i32) {
+fn draw_running_indicator(frame: &mut GlesFrame<'_, '_>, x: i32, y: i32, height: i32, damage: Rectangle<i32, Physical>) {
     let running_rect = Rectangle::new(
-        (0, y).into(),
+        (x, y).into(),
         (FOCUS_INDICATOR_WIDTH, height).into(),
     );
-    frame.clear(Color32F::new(0.3, 0.6, 1.0, 1.0), &[running_rect]).ok();
+    frame.clear(Color32F::new(0.3, 0.6, 1.0, 1.0), running_rect.intersection(damage).as_slice()).ok();
 }
 
 /// Data needed to render a single cell
 pub enum CellRenderData<'a> {
     Terminal {
         id: TerminalId,
+        /// Left edge of the cell's column
+        x: i32,
         y: i32,
         height: i32,
         title_bar_texture: Option<&'a GlesTexture>,
     },
     External {
+        x: i32,
         y: i32,
         height: i32,
         elements: Vec<WaylandSurfaceRenderElement<GlesRenderer>>,
         title_bar_texture: Option<&'a GlesTexture>,
         uses_csd: bool,
+        /// See `WindowEntry::display_scale`
+        display_scale: Option<f64>,
     },
 }
 
-/// Pre-render all terminal textures so they're ready for the frame
+/// What each cell in `render_data` shows this frame, for damage tracking
+pub fn cell_frames(
+    render_data: &[CellRenderData<'_>],
+    layout_nodes: &[LayoutNode],
+    hidden: &[bool],
+    focused: Option<usize>,
+    terminal_manager: &TerminalManager,
+    scale: Scale<f64>,
+) -> Vec<CellFrame> {
+    render_data
+        .iter()
+        .zip(layout_nodes)
+        .enumerate()
+        .map(|(index, (data, node))| {
+            let mut hasher = DefaultHasher::new();
+            (hidden[index], focused == Some(index), node.tag).hash(&mut hasher);
+            let (y, height) = match data {
+                CellRenderData::Terminal { id, x, y, height, title_bar_texture } => {
+                    x.hash(&mut hasher);
+                    let terminal = terminal_manager.get(*id);
+                    terminal.map(|t| (t.texture_generation(), t.has_exited())).hash(&mut hasher);
+                    title_bar_texture.map(GlesTexture::tex_id).hash(&mut hasher);
+                    (*y, *height)
+                }
+                CellRenderData::External { x, y, height, elements, title_bar_texture, uses_csd, display_scale } => {
+                    x.hash(&mut hasher);
+                    title_bar_texture.map(GlesTexture::tex_id).hash(&mut hasher);
+                    (uses_csd, display_scale.map(f64::to_bits)).hash(&mut hasher);
+                    for element in elements {
+                        element.id().hash(&mut hasher);
+                        element.current_commit().distance(Some(CommitCounter::default())).hash(&mut hasher);
+                        let geo = element.geometry(apply_display_scale(scale, *display_scale));
+                        (geo.loc.x, geo.loc.y, geo.size.w, geo.size.h).hash(&mut hasher);
+                    }
+                    (*y, *height)
+                }
+            };
+            CellFrame { id: node.id, y, height, content: hasher.finish() }
+        })
+        .collect()
+}
+
+/// Output scale adjusted for a window shown scaled down
+pub fn apply_display_scale(scale: Scale<f64>, display_scale: Option<f64>) -> Scale<f64> {
+    match display_scale {
+        Some(factor) => Scale::from((scale.x * factor, scale.y * factor)),
+        None => scale,
+    }
+}
+
+/// Upper bound on rasterization worker threads
+const MAX_RASTER_THREADS: usize = 8;
+
+/// Pre-render textures of terminals near the viewport
+///
+/// Dirty terminals are rasterized in parallel on a pool of worker threads
+/// (pure CPU work), then uploaded to GPU textures on this thread, so several
+/// chatty terminals don't serialize rendering on the main thread. Terminals whose
+/// cell is far off-screen (`near_viewport`, indexed like `layout_nodes`)
+/// stay dirty until they are scrolled closer, and lose their textures if they
+/// stay away long enough (see `TerminalManager::evict_textures`).
+///
+/// With `only` set (typing echo, see `crate::typing_echo`) just that terminal
+/// is rasterized. Returns whether other terminals were left dirty.
 pub fn prerender_terminals(
+    layout_nodes: &[LayoutNode],
+    near_viewport: &[bool],
+    overlay: Option<TerminalId>,
+    only: Option<TerminalId>,
     terminal_manager: &mut TerminalManager,
     renderer: &mut GlesRenderer,
-) {
-    let all_ids = terminal_manager.ids();
-    tracing::debug!(
-        count = all_ids.len(),
-        ids = ?all_ids.iter().map(|id| id.0).collect::<Vec<_>>(),
-        "pre-rendering terminals"
-    );
-    for id in all_ids {
-        if let Some(terminal) = terminal_manager.get_mut(id) {
-            tracing::debug!(
-                id = id.0,
-                dirty = terminal.is_dirty(),
-                has_texture = terminal.get_texture().is_some(),
-                "pre-render check"
-            );
-            terminal.render(renderer);
-        }
+) -> bool {
+    let on_screen: HashSet<TerminalId> = layout_nodes
+        .iter()
+        .zip(near_viewport)
+        .filter_map(|(node, &near)| match node.cell {
+            StackWindow::Terminal(id) if near => Some(id),
+            _ => None,
+        })
+        .chain(overlay)
+        .collect();
+
+    let evicted = terminal_manager.evict_textures(&on_screen, std::time::Instant::now());
+    if evicted > 0 {
+        tracing::debug!(evicted, "released textures of off-screen terminals");
+    }
+
+    let mut skipped = false;
+    let jobs: Vec<RasterJob<'_>> = terminal_manager
+        .iter_mut()
+        .filter(|(id, terminal)| on_screen.contains(id) && terminal.needs_render())
+        .filter(|(id, _)| {
+            let wanted = only.map_or(true, |only| only == **id);
+            skipped |= !wanted;
+            wanted
+        })
+        .map(|(_, terminal)| terminal.raster_job())
+        .collect();
+    tracing::debug!(count = jobs.len(), skipped, "rasterizing terminals");
+    run_raster_jobs(jobs);
+
+    terminal_manager.upload_textures(renderer);
+    skipped
+}
+
+/// Run raster jobs, spreading them over the worker pool when there are several
+fn run_raster_jobs(jobs: Vec<RasterJob<'_>>) {
+    match raster_pool() {
+        Some(pool) if jobs.len() > 1 => pool.install(|| jobs.into_par_iter().for_each(RasterJob::run)),
+        _ => jobs.into_iter().for_each(RasterJob::run),
     }
 }
 
-/// Pre-render title bar textures for all cells with SSD
+/// Worker threads for rasterizing, started on first use and kept for the
+/// life of the compositor (`None` if they couldn't be started)
+fn raster_pool() -> Option<&'static rayon::ThreadPool> {
+    static POOL: OnceLock<Option<rayon::ThreadPool>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get()).clamp(1, MAX_RASTER_THREADS);
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("raster-{i}"))
+            .build()
+            .map_err(|e| tracing::warn!(error = %e, "failed to start raster threads, rasterizing on the main thread"))
+            .ok()
+    })
+    .as_ref()
+}
+
+/// Pre-render title bar textures for all on-screen cells with SSD
 ///
-/// Uses a cache to avoid re-rendering title bars that haven't changed.
-/// Returns references to cached textures.
+/// Title bars are cached across frames by their content (see
+/// `TitleBarCache`), so only new or changed titles are rendered. Cells whose
+/// title bar is scrolled off-screen (by `tops`, the screen top of each cell)
+/// are skipped entirely. Returns references
+/// to the cached textures, indexed like `layout_nodes`.
 /// Also populates the char_info_cache for text selection hit-testing.
 #[allow(clippy::too_many_arguments)]
 pub fn prerender_title_bars<'a>(
     layout_nodes: &[LayoutNode],
+    tops: &[i32],
+    screen_height: i32,
+    focused_index: Option<usize>,
     title_bar_renderer: &mut Option<TitleBarRenderer>,
     terminal_manager: &TerminalManager,
+    plugins: &PluginHost,
     renderer: &mut GlesRenderer,
     width: i32,
-    cache: &'a mut TitleBarCache,
+    cache: &'a mut TitleBarTextureCache,
     char_info_cache: &mut crate::state::TitleBarCharInfoCache,
 ) -> Vec<Option<&'a GlesTexture>> {
+    cache.begin_frame();
+
     // First pass: collect keys and render any missing textures
-    let mut keys: Vec<Option<(String, u32)>> = Vec::new();
+    let mut keys: Vec<Option<TitleBarKey>> = Vec::new();
 
     for (window_idx, node) in layout_nodes.iter().enumerate() {
-        match &node.cell {
-            StackWindow::Terminal(id) => {
-                let show_title_bar = terminal_manager.get(*id)
-                    .map(|t| t.show_title_bar)
-                    .unwrap_or(false);
-                if show_title_bar {
-                    if let Some(ref mut tb_renderer) = title_bar_renderer {
-                        let title = terminal_manager.get(*id)
-                            .map(|t| t.title.as_str())
-                            .unwrap_or("Terminal");
-                        let key = (title.to_string(), width as u32);
-                        // Render if texture not cached, or if char_info is missing
-                        let needs_render = !cache.contains_key(&key)
-                            || !char_info_cache.contains_key(&window_idx);
-                        if needs_render {
-                            let (pixels, tb_width, tb_height, char_info) =
-                                tb_renderer.render_with_char_info(title, width as u32);
-                            if !cache.contains_key(&key) {
-                                if let Ok(tex) = renderer.import_memory(
-                                    &pixels,
-                                    smithay::backend::allocator::Fourcc::Argb8888,
-                                    (tb_width as i32, tb_height as i32).into(),
-                                    false,
-                                ) {
-                                    cache.insert(key.clone(), tex);
-                                }
-                            }
-                            char_info_cache.insert(window_idx, char_info);
-                        }
-                        keys.push(Some(key));
-                    } else {
-                        keys.push(None);
-                    }
-                } else {
-                    keys.push(None);
-                }
-            }
-            StackWindow::External(entry) => {
-                if entry.uses_csd {
-                    keys.push(None);
-                } else if let Some(ref mut tb_renderer) = title_bar_renderer {
-                    let key = (entry.command.clone(), width as u32);
-                    // Render if texture not cached, or if char_info is missing
-                    let needs_render = !cache.contains_key(&key)
-                        || !char_info_cache.contains_key(&window_idx);
-                    if needs_render {
-                        let (pixels, tb_width, tb_height, char_info) =
-                            tb_renderer.render_with_char_info(&entry.command, width as u32);
-                        if !cache.contains_key(&key) {
-                            if let Ok(tex) = renderer.import_memory(
-                                &pixels,
-                                smithay::backend::allocator::Fourcc::Argb8888,
-                                (tb_width as i32, tb_height as i32).into(),
-                                false,
-                            ) {
-                                cache.insert(key.clone(), tex);
-                            }
-                        }
-                        char_info_cache.insert(window_idx, char_info);
-                    }
-                    keys.push(Some(key));
-                } else {
-                    keys.push(None);
-                }
+        let top = tops.get(window_idx).copied().unwrap_or(0);
+
+        // (title, running, progress) for cells that draw a title bar
+        let title_bar = match &node.cell {
+            StackWindow::Terminal(id) => terminal_manager
+                .get(*id)
+                .filter(|t| t.show_title_bar)
+                .map(|t| {
+                    let running = !t.has_exited();
+                    let progress = t.terminal.progress().filter(|_| running);
+                    (t.title_bar_text(), running, progress)
+                }),
+            StackWindow::External(entry) => entry.has_title_bar()
+                .then(|| (entry.title_bar_text(), true, None)),
+        }
+        .map(|(title, running, progress)| (plugins.decorate_title(node.id, title), running, progress));
+
+        let Some(tb_renderer) = title_bar_renderer.as_mut() else {
+            keys.push(None);
+            continue;
+        };
+        let Some((title, running, progress)) = title_bar else {
+            keys.push(None);
+            continue;
+        };
+        if !title_bar_visible(top, TITLE_BAR_HEIGHT as i32, screen_height) {
+            char_info_cache.remove(&window_idx);
+            keys.push(None);
+            continue;
+        }
+
+        let key = TitleBarKey {
+            text: title.into_owned(),
+            width: width as u32,
+            theme: tb_renderer.theme(),
+            focused: focused_index == Some(window_idx),
+            running,
+            progress,
+        };
+        let cached = cache.ensure(&key, || {
+            let (mut pixels, tb_width, tb_height, char_info) =
+                tb_renderer.render_with_char_info(&key.text, key.width);
+            if let Some(progress) = key.progress {
+                tb_renderer.draw_progress(&mut pixels, tb_width, progress);
             }
+            renderer
+                .import_memory(
+                    &pixels,
+                    smithay::backend::allocator::Fourcc::Argb8888,
+                    (tb_width as i32, tb_height as i32).into(),
+                    false,
+                )
+                .map_err(|e| tracing::warn!(error = ?e, "failed to upload title bar texture"))
+                .ok()
+                .map(|tex| (tex, char_info))
+        });
+        if let Some((_, char_info)) = cache.get(&key) {
+            char_info_cache.insert(window_idx, char_info.clone());
         }
+        keys.push(cached.then_some(key));
     }
 
     // Second pass: look up references from cache
     keys.into_iter()
-        .map(|key| key.and_then(|k| cache.get(&k)))
+        .map(|key| key.and_then(|k| cache.get(&k)).map(|(tex, _)| tex))
         .collect()
 }
 
-/// Collect actual heights for all cells and render external window elements
+/// Measure the heights of all cells as drawn and render external window elements
 ///
-/// Returns (heights, external_elements_per_cell)
+/// Returns (measured heights, external_elements_per_cell); the frame's layout
+/// heights come from `FrameLayout::compute` with these.
 pub fn collect_window_data(
     layout_nodes: &[LayoutNode],
+    near_viewport: &[bool],
     terminal_manager: &TerminalManager,
     renderer: &mut GlesRenderer,
     scale: Scale<f64>,
@@ -200,14 +344,15 @@ pub fn collect_window_data(
     let mut heights = Vec::new();
     let mut external_elements = Vec::new();
 
-    for node in layout_nodes.iter() {
+    for (node, &near) in layout_nodes.iter().zip(near_viewport) {
         match &node.cell {
             StackWindow::Terminal(id) => {
                 let (content_height, show_title_bar, is_visible) = terminal_manager.get(*id)
                     .map(|t| {
+                        // A culled terminal's texture may be stale; its size is not
                         let h = if !t.is_visible() {
                             0
-                        } else if let Some(tex) = t.get_texture() {
+                        } else if let Some(tex) = t.get_texture().filter(|_| !t.needs_render()) {
                             tex.size().h
                         } else {
                             t.height as i32
@@ -219,11 +364,18 @@ pub fn collect_window_data(
                 heights.push(height);
                 external_elements.push(Vec::new());
             }
+            StackWindow::External(_) if !near => {
+                // Far off-screen: skip collecting elements, keep the cached height
+                heights.push(node.height);
+                external_elements.push(Vec::new());
+            }
             StackWindow::External(entry) => {
                 // Use render_elements_from_surface_tree directly on the surface
                 // instead of window.render_elements() which includes popups when PopupManager is used.
                 // This ensures popup surfaces don't affect the window's height calculation.
                 let wl_surface = entry.surface.wl_surface();
+                // Tall windows shown scaled down are drawn at a smaller scale
+                let scale = apply_display_scale(scale, entry.display_scale);
                 let elements: Vec<WaylandSurfaceRenderElement<GlesRenderer>> =
                     render_elements_from_surface_tree(
                         renderer,
@@ -250,7 +402,7 @@ pub fn collect_window_data(
 
                 // window_height is from surface elements (content only),
                 // so add title bar for SSD windows.
-                let actual_height = if entry.uses_csd {
+                let actual_height = if !entry.has_title_bar() {
                     window_height
                 } else {
                     window_height + TITLE_BAR_HEIGHT as i32
@@ -265,21 +417,24 @@ pub fn collect_window_data(
     (heights, external_elements)
 }
 
-/// Build render data with computed Y positions for each cell
+/// Build render data with computed positions for each cell
+///
+/// `origins` holds each cell's column x and screen top (see
+/// `TermStack::cell_origins`).
 pub fn build_render_data<'a>(
     layout_nodes: &[LayoutNode],
     heights: &[i32],
+    origins: &[(i32, i32)],
     external_elements: &mut [Vec<WaylandSurfaceRenderElement<GlesRenderer>>],
     title_bar_textures: &[Option<&'a GlesTexture>],
-    scroll_offset: f64,
     screen_height: i32,
     terminal_manager: &TerminalManager,
 ) -> Vec<CellRenderData<'a>> {
     let mut render_data = Vec::new();
-    let mut content_y: i32 = -(scroll_offset as i32);
 
     for (window_idx, node) in layout_nodes.iter().enumerate() {
         let height = heights[window_idx];
+        let (x, content_y) = origins[window_idx];
         let render_y = crate::coords::content_to_render_y(content_y as f64, height as f64, screen_height as f64) as i32;
 
         match &node.cell {
@@ -299,6 +454,7 @@ pub fn build_render_data<'a>(
                 let title_bar_texture = title_bar_textures.get(window_idx).copied().flatten();
                 render_data.push(CellRenderData::Terminal {
                     id: *id,
+                    x,
                     y: render_y,
                     height,
                     title_bar_texture,
@@ -307,11 +463,11 @@ pub fn build_render_data<'a>(
             StackWindow::External(entry) => {
                 let elements = std::mem::take(&mut external_elements[window_idx]);
                 let title_bar_texture = title_bar_textures.get(window_idx).copied().flatten();
-                let uses_csd = entry.uses_csd;
+                let uses_csd = !entry.has_title_bar();
 
                 // Calculate render height using the tested helper function.
                 // This handles both new windows (committed=0) and resize scenarios.
-                let committed_height = entry.state.current_height() as i32;
+                let committed_height = entry.displayed_height(entry.state.current_height()) as i32;
                 let render_height = calculate_external_render_height(committed_height, height);
 
                 // INVARIANT: A window with positive layout height must render with positive height.
@@ -334,69 +490,506 @@ pub fn build_render_data<'a>(
                 };
 
                 render_data.push(CellRenderData::External {
+                    x,
                     y: adjusted_render_y,
                     height: render_height,
                     elements,
                     title_bar_texture,
                     uses_csd,
+                    display_scale: entry.display_scale,
                 });
             }
         }
-
-        content_y += height;
     }
 
     render_data
 }
 
 /// Log frame state for debugging (only when external windows present)
-pub fn log_frame_state(
-    layout_nodes: &[LayoutNode],
-    render_data: &[CellRenderData],
-    terminal_manager: &TerminalManager,
-    scroll_offset: f64,
-    focused_index: Option<usize>,
-    screen_height: i32,
-) {
-    let has_external = layout_nodes.iter().any(|n| matches!(n.cell, StackWindow::External(_)));
+pub fn log_frame_state(compositor: &TermStack, terminal_manager: &TerminalManager) {
+    let has_external = compositor.layout_nodes.iter().any(|n| matches!(n.cell, StackWindow::External(_)));
     if !has_external {
         return;
     }
+    tracing::debug!(layout = %compositor.dump_layout(terminal_manager), "FRAME STATE");
+}
 
-    let window_info: Vec<String> = layout_nodes.iter().enumerate().map(|(i, node)| {
-        match &node.cell {
-            StackWindow::Terminal(id) => {
-                let visible = terminal_manager.is_terminal_visible(*id);
-                format!("[{}]Term({})h={}{}",
-                    i, id.0,
-                    node.height,
-                    if !visible { " HIDDEN" } else { "" })
-            }
-            StackWindow::External(e) => {
-                format!("[{}]Ext h={}", i, e.state.current_height())
+/// Upload lines of text on a plain panel as a texture (frame-time HUD,
+/// window switcher, keybinding help)
+pub fn prerender_hud(
+    lines: &[String],
+    title_bar_renderer: &mut Option<TitleBarRenderer>,
+    renderer: &mut GlesRenderer,
+) -> Option<GlesTexture> {
+    let tb_renderer = title_bar_renderer.as_mut()?;
+    let (pixels, width, height) = tb_renderer.render_panel(lines);
+    renderer
+        .import_memory(
+            &pixels,
+            smithay::backend::allocator::Fourcc::Argb8888,
+            (width as i32, height as i32).into(),
+            false,
+        )
+        .map_err(|e| tracing::warn!(error = ?e, "failed to upload HUD texture"))
+        .ok()
+}
+
+/// Upload the background rendered for an output of `size`
+pub fn prerender_background(
+    background: &Background,
+    size: Size<i32, Physical>,
+    renderer: &mut GlesRenderer,
+) -> Option<GlesTexture> {
+    let pixels = background.render(size.w.max(0) as u32, size.h.max(0) as u32);
+    renderer
+        .import_memory(&pixels, smithay::backend::allocator::Fourcc::Argb8888, (size.w, size.h).into(), false)
+        .map_err(|e| tracing::warn!(error = ?e, "failed to upload background texture"))
+        .ok()
+}
+
+/// Render the background texture over the whole output
+pub fn render_background(
+    frame: &mut GlesFrame<'_, '_>,
+    texture: &GlesTexture,
+    damage: Rectangle<i32, Physical>,
+) {
+    let pos = Point::from((0, 0));
+    frame.render_texture_at(
+        texture,
+        pos,
+        1,
+        1.0,
+        Transform::Flipped180,
+        local_damage(texture_rect(texture, pos), damage).as_slice(),
+        &[],
+        1.0,
+    ).ok();
+}
+
+/// Layer surfaces to draw: each one's top-left corner on screen (Y=0 at top)
+/// and its render elements
+pub type LayerRenderData = Vec<(Point<i32, Physical>, Vec<WaylandSurfaceRenderElement<GlesRenderer>>)>;
+
+/// Collect the render elements of the layer surfaces on `layers` (given
+/// topmost first), in drawing order
+pub fn collect_layer_elements(
+    compositor: &TermStack,
+    layers: &[Layer],
+    renderer: &mut GlesRenderer,
+    scale: Scale<f64>,
+) -> LayerRenderData {
+    let Some(output) = compositor.space.outputs().next() else {
+        return Vec::new();
+    };
+    let map = layer_map_for_output(output);
+    layers
+        .iter()
+        .rev()
+        .flat_map(|&level| map.layers_on(level))
+        .filter_map(|layer| {
+            let geometry = map.layer_geometry(layer)?;
+            let elements = render_elements_from_surface_tree(
+                renderer,
+                layer.wl_surface(),
+                Point::from((0, 0)),
+                scale,
+                1.0,
+                Kind::Unspecified,
+            );
+            Some((geometry.loc.to_physical_precise_round(scale), elements))
+        })
+        .collect()
+}
+
+/// Render layer surfaces collected by `collect_layer_elements()`
+pub fn render_layer_surfaces(
+    frame: &mut GlesFrame<'_, '_>,
+    layers: &LayerRenderData,
+    screen_height: i32,
+    damage: Rectangle<i32, Physical>,
+    scale: Scale<f64>,
+) {
+    for (origin, elements) in layers {
+        for element in elements {
+            let geo = element.geometry(scale);
+            let src = element.src();
+
+            // Y-flip: the element's screen top becomes its render top
+            let dest = Rectangle::new(
+                Point::from((origin.x + geo.loc.x, screen_height - origin.y - geo.loc.y - geo.size.h)),
+                geo.size,
+            );
+
+            // Same counter-flip as external windows (see render_external)
+            match element.texture() {
+                WaylandSurfaceTexture::Texture(texture) => {
+                    frame.render_texture_from_to(
+                        texture,
+                        src,
+                        dest,
+                        local_damage(dest, damage).as_slice(),
+                        &[],
+                        Transform::Flipped180,
+                        1.0,
+                        None,
+                        &[],
+                    ).ok();
+                }
+                WaylandSurfaceTexture::SolidColor(color) => {
+                    frame.draw_solid(dest, local_damage(dest, damage).as_slice(), *color).ok();
+                }
             }
         }
-    }).collect();
+    }
+}
+
+/// Margin between the HUD panel and the screen edges (pixels)
+const HUD_MARGIN: i32 = 8;
 
-    let render_info: Vec<String> = render_data.iter().enumerate().map(|(i, data)| {
-        match data {
-            CellRenderData::Terminal { id, y, height, .. } => {
-                format!("[{}]T{}@y={},h={}", i, id.0, y, height)
+/// Render the frame-time HUD panel in the top-right corner
+pub fn render_hud(
+    frame: &mut GlesFrame<'_, '_>,
+    texture: &GlesTexture,
+    screen_size: Size<i32, Physical>,
+    damage: Rectangle<i32, Physical>,
+) {
+    let size = texture.size();
+    // Render coords have Y=0 at the bottom, so the top edge is screen height
+    let x = screen_size.w - size.w - HUD_MARGIN;
+    let y = screen_size.h - size.h - HUD_MARGIN;
+
+    let pos = Point::from((x.max(0), y.max(0)));
+    frame.render_texture_at(
+        texture,
+        pos,
+        1,
+        1.0,
+        Transform::Flipped180,
+        local_damage(texture_rect(texture, pos), damage).as_slice(),
+        &[],
+        1.0,
+    ).ok();
+}
+
+/// Render a banner at the top of the screen, centered (filter view)
+pub fn render_banner(
+    frame: &mut GlesFrame<'_, '_>,
+    texture: &GlesTexture,
+    screen_size: Size<i32, Physical>,
+    damage: Rectangle<i32, Physical>,
+) {
+    let size = texture.size();
+    let x = (screen_size.w - size.w) / 2;
+    let y = screen_size.h - size.h - HUD_MARGIN;
+
+    let pos = Point::from((x.max(0), y.max(0)));
+    frame.render_texture_at(
+        texture,
+        pos,
+        1,
+        1.0,
+        Transform::Flipped180,
+        local_damage(texture_rect(texture, pos), damage).as_slice(),
+        &[],
+        1.0,
+    ).ok();
+}
+
+/// Render a panel in the middle of the screen (window switcher, keybinding help)
+pub fn render_centered_panel(
+    frame: &mut GlesFrame<'_, '_>,
+    texture: &GlesTexture,
+    screen_size: Size<i32, Physical>,
+    damage: Rectangle<i32, Physical>,
+) {
+    let size = texture.size();
+    let x = (screen_size.w - size.w) / 2;
+    let y = (screen_size.h - size.h) / 2;
+
+    let pos = Point::from((x.max(0), y.max(0)));
+    frame.render_texture_at(
+        texture,
+        pos,
+        1,
+        1.0,
+        Transform::Flipped180,
+        local_damage(texture_rect(texture, pos), damage).as_slice(),
+        &[],
+        1.0,
+    ).ok();
+}
+
+/// What a window switcher thumbnail is drawn from
+pub enum Thumbnail {
+    /// The terminal's texture, cut and scaled when drawn
+    Terminal(TerminalId),
+    /// The window's surface tree, collected at thumbnail scale
+    External(Vec<WaylandSurfaceRenderElement<GlesRenderer>>, Scale<f64>),
+}
+
+/// Collect the window switcher's thumbnails for a screen `screen_width`
+/// wide, each with whether it is the selected one
+pub fn collect_switcher_thumbnails(
+    compositor: &TermStack,
+    renderer: &mut GlesRenderer,
+    scale: Scale<f64>,
+    screen_width: i32,
+) -> Vec<(Thumbnail, bool)> {
+    compositor
+        .window_switcher
+        .thumbnail_cells(screen_width)
+        .into_iter()
+        .filter_map(|(cell, selected)| {
+            let node = compositor.layout_nodes.get(compositor.cell_index(cell)?)?;
+            let thumbnail = match &node.cell {
+                StackWindow::Terminal(id) => Thumbnail::Terminal(*id),
+                StackWindow::External(entry) => {
+                    let size = entry.window.geometry().size.to_f64().to_physical(scale).to_i32_round();
+                    let fit = crate::window_switcher::thumbnail_scale(size.w, size.h);
+                    let scale = Scale::from((scale.x * fit, scale.y * fit));
+                    let elements = render_elements_from_surface_tree(
+                        renderer,
+                        entry.surface.wl_surface(),
+                        Point::from((0i32, 0i32)),
+                        scale,
+                        1.0,
+                        Kind::Unspecified,
+                    );
+                    Thumbnail::External(elements, scale)
+                }
+            };
+            Some((thumbnail, selected))
+        })
+        .collect()
+}
+
+/// Render the window switcher's thumbnails in a row above its panel
+pub fn render_switcher_thumbnails(
+    frame: &mut GlesFrame<'_, '_>,
+    terminal_manager: &TerminalManager,
+    thumbnails: &[(Thumbnail, bool)],
+    panel: &GlesTexture,
+    screen_size: Size<i32, Physical>,
+    damage: Rectangle<i32, Physical>,
+) {
+    use crate::window_switcher::{thumbnail_boxes, thumbnail_rows, thumbnail_scale, THUMBNAIL_BORDER, THUMBNAIL_SIZE};
+
+    let (box_width, box_height) = THUMBNAIL_SIZE;
+    let boxes = thumbnail_boxes(thumbnails.len(), (screen_size.w, screen_size.h), panel.size().h);
+    for ((thumbnail, selected), (x, top)) in thumbnails.iter().zip(boxes) {
+        // Render coordinates count up from the bottom of the screen
+        let box_top = screen_size.h - top;
+        let rect = Rectangle::new(Point::from((x, box_top - box_height)), Size::from((box_width, box_height)));
+        if *selected {
+            let mut frame_rect = rect;
+            frame_rect.loc -= Point::from((THUMBNAIL_BORDER, THUMBNAIL_BORDER));
+            frame_rect.size += Size::from((2 * THUMBNAIL_BORDER, 2 * THUMBNAIL_BORDER));
+            frame.draw_solid(frame_rect, local_damage(frame_rect, damage).as_slice(), Color32F::new(0.0, 0.8, 0.0, 1.0)).ok();
+        }
+        frame.draw_solid(rect, local_damage(rect, damage).as_slice(), Color32F::new(0.1, 0.1, 0.1, 1.0)).ok();
+
+        match thumbnail {
+            Thumbnail::Terminal(id) => {
+                // An evicted texture leaves the box empty
+                let Some(texture) = terminal_manager.get(*id).and_then(|t| t.get_texture()) else { continue };
+                let size = texture.size();
+                let rows = thumbnail_rows(size.w, size.h);
+                let fit = thumbnail_scale(size.w, rows);
+
+                // Keep the bottom rows of the (possibly atlas slot) source
+                let src = texture.src();
+                let kept = src.size.h * rows as f64 / size.h.max(1) as f64;
+                let src = Rectangle::new(
+                    Point::from((src.loc.x, src.loc.y + src.size.h - kept)),
+                    Size::from((src.size.w, kept)),
+                );
+                let width = (size.w as f64 * fit).round() as i32;
+                let height = (rows as f64 * fit).round() as i32;
+                let dest = Rectangle::new(
+                    Point::from((x + (box_width - width) / 2, box_top - height)),
+                    Size::from((width, height)),
+                );
+                Frame::render_texture_from_to(
+                    frame,
+                    texture.texture(),
+                    src,
+                    dest,
+                    local_damage(dest, damage).as_slice(),
+                    &[],
+                    Transform::Flipped180,
+                    1.0,
+                ).ok();
             }
-            CellRenderData::External { y, height, .. } => {
-                format!("[{}]E@y={},h={}", i, y, height)
+            Thumbnail::External(elements, scale) => {
+                let bounds = elements
+                    .iter()
+                    .map(|element| element.geometry(*scale))
+                    .reduce(|a, b| a.merge(b));
+                let Some(bounds) = bounds else { continue };
+                let left = x + (box_width - bounds.size.w) / 2;
+                let bottom = box_top - bounds.size.h;
+                for element in elements {
+                    let geo = element.geometry(*scale);
+                    let loc = geo.loc - bounds.loc;
+                    let dest = Rectangle::new(Point::from((loc.x + left, loc.y + bottom)), geo.size);
+                    match element.texture() {
+                        WaylandSurfaceTexture::Texture(texture) => {
+                            frame.render_texture_from_to(
+                                texture,
+                                element.src(),
+                                dest,
+                                local_damage(dest, damage).as_slice(),
+                                &[],
+                                Transform::Flipped180,
+                                1.0,
+                                None,
+                                &[],
+                            ).ok();
+                        }
+                        WaylandSurfaceTexture::SolidColor(color) => {
+                            frame.draw_solid(dest, local_damage(dest, damage).as_slice(), *color).ok();
+                        }
+                    }
+                }
             }
         }
-    }).collect();
-
-    tracing::debug!(
-        scroll = scroll_offset,
-        focused = ?focused_index,
-        screen_h = screen_height,
-        cells = %window_info.join(" "),
-        render = %render_info.join(" "),
-        "FRAME STATE"
-    );
+    }
+}
+
+/// Render plugin overlay panels at their anchors, stacking panels that share
+/// an anchor away from its edge in the order given
+pub fn render_plugin_overlays(
+    frame: &mut GlesFrame<'_, '_>,
+    panels: &[(OverlayAnchor, GlesTexture)],
+    screen_size: Size<i32, Physical>,
+    damage: Rectangle<i32, Physical>,
+) {
+    let mut stacked: HashMap<OverlayAnchor, i32> = HashMap::new();
+    for (anchor, texture) in panels {
+        let size = texture.size();
+        let offset = stacked.entry(*anchor).or_insert(0);
+        let left = HUD_MARGIN;
+        let right = screen_size.w - size.w - HUD_MARGIN;
+        // Render coords have Y=0 at the bottom, so the top edge is screen height
+        let top = screen_size.h - size.h - HUD_MARGIN - *offset;
+        let bottom = HUD_MARGIN + *offset;
+        let (x, y) = match anchor {
+            OverlayAnchor::TopLeft => (left, top),
+            OverlayAnchor::TopRight => (right, top),
+            OverlayAnchor::BottomLeft => (left, bottom),
+            OverlayAnchor::BottomRight => (right, bottom),
+            OverlayAnchor::Center => ((screen_size.w - size.w) / 2, (screen_size.h - size.h) / 2 - *offset),
+        };
+        *offset += size.h + HUD_MARGIN;
+
+        let pos = Point::from((x.max(0), y.max(0)));
+        frame.render_texture_at(
+            texture,
+            pos,
+            1,
+            1.0,
+            Transform::Flipped180,
+            local_damage(texture_rect(texture, pos), damage).as_slice(),
+            &[],
+            1.0,
+        ).ok();
+    }
+}
+
+/// Arrow pointer for backends without a host cursor (DRM): `#` outline,
+/// `o` fill, hotspot at the top-left corner
+const CURSOR_ARROW: [&str; 17] = [
+    "#          ",
+    "##         ",
+    "#o#        ",
+    "#oo#       ",
+    "#ooo#      ",
+    "#oooo#     ",
+    "#ooooo#    ",
+    "#oooooo#   ",
+    "#ooooooo#  ",
+    "#oooooooo# ",
+    "#ooooo#####",
+    "#oo#oo#    ",
+    "#o# #oo#   ",
+    "##  #oo#   ",
+    "#    #oo#  ",
+    "     #oo#  ",
+    "      ##   ",
+];
+
+/// ARGB8888 pixels of the arrow pointer, with its width and height
+fn cursor_pixels() -> (Vec<u8>, i32, i32) {
+    let width = CURSOR_ARROW[0].len();
+    let mut pixels = Vec::with_capacity(width * CURSOR_ARROW.len() * 4);
+    for row in CURSOR_ARROW {
+        for cell in row.bytes() {
+            // Little-endian ARGB: B, G, R, A
+            pixels.extend_from_slice(match cell {
+                b'#' => &[0, 0, 0, 255],
+                b'o' => &[255, 255, 255, 255],
+                _ => &[0, 0, 0, 0],
+            });
+        }
+    }
+    (pixels, width as i32, CURSOR_ARROW.len() as i32)
+}
+
+/// Upload the arrow pointer drawn when the host shows none
+pub fn prerender_cursor(renderer: &mut GlesRenderer) -> Option<GlesTexture> {
+    let (pixels, width, height) = cursor_pixels();
+    renderer
+        .import_memory(&pixels, smithay::backend::allocator::Fourcc::Argb8888, (width, height).into(), false)
+        .map_err(|e| tracing::warn!(error = ?e, "failed to upload cursor texture"))
+        .ok()
+}
+
+/// Render the arrow pointer with its tip at `pointer` (render coordinates)
+pub fn render_cursor(
+    frame: &mut GlesFrame<'_, '_>,
+    texture: &GlesTexture,
+    pointer: Point<f64, smithay::utils::Logical>,
+    damage: Rectangle<i32, Physical>,
+) {
+    // Render coords have Y=0 at the bottom, so the arrow hangs below its tip
+    let pos = Point::from((pointer.x.round() as i32, pointer.y.round() as i32 - texture.size().h));
+    frame.render_texture_at(
+        texture,
+        pos,
+        1,
+        1.0,
+        Transform::Flipped180,
+        local_damage(texture_rect(texture, pos), damage).as_slice(),
+        &[],
+        1.0,
+    ).ok();
+}
+
+/// Render the quick terminal sliding down from the top edge
+///
+/// `progress` is how far it has slid in (0.0..=1.0).
+pub fn render_quick_terminal(
+    frame: &mut GlesFrame<'_, '_>,
+    terminal_manager: &TerminalManager,
+    id: TerminalId,
+    progress: f32,
+    background: Color32F,
+    screen_size: Size<i32, Physical>,
+    damage: Rectangle<i32, Physical>,
+) {
+    let Some(terminal) = terminal_manager.get(id) else { return };
+    let height = terminal.height as i32;
+    let total = height + BORDER_HEIGHT;
+
+    // Render coords have Y=0 at the bottom: slide down from above the top edge
+    let bottom = screen_size.h - (total as f32 * progress).round() as i32;
+    let border = Rectangle::new((0, bottom).into(), (screen_size.w, BORDER_HEIGHT).into());
+    let content_y = bottom + BORDER_HEIGHT;
+    let content = Rectangle::new((0, content_y).into(), (screen_size.w, height).into());
+    frame.clear(background, &[content]).ok();
+    frame.clear(Color32F::new(0.0, 0.8, 0.0, 1.0), &[border]).ok();
+
+    render_terminal(frame, terminal_manager, id, 0, content_y, height, None, true, true, screen_size, damage);
 }
 
 /// Render a terminal cell
@@ -405,6 +998,7 @@ pub fn render_terminal(
     frame: &mut GlesFrame<'_, '_>,
     terminal_manager: &TerminalManager,
     id: TerminalId,
+    x: i32,
     y: i32,
     height: i32,
     title_bar_texture: Option<&GlesTexture>,
@@ -425,18 +1019,20 @@ pub fn render_terminal(
     }
 
     // Calculate content area (below title bar if present)
-    let title_bar_height = if title_bar_texture.is_some() { TITLE_BAR_HEIGHT as i32 } else { 0 };
+    // From the terminal, not the texture: an off-screen title bar has no texture
+    let title_bar_height = if terminal.show_title_bar { TITLE_BAR_HEIGHT as i32 } else { 0 };
     let content_area_top = y + height - title_bar_height;
 
     // Render title bar if present (even if there's no content texture)
     if let Some(tex) = title_bar_texture {
+        let pos = Point::from((x + FOCUS_INDICATOR_WIDTH, content_area_top));
         frame.render_texture_at(
             tex,
-            Point::from((FOCUS_INDICATOR_WIDTH, content_area_top)),
+            pos,
             1,
             1.0,
             Transform::Flipped180,
-            &[damage],
+            local_damage(texture_rect(tex, pos), damage).as_slice(),
             &[],
             1.0,
         ).ok();
@@ -447,17 +1043,22 @@ pub fn render_terminal(
     if let Some(texture) = terminal.get_texture() {
         // Top-align terminal content within content area
         // (texture may be smaller than cell during resize)
-        let texture_height = texture.size().h;
-        let content_y = content_area_top - texture_height;
+        let texture_size = texture.size();
+        let content_y = content_area_top - texture_size.h;
 
-        frame.render_texture_at(
-            texture,
-            Point::from((FOCUS_INDICATOR_WIDTH, content_y)),
-            1,
-            1.0,
-            Transform::Flipped180,
-            &[damage],
+        // Atlas-backed textures only cover a slot of the shared texture
+        let dest = Rectangle::new(
+            Point::from((x + FOCUS_INDICATOR_WIDTH, content_y)),
+            Size::from((texture_size.w, texture_size.h)),
+        );
+        Frame::render_texture_from_to(
+            frame,
+            texture.texture(),
+            texture.src(),
+            dest,
+            local_damage(dest, damage).as_slice(),
             &[],
+            Transform::Flipped180,
             1.0,
         ).ok();
     }
@@ -465,9 +1066,9 @@ pub fn render_terminal(
     // Draw focus indicator on left side of cell (after content so it's visible)
     // Focus indicator takes precedence over running indicator
     if is_focused {
-        draw_focus_indicator(frame, y, height);
+        draw_focus_indicator(frame, x, y, height, damage);
     } else if is_running {
-        draw_running_indicator(frame, y, height);
+        draw_running_indicator(frame, x, y, height, damage);
     }
 }
 
@@ -475,6 +1076,7 @@ pub fn render_terminal(
 #[allow(clippy::too_many_arguments)]
 pub fn render_external(
     frame: &mut GlesFrame<'_, '_>,
+    x: i32,
     y: i32,
     height: i32,
     elements: Vec<WaylandSurfaceRenderElement<GlesRenderer>>,
@@ -491,13 +1093,14 @@ pub fn render_external(
 
     // Render title bar
     if let Some(tex) = title_bar_texture {
+        let pos = Point::from((x + FOCUS_INDICATOR_WIDTH, title_bar_y));
         frame.render_texture_at(
             tex,
-            Point::from((FOCUS_INDICATOR_WIDTH, title_bar_y)),
+            pos,
             1,
             1.0,
             Transform::Flipped180,
-            &[damage],
+            local_damage(texture_rect(tex, pos), damage).as_slice(),
             &[],
             1.0,
         ).ok();
@@ -515,7 +1118,7 @@ pub fn render_external(
         let dest_y = geo.loc.y + y;
 
         let dest = Rectangle::new(
-            Point::from((geo.loc.x + FOCUS_INDICATOR_WIDTH, dest_y)),
+            Point::from((geo.loc.x + x + FOCUS_INDICATOR_WIDTH, dest_y)),
             geo.size,
         );
 
@@ -526,7 +1129,7 @@ pub fn render_external(
                     texture,
                     src,
                     dest,
-                    &[damage],
+                    local_damage(dest, damage).as_slice(),
                     &[],
                     Transform::Flipped180,
                     1.0,
@@ -536,7 +1139,7 @@ pub fn render_external(
             }
             WaylandSurfaceTexture::SolidColor(color) => {
                 // Solid color surface (e.g., blank or placeholder)
-                frame.draw_solid(dest, &[damage], *color).ok();
+                frame.draw_solid(dest, local_damage(dest, damage).as_slice(), *color).ok();
             }
         }
     }
@@ -545,9 +1148,9 @@ pub fn render_external(
     // Focus indicator takes precedence over running indicator
     // External windows are always running (blue) when not focused
     if is_focused {
-        draw_focus_indicator(frame, y, height);
+        draw_focus_indicator(frame, x, y, height, damage);
     } else {
-        draw_running_indicator(frame, y, height);
+        draw_running_indicator(frame, x, y, height, damage);
     }
 }
 
@@ -575,12 +1178,14 @@ const SELECTION_COLOR: Color32F = Color32F::new(0.2, 0.4, 0.8, 0.5);
 ///
 /// Draws a semi-transparent blue overlay on the selected portion of a title bar.
 /// Uses the title bar's character info to determine pixel boundaries.
+/// `title_bar_x` and `cell_width` are the cell's column span.
 #[allow(clippy::too_many_arguments)]
 pub fn render_title_bar_selection(
     frame: &mut GlesFrame<'_, '_>,
     window_index: usize,
+    title_bar_x: i32,
     title_bar_y: i32,
-    output_width: i32,
+    cell_width: i32,
     cross_selection: Option<&CrossSelection>,
     title_bar_char_info: &std::collections::HashMap<usize, crate::title_bar::TitleBarCharInfo>,
     damage: Rectangle<i32, Physical>,
@@ -615,7 +1220,7 @@ pub fn render_title_bar_selection(
                     let width = info.char_widths.get(end).copied().unwrap_or(8.0);
                     pos + width
                 })
-                .unwrap_or(output_width as f32) as i32 + TITLE_BAR_PADDING as i32;
+                .unwrap_or(cell_width as f32) as i32 + TITLE_BAR_PADDING as i32;
             (start_x, end_x)
         }
         (Some(WindowPosition::TitleBar { char_index: start }), Some(WindowPosition::Content { .. })) => {
@@ -623,14 +1228,14 @@ pub fn render_title_bar_selection(
             let start_x = char_info
                 .and_then(|info| info.char_positions.get(start).copied())
                 .unwrap_or(0.0) as i32 + TITLE_BAR_PADDING as i32;
-            (start_x, output_width - FOCUS_INDICATOR_WIDTH)
+            (start_x, cell_width - FOCUS_INDICATOR_WIDTH)
         }
         (Some(WindowPosition::TitleBar { char_index: start }), None) => {
             // First window in multi-window: selection starts in title bar, goes to end of window
             let start_x = char_info
                 .and_then(|info| info.char_positions.get(start).copied())
                 .unwrap_or(0.0) as i32 + TITLE_BAR_PADDING as i32;
-            (start_x, output_width - FOCUS_INDICATOR_WIDTH)
+            (start_x, cell_width - FOCUS_INDICATOR_WIDTH)
         }
         (Some(WindowPosition::Content { .. }), Some(WindowPosition::Content { .. })) => {
             // Single window: selection entirely in content - title bar NOT included
@@ -654,23 +1259,23 @@ pub fn render_title_bar_selection(
                     let width = info.char_widths.get(end).copied().unwrap_or(8.0);
                     pos + width
                 })
-                .unwrap_or(output_width as f32) as i32 + TITLE_BAR_PADDING as i32;
+                .unwrap_or(cell_width as f32) as i32 + TITLE_BAR_PADDING as i32;
             (TITLE_BAR_PADDING as i32, end_x)
         }
         (None, Some(WindowPosition::Content { .. })) => {
             // Last window: selection from above, ends in content - title bar fully included
             // (selection passes through title bar to reach content)
-            (TITLE_BAR_PADDING as i32, output_width - FOCUS_INDICATOR_WIDTH)
+            (TITLE_BAR_PADDING as i32, cell_width - FOCUS_INDICATOR_WIDTH)
         }
         (None, None) => {
             // Middle window: fully selected including title bar
-            (TITLE_BAR_PADDING as i32, output_width - FOCUS_INDICATOR_WIDTH)
+            (TITLE_BAR_PADDING as i32, cell_width - FOCUS_INDICATOR_WIDTH)
         }
     };
 
     // Clamp to valid range
     let start_x = start_x.max(FOCUS_INDICATOR_WIDTH);
-    let end_x = end_x.min(output_width).max(start_x);
+    let end_x = end_x.min(cell_width).max(start_x);
     let width = end_x - start_x;
 
     if width <= 0 {
@@ -679,11 +1284,11 @@ pub fn render_title_bar_selection(
 
     // Draw selection rectangle
     let selection_rect = Rectangle::new(
-        (start_x, title_bar_y).into(),
+        (title_bar_x + start_x, title_bar_y).into(),
         (width, TITLE_BAR_HEIGHT as i32).into(),
     );
 
-    frame.draw_solid(selection_rect, &[damage], SELECTION_COLOR).ok();
+    frame.draw_solid(selection_rect, local_damage(selection_rect, damage).as_slice(), SELECTION_COLOR).ok();
 }
 
 #[cfg(test)]
@@ -821,4 +1426,14 @@ mod tests {
         let render_width = render_width_for_external(geometry_width);
         assert_eq!(render_width, 1280, "App should render at full width after configure");
     }
+
+    #[test]
+    fn cursor_arrow_rows_are_even() {
+        // Every row of the arrow is a full row of pixels, and the hotspot
+        // (top-left) is opaque
+        let (pixels, width, height) = cursor_pixels();
+        assert!(CURSOR_ARROW.iter().all(|row| row.len() == width as usize));
+        assert_eq!(pixels.len(), (width * height * 4) as usize);
+        assert_eq!(&pixels[..4], &[0, 0, 0, 255]);
+    }
 }
@@ -9,12 +9,47 @@ use std::time::Instant;
 use crate::coords::RenderY;
 use crate::layout::FOCUS_INDICATOR_WIDTH;
 use crate::state::{CrossSelection, StackWindow, TermStack, WindowPosition};
-use crate::terminal_manager::TerminalManager;
+use crate::terminal_manager::{TerminalId, TerminalManager};
 use crate::title_bar::{TITLE_BAR_HEIGHT, TITLE_BAR_PADDING};
 
 /// Maximum number of windows a selection can span
 const MAX_SELECTION_WINDOWS: usize = 50;
 
+/// Where finished selections are copied, and how their text is cleaned up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CopyOptions {
+    /// Put the selection on PRIMARY when the mouse button is released
+    pub primary: bool,
+    /// Also put it on CLIPBOARD
+    pub clipboard: bool,
+    /// Drop trailing spaces and tabs from every selected line
+    pub trim_trailing_whitespace: bool,
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        Self { primary: true, clipboard: false, trim_trailing_whitespace: true }
+    }
+}
+
+impl CopyOptions {
+    pub fn from_config(config: &crate::config::Config) -> Self {
+        Self {
+            primary: config.copy_on_select,
+            clipboard: config.copy_on_select_clipboard,
+            trim_trailing_whitespace: config.trim_selection_whitespace,
+        }
+    }
+
+    /// Selected text as it should be copied
+    pub fn prepare(&self, text: &str) -> String {
+        if !self.trim_trailing_whitespace {
+            return text.to_string();
+        }
+        text.split('\n').map(|line| line.trim_end_matches([' ', '\t'])).collect::<Vec<_>>().join("\n")
+    }
+}
+
 /// Determine which window and what position within it a click landed on
 ///
 /// Returns (window_index, position) where position indicates whether the click
@@ -25,8 +60,10 @@ pub fn position_at_point(
     render_x: f64,
     render_y: RenderY,
 ) -> Option<(usize, WindowPosition)> {
-    let window_index = compositor.window_at(render_y)?;
+    let window_index = compositor.window_at(render_x, render_y)?;
     let node = compositor.layout_nodes.get(window_index)?;
+    // From here on X is relative to the cell's column
+    let render_x = render_x - compositor.cell_x(window_index) as f64;
 
     // Calculate window's render position
     let (window_render_y, window_height) = compositor.get_window_render_position(window_index);
@@ -38,7 +75,7 @@ pub fn position_at_point(
             .get(*id)
             .map(|t| t.show_title_bar)
             .unwrap_or(false),
-        StackWindow::External(entry) => !entry.uses_csd,
+        StackWindow::External(entry) => entry.has_title_bar(),
     };
 
     if has_title_bar {
@@ -79,7 +116,8 @@ pub fn position_at_point(
             let local_x = (render_x - FOCUS_INDICATOR_WIDTH as f64).max(0.0);
 
             let col = (local_x / char_width as f64) as usize;
-            let row = (local_y / char_height as f64) as usize;
+            // Rows count from the first line, not the top of the scrolled viewport
+            let row = managed.terminal.visible_rows().start + (local_y / char_height as f64) as usize;
             let grid_rows = managed.terminal.grid_rows();
 
             tracing::debug!(
@@ -107,42 +145,112 @@ pub fn position_at_point(
     }
 }
 
+/// Clear any existing selections in terminals
+fn clear_terminal_selections(compositor: &TermStack, terminals: &mut TerminalManager) {
+    for node in &compositor.layout_nodes {
+        if let StackWindow::Terminal(id) = &node.cell {
+            if let Some(term) = terminals.get_mut(*id) {
+                if term.terminal.has_selection() {
+                    term.terminal.clear_selection();
+                    term.mark_selection_dirty();
+                }
+            }
+        }
+    }
+}
+
+/// Longest time between the two clicks of a double-click
+const DOUBLE_CLICK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(400);
+
+/// Furthest the pointer may move between the two clicks of a double-click (pixels)
+const DOUBLE_CLICK_DISTANCE: f64 = 4.0;
+
+/// Record a left click, returning true if it completes a double-click
+pub fn register_click(compositor: &mut TermStack, render_x: f64, render_y: RenderY) -> bool {
+    let now = Instant::now();
+    let double = compositor.last_click.is_some_and(|(at, x, y)| {
+        now.duration_since(at) <= DOUBLE_CLICK_INTERVAL
+            && (x - render_x).abs() <= DOUBLE_CLICK_DISTANCE
+            && (y - render_y.value()).abs() <= DOUBLE_CLICK_DISTANCE
+    });
+    // A third click starts a new pair
+    compositor.last_click = if double { None } else { Some((now, render_x, render_y.value())) };
+    double
+}
+
+/// Select the word under the pointer (double-click)
+///
+/// Words are letters, digits and the configured `word_chars`. The selection is
+/// complete at once, so releasing the button copies it like a dragged one.
+/// Returns false if there is no word under the pointer.
+pub fn select_word_at(
+    compositor: &mut TermStack,
+    terminals: &mut TerminalManager,
+    render_x: f64,
+    render_y: RenderY,
+) -> bool {
+    let Some((window_index, position @ WindowPosition::Content { col, row })) =
+        position_at_point(compositor, terminals, render_x, render_y)
+    else {
+        return false;
+    };
+    let Some(StackWindow::Terminal(id)) = compositor.layout_nodes.get(window_index).map(|node| &node.cell) else {
+        return false;
+    };
+    let id = *id;
+
+    clear_terminal_selections(compositor, terminals);
+    let Some(term) = terminals.get_mut(id) else {
+        return false;
+    };
+    if !term.terminal.select_word(col, row, &compositor.word_chars) {
+        return false;
+    }
+    term.mark_selection_dirty();
+
+    let mut selection = CrossSelection::new(window_index, position);
+    selection.active = false;
+    compositor.cross_selection = Some(selection);
+    true
+}
+
 /// Start a cross-window selection at the given render coordinates
+///
+/// `block` starts a rectangular selection instead, if the point is on terminal content.
 pub fn start_cross_selection(
     compositor: &mut TermStack,
     terminals: &mut TerminalManager,
     render_x: f64,
     render_y: RenderY,
+    block: bool,
 ) -> bool {
     let Some((window_index, position)) = position_at_point(compositor, terminals, render_x, render_y) else {
         return false;
     };
 
-    // Clear any existing selections in terminals
-    for node in &compositor.layout_nodes {
-        if let StackWindow::Terminal(id) = &node.cell {
-            if let Some(term) = terminals.get_mut(*id) {
-                if term.terminal.has_selection() {
-                    term.terminal.clear_selection();
-                    term.mark_selection_dirty();
-                }
-            }
-        }
-    }
+    clear_terminal_selections(compositor, terminals);
 
     // If starting in a terminal's content, also start the terminal's internal selection
+    let mut block_started = false;
     if let WindowPosition::Content { col, row } = &position {
         if let Some(node) = compositor.layout_nodes.get(window_index) {
             if let StackWindow::Terminal(id) = &node.cell {
                 if let Some(term) = terminals.get_mut(*id) {
-                    term.terminal.start_selection(*col, *row);
+                    if block {
+                        term.terminal.start_block_selection(*col, *row);
+                        block_started = true;
+                    } else {
+                        term.terminal.start_selection(*col, *row);
+                    }
                     term.mark_dirty();
                 }
             }
         }
     }
 
-    compositor.cross_selection = Some(CrossSelection::new(window_index, position));
+    let mut selection = CrossSelection::new(window_index, position);
+    selection.block = block_started;
+    compositor.cross_selection = Some(selection);
     true
 }
 
@@ -163,21 +271,39 @@ pub fn update_cross_selection(
     if !cross_sel.active {
         return false;
     }
+    if let Some(sel) = &mut compositor.cross_selection {
+        sel.pointer = Some((render_x, render_y.value()));
+    }
 
     // Throttle updates to avoid overwhelming the system
     let now = Instant::now();
-    if now.duration_since(cross_sel.last_update) < std::time::Duration::from_millis(16) {
+    if now.duration_since(compositor.cross_selection.as_ref().unwrap().last_update) < compositor.frame_time() {
         return false;
     }
 
-    let Some((end_window, end_position)) =
-        position_at_point(compositor, terminals, render_x, render_y)
+    // Get start anchor info (clone to avoid borrow issues)
+    let start = compositor.cross_selection.as_ref().unwrap().start.clone();
+    let block = compositor.cross_selection.as_ref().unwrap().block;
+
+    // Past the edge of a terminal with more scrollback that way, the selection
+    // stays on its edge row while `autoscroll_selection` scrolls
+    let edge = scrollback_edge(compositor, terminals, &start, render_x, render_y);
+    let Some((end_window, end_position)) = edge
+        .map(|(_, position)| (start.window_index, position))
+        .or_else(|| position_at_point(compositor, terminals, render_x, render_y))
     else {
         return false;
     };
 
-    // Get start anchor info (clone to avoid borrow issues)
-    let start = compositor.cross_selection.as_ref().unwrap().start.clone();
+    // Blocks are columns of one terminal, so they stop at its edges
+    if block && (end_window != start.window_index || !matches!(end_position, WindowPosition::Content { .. })) {
+        return false;
+    }
+    // Selections run down one column of cells
+    let column_of = |index: usize| compositor.layout_nodes.get(index).map(|node| node.column);
+    if column_of(end_window) != column_of(start.window_index) {
+        return false;
+    }
 
     // Clamp selection span to MAX_SELECTION_WINDOWS
     let clamped_end_window = clamp_selection_window(start.window_index, end_window);
@@ -191,11 +317,99 @@ pub fn update_cross_selection(
     let end_window = clamped_end_window;
 
     // Update terminal internal selections based on new range
-    update_terminal_selections_for_range(compositor, terminals, &start, end_window, &end_position);
+    update_terminal_selections_for_range(compositor, terminals, &start, end_window, &end_position, block);
 
     true
 }
 
+/// Interval between lines scrolled while a selection is held past a terminal's edge
+const AUTOSCROLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// The terminal a selection started in, if the pointer is above the rows it
+/// shows with scrollback above, or below them while scrolled back
+///
+/// Returns the terminal, the lines to scroll it by (positive = into history)
+/// and the selection end on its edge row.
+fn scrollback_edge(
+    compositor: &TermStack,
+    terminals: &TerminalManager,
+    start: &crate::state::SelectionAnchor,
+    render_x: f64,
+    render_y: RenderY,
+) -> Option<((TerminalId, i32), WindowPosition)> {
+    if !matches!(start.position, WindowPosition::Content { .. }) {
+        return None;
+    }
+    let StackWindow::Terminal(id) = compositor.layout_nodes.get(start.window_index)?.cell else {
+        return None;
+    };
+    let term = terminals.get(id)?;
+    let (window_render_y, window_height) = compositor.get_window_render_position(start.window_index);
+    let title_bar_height = if term.show_title_bar { TITLE_BAR_HEIGHT as f64 } else { 0.0 };
+    let content_top = window_render_y.value() + window_height as f64 - title_bar_height;
+
+    let rows = term.terminal.visible_rows();
+    let (lines, row) = if render_y.value() > content_top && rows.start > 0 {
+        (1, rows.start)
+    } else if render_y.value() < window_render_y.value() && term.terminal.display_offset() > 0 {
+        (-1, rows.end.saturating_sub(1))
+    } else {
+        return None;
+    };
+    let (char_width, _) = term.terminal.cell_size();
+    let local_x = render_x - compositor.cell_x(start.window_index) as f64 - FOCUS_INDICATOR_WIDTH as f64;
+    let col = (local_x.max(0.0) / char_width as f64) as usize;
+    Some(((id, lines), WindowPosition::Content { col, row }))
+}
+
+/// Scroll the terminal a selection is being dragged out of, a line at a time
+///
+/// Called every frame. While the pointer stays above (or below) the rows the
+/// terminal the selection started in shows, its scrollback moves toward the
+/// pointer and the selection follows, as in other terminals.
+pub fn autoscroll_selection(compositor: &mut TermStack, terminals: &mut TerminalManager) {
+    let Some(sel) = &compositor.cross_selection else {
+        return;
+    };
+    let Some((render_x, render_y)) = sel.pointer else {
+        return;
+    };
+    if !sel.active || sel.last_update.elapsed() < AUTOSCROLL_INTERVAL {
+        return;
+    }
+    let start = sel.start.clone();
+    let block = sel.block;
+    let Some(((id, lines), position)) =
+        scrollback_edge(compositor, terminals, &start, render_x, RenderY::new(render_y))
+    else {
+        return;
+    };
+    let Some(term) = terminals.get_mut(id) else {
+        return;
+    };
+
+    let before = term.terminal.display_offset();
+    term.terminal.scroll_display(lines);
+    let scrolled = term.terminal.display_offset() as isize - before as isize;
+    if scrolled == 0 {
+        return;
+    }
+    term.mark_dirty();
+
+    // The rows shown move by what was scrolled once the terminal renders again
+    let WindowPosition::Content { col, row } = position else {
+        return;
+    };
+    let end_position = WindowPosition::Content { col, row: row.saturating_add_signed(-scrolled) };
+    if let Some(sel) = &mut compositor.cross_selection {
+        sel.end.window_index = start.window_index;
+        sel.end.position = end_position.clone();
+        sel.last_update = Instant::now();
+    }
+    update_terminal_selections_for_range(compositor, terminals, &start, start.window_index, &end_position, block);
+    compositor.redraw_requested = true;
+}
+
 /// Update terminal internal selections based on the cross-selection range
 fn update_terminal_selections_for_range(
     compositor: &TermStack,
@@ -203,6 +417,7 @@ fn update_terminal_selections_for_range(
     start: &crate::state::SelectionAnchor,
     end_window: usize,
     end_position: &WindowPosition,
+    block: bool,
 ) {
     let (first_window, last_window) = if start.window_index <= end_window {
         (start.window_index, end_window)
@@ -324,7 +539,11 @@ fn update_terminal_selections_for_range(
             grid_rows,
             "setting terminal selection"
         );
-        term.terminal.start_selection(start_col, start_row);
+        if block {
+            term.terminal.start_block_selection(start_col, start_row);
+        } else {
+            term.terminal.start_selection(start_col, start_row);
+        }
         term.terminal.update_selection(start_col, start_row, end_col, end_row);
         term.mark_selection_dirty();
     }
@@ -374,10 +593,10 @@ fn extract_cross_selection_text(
             StackWindow::Terminal(id) => {
                 let term = terminals.get(*id);
                 let has_tb = term.map(|t| t.show_title_bar).unwrap_or(false);
-                let title = term.map(|t| t.title.clone()).unwrap_or_default();
+                let title = term.map(|t| t.display_title().to_string()).unwrap_or_default();
                 (has_tb, title)
             }
-            StackWindow::External(entry) => (!entry.uses_csd, entry.command.clone()),
+            StackWindow::External(entry) => (entry.has_title_bar(), entry.display_title().to_string()),
         };
 
         let is_first = idx == first_window;
@@ -488,6 +707,14 @@ fn clamp_selection_window(start_window: usize, end_window: usize) -> usize {
 mod tests {
     use super::*;
 
+    #[test]
+    fn copy_options_trim_trailing_whitespace() {
+        let text = "ls   \nfoo\t\n  indented  ";
+        assert_eq!(CopyOptions::default().prepare(text), "ls\nfoo\n  indented");
+        let keep = CopyOptions { trim_trailing_whitespace: false, ..CopyOptions::default() };
+        assert_eq!(keep.prepare(text), text);
+    }
+
     #[test]
     fn cross_selection_window_range() {
         // Selection from window 1 to window 3
@@ -20,10 +20,12 @@ use crate::state::{ClientState, TermStack};
 use crate::terminal_manager::TerminalManager;
 
 /// Create a Smithay output with standard configuration.
-pub fn create_output(name: &str, width: i32, height: i32) -> (Output, Mode, Size<i32, Physical>) {
+///
+/// `refresh` is the monitor's refresh rate in mHz.
+pub fn create_output(name: &str, width: i32, height: i32, refresh: i32) -> (Output, Mode, Size<i32, Physical>) {
     let mode = Mode {
         size: (width, height).into(),
-        refresh: 60_000,
+        refresh,
     };
 
     let output = Output::new(
@@ -49,6 +51,30 @@ pub fn create_terminal_manager(config: &Config, width: u32, height: u32) -> Term
         TerminalManager::new_with_size(width, height, terminal_theme, config.font_size);
     terminal_manager.set_max_terminals(config.max_terminals);
     terminal_manager.set_max_dead_terminals(config.max_dead_terminals);
+    terminal_manager.set_fold_repeated_lines(config.fold_repeated_lines);
+    terminal_manager.set_tui_overrides(config.tui.clone());
+    terminal_manager.set_background_max_rows(config.background_max_rows);
+    terminal_manager.set_smart_output(config.smart_output);
+    terminal_manager.set_contrast(terminal::Contrast {
+        high: config.high_contrast,
+        minimum: config.minimum_contrast,
+    });
+    terminal_manager.set_ligatures(config.ligatures);
+    terminal_manager.set_texture_eviction(
+        (config.texture_evict_secs > 0).then(|| Duration::from_secs(config.texture_evict_secs)),
+    );
+    terminal_manager.set_scrollback_compression(
+        (config.compress_idle_secs > 0).then(|| Duration::from_secs(config.compress_idle_secs)),
+    );
+    if config.record_sessions {
+        match crate::recording::create_session_dir() {
+            Ok(dir) => {
+                tracing::info!(dir = %dir.display(), "recording session");
+                terminal_manager.set_recording_dir(Some(dir));
+            }
+            Err(e) => tracing::warn!(error = %e, "failed to create recording directory"),
+        }
+    }
     terminal_manager
         .set_dead_terminal_ttl(Duration::from_secs(config.dead_terminal_ttl_minutes * 60));
     terminal_manager
@@ -142,6 +168,7 @@ pub fn setup_ipc_socket(
         .insert_source(
             Generic::new(ipc_listener, Interest::READ, CalloopMode::Level),
             |_, listener, state| {
+                state.redraw_requested = true;
                 loop {
                     match listener.accept() {
                         Ok((stream, _)) => {
@@ -183,6 +210,82 @@ pub fn setup_ipc_socket(
                                         );
                                         state.pending_builtin_requests.push(builtin_req);
                                     }
+                                    crate::ipc::IpcRequest::Calc(calc_req) => {
+                                        tracing::info!(
+                                            command = %calc_req.command,
+                                            "IPC calc request queued"
+                                        );
+                                        state.pending_calc_requests.push(calc_req);
+                                    }
+                                    crate::ipc::IpcRequest::Status { watch } => {
+                                        state.status_feed.add(stream, watch);
+                                    }
+                                    crate::ipc::IpcRequest::Export { index, format } => {
+                                        state.pending_export_requests.push((index, format, stream));
+                                    }
+                                    crate::ipc::IpcRequest::SetTitle { index, title } => {
+                                        state.pending_title_requests.push((index, title, stream));
+                                    }
+                                    crate::ipc::IpcRequest::SetTag { index, tag } => {
+                                        state.pending_tag_requests.push((index, tag, stream));
+                                    }
+                                    crate::ipc::IpcRequest::Accessibility { watch } => {
+                                        state.accessibility_feed.add(stream, watch);
+                                    }
+                                    crate::ipc::IpcRequest::Dnd { mode, focus } => {
+                                        state.do_not_disturb.set(mode, focus);
+                                        let status = state.do_not_disturb.status();
+                                        if let Err(e) = crate::ipc::send_json_response(stream, &status) {
+                                            tracing::warn!(error = ?e, "Failed to send dnd response");
+                                        }
+                                    }
+                                    crate::ipc::IpcRequest::FocusBack => {
+                                        let switched = state.focus_back();
+                                        state.redraw_requested |= switched;
+                                        if let Err(e) = crate::ipc::send_json_response(stream, &switched) {
+                                            tracing::warn!(error = ?e, "Failed to send focus_back response");
+                                        }
+                                    }
+                                    crate::ipc::IpcRequest::Layout => {
+                                        state.pending_layout_requests.push(stream);
+                                    }
+                                    crate::ipc::IpcRequest::LayoutExport => {
+                                        state.pending_layout_exports.push(stream);
+                                    }
+                                    crate::ipc::IpcRequest::LayoutImport(layout) => {
+                                        state.pending_layout_imports.push((layout, stream));
+                                    }
+                                    crate::ipc::IpcRequest::Filter(filter) => {
+                                        state.filter_view.pending.push((filter, stream));
+                                    }
+                                    crate::ipc::IpcRequest::HitTest(points) => {
+                                        state.pending_hit_tests.push((points, stream));
+                                    }
+                                    crate::ipc::IpcRequest::Bulk(action) => {
+                                        state.bulk_actions.pending.push((action, stream));
+                                    }
+                                    crate::ipc::IpcRequest::Archive { action, id } => {
+                                        state.archive.pending.push((action, id, stream));
+                                    }
+                                    crate::ipc::IpcRequest::XWayland => {
+                                        let status = state.xwayland.status(std::time::Instant::now());
+                                        if let Err(e) = crate::ipc::send_json_response(stream, &status) {
+                                            tracing::warn!(error = ?e, "Failed to send xwayland response");
+                                        }
+                                    }
+                                    crate::ipc::IpcRequest::Env => {
+                                        let update = state.xwayland.env_update();
+                                        if let Err(e) = crate::ipc::send_json_response(stream, &update) {
+                                            tracing::warn!(error = ?e, "Failed to send env response");
+                                        }
+                                    }
+                                    crate::ipc::IpcRequest::Quit => {
+                                        tracing::info!("quit requested over IPC");
+                                        state.running = false;
+                                        if let Err(e) = crate::ipc::send_json_response(stream, &true) {
+                                            tracing::warn!(error = ?e, "Failed to send quit response");
+                                        }
+                                    }
                                     crate::ipc::IpcRequest::QueryWindows => {
                                         let windows: Vec<crate::ipc::WindowInfo> = state
                                             .layout_nodes
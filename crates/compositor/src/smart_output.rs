@@ -0,0 +1,404 @@
+//! Formatted views of JSON and table output
+//!
+//! A finished command whose whole output is a JSON document or CSV/TSV rows
+//! can be looked at a second way: as a collapsible tree or an aligned table,
+//! drawn into its cell in place of the raw text (see `Terminal::show_view`).
+//! Super+E or Ctrl+Shift+E switches between the two, and clicking a `▾`/`▸`
+//! row of a tree collapses or expands that object or array.
+//!
+//! Output is looked at once, when the command exits, and only up to
+//! [`MAX_BYTES`]. Object keys are shown sorted.
+
+use std::collections::BTreeSet;
+
+use serde_json::Value;
+
+use crate::state::{FocusedWindow, TermStack};
+use crate::terminal_manager::{ManagedTerminal, TerminalId, TerminalManager};
+
+/// Largest output that is looked at (bytes)
+pub const MAX_BYTES: usize = 1 << 20;
+
+/// Fewest rows, header included, that make a table
+const MIN_TABLE_ROWS: usize = 2;
+
+/// Delimiters CSV-like output is split on, in order of preference
+const DELIMITERS: [char; 3] = ['\t', ',', ';'];
+
+const KEY: &str = "\x1b[34m";
+const STRING: &str = "\x1b[32m";
+const SCALAR: &str = "\x1b[33m";
+const DIM: &str = "\x1b[2m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Output that has a formatted view
+#[derive(Debug, Clone, PartialEq)]
+pub enum SmartOutput {
+    /// A JSON object or array
+    Json(Value),
+    /// Rows of fields, the first one the header
+    Table(Vec<Vec<String>>),
+}
+
+impl SmartOutput {
+    /// Recognize output that is all JSON or all delimited rows
+    pub fn detect(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text.is_empty() || text.len() > MAX_BYTES {
+            return None;
+        }
+        if text.starts_with('{') || text.starts_with('[') {
+            if let Ok(value @ (Value::Object(_) | Value::Array(_))) = serde_json::from_str(text) {
+                return Some(Self::Json(value));
+            }
+        }
+        parse_table(text).map(Self::Table)
+    }
+
+    /// What the view shows, for the title bar hint
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Json(_) => "JSON",
+            Self::Table(_) => "table",
+        }
+    }
+}
+
+/// Rows of `text` split on the first delimiter that gives every line the
+/// same number of fields (at least two, none of the header's empty)
+fn parse_table(text: &str) -> Option<Vec<Vec<String>>> {
+    let lines: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).collect();
+    if lines.len() < MIN_TABLE_ROWS {
+        return None;
+    }
+    DELIMITERS.iter().find_map(|&delimiter| {
+        let rows: Vec<Vec<String>> = lines.iter().map(|line| split_fields(line, delimiter)).collect();
+        let columns = rows[0].len();
+        let header_named = rows[0].iter().all(|field| !field.is_empty());
+        (columns >= 2 && header_named && rows.iter().all(|row| row.len() == columns)).then_some(rows)
+    })
+}
+
+/// Fields of a CSV line, with `"quoted, fields"` and `""` for a quote
+fn split_fields(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().expect("fields start with one").push('"');
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(String::new()),
+            c => fields.last_mut().expect("fields start with one").push(c),
+        }
+    }
+    fields.into_iter().map(|field| field.trim().to_string()).collect()
+}
+
+/// A formatted view of a command's output, with the tree nodes collapsed
+#[derive(Debug)]
+pub struct SmartView {
+    output: SmartOutput,
+    /// JSON pointers of the collapsed objects and arrays
+    collapsed: BTreeSet<String>,
+    /// The node each line of the last render opens, for clicks
+    nodes: Vec<Option<String>>,
+    /// Rows the cell had while showing the output, set while the view is shown
+    pub output_rows: Option<u16>,
+}
+
+impl SmartView {
+    pub fn new(output: SmartOutput) -> Self {
+        Self { output, collapsed: BTreeSet::new(), nodes: Vec::new(), output_rows: None }
+    }
+
+    /// What the view shows, for the title bar hint
+    pub fn kind(&self) -> &'static str {
+        self.output.kind()
+    }
+
+    /// The view as ANSI text, one line per row
+    pub fn render(&mut self) -> String {
+        let lines = match &self.output {
+            SmartOutput::Json(value) => {
+                let mut lines = Vec::new();
+                json_lines(value, None, "", 0, true, &self.collapsed, &mut lines);
+                lines
+            }
+            SmartOutput::Table(rows) => table_lines(rows).into_iter().map(|line| (line, None)).collect(),
+        };
+        let (text, nodes): (Vec<String>, Vec<Option<String>>) = lines.into_iter().unzip();
+        self.nodes = nodes;
+        text.join("\n")
+    }
+
+    /// Collapse or expand the node opened on line `line` of the last render
+    ///
+    /// Returns false if that line doesn't open one.
+    pub fn toggle_line(&mut self, line: usize) -> bool {
+        let Some(Some(pointer)) = self.nodes.get(line) else {
+            return false;
+        };
+        if !self.collapsed.remove(pointer) {
+            self.collapsed.insert(pointer.clone());
+        }
+        true
+    }
+}
+
+/// Lines of `value` and the node each opens, indented by `depth`
+fn json_lines(
+    value: &Value,
+    key: Option<&str>,
+    pointer: &str,
+    depth: usize,
+    last: bool,
+    collapsed: &BTreeSet<String>,
+    lines: &mut Vec<(String, Option<String>)>,
+) {
+    let indent = "  ".repeat(depth);
+    let key = key.map_or(String::new(), |key| format!("{KEY}{}{RESET}: ", Value::from(key)));
+    let comma = if last { "" } else { "," };
+
+    let (open, close, len, noun) = match value {
+        Value::Object(map) => ('{', '}', map.len(), if map.len() == 1 { "key" } else { "keys" }),
+        Value::Array(items) => ('[', ']', items.len(), if items.len() == 1 { "item" } else { "items" }),
+        scalar => {
+            let color = if scalar.is_string() { STRING } else { SCALAR };
+            lines.push((format!("  {indent}{key}{color}{scalar}{RESET}{comma}"), None));
+            return;
+        }
+    };
+    if len == 0 {
+        lines.push((format!("  {indent}{key}{open}{close}{comma}"), None));
+        return;
+    }
+    if collapsed.contains(pointer) {
+        let line = format!("{DIM}▸{RESET} {indent}{key}{open}…{close}{comma} {DIM}{len} {noun}{RESET}");
+        lines.push((line, Some(pointer.to_string())));
+        return;
+    }
+
+    lines.push((format!("{DIM}▾{RESET} {indent}{key}{open}"), Some(pointer.to_string())));
+    match value {
+        Value::Object(map) => {
+            for (i, (child_key, child)) in map.iter().enumerate() {
+                let child_pointer = format!("{pointer}/{}", child_key.replace('~', "~0").replace('/', "~1"));
+                json_lines(child, Some(child_key), &child_pointer, depth + 1, i + 1 == len, collapsed, lines);
+            }
+        }
+        Value::Array(items) => {
+            for (i, child) in items.iter().enumerate() {
+                json_lines(child, None, &format!("{pointer}/{i}"), depth + 1, i + 1 == len, collapsed, lines);
+            }
+        }
+        _ => unreachable!("scalars returned above"),
+    }
+    lines.push((format!("  {indent}{close}{comma}"), None));
+}
+
+/// Rows aligned in columns under a bold header, numbers on the right
+fn table_lines(rows: &[Vec<String>]) -> Vec<String> {
+    let columns = rows.first().map_or(0, Vec::len);
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| rows.iter().map(|row| row[column].chars().count()).max().unwrap_or(0))
+        .collect();
+    let numeric: Vec<bool> = (0..columns)
+        .map(|column| rows[1..].iter().all(|row| row[column].parse::<f64>().is_ok()))
+        .collect();
+
+    let separator = format!(" {DIM}│{RESET} ");
+    let format_row = |row: &[String], style: &str| {
+        let cells: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(column, field)| {
+                let width = widths[column];
+                let field = if numeric[column] {
+                    format!("{field:>width$}")
+                } else if column + 1 == columns {
+                    field.clone()
+                } else {
+                    format!("{field:<width$}")
+                };
+                format!("{style}{field}{RESET}")
+            })
+            .collect();
+        cells.join(&separator)
+    };
+
+    let mut lines = vec![format_row(&rows[0], BOLD)];
+    let rule: Vec<String> = widths.iter().map(|&width| "─".repeat(width)).collect();
+    lines.push(format!("{DIM}{}{RESET}", rule.join("─┼─")));
+    lines.extend(rows[1..].iter().map(|row| format_row(row, "")));
+    lines
+}
+
+/// Look at the output of commands that finished since the last frame
+pub fn detect_finished(terminal_manager: &mut TerminalManager) {
+    if !terminal_manager.smart_output() {
+        return;
+    }
+    for (id, terminal) in terminal_manager.iter_mut() {
+        if terminal.smart_output_checked || !terminal.has_exited() {
+            continue;
+        }
+        terminal.smart_output_checked = true;
+        terminal.smart_view = SmartOutput::detect(&terminal.terminal.output_text()).map(SmartView::new);
+        if let Some(view) = &terminal.smart_view {
+            tracing::info!(id = id.0, kind = view.kind(), "output has a formatted view");
+        }
+    }
+}
+
+/// Switch the focused cell between its output and its formatted view
+/// (Super+E), if it has one
+pub fn handle_toggle_request(compositor: &mut TermStack, terminal_manager: &mut TerminalManager) {
+    if !std::mem::take(&mut compositor.smart_view_toggle_requested) {
+        return;
+    }
+    let Some(FocusedWindow::Terminal(id)) = compositor.focused_window().cloned() else {
+        return;
+    };
+    let (_, cell_height, max_rows) = terminal_manager.cell_metrics(id);
+    let Some(terminal) = terminal_manager.get_mut(id) else {
+        return;
+    };
+    let Some(view) = &mut terminal.smart_view else {
+        tracing::debug!(id = id.0, "focused cell has no formatted view");
+        return;
+    };
+
+    if let Some(rows) = view.output_rows.take() {
+        terminal.terminal.hide_view();
+        terminal.resize(rows, cell_height);
+    } else {
+        view.output_rows = Some((terminal.height / cell_height.max(1)) as u16);
+        show(terminal, cell_height, max_rows);
+    }
+    terminal.mark_dirty();
+    compositor.redraw_requested = true;
+}
+
+/// Collapse or expand the tree node on viewport `row` of terminal `id`
+///
+/// Returns false if the terminal isn't showing a tree with a node there.
+pub fn toggle_node_at_row(terminal_manager: &mut TerminalManager, id: TerminalId, row: usize) -> bool {
+    let (_, cell_height, max_rows) = terminal_manager.cell_metrics(id);
+    let Some(terminal) = terminal_manager.get_mut(id) else {
+        return false;
+    };
+    let line = terminal.terminal.visible_rows().start + row;
+    let Some(view) = terminal.smart_view.as_mut().filter(|view| view.output_rows.is_some()) else {
+        return false;
+    };
+    if !view.toggle_line(line) {
+        return false;
+    }
+    show(terminal, cell_height, max_rows);
+    terminal.mark_dirty();
+    true
+}
+
+/// Draw the terminal's view and size the cell to it
+fn show(terminal: &mut ManagedTerminal, cell_height: u32, max_rows: u16) {
+    let Some(view) = &mut terminal.smart_view else {
+        return;
+    };
+    let text = view.render();
+    let lines = terminal.terminal.show_view(&text);
+    terminal.resize((lines as u16).clamp(1, max_rows), cell_height);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(text: &str) -> String {
+        let mut plain = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                plain.push(c);
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn json_and_tables_are_recognized() {
+        assert_eq!(SmartOutput::detect("  {\"a\": 1}\n").map(|o| o.kind()), Some("JSON"));
+        assert_eq!(SmartOutput::detect("[1, 2]").map(|o| o.kind()), Some("JSON"));
+        assert_eq!(SmartOutput::detect("42"), None);
+        assert_eq!(SmartOutput::detect("{not json"), None);
+
+        let csv = SmartOutput::detect("name,size\n\"a, b\",3\nc,\"say \"\"hi\"\"\"\n").unwrap();
+        assert_eq!(
+            csv,
+            SmartOutput::Table(vec![
+                vec!["name".into(), "size".into()],
+                vec!["a, b".into(), "3".into()],
+                vec!["c".into(), "say \"hi\"".into()],
+            ])
+        );
+        assert_eq!(SmartOutput::detect("a\tb\n1\t2").map(|o| o.kind()), Some("table"));
+        // Ragged rows, a single column or a single row aren't tables
+        assert_eq!(SmartOutput::detect("a,b\n1,2,3"), None);
+        assert_eq!(SmartOutput::detect("plain\noutput"), None);
+        assert_eq!(SmartOutput::detect("a,b"), None);
+    }
+
+    #[test]
+    fn json_tree_collapses_on_the_clicked_line() {
+        let value = serde_json::json!({"name": "x", "tags": ["a", "b"], "empty": {}});
+        let mut view = SmartView::new(SmartOutput::Json(value));
+        assert_eq!(
+            strip(&view.render()),
+            [
+                "▾ {",
+                "    \"empty\": {},",
+                "    \"name\": \"x\",",
+                "▾   \"tags\": [",
+                "      \"a\",",
+                "      \"b\"",
+                "    ]",
+                "  }",
+            ]
+            .join("\n")
+        );
+
+        assert!(!view.toggle_line(1));
+        assert!(view.toggle_line(3));
+        assert_eq!(strip(&view.render()).lines().nth(3), Some("▸   \"tags\": […] 2 items"));
+        assert!(view.toggle_line(0));
+        assert_eq!(strip(&view.render()), "▸ {…} 3 keys");
+        assert!(view.toggle_line(0));
+        assert_eq!(strip(&view.render()).lines().count(), 5);
+    }
+
+    #[test]
+    fn tables_align_columns_and_right_align_numbers() {
+        let rows = vec![
+            vec!["name".to_string(), "size".to_string(), "kind".to_string()],
+            vec!["a".to_string(), "1200".to_string(), "file".to_string()],
+            vec!["longer".to_string(), "7".to_string(), "dir".to_string()],
+        ];
+        let mut view = SmartView::new(SmartOutput::Table(rows));
+        assert_eq!(
+            strip(&view.render()),
+            [
+                "name   │ size │ kind",
+                "───────┼──────┼─────",
+                "a      │ 1200 │ file",
+                "longer │    7 │ dir",
+            ]
+            .join("\n")
+        );
+    }
+}
@@ -3,11 +3,77 @@
 //! Processes IPC spawn requests for both terminal commands and GUI applications.
 //! Handles environment setup, script extraction, and focus management.
 
-use std::path::PathBuf;
-use crate::ipc::SpawnRequest;
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::command_line::CommandLine;
+use crate::ipc::{BuiltinRequest, SpawnRequest};
 use crate::state::{FocusedWindow, StackWindow, TermStack};
 use crate::terminal_manager::{TerminalId, TerminalManager};
 
+/// Default window in which an identical spawn counts as a repeat
+pub const DEFAULT_SPAWN_DEDUP_WINDOW: Duration = Duration::from_millis(500);
+
+/// A command spawned recently, kept for deduplication
+#[derive(Debug, Clone)]
+struct RecentSpawn {
+    parent: Option<TerminalId>,
+    spawned: TerminalId,
+    command: String,
+    at: Instant,
+}
+
+/// Drops repeats of a spawn that arrive within a short window, so hitting
+/// Enter twice quickly doesn't create two identical cells. Kept on `TermStack`.
+#[derive(Debug)]
+pub struct SpawnDedup {
+    /// Zero when deduplication is off
+    window: Duration,
+    recent: Vec<RecentSpawn>,
+}
+
+impl Default for SpawnDedup {
+    fn default() -> Self {
+        Self::new(DEFAULT_SPAWN_DEDUP_WINDOW)
+    }
+}
+
+impl SpawnDedup {
+    /// Deduplicate within `window` (`Duration::ZERO` turns it off)
+    pub fn new(window: Duration) -> Self {
+        Self { window, recent: Vec::new() }
+    }
+
+    /// Settings from the config (`spawn_dedup_ms = 0` turns it off)
+    pub fn from_config(config: &crate::config::Config) -> Self {
+        Self::new(Duration::from_millis(config.spawn_dedup_ms))
+    }
+
+    /// Whether `command` from `parent` repeats a spawn made within the window
+    ///
+    /// The first spawn moves focus to its new cell, so a repeat processed after
+    /// it sees that cell as its parent; both count as the same origin.
+    pub fn is_duplicate(&mut self, parent: Option<TerminalId>, command: &str, now: Instant) -> bool {
+        let window = self.window;
+        self.recent.retain(|spawn| now.saturating_duration_since(spawn.at) < window);
+        self.recent.iter().any(|spawn| {
+            spawn.command == command && (spawn.parent == parent || parent == Some(spawn.spawned))
+        })
+    }
+
+    /// Remember a spawn so repeats of it can be dropped
+    pub fn record(&mut self, parent: Option<TerminalId>, spawned: TerminalId, command: &str, now: Instant) {
+        if self.window.is_zero() {
+            return;
+        }
+        self.recent.push(RecentSpawn { parent, spawned, command: command.to_string(), at: now });
+    }
+}
+
 /// Handle IPC spawn requests for terminal commands
 ///
 /// Processes pending terminal spawn requests, focuses the new terminal,
@@ -15,9 +81,21 @@ use crate::terminal_manager::{TerminalId, TerminalManager};
 pub fn handle_ipc_spawn_requests(
     compositor: &mut TermStack,
     terminal_manager: &mut TerminalManager,
-    calculate_window_heights: impl Fn(&TermStack, &TerminalManager) -> Vec<i32>,
 ) {
+    // X11 apps with no display of their own wait (in their own queue) for
+    // ours; everything else starts right away
+    let now = Instant::now();
+    if !compositor.deferred_x11_spawn_requests.is_empty() && !compositor.xwayland.defer_x11_spawn(now) {
+        let ready = std::mem::take(&mut compositor.deferred_x11_spawn_requests);
+        compositor.pending_spawn_requests.extend(ready);
+    }
+
     while let Some(request) = compositor.pending_spawn_requests.pop() {
+        if needs_x11_display(&request) && compositor.xwayland.defer_x11_spawn(now) {
+            tracing::info!(command = %request.command, "holding X11 app until XWayland is ready");
+            compositor.deferred_x11_spawn_requests.push(request);
+            continue;
+        }
         if let Some(id) = process_spawn_request(compositor, terminal_manager, request) {
             // Focus the new command terminal
             for (i, node) in compositor.layout_nodes.iter().enumerate() {
@@ -30,25 +108,62 @@ pub fn handle_ipc_spawn_requests(
                 }
             }
 
-            // Update cell heights
-            let new_heights = calculate_window_heights(compositor, terminal_manager);
-            compositor.update_layout_heights(new_heights);
-
-            // Scroll to show the new terminal
+            // Scroll to show the new terminal once it is laid out
             if let Some(focused_idx) = compositor.focused_index() {
-                if let Some(new_scroll) = compositor.scroll_to_show_window_bottom(focused_idx) {
-                    tracing::info!(
-                        id = id.0,
-                        focused_idx,
-                        new_scroll,
-                        "spawned command terminal, scrolling to show"
-                    );
-                }
+                compositor.reveal_after_layout(focused_idx);
+                tracing::info!(id = id.0, focused_idx, "spawned command terminal, scrolling to show");
             }
         }
     }
 }
 
+/// Whether a command spawn is an X11 app with no display unless it gets
+/// ours: no host display, none from the shell that asked (started before
+/// XWayland was up), and a program that links an X11 client library
+fn needs_x11_display(request: &SpawnRequest) -> bool {
+    if std::env::var_os("HOST_DISPLAY").is_some()
+        || request.env.contains_key("DISPLAY")
+        || request.env_overrides.contains_key("DISPLAY")
+    {
+        return false;
+    }
+    let path = request
+        .env_overrides
+        .get("PATH")
+        .or_else(|| request.env.get("PATH"))
+        .map(OsString::from)
+        .or_else(|| std::env::var_os("PATH"))
+        .unwrap_or_default();
+    runs_x11_client(&request.command, &path)
+}
+
+/// Libraries that X11 clients link directly (toolkits such as GTK and Qt
+/// pick Wayland by themselves)
+const X11_LIBRARIES: &[&[u8]] = &[b"libX11.so", b"libxcb.so"];
+
+/// Bytes at the start of a binary searched for the names of the libraries
+/// it links (the dynamic string table comes early)
+const LIBRARY_SCAN_BYTES: u64 = 256 * 1024;
+
+/// Whether a program of `command`, looked up in `path`, is an ELF binary
+/// linking an X11 client library; shell builtins and scripts aren't
+fn runs_x11_client(command: &str, path: &OsStr) -> bool {
+    let links_x11 = |binary: &Path| {
+        let mut head = Vec::new();
+        let read = File::open(binary).and_then(|file| file.take(LIBRARY_SCAN_BYTES).read_to_end(&mut head));
+        read.is_ok()
+            && head.starts_with(b"\x7fELF")
+            && X11_LIBRARIES.iter().any(|library| head.windows(library.len()).any(|window| window == *library))
+    };
+    CommandLine::parse(command)
+        .pipelines
+        .iter()
+        .flatten()
+        .filter_map(|stage| stage.program())
+        .filter_map(|program| std::env::split_paths(path).map(|dir| dir.join(program)).find(|binary| binary.is_file()))
+        .any(|binary| links_x11(&binary))
+}
+
 /// Handle GUI spawn requests from IPC (termstack gui)
 ///
 /// Spawns GUI app commands with foreground/background mode support.
@@ -56,15 +171,25 @@ pub fn handle_ipc_spawn_requests(
 pub fn handle_gui_spawn_requests(
     compositor: &mut TermStack,
     terminal_manager: &mut TerminalManager,
-    calculate_window_heights: impl Fn(&TermStack, &TerminalManager) -> Vec<i32>,
 ) {
+    // Launches wait (in the queue) for X11 to come up, so X11 apps get a DISPLAY
+    if !compositor.pending_gui_spawn_requests.is_empty() && compositor.xwayland.defer_x11_spawn(Instant::now()) {
+        return;
+    }
+
     while let Some(request) = compositor.pending_gui_spawn_requests.pop() {
         // Get the launching terminal (currently focused)
-        let launching_terminal = compositor.focused_window.as_ref().and_then(|cell| match cell {
+        let launching_terminal = compositor.focused_window().and_then(|cell| match cell {
             FocusedWindow::Terminal(id) => Some(*id),
             FocusedWindow::External(_) => None,
         });
 
+        let now = Instant::now();
+        if compositor.spawn_dedup.is_duplicate(launching_terminal, &request.command, now) {
+            tracing::info!(command = %request.command, "dropping duplicate GUI spawn request");
+            continue;
+        }
+
         // Extract foreground flag (guaranteed to be Some for GUI spawns)
         let foreground = request.foreground.unwrap_or(true);
 
@@ -113,6 +238,10 @@ pub fn handle_gui_spawn_requests(
         if let Ok(logname) = std::env::var("LOGNAME") {
             env.insert("LOGNAME".to_string(), logname);
         }
+        // Per-app overrides from config go last so they win over the defaults above,
+        // except for the ones given for this spawn (`termstack --env`)
+        crate::config::apply_gui_env(&compositor.gui_env, &request.command, &mut env);
+        env.extend(request.env_overrides.clone());
 
         tracing::debug!(
             display = ?env.get("DISPLAY"),
@@ -128,6 +257,11 @@ pub fn handle_gui_spawn_requests(
         // Redirect stderr to stdout to capture all output
         let wrapped_command = format!("nohup {} 2>&1", request.command);
 
+        // Tag the app so its windows find this launch even when another GUI
+        // app maps first
+        let token = compositor.pending_launches.next_token();
+        env.insert(crate::gui_launch::LAUNCH_TOKEN_ENV.to_string(), token.clone());
+
         // Create output terminal with WaitingForOutput visibility
         let parent = launching_terminal;
         match terminal_manager.spawn_command(&request.prompt, &wrapped_command, &request.cwd, &env, parent) {
@@ -143,15 +277,17 @@ pub fn handle_gui_spawn_requests(
                 // Add output terminal to layout
                 compositor.add_terminal(output_terminal_id);
                 compositor.enforce_terminal_limit(terminal_manager);
+                compositor.spawn_dedup.record(launching_terminal, output_terminal_id, &request.command, now);
 
                 // Set up for window linking
-                compositor.pending_window_output_terminal = Some(output_terminal_id);
-                compositor.pending_window_command = Some(request.command.clone());
-                compositor.pending_gui_foreground = foreground;
-                compositor.pending_window_set_at = Some(std::time::Instant::now());
+                let process = terminal_manager
+                    .get(output_terminal_id)
+                    .and_then(|term| crate::gui_launch::ProcessId::of(term.pid() as i32));
+                compositor.pending_launches.push(token, process, output_terminal_id, &request.command, foreground);
 
                 // If foreground mode, hide launching terminal and track the session
-                if foreground {
+                // (unless the config keeps launchers visible)
+                if foreground && compositor.hide_gui_launcher {
                     if let Some(launcher_id) = launching_terminal {
                         if let Some(launcher) = terminal_manager.get_mut(launcher_id) {
                             launcher.visibility.hide_for_gui();
@@ -169,10 +305,6 @@ pub fn handle_gui_spawn_requests(
                     }
                 }
 
-                // Update cell heights
-                let new_heights = calculate_window_heights(compositor, terminal_manager);
-                compositor.update_layout_heights(new_heights);
-
                 // spawn_command auto-focuses the new terminal, but for GUI spawns we want different behavior:
                 // - Foreground mode: GUI window will get focus when created (in add_window)
                 // - Background mode: focus stays on launcher terminal
@@ -180,8 +312,7 @@ pub fn handle_gui_spawn_requests(
                 // In both cases, restore focus to the launcher terminal now.
                 // For foreground mode, add_window will focus the GUI window when it's created.
                 if let Some(launcher_id) = launching_terminal {
-                    compositor.focused_window = Some(FocusedWindow::Terminal(launcher_id));
-                    compositor.invalidate_focused_index_cache();
+                    compositor.focus_terminal(launcher_id);
                     tracing::debug!(
                         launcher_id = launcher_id.0,
                         "restored terminal focus to launcher after gui_spawn"
@@ -195,70 +326,94 @@ pub fn handle_gui_spawn_requests(
     }
 }
 
-/// Handle builtin command requests from IPC (termstack --builtin)
+/// How long a calculator answer stays in the stack
+pub const CALC_RESULT_TTL: Duration = Duration::from_secs(10);
+
+/// Handle builtin command requests from IPC (termstack --builtin and --calc)
 ///
 /// Creates persistent stack entries for shell builtins like cd, export, alias, etc.
 /// These show the command and its output (if any) as a static terminal entry.
+/// Calculator answers get the same entry, dismissed after `CALC_RESULT_TTL`.
 pub fn handle_builtin_requests(
     compositor: &mut TermStack,
     terminal_manager: &mut TerminalManager,
-    calculate_window_heights: impl Fn(&TermStack, &TerminalManager) -> Vec<i32>,
 ) {
     while let Some(request) = compositor.pending_builtin_requests.pop() {
-        // Create static terminal with the builtin command and result
-        match terminal_manager.create_builtin_terminal(
-            &request.prompt,
-            &request.command,
-            &request.result,
-            request.success,
-        ) {
-            Ok(id) => {
-                // Find the launcher terminal (last terminal in layout)
-                // Builtins should appear above the launcher
-                let launcher_idx = compositor.layout_nodes.iter()
-                    .rposition(|node| matches!(node.cell, StackWindow::Terminal(_)))
-                    .unwrap_or(compositor.layout_nodes.len());
-
-                // Insert above launcher (at launcher's position, pushing launcher down)
-                compositor.layout_nodes.insert(launcher_idx, super::state::LayoutNode {
-                    cell: StackWindow::Terminal(id),
-                    height: 0, // Will be updated in calculate_window_heights
-                });
-                // Invalidate cache since layout_nodes changed
-                compositor.invalidate_focused_index_cache();
-
-                tracing::info!(
-                    id = id.0,
-                    insert_index = launcher_idx,
-                    command = %request.command,
-                    success = request.success,
-                    "inserted builtin terminal"
-                );
+        insert_builtin_entry(compositor, terminal_manager, &request);
+    }
+    while let Some(request) = compositor.pending_calc_requests.pop() {
+        if let Some(id) = insert_builtin_entry(compositor, terminal_manager, &request) {
+            compositor.calc_results.push((id, Instant::now() + CALC_RESULT_TTL));
+        }
+    }
+}
 
-                // Update cell heights
-                let new_heights = calculate_window_heights(compositor, terminal_manager);
-                compositor.update_layout_heights(new_heights);
+/// Remove calculator answers whose time is up
+pub fn dismiss_calc_results(compositor: &mut TermStack, terminal_manager: &mut TerminalManager) {
+    let now = Instant::now();
+    let (expired, kept): (Vec<_>, Vec<_>) = compositor.calc_results.iter().partition(|(_, until)| *until <= now);
+    if expired.is_empty() {
+        return;
+    }
+    compositor.calc_results = kept;
+    for (id, _) in expired {
+        terminal_manager.remove(id);
+        compositor.remove_terminal(id);
+        tracing::debug!(id = id.0, "dismissed calculator answer");
+    }
+    compositor.redraw_requested = true;
+}
 
-                // Scroll to show the builtin entry
-                if let Some(new_scroll) = compositor.scroll_to_show_window_bottom(launcher_idx) {
-                    tracing::debug!(
-                        id = id.0,
-                        new_scroll,
-                        "scrolled to show builtin terminal"
-                    );
-                }
-            }
-            Err(e) => {
-                tracing::error!(
-                    command = %request.command,
-                    error = ?e,
-                    "failed to create builtin terminal"
-                );
-            }
+/// Insert a static entry for a builtin command above the launcher
+fn insert_builtin_entry(
+    compositor: &mut TermStack,
+    terminal_manager: &mut TerminalManager,
+    request: &BuiltinRequest,
+) -> Option<TerminalId> {
+    // Create static terminal with the builtin command and result
+    match terminal_manager.create_builtin_terminal(
+        &request.prompt,
+        &request.command,
+        &request.result,
+        request.success,
+    ) {
+        Ok(id) => {
+            // Find the launcher terminal (last terminal in layout)
+            // Builtins should appear above the launcher
+            let launcher_idx = compositor.layout_nodes.iter()
+                .rposition(|node| matches!(node.cell, StackWindow::Terminal(_)))
+                .unwrap_or(compositor.layout_nodes.len());
+
+            // Insert above launcher (at launcher's position, pushing launcher down)
+            // Height 0 is filled in by the next frame layout
+            compositor.insert_cell(launcher_idx, StackWindow::Terminal(id), 0);
+            // Invalidate cache since layout_nodes changed
+            compositor.invalidate_focused_index_cache();
+
+            tracing::info!(
+                id = id.0,
+                insert_index = launcher_idx,
+                command = %request.command,
+                success = request.success,
+                "inserted builtin terminal"
+            );
+
+            // Scroll to show the builtin entry once it is laid out
+            compositor.reveal_after_layout(launcher_idx);
+            Some(id)
+        }
+        Err(e) => {
+            tracing::error!(
+                command = %request.command,
+                error = ?e,
+                "failed to create builtin terminal"
+            );
+            None
         }
     }
 }
 
+
 /// Process a spawn request for a terminal command
 ///
 /// Sets up environment variables, adds helper scripts to PATH, and spawns
@@ -271,56 +426,19 @@ fn process_spawn_request(
     // Decide what command to run
     // Title bar now shows the command, so no need for echo prefix
     let command = if request.command.is_empty() {
-        std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string())
+        request.shell.clone()
+            .or_else(|| std::env::var("SHELL").ok())
+            .unwrap_or_else(|| "/bin/sh".to_string())
     } else {
         request.command.clone()
     };
 
-    // Modify environment
-    let mut env = request.env.clone();
-    env.insert("GIT_PAGER".to_string(), "cat".to_string());
-    env.insert("PAGER".to_string(), "cat".to_string());
-    env.insert("LESS".to_string(), "-FRX".to_string());
-
-    // For regular terminal spawns (not gui spawns), use host display so GUI windows
-    // appear on the host desktop. Only 'gui' prefix should bring windows into termstack.
-    if let Ok(host_wayland) = std::env::var("HOST_WAYLAND_DISPLAY") {
-        env.insert("WAYLAND_DISPLAY".to_string(), host_wayland.clone());
-        env.insert("HOST_WAYLAND_DISPLAY".to_string(), host_wayland);
-    }
-    if let Ok(host_x11) = std::env::var("HOST_DISPLAY") {
-        env.insert("DISPLAY".to_string(), host_x11.clone());
-        env.insert("HOST_DISPLAY".to_string(), host_x11);
-    }
-    // Don't force GTK/Qt backend - let apps use host defaults
-    env.remove("GDK_BACKEND");
-    env.remove("QT_QPA_PLATFORM");
-    // Pass SHELL so spawn_command uses the correct shell for syntax
-    // This ensures fish loops work when user's shell is fish
-    if let Ok(shell) = std::env::var("SHELL") {
-        env.insert("SHELL".to_string(), shell);
-    }
+    let mut env = command_env(compositor, &request);
 
-    // Add scripts directory to PATH so helper scripts (like 'gui') are available
-    // Scripts are extracted from embedded content at runtime
-    match get_or_create_scripts_dir() {
-        Ok(scripts_dir) => {
-            let scripts_dir_str = scripts_dir.display().to_string();
-            if let Some(current_path) = env.get("PATH") {
-                // Prepend scripts directory to existing PATH
-                env.insert("PATH".to_string(), format!("{}:{}", scripts_dir_str, current_path));
-            } else {
-                // No PATH set, create one with just scripts directory
-                env.insert("PATH".to_string(), scripts_dir_str);
-            }
-        }
-        Err(e) => {
-            // Log warning but don't fail spawn - terminal will work, just without helper scripts
-            tracing::warn!(?e, "failed to create scripts directory, helper scripts unavailable");
-        }
-    }
+    let token = compositor.pending_launches.next_token();
+    env.insert(crate::gui_launch::LAUNCH_TOKEN_ENV.to_string(), token.clone());
 
-    let parent = compositor.focused_window.as_ref().and_then(|cell| match cell {
+    let parent = compositor.focused_window().and_then(|cell| match cell {
         FocusedWindow::Terminal(id) => Some(*id),
         FocusedWindow::External(_) => None,
     });
@@ -335,6 +453,13 @@ fn process_spawn_request(
         }
     }
 
+    // Drop the second of two identical spawns from a quick double Enter
+    let now = Instant::now();
+    if compositor.spawn_dedup.is_duplicate(parent, &request.command, now) {
+        tracing::info!(command = %command, ?parent, "dropping duplicate spawn request");
+        return None;
+    }
+
     tracing::info!(
         command = %command,
         ?parent,
@@ -343,28 +468,33 @@ fn process_spawn_request(
 
     match terminal_manager.spawn_command(&request.prompt, &command, &request.cwd, &env, parent) {
         Ok(id) => {
-            if let Some(term) = terminal_manager.get(id) {
+            if let Some(term) = terminal_manager.get_mut(id) {
                 let (cols, pty_rows) = term.terminal.dimensions();
                 tracing::info!(id = id.0, cols, pty_rows, height = term.height, "terminal created");
+                // Where the output goes when it's redirected, for the cell to point to
+                let home = request.env.get("HOME").map(std::path::Path::new);
+                term.output_files = CommandLine::parse(&request.command).output_files(&request.cwd, home);
             }
             compositor.add_terminal(id);
             compositor.enforce_terminal_limit(terminal_manager);
-
-            // Set this terminal as the pending output terminal for GUI windows,
-            // but ONLY if no pending value is already set. This protects against
-            // a race where a GUI spawn sets pending_window_output_terminal, then
-            // a regular spawn (user typing elsewhere) overwrites it before the
-            // GUI window connects.
-            if compositor.pending_window_output_terminal.is_none() {
-                compositor.pending_window_output_terminal = Some(id);
-                compositor.pending_window_command = Some(request.command.clone());
-                compositor.pending_window_set_at = Some(std::time::Instant::now());
+            compositor.spawn_dedup.record(parent, id, &request.command, now);
+
+            // Queue this terminal as the output terminal for GUI windows,
+            // but ONLY if no GUI launch is pending. Windows without a launch
+            // token take the oldest pending launch, so a regular spawn (user
+            // typing elsewhere) must not get in line before a GUI app that
+            // hasn't connected yet.
+            if compositor.pending_launches.is_empty() {
+                let process = terminal_manager
+                    .get(id)
+                    .and_then(|term| crate::gui_launch::ProcessId::of(term.pid() as i32));
+                compositor.pending_launches.push(token, process, id, &request.command, false);
                 tracing::info!(id = id.0, command = %request.command, "set as pending output terminal for GUI windows");
             } else {
                 tracing::debug!(
                     id = id.0,
-                    existing = ?compositor.pending_window_output_terminal,
-                    "not overwriting existing pending_window_output_terminal"
+                    pending = compositor.pending_launches.len(),
+                    "GUI launches pending, not queueing output terminal"
                 );
             }
 
@@ -377,6 +507,70 @@ fn process_spawn_request(
     }
 }
 
+/// Environment for a command cell: the request's variables adjusted so
+/// pagers don't wait for input, apps open on the host display and the
+/// helper scripts are on PATH
+///
+/// A command piped into a pager (`git log | less`) asked for paging, so it
+/// keeps the user's pager settings and the pager runs full screen.
+pub(crate) fn command_env(compositor: &TermStack, request: &SpawnRequest) -> HashMap<String, String> {
+    let mut env = request.env.clone();
+    if !CommandLine::parse(&request.command).pipes_into_pager() {
+        env.insert("GIT_PAGER".to_string(), "cat".to_string());
+        env.insert("PAGER".to_string(), "cat".to_string());
+        env.insert("LESS".to_string(), "-FRX".to_string());
+    }
+
+    // For regular terminal spawns (not gui spawns), use host display so GUI windows
+    // appear on the host desktop. Only 'gui' prefix should bring windows into termstack.
+    if let Ok(host_wayland) = std::env::var("HOST_WAYLAND_DISPLAY") {
+        env.insert("WAYLAND_DISPLAY".to_string(), host_wayland.clone());
+        env.insert("HOST_WAYLAND_DISPLAY".to_string(), host_wayland);
+    }
+    if let Ok(host_x11) = std::env::var("HOST_DISPLAY") {
+        env.insert("DISPLAY".to_string(), host_x11.clone());
+        env.insert("HOST_DISPLAY".to_string(), host_x11);
+    }
+    // Without a host display, X11 apps use our XWayland once it is up
+    if !env.contains_key("DISPLAY") {
+        env.extend(compositor.xwayland.env_update().set);
+    }
+    // Don't force GTK/Qt backend - let apps use host defaults
+    env.remove("GDK_BACKEND");
+    env.remove("QT_QPA_PLATFORM");
+    // Pass SHELL so spawn_command uses the correct shell for syntax
+    // This ensures fish loops work when user's shell is fish
+    if let Ok(shell) = std::env::var("SHELL") {
+        env.insert("SHELL".to_string(), shell);
+    }
+    // Per-cell overrides (`termstack --shell/--env`) win over all of the above
+    if let Some(shell) = &request.shell {
+        env.insert("SHELL".to_string(), shell.clone());
+    }
+    env.extend(request.env_overrides.clone());
+
+    // Add scripts directory to PATH so helper scripts (like 'gui') are available
+    // Scripts are extracted from embedded content at runtime
+    match get_or_create_scripts_dir() {
+        Ok(scripts_dir) => {
+            let scripts_dir_str = scripts_dir.display().to_string();
+            if let Some(current_path) = env.get("PATH") {
+                // Prepend scripts directory to existing PATH
+                env.insert("PATH".to_string(), format!("{}:{}", scripts_dir_str, current_path));
+            } else {
+                // No PATH set, create one with just scripts directory
+                env.insert("PATH".to_string(), scripts_dir_str);
+            }
+        }
+        Err(e) => {
+            // Log warning but don't fail spawn - terminal will work, just without helper scripts
+            tracing::warn!(?e, "failed to create scripts directory, helper scripts unavailable");
+        }
+    }
+
+    env
+}
+
 /// Get or create the termstack scripts directory and extract embedded scripts
 ///
 /// Scripts are extracted from `scripts/bin/*` (embedded at compile time) and written
@@ -494,4 +688,60 @@ mod tests {
             "GUI window should be linked to GUI's output terminal"
         );
     }
+
+    #[test]
+    fn double_enter_spawns_once() {
+        use super::SpawnDedup;
+        use std::time::{Duration, Instant};
+
+        let mut dedup = SpawnDedup::new(Duration::from_millis(500));
+        let shell = Some(TerminalId(1));
+        let start = Instant::now();
+
+        assert!(!dedup.is_duplicate(shell, "make", start));
+        dedup.record(shell, TerminalId(2), "make", start);
+
+        // Same command from the same shell, or from the cell it just spawned
+        // (which took focus), is a repeat
+        let soon = start + Duration::from_millis(100);
+        assert!(dedup.is_duplicate(shell, "make", soon));
+        assert!(dedup.is_duplicate(Some(TerminalId(2)), "make", soon));
+        // Other commands and other shells are not
+        assert!(!dedup.is_duplicate(shell, "make test", soon));
+        assert!(!dedup.is_duplicate(Some(TerminalId(3)), "make", soon));
+        // Running it again later is deliberate
+        assert!(!dedup.is_duplicate(shell, "make", start + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn only_x11_clients_wait_for_a_display() {
+        use super::runs_x11_client;
+
+        let dir = std::env::temp_dir().join(format!("termstack-x11-client-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("xclock"), b"\x7fELF\x02\x01\x01\0libxcb.so.1\0libc.so.6\0").unwrap();
+        std::fs::write(dir.join("ls"), b"\x7fELF\x02\x01\x01\0libc.so.6\0").unwrap();
+        std::fs::write(dir.join("launcher"), b"#!/bin/sh\n# libX11.so\n").unwrap();
+        let path = dir.as_os_str();
+
+        assert!(runs_x11_client("xclock -digital", path));
+        assert!(runs_x11_client("DISPLAY= env xclock &", path));
+        assert!(runs_x11_client("ls | xclock", path));
+        assert!(!runs_x11_client("ls -l", path));
+        assert!(!runs_x11_client("launcher", path));
+        assert!(!runs_x11_client("cd /tmp; missing", path));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn zero_window_disables_dedup() {
+        use super::SpawnDedup;
+        use std::time::{Duration, Instant};
+
+        let mut dedup = SpawnDedup::new(Duration::ZERO);
+        let now = Instant::now();
+        dedup.record(Some(TerminalId(1)), TerminalId(2), "make", now);
+        assert!(!dedup.is_duplicate(Some(TerminalId(1)), "make", now));
+    }
 }
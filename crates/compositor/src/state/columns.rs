@@ -0,0 +1,389 @@
+//! Side-by-side columns of cells
+//!
+//! Every cell belongs to a column (`LayoutNode::column`). The cells of a
+//! column are contiguous in `layout_nodes` and columns follow each other
+//! left to right in the same order, so a column is a range of indices and
+//! the shared height index still gives offsets within it.
+//!
+//! Each column scrolls on its own. `TermStack::scroll_offset` is the scroll
+//! of the active column (the one focus or the pointer was last in); the
+//! others keep theirs in `Columns` until they become active again, so code
+//! that scrolls "the" column keeps working on the one the user is in.
+//!
+//! A column is created by moving a cell past the outermost one (or by
+//! importing a layout file with more columns) and goes away when its last
+//! cell leaves. There is always at least one.
+
+use std::ops::Range;
+
+use crate::cell_id::CellId;
+use crate::layout::{cells_near_viewport, column_span, ColumnId};
+use super::{StackWindow, TermStack};
+
+/// Direction to move focus or a cell between columns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnSide {
+    Left,
+    Right,
+}
+
+#[derive(Debug)]
+struct Column {
+    id: ColumnId,
+    /// Scroll offset while the column isn't active
+    scroll_offset: f64,
+}
+
+/// The columns, left to right, and which one is active
+#[derive(Debug)]
+pub struct Columns {
+    /// Never empty
+    columns: Vec<Column>,
+    /// Column whose scroll offset is `TermStack::scroll_offset`
+    active: ColumnId,
+    next_id: u32,
+    /// Number of columns cells were last sized for
+    sized_for: usize,
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        Self {
+            columns: vec![Column { id: ColumnId(0), scroll_offset: 0.0 }],
+            active: ColumnId(0),
+            next_id: 1,
+            sized_for: 1,
+        }
+    }
+}
+
+impl Columns {
+    /// Number of columns
+    pub fn len(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Always false: there is at least one column
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// The column holding `TermStack::scroll_offset`
+    pub fn active(&self) -> ColumnId {
+        self.active
+    }
+
+    /// Column ids, left to right
+    pub fn ids(&self) -> impl Iterator<Item = ColumnId> + '_ {
+        self.columns.iter().map(|column| column.id)
+    }
+
+    /// Position of a column from the left, or `None` if it doesn't exist
+    pub fn position(&self, id: ColumnId) -> Option<usize> {
+        self.columns.iter().position(|column| column.id == id)
+    }
+
+    /// The column next to `id` on `side`
+    pub fn neighbour(&self, id: ColumnId, side: ColumnSide) -> Option<ColumnId> {
+        let position = self.position(id)?;
+        let position = match side {
+            ColumnSide::Left => position.checked_sub(1)?,
+            ColumnSide::Right => position + 1,
+        };
+        self.columns.get(position).map(|column| column.id)
+    }
+
+    /// Whether columns came or went since the last call, so cells need
+    /// resizing to the new column width
+    pub fn take_count_change(&mut self) -> bool {
+        let changed = self.sized_for != self.columns.len();
+        self.sized_for = self.columns.len();
+        changed
+    }
+
+    /// Add an empty column at the `side` edge
+    fn add(&mut self, side: ColumnSide) -> ColumnId {
+        let id = ColumnId(self.next_id);
+        self.next_id += 1;
+        let column = Column { id, scroll_offset: 0.0 };
+        match side {
+            ColumnSide::Left => self.columns.insert(0, column),
+            ColumnSide::Right => self.columns.push(column),
+        }
+        id
+    }
+
+    /// Remove a column unless it is the last one
+    fn remove(&mut self, id: ColumnId) {
+        if self.columns.len() > 1 {
+            self.columns.retain(|column| column.id != id);
+        }
+    }
+
+    fn stored_scroll(&self, id: ColumnId) -> f64 {
+        self.columns
+            .iter()
+            .find(|column| column.id == id)
+            .map_or(0.0, |column| column.scroll_offset)
+    }
+
+    fn store_scroll(&mut self, id: ColumnId, scroll_offset: f64) {
+        if let Some(column) = self.columns.iter_mut().find(|column| column.id == id) {
+            column.scroll_offset = scroll_offset;
+        }
+    }
+}
+
+impl TermStack {
+    /// Indices in `layout_nodes` of a column's cells
+    pub fn column_range(&self, id: ColumnId) -> Range<usize> {
+        let Some(position) = self.columns.position(id) else {
+            return 0..0;
+        };
+        let position_of = |node: &super::LayoutNode| self.columns.position(node.column).unwrap_or(0);
+        let start = self.layout_nodes.partition_point(|node| position_of(node) < position);
+        let end = self.layout_nodes.partition_point(|node| position_of(node) <= position);
+        start..end
+    }
+
+    /// Scroll offset of a column
+    pub fn column_scroll(&self, id: ColumnId) -> f64 {
+        if id == self.columns.active {
+            self.scroll_offset
+        } else {
+            self.columns.stored_scroll(id)
+        }
+    }
+
+    /// Set the scroll offset of a column
+    pub fn set_column_scroll(&mut self, id: ColumnId, scroll_offset: f64) {
+        if id == self.columns.active {
+            self.scroll_offset = scroll_offset;
+        } else {
+            self.columns.store_scroll(id, scroll_offset);
+        }
+    }
+
+    /// Height of a column's cells (including title bars)
+    pub fn column_height(&self, id: ColumnId) -> i32 {
+        let range = self.column_range(id);
+        self.content_y_before(range.end) - self.content_y_before(range.start)
+    }
+
+    /// Horizontal span `(x, width)` of a column on the output
+    pub fn column_span(&self, id: ColumnId) -> (i32, i32) {
+        let position = self.columns.position(id).unwrap_or(0);
+        column_span(self.output_size.w, self.columns.len(), position)
+    }
+
+    /// Width of every column
+    pub fn column_width(&self) -> i32 {
+        column_span(self.output_size.w, self.columns.len(), 0).1
+    }
+
+    /// Left edge of the column holding the cell at `index`
+    pub fn cell_x(&self, index: usize) -> i32 {
+        self.layout_nodes
+            .get(index)
+            .map_or(0, |node| self.column_span(node.column).0)
+    }
+
+    /// The column under an X position on the output
+    pub fn column_at_x(&self, x: f64) -> ColumnId {
+        let width = self.column_width().max(1) as f64;
+        let position = ((x / width).max(0.0) as usize).min(self.columns.len() - 1);
+        self.columns.columns[position].id
+    }
+
+    /// Column x and screen top of every cell, stacking each column's cells
+    /// with `heights` from its own scroll offset
+    pub fn cell_origins(&self, heights: &[i32]) -> Vec<(i32, i32)> {
+        let mut origins = Vec::with_capacity(self.layout_nodes.len());
+        for id in self.columns.ids() {
+            let x = self.column_span(id).0;
+            let mut top = self.column_top() - self.column_scroll(id) as i32;
+            for index in self.column_range(id) {
+                origins.push((x, top));
+                top += heights.get(index).copied().unwrap_or(self.layout_nodes[index].height);
+            }
+        }
+        origins
+    }
+
+    /// Which cells are within `margin` of the viewport in their column
+    /// (see `layout::cells_near_viewport`)
+    pub fn cells_near_viewport(&self, viewport_height: i32, margin: i32) -> Vec<bool> {
+        let mut near = Vec::with_capacity(self.layout_nodes.len());
+        for id in self.columns.ids() {
+            let heights = self.layout_nodes[self.column_range(id)].iter().map(|node| node.height);
+            near.extend(cells_near_viewport(heights, self.column_scroll(id), viewport_height, margin));
+        }
+        near
+    }
+
+    /// Make `id` the active column, parking the current scroll offset with
+    /// the column it belongs to
+    pub fn activate_column(&mut self, id: ColumnId) {
+        if id == self.columns.active || self.columns.position(id).is_none() {
+            return;
+        }
+        let previous = self.columns.active;
+        self.columns.store_scroll(previous, self.scroll_offset);
+        self.scroll_offset = self.columns.stored_scroll(id);
+        self.columns.active = id;
+        // Scroll keys pressed for the other column don't carry over
+        self.pending_scroll_delta = 0.0;
+        tracing::debug!(from = ?previous, to = ?id, "active column changed");
+    }
+
+    /// Make the focused cell's column the active one
+    pub(super) fn activate_focused_column(&mut self) {
+        if let Some(column) = self.focused_index().map(|index| self.layout_nodes[index].column) {
+            self.activate_column(column);
+        }
+    }
+
+    /// Column a cell inserted at `index` joins: the column around the
+    /// insertion point, preferring the active one where two columns meet
+    pub(super) fn column_for_insert(&self, index: usize) -> ColumnId {
+        let above = index.checked_sub(1).and_then(|i| self.layout_nodes.get(i)).map(|node| node.column);
+        let below = self.layout_nodes.get(index).map(|node| node.column);
+        let active = self.columns.active;
+        match (above, below) {
+            (Some(above), Some(below)) if above == below => above,
+            _ if above == Some(active) || below == Some(active) => active,
+            _ => above.or(below).unwrap_or(active),
+        }
+    }
+
+    /// Append a cell at the bottom of the column `position` places from the
+    /// left, adding columns at the right edge until there is one there
+    pub fn push_cell_to_column(&mut self, cell: StackWindow, position: usize) -> CellId {
+        while self.columns.len() <= position {
+            self.columns.add(ColumnSide::Right);
+        }
+        let column = self.columns.columns[position].id;
+        let index = self.column_range(column).end;
+        let id = self.insert_cell(index, cell, 0);
+        self.layout_nodes[index].column = column;
+        id
+    }
+
+    /// Drop the column of a just removed cell if that was its last cell
+    pub(super) fn remove_column_if_empty(&mut self, id: ColumnId) {
+        if self.columns.len() <= 1 || !self.column_range(id).is_empty() {
+            return;
+        }
+        if id == self.columns.active {
+            let fallback = self
+                .columns
+                .neighbour(id, ColumnSide::Left)
+                .or_else(|| self.columns.neighbour(id, ColumnSide::Right));
+            if let Some(fallback) = fallback {
+                self.activate_column(fallback);
+            }
+        }
+        self.columns.remove(id);
+        tracing::info!(column = ?id, columns = self.columns.len(), "removed empty column");
+    }
+
+    /// Focus the cell last focused in the column on `side` of the focused
+    /// one (its top cell if none was). Returns false if there is no column there.
+    pub fn focus_column(&mut self, side: ColumnSide) -> bool {
+        let Some(focused) = self.focused_index() else {
+            return false;
+        };
+        let Some(target) = self.columns.neighbour(self.layout_nodes[focused].column, side) else {
+            return false;
+        };
+        let range = self.column_range(target);
+        let recent = self.cells_by_recent_focus().into_iter().find_map(|cell| {
+            self.cell_index(cell).filter(|index| range.contains(index))
+        });
+        let Some(index) = recent.or((!range.is_empty()).then_some(range.start)) else {
+            return false;
+        };
+        self.set_focus_by_index(index);
+        self.scroll_to_show_window_bottom(index);
+        true
+    }
+
+    /// Move the focused cell to the bottom of the column on `side`,
+    /// starting a new column at the edge if there is none. A cell alone in
+    /// the outermost column stays put. Returns whether the cell moved.
+    pub fn move_focused_cell_to_column(&mut self, side: ColumnSide) -> bool {
+        let Some(index) = self.focused_index() else {
+            return false;
+        };
+        let from = self.layout_nodes[index].column;
+        let alone = self.column_range(from).len() == 1;
+        let target = match self.columns.neighbour(from, side) {
+            Some(target) => target,
+            None if alone => return false,
+            None => self.columns.add(side),
+        };
+
+        // Bottom of the target column, as an index after removing the cell
+        let end = self.column_range(target).end;
+        let to = if end > index { end - 1 } else { end };
+        self.move_cell(index, to);
+        self.layout_nodes[to].column = target;
+
+        self.activate_column(target);
+        self.remove_column_if_empty(from);
+        let from_max = (self.column_height(from) - self.viewport_height()).max(0) as f64;
+        if self.column_scroll(from) > from_max {
+            self.set_column_scroll(from, from_max);
+        }
+        self.recalculate_layout();
+        self.scroll_to_show_window_bottom(to);
+        tracing::info!(from = ?from, to = ?target, columns = self.columns.len(), "moved cell to column");
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_are_added_at_the_edges() {
+        let mut columns = Columns::default();
+        let right = columns.add(ColumnSide::Right);
+        let left = columns.add(ColumnSide::Left);
+        assert_eq!(columns.ids().collect::<Vec<_>>(), vec![left, ColumnId(0), right]);
+        assert_eq!(columns.neighbour(ColumnId(0), ColumnSide::Left), Some(left));
+        assert_eq!(columns.neighbour(ColumnId(0), ColumnSide::Right), Some(right));
+        assert_eq!(columns.neighbour(right, ColumnSide::Right), None);
+        assert_eq!(columns.neighbour(left, ColumnSide::Left), None);
+    }
+
+    #[test]
+    fn the_last_column_stays() {
+        let mut columns = Columns::default();
+        let right = columns.add(ColumnSide::Right);
+        columns.remove(ColumnId(0));
+        columns.remove(right);
+        assert_eq!(columns.ids().collect::<Vec<_>>(), vec![right]);
+    }
+
+    #[test]
+    fn count_changes_are_reported_once() {
+        let mut columns = Columns::default();
+        assert!(!columns.take_count_change());
+        let right = columns.add(ColumnSide::Right);
+        assert!(columns.take_count_change());
+        assert!(!columns.take_count_change());
+        columns.remove(right);
+        assert!(columns.take_count_change());
+    }
+
+    #[test]
+    fn parked_scroll_offsets_are_kept_per_column() {
+        let mut columns = Columns::default();
+        let right = columns.add(ColumnSide::Right);
+        columns.store_scroll(right, 120.0);
+        assert_eq!(columns.stored_scroll(right), 120.0);
+        assert_eq!(columns.stored_scroll(ColumnId(0)), 0.0);
+    }
+}
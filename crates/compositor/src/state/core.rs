@@ -5,23 +5,30 @@
 use smithay::desktop::Window;
 use smithay::reexports::wayland_server::Resource;
 use smithay::wayland::shell::xdg::ToplevelSurface;
+use crate::cell_id::CellId;
 use crate::terminal_manager::TerminalId;
-use super::{FocusedWindow, LayoutNode, StackWindow, TermStack, WindowEntry, WindowState};
+use super::{LayoutNode, StackWindow, TermStack, WindowEntry, WindowState};
 
 impl TermStack {
     /// Add a new external window at the focused position
     pub fn add_window(&mut self, toplevel: ToplevelSurface) {
         let window = Window::new_wayland_window(toplevel.clone());
 
-        // Read pending values WITHOUT consuming them. This allows multi-window apps
-        // (like WebKitGTK-based surf) to have all their windows linked to the same
-        // output terminal. The values are cleared when a new GUI spawn happens.
-        let output_terminal = self.pending_window_output_terminal;
-        let command = self.pending_window_command.clone().unwrap_or_default();
-
-        // Only consume foreground flag for the FIRST window - subsequent windows
-        // from the same app should not steal focus
-        let is_foreground_gui = std::mem::take(&mut self.pending_gui_foreground);
+        // Find the launch this window belongs to from the client's process
+        // ancestry (or launch token). Launches stay queued after their first
+        // window, so multi-window apps (like WebKitGTK-based surf) have all
+        // their windows linked to the same output terminal. Only the FIRST
+        // window of a foreground launch takes focus.
+        let origin = toplevel
+            .wl_surface()
+            .client()
+            .and_then(|client| client.get_credentials(&self.display_handle).ok())
+            .map(|credentials| crate::gui_launch::ClientOrigin::of(credentials.pid))
+            .unwrap_or_default();
+        let launch = self.pending_launches.claim(&origin);
+        let output_terminal = launch.as_ref().map(|launch| launch.output_terminal);
+        let is_foreground_gui = launch.as_ref().is_some_and(|launch| launch.foreground);
+        let command = launch.map(|launch| launch.command).unwrap_or_default();
 
         // Mark that this output terminal is now linked to a window
         // (for foreground GUI fallback trigger - we only restore launcher on process exit
@@ -41,6 +48,13 @@ impl TermStack {
         // Initial height is 0 - will be updated when client commits its first buffer.
         let initial_height = 0u32;
 
+        // Rules on app_id/title are evaluated again once the client has set them
+        let app_id = super::external::toplevel_app_id(toplevel.wl_surface());
+        let title = super::external::toplevel_title(toplevel.wl_surface());
+        let rule = crate::config::resolve_window_rules(&self.window_rules, app_id.as_deref(), title.as_deref(), &command);
+        let window_rules_pending = app_id.is_none()
+            && self.window_rules.iter().any(crate::config::WindowRule::needs_client_properties);
+
         let entry = WindowEntry {
             surface: toplevel,
             window: window.clone(),
@@ -49,9 +63,20 @@ impl TermStack {
             },
             output_terminal,
             command: command.clone(),
+            custom_title: None,
             uses_csd: false, // Will be set by XdgDecorationHandler if client requests CSD
+            decoration_negotiated: false,
+            window_rule: None,
+            window_rules_pending,
             is_foreground_gui,
             launcher_terminal,
+            liveness: Default::default(),
+            mapped_location: None,
+            maximized: false,
+            fullscreen: false,
+            restore_height: None,
+            display_scale: None,
+            expanded: false,
         };
 
         // Keep the output terminal in the layout - its title bar shows the command
@@ -86,10 +111,11 @@ impl TermStack {
             self.focused_or_last()
         };
 
-        self.layout_nodes.insert(insert_index, LayoutNode {
-            cell: StackWindow::External(Box::new(entry)),
-            height: initial_height as i32,
-        });
+        let cell_id = self.insert_cell(insert_index, StackWindow::External(Box::new(entry)), initial_height as i32);
+        let insert_index = match rule {
+            Some(rule) => self.apply_window_rule(insert_index, rule),
+            None => insert_index,
+        };
 
         // For foreground GUI windows, focus the new window
         // For other windows (background GUI or regular), focus stays on existing cell
@@ -101,7 +127,7 @@ impl TermStack {
         // Note: with identity-based focus, we don't need to adjust for insertion
 
         // Signal main loop to scroll to show this new window and set keyboard focus if needed
-        self.new_external_window_index = Some(insert_index);
+        self.new_external_window = Some(cell_id);
         self.new_window_needs_keyboard_focus = is_foreground_gui;
 
         self.recalculate_layout();
@@ -125,15 +151,12 @@ impl TermStack {
         let insert_index = self.focused_or_last();
 
         // Insert with placeholder height 0, will be updated in next frame
-        self.layout_nodes.insert(insert_index, LayoutNode {
-            cell: StackWindow::Terminal(id),
-            height: 0,
-        });
+        self.insert_cell(insert_index, StackWindow::Terminal(id), 0);
 
         // With identity-based focus, the previously focused cell's identity is unchanged
         // If nothing was focused, focus the new cell
-        if self.focused_window.is_none() {
-            self.focused_window = Some(FocusedWindow::Terminal(id));
+        if self.focused_window().is_none() {
+            self.focus_terminal(id);
         }
 
         self.recalculate_layout();
@@ -160,21 +183,15 @@ impl TermStack {
     /// Remove terminals from layout_nodes by ID
     pub fn remove_terminals(&mut self, ids: &[TerminalId]) {
         for id in ids {
-            // Clear resize drag if it targets this terminal
-            self.clear_resize_drag_for_terminal(*id);
-
             if let Some(index) = self.layout_nodes.iter().position(|node| {
                 matches!(node.cell, StackWindow::Terminal(tid) if tid == *id)
             }) {
-                self.layout_nodes.remove(index);
+                self.remove_cell(index);
                 tracing::debug!(terminal_id = id.0, index, "terminal removed from layout");
 
                 // Clear focus if we're removing the focused terminal
-                if let Some(FocusedWindow::Terminal(focused_id)) = self.focused_window {
-                    if focused_id == *id {
-                        self.focused_window = None;
-                        self.invalidate_focused_index_cache();
-                    }
+                if self.focused_terminal() == Some(*id) {
+                    self.clear_focus();
                 }
             }
         }
@@ -253,15 +270,7 @@ impl TermStack {
         if let Some(index) = self.layout_nodes.iter().position(|node| {
             matches!(&node.cell, StackWindow::External(entry) if entry.surface.wl_surface() == surface)
         }) {
-            // Clear resize drag if it targets this window
-            if let Some(drag) = &self.resizing {
-                if drag.window_index == index {
-                    tracing::info!(index, "clearing resize drag for removed external window");
-                    self.resizing = None;
-                }
-            }
-
-            let (output_terminal, is_foreground_gui, launcher_terminal) = if let StackWindow::External(entry) = &self.layout_nodes.remove(index).cell {
+            let (output_terminal, is_foreground_gui, launcher_terminal) = if let StackWindow::External(entry) = &self.remove_cell(index).cell {
                 self.space.unmap_elem(&entry.window);
                 (entry.output_terminal, entry.is_foreground_gui, entry.launcher_terminal)
             } else {
@@ -305,7 +314,7 @@ impl TermStack {
 
             tracing::info!(
                 window_count = self.layout_nodes.len(),
-                focused = ?self.focused_window,
+                focused = ?self.focused_window(),
                 has_output_terminal = output_terminal.is_some(),
                 is_foreground_gui,
                 "external window removed"
@@ -328,17 +337,81 @@ impl TermStack {
         if let Some(index) = self.layout_nodes.iter().position(|node| {
             matches!(node.cell, StackWindow::Terminal(tid) if tid == id)
         }) {
-            self.clear_resize_drag_for_terminal(id);
-            self.layout_nodes.remove(index);
+            self.remove_cell(index);
             self.update_focus_after_removal(index);
             self.recalculate_layout();
 
             tracing::info!(
                 window_count = self.layout_nodes.len(),
-                focused = ?self.focused_window,
+                focused = ?self.focused_window(),
                 terminal_id = ?id,
                 "terminal removed"
             );
         }
     }
+
+    /// Insert a cell at `index`, returning its stable id
+    ///
+    /// The cell joins the column around `index` (see `column_for_insert()`).
+    pub fn insert_cell(&mut self, index: usize, cell: StackWindow, height: i32) -> CellId {
+        let column = self.column_for_insert(index);
+        let id = self.cells.insert(index);
+        self.layout_nodes.insert(index, LayoutNode { id, cell, height, column, tag: None });
+        self.reindex_cells_from(index + 1);
+        id
+    }
+
+    /// Append a cell at the bottom of the stack, returning its stable id
+    pub fn push_cell(&mut self, cell: StackWindow, height: i32) -> CellId {
+        self.insert_cell(self.layout_nodes.len(), cell, height)
+    }
+
+    /// Remove the cell at `index`, releasing its id
+    ///
+    /// A resize drag on the removed cell is cancelled, and a column left
+    /// without cells goes away.
+    pub fn remove_cell(&mut self, index: usize) -> LayoutNode {
+        let node = self.layout_nodes.remove(index);
+        self.cells.remove(node.id);
+        self.reindex_cells_from(index);
+        self.remove_column_if_empty(node.column);
+        if self.resizing.as_ref().is_some_and(|drag| drag.cell == node.id) {
+            tracing::info!(index, "clearing resize drag for removed cell");
+            self.resizing = None;
+        }
+        node
+    }
+
+    /// Move the cell at `from` to index `to`, keeping its id
+    pub fn move_cell(&mut self, from: usize, to: usize) {
+        let node = self.layout_nodes.remove(from);
+        let to = to.min(self.layout_nodes.len());
+        self.layout_nodes.insert(to, node);
+        self.reindex_cells_from(from.min(to));
+        self.invalidate_focused_index_cache();
+    }
+
+    /// Keep only the cells for which `keep` returns true
+    pub fn retain_cells(&mut self, mut keep: impl FnMut(&LayoutNode) -> bool) {
+        let mut index = 0;
+        while index < self.layout_nodes.len() {
+            if keep(&self.layout_nodes[index]) {
+                index += 1;
+            } else {
+                self.remove_cell(index);
+            }
+        }
+    }
+
+    /// Current index of a cell, or `None` if it was removed
+    pub fn cell_index(&self, id: CellId) -> Option<usize> {
+        self.cells.index_of(id)
+    }
+
+    /// Update registry indices of the cells at and below `start` after a shift
+    fn reindex_cells_from(&mut self, start: usize) {
+        for (index, node) in self.layout_nodes.iter().enumerate().skip(start) {
+            self.cells.set_index(node.id, index);
+        }
+    }
 }
@@ -1,9 +1,62 @@
 //! External window helpers for TermStack
 //!
-//! Handles external window-specific operations: CSD detection, activation, and hit testing.
+//! Handles external window-specific operations: CSD detection, window rules,
+//! activation, and hit testing.
+//!
+//! Whether a window gets our title bar is decided, in order of precedence, by
+//! the user's per-app choice (`Config::decorations`, toggled with a right-click
+//! on the title bar or Ctrl+Shift+D), a matching window rule, the mode
+//! negotiated over xdg-decoration, and finally the static `csd_apps` list for
+//! clients that never negotiate.
+//!
+//! Window rules are evaluated when a window is added. Most clients set their
+//! app_id and title only after creating the toplevel, so rules on those are
+//! evaluated again on the first commit that knows them.
 
+use smithay::reexports::wayland_protocols::xdg::decoration::zv1::server::zxdg_toplevel_decoration_v1::Mode as DecorationMode;
 use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel::State as ToplevelState;
+use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+use smithay::wayland::compositor::with_states;
+use smithay::wayland::shell::xdg::{SurfaceCachedState, ToplevelSurface, XdgToplevelSurfaceData};
 use super::{StackWindow, TermStack};
+use crate::config::{Config, Decorations, WindowPosition, WindowRule};
+
+/// App id of a toplevel, once the client has set one
+pub fn toplevel_app_id(surface: &WlSurface) -> Option<String> {
+    with_states(surface, |states| {
+        states
+            .data_map
+            .get::<XdgToplevelSurfaceData>()
+            .and_then(|data| data.lock().ok())
+            .and_then(|attrs| attrs.app_id.clone())
+    })
+}
+
+/// Minimum height the client requested with xdg_toplevel.set_min_size (0 if unconstrained)
+pub fn toplevel_min_height(surface: &WlSurface) -> i32 {
+    with_states(surface, |states| {
+        states.cached_state.get::<SurfaceCachedState>().current().min_size.h
+    })
+}
+
+/// Title of a toplevel, once the client has set one
+pub fn toplevel_title(surface: &WlSurface) -> Option<String> {
+    with_states(surface, |states| {
+        states
+            .data_map
+            .get::<XdgToplevelSurfaceData>()
+            .and_then(|data| data.lock().ok())
+            .and_then(|attrs| attrs.title.clone())
+    })
+}
+
+fn decoration_mode(uses_csd: bool) -> DecorationMode {
+    if uses_csd {
+        DecorationMode::ClientSide
+    } else {
+        DecorationMode::ServerSide
+    }
+}
 
 impl TermStack {
     /// Check if an app uses client-side decorations based on app_id pattern matching
@@ -20,6 +73,174 @@ impl TermStack {
         })
     }
 
+    /// The user's decoration choice for a window's app, if any
+    pub fn decoration_override(&self, surface: &WlSurface) -> Option<Decorations> {
+        let app_id = toplevel_app_id(surface)?;
+        self.decoration_overrides.get(&app_id).copied()
+    }
+
+    /// Decoration a window should switch to after a commit, if it differs
+    /// from the current one (app_id may only be known after the first commit)
+    pub(super) fn decoration_after_commit(&self, index: usize) -> Option<bool> {
+        let StackWindow::External(entry) = &self.layout_nodes.get(index)?.cell else {
+            return None;
+        };
+        let app_id = toplevel_app_id(entry.surface.wl_surface())?;
+        let chosen = self
+            .decoration_overrides
+            .get(&app_id)
+            .copied()
+            .or(entry.window_rule.as_ref().and_then(|rule| rule.decorations));
+        let uses_csd = match chosen {
+            Some(decorations) => decorations == Decorations::Client,
+            None if !entry.decoration_negotiated && self.is_csd_app(&app_id) => true,
+            None => return None,
+        };
+        (uses_csd != entry.uses_csd).then_some(uses_csd)
+    }
+
+    /// Record the mode a client asked for via xdg-decoration and answer with
+    /// the user's choice for the app if there is one, else the requested mode
+    pub(super) fn apply_negotiated_decoration(&mut self, toplevel: &ToplevelSurface, requested: DecorationMode) {
+        let surface = toplevel.wl_surface();
+        let rule_decorations = self.layout_nodes.iter().find_map(|node| match &node.cell {
+            StackWindow::External(entry) if entry.surface.wl_surface() == surface => {
+                entry.window_rule.as_ref().and_then(|rule| rule.decorations)
+            }
+            _ => None,
+        });
+        let overridden = self.decoration_override(surface).or(rule_decorations);
+        let uses_csd = match overridden {
+            Some(decorations) => decorations == Decorations::Client,
+            None => matches!(requested, DecorationMode::ClientSide),
+        };
+
+        for node in &mut self.layout_nodes {
+            if let StackWindow::External(entry) = &mut node.cell {
+                if entry.surface.wl_surface() == surface {
+                    entry.uses_csd = uses_csd;
+                    entry.decoration_negotiated = true;
+                    tracing::info!(
+                        ?requested,
+                        ?overridden,
+                        uses_csd,
+                        command = %entry.command,
+                        "decoration mode negotiated"
+                    );
+                    break;
+                }
+            }
+        }
+
+        toplevel.with_pending_state(|state| {
+            state.decoration_mode = Some(decoration_mode(uses_csd));
+        });
+        toplevel.send_configure();
+    }
+
+    /// Apply a window rule to the just-added window at `index`, which sits
+    /// directly above its parent; returns the window's new index
+    pub(super) fn apply_window_rule(&mut self, index: usize, rule: WindowRule) -> usize {
+        let last = self.layout_nodes.len().saturating_sub(1);
+        let target = match rule.position {
+            None | Some(WindowPosition::AboveParent) => index,
+            Some(WindowPosition::BelowParent) => (index + 1).min(last),
+            Some(WindowPosition::Top) => 0,
+            Some(WindowPosition::Bottom) => last,
+        };
+        if target != index {
+            self.move_cell(index, target);
+        }
+
+        let user_choice = match &self.layout_nodes[target].cell {
+            StackWindow::External(entry) => self.decoration_override(entry.surface.wl_surface()),
+            StackWindow::Terminal(_) => return target,
+        };
+        let height = rule.height;
+        if let StackWindow::External(entry) = &mut self.layout_nodes[target].cell {
+            if let Some(decorations) = rule.decorations.filter(|_| user_choice.is_none()) {
+                entry.uses_csd = decorations == Decorations::Client;
+                let uses_csd = entry.uses_csd;
+                entry.surface.with_pending_state(|state| {
+                    state.decoration_mode = Some(decoration_mode(uses_csd));
+                });
+            }
+            tracing::info!(command = %entry.command, from = index, to = target, ?rule, "applied window rule");
+            entry.window_rule = Some(rule);
+        }
+        if let Some(height) = height {
+            self.request_resize(target, height);
+        }
+        target
+    }
+
+    /// Evaluate rules on app_id/title once the client has set them
+    ///
+    /// Returns the window's index, which changes if a rule moves it.
+    pub(super) fn apply_pending_window_rules(&mut self, index: usize) -> usize {
+        let StackWindow::External(entry) = &mut self.layout_nodes[index].cell else {
+            return index;
+        };
+        if !entry.window_rules_pending {
+            return index;
+        }
+        let surface = entry.surface.wl_surface().clone();
+        let Some(app_id) = toplevel_app_id(&surface) else {
+            return index;
+        };
+        entry.window_rules_pending = false;
+
+        let title = toplevel_title(&surface);
+        let rule = crate::config::resolve_window_rules(&self.window_rules, Some(&app_id), title.as_deref(), &entry.command);
+        match rule {
+            Some(rule) if entry.window_rule.as_ref() != Some(&rule) => self.apply_window_rule(index, rule),
+            _ => index,
+        }
+    }
+
+    /// Switch a window between our title bar and the app's own decorations,
+    /// remembering the choice for its app in the config file
+    pub fn toggle_decorations(&mut self, index: usize) {
+        let Some(StackWindow::External(entry)) = self.layout_nodes.get_mut(index).map(|node| &mut node.cell) else {
+            return;
+        };
+        entry.uses_csd = !entry.uses_csd;
+        let uses_csd = entry.uses_csd;
+        entry.surface.with_pending_state(|state| {
+            state.decoration_mode = Some(decoration_mode(uses_csd));
+        });
+        entry.surface.send_pending_configure();
+
+        let app_id = toplevel_app_id(entry.surface.wl_surface());
+        tracing::info!(command = %entry.command, ?app_id, uses_csd, "decorations toggled");
+        self.redraw_requested = true;
+
+        // Windows without an app_id can be toggled but not remembered
+        let Some(app_id) = app_id else {
+            return;
+        };
+        let decorations = if uses_csd { Decorations::Client } else { Decorations::Server };
+        self.decoration_overrides.insert(app_id.clone(), decorations);
+        if let Err(e) = Config::save_decoration(&app_id, decorations) {
+            tracing::warn!(error = %e, app_id, "failed to save decoration override");
+        }
+    }
+
+    /// Show a scaled-down window (see `Config::max_window_height`) at full size
+    pub fn expand_window(&mut self, index: usize) {
+        let Some(node) = self.layout_nodes.get_mut(index) else {
+            return;
+        };
+        let StackWindow::External(entry) = &mut node.cell else {
+            return;
+        };
+        entry.expanded = true;
+        entry.display_scale = None;
+        let height = entry.state.current_height() as i32;
+        tracing::info!(index, height, command = %entry.command, "expanding scaled-down window");
+        self.external_window_resized = Some((node.id, height));
+    }
+
     /// Set the activated state on a toplevel window at the given index.
     /// Also clears the activated state from all other toplevels.
     /// This is required for GTK apps to run animations and handle input properly.
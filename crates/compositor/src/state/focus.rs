@@ -1,11 +1,142 @@
 //! Focus management for TermStack
 //!
 //! Handles focus tracking, focus changes, and keyboard focus updates.
+//!
+//! All focus state lives in `FocusManager`, which only changes through its
+//! transitions: `focus()` / `clear()` move cell focus, `keyboard_moved()`
+//! records where the seat's keyboard focus was handed. Cell focus changes
+//! first and the keyboard follows, either right away (clicks, focus keys) or
+//! in `sync_keyboard_focus()` at the end of the frame, so a path that forgets
+//! to move the keyboard can't leave it on a window that lost focus.
+//!
+//! It also remembers previously focused cells, most recent first, for the
+//! window switcher and for jumping back to the previous cell (like `cd -`).
+
+use std::cell::Cell;
 
 use smithay::utils::SERIAL_COUNTER;
 use smithay::reexports::wayland_server::Resource;
+use crate::cell_id::CellId;
 use crate::terminal_manager::TerminalId;
-use super::{FocusedWindow, StackWindow, TermStack};
+use super::{FocusedWindow, LayoutNode, StackWindow, TermStack};
+
+/// Cell focus and the keyboard focus that follows it
+#[derive(Debug, Default)]
+pub struct FocusManager {
+    /// Identity of the focused cell (stable across cell additions/removals)
+    focused: Option<FocusedWindow>,
+    /// Cached index of `focused` in `layout_nodes` (outer `None`: not computed)
+    cached_index: Cell<Option<Option<usize>>>,
+    /// Cell the keyboard was last handed to; `Some(None)` means the compositor
+    /// itself (terminals), `None` that it was never handed out
+    keyboard: Option<Option<FocusedWindow>>,
+    /// Previously focused cells, most recent first (may include closed ones)
+    history: Vec<FocusedWindow>,
+}
+
+/// Number of previously focused cells remembered
+const HISTORY_LEN: usize = 64;
+
+impl FocusManager {
+    /// The focused cell
+    pub fn current(&self) -> Option<&FocusedWindow> {
+        self.focused.as_ref()
+    }
+
+    /// The focused terminal, if the focused cell is a terminal
+    pub fn terminal(&self) -> Option<TerminalId> {
+        match self.focused {
+            Some(FocusedWindow::Terminal(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Move focus to `target`, returning whether it changed
+    pub fn focus(&mut self, target: FocusedWindow) -> bool {
+        if self.focused.as_ref() == Some(&target) {
+            return false;
+        }
+        tracing::debug!(from = ?self.focused, to = ?target, "focus changed");
+        self.history.retain(|window| *window != target);
+        if let Some(previous) = self.focused.take() {
+            self.history.insert(0, previous);
+            self.history.truncate(HISTORY_LEN);
+        }
+        self.focused = Some(target);
+        self.cached_index.set(None);
+        true
+    }
+
+    /// Drop focus, returning whether anything was focused
+    pub fn clear(&mut self) -> bool {
+        self.cached_index.set(None);
+        self.focused.take().is_some()
+    }
+
+    /// The focused cell, then previously focused ones, most recent first
+    pub fn recent(&self) -> impl Iterator<Item = &FocusedWindow> {
+        self.focused.iter().chain(&self.history)
+    }
+
+    /// Forget the cached index after `layout_nodes` changed
+    pub fn layout_changed(&self) {
+        self.cached_index.set(None);
+    }
+
+    /// Record that the seat's keyboard focus was handed to `target`
+    /// (`None` for the compositor's own terminals)
+    pub fn keyboard_moved(&mut self, target: Option<FocusedWindow>) {
+        self.keyboard = Some(target);
+    }
+
+    /// Where the keyboard should be for the current cell focus
+    fn keyboard_target(&self) -> Option<FocusedWindow> {
+        self.focused
+            .clone()
+            .filter(|focused| matches!(focused, FocusedWindow::External(_)))
+    }
+
+    /// Whether keyboard focus still has to follow a cell focus change
+    pub fn keyboard_out_of_sync(&self) -> bool {
+        self.keyboard.as_ref() != Some(&self.keyboard_target())
+    }
+
+    /// Index of the focused cell in `nodes`, cached until the next transition
+    fn index_in(&self, nodes: &[LayoutNode]) -> Option<usize> {
+        if let Some(cached) = self.cached_index.get() {
+            #[cfg(debug_assertions)]
+            {
+                let computed = self.compute_index(nodes);
+                debug_assert_eq!(
+                    cached, computed,
+                    "focused_index cache is stale: cached={:?}, computed={:?}, focused={:?}",
+                    cached, computed, self.focused
+                );
+            }
+            return cached;
+        }
+
+        let result = self.compute_index(nodes);
+        self.cached_index.set(Some(result));
+        result
+    }
+
+    fn compute_index(&self, nodes: &[LayoutNode]) -> Option<usize> {
+        let focused = self.focused.as_ref()?;
+        nodes.iter().position(|node| node_is(node, focused))
+    }
+}
+
+/// Whether `node` holds `window`
+fn node_is(node: &LayoutNode, window: &FocusedWindow) -> bool {
+    match (&node.cell, window) {
+        (StackWindow::Terminal(tid), FocusedWindow::Terminal(focused_tid)) => tid == focused_tid,
+        (StackWindow::External(entry), FocusedWindow::External(focused_id)) => {
+            entry.surface.wl_surface().id() == *focused_id
+        }
+        _ => false,
+    }
+}
 
 impl TermStack {
     /// Update focus after a cell is removed.
@@ -35,15 +166,16 @@ impl TermStack {
         self.set_focus_by_index(new_index);
     }
 
-    /// Focus previous visible cell, skipping hidden terminals
+    /// Focus previous visible cell in the focused column, skipping hidden terminals
     ///
     /// # Arguments
     /// * `is_terminal_visible` - Closure that returns true if a terminal ID is visible
     pub fn focus_prev<F: Fn(TerminalId) -> bool>(&mut self, is_terminal_visible: F) {
         if let Some(current) = self.focused_index() {
-            if current > 0 {
+            let start = self.column_range(self.layout_nodes[current].column).start;
+            if current > start {
                 // Search backward from previous index
-                for i in (0..current).rev() {
+                for i in (start..current).rev() {
                     let cell = &self.layout_nodes[i].cell;
                     let is_visible = match cell {
                         StackWindow::Terminal(id) => is_terminal_visible(*id),
@@ -62,14 +194,15 @@ impl TermStack {
         }
     }
 
-    /// Focus next visible cell, skipping hidden terminals
+    /// Focus next visible cell in the focused column, skipping hidden terminals
     ///
     /// # Arguments
     /// * `is_terminal_visible` - Closure that returns true if a terminal ID is visible
     pub fn focus_next<F: Fn(TerminalId) -> bool>(&mut self, is_terminal_visible: F) {
         if let Some(current) = self.focused_index() {
+            let end = self.column_range(self.layout_nodes[current].column).end;
             // Search forward from next index
-            for i in (current + 1)..self.layout_nodes.len() {
+            for i in (current + 1)..end {
                 let cell = &self.layout_nodes[i].cell;
                 let is_visible = match cell {
                     StackWindow::Terminal(id) => is_terminal_visible(*id),
@@ -110,15 +243,37 @@ impl TermStack {
             // Clear keyboard focus from external windows
             keyboard.set_focus(self, None, serial);
             self.deactivate_all_toplevels();
+            self.focus.keyboard_moved(None);
         } else {
             // Set keyboard focus on the wl_surface
             if let Some(surface) = wl_surface {
+                let id = surface.id();
                 keyboard.set_focus(self, Some(surface.clone()), serial);
                 self.activate_toplevel(focused_idx);
+                self.focus.keyboard_moved(Some(FocusedWindow::External(id)));
             }
         }
     }
 
+    /// Move keyboard focus to the focused cell if a focus change left it behind
+    pub fn sync_keyboard_focus(&mut self) {
+        if !self.focus.keyboard_out_of_sync() {
+            return;
+        }
+        if self.focused_index().is_some() {
+            tracing::debug!(focused = ?self.focus.current(), "syncing keyboard focus to focused cell");
+            self.update_keyboard_focus_for_focused_window();
+        } else if self.focus.current().is_none() {
+            // Nothing focused: keyboard goes back to the compositor
+            let serial = SERIAL_COUNTER.next_serial();
+            let seat = self.seat.clone();
+            if let Some(keyboard) = seat.get_keyboard() {
+                keyboard.set_focus(self, None, serial);
+            }
+            self.focus.keyboard_moved(None);
+        }
+    }
+
     /// Ensure the focused cell is visible
     fn ensure_focused_visible(&mut self) {
         if let Some(index) = self.focused_index() {
@@ -131,44 +286,19 @@ impl TermStack {
     /// Returns None if no cell is focused or if the focused cell no longer exists.
     /// Uses caching to avoid O(n) lookup on repeated calls within the same frame.
     pub fn focused_index(&self) -> Option<usize> {
-        // Check cache first
-        if let Some(cached) = self.cached_focused_index.get() {
-            // Debug assertion: verify cache consistency
-            #[cfg(debug_assertions)]
-            {
-                let computed = self.compute_focused_index();
-                debug_assert_eq!(
-                    cached, computed,
-                    "focused_index cache is stale: cached={:?}, computed={:?}, focused_window={:?}",
-                    cached, computed, self.focused_window
-                );
-            }
-            return cached;
-        }
-
-        // Compute and cache the result
-        let result = self.compute_focused_index();
-        self.cached_focused_index.set(Some(result));
-        result
+        self.focus.index_in(&self.layout_nodes)
     }
 
-    /// Compute focused index without caching (internal helper)
-    fn compute_focused_index(&self) -> Option<usize> {
-        let focused = self.focused_window.as_ref()?;
-        self.layout_nodes.iter().position(|node| match (&node.cell, focused) {
-            (StackWindow::Terminal(tid), FocusedWindow::Terminal(focused_tid)) => tid == focused_tid,
-            (StackWindow::External(entry), FocusedWindow::External(focused_id)) => {
-                entry.surface.wl_surface().id() == *focused_id
-            }
-            _ => false,
-        })
+    /// Identity of the focused cell
+    pub fn focused_window(&self) -> Option<&FocusedWindow> {
+        self.focus.current()
     }
 
     /// Invalidate the cached focused index.
     ///
-    /// Call this after any mutation to `layout_nodes` (insert/remove) or `focused_window`.
+    /// Call this after any mutation to `layout_nodes` (insert/remove).
     pub fn invalidate_focused_index_cache(&self) {
-        self.cached_focused_index.set(None);
+        self.focus.layout_changed();
     }
 
     /// Get the focused index or fallback to the end of the list.
@@ -181,45 +311,135 @@ impl TermStack {
 
     /// Set focus to the cell at the given index.
     ///
-    /// Extracts the cell's identity and stores it in focused_window.
+    /// Extracts the cell's identity and stores it in focused_window. The
+    /// cell's column becomes the active one.
     pub fn set_focus_by_index(&mut self, index: usize) {
         if let Some(node) = self.layout_nodes.get(index) {
-            self.focused_window = Some(match &node.cell {
+            let target = match &node.cell {
                 StackWindow::Terminal(id) => FocusedWindow::Terminal(*id),
                 StackWindow::External(entry) => {
                     // All external windows are now Wayland toplevels (via xwayland-satellite)
                     let surface = entry.surface.wl_surface();
                     FocusedWindow::External(surface.id())
                 }
-            });
-            // The cache now needs to resolve to `index`, but it's correct by construction
-            // since we just set focused_window to point to layout_nodes[index].
-            // We could set the cache directly here, but invalidating is safer.
-            self.invalidate_focused_index_cache();
+            };
+            self.focus.focus(target);
+            self.activate_focused_column();
+        }
+    }
+
+    /// All cells, most recently focused first; cells never focused follow in
+    /// stack order
+    pub fn cells_by_recent_focus(&self) -> Vec<CellId> {
+        let mut cells: Vec<CellId> = self
+            .focus
+            .recent()
+            .filter_map(|window| self.layout_nodes.iter().find(|node| node_is(node, window)))
+            .map(|node| node.id)
+            .collect();
+        for node in &self.layout_nodes {
+            if !cells.contains(&node.id) {
+                cells.push(node.id);
+            }
         }
+        cells
+    }
+
+    /// Focus the cell that was focused before the current one (like `cd -`),
+    /// skipping closed ones. Returns false if there is none.
+    pub fn focus_back(&mut self) -> bool {
+        let previous = self
+            .focus
+            .history
+            .iter()
+            .find_map(|window| self.layout_nodes.iter().position(|node| node_is(node, window)));
+        let Some(index) = previous else {
+            return false;
+        };
+        self.set_focus_by_index(index);
+        self.ensure_focused_visible();
+        true
+    }
+
+    /// Focus a terminal by ID.
+    pub fn focus_terminal(&mut self, id: TerminalId) {
+        self.focus.focus(FocusedWindow::Terminal(id));
+        self.activate_focused_column();
     }
 
     /// Clear focus (no cell focused).
     pub fn clear_focus(&mut self) {
-        self.focused_window = None;
-        self.invalidate_focused_index_cache();
+        self.focus.clear();
     }
 
     /// Check if the focused cell is a terminal
     pub fn is_terminal_focused(&self) -> bool {
-        matches!(self.focused_window, Some(FocusedWindow::Terminal(_)))
+        self.focus.terminal().is_some()
     }
 
     /// Check if the focused cell is an external window
     pub fn is_external_focused(&self) -> bool {
-        matches!(self.focused_window, Some(FocusedWindow::External(_)))
+        matches!(self.focus.current(), Some(FocusedWindow::External(_)))
     }
 
     /// Get the focused terminal ID, if any
     pub fn focused_terminal(&self) -> Option<TerminalId> {
-        match &self.focused_window {
-            Some(FocusedWindow::Terminal(id)) => Some(*id),
-            _ => None,
+        self.focus.terminal()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smithay::reexports::wayland_server::backend::ObjectId;
+
+    #[test]
+    fn transitions_report_changes() {
+        let mut focus = FocusManager::default();
+        assert!(focus.focus(FocusedWindow::Terminal(TerminalId(1))));
+        assert!(!focus.focus(FocusedWindow::Terminal(TerminalId(1))));
+        assert_eq!(focus.terminal(), Some(TerminalId(1)));
+
+        assert!(focus.clear());
+        assert!(!focus.clear());
+        assert_eq!(focus.current(), None);
+    }
+
+    #[test]
+    fn keyboard_follows_cell_focus() {
+        let mut focus = FocusManager::default();
+        // Never handed out
+        assert!(focus.keyboard_out_of_sync());
+        focus.keyboard_moved(None);
+        assert!(!focus.keyboard_out_of_sync());
+
+        // Terminals keep the keyboard with the compositor
+        focus.focus(FocusedWindow::Terminal(TerminalId(1)));
+        assert!(!focus.keyboard_out_of_sync());
+
+        let window = FocusedWindow::External(ObjectId::null());
+        focus.focus(window.clone());
+        assert!(focus.keyboard_out_of_sync());
+        focus.keyboard_moved(Some(window));
+        assert!(!focus.keyboard_out_of_sync());
+
+        // Back to a terminal: the window must give the keyboard up
+        focus.focus(FocusedWindow::Terminal(TerminalId(2)));
+        assert!(focus.keyboard_out_of_sync());
+    }
+
+    #[test]
+    fn history_is_most_recent_first() {
+        let mut focus = FocusManager::default();
+        for id in [1, 2, 3, 1] {
+            focus.focus(FocusedWindow::Terminal(TerminalId(id)));
+        }
+        let recent: Vec<_> = focus.recent().cloned().collect();
+        assert_eq!(recent, [1, 3, 2].map(|id| FocusedWindow::Terminal(TerminalId(id))));
+
+        for id in 0..100 {
+            focus.focus(FocusedWindow::Terminal(TerminalId(id)));
         }
+        assert_eq!(focus.recent().count(), HISTORY_LEN + 1);
     }
 }
@@ -0,0 +1,198 @@
+//! Maximized and fullscreen external windows
+//!
+//! A maximized window's cell is exactly as tall as the viewport; it stays in
+//! the column and scrolls like any other cell. A fullscreen window also loses
+//! its title bar, and the scroll offset is pinned to its cell so it covers the
+//! whole output. Fullscreen ends when the client asks for it or when focus
+//! moves to another cell.
+//!
+//! The cell height from before either state is remembered and restored once
+//! the window leaves both.
+
+use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel::State as ToplevelState;
+use smithay::utils::Size;
+use smithay::wayland::shell::xdg::ToplevelSurface;
+
+use super::{StackWindow, TermStack};
+use crate::title_bar::TITLE_BAR_HEIGHT;
+
+/// Cell height for a window in the given states
+///
+/// `None` means there's no height to go back to; the client picks its own.
+fn target_cell_height(maximized: bool, fullscreen: bool, viewport: u32, restore: Option<u32>) -> Option<u32> {
+    if maximized || fullscreen {
+        Some(viewport)
+    } else {
+        restore
+    }
+}
+
+impl TermStack {
+    /// Index of the external window for `surface`
+    fn toplevel_index(&self, surface: &ToplevelSurface) -> Option<usize> {
+        self.layout_nodes.iter().position(|node| {
+            matches!(&node.cell, StackWindow::External(entry) if entry.surface.wl_surface() == surface.wl_surface())
+        })
+    }
+
+    /// Handle a client's request to (un)maximize
+    pub fn set_maximized(&mut self, surface: &ToplevelSurface, maximized: bool) {
+        let Some(index) = self.toplevel_index(surface) else {
+            // The protocol wants a configure in reply even if nothing changes
+            surface.send_configure();
+            return;
+        };
+        let StackWindow::External(entry) = &mut self.layout_nodes[index].cell else {
+            return;
+        };
+        if entry.maximized == maximized {
+            entry.surface.send_configure();
+            return;
+        }
+
+        entry.maximized = maximized;
+        entry.surface.with_pending_state(|state| {
+            if maximized {
+                state.states.set(ToplevelState::Maximized);
+            } else {
+                state.states.unset(ToplevelState::Maximized);
+            }
+        });
+        tracing::info!(index, maximized, command = %entry.command, "maximize requested");
+        self.configure_window_states(index);
+    }
+
+    /// Handle a client's request to (un)fullscreen
+    pub fn set_fullscreen(&mut self, surface: &ToplevelSurface, fullscreen: bool) {
+        let Some(index) = self.toplevel_index(surface) else {
+            surface.send_configure();
+            return;
+        };
+
+        // Only one window can cover the output
+        if fullscreen {
+            let other = self.fullscreen.and_then(|id| self.cell_index(id)).filter(|&i| i != index);
+            if let Some(StackWindow::External(entry)) = other.map(|i| &self.layout_nodes[i].cell) {
+                let other_surface = entry.surface.clone();
+                self.set_fullscreen(&other_surface, false);
+            }
+        }
+
+        let id = self.layout_nodes[index].id;
+        let StackWindow::External(entry) = &mut self.layout_nodes[index].cell else {
+            return;
+        };
+        if entry.fullscreen == fullscreen {
+            entry.surface.send_configure();
+            return;
+        }
+
+        entry.fullscreen = fullscreen;
+        entry.surface.with_pending_state(|state| {
+            if fullscreen {
+                state.states.set(ToplevelState::Fullscreen);
+            } else {
+                state.states.unset(ToplevelState::Fullscreen);
+            }
+        });
+        tracing::info!(index, fullscreen, command = %entry.command, "fullscreen requested");
+
+        self.fullscreen = fullscreen.then_some(id);
+        if fullscreen {
+            self.set_focus_by_index(index);
+        }
+        self.configure_window_states(index);
+    }
+
+    /// Resize the window at `index` for its maximized/fullscreen states and
+    /// send the configure carrying them
+    fn configure_window_states(&mut self, index: usize) {
+        let viewport = self.viewport_height().max(0) as u32;
+        let width = self.column_width();
+        let node = &mut self.layout_nodes[index];
+        let StackWindow::External(entry) = &mut node.cell else {
+            return;
+        };
+
+        let in_state = entry.maximized || entry.fullscreen;
+        if in_state && entry.restore_height.is_none() && node.height > 0 {
+            entry.restore_height = Some(node.height as u32);
+        }
+        let target = target_cell_height(entry.maximized, entry.fullscreen, viewport, entry.restore_height);
+        if !in_state {
+            entry.restore_height = None;
+        }
+
+        let Some(height) = target else {
+            entry.surface.with_pending_state(|state| state.size = None);
+            entry.surface.send_pending_configure();
+            return;
+        };
+
+        // request_resize skips unchanged cell heights, but the surface height
+        // still changes when the title bar comes or goes
+        let surface_height = if entry.has_title_bar() {
+            height.saturating_sub(TITLE_BAR_HEIGHT)
+        } else {
+            height
+        };
+        entry.surface.with_pending_state(|state| {
+            state.size = Some(Size::from((width, surface_height as i32)));
+        });
+        self.request_resize(index, height);
+
+        if let StackWindow::External(entry) = &self.layout_nodes[index].cell {
+            entry.surface.send_pending_configure();
+        }
+    }
+
+    /// Keep a fullscreen window covering the output
+    ///
+    /// Call after the layout is recalculated. Ends fullscreen when the window
+    /// is gone or focus moved to another cell.
+    pub fn pin_fullscreen(&mut self) {
+        let Some(id) = self.fullscreen else {
+            return;
+        };
+        let Some(index) = self.cell_index(id) else {
+            tracing::debug!("fullscreen window closed");
+            self.fullscreen = None;
+            return;
+        };
+
+        if self.focused_index() != Some(index) {
+            if let StackWindow::External(entry) = &self.layout_nodes[index].cell {
+                let surface = entry.surface.clone();
+                self.set_fullscreen(&surface, false);
+            }
+            return;
+        }
+
+        // Scrolling would reveal the cells around it
+        self.pending_scroll_delta = 0.0;
+        let top = (self.column_content_y(index) as f64).min(self.max_scroll());
+        if (self.scroll_offset - top).abs() > 0.5 {
+            self.scroll_offset = top;
+            self.update_space_positions();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maximized_and_fullscreen_fill_the_viewport() {
+        assert_eq!(target_cell_height(true, false, 1080, Some(400)), Some(1080));
+        assert_eq!(target_cell_height(false, true, 1080, None), Some(1080));
+        assert_eq!(target_cell_height(true, true, 1080, Some(400)), Some(1080));
+    }
+
+    #[test]
+    fn leaving_both_states_restores_the_old_height() {
+        assert_eq!(target_cell_height(false, false, 1080, Some(400)), Some(400));
+        // Maximized before the first commit: nothing to restore
+        assert_eq!(target_cell_height(false, false, 1080, None), None);
+    }
+}
@@ -10,11 +10,15 @@
 //!
 //! Per xdg-shell spec: size=(width, 0) means width is constrained, height is client's choice.
 //! Apps should render at full width while using their preferred height.
+//!
+//! The cell takes the height the client commits. Before its first buffer,
+//! the client's xdg min height (if any) is reserved instead of 0.
 
 #[cfg(test)]
 mod tests {
     use smithay::utils::{Physical, Size};
     use crate::state::initial_configure_bounds;
+    use crate::window_height::initial_surface_height;
 
     /// Test: initial configure bounds match output size
     ///
@@ -134,6 +138,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn committed_height_is_used_as_is() {
+        // A dialog that commits 420px gets 420px, even with a smaller min size
+        assert_eq!(initial_surface_height(420, 200), 420);
+        assert_eq!(initial_surface_height(420, 0), 420);
+    }
+
+    #[test]
+    fn min_height_is_reserved_before_first_buffer() {
+        assert_eq!(initial_surface_height(0, 300), 300);
+        // No min size: nothing but the title bar until the client draws
+        assert_eq!(initial_surface_height(0, 0), 0);
+    }
+
     #[test]
     fn initial_configure_width_is_full_width() {
         let output_width = 1920;
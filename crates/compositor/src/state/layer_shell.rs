@@ -0,0 +1,212 @@
+//! Layer-shell surfaces: panels, docks, wallpapers and notifications
+//!
+//! Clients of `zwlr_layer_shell_v1` (status bars, notification daemons)
+//! anchor surfaces to the output's edges outside the scrollable column. They
+//! live in the output's `LayerMap`, which places them from their anchors and
+//! margins. A surface with an exclusive zone on the top or bottom edge
+//! reserves that strip (`layout::ReservedArea`): the column starts below the
+//! top strip and its viewport ends above the bottom one.
+//!
+//! Background and bottom layers are drawn under the cells, top and overlay
+//! layers over them. Pointer input on a layer surface goes to its client and
+//! never focuses a cell; layer surfaces don't take keyboard focus.
+
+use smithay::desktop::{layer_map_for_output, LayerSurface, WindowSurfaceType};
+use smithay::output::Output;
+use smithay::reexports::wayland_server::protocol::wl_output::WlOutput;
+use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+use smithay::reexports::wayland_server::DisplayHandle;
+use smithay::utils::{Logical, Point};
+use smithay::wayland::compositor::with_states;
+use smithay::wayland::shell::wlr_layer::{
+    Layer, LayerSurface as WlrLayerSurface, LayerSurfaceData, WlrLayerShellHandler, WlrLayerShellState,
+};
+
+use super::TermStack;
+use crate::layout::ReservedArea;
+
+/// Layers drawn over the cells
+pub const LAYERS_ABOVE_CELLS: [Layer; 2] = [Layer::Overlay, Layer::Top];
+
+/// Layers drawn under the cells
+pub const LAYERS_BELOW_CELLS: [Layer; 2] = [Layer::Bottom, Layer::Background];
+
+/// Layer-shell protocol state and the strips panels reserve
+pub struct LayerShell {
+    pub state: WlrLayerShellState,
+    reserved: ReservedArea,
+    /// Set when `reserved` changed, until the cells are refitted
+    reserved_changed: bool,
+    /// Set when a layer surface changed, until the next frame is drawn
+    damaged: bool,
+}
+
+impl LayerShell {
+    pub fn new(display_handle: &DisplayHandle) -> Self {
+        Self {
+            state: WlrLayerShellState::new::<TermStack>(display_handle),
+            reserved: ReservedArea::default(),
+            reserved_changed: false,
+            damaged: false,
+        }
+    }
+
+    /// Strips reserved by panels' exclusive zones
+    pub fn reserved(&self) -> ReservedArea {
+        self.reserved
+    }
+
+    /// Whether the reserved strips changed since the last call
+    pub fn take_reserved_change(&mut self) -> bool {
+        std::mem::take(&mut self.reserved_changed)
+    }
+
+    /// Whether a layer surface changed since the last call (layer surfaces
+    /// aren't damage-tracked, so the frame is redrawn in full)
+    pub fn take_damage(&mut self) -> bool {
+        std::mem::take(&mut self.damaged)
+    }
+}
+
+impl TermStack {
+    /// Screen Y where the column starts, below any top panel
+    pub fn column_top(&self) -> i32 {
+        self.layer_shell.reserved().top
+    }
+
+    /// Height of the column's viewport: the output minus the panels' strips
+    pub fn viewport_height(&self) -> i32 {
+        self.layer_shell.reserved().viewport_height(self.output_size.h)
+    }
+
+    /// Place the layer surfaces again (after one changed or the output was
+    /// resized) and update the strips they reserve
+    pub fn arrange_layers(&mut self) {
+        let Some(output) = self.space.outputs().next().cloned() else {
+            return;
+        };
+        let zone = {
+            let mut map = layer_map_for_output(&output);
+            map.arrange();
+            map.non_exclusive_zone()
+        };
+        let reserved = ReservedArea::from_zone(self.output_size.h, zone.loc.y, zone.size.h);
+        let layer_shell = &mut self.layer_shell;
+        layer_shell.damaged = true;
+        if reserved != layer_shell.reserved {
+            tracing::info!(top = reserved.top, bottom = reserved.bottom, "panel area changed");
+            layer_shell.reserved = reserved;
+            layer_shell.reserved_changed = true;
+        }
+        self.redraw_requested = true;
+    }
+
+    /// Handle a commit if `surface` is a layer surface: place it and answer
+    /// its first commit with the initial configure. Returns whether it was one.
+    pub(super) fn handle_layer_commit(&mut self, surface: &WlSurface) -> bool {
+        let Some(output) = self.space.outputs().next().cloned() else {
+            return false;
+        };
+        let Some(layer) = layer_map_for_output(&output)
+            .layer_for_surface(surface, WindowSurfaceType::TOPLEVEL)
+            .cloned()
+        else {
+            return false;
+        };
+        self.arrange_layers();
+
+        let initial_configure_sent = with_states(surface, |states| {
+            states
+                .data_map
+                .get::<LayerSurfaceData>()
+                .is_some_and(|data| data.lock().unwrap().initial_configure_sent)
+        });
+        if !initial_configure_sent {
+            layer.layer_surface().send_configure();
+        }
+        true
+    }
+
+    /// Layer surface (or subsurface) at a point on one of `layers`, topmost
+    /// first, with the surface's position in screen coordinates
+    ///
+    /// `point` is in render coordinates (Y=0 at bottom), like `surface_under`.
+    pub fn layer_surface_under(
+        &self,
+        point: Point<f64, Logical>,
+        layers: &[Layer],
+    ) -> Option<(WlSurface, Point<f64, Logical>)> {
+        let output = self.space.outputs().next()?;
+        let screen_point = Point::from((point.x, self.output_size.h as f64 - point.y));
+        let map = layer_map_for_output(output);
+        layers.iter().find_map(|&level| {
+            let layer = map.layer_under(level, screen_point)?;
+            let origin = map.layer_geometry(layer)?.loc;
+            let (surface, location) =
+                layer.surface_under(screen_point - origin.to_f64(), WindowSurfaceType::ALL)?;
+            Some((surface, (origin + location).to_f64()))
+        })
+    }
+
+    /// Whether a click at `point` (render coordinates) is meant for a panel:
+    /// it is on a layer surface over the cells or in a reserved strip
+    pub fn is_on_panel(&self, point: Point<f64, Logical>) -> bool {
+        let screen_y = self.output_size.h as f64 - point.y;
+        self.layer_shell.reserved().covers(screen_y, self.output_size.h)
+            || self.layer_surface_under(point, &LAYERS_ABOVE_CELLS).is_some()
+    }
+
+    /// Surfaces of all mapped layer surfaces, for frame callbacks
+    pub fn layer_surfaces(&self) -> Vec<WlSurface> {
+        let Some(output) = self.space.outputs().next() else {
+            return Vec::new();
+        };
+        layer_map_for_output(output).layers().map(|layer| layer.wl_surface().clone()).collect()
+    }
+}
+
+impl WlrLayerShellHandler for TermStack {
+    fn shell_state(&mut self) -> &mut WlrLayerShellState {
+        &mut self.layer_shell.state
+    }
+
+    fn new_layer_surface(
+        &mut self,
+        surface: WlrLayerSurface,
+        _output: Option<WlOutput>,
+        layer: Layer,
+        namespace: String,
+    ) {
+        // Single output: whichever output the client asked for, it's this one
+        let Some(output) = self.space.outputs().next().cloned() else {
+            tracing::warn!(namespace, "no output for layer surface");
+            surface.send_close();
+            return;
+        };
+        tracing::info!(namespace, ?layer, "layer surface created");
+        let layer_surface = LayerSurface::new(surface, namespace);
+        if let Err(e) = layer_map_for_output(&output).map_layer(&layer_surface) {
+            tracing::warn!(?e, "failed to map layer surface");
+            return;
+        }
+        self.arrange_layers();
+    }
+
+    fn layer_destroyed(&mut self, surface: WlrLayerSurface) {
+        let Some(output) = self.space.outputs().next().cloned() else {
+            return;
+        };
+        unmap_layer(&output, &surface);
+        tracing::info!("layer surface destroyed");
+        self.arrange_layers();
+    }
+}
+
+/// Remove the layer surface for `surface` from the output's layer map
+fn unmap_layer(output: &Output, surface: &WlrLayerSurface) {
+    let mut map = layer_map_for_output(output);
+    let layer = map.layers().find(|layer| layer.layer_surface() == surface).cloned();
+    if let Some(layer) = layer {
+        map.unmap_layer(&layer);
+    }
+}
@@ -20,16 +20,27 @@
 //! - Terminal content management (see `terminal_manager/` - terminal lifecycle)
 //! - Input event handling (see `input.rs` - keyboard/pointer events)
 
+mod columns;
 mod core;
 mod external;
 mod focus;
+mod fullscreen;
+mod layer_shell;
+mod popup;
 mod resize;
 #[cfg(test)]
 mod initial_size_test;
 
+pub use columns::{ColumnSide, Columns};
+pub use focus::FocusManager;
+pub use layer_shell::{LayerShell, LAYERS_ABOVE_CELLS, LAYERS_BELOW_CELLS};
+pub use external::toplevel_min_height;
+
 use smithay::delegate_compositor;
 use smithay::delegate_data_device;
+use smithay::delegate_layer_shell;
 use smithay::delegate_output;
+use smithay::delegate_presentation;
 use smithay::delegate_seat;
 use smithay::delegate_shm;
 use smithay::delegate_text_input_manager;
@@ -44,10 +55,11 @@ use smithay::reexports::calloop::LoopHandle;
 use smithay::reexports::wayland_protocols::xdg::decoration::zv1::server::zxdg_toplevel_decoration_v1::Mode as DecorationMode;
 use smithay::reexports::wayland_server::backend::{ClientData, ClientId, DisconnectReason};
 use smithay::reexports::wayland_server::protocol::wl_buffer::WlBuffer;
+use smithay::reexports::wayland_server::protocol::wl_output::WlOutput;
 use smithay::reexports::wayland_server::protocol::wl_seat::WlSeat;
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::reexports::wayland_server::{Display, DisplayHandle};
-use smithay::utils::{Logical, Physical, Point, Rectangle, Size};
+use smithay::utils::{Logical, Physical, Point, Serial, Size};
 use smithay::backend::renderer::utils::on_commit_buffer_handler;
 use smithay::wayland::buffer::BufferHandler;
 use smithay::wayland::compositor::{
@@ -58,7 +70,7 @@ use smithay::wayland::selection::data_device::{
 };
 use smithay::wayland::selection::SelectionHandler;
 use smithay::wayland::shell::xdg::{
-    PopupSurface, PositionerState, ToplevelSurface, XdgShellHandler,
+    Configure, PopupSurface, PositionerState, ShellClient, ToplevelSurface, XdgShellHandler,
     XdgShellState,
 };
 use smithay::wayland::shell::xdg::decoration::{XdgDecorationHandler, XdgDecorationState};
@@ -66,14 +78,17 @@ use smithay::wayland::shm::{ShmHandler, ShmState};
 use smithay::wayland::text_input::{TextInputManagerState, TextInputSeat};
 
 use std::os::unix::net::UnixStream;
+use std::rc::Rc;
 use std::sync::mpsc;
 use std::time::Instant;
 
 use std::collections::HashMap;
 
+use crate::cell_id::{CellId, CellRegistry};
+use crate::color_tags::ColorTag;
 use crate::title_bar::TitleBarCharInfo;
-use crate::ipc::{BuiltinRequest, ResizeMode, SpawnRequest};
-use crate::layout::ColumnLayout;
+use crate::ipc::{BuiltinRequest, ExportFormat, ResizeMode, SpawnRequest};
+use crate::layout::{ColumnId, HeightIndex};
 use crate::terminal_manager::TerminalId;
 
 /// Selection drag state: (terminal_id, window_render_y, window_height, start_col, start_row, last_col, last_row, last_update_time)
@@ -127,6 +142,12 @@ pub struct CrossSelection {
     /// Active selections are cleared by `clear_stale_drag_state` if button is released.
     /// Completed selections persist until the next selection starts.
     pub active: bool,
+    /// Rectangular selection (Alt+drag) of a block of columns, kept within
+    /// the terminal it started in
+    pub block: bool,
+    /// Pointer position (render x, y) of the last drag motion, for scrolling
+    /// a terminal while the selection is held past its edge
+    pub pointer: Option<(f64, f64)>,
 }
 
 impl CrossSelection {
@@ -143,6 +164,8 @@ impl CrossSelection {
             },
             last_update: Instant::now(),
             active: true,
+            block: false,
+            pointer: None,
         }
     }
 
@@ -235,20 +258,30 @@ pub enum FocusedWindow {
 
 /// Active resize drag state
 pub struct ResizeDrag {
-    /// Index of the cell being resized
-    pub window_index: usize,
-    /// Identity of the window being resized (for stale index detection)
-    pub window_identity: FocusedWindow,
+    /// The cell being resized
+    pub cell: CellId,
     /// Initial pointer Y in screen coordinates (Y=0 at top)
     pub start_screen_y: i32,
     /// Cell height when drag started
     pub start_height: i32,
-    /// Last time we sent a configure request (for throttling)
-    pub last_configure_time: std::time::Instant,
     /// Current drag target height (may differ from committed height)
     pub target_height: i32,
     /// Last height we sent in configure (for deduplication)
     pub last_sent_height: Option<u32>,
+    /// Driven by keyboard resize mode rather than the pointer
+    pub keyboard: bool,
+}
+
+impl ResizeDrag {
+    /// Height for this frame's configure of an external window, if one is due.
+    ///
+    /// At most one configure goes out per frame, and only once the window has
+    /// committed the previous one (or `CONFIGURE_COMMIT_TIMEOUT_MS` has passed),
+    /// so a slow client only ever sees the latest target.
+    pub fn configure_due(&self, in_flight: bool) -> Option<u32> {
+        let target = self.target_height as u32;
+        (target > 0 && !in_flight && self.last_sent_height != Some(target)).then_some(target)
+    }
 }
 
 /// Maximum time to wait for a window commit before sending next configure (milliseconds)
@@ -259,16 +292,6 @@ pub const CONFIGURE_COMMIT_TIMEOUT_MS: u64 = 100;
 /// Minimum cell height (pixels)
 pub const MIN_WINDOW_HEIGHT: i32 = 50;
 
-/// xwayland-satellite process monitor with crash tracking
-pub struct XWaylandSatelliteMonitor {
-    /// The xwayland-satellite process handle
-    pub child: std::process::Child,
-    /// Timestamp of last crash (for detecting rapid crash loops)
-    pub last_crash_time: Option<Instant>,
-    /// Number of rapid crashes (within 10s window)
-    pub crash_count: u32,
-}
-
 /// Main compositor state
 pub struct TermStack {
     /// Wayland display handle
@@ -287,6 +310,9 @@ pub struct TermStack {
     pub text_input_state: TextInputManagerState,
     pub viewporter_state: smithay::wayland::viewporter::ViewporterState,
 
+    /// Panels and other layer-shell surfaces (see `layer_shell`)
+    pub layer_shell: LayerShell,
+
     /// Desktop space for managing external windows
     pub space: Space<Window>,
 
@@ -299,18 +325,42 @@ pub struct TermStack {
     /// After any mutation (insert/remove), must call recalculate_layout() to update positions.
     pub layout_nodes: Vec<LayoutNode>,
 
-    /// Current scroll offset (pixels from top)
+    /// Maps the `CellId` of each layout node to its current index
+    ///
+    /// Kept in sync by `insert_cell()` / `remove_cell()`; don't insert into or
+    /// remove from `layout_nodes` directly.
+    pub cells: CellRegistry,
+
+    /// Current scroll offset of the active column (pixels from top)
     pub scroll_offset: f64,
 
-    /// Identity of focused cell (stable across cell additions/removals)
-    pub focused_window: Option<FocusedWindow>,
+    /// Side-by-side columns and the other columns' scroll offsets (see `columns`)
+    pub columns: Columns,
+
+    /// Cell focus and keyboard focus (see `focus::FocusManager`)
+    pub focus: FocusManager,
 
-    /// Cached focused index (lazily computed from focused_window)
-    /// Use `focused_index()` to access; use `invalidate_focused_index_cache()` after mutations
-    cached_focused_index: std::cell::Cell<Option<Option<usize>>>,
+    /// External plugins: their key bindings and title bar badges
+    pub plugins: crate::plugins::PluginHost,
 
-    /// Cached layout calculation
-    pub layout: ColumnLayout,
+    /// Heights and positions of the cells as last laid out for a frame
+    /// (see `frame_layout`)
+    pub frame_layout: Rc<crate::frame_layout::FrameLayout>,
+
+    /// Heights given to cells between frame layouts (resize drags, terminal
+    /// resizes, window commits); inputs to `FrameLayout::compute()` (see
+    /// `request_window_height()`)
+    pub height_requests: crate::frame_layout::HeightRequests,
+
+    /// Cell whose bottom to scroll into view once the next frame layout is
+    /// committed (see `reveal_after_layout()`)
+    pub pending_reveal: Option<CellId>,
+
+    /// Cumulative cell heights for O(log n) position lookups
+    ///
+    /// Synced with `layout_nodes` by `recalculate_layout()` and
+    /// `commit_frame_layout()`, the only places cell heights change.
+    pub height_index: HeightIndex,
 
     /// Output dimensions
     pub output_size: Size<i32, Physical>,
@@ -327,51 +377,119 @@ pub struct TermStack {
     /// Focus navigation request (1 = next, -1 = prev)
     pub focus_change_requested: i32,
 
+    /// Parent/child focus navigation request
+    pub family_focus_requested: Option<crate::input_handler::FamilyFocus>,
+
+    /// Switch the focused cell between its output and its formatted view
+    /// (set by keybinding, applied in main loop, see `smart_output`)
+    pub smart_view_toggle_requested: bool,
+
+    /// Column focus or cell move request
+    pub column_requested: Option<crate::input_handler::ColumnRequest>,
+
+    /// Flag to merge the focused command cell into its shell (set by input handler)
+    pub absorb_requested: bool,
+
     /// Accumulated scroll delta from input events (applied once per frame to avoid repeated layout recalc)
     pub pending_scroll_delta: f64,
 
     /// Pending terminal spawn requests from IPC (termstack commands with foreground=None)
     pub pending_spawn_requests: Vec<SpawnRequest>,
 
+    /// Spawn requests for X11 apps waiting for XWayland to come up (see
+    /// `XWaylandSupervisor::defer_x11_spawn`)
+    pub deferred_x11_spawn_requests: Vec<SpawnRequest>,
+
     /// Pending GUI spawn requests from IPC (termstack commands with foreground=Some(_))
     pub pending_gui_spawn_requests: Vec<SpawnRequest>,
 
     /// Pending builtin command notifications from IPC (termstack --builtin)
     pub pending_builtin_requests: Vec<BuiltinRequest>,
 
+    /// Pending calculator answers from IPC (termstack --calc)
+    pub pending_calc_requests: Vec<BuiltinRequest>,
+
+    /// Calculator answer entries and when they are dismissed
+    pub calc_results: Vec<(TerminalId, Instant)>,
+
     /// Pending resize request from IPC (termstack resize)
     /// Includes the stream for sending acknowledgement after resize completes
     pub pending_resize_request: Option<(ResizeMode, UnixStream)>,
 
-    /// Index of newly added external window (for scroll-to-show)
-    pub new_external_window_index: Option<usize>,
+    /// Pending cell exports from IPC (termstack export): index, format and
+    /// the stream the content is sent back on
+    pub pending_export_requests: Vec<(usize, ExportFormat, UnixStream)>,
 
-    /// Whether the new external window needs keyboard focus (for foreground GUI)
-    pub new_window_needs_keyboard_focus: bool,
+    /// IPC clients waiting for a layout snapshot (termstack ls)
+    pub pending_layout_requests: Vec<UnixStream>,
+
+    /// IPC clients waiting for a layout file (termstack layout export)
+    pub pending_layout_exports: Vec<UnixStream>,
+
+    /// Screen points to hit-test from IPC (termstack hit-test) with the
+    /// stream the reports are sent back on
+    pub pending_hit_tests: Vec<(Vec<(f64, f64)>, UnixStream)>,
+
+    /// Pending layout imports from IPC (termstack layout import) with the
+    /// stream the result is sent back on
+    pub pending_layout_imports: Vec<(crate::layout_file::LayoutFile, UnixStream)>,
+
+    /// Pending color tags from IPC (termstack tag): index, tag (`None`
+    /// removes it) and the stream the result is sent back on
+    pub pending_tag_requests: Vec<(usize, Option<ColorTag>, UnixStream)>,
 
-    /// Index and new height of resized external window (for scroll adjustment)
-    pub external_window_resized: Option<(usize, i32)>,
+    /// Pending cell names from IPC (termstack title): index, title and the
+    /// stream the result is sent back on
+    pub pending_title_requests: Vec<(usize, String, UnixStream)>,
 
-    /// Pending output terminal to link with the next external window
-    /// Set when spawning a GUI app command, consumed by add_window()
-    pub pending_window_output_terminal: Option<TerminalId>,
+    /// Input event log, when TERMSTACK_RECORD_INPUT is set
+    pub input_recorder: Option<crate::input_log::InputRecorder>,
 
-    /// Pending command string for the next external window's title bar
-    /// Set when spawning a GUI app command, consumed by add_window()
-    pub pending_window_command: Option<String>,
+    /// IPC clients waiting for the focused cell's status
+    pub status_feed: crate::now_running::StatusFeed,
 
-    /// Whether the next window should be treated as a foreground GUI
-    /// Set when processing a gui_spawn request, consumed by add_window()
-    pub pending_gui_foreground: bool,
+    /// IPC clients following the accessibility tree (screen reader bridges)
+    pub accessibility_feed: crate::now_running::StatusFeed<crate::accessibility::AccessTree>,
 
-    /// When the pending window state was set (for timeout detection)
-    pub pending_window_set_at: Option<Instant>,
+    /// Screen reader access over AT-SPI, started on the first frame
+    #[cfg(target_os = "linux")]
+    pub atspi: Option<crate::accessibility::AtSpiBridge>,
+
+    /// Desktop color scheme change not yet applied (see `system_theme`)
+    pub pending_color_scheme: Option<crate::system_theme::ColorScheme>,
+
+    /// Surfaces taller than this are shown scaled down (0 = unlimited,
+    /// see `Config::max_window_height`)
+    pub max_window_height: u32,
+
+    /// External window that has taken over the whole output
+    ///
+    /// While set, the scroll offset is pinned to the window's cell, which is
+    /// exactly one output tall and has no title bar, so no other cell shows.
+    pub fullscreen: Option<CellId>,
+
+    /// Newly added external window (for scroll-to-show)
+    pub new_external_window: Option<CellId>,
+
+    /// Whether the new external window needs keyboard focus (for foreground GUI)
+    pub new_window_needs_keyboard_focus: bool,
+
+    /// Resized external window and its new height (for scroll adjustment)
+    pub external_window_resized: Option<(CellId, i32)>,
+
+    /// Spawned commands waiting for their windows, matched by launch token
+    /// Pushed when spawning a GUI app command, claimed by add_window()
+    pub pending_launches: crate::gui_launch::PendingLaunches,
 
     /// Maps output_terminal_id -> (launching_terminal_id, window_was_linked)
     /// For restoring launcher when GUI exits. The bool tracks whether a window
     /// was ever linked to this output terminal.
     pub foreground_gui_sessions: HashMap<TerminalId, (TerminalId, bool)>,
 
+    /// Foreground GUI sessions with neither a running command nor a live
+    /// window, and since when (the launcher is restored after a grace period)
+    pub orphaned_gui_sessions: HashMap<TerminalId, Instant>,
+
     /// Output terminals from closed windows that need cleanup
     /// Processed in main loop - if terminal has no content, remove it; otherwise keep visible
     pub pending_output_terminal_cleanup: Vec<TerminalId>,
@@ -385,6 +503,15 @@ pub struct TermStack {
     /// via clipboard_receiver.
     pub clipboard: Option<arboard::Clipboard>,
 
+    /// Where finished selections are copied (from config)
+    pub copy_options: crate::selection::CopyOptions,
+
+    /// Punctuation double-click selection treats as part of words (from config)
+    pub word_chars: String,
+
+    /// Time and render position of the last left click, for double-clicks
+    pub last_click: Option<(Instant, f64, f64)>,
+
     /// Receiver for async clipboard read results.
     /// When pending_paste is triggered, a background thread reads the clipboard
     /// and sends the result here to avoid blocking the compositor.
@@ -448,25 +575,111 @@ pub struct TermStack {
     /// Set when the compositor's own window is resized (X11Event::Resized), processed in main loop
     pub compositor_window_resize_pending: Option<(u16, u16)>,
 
+    /// Virtual terminal to switch to (Ctrl+Alt+F<n>), processed in main loop
+    /// Only the DRM backend owns a VT; the others ignore it
+    pub pending_vt_switch: Option<i32>,
+
+    /// Whether the backend runs on a virtual terminal of its own (DRM), so
+    /// Ctrl+Alt+F<n> switches VTs instead of reaching the focused client
+    pub owns_vt: bool,
+
     /// App IDs that use client-side decorations (from config)
     pub csd_apps: Vec<String>,
 
+    /// Environment overrides for GUI apps (from config)
+    pub gui_env: Vec<crate::config::GuiEnvOverride>,
+
+    /// Whether foreground GUI launches hide their launcher (from config)
+    pub hide_gui_launcher: bool,
+
+    /// Rules for placing new windows (from config)
+    pub window_rules: Vec<crate::config::WindowRule>,
+
+    /// Per-app decoration choices by app_id (from config, updated when toggled)
+    pub decoration_overrides: std::collections::BTreeMap<String, crate::config::Decorations>,
+
     /// Maximum number of GUI windows allowed (from config)
     pub max_gui_windows: usize,
 
     // XWayland support (via xwayland-satellite)
-    /// xwayland-satellite process monitor (acts as X11 WM, presents X11 windows as Wayland)
-    /// Includes crash tracking for auto-restart with backoff
-    pub xwayland_satellite: Option<XWaylandSatelliteMonitor>,
+    /// XWayland and xwayland-satellite (acts as X11 WM, presents X11 windows as Wayland),
+    /// restarted with backoff when the satellite crashes
+    pub xwayland: crate::xwayland_supervisor::XWaylandSupervisor,
+
+    /// SIGCHLD state for reaping child processes
+    pub child_reaper: crate::child_reaper::ChildReaper,
+
+    /// Rate limiting for unresponsive cell detection
+    pub watchdog: crate::watchdog::Watchdog,
 
-    /// X11 display number (e.g., 0 for :0)
-    pub x11_display_number: Option<u32>,
+    /// Set by event sources when something may have changed on screen
+    pub redraw_requested: bool,
+
+    /// Calloop sources that wake the main loop on PTY output
+    pub pty_sources: crate::event_sources::PtySources,
 
     /// Flag to spawn initial terminal (set when XWayland is ready)
     pub spawn_initial_terminal: bool,
 
     /// Pending font size change delta (set by keybinding, applied in main loop)
     pub pending_font_size_delta: f32,
+
+    /// Pages to scroll the focused terminal's scrollback, up positive
+    /// (set by keybinding, applied in main loop)
+    pub pending_scrollback_pages: i32,
+
+    /// Whether the frame-time HUD overlay is shown (toggled by keybinding)
+    pub hud_visible: bool,
+
+    /// Whether the keybinding help overlay is shown (toggled by keybinding, Esc closes)
+    pub help_visible: bool,
+
+    /// Drop-down scratch terminal shown over the stack (toggled by keybinding)
+    pub quick_terminal: crate::quick_terminal::QuickTerminal,
+
+    /// Do-not-disturb and focus mode (toggled by keybinding or IPC)
+    pub do_not_disturb: crate::do_not_disturb::DoNotDisturb,
+
+    /// Keyboard-only resize mode for the focused cell (toggled by keybinding)
+    pub keyboard_resize: crate::keyboard_resize::KeyboardResize,
+
+    /// Alt+Tab window switcher overlay
+    pub window_switcher: crate::window_switcher::WindowSwitcher,
+
+    /// Showing only the cells of one tag or command (see `cell_filter`)
+    pub filter_view: crate::cell_filter::FilterView,
+
+
+    /// Cells of trivially short commands, closed after a moment
+    pub ephemeral_cells: crate::ephemeral::EphemeralCells,
+
+    /// Recent spawns, to drop repeats from a double Enter
+    pub spawn_dedup: crate::spawn_handler::SpawnDedup,
+
+    /// Stack-wide actions from IPC and collapsed cells
+    pub bulk_actions: crate::bulk_actions::BulkActions,
+
+    /// Recently closed cells and collapses, for undo
+    pub undo_stack: crate::undo::UndoStack,
+
+    /// Output of closed command cells, for `termstack archive`
+    pub archive: crate::archive::Archive,
+
+    /// Open a fresh shell when the last cell exits instead of shutting down
+    pub respawn_last_shell: bool,
+
+    /// Prompt between the first and second press of the quit binding
+    pub quit_confirm: crate::quit::QuitConfirm,
+
+    /// Refresh rate of the output in mHz, pacing frames (see `crate::refresh`)
+    pub refresh: i32,
+
+    /// `wp_presentation` feedback and frame callback timing
+    pub presentation: crate::presentation::Presentation,
+
+    /// Focused terminal waiting for the echo of a key, drawn ahead of the
+    /// frame cadence
+    pub typing_echo: crate::typing_echo::TypingEcho,
 }
 
 /// A node in the column layout containing the cell and its cached height.
@@ -483,19 +696,26 @@ pub struct TermStack {
 ///    using element geometry. If click detection used different heights (e.g., from
 ///    `bbox()` which can differ), clicks would hit the wrong cells.
 ///
-/// 3. **Frame-to-frame lag is acceptable**: Heights are updated at the start of each
-///    frame via `update_layout_heights()`. This means click detection uses heights from
-///    frame N-1, but since we also rendered with those heights in frame N-1, coordinates
-///    remain consistent.
+/// 3. **One source per frame**: Heights are worked out once per frame by
+///    `FrameLayout::compute()` and applied by `commit_frame_layout()`, which also keeps
+///    the layout as the `frame_layout` snapshot the renderer draws from. Click detection
+///    between frames looks points up in that snapshot, and code changing a cell's size
+///    passes the new height in with `request_window_height()` rather than writing it.
 ///
 /// **Do not** read heights from `Terminal.bbox()` or `WindowState.current_height()` for
 /// positioning calculations - always use `LayoutNode.height` which matches what was
 /// actually rendered.
 pub struct LayoutNode {
+    /// Stable identity (see `TermStack::cell_index()`)
+    pub id: CellId,
     pub cell: StackWindow,
-    /// Cached height from last render frame. Used for layout, click detection, and scroll.
-    /// Updated by `update_layout_heights()` at the start of each frame.
+    /// Cached height from the last committed frame layout. Used for layout and
+    /// scroll. Updated by `commit_frame_layout()` once per frame.
     pub height: i32,
+    /// Column the cell is shown in
+    pub column: ColumnId,
+    /// Color the user tagged the cell with (see `color_tags`)
+    pub tag: Option<ColorTag>,
 }
 
 /// All external windows (including X11 apps via xwayland-satellite)
@@ -519,9 +739,22 @@ pub struct WindowEntry {
     /// Command that spawned this window (for title bar display)
     pub command: String,
 
+    /// Name the user gave the cell, shown instead of `command`
+    pub custom_title: Option<String>,
+
     /// Whether window uses client-side decorations (skip our title bar if true)
     pub uses_csd: bool,
 
+    /// Whether the client negotiated a mode via xdg-decoration
+    /// (takes precedence over the static `csd_apps` list)
+    pub decoration_negotiated: bool,
+
+    /// Combined window rules applied to this window (see `Config::window_rules`)
+    pub window_rule: Option<crate::config::WindowRule>,
+
+    /// Whether rules on app_id/title still need evaluating (client hadn't set them yet)
+    pub window_rules_pending: bool,
+
     /// Whether this window was launched in foreground mode
     /// (launching terminal is hidden and should be restored when this window closes)
     pub is_foreground_gui: bool,
@@ -529,6 +762,56 @@ pub struct WindowEntry {
     /// The terminal that launched this GUI app (only set for foreground GUI)
     /// This terminal is hidden while the GUI runs and restored when the window closes
     pub launcher_terminal: Option<TerminalId>,
+
+    /// Whether the client keeps up with configures (see `watchdog`)
+    pub liveness: crate::watchdog::Liveness,
+
+    /// Render location `(x, y)` the window was last mapped at in the Space
+    /// (skips remapping windows that didn't move)
+    pub mapped_location: Option<(i32, i32)>,
+
+    /// Whether the client asked to be maximized (cell fills the viewport)
+    pub maximized: bool,
+
+    /// Whether the client is fullscreen (see `TermStack::fullscreen`)
+    pub fullscreen: bool,
+
+    /// Cell height to go back to once neither maximized nor fullscreen
+    pub restore_height: Option<u32>,
+
+    /// Scale the window is shown at while taller than `Config::max_window_height`
+    /// (`None` = full size); clicking the window expands it
+    pub display_scale: Option<f64>,
+
+    /// Whether the user expanded the window after it was shown scaled down
+    pub expanded: bool,
+}
+
+impl WindowEntry {
+    /// Whether we draw a title bar above the window
+    pub fn has_title_bar(&self) -> bool {
+        !self.uses_csd && !self.fullscreen
+    }
+
+    /// Cell height as shown, for a cell height in surface pixels
+    pub fn displayed_height(&self, cell_height: u32) -> u32 {
+        let title_bar = if self.has_title_bar() { crate::title_bar::TITLE_BAR_HEIGHT } else { 0 };
+        crate::window_height::scaled_cell_height(cell_height, title_bar, self.display_scale)
+    }
+
+    /// The cell's custom name, else the command
+    pub fn display_title(&self) -> &str {
+        self.custom_title.as_deref().unwrap_or(&self.command)
+    }
+
+    /// Title bar text: the display title, marked when frozen or scaled down
+    pub fn title_bar_text(&self) -> std::borrow::Cow<'_, str> {
+        let title = self.liveness.decorate_title(self.display_title());
+        match self.display_scale {
+            Some(_) => format!("{}{}", title, crate::window_height::SCALED_DOWN_SUFFIX).into(),
+            None => title,
+        }
+    }
 }
 
 /// Timeout for pending resize operations (milliseconds)
@@ -561,7 +844,8 @@ pub enum WindowState {
     PendingResize {
         current_height: u32,
         requested_height: u32,
-        request_serial: u32,
+        /// Serial of the configure carrying the request
+        request_serial: Serial,
         /// When the resize was requested, for timeout detection
         requested_at: Instant,
     },
@@ -570,6 +854,9 @@ pub enum WindowState {
     AwaitingCommit {
         current_height: u32,
         target_height: u32,
+        /// Serial the client acknowledged; a commit at or past it completes the resize
+        serial: Serial,
+        requested_at: Instant,
     },
 }
 
@@ -582,6 +869,27 @@ impl WindowState {
             Self::AwaitingCommit { current_height, .. } => *current_height,
         }
     }
+
+    /// Height of the resize under way, if any
+    pub fn requested_height(&self) -> Option<u32> {
+        match self {
+            Self::Active { .. } => None,
+            Self::PendingResize { requested_height, .. } => Some(*requested_height),
+            Self::AwaitingCommit { target_height, .. } => Some(*target_height),
+        }
+    }
+
+    /// Whether a configure is still waiting for its ack/commit pair, within
+    /// `CONFIGURE_COMMIT_TIMEOUT_MS`
+    pub fn configure_in_flight(&self, now: Instant) -> bool {
+        match self {
+            Self::Active { .. } => false,
+            Self::PendingResize { requested_at, .. } | Self::AwaitingCommit { requested_at, .. } => {
+                now.saturating_duration_since(*requested_at)
+                    < std::time::Duration::from_millis(CONFIGURE_COMMIT_TIMEOUT_MS)
+            }
+        }
+    }
 }
 
 /// A cell in the column layout - either a terminal or external window
@@ -666,6 +974,8 @@ impl TermStack {
         let data_device_state = DataDeviceState::new::<Self>(&display_handle);
         let text_input_state = TextInputManagerState::new::<Self>(&display_handle);
         let viewporter_state = smithay::wayland::viewporter::ViewporterState::new::<Self>(&display_handle);
+        let layer_shell = LayerShell::new(&display_handle);
+        let presentation = crate::presentation::Presentation::new(&display_handle);
 
         let mut seat = seat_state.new_wl_seat(&display_handle, "seat0");
 
@@ -684,34 +994,63 @@ impl TermStack {
             data_device_state,
             text_input_state,
             viewporter_state,
+            layer_shell,
             space: Space::default(),
             popup_manager: PopupManager::default(),
             layout_nodes: Vec::new(),
+            cells: CellRegistry::default(),
             scroll_offset: 0.0,
-            focused_window: None,
-            cached_focused_index: std::cell::Cell::new(None),
-            layout: ColumnLayout::empty(),
+            columns: Columns::default(),
+            focus: FocusManager::default(),
+            plugins: Default::default(),
+            frame_layout: Default::default(),
+            height_requests: Default::default(),
+            pending_reveal: None,
+            height_index: HeightIndex::default(),
             output_size,
             seat,
             running: true,
             spawn_terminal_requested: false,
             focus_change_requested: 0,
+            smart_view_toggle_requested: false,
+            family_focus_requested: None,
+            column_requested: None,
+            absorb_requested: false,
             pending_scroll_delta: 0.0,
             pending_spawn_requests: Vec::new(),
+            deferred_x11_spawn_requests: Vec::new(),
             pending_resize_request: None,
-            new_external_window_index: None,
+            pending_export_requests: Vec::new(),
+            pending_layout_requests: Vec::new(),
+            pending_layout_exports: Vec::new(),
+            pending_hit_tests: Vec::new(),
+            pending_layout_imports: Vec::new(),
+            pending_title_requests: Vec::new(),
+            pending_tag_requests: Vec::new(),
+            input_recorder: crate::input_log::InputRecorder::from_env(output_size.w, output_size.h),
+            status_feed: Default::default(),
+            accessibility_feed: Default::default(),
+            #[cfg(target_os = "linux")]
+            atspi: None,
+            pending_color_scheme: None,
+            max_window_height: 0,
+            fullscreen: None,
+            new_external_window: None,
             new_window_needs_keyboard_focus: false,
             external_window_resized: None,
-            pending_window_output_terminal: None,
-            pending_window_command: None,
+            pending_launches: Default::default(),
             pending_gui_spawn_requests: Vec::new(),
             pending_builtin_requests: Vec::new(),
-            pending_gui_foreground: false,
-            pending_window_set_at: None,
+            pending_calc_requests: Vec::new(),
+            calc_results: Vec::new(),
             foreground_gui_sessions: HashMap::new(),
+            orphaned_gui_sessions: HashMap::new(),
             pending_output_terminal_cleanup: Vec::new(),
             pending_launcher_restoration: Vec::new(),
             clipboard: arboard::Clipboard::new().ok(),
+            copy_options: Default::default(),
+            word_chars: crate::config::DEFAULT_WORD_CHARS.to_string(),
+            last_click: None,
             clipboard_receiver: None,
             clipboard_read_started_at: None,
             pending_paste: false,
@@ -729,12 +1068,39 @@ impl TermStack {
             cursor_on_resize_handle: false,
             pointer_buttons_pressed: 0,
             compositor_window_resize_pending: None,
+            pending_vt_switch: None,
+            owns_vt: false,
             csd_apps,
+            gui_env: Vec::new(),
+            hide_gui_launcher: true,
+            window_rules: Vec::new(),
+            decoration_overrides: Default::default(),
             max_gui_windows,
-            xwayland_satellite: None,
-            x11_display_number: None,
+            xwayland: Default::default(),
+            child_reaper: Default::default(),
+            watchdog: Default::default(),
+            redraw_requested: true,
+            pty_sources: Default::default(),
             spawn_initial_terminal: false,
             pending_font_size_delta: 0.0,
+            pending_scrollback_pages: 0,
+            hud_visible: false,
+            help_visible: false,
+            quick_terminal: Default::default(),
+            do_not_disturb: Default::default(),
+            keyboard_resize: Default::default(),
+            window_switcher: Default::default(),
+            filter_view: Default::default(),
+            ephemeral_cells: Default::default(),
+            spawn_dedup: Default::default(),
+            bulk_actions: Default::default(),
+            undo_stack: Default::default(),
+            archive: Default::default(),
+            respawn_last_shell: false,
+            quit_confirm: Default::default(),
+            refresh: crate::refresh::DEFAULT_REFRESH,
+            presentation,
+            typing_echo: Default::default(),
         };
 
         (compositor, display)
@@ -745,13 +1111,10 @@ impl TermStack {
         // Invalidate focused index cache since layout_nodes may have changed
         self.invalidate_focused_index_cache();
 
-        // Use cached heights for layout calculation
-        let heights = self.layout_nodes.iter().map(|node| node.height as u32);
-        self.layout = ColumnLayout::calculate_from_heights(
-            heights,
-            self.output_size.h as u32,
-            self.scroll_offset,
-        );
+        // Only offsets after the first changed height are recomputed
+        if let Some(first_changed) = self.height_index.sync(self.layout_nodes.iter().map(|node| node.height)) {
+            tracing::trace!(first_changed, "height index updated");
+        }
 
         // Update external window positions in Space for click detection
         self.update_space_positions();
@@ -759,44 +1122,91 @@ impl TermStack {
 
     /// Update Space element positions for external windows
     /// This ensures Smithay's click detection matches actual rendered positions
+    ///
+    /// Windows already mapped at their current position are skipped, so only
+    /// windows that moved (all of them when scrolling) are remapped.
     pub fn update_space_positions(&mut self) {
         // Calculate render_y for each cell (with Y-flip for OpenGL)
         let screen_height = self.output_size.h;
-        let mut content_y: i32 = -(self.scroll_offset as i32);
 
-        for (i, node) in self.layout_nodes.iter().enumerate() {
+        for i in 0..self.layout_nodes.len() {
+            // Only external windows need to be mapped in Space
+            if !self.layout_nodes[i].cell.is_external() {
+                continue;
+            }
+            let column = self.layout_nodes[i].column;
+            let scroll = self.column_scroll(column);
+            let x = self.column_span(column).0;
+            let content_y = self.column_top() + self.column_content_y(i) - scroll as i32;
+            let node = &mut self.layout_nodes[i];
+            let StackWindow::External(entry) = &mut node.cell else {
+                continue;
+            };
             let height = node.height;
 
-            // Only external windows need to be mapped in Space
-            if let StackWindow::External(entry) = &node.cell {
-                // Apply Y-flip
-                let render_y = crate::coords::content_to_render_y(
-                    content_y as f64,
-                    height as f64,
-                    screen_height as f64
-                ) as i32;
-                let loc = Point::from((0, render_y));
-                self.space.map_element(entry.window.clone(), loc, false);
-
-                tracing::trace!(
-                    index = i,
-                    content_y,
-                    render_y,
-                    height,
-                    scroll = self.scroll_offset,
-                    "update_space_positions: external window"
-                );
+            // Apply Y-flip
+            let render_y = crate::coords::content_to_render_y(
+                content_y as f64,
+                height as f64,
+                screen_height as f64
+            ) as i32;
+            if entry.mapped_location == Some((x, render_y)) {
+                continue;
             }
+            let loc = Point::from((x, render_y));
+            self.space.map_element(entry.window.clone(), loc, false);
+            entry.mapped_location = Some((x, render_y));
+
+            tracing::trace!(
+                index = i,
+                x,
+                content_y,
+                render_y,
+                height,
+                scroll,
+                "update_space_positions: external window"
+            );
+        }
+    }
 
-            content_y += height;
+    /// Give a cell a new height from the next frame layout on
+    ///
+    /// The cached height, and so clicks and scrolling, keep the height on
+    /// screen until `commit_frame_layout()` applies it.
+    pub fn request_window_height(&mut self, index: usize, height: i32) {
+        if let Some(node) = self.layout_nodes.get(index) {
+            self.height_requests.request(node.id, height);
+        }
+    }
+
+    /// Scroll the cell's bottom into view once the next frame layout is
+    /// committed, after the heights requested before it are applied
+    pub fn reveal_after_layout(&mut self, index: usize) {
+        if let Some(node) = self.layout_nodes.get(index) {
+            self.pending_reveal = Some(node.id);
         }
     }
 
-    /// Calculate maximum scroll offset based on content height
+    /// The height index, unless cells were added or removed since the last sync
+    fn synced_height_index(&self) -> Option<&HeightIndex> {
+        (self.height_index.len() == self.layout_nodes.len()).then_some(&self.height_index)
+    }
+
+    /// Total height of the active column's cells (including title bars)
+    pub fn total_layout_height(&self) -> i32 {
+        if self.columns.len() > 1 {
+            return self.column_height(self.columns.active());
+        }
+        match self.synced_height_index() {
+            Some(index) => index.total(),
+            None => self.layout_nodes.iter().map(|node| node.height).sum(),
+        }
+    }
+
+    /// Calculate maximum scroll offset of the active column based on content height
     pub fn max_scroll(&self) -> f64 {
         // Use layout_nodes height which includes title bars for terminals
-        let total_height: i32 = self.layout_nodes.iter().map(|node| node.height).sum();
-        (total_height as f64 - self.output_size.h as f64).max(0.0)
+        (self.total_layout_height() as f64 - self.viewport_height() as f64).max(0.0)
     }
 
     /// Apply any accumulated scroll delta (call once per frame after input processing)
@@ -848,7 +1258,7 @@ impl TermStack {
                 tracing::debug!("clearing stale cross-selection state");
                 self.cross_selection = None;
             }
-            if self.resizing.is_some() {
+            if self.resizing.as_ref().is_some_and(|drag| !drag.keyboard) {
                 tracing::debug!("clearing stale resize state");
                 self.resizing = None;
                 self.cursor_on_resize_handle = false;
@@ -858,9 +1268,8 @@ impl TermStack {
 
     /// Update cached cell heights from actual render heights.
     ///
-    /// Called at the start of each frame with heights from the previous frame's
-    /// rendering. This ensures click detection coordinates match what was actually
-    /// rendered. See [`LayoutNode`] for why this consistency matters.
+    /// Called with the heights of a `FrameLayout` by `commit_frame_layout()`, so
+    /// cached heights match what is rendered. See [`LayoutNode`] for why this consistency matters.
     ///
     /// The `heights` vector must have the same length as `layout_nodes`.
     pub fn update_layout_heights(&mut self, heights: Vec<i32>) {
@@ -876,6 +1285,7 @@ impl TermStack {
         for (node, height) in self.layout_nodes.iter_mut().zip(heights.into_iter()) {
             node.height = height;
         }
+        self.height_index.sync(self.layout_nodes.iter().map(|node| node.height));
     }
 
     /// Get the height of a cell at the given index
@@ -886,10 +1296,22 @@ impl TermStack {
     /// Sum of heights of layout nodes before the given index.
     /// This is the content-space Y offset where the window at `index` begins.
     fn content_y_before(&self, index: usize) -> i32 {
-        self.layout_nodes[..index].iter().map(|node| node.height).sum()
+        match self.synced_height_index() {
+            Some(heights) => heights.offset(index),
+            None => self.layout_nodes[..index].iter().map(|node| node.height).sum(),
+        }
     }
 
-    /// Scroll to ensure a cell's bottom edge is visible on screen.
+    /// Y offset of the cell at `index` from the top of its column's content
+    pub fn column_content_y(&self, index: usize) -> i32 {
+        let Some(node) = self.layout_nodes.get(index) else {
+            return self.content_y_before(index.min(self.layout_nodes.len()));
+        };
+        let start = self.column_range(node.column).start;
+        self.content_y_before(index) - self.content_y_before(start)
+    }
+
+    /// Scroll the cell's column to ensure its bottom edge is visible on screen.
     /// Returns the new scroll offset if it changed, None otherwise.
     pub fn scroll_to_show_window_bottom(&mut self, window_index: usize) -> Option<f64> {
         debug_assert!(
@@ -898,17 +1320,20 @@ impl TermStack {
             window_index,
             self.layout_nodes.len()
         );
-        let y = self.content_y_before(window_index);
+        let column = self
+            .layout_nodes
+            .get(window_index)
+            .map_or(self.columns.active(), |node| node.column);
+        let y = self.column_content_y(window_index);
         let height = self.layout_nodes.get(window_index).map(|n| n.height).unwrap_or(0);
         let bottom_y = y + height;
-        let visible_height = self.output_size.h;
-        let total_height: i32 = self.layout_nodes.iter().map(|node| node.height).sum();
-        let max_scroll = (total_height - visible_height).max(0) as f64;
+        let visible_height = self.viewport_height();
+        let max_scroll = (self.column_height(column) - visible_height).max(0) as f64;
         let min_scroll_for_bottom = (bottom_y - visible_height).max(0) as f64;
         let new_scroll = min_scroll_for_bottom.min(max_scroll);
 
-        if (new_scroll - self.scroll_offset).abs() > 0.5 {
-            self.scroll_offset = new_scroll;
+        if (new_scroll - self.column_scroll(column)).abs() > 0.5 {
+            self.set_column_scroll(column, new_scroll);
             Some(new_scroll)
         } else {
             None
@@ -917,49 +1342,31 @@ impl TermStack {
 
     /// Get the cell under a point
     ///
-    /// The point must be in render coordinates (Y=0 at bottom).
-    /// Returns the cell index if found.
-    ///
-    /// This uses our own coordinate calculation (not Smithay's Space.element_under)
-    /// to ensure consistent behavior with Y-flip coordinates.
-    pub fn window_at(&self, render_y: crate::coords::RenderY) -> Option<usize> {
-        let render_y_value = render_y.value();
-        let screen_height = self.output_size.h as f64;
-        let mut content_y = -self.scroll_offset;
-
-        for i in 0..self.layout_nodes.len() {
-            let window_height = self.layout_nodes[i].height as f64;
-
-            // Calculate render Y for this cell (same formula as main.rs rendering)
-            let cell_render_y = crate::coords::content_to_render_y(content_y, window_height, screen_height);
-            let render_end = cell_render_y + window_height;
-
-            if render_y_value >= cell_render_y && render_y_value < render_end {
-                tracing::debug!(
-                    index = i,
-                    render_y = render_y_value,
-                    cell_render_y,
-                    render_end,
-                    window_height,
-                    content_y,
-                    "window_at: hit"
-                );
-                return Some(i);
-            }
-            content_y += window_height;
+    /// The point must be in render coordinates (Y=0 at bottom). Looks the
+    /// point up in the last committed `frame_layout`, so clicks land on the
+    /// cell drawn there even if heights or scroll changed since. Returns the
+    /// cell index if found.
+    pub fn window_at(&self, x: f64, render_y: crate::coords::RenderY) -> Option<usize> {
+        let screen_y = self.output_size.h as f64 - render_y.value();
+        // Panels' strips hide the cells scrolled under them
+        if self.layer_shell.reserved().covers(screen_y, self.output_size.h) {
+            return None;
         }
-        None
+        let index = self.frame_layout.cell_at(x, screen_y).and_then(|cell| self.cell_index(cell))?;
+        tracing::debug!(index, render_y = render_y.value(), screen_y, "window_at: hit");
+        Some(index)
     }
 
     /// Check if a point is on a terminal cell
     pub fn is_on_terminal(&self, point: Point<f64, smithay::utils::Logical>) -> bool {
-        self.window_at(crate::coords::RenderY::new(point.y))
+        self.window_at(point.x, crate::coords::RenderY::new(point.y))
             .map(|i| matches!(self.layout_nodes.get(i), Some(node) if matches!(node.cell, StackWindow::Terminal(_))))
             .unwrap_or(false)
     }
 
     /// Get the render position (render_y, height) for a cell at the given index
     /// Returns (render_y, height) where render_y is in render coordinates (Y=0 at bottom)
+    /// (see `get_window_screen_bounds()`)
     pub fn get_window_render_position(&self, index: usize) -> (crate::coords::RenderY, i32) {
         debug_assert!(
             index < self.layout_nodes.len(),
@@ -967,18 +1374,19 @@ impl TermStack {
             index,
             self.layout_nodes.len()
         );
-        if let Some(node) = self.layout_nodes.get(index) {
-            let content_y = self.content_y_before(index) as f64 - self.scroll_offset;
-            let height = node.height;
-            let render_y = crate::coords::content_to_render_y(content_y, height as f64, self.output_size.h as f64);
-            (crate::coords::RenderY::new(render_y), height)
-        } else {
-            (crate::coords::RenderY::new(0.0), 0)
-        }
+        let Some((top, bottom)) = self.get_window_screen_bounds(index) else {
+            return (crate::coords::RenderY::new(0.0), 0);
+        };
+        let height = bottom - top;
+        let render_y = crate::coords::content_to_render_y(top as f64, height as f64, self.output_size.h as f64);
+        (crate::coords::RenderY::new(render_y), height)
     }
 
     /// Get the screen bounds (top_y, bottom_y) for a cell at the given index
     /// Returns (top_y, bottom_y) in screen coordinates (Y=0 at top)
+    ///
+    /// Cells in the last committed `frame_layout` are where it drew them;
+    /// cells added since are placed by their cached heights.
     pub fn get_window_screen_bounds(&self, index: usize) -> Option<(i32, i32)> {
         debug_assert!(
             index < self.layout_nodes.len(),
@@ -987,7 +1395,10 @@ impl TermStack {
             self.layout_nodes.len()
         );
         self.layout_nodes.get(index).map(|node| {
-            let top_y = self.content_y_before(index) - self.scroll_offset as i32;
+            if let Some(bounds) = self.frame_layout.screen_bounds(node.id) {
+                return bounds;
+            }
+            let top_y = self.column_top() + self.column_content_y(index) - self.column_scroll(node.column) as i32;
             let bottom_y = top_y + node.height;
             (top_y, bottom_y)
         })
@@ -1004,7 +1415,7 @@ impl TermStack {
                 Ok(text) => {
                     self.primary_selection_receiver = None;
                     self.primary_selection_read_started_at = None;
-                    if let Some(terminal) = terminals.get_focused_mut(self.focused_window.as_ref()) {
+                    if let Some(terminal) = terminals.get_focused_mut(self.focused_window()) {
                         if terminal.has_exited() {
                             tracing::debug!("ignoring primary paste to exited terminal");
                         } else if let Err(e) = terminal.write(text.as_bytes()) {
@@ -1051,23 +1462,17 @@ impl TermStack {
         }
     }
 
-    /// Clear pending GUI window state if it has been stale for too long
+    /// Forget GUI launches that have been pending for too long
     ///
-    /// If a GUI app was spawned but never opened a window, the pending state
-    /// would incorrectly link the next unrelated window. This timeout prevents that.
+    /// If a GUI app was spawned but never opened a window, its launch would
+    /// incorrectly link the next unrelated window. This timeout prevents that.
     pub fn timeout_stale_pending_window(&mut self) {
-        const PENDING_WINDOW_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
-
-        if let Some(set_at) = self.pending_window_set_at {
-            if Instant::now().duration_since(set_at) > PENDING_WINDOW_TIMEOUT {
-                let command = self.pending_window_command.take().unwrap_or_default();
-                self.pending_window_output_terminal = None;
-                self.pending_gui_foreground = false;
-                self.pending_window_set_at = None;
+        for launch in self.pending_launches.expire(Instant::now()) {
+            if !launch.linked {
                 tracing::warn!(
-                    command = %command,
-                    "pending GUI window state timed out after {:?}, clearing",
-                    PENDING_WINDOW_TIMEOUT
+                    command = %launch.command,
+                    "pending GUI launch timed out after {:?}, clearing",
+                    crate::gui_launch::PENDING_LAUNCH_TIMEOUT
                 );
             }
         }
@@ -1080,7 +1485,7 @@ impl TermStack {
     #[cfg(debug_assertions)]
     pub fn validate_state(&self, terminal_manager: &crate::terminal_manager::TerminalManager) {
         // 1. Focused window must resolve to a valid index or be None
-        if let Some(focused) = &self.focused_window {
+        if let Some(focused) = self.focused_window() {
             let index = self.focused_index();
             debug_assert!(
                 index.is_some(),
@@ -1097,6 +1502,13 @@ impl TermStack {
             }
         }
 
+        // 1b. Keyboard focus follows cell focus (synced every frame)
+        debug_assert!(
+            !self.focus.keyboard_out_of_sync() || self.focused_index().is_none(),
+            "keyboard focus was not moved to focused cell {:?}",
+            self.focused_window()
+        );
+
         // 2. No duplicate terminal IDs in layout_nodes
         let terminal_ids: Vec<_> = self.layout_nodes.iter()
             .filter_map(|n| n.cell.terminal_id())
@@ -1140,22 +1552,20 @@ impl TermStack {
         // 5. Resize drag target must be valid if set
         if let Some(drag) = &self.resizing {
             debug_assert!(
-                drag.window_index < self.layout_nodes.len(),
-                "resize drag window_index {} >= layout_nodes.len() {}",
-                drag.window_index,
-                self.layout_nodes.len()
+                self.cells.contains(drag.cell),
+                "resize drag targets removed cell {:?}",
+                drag.cell
             );
         }
 
-        // 6. Pending window state consistency
-        if self.pending_window_set_at.is_some() {
-            debug_assert!(
-                self.pending_window_output_terminal.is_some()
-                    || self.pending_window_command.is_some()
-                    || self.pending_gui_foreground,
-                "pending_window_set_at is set but no pending window data"
-            );
-        }
+        // 6. Pending launches are queued oldest first
+        debug_assert!(
+            self.pending_launches
+                .iter()
+                .zip(self.pending_launches.iter().skip(1))
+                .all(|(a, b)| a.set_at <= b.set_at),
+            "pending GUI launches out of order"
+        );
     }
 }
 
@@ -1204,6 +1614,10 @@ impl CompositorHandler for TermStack {
             return; // Popup handled, don't process as toplevel
         }
 
+        if self.handle_layer_commit(surface) {
+            return;
+        }
+
         // Handle toplevel commits
         self.handle_commit(surface);
     }
@@ -1239,8 +1653,7 @@ impl XdgShellHandler for TermStack {
 
     fn new_toplevel(&mut self, surface: ToplevelSurface) {
         tracing::debug!(
-            pending_output_terminal = ?self.pending_window_output_terminal,
-            pending_gui_foreground = self.pending_gui_foreground,
+            pending_launches = self.pending_launches.len(),
             "XDG toplevel created"
         );
 
@@ -1248,7 +1661,7 @@ impl XdgShellHandler for TermStack {
         // Per xdg-shell spec: size=(width, 0) means width is constrained, height is client's choice.
         // Tiled states indicate the app is in a column layout with fixed width.
         let bounds = initial_configure_bounds(self.output_size);
-        let constrained_width = self.output_size.w;
+        let constrained_width = self.column_width();
         surface.with_pending_state(|state| {
             state.bounds = Some(bounds);
             // Width constrained, height=0 means client chooses
@@ -1268,72 +1681,8 @@ impl XdgShellHandler for TermStack {
     fn new_popup(&mut self, surface: PopupSurface, positioner: PositionerState) {
         // Following Anvil's pattern: set geometry with constraints, then track
         // Initial configure is sent during commit, not here
-
-        let parent_surface = surface.get_parent_surface();
-
-        // Find the parent window's position in content coordinates
-        // This is needed to properly constrain the popup to the screen
-        let parent_content_y = parent_surface.as_ref().and_then(|parent| {
-            self.layout_nodes.iter().enumerate().find_map(|(idx, node)| {
-                if let StackWindow::External(entry) = &node.cell {
-                    if entry.surface.wl_surface() == parent {
-                        // Calculate content Y position (sum of heights above this window)
-                        let content_y: i32 = self.layout_nodes[..idx]
-                            .iter()
-                            .map(|n| n.height)
-                            .sum();
-                        return Some(content_y);
-                    }
-                }
-                None
-            })
-        }).unwrap_or(0);
-
-        // Calculate the PARENT SURFACE position on screen (accounting for scroll)
-        // The positioner works in parent-surface-local coordinates
-        // Content Y to Screen Y: screen_y = content_y - scroll_offset
-        let parent_screen_y = (parent_content_y as f64 - self.scroll_offset).max(0.0) as i32;
-
-        // Parent X is at the focus indicator offset
-        let parent_screen_x = crate::layout::FOCUS_INDICATOR_WIDTH;
-
-        // Create target rectangle in PARENT-SURFACE-LOCAL coordinates
-        // This tells the positioner where the screen edges are relative to the parent's (0,0)
-        // Screen top (Y=0) is at parent-local Y = -parent_screen_y
-        // Screen left (X=0) is at parent-local X = -parent_screen_x
-        let target = Rectangle::new(
-            Point::from((-parent_screen_x, -parent_screen_y)),
-            Size::from((self.output_size.w, self.output_size.h)),
-        );
-
-        let geo = positioner.get_unconstrained_geometry(target);
-
-        // Clamp popup geometry to prevent extreme offsets from buggy clients
-        let max_offset = self.output_size.w.max(self.output_size.h) * 2;
-        let clamped_geo = Rectangle::new(
-            Point::from((
-                geo.loc.x.max(-max_offset).min(max_offset),
-                geo.loc.y.max(-max_offset).min(max_offset),
-            )),
-            Size::from((
-                geo.size.w.max(1).min(self.output_size.w * 2),
-                geo.size.h.max(1).min(self.output_size.h * 2),
-            )),
-        );
-
-        if clamped_geo != geo {
-            tracing::warn!(
-                original = ?geo,
-                clamped = ?clamped_geo,
-                "popup geometry clamped to screen bounds"
-            );
-        }
-
-        tracing::debug!(
-            geo = ?clamped_geo,
-            ?target,
-            "new_popup: XDG popup created"
-        );
+        let clamped_geo = self.constrained_popup_geometry(&surface, positioner);
+        tracing::debug!(geo = ?clamped_geo, "new_popup: XDG popup created");
 
         surface.with_pending_state(|state| {
             state.geometry = clamped_geo;
@@ -1346,10 +1695,37 @@ impl XdgShellHandler for TermStack {
         }
     }
 
+    fn ack_configure(&mut self, surface: WlSurface, configure: Configure) {
+        if let Configure::Toplevel(configure) = configure {
+            self.ack_resize(&surface, configure.serial);
+        }
+    }
+
+    fn client_pong(&mut self, client: ShellClient) {
+        crate::watchdog::handle_pong(self, &client);
+    }
+
     fn toplevel_destroyed(&mut self, surface: ToplevelSurface) {
         self.remove_window(surface.wl_surface());
     }
 
+    fn maximize_request(&mut self, surface: ToplevelSurface) {
+        self.set_maximized(&surface, true);
+    }
+
+    fn unmaximize_request(&mut self, surface: ToplevelSurface) {
+        self.set_maximized(&surface, false);
+    }
+
+    fn fullscreen_request(&mut self, surface: ToplevelSurface, _output: Option<WlOutput>) {
+        // There is only one output, so the requested one doesn't matter
+        self.set_fullscreen(&surface, true);
+    }
+
+    fn unfullscreen_request(&mut self, surface: ToplevelSurface) {
+        self.set_fullscreen(&surface, false);
+    }
+
     fn popup_destroyed(&mut self, surface: PopupSurface) {
         // Log popup destruction for debugging
         let popup_id = format!("{:?}", surface.wl_surface().id());
@@ -1379,27 +1755,23 @@ impl XdgShellHandler for TermStack {
             return;
         }
 
-        // Find the keyboard focus surface (the toplevel window)
-        let keyboard = self.seat.get_keyboard();
-        let keyboard_focus = keyboard.as_ref().and_then(|kbd| {
-            kbd.current_focus().clone()
-        });
-        let keyboard_grabbed = keyboard.as_ref().map(|kbd| kbd.is_grabbed()).unwrap_or(false);
+        // The grab is rooted at the popup's toplevel, not whatever has keyboard
+        // focus: for a submenu that's the parent popup, and smithay insists on
+        // the toplevel
+        let popup_kind = PopupKind::Xdg(surface.clone());
+        let Ok(focus) = smithay::desktop::find_popup_root_surface(&popup_kind) else {
+            tracing::warn!("grab(): popup's toplevel is gone - ignoring grab request");
+            // Do NOT call send_popup_done() - just ignore the grab
+            return;
+        };
 
         tracing::info!(
-            keyboard_focus_id = ?keyboard_focus.as_ref().map(|f| f.id()),
-            keyboard_grabbed,
+            root_id = ?focus.id(),
+            keyboard_grabbed = self.seat.get_keyboard().is_some_and(|kbd| kbd.is_grabbed()),
             "grab(): current keyboard state"
         );
 
-        let Some(focus) = keyboard_focus else {
-            tracing::warn!("grab(): no keyboard focus - ignoring grab request");
-            // Do NOT call send_popup_done() - just ignore the grab
-            return;
-        };
-
         // Try to set up the popup grab
-        let popup_kind = PopupKind::Xdg(surface.clone());
         match self.popup_manager.grab_popup::<Self>(
             focus.clone(),
             popup_kind,
@@ -1472,55 +1844,8 @@ impl XdgShellHandler for TermStack {
     fn reposition_request(&mut self, surface: PopupSurface, positioner: PositionerState, token: u32) {
         // GTK may request popup repositioning (e.g., when popup would go off-screen)
         // We must respond with send_repositioned() or GTK may hang
-
-        // Find parent window position (same logic as new_popup)
-        let parent_surface = surface.get_parent_surface();
-        let parent_content_y = parent_surface.as_ref().and_then(|parent| {
-            self.layout_nodes.iter().enumerate().find_map(|(idx, node)| {
-                if let StackWindow::External(entry) = &node.cell {
-                    if entry.surface.wl_surface() == parent {
-                        let content_y: i32 = self.layout_nodes[..idx]
-                            .iter()
-                            .map(|n| n.height)
-                            .sum();
-                        return Some(content_y);
-                    }
-                }
-                None
-            })
-        }).unwrap_or(0);
-
-        // Calculate parent surface position on screen (same as new_popup)
-        let parent_screen_y = (parent_content_y as f64 - self.scroll_offset).max(0.0) as i32;
-        let parent_screen_x = crate::layout::FOCUS_INDICATOR_WIDTH;
-
-        // Target in parent-surface-local coordinates
-        let target = Rectangle::new(
-            Point::from((-parent_screen_x, -parent_screen_y)),
-            Size::from((self.output_size.w, self.output_size.h)),
-        );
-
-        let new_geo = positioner.get_unconstrained_geometry(target);
-
-        // Clamp popup geometry (same as new_popup)
-        let max_offset = self.output_size.w.max(self.output_size.h) * 2;
-        let clamped_geo = Rectangle::new(
-            Point::from((
-                new_geo.loc.x.max(-max_offset).min(max_offset),
-                new_geo.loc.y.max(-max_offset).min(max_offset),
-            )),
-            Size::from((
-                new_geo.size.w.max(1).min(self.output_size.w * 2),
-                new_geo.size.h.max(1).min(self.output_size.h * 2),
-            )),
-        );
-
-        tracing::info!(
-            ?token,
-            geo = ?clamped_geo,
-            ?target,
-            "reposition_request: updating popup position"
-        );
+        let clamped_geo = self.constrained_popup_geometry(&surface, positioner);
+        tracing::info!(?token, geo = ?clamped_geo, "reposition_request: updating popup position");
 
         // Update popup geometry and positioner
         surface.with_pending_state(|state| {
@@ -1587,51 +1912,13 @@ impl XdgDecorationHandler for TermStack {
     }
 
     fn request_mode(&mut self, toplevel: ToplevelSurface, mode: DecorationMode) {
-        let uses_csd = matches!(mode, DecorationMode::ClientSide);
-
-        let surface = toplevel.wl_surface();
-        for node in &mut self.layout_nodes {
-            if let StackWindow::External(entry) = &mut node.cell {
-                if entry.surface.wl_surface() == surface {
-                    entry.uses_csd = uses_csd;
-                    tracing::info!(
-                        requested = ?mode,
-                        uses_csd = entry.uses_csd,
-                        command = %entry.command,
-                        "decoration mode negotiated"
-                    );
-                    break;
-                }
-            }
-        }
-
-        // Honor client's request
-        toplevel.with_pending_state(|state| {
-            state.decoration_mode = Some(mode);
-        });
-        toplevel.send_configure();
+        // Honor client's request unless the user picked a mode for this app
+        self.apply_negotiated_decoration(&toplevel, mode);
     }
 
     fn unset_mode(&mut self, toplevel: ToplevelSurface) {
         // Client unset mode preference - revert to server-side
-        let surface = toplevel.wl_surface();
-        for node in &mut self.layout_nodes {
-            if let StackWindow::External(entry) = &mut node.cell {
-                if entry.surface.wl_surface() == surface {
-                    entry.uses_csd = false;
-                    tracing::info!(
-                        command = %entry.command,
-                        "decoration mode unset, reverting to SSD"
-                    );
-                    break;
-                }
-            }
-        }
-
-        toplevel.with_pending_state(|state| {
-            state.decoration_mode = Some(DecorationMode::ServerSide);
-        });
-        toplevel.send_configure();
+        self.apply_negotiated_decoration(&toplevel, DecorationMode::ServerSide);
     }
 }
 
@@ -1654,8 +1941,10 @@ delegate_xdg_decoration!(TermStack);
 delegate_seat!(TermStack);
 delegate_data_device!(TermStack);
 delegate_output!(TermStack);
+delegate_presentation!(TermStack);
 delegate_text_input_manager!(TermStack);
 delegate_viewporter!(TermStack);
+delegate_layer_shell!(TermStack);
 
 #[cfg(test)]
 mod tests {
@@ -2001,13 +2290,17 @@ mod tests {
         // Simulate cell insertion behavior
         let mut layout_nodes: Vec<LayoutNode> = Vec::new();
         let mut focused_index: Option<usize> = None;
+        let mut cells = crate::cell_id::CellRegistry::default();
 
         // Helper to add terminal with the same logic as add_terminal
-        let add_terminal = |id: u32, nodes: &mut Vec<LayoutNode>, focused: &mut Option<usize>| {
+        let mut add_terminal = |id: u32, nodes: &mut Vec<LayoutNode>, focused: &mut Option<usize>| {
             let insert_index = focused.unwrap_or(nodes.len());
             nodes.insert(insert_index, LayoutNode {
+                id: cells.insert(insert_index),
                 cell: StackWindow::Terminal(TerminalId(id)),
-                height: 0
+                height: 0,
+                column: Default::default(),
+                tag: None,
             });
             *focused = Some(focused.map(|idx| idx + 1).unwrap_or(insert_index));
         };
@@ -2339,4 +2632,35 @@ mod tests {
         // If title bar is 24px, surface-local Y should be 50 - 24 = 26
         // (click is 26px into the actual surface content)
     }
+
+    #[test]
+    fn drag_configures_wait_for_the_previous_commit() {
+        let mut drag = ResizeDrag {
+            cell: CellId::from_bits(1),
+            start_screen_y: 0,
+            start_height: 300,
+            target_height: 340,
+            last_sent_height: None,
+            keyboard: false,
+        };
+        assert_eq!(drag.configure_due(false), Some(340));
+        drag.last_sent_height = Some(340);
+        assert_eq!(drag.configure_due(false), None);
+
+        // Motion while the window is still busy only moves the target
+        drag.target_height = 380;
+        assert_eq!(drag.configure_due(true), None);
+        assert_eq!(drag.configure_due(false), Some(380));
+
+        let now = Instant::now();
+        let pending = WindowState::AwaitingCommit {
+            current_height: 300,
+            target_height: 340,
+            serial: Serial::from(7),
+            requested_at: now,
+        };
+        assert!(pending.configure_in_flight(now));
+        assert!(!pending.configure_in_flight(now + std::time::Duration::from_millis(CONFIGURE_COMMIT_TIMEOUT_MS)));
+        assert!(!WindowState::Active { height: 300 }.configure_in_flight(now));
+    }
 }
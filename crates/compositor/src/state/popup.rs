@@ -0,0 +1,121 @@
+//! Popup placement and dismissal
+//!
+//! Popups are placed by the client's xdg_positioner and constrained to the
+//! output: smithay gets the output rectangle in the coordinates of the popup's
+//! parent surface and flips or slides the popup as the positioner allows.
+//! Nested popups (submenus) are placed relative to their parent popup.
+//!
+//! Popups are dismissed (`popup_done`) by a click outside both the popup and
+//! the window it belongs to, and by Escape while a popup holds the keyboard
+//! grab. Clicks inside the parent window are left to the client, which closes
+//! its own menus.
+
+use smithay::desktop::{find_popup_root_surface, get_popup_toplevel_coords, PopupKind, PopupManager};
+use smithay::utils::{Logical, Point, Rectangle, Size};
+use smithay::wayland::shell::xdg::{PopupSurface, PositionerState};
+
+use super::{StackWindow, TermStack};
+use crate::title_bar::TITLE_BAR_HEIGHT;
+
+impl TermStack {
+    /// The output as a rectangle in the coordinates of `popup`'s parent surface
+    fn popup_constraint_target(&self, popup: &PopupSurface) -> Rectangle<i32, Logical> {
+        let kind = PopupKind::Xdg(popup.clone());
+        let root = find_popup_root_surface(&kind).ok();
+        let root_index = root.as_ref().and_then(|root| {
+            self.layout_nodes.iter().position(|node| {
+                matches!(&node.cell, StackWindow::External(entry) if entry.surface.wl_surface() == root)
+            })
+        });
+
+        // Top of the root window's client area on screen (may be above the
+        // screen when scrolled; the positioner then slides the popup down)
+        let root_y = root_index.map_or(0, |index| {
+            let title_bar = match &self.layout_nodes[index].cell {
+                StackWindow::External(entry) if entry.has_title_bar() => TITLE_BAR_HEIGHT as i32,
+                _ => 0,
+            };
+            let column_scroll = self.column_scroll(self.layout_nodes[index].column);
+            self.column_top() + self.column_content_y(index) - column_scroll as i32 + title_bar
+        });
+        let root_x = crate::layout::FOCUS_INDICATOR_WIDTH + root_index.map_or(0, |index| self.cell_x(index));
+
+        let parent = get_popup_toplevel_coords(&kind);
+        Rectangle::new(
+            Point::from((-root_x - parent.x, -root_y - parent.y)),
+            Size::from((self.output_size.w, self.output_size.h)),
+        )
+    }
+
+    /// Geometry for `popup` under `positioner`, constrained to the output
+    pub(super) fn constrained_popup_geometry(
+        &self,
+        popup: &PopupSurface,
+        positioner: PositionerState,
+    ) -> Rectangle<i32, Logical> {
+        let target = self.popup_constraint_target(popup);
+        let geo = positioner.get_unconstrained_geometry(target);
+
+        // Clamp popup geometry to prevent extreme offsets from buggy clients
+        let max_offset = self.output_size.w.max(self.output_size.h) * 2;
+        let clamped_geo = Rectangle::new(
+            Point::from((
+                geo.loc.x.max(-max_offset).min(max_offset),
+                geo.loc.y.max(-max_offset).min(max_offset),
+            )),
+            Size::from((
+                geo.size.w.max(1).min(self.output_size.w * 2),
+                geo.size.h.max(1).min(self.output_size.h * 2),
+            )),
+        );
+
+        if clamped_geo != geo {
+            tracing::warn!(
+                original = ?geo,
+                clamped = ?clamped_geo,
+                "popup geometry clamped to screen bounds"
+            );
+        }
+        tracing::debug!(geo = ?clamped_geo, ?target, "popup geometry constrained");
+
+        clamped_geo
+    }
+
+    /// Dismiss the popups of every external window except the one at `keep`
+    pub fn dismiss_popups_except(&self, keep: Option<usize>) {
+        for (index, node) in self.layout_nodes.iter().enumerate() {
+            if Some(index) == keep {
+                continue;
+            }
+            let StackWindow::External(entry) = &node.cell else {
+                continue;
+            };
+            let root = entry.surface.wl_surface();
+            let popups: Vec<PopupKind> = PopupManager::popups_for_surface(root).map(|(popup, _)| popup).collect();
+            for popup in popups {
+                tracing::debug!(command = %entry.command, "dismissing popup after outside click");
+                let _ = PopupManager::dismiss_popup(root, &popup);
+            }
+        }
+    }
+
+    /// Dismiss the popup holding the keyboard grab, if any
+    ///
+    /// Returns whether a popup was dismissed.
+    pub fn dismiss_grabbed_popup(&self) -> bool {
+        let Some(keyboard) = self.seat.get_keyboard() else {
+            return false;
+        };
+        if !keyboard.is_grabbed() {
+            return false;
+        }
+        let Some(popup) = keyboard.current_focus().and_then(|focus| self.popup_manager.find_popup(&focus)) else {
+            return false;
+        };
+        let Ok(root) = find_popup_root_surface(&popup) else {
+            return false;
+        };
+        tracing::debug!("dismissing grabbed popup on Escape");
+        PopupManager::dismiss_popup(&root, &popup).is_ok()
+    }
+}
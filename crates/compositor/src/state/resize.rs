@@ -5,22 +5,21 @@
 use std::time::Instant;
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel::State as ToplevelState;
-use smithay::utils::{Size, SERIAL_COUNTER};
+use smithay::utils::{Serial, Size};
 use smithay::wayland::compositor::with_states;
 use smithay::wayland::shell::xdg::XdgToplevelSurfaceData;
-use smithay::reexports::wayland_server::Resource;
-use super::{FocusedWindow, StackWindow, TermStack, WindowState};
+use super::{StackWindow, TermStack, WindowState};
 use crate::coords::ScreenY;
-use crate::terminal_manager::TerminalId;
 
 // Constants
 const RESIZE_TIMEOUT_MS: u128 = 5000;
-const MIN_CONFIGURE_INTERVAL_MS: u64 = 100;
 const RESIZE_HANDLE_SIZE: i32 = 8;
 
 impl TermStack {
     /// Request a resize on an external window
     pub fn request_resize(&mut self, index: usize, new_height: u32) {
+        // Cells are as wide as their column
+        let width = self.column_width() as u32;
         let Some(node) = self.layout_nodes.get_mut(index) else {
             tracing::warn!("request_resize: node not found at index {}", index);
             return;
@@ -36,6 +35,10 @@ impl TermStack {
             tracing::trace!("request_resize: height unchanged ({})", current);
             return;
         }
+        if entry.state.requested_height() == Some(new_height) {
+            tracing::trace!("request_resize: already waiting for height {}", new_height);
+            return;
+        }
 
         tracing::info!(
             index,
@@ -44,12 +47,9 @@ impl TermStack {
             "request_resize called"
         );
 
-        // Get output width
-        let width = self.output_size.w as u32;
-
         // For SSD windows, subtract title bar height from the total cell height
         // to get the actual surface content height
-        let surface_height = if entry.uses_csd {
+        let surface_height = if !entry.has_title_bar() {
             new_height
         } else {
             new_height.saturating_sub(crate::title_bar::TITLE_BAR_HEIGHT)
@@ -59,24 +59,24 @@ impl TermStack {
         entry.surface.with_pending_state(|state| {
             state.size = Some(Size::from((width as i32, surface_height as i32)));
         });
-        entry.surface.send_configure();
+        let serial = entry.surface.send_configure();
 
         tracing::debug!(
             index,
             total_height = new_height,
             surface_height,
             uses_csd = entry.uses_csd,
+            ?serial,
             "sending configure with surface height"
         );
 
-        let serial = SERIAL_COUNTER.next_serial().into();
-
         entry.state = WindowState::PendingResize {
             current_height: current,
             requested_height: new_height,
             request_serial: serial,
             requested_at: Instant::now(),
         };
+        entry.liveness.mark_waiting(Instant::now());
 
         tracing::debug!(
             index,
@@ -86,6 +86,52 @@ impl TermStack {
         );
     }
 
+    /// Send the external window being dragged its latest target height, at
+    /// most once per frame (called from the frame loop).
+    ///
+    /// Motion events only move the target; the configure waits here until the
+    /// window has acked and committed the previous one, so a drag costs the
+    /// client one resize per frame it can keep up with instead of one per
+    /// motion event. The final height goes out when the drag ends.
+    pub fn send_drag_configure(&mut self) {
+        let Some(drag) = &self.resizing else { return };
+        let Some(index) = self.cells.index_of(drag.cell) else { return };
+        let Some(StackWindow::External(entry)) = self.layout_nodes.get(index).map(|node| &node.cell) else {
+            return;
+        };
+        let Some(height) = drag.configure_due(entry.state.configure_in_flight(Instant::now())) else {
+            return;
+        };
+        self.request_resize(index, height);
+        if let Some(drag) = self.resizing.as_mut() {
+            drag.last_sent_height = Some(height);
+        }
+    }
+
+    /// Record a client's ack of a resize configure; the resize completes on
+    /// the next commit
+    pub fn ack_resize(&mut self, surface: &WlSurface, acked: Serial) {
+        for node in &mut self.layout_nodes {
+            let StackWindow::External(entry) = &mut node.cell else { continue };
+            if entry.surface.wl_surface() != surface {
+                continue;
+            }
+            if let WindowState::PendingResize { current_height, requested_height, request_serial, requested_at } = entry.state {
+                // Acking a later serial also covers this one
+                if acked.is_no_older_than(&request_serial) {
+                    tracing::trace!(?acked, requested_height, "resize configure acked");
+                    entry.state = WindowState::AwaitingCommit {
+                        current_height,
+                        target_height: requested_height,
+                        serial: request_serial,
+                        requested_at,
+                    };
+                }
+            }
+            return;
+        }
+    }
+
     /// Resize all external windows to new width (called when compositor is resized)
     pub fn resize_all_external_windows(&mut self, new_width: i32) {
         for node in &mut self.layout_nodes {
@@ -113,40 +159,43 @@ impl TermStack {
             return;
         };
 
-        // Skip processing commits if we're actively resizing this window
-        // (commits during drag have the old size and would overwrite our visual updates)
-        if self.resizing.as_ref().map(|d| d.window_index) == Some(index) {
-            tracing::debug!(index, "skipping commit during active resize drag");
-            return;
+        if let StackWindow::External(entry) = &mut self.layout_nodes[index].cell {
+            entry.liveness.mark_alive();
         }
 
-        // Check if this is a CSD app (before getting mutable borrow)
-        let should_mark_csd = {
-            let Some(node) = self.layout_nodes.get(index) else {
-                return;
-            };
-            let StackWindow::External(entry) = &node.cell else {
-                return;
-            };
-
-            if !entry.uses_csd {
-                let app_id: Option<String> = with_states(surface, |states| {
-                    states
-                        .data_map
-                        .get::<XdgToplevelSurfaceData>()
-                        .and_then(|data| data.lock().ok())
-                        .and_then(|attrs| attrs.app_id.clone())
-                });
+        // Serial of the configure whose state this commit applies
+        let committed_serial: Option<Serial> = with_states(surface, |states| {
+            states
+                .data_map
+                .get::<XdgToplevelSurfaceData>()
+                .and_then(|data| data.lock().ok())
+                .and_then(|data| data.current_serial)
+        });
 
-                if let Some(ref id) = app_id {
-                    self.is_csd_app(id)
-                } else {
-                    false
+        // While this window is being resized, its layout follows the drag target,
+        // so a commit only closes the configure it answers (letting the next one
+        // go out); applying its size would undo the visual update
+        let cell = self.layout_nodes[index].id;
+        if self.resizing.as_ref().is_some_and(|d| d.cell == cell) {
+            if let StackWindow::External(entry) = &mut self.layout_nodes[index].cell {
+                if let WindowState::AwaitingCommit { target_height, serial, .. } = entry.state {
+                    if committed_serial.is_some_and(|committed| committed.is_no_older_than(&serial)) {
+                        tracing::trace!(index, target_height, "drag configure committed");
+                        entry.state = WindowState::Active { height: target_height };
+                    }
                 }
-            } else {
-                false
             }
-        };
+            return;
+        }
+
+        let min_height = super::external::toplevel_min_height(surface);
+
+        // Rules may move the window once its app_id is known
+        let index = self.apply_pending_window_rules(index);
+        let expected_width = self.column_width();
+
+        // Apply the user's per-app choice or `csd_apps` (before getting mutable borrow)
+        let decoration_change = self.decoration_after_commit(index);
 
         let Some(node) = self.layout_nodes.get_mut(index) else {
             return;
@@ -155,9 +204,9 @@ impl TermStack {
             return;
         };
 
-        if should_mark_csd {
-            entry.uses_csd = true;
-            tracing::debug!(command = %entry.command, "marked window as CSD from config");
+        if let Some(uses_csd) = decoration_change {
+            entry.uses_csd = uses_csd;
+            tracing::debug!(command = %entry.command, uses_csd, "applied decoration from config");
         }
 
         // Refresh the Window's internal geometry cache from the newly committed surface state.
@@ -210,7 +259,9 @@ impl TermStack {
 
         if let Some(size) = committed_size {
             let committed_surface_width = size.w;
-            let committed_surface_height = size.h as u32;
+            // Before its first buffer, a client that set a min size gets that much room
+            let committed_surface_height =
+                crate::window_height::initial_surface_height(size.h, min_height) as u32;
 
             tracing::debug!(
                 index,
@@ -222,16 +273,24 @@ impl TermStack {
 
             // For SSD windows, the total cell height includes the title bar
             // For CSD windows, surface height = cell height
-            let committed_window_height = if entry.uses_csd {
+            let committed_window_height = if !entry.has_title_bar() {
                 committed_surface_height
             } else {
                 committed_surface_height + crate::title_bar::TITLE_BAR_HEIGHT
             };
 
+            // Tall windows are shown scaled down until clicked
+            entry.display_scale = if entry.expanded || entry.maximized || entry.fullscreen {
+                None
+            } else {
+                crate::window_height::downscale_factor(committed_surface_height as i32, self.max_window_height)
+            };
+            let displayed_window_height = entry.displayed_height(committed_window_height);
+
             // Check if width needs to be enforced (app used wrong width)
-            let expected_width = self.output_size.w;
             if committed_surface_width != expected_width {
-                width_resize_info = Some((expected_width, committed_surface_height as i32));
+                // Keep the client's own height (0 = its choice), not the placeholder
+                width_resize_info = Some((expected_width, size.h));
             }
 
             match &entry.state {
@@ -245,35 +304,8 @@ impl TermStack {
                         surface_height = committed_surface_height,
                         "resize completed"
                     );
-                    self.external_window_resized = Some((index, committed_window_height as i32));
+                    self.external_window_resized = Some((cell, displayed_window_height as i32));
                     self.recalculate_layout();
-
-                    // Reset throttle for catch-up (don't send immediately - give window breathing room)
-                    if let Some(drag) = &self.resizing {
-                        if drag.window_index == index {
-                            let current_target = drag.target_height as u32;
-                            if current_target != committed_window_height {
-                                // User continued dragging while commit was pending
-                                // Reset throttle timer so next configure will be sent after standard delay
-                                // This gives the window breathing room after commit instead of immediately
-                                // sending another configure
-                                tracing::debug!(
-                                    index,
-                                    committed = committed_window_height,
-                                    target = current_target,
-                                    "drag moved during commit - will catch up on next motion event"
-                                );
-
-                                if let Some(drag_mut) = self.resizing.as_mut() {
-                                    // Clear last_sent_height so next motion event sees height changed
-                                    drag_mut.last_sent_height = None;
-                                    // Set time far in past so throttle allows immediate send on next motion
-                                    drag_mut.last_configure_time =
-                                        Instant::now() - std::time::Duration::from_millis(MIN_CONFIGURE_INTERVAL_MS);
-                                }
-                            }
-                        }
-                    }
                 }
                 WindowState::PendingResize { requested_height, current_height, .. } => {
                     // Resize pending but committed height doesn't match - log mismatch
@@ -287,8 +319,11 @@ impl TermStack {
                         "resize mismatch - committed height != requested"
                     );
                 }
-                WindowState::AwaitingCommit { target_height, .. }
-                    if committed_window_height == *target_height =>
+                // The client may settle on another height than asked; committing
+                // the acked serial still ends the resize
+                WindowState::AwaitingCommit { target_height, serial, .. }
+                    if committed_window_height == *target_height
+                        || committed_serial.is_some_and(|committed| committed.is_no_older_than(serial)) =>
                 {
                     entry.state = WindowState::Active { height: committed_window_height };
                     tracing::info!(
@@ -297,7 +332,7 @@ impl TermStack {
                         surface_height = committed_surface_height,
                         "resize completed"
                     );
-                    self.external_window_resized = Some((index, committed_window_height as i32));
+                    self.external_window_resized = Some((cell, displayed_window_height as i32));
                     self.recalculate_layout();
                 }
                 WindowState::Active { height } if committed_window_height != *height => {
@@ -310,7 +345,7 @@ impl TermStack {
                         old_height,
                         "external window size changed"
                     );
-                    self.external_window_resized = Some((index, committed_window_height as i32));
+                    self.external_window_resized = Some((cell, displayed_window_height as i32));
                     self.recalculate_layout();
                 }
                 _ => {}
@@ -350,7 +385,9 @@ impl TermStack {
 
         for node in &mut self.layout_nodes {
             if let StackWindow::External(entry) = &mut node.cell {
-                if let WindowState::PendingResize { current_height, requested_at, .. } = &entry.state {
+                if let WindowState::PendingResize { current_height, requested_at, .. }
+                | WindowState::AwaitingCommit { current_height, requested_at, .. } = &entry.state
+                {
                     let elapsed = now.duration_since(*requested_at).as_millis();
                     if elapsed > RESIZE_TIMEOUT_MS {
                         tracing::warn!(
@@ -365,32 +402,41 @@ impl TermStack {
         }
     }
 
-    /// Find resize handle at screen Y coordinate, returns window index above the handle
-    pub fn find_resize_handle_at(&self, screen_y: ScreenY) -> Option<usize> {
+    /// Find resize handle at a screen position in the column under `screen_x`,
+    /// returns window index above the handle
+    pub fn find_resize_handle_at(&self, screen_x: f64, screen_y: ScreenY) -> Option<usize> {
         let screen_y_value = screen_y.value() as i32;
+        let column = self.column_at_x(screen_x);
+        let range = self.column_range(column);
 
         // Don't allow resizing the last cell (no border below it)
-        if self.layout_nodes.len() < 2 {
+        if range.len() < 2 {
             tracing::debug!(
                 screen_y = screen_y_value,
-                cells = self.layout_nodes.len(),
+                cells = range.len(),
                 "find_resize_handle_at: too few cells"
             );
             return None;
         }
 
-        let mut content_y = -(self.scroll_offset as i32);
+        // No handles under the panels' strips
+        if self.layer_shell.reserved().covers(screen_y.value(), self.output_size.h) {
+            return None;
+        }
+
+        let scroll_offset = self.column_scroll(column);
+        let mut content_y = self.column_top() - scroll_offset as i32;
         let half_handle = RESIZE_HANDLE_SIZE / 2;
 
         tracing::debug!(
             screen_y = screen_y_value,
-            scroll_offset = self.scroll_offset,
+            scroll_offset,
             initial_content_y = content_y,
             half_handle,
             "find_resize_handle_at: starting search"
         );
 
-        for i in 0..self.layout_nodes.len() {
+        for i in range.clone() {
             // Use layout_nodes height which includes title bar for terminals
             let height = self.layout_nodes[i].height;
             let bottom_y = content_y + height;
@@ -408,8 +454,8 @@ impl TermStack {
             );
 
             // Check if screen_y is in the handle zone around this cell's bottom edge
-            // But not for the last cell (nothing below to resize into)
-            if i < self.layout_nodes.len() - 1
+            // But not for the column's last cell (nothing below to resize into)
+            if i < range.end - 1
                 && screen_y_value >= bottom_y - half_handle
                 && screen_y_value <= bottom_y + half_handle
             {
@@ -429,15 +475,26 @@ impl TermStack {
         None
     }
 
-    /// Find which window is at a given screen Y coordinate (Y=0 at top).
+    /// Find which window is at a given screen position (Y=0 at top).
     ///
-    /// Uses the same layout walk as `find_resize_handle_at` but returns the
+    /// Uses the same layout as `find_resize_handle_at` but returns the
     /// window index whose vertical extent contains the point.
-    pub fn window_at_screen_y(&self, screen_y: ScreenY) -> Option<usize> {
+    pub fn window_at_screen_y(&self, screen_x: f64, screen_y: ScreenY) -> Option<usize> {
         let screen_y_value = screen_y.value() as i32;
-        let mut content_y = -(self.scroll_offset as i32);
+        let column = self.column_at_x(screen_x);
+        let range = self.column_range(column);
+        // Content Y at the top of the screen
+        let screen_top = self.column_scroll(column) as i32 - self.column_top();
+        if let Some(heights) = self.synced_height_index() {
+            let column_start = heights.offset(range.start);
+            return heights
+                .index_at(screen_y_value + screen_top + column_start)
+                .filter(|index| range.contains(index));
+        }
 
-        for (i, node) in self.layout_nodes.iter().enumerate() {
+        let mut content_y = -screen_top;
+
+        for (i, node) in self.layout_nodes.iter().enumerate().take(range.end).skip(range.start) {
             let bottom_y = content_y + node.height;
 
             if screen_y_value >= content_y && screen_y_value < bottom_y {
@@ -450,45 +507,16 @@ impl TermStack {
         None
     }
 
-    /// Clear resize drag if the dragged window no longer exists or has moved.
+    /// Clear resize drag if the dragged window no longer exists.
     ///
-    /// Call this after removing windows from layout_nodes. The identity check
-    /// ensures we don't accidentally resize the wrong window if indices shifted.
+    /// `remove_cell()` already cancels drags on removed cells; this catches
+    /// anything that slipped past it.
     pub fn clear_stale_resize_drag(&mut self) {
         let Some(drag) = &self.resizing else { return };
 
-        let is_valid = if let Some(node) = self.layout_nodes.get(drag.window_index) {
-            // Verify the identity at the stored index still matches
-            match (&node.cell, &drag.window_identity) {
-                (StackWindow::Terminal(id), FocusedWindow::Terminal(drag_id)) => id == drag_id,
-                (StackWindow::External(entry), FocusedWindow::External(drag_id)) => {
-                    entry.surface.wl_surface().id() == *drag_id
-                }
-                _ => false,
-            }
-        } else {
-            false
-        };
-
-        if !is_valid {
-            tracing::info!(
-                window_index = drag.window_index,
-                "clearing stale resize drag (window removed or shifted)"
-            );
+        if !self.cells.contains(drag.cell) {
+            tracing::info!(cell = ?drag.cell, "clearing stale resize drag (window removed)");
             self.resizing = None;
         }
     }
-
-    /// Clear resize drag if it targets the given terminal ID
-    pub fn clear_resize_drag_for_terminal(&mut self, id: TerminalId) {
-        if let Some(drag) = &self.resizing {
-            if drag.window_identity == FocusedWindow::Terminal(id) {
-                tracing::info!(
-                    terminal_id = id.0,
-                    "clearing resize drag for removed terminal"
-                );
-                self.resizing = None;
-            }
-        }
-    }
 }
@@ -0,0 +1,218 @@
+//! Following the desktop's light/dark preference
+//!
+//! With `follow_system_theme` set, a background thread reads the desktop's
+//! color scheme and watches it for changes. The xdg-desktop-portal setting
+//! (`org.freedesktop.appearance color-scheme`) is read through `gdbus`; without
+//! a portal, GNOME's `org.gnome.desktop.interface color-scheme` is read through
+//! `gsettings`. Changes reach the main loop over a calloop channel as
+//! `TermStack::pending_color_scheme`; the X11 loop then re-themes the title
+//! bars, the background color and every terminal.
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use smithay::reexports::calloop::channel::{self, Sender};
+use smithay::reexports::calloop::LoopHandle;
+
+use crate::config::Theme;
+use crate::state::TermStack;
+
+const PORTAL_CALL: &[&str] = &[
+    "call",
+    "--session",
+    "--dest",
+    "org.freedesktop.portal.Desktop",
+    "--object-path",
+    "/org/freedesktop/portal/desktop",
+    "--method",
+    "org.freedesktop.portal.Settings.ReadOne",
+    "org.freedesktop.appearance",
+    "color-scheme",
+];
+
+const PORTAL_MONITOR: &[&str] = &[
+    "monitor",
+    "--session",
+    "--dest",
+    "org.freedesktop.portal.Desktop",
+    "--object-path",
+    "/org/freedesktop/portal/desktop",
+];
+
+const GSETTINGS_GET: &[&str] = &["get", "org.gnome.desktop.interface", "color-scheme"];
+const GSETTINGS_MONITOR: &[&str] = &["monitor", "org.gnome.desktop.interface", "color-scheme"];
+
+/// The desktop's color scheme preference
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorScheme {
+    NoPreference,
+    Dark,
+    Light,
+}
+
+impl ColorScheme {
+    /// Theme to use, `fallback` when the desktop has no preference
+    pub fn resolve(self, fallback: Theme) -> Theme {
+        match self {
+            ColorScheme::NoPreference => fallback,
+            ColorScheme::Dark => Theme::Dark,
+            ColorScheme::Light => Theme::Light,
+        }
+    }
+}
+
+/// Portal value as printed by gdbus, e.g. `(<uint32 1>,)`
+fn parse_portal_value(text: &str) -> Option<ColorScheme> {
+    let value = text.split("uint32 ").nth(1)?;
+    let digits: String = value.chars().take_while(char::is_ascii_digit).collect();
+    match digits.parse::<u32>().ok()? {
+        1 => Some(ColorScheme::Dark),
+        2 => Some(ColorScheme::Light),
+        _ => Some(ColorScheme::NoPreference),
+    }
+}
+
+/// `gdbus monitor` line for a changed color-scheme setting
+fn parse_portal_signal(line: &str) -> Option<ColorScheme> {
+    if !line.contains("SettingChanged") || !line.contains("'org.freedesktop.appearance', 'color-scheme'") {
+        return None;
+    }
+    parse_portal_value(line)
+}
+
+/// gsettings value, e.g. `'prefer-dark'` or `color-scheme: 'prefer-dark'`
+fn parse_gsettings(text: &str) -> Option<ColorScheme> {
+    if text.contains("'prefer-dark'") {
+        Some(ColorScheme::Dark)
+    } else if text.contains("'prefer-light'") {
+        Some(ColorScheme::Light)
+    } else if text.contains("'default'") {
+        Some(ColorScheme::NoPreference)
+    } else {
+        None
+    }
+}
+
+/// Stdout of a successful command
+fn read_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).stderr(Stdio::null()).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Watches the desktop color scheme until dropped
+#[derive(Debug, Default)]
+pub struct SystemThemeWatcher {
+    /// The running `gdbus monitor` / `gsettings monitor`
+    monitor: Arc<Mutex<Option<Child>>>,
+}
+
+impl SystemThemeWatcher {
+    /// Read the current scheme and send it and every change to `sender`
+    pub fn start(sender: Sender<ColorScheme>) -> Self {
+        let watcher = Self::default();
+        let monitor = watcher.monitor.clone();
+        std::thread::spawn(move || watch(&sender, &monitor));
+        watcher
+    }
+}
+
+impl Drop for SystemThemeWatcher {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.monitor.lock().ok().and_then(|mut monitor| monitor.take()) {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+fn watch(sender: &Sender<ColorScheme>, monitor: &Mutex<Option<Child>>) {
+    let (initial, program, args, parse): (_, _, _, fn(&str) -> Option<ColorScheme>) =
+        if let Some(scheme) = read_output("gdbus", PORTAL_CALL).and_then(|out| parse_portal_value(&out)) {
+            (scheme, "gdbus", PORTAL_MONITOR, parse_portal_signal)
+        } else if let Some(scheme) = read_output("gsettings", GSETTINGS_GET).and_then(|out| parse_gsettings(&out)) {
+            (scheme, "gsettings", GSETTINGS_MONITOR, parse_gsettings)
+        } else {
+            tracing::warn!("no desktop color scheme found (needs xdg-desktop-portal or gsettings), keeping theme");
+            return;
+        };
+    tracing::info!(?initial, source = program, "following desktop color scheme");
+    if sender.send(initial).is_err() {
+        return;
+    }
+
+    let mut child = match Command::new(program).args(args).stdout(Stdio::piped()).stderr(Stdio::null()).spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            tracing::warn!(program, error = %e, "failed to watch desktop color scheme");
+            return;
+        }
+    };
+    let stdout = child.stdout.take().expect("stdout is piped");
+    match monitor.lock() {
+        Ok(mut monitor) => *monitor = Some(child),
+        Err(_) => return,
+    }
+
+    let mut last = initial;
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else { break };
+        let Some(scheme) = parse(&line) else { continue };
+        if scheme != last {
+            tracing::info!(?scheme, "desktop color scheme changed");
+            last = scheme;
+            if sender.send(scheme).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Start watching the desktop color scheme; changes land in
+/// `TermStack::pending_color_scheme` and wake the loop
+pub fn setup_system_theme_source(
+    calloop_handle: &LoopHandle<'static, TermStack>,
+) -> anyhow::Result<SystemThemeWatcher> {
+    let (sender, receiver) = channel::channel();
+    calloop_handle
+        .insert_source(receiver, |event, _, state| {
+            if let channel::Event::Msg(scheme) = event {
+                state.pending_color_scheme = Some(scheme);
+                state.redraw_requested = true;
+            }
+        })
+        .map_err(|e| anyhow::anyhow!("Failed to insert system theme source: {e}"))?;
+    Ok(SystemThemeWatcher::start(sender))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn portal_values_are_parsed() {
+        assert_eq!(parse_portal_value("(<uint32 1>,)\n"), Some(ColorScheme::Dark));
+        // Settings.Read wraps the value once more
+        assert_eq!(parse_portal_value("(<<uint32 2>>,)\n"), Some(ColorScheme::Light));
+        assert_eq!(parse_portal_value("(<uint32 0>,)"), Some(ColorScheme::NoPreference));
+        assert_eq!(parse_portal_value("Error: no such method"), None);
+    }
+
+    #[test]
+    fn portal_signals_for_other_settings_are_ignored() {
+        let changed = "/org/freedesktop/portal/desktop: org.freedesktop.portal.Settings.SettingChanged \
+                       ('org.freedesktop.appearance', 'color-scheme', <uint32 1>)";
+        assert_eq!(parse_portal_signal(changed), Some(ColorScheme::Dark));
+        let other = "/org/freedesktop/portal/desktop: org.freedesktop.portal.Settings.SettingChanged \
+                     ('org.freedesktop.appearance', 'contrast', <uint32 1>)";
+        assert_eq!(parse_portal_signal(other), None);
+    }
+
+    #[test]
+    fn gsettings_values_are_parsed() {
+        assert_eq!(parse_gsettings("color-scheme: 'prefer-dark'"), Some(ColorScheme::Dark));
+        assert_eq!(parse_gsettings("'default'\n"), Some(ColorScheme::NoPreference));
+        assert_eq!(ColorScheme::NoPreference.resolve(Theme::Light), Theme::Light);
+        assert_eq!(ColorScheme::Light.resolve(Theme::Dark), Theme::Light);
+    }
+}
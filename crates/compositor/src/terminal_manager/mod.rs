@@ -10,24 +10,27 @@
 //!
 //! When both features are enabled, X11 takes precedence.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::os::fd::RawFd;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 #[cfg(all(feature = "x11-backend", target_os = "linux"))]
 use smithay::backend::renderer::gles::GlesRenderer;
 #[cfg(all(feature = "x11-backend", target_os = "linux"))]
-use smithay::backend::renderer::gles::GlesTexture;
-#[cfg(all(feature = "x11-backend", target_os = "linux"))]
-use smithay::backend::renderer::ImportMem;
-#[cfg(all(feature = "x11-backend", target_os = "linux"))]
 use smithay::utils::Size;
 
+use terminal::Contrast;
 use terminal::Terminal;
 use terminal::Theme;
 use terminal::sizing::SizingAction;
 
+use crate::config::TuiMode;
+use crate::smart_output::SmartView;
 use crate::coords::RenderY;
+use crate::recording::CastWriter;
+#[cfg(all(feature = "x11-backend", target_os = "linux"))]
+use crate::texture_pool::{TerminalTexture, TexturePool};
 
 /// Unique identifier for a managed terminal
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -162,12 +165,39 @@ pub struct ManagedTerminal {
     /// Title for the title bar
     pub title: String,
 
+    /// Name the user or the program gave the cell, shown instead of `title`
+    pub custom_title: Option<String>,
+
     /// Whether to show the title bar (false for initial shell terminals)
     pub show_title_bar: bool,
 
+    /// Command line the terminal runs (the shell name for shell terminals)
+    pub command: String,
+
+    /// Working directory the command was started in, if known
+    pub cwd: Option<PathBuf>,
+
+    /// Wall-clock time the command started
+    pub started_at: SystemTime,
+
+    /// Wall-clock time the command exited
+    pub finished_at: Option<SystemTime>,
+
     /// Cached texture for GPU rendering (X11 backend)
     #[cfg(all(feature = "x11-backend", target_os = "linux"))]
-    texture: Option<GlesTexture>,
+    texture: Option<TerminalTexture>,
+
+    /// Rasterized BGRA pixels waiting to be uploaded into `texture`
+    #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+    staging: Vec<u8>,
+
+    /// Last time the cell was near the viewport, for texture eviction (X11 backend)
+    #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+    last_on_screen: std::time::Instant,
+
+    /// Bumped on every texture upload, for damage tracking (X11 backend)
+    #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+    texture_generation: u64,
 
     /// Cached pixel buffer for software rendering (headless backend)
     #[cfg(all(feature = "headless-backend", not(feature = "x11-backend")))]
@@ -205,15 +235,84 @@ pub struct ManagedTerminal {
     /// When true, auto-growth is disabled (user explicitly chose a size)
     pub manually_sized: bool,
 
+    /// Whether growth stopped at the background cap while unfocused
+    /// (it catches up with its content once focused)
+    pub growth_capped: bool,
+
     /// Pending write buffer for data that couldn't be written due to full PTY buffer.
     /// This prevents paste operations from blocking the compositor.
     pending_write: Vec<u8>,
+
+    /// Whether the foreground job is stuck (see `watchdog`)
+    pub liveness: crate::watchdog::Liveness,
+
+    /// Where this terminal's output is recorded (see `recording`)
+    recording: Option<CastWriter>,
+
+    /// Font size set for this cell alone (Ctrl+wheel), `None` to follow the global size
+    pub font_size: Option<f32>,
+
+    /// Files the command's output is redirected to, shown if it exits
+    /// without printing anything (see `command_line`)
+    pub output_files: Vec<PathBuf>,
+
+    /// Sizing from the `[tui]` config, overriding alternate screen detection
+    pub tui_mode: Option<TuiMode>,
+
+    /// Formatted view of the output, if it is JSON or a table (see `smart_output`)
+    pub smart_view: Option<SmartView>,
+
+    /// Whether the output was looked at for a formatted view since exiting
+    pub smart_output_checked: bool,
+}
+
+/// CPU half of rendering a terminal: grid to BGRA pixels (X11 backend)
+///
+/// Holds only the terminal and its staging buffer, so jobs for different
+/// terminals can run on worker threads while textures stay on the render
+/// thread. See `ManagedTerminal::upload` for the GPU half.
+#[cfg(all(feature = "x11-backend", target_os = "linux"))]
+pub struct RasterJob<'a> {
+    terminal: &'a mut Terminal,
+    width: u32,
+    height: u32,
+    show_cursor: bool,
+    staging: &'a mut Vec<u8>,
+}
+
+#[cfg(all(feature = "x11-backend", target_os = "linux"))]
+impl RasterJob<'_> {
+    /// Rasterize the terminal into the staging buffer
+    pub fn run(self) {
+        self.terminal.render(self.width, self.height, self.show_cursor);
+
+        // Convert u32 ARGB to BGRA bytes for Argb8888 format
+        self.staging.clear();
+        self.staging.extend(self.terminal.buffer().iter().flat_map(|pixel| {
+            let a = ((pixel >> 24) & 0xFF) as u8;
+            let r = ((pixel >> 16) & 0xFF) as u8;
+            let g = ((pixel >> 8) & 0xFF) as u8;
+            let b = (pixel & 0xFF) as u8;
+            [b, g, r, a]
+        }));
+    }
 }
 
 impl ManagedTerminal {
-    /// Create a new managed terminal
-    pub fn new(id: TerminalId, cols: u16, rows: u16, cell_width: u32, cell_height: u32, theme: Theme, font_size: f32) -> Result<Self, terminal::state::TerminalError> {
-        let terminal = Terminal::new_with_options(cols, rows, theme, font_size)?;
+    /// Create a new managed terminal, its shell started in `working_dir`
+    /// (the compositor's working directory if `None`)
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        id: TerminalId,
+        cols: u16,
+        rows: u16,
+        cell_width: u32,
+        cell_height: u32,
+        theme: Theme,
+        font_size: f32,
+        working_dir: Option<&Path>,
+    ) -> Result<Self, terminal::state::TerminalError> {
+        let terminal = Terminal::new_with_options_in(cols, rows, theme, font_size, working_dir)?;
 
         // Use shell name as title
         let title = std::env::var("SHELL")
@@ -226,10 +325,21 @@ impl ManagedTerminal {
             id,
             width: cols as u32 * cell_width,
             height: rows as u32 * cell_height,
+            command: title.clone(),
             title,
+            custom_title: None,
             show_title_bar: false, // Shell terminals don't show title bar
+            cwd: working_dir.map(Path::to_path_buf).or_else(|| std::env::current_dir().ok()),
+            started_at: SystemTime::now(),
+            finished_at: None,
             #[cfg(all(feature = "x11-backend", target_os = "linux"))]
             texture: None,
+            #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+            staging: Vec::new(),
+            #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+            last_on_screen: std::time::Instant::now(),
+            #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+            texture_generation: 0,
             #[cfg(all(feature = "headless-backend", not(feature = "x11-backend")))]
             pixel_buffer: Vec::new(),
             dirty: true,
@@ -242,7 +352,15 @@ impl ManagedTerminal {
             death_time: None,
             prev_alt_screen: false,
             manually_sized: false,
+            growth_capped: false,
             pending_write: Vec::new(),
+            liveness: Default::default(),
+            recording: None,
+            font_size: None,
+            output_files: Vec::new(),
+            tui_mode: None,
+            smart_view: None,
+            smart_output_checked: false,
         })
     }
 
@@ -284,9 +402,20 @@ impl ManagedTerminal {
             width: cols as u32 * cell_width,
             height: visual_rows as u32 * cell_height, // Use visual rows for display
             title,
+            custom_title: None,
             show_title_bar: true, // Command terminals show title bar
+            command: command.to_string(),
+            cwd: Some(working_dir.to_path_buf()),
+            started_at: SystemTime::now(),
+            finished_at: None,
             #[cfg(all(feature = "x11-backend", target_os = "linux"))]
             texture: None,
+            #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+            staging: Vec::new(),
+            #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+            last_on_screen: std::time::Instant::now(),
+            #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+            texture_generation: 0,
             #[cfg(all(feature = "headless-backend", not(feature = "x11-backend")))]
             pixel_buffer: Vec::new(),
             dirty: true,
@@ -299,7 +428,15 @@ impl ManagedTerminal {
             death_time: None,
             prev_alt_screen: false,
             manually_sized: false,
+            growth_capped: false,
             pending_write: Vec::new(),
+            liveness: Default::default(),
+            recording: None,
+            font_size: None,
+            output_files: Vec::new(),
+            tui_mode: None,
+            smart_view: None,
+            smart_output_checked: false,
         })
     }
 
@@ -314,9 +451,33 @@ impl ManagedTerminal {
         matches!(self.visibility.reason, VisibilityReason::Shell | VisibilityReason::HasOutput)
     }
 
+    /// Show where the output went, for a command that printed nothing
+    /// because it was redirected (`make > build.log`)
+    ///
+    /// Returns the growth needed to show every file.
+    fn show_output_files(&mut self) -> Vec<SizingAction> {
+        let note: String = self.output_files.iter()
+            .map(|path| format!("\x1b[2m→ {}\x1b[0m\r\n", path.display()))
+            .collect();
+        let actions = self.terminal.process_output(note.as_bytes());
+        self.dirty = true;
+        self.visibility.on_output();
+        actions
+    }
+
     /// Process PTY output and mark dirty if needed
     pub fn process(&mut self) -> (Vec<SizingAction>, usize) {
         let (actions, bytes_read) = self.terminal.process_pty_with_count();
+        if let Some(recording) = &mut self.recording {
+            let output = self.terminal.take_recorded_output();
+            if !output.is_empty() {
+                if let Err(e) = recording.write_output(&output) {
+                    tracing::warn!(id = self.id.0, error = %e, "failed to record output, stopping recording");
+                    self.recording = None;
+                    self.terminal.set_recording(false);
+                }
+            }
+        }
         // Only mark dirty when there's actual output to render
         if bytes_read > 0 && !self.dirty {
             self.dirty = true;
@@ -418,6 +579,10 @@ impl ManagedTerminal {
                 );
             }
 
+            if self.tui_mode == Some(TuiMode::Never) {
+                tracing::info!(id = self.id.0, "terminal entered alternate screen, kept small by config");
+                return false;
+            }
             if self.height < max_height {
                 tracing::info!(
                     id = self.id.0,
@@ -470,6 +635,23 @@ impl ManagedTerminal {
         self.dirty = true;
     }
 
+    /// Re-rasterize at `font_size`, keeping the row count (capped at the output)
+    fn apply_font_size(&mut self, font_size: f32, output_width: u32, output_height: u32) -> bool {
+        let (_, old_cell_height) = self.cell_size();
+        let rows = self.height / old_cell_height.max(1);
+        let Some((cell_width, cell_height)) = self.terminal.set_font_size(font_size) else {
+            return false;
+        };
+        self.resize_cols((output_width / cell_width).max(1) as u16, cell_width);
+        let rows = rows.clamp(1, (output_height / cell_height).max(1));
+        self.height = rows * cell_height;
+        if let SizingAction::ApplyResize { .. } = self.terminal.configure(rows as u16) {
+            self.terminal.complete_resize();
+        }
+        self.dirty = true;
+        true
+    }
+
     /// Get the terminal's PTY fd for polling
     pub fn pty_fd(&self) -> RawFd {
         self.terminal.pty_fd()
@@ -480,10 +662,42 @@ impl ManagedTerminal {
         self.terminal.is_running()
     }
 
+    /// Process ID of the terminal's child process
+    pub fn pid(&self) -> u32 {
+        self.terminal.pid()
+    }
+
+    /// Exit status of the child process, once it has been reaped
+    pub fn exit_status(&self) -> Option<std::process::ExitStatus> {
+        self.terminal.exit_status()
+    }
+
+    /// Record an exit status collected by the child reaper
+    pub fn record_exit(&mut self, status: std::process::ExitStatus) {
+        self.terminal.record_exit(status);
+    }
+
     /// Mark terminal as exited (hides cursor on next render)
     pub fn mark_exited(&mut self) {
         self.exited = true;
         self.death_time = Some(std::time::Instant::now());
+        self.finished_at.get_or_insert_with(SystemTime::now);
+    }
+
+    /// Title to show: the cell's custom name, else `title`
+    pub fn display_title(&self) -> &str {
+        self.custom_title.as_deref().unwrap_or(&self.title)
+    }
+
+    /// Title bar text: the display title, marked when frozen or when the
+    /// output has a formatted view to switch to
+    pub fn title_bar_text(&self) -> std::borrow::Cow<'_, str> {
+        let title = self.liveness.decorate_title(self.display_title());
+        match &self.smart_view {
+            Some(view) if view.output_rows.is_some() => format!("{} [Super+E: raw output]", title).into(),
+            Some(view) => format!("{} [Super+E: {}]", title, view.kind()).into(),
+            None => title,
+        }
     }
 
     /// Check if terminal process has exited
@@ -501,52 +715,55 @@ impl ManagedTerminal {
         self.terminal.cell_size()
     }
 
-    /// Render terminal to GPU texture (X11 backend)
+    /// Whether the texture is out of date (X11 backend)
+    ///
+    /// Re-render if dirty OR if selection coordinates changed. This ensures
+    /// we only regenerate the texture when selection actually moves, not
+    /// every frame.
     #[cfg(all(feature = "x11-backend", target_os = "linux"))]
-    pub fn render(&mut self, renderer: &mut GlesRenderer) -> Option<&GlesTexture> {
-        // Re-render if dirty OR if selection coordinates changed
-        // This ensures we only regenerate texture when selection actually moves, not every frame
-        if !self.dirty && !self.selection_dirty && self.texture.is_some() {
-            return self.texture.as_ref();
-        }
-
-        // Render terminal to pixel buffer (hide cursor if process exited)
-        self.terminal.render(self.width, self.height, !self.exited);
-        let buffer = self.terminal.buffer();
+    pub fn needs_render(&self) -> bool {
+        self.dirty || self.selection_dirty || self.texture.is_none()
+    }
 
-        if buffer.is_empty() {
-            return None;
+    /// Borrow what rasterization needs, so it can run on a worker thread
+    /// while the GL texture stays on the render thread (X11 backend)
+    #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+    pub fn raster_job(&mut self) -> RasterJob<'_> {
+        RasterJob {
+            terminal: &mut self.terminal,
+            width: self.width,
+            height: self.height,
+            // Hide cursor if process exited
+            show_cursor: !self.exited,
+            staging: &mut self.staging,
         }
+    }
 
-        // Convert u32 ARGB to BGRA bytes for Argb8888 format
-        let bytes: Vec<u8> = buffer.iter()
-            .flat_map(|pixel| {
-                let a = ((pixel >> 24) & 0xFF) as u8;
-                let r = ((pixel >> 16) & 0xFF) as u8;
-                let g = ((pixel >> 8) & 0xFF) as u8;
-                let b = (pixel & 0xFF) as u8;
-                [b, g, r, a]
-            })
-            .collect();
+    /// Upload rasterized pixels to the GPU texture (X11 backend)
+    ///
+    /// Must run on the render thread. Does nothing if no new pixels were
+    /// rasterized since the last upload. The previous texture is updated in
+    /// place or handed back to `pool`.
+    #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+    pub fn upload(&mut self, renderer: &mut GlesRenderer, pool: &mut TexturePool) {
+        if self.staging.is_empty() {
+            return;
+        }
 
-        // Import texture from raw pixels
         let size = Size::from((self.width as i32, self.height as i32));
+        let result = pool.upload(renderer, &self.staging, size, self.texture.take());
+        // Keep the allocation for the next frame
+        self.staging.clear();
 
-        match renderer.import_memory(
-            &bytes,
-            smithay::backend::allocator::Fourcc::Argb8888,
-            size,
-            false,
-        ) {
+        match result {
             Ok(texture) => {
                 self.texture = Some(texture);
+                self.texture_generation += 1;
                 self.dirty = false;
                 self.selection_dirty = false; // Clear after rendering
-                self.texture.as_ref()
             }
             Err(e) => {
                 tracing::warn!("Failed to create texture: {:?}", e);
-                None
             }
         }
     }
@@ -614,10 +831,16 @@ impl ManagedTerminal {
 
     /// Get cached texture (for rendering after pre-render pass)
     #[cfg(all(feature = "x11-backend", target_os = "linux"))]
-    pub fn get_texture(&self) -> Option<&GlesTexture> {
+    pub fn get_texture(&self) -> Option<&TerminalTexture> {
         self.texture.as_ref()
     }
 
+    /// Changes whenever a new texture is uploaded (X11 backend)
+    #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+    pub fn texture_generation(&self) -> u64 {
+        self.texture_generation
+    }
+
     /// Get cached pixel buffer (for headless rendering)
     #[cfg(all(feature = "headless-backend", not(feature = "x11-backend")))]
     pub fn get_pixel_buffer(&self) -> Option<&[u8]> {
@@ -650,6 +873,10 @@ pub struct TerminalManager {
     /// Maximum terminal height in rows (capped at viewport)
     pub max_rows: u16,
 
+    /// Output size in pixels, for sizing zoomed terminals
+    output_width: u32,
+    output_height: u32,
+
     /// Color theme for terminals
     theme: Theme,
 
@@ -664,6 +891,40 @@ pub struct TerminalManager {
 
     /// Time to live for dead terminals
     dead_terminal_ttl: std::time::Duration,
+
+    /// Fold repeated output lines in command terminals
+    fold_repeated_lines: bool,
+
+    /// TUI sizing overrides by program (`[tui]` in the config)
+    tui_overrides: BTreeMap<String, TuiMode>,
+
+    /// Rows unfocused terminals grow to at most (None = no cap)
+    background_max_rows: Option<u16>,
+
+    /// Look for JSON and table output in finished commands
+    smart_output: bool,
+
+    /// Low-vision palette adjustments for terminals
+    contrast: Contrast,
+
+    /// Draw programming ligatures in terminals
+    ligatures: bool,
+
+    /// Session directory command terminals are recorded to
+    recording_dir: Option<PathBuf>,
+
+    /// Directory the next shell from `spawn` starts in, once
+    next_shell_dir: Option<PathBuf>,
+
+    /// Textures of removed terminals, reused for new ones (X11 backend)
+    #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+    texture_pool: TexturePool,
+
+    /// Release textures of terminals off-screen and unchanged this long
+    texture_evict_after: Option<std::time::Duration>,
+
+    /// Compress the scrollback of exited terminals left alone this long
+    compress_idle_after: Option<std::time::Duration>,
 }
 
 impl TerminalManager {
@@ -688,11 +949,25 @@ impl TerminalManager {
             default_cols,
             initial_rows,
             max_rows,
+            output_width,
+            output_height,
             theme,
             font_size,
             max_terminals: 100,
             max_dead_terminals: 20,
             dead_terminal_ttl: std::time::Duration::from_secs(60 * 60),
+            fold_repeated_lines: true,
+            tui_overrides: BTreeMap::new(),
+            background_max_rows: None,
+            smart_output: true,
+            contrast: Contrast::default(),
+            ligatures: false,
+            recording_dir: None,
+            next_shell_dir: None,
+            #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+            texture_pool: TexturePool::default(),
+            texture_evict_after: None,
+            compress_idle_after: None,
         }
     }
 
@@ -716,6 +991,59 @@ impl TerminalManager {
         self.terminals.values().filter(|t| t.has_exited()).count()
     }
 
+    /// Fold repeated output lines in newly spawned command terminals
+    pub fn set_fold_repeated_lines(&mut self, fold: bool) {
+        self.fold_repeated_lines = fold;
+    }
+
+    /// Size command terminals spawned from now on by these overrides
+    pub fn set_tui_overrides(&mut self, overrides: BTreeMap<String, TuiMode>) {
+        self.tui_overrides = overrides;
+    }
+
+    /// Cap the growth of unfocused terminals at `rows` (0 = no cap)
+    pub fn set_background_max_rows(&mut self, rows: u16) {
+        self.background_max_rows = (rows > 0).then_some(rows);
+    }
+
+    /// Rows an unfocused terminal asking for `target_rows` may grow to
+    ///
+    /// Never fewer than it has: the cap stops growth, it doesn't shrink
+    /// cells that grew while focused. Marks the terminal `growth_capped`
+    /// when the cap holds it back.
+    pub fn cap_background_growth(&mut self, id: TerminalId, target_rows: u16) -> u16 {
+        let Some(cap) = self.background_max_rows else {
+            return target_rows;
+        };
+        let (_, cell_height, _) = self.cell_metrics(id);
+        let Some(terminal) = self.terminals.get_mut(&id) else {
+            return target_rows;
+        };
+        let rows = (terminal.height / cell_height.max(1)).min(u16::MAX as u32) as u16;
+        let cap = cap.max(rows);
+        if target_rows > cap {
+            terminal.growth_capped = true;
+            cap
+        } else {
+            target_rows
+        }
+    }
+
+    /// Offer formatted views of JSON and table output (see `smart_output`)
+    pub fn set_smart_output(&mut self, enabled: bool) {
+        self.smart_output = enabled;
+    }
+
+    /// Whether finished commands get formatted views of their output
+    pub fn smart_output(&self) -> bool {
+        self.smart_output
+    }
+
+    /// Record the output of command terminals spawned from now on to `dir`
+    pub fn set_recording_dir(&mut self, dir: Option<PathBuf>) {
+        self.recording_dir = dir;
+    }
+
     /// Set maximum dead terminals to keep
     pub fn set_max_dead_terminals(&mut self, max: usize) {
         self.max_dead_terminals = max;
@@ -765,7 +1093,7 @@ impl TerminalManager {
             dead.sort_by_key(|(_, dt)| *dt);
             let to_remove = dead.len() - self.max_dead_terminals;
             for (id, _) in dead.iter().take(to_remove) {
-                if self.terminals.remove(id).is_some() {
+                if self.remove(*id).is_some() {
                     removed.push(*id);
                     tracing::info!(id = id.0, "removed dead terminal (max_dead_terminals exceeded)");
                 }
@@ -797,7 +1125,7 @@ impl TerminalManager {
 
         // Remove oldest terminals (from beginning of visual order)
         for id in ordered_terminal_ids.iter().take(to_remove) {
-            if self.terminals.remove(id).is_some() {
+            if self.remove(*id).is_some() {
                 tracing::info!(id = id.0, "Removed terminal to enforce limit");
                 removed.push(*id);
             }
@@ -830,12 +1158,28 @@ impl TerminalManager {
         self.cell_height = height;
         self.default_cols = (output_width / width).max(1) as u16;
         self.max_rows = (output_height / height).max(1) as u16;
+        self.output_width = output_width;
+        self.output_height = output_height;
     }
 
     /// Update output size (called when compositor window is resized)
     pub fn update_output_size(&mut self, width: u32, height: u32) {
         self.default_cols = (width / self.cell_width).max(1) as u16;
         self.max_rows = (height / self.cell_height).max(1) as u16;
+        self.output_width = width;
+        self.output_height = height;
+    }
+
+    /// Cell width, cell height and maximum rows of a terminal, which differ
+    /// from the manager's when the terminal is zoomed
+    pub fn cell_metrics(&self, id: TerminalId) -> (u32, u32, u16) {
+        match self.terminals.get(&id).filter(|t| t.font_size.is_some()) {
+            Some(terminal) => {
+                let (cell_width, cell_height) = terminal.cell_size();
+                (cell_width, cell_height, (self.output_height / cell_height.max(1)).max(1) as u16)
+            }
+            None => (self.cell_width, self.cell_height, self.max_rows),
+        }
     }
 
     /// Resize all terminals to new column width
@@ -844,7 +1188,13 @@ impl TerminalManager {
         let cell_width = self.cell_width;
 
         for terminal in self.terminals.values_mut() {
-            terminal.resize_cols(new_cols, cell_width);
+            if terminal.font_size.is_some() {
+                // Zoomed terminals fit their own number of columns
+                let (cell_width, _) = terminal.cell_size();
+                terminal.resize_cols((output_width / cell_width).max(1) as u16, cell_width);
+            } else {
+                terminal.resize_cols(new_cols, cell_width);
+            }
         }
 
         tracing::info!(
@@ -856,8 +1206,7 @@ impl TerminalManager {
 
     /// Grow a terminal to accommodate more content (capped at max_rows)
     pub fn grow_terminal(&mut self, id: TerminalId, target_rows: u16) {
-        let max_rows = self.max_rows;
-        let cell_height = self.cell_height;
+        let (_, cell_height, max_rows) = self.cell_metrics(id);
 
         if let Some(terminal) = self.terminals.get_mut(&id) {
             let old_height = terminal.height;
@@ -876,7 +1225,25 @@ impl TerminalManager {
     }
 
     /// Spawn a new terminal
+    ///
+    /// Its shell starts in the directory set with `start_next_shell_in`, if
+    /// any, otherwise in the compositor's working directory.
     pub fn spawn(&mut self) -> Result<TerminalId, SpawnError> {
+        let working_dir = self.next_shell_dir.take();
+        self.spawn_shell(working_dir.as_deref())
+    }
+
+    /// Spawn a new terminal with its shell started in `working_dir`
+    pub fn spawn_in(&mut self, working_dir: &Path) -> Result<TerminalId, SpawnError> {
+        self.spawn_shell(Some(working_dir))
+    }
+
+    /// Start the next shell from `spawn` in `dir` (for a restored session)
+    pub fn start_next_shell_in(&mut self, dir: PathBuf) {
+        self.next_shell_dir = Some(dir);
+    }
+
+    fn spawn_shell(&mut self, working_dir: Option<&Path>) -> Result<TerminalId, SpawnError> {
         // Check terminal count limit - if at max, we'll remove oldest later
         // (done after ID allocation to avoid removing the terminal we're spawning)
         let id = TerminalId(self.next_id);
@@ -890,6 +1257,7 @@ impl TerminalManager {
             self.cell_height,
             self.theme,
             self.font_size,
+            working_dir,
         )?;
 
         // Get actual cell dimensions from the font and update
@@ -903,6 +1271,9 @@ impl TerminalManager {
             terminal.height = self.initial_rows as u32 * actual_cell_height;
         }
 
+        terminal.terminal.set_contrast(self.contrast);
+        terminal.terminal.set_ligatures(self.ligatures);
+
         tracing::info!(id = id.0, cols = self.default_cols, rows = self.initial_rows,
                        max_rows = self.max_rows,
                        cell_w = self.cell_width, cell_h = self.cell_height,
@@ -962,6 +1333,8 @@ impl TerminalManager {
             self.theme,
             self.font_size,
         )?;
+        terminal.set_contrast(self.contrast);
+        terminal.set_ligatures(self.ligatures);
 
         // Inject the result content into the terminal
         if !result.is_empty() {
@@ -996,9 +1369,20 @@ impl TerminalManager {
             width: self.default_cols as u32 * cell_width,
             height: visual_rows as u32 * cell_height,
             title,
+            custom_title: None,
             show_title_bar: true,
+            command: command.to_string(),
+            cwd: None,
+            started_at: SystemTime::now(),
+            finished_at: Some(SystemTime::now()),
             #[cfg(all(feature = "x11-backend", target_os = "linux"))]
             texture: None,
+            #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+            staging: Vec::new(),
+            #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+            last_on_screen: std::time::Instant::now(),
+            #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+            texture_generation: 0,
             #[cfg(all(feature = "headless-backend", not(feature = "x11-backend")))]
             pixel_buffer: Vec::new(),
             dirty: true,
@@ -1014,7 +1398,15 @@ impl TerminalManager {
             death_time: None,
             prev_alt_screen: false,
             manually_sized: false,
+            growth_capped: false,
             pending_write: Vec::new(),
+            liveness: Default::default(),
+            recording: None,
+            font_size: None,
+            output_files: Vec::new(),
+            tui_mode: None,
+            smart_view: None,
+            smart_output_checked: false,
         };
 
         // Update cell size if it changed
@@ -1058,8 +1450,13 @@ impl TerminalManager {
         self.next_id += 1;
 
         // Use large PTY (no scrolling) but small visual size
-        // TUI apps will auto-resize when alternate screen is detected
-        let (pty_rows, visual_rows) = (1000, self.initial_rows);
+        // TUI apps will auto-resize when alternate screen is detected,
+        // commands configured as TUIs start at full height
+        let tui_mode = crate::config::tui_mode(&self.tui_overrides, command);
+        let (pty_rows, visual_rows) = match tui_mode {
+            Some(TuiMode::Always) => (self.max_rows, self.max_rows),
+            _ => (1000, self.initial_rows),
+        };
 
         let mut terminal = ManagedTerminal::new_with_command(
             id,
@@ -1076,6 +1473,21 @@ impl TerminalManager {
             self.theme,
             self.font_size,
         )?;
+        terminal.terminal.set_fold_repeated_lines(self.fold_repeated_lines);
+        terminal.tui_mode = tui_mode;
+        terminal.terminal.set_contrast(self.contrast);
+        terminal.terminal.set_ligatures(self.ligatures);
+        if let Some(dir) = &self.recording_dir {
+            let path = dir.join(format!("{}.cast", id.0));
+            let header = crate::recording::header(self.default_cols, self.max_rows, &terminal.title, command);
+            match CastWriter::create(&path, &header) {
+                Ok(writer) => {
+                    terminal.terminal.set_recording(true);
+                    terminal.recording = Some(writer);
+                }
+                Err(e) => tracing::warn!(path = %path.display(), error = %e, "failed to start recording"),
+            }
+        }
 
         // Get actual cell dimensions from the font and update
         let (actual_cell_width, actual_cell_height) = terminal.cell_size();
@@ -1124,9 +1536,83 @@ impl TerminalManager {
         self.get(id).map(|t| t.is_visible()).unwrap_or(false)
     }
 
-    /// Remove a terminal
+    /// Put back a terminal taken out with `remove`
+    pub fn restore(&mut self, terminal: ManagedTerminal) {
+        self.terminals.insert(terminal.id, terminal);
+    }
+
+    /// Remove a terminal, returning its texture to the pool
     pub fn remove(&mut self, id: TerminalId) -> Option<ManagedTerminal> {
-        self.terminals.remove(&id)
+        #[allow(unused_mut)]
+        let mut terminal = self.terminals.remove(&id)?;
+        #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+        if let Some(texture) = terminal.texture.take() {
+            self.texture_pool.release(texture);
+        }
+        Some(terminal)
+    }
+
+    /// Release the textures of terminals that are off-screen and unchanged
+    /// for a while, returning how many were released (X11 backend)
+    ///
+    /// Terminals in `on_screen` are noted as seen. Released textures go back
+    /// to the pool; `needs_render` asks for a new one once the cell is near
+    /// the viewport again.
+    #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+    pub fn evict_textures(&mut self, on_screen: &std::collections::HashSet<TerminalId>, now: std::time::Instant) -> usize {
+        let Some(after) = self.texture_evict_after else {
+            return 0;
+        };
+        let mut evicted = 0;
+        for (id, terminal) in &mut self.terminals {
+            if on_screen.contains(id) {
+                terminal.last_on_screen = now;
+                continue;
+            }
+            if !crate::texture_pool::is_evictable(terminal.last_on_screen, terminal.last_dirty_time, now, after) {
+                continue;
+            }
+            if let Some(texture) = terminal.texture.take() {
+                self.texture_pool.release(texture);
+                terminal.staging = Vec::new();
+                evicted += 1;
+            }
+        }
+        evicted
+    }
+
+    /// Compress the scrollback of terminals whose program exited and that
+    /// haven't been marked dirty since, both at least the configured time ago
+    ///
+    /// The grid comes back on its own the next time it is read (rendering,
+    /// search, export, selection). Returns how many were compressed.
+    pub fn compress_idle_scrollback(&mut self, now: std::time::Instant) -> usize {
+        let Some(after) = self.compress_idle_after else {
+            return 0;
+        };
+        let mut compressed = 0;
+        for (id, terminal) in &mut self.terminals {
+            let Some(death_time) = terminal.death_time else {
+                continue;
+            };
+            let idle_since = death_time.max(terminal.last_dirty_time);
+            if now.saturating_duration_since(idle_since) < after || terminal.terminal.is_scrollback_compressed() {
+                continue;
+            }
+            if let Some(bytes) = terminal.terminal.compress_scrollback() {
+                tracing::debug!(id = id.0, bytes, "compressed scrollback of idle terminal");
+                compressed += 1;
+            }
+        }
+        compressed
+    }
+
+    /// Upload rasterized pixels of all terminals to their textures (X11 backend)
+    #[cfg(all(feature = "x11-backend", target_os = "linux"))]
+    pub fn upload_textures(&mut self, renderer: &mut GlesRenderer) {
+        for terminal in self.terminals.values_mut() {
+            terminal.upload(renderer, &mut self.texture_pool);
+        }
     }
 
     /// Get all terminal IDs in order
@@ -1218,6 +1704,7 @@ impl TerminalManager {
         let mut dead = Vec::new();
         let mut parents_to_focus = Vec::new();
         let mut terminals_to_transition = Vec::new();
+        let mut terminals_to_grow = Vec::new();
         let mut focus_changed_to = None;
 
         for id in ids {
@@ -1243,8 +1730,18 @@ impl TerminalManager {
                         // WaitingForOutput -> ExitedEmpty (hidden)
                         // HasOutput -> HasOutput (stays visible)
                         if term.visibility.reason == VisibilityReason::WaitingForOutput {
-                            terminals_to_transition.push(id);
-                            tracing::info!(id = id.0, "command terminal exited without output");
+                            if term.output_files.is_empty() {
+                                terminals_to_transition.push(id);
+                                tracing::info!(id = id.0, "command terminal exited without output");
+                            } else {
+                                // Nothing printed because it all went to files: say where
+                                for action in term.show_output_files() {
+                                    if let SizingAction::RequestGrowth { target_rows } = action {
+                                        terminals_to_grow.push((id, target_rows));
+                                    }
+                                }
+                                tracing::info!(id = id.0, files = ?term.output_files, "command output went to files");
+                            }
                         }
                     }
                 }
@@ -1261,10 +1758,13 @@ impl TerminalManager {
                 term.visibility.on_exit();
             }
         }
+        for (id, target_rows) in terminals_to_grow {
+            self.grow_terminal(id, target_rows);
+        }
 
         // Remove dead terminals
         for id in &dead {
-            self.terminals.remove(id);
+            self.remove(*id);
             tracing::info!(id = id.0, "terminal removed");
         }
 
@@ -1286,6 +1786,57 @@ impl TerminalManager {
         self.font_size
     }
 
+    /// Switch the color theme of all terminals at runtime
+    ///
+    /// Programs that enabled color scheme reports (mode 2031) are told.
+    pub fn set_theme(&mut self, theme: Theme) {
+        if theme == self.theme {
+            return;
+        }
+        self.theme = theme;
+        for terminal in self.terminals.values_mut() {
+            terminal.terminal.set_theme(theme);
+            terminal.mark_dirty();
+        }
+        tracing::info!(?theme, terminal_count = self.terminals.len(), "theme changed for all terminals");
+    }
+
+    /// Set the high-contrast and minimum-contrast adjustments of all terminals
+    pub fn set_contrast(&mut self, contrast: Contrast) {
+        if contrast == self.contrast {
+            return;
+        }
+        self.contrast = contrast;
+        for terminal in self.terminals.values_mut() {
+            terminal.terminal.set_contrast(contrast);
+            terminal.mark_dirty();
+        }
+    }
+
+    /// Release textures of terminals off-screen and unchanged for `after`
+    /// (`None` keeps them)
+    pub fn set_texture_eviction(&mut self, after: Option<std::time::Duration>) {
+        self.texture_evict_after = after;
+    }
+
+    /// Compress the scrollback of exited terminals left alone for `after`
+    /// (`None` keeps it as it is)
+    pub fn set_scrollback_compression(&mut self, after: Option<std::time::Duration>) {
+        self.compress_idle_after = after;
+    }
+
+    /// Draw programming ligatures in all terminals
+    pub fn set_ligatures(&mut self, ligatures: bool) {
+        if ligatures == self.ligatures {
+            return;
+        }
+        self.ligatures = ligatures;
+        for terminal in self.terminals.values_mut() {
+            terminal.terminal.set_ligatures(ligatures);
+            terminal.mark_dirty();
+        }
+    }
+
     /// Change font size for all terminals at runtime
     ///
     /// Updates cell dimensions, recalculates cols/rows, and resizes every terminal.
@@ -1309,11 +1860,13 @@ impl TerminalManager {
         self.cell_height = new_cell_height;
         self.default_cols = (output_width / new_cell_width).max(1) as u16;
         self.max_rows = (output_height / new_cell_height).max(1) as u16;
+        self.output_width = output_width;
+        self.output_height = output_height;
 
         let new_cols = self.default_cols;
 
-        // Update every terminal
-        for terminal in self.terminals.values_mut() {
+        // Update every terminal that isn't zoomed on its own
+        for terminal in self.terminals.values_mut().filter(|t| t.font_size.is_none()) {
             terminal.terminal.set_font_size(new_font_size);
             terminal.terminal.resize_cols(new_cols);
             terminal.width = new_cols as u32 * new_cell_width;
@@ -1344,6 +1897,30 @@ impl TerminalManager {
     }
 
 
+    /// Change the font size of one terminal by `delta`, independently of the
+    /// global size
+    ///
+    /// Only that terminal is re-rasterized; its row count is kept. Zooming it
+    /// back to the global size makes it follow global changes again.
+    pub fn zoom_terminal(&mut self, id: TerminalId, delta: f32) {
+        let global = self.font_size;
+        let (output_width, output_height) = (self.output_width, self.output_height);
+        let Some(terminal) = self.terminals.get_mut(&id) else {
+            return;
+        };
+        let current = terminal.font_size.unwrap_or(global);
+        let new_font_size = (current + delta).clamp(6.0, 72.0);
+        if (new_font_size - current).abs() < f32::EPSILON {
+            return;
+        }
+        if !terminal.apply_font_size(new_font_size, output_width, output_height) {
+            tracing::warn!("failed to load font at size {}", new_font_size);
+            return;
+        }
+        terminal.font_size = Some(new_font_size).filter(|size| (size - global).abs() >= f32::EPSILON);
+        tracing::info!(id = id.0, font_size = new_font_size, rows = terminal.height / terminal.cell_size().1, "terminal zoomed");
+    }
+
     /// Get the Y position of a visible terminal (for scrolling to it)
     pub fn terminal_y_position(&self, target_id: TerminalId) -> Option<i32> {
         let mut y = 0i32;
@@ -1,5 +1,6 @@
     use super::*;
     use std::collections::HashMap;
+    use crate::cell_id::CellRegistry;
     use crate::state::{LayoutNode, StackWindow};
 
     #[test]
@@ -89,6 +90,35 @@
         }
     }
 
+    #[test]
+    fn zooming_one_terminal_leaves_the_others() {
+        let mut manager = TerminalManager::new_with_size(800, 720, terminal::Theme::default(), 14.0);
+        let env = HashMap::new();
+        let cwd = std::path::Path::new("/tmp");
+        let zoomed = manager.spawn_command("", "echo zoomed", cwd, &env, None).unwrap();
+        let other = manager.spawn_command("", "echo other", cwd, &env, None).unwrap();
+        manager.grow_terminal(zoomed, 3);
+        let (other_width, other_height) = manager.get(other).unwrap().cell_size();
+
+        manager.zoom_terminal(zoomed, 10.0);
+        let terminal = manager.get(zoomed).unwrap();
+        let (cell_width, cell_height) = terminal.cell_size();
+        assert_eq!(terminal.font_size, Some(24.0));
+        assert!(cell_height > other_height);
+        assert_eq!(terminal.height, 3 * cell_height, "row count is kept");
+        assert_eq!(terminal.width, (800 / cell_width) * cell_width);
+        assert_eq!(manager.cell_metrics(zoomed).2, (720 / cell_height) as u16);
+        assert_eq!(manager.get(other).unwrap().cell_size(), (other_width, other_height));
+
+        // Zoomed terminals keep their size when the global size changes
+        manager.set_font_size(16.0, 800, 720);
+        assert_eq!(manager.get(zoomed).unwrap().cell_size(), (cell_width, cell_height));
+
+        // Back at the global size it follows global changes again
+        manager.zoom_terminal(zoomed, -8.0);
+        assert_eq!(manager.get(zoomed).unwrap().font_size, None);
+    }
+
     #[test]
     fn non_tui_terminal_has_small_height() {
         let output_width = 800;
@@ -255,6 +285,57 @@
         );
     }
 
+    #[test]
+    fn tui_overrides_size_command_terminals() {
+        let mut manager = TerminalManager::new_with_size(800, 720, terminal::Theme::default(), 14.0);
+        manager.set_tui_overrides(
+            [("dialog".to_string(), TuiMode::Always), ("tig".to_string(), TuiMode::Never)].into(),
+        );
+        let env = HashMap::new();
+        let cwd = std::path::Path::new("/tmp");
+
+        // Configured as a TUI: full height, and the PTY says so from the start
+        let id = manager.spawn_command("", "dialog --msgbox hi 5 20", cwd, &env, None).unwrap();
+        let max_rows = manager.max_rows;
+        let cell_height = manager.cell_height;
+        let terminal = manager.get(id).unwrap();
+        assert_eq!(terminal.tui_mode, Some(TuiMode::Always));
+        assert_eq!(terminal.terminal.dimensions().1, max_rows);
+        assert_eq!(terminal.height, max_rows as u32 * cell_height);
+
+        // Kept small: entering the alternate screen asks for no resize
+        let id = manager.spawn_command("", "tig", cwd, &env, None).unwrap();
+        let terminal = manager.get_mut(id).unwrap();
+        terminal.terminal.inject_bytes(b"\x1b[?1049h");
+        assert!(!terminal.check_alt_screen_resize_needed(max_rows as u32 * cell_height));
+        assert!(terminal.is_visible());
+
+        let id = manager.spawn_command("", "mc", cwd, &env, None).unwrap();
+        let terminal = manager.get_mut(id).unwrap();
+        assert_eq!(terminal.tui_mode, None);
+        terminal.terminal.inject_bytes(b"\x1b[?1049h");
+        assert!(terminal.check_alt_screen_resize_needed(max_rows as u32 * cell_height));
+    }
+
+    #[test]
+    fn background_growth_stops_at_the_cap() {
+        let mut manager = TerminalManager::new_with_size(800, 720, terminal::Theme::default(), 14.0);
+        let env = HashMap::new();
+        let id = manager.spawn_command("", "true", std::path::Path::new("/tmp"), &env, None).unwrap();
+        assert_eq!(manager.cap_background_growth(id, 30), 30, "no cap by default");
+
+        manager.set_background_max_rows(8);
+        manager.grow_terminal(id, 5);
+        assert_eq!(manager.cap_background_growth(id, 6), 6);
+        assert!(!manager.get(id).unwrap().growth_capped);
+        assert_eq!(manager.cap_background_growth(id, 30), 8);
+        assert!(manager.get(id).unwrap().growth_capped);
+
+        // A cell that grew past the cap while focused keeps its rows
+        manager.grow_terminal(id, 12);
+        assert_eq!(manager.cap_background_growth(id, 30), 12);
+    }
+
     #[test]
     fn stty_command_has_large_pty() {
         // All command terminals have 1000 PTY rows for internal scrollback
@@ -2327,6 +2408,39 @@
         );
     }
 
+    /// A command whose output all went to files stays visible and says where
+    #[test]
+    fn redirected_command_shows_output_files() {
+        use std::time::Duration;
+
+        let mut manager = TerminalManager::new_with_size(800, 720, terminal::Theme::default(), 14.0);
+        let parent_id = manager.spawn().expect("spawn parent");
+
+        let dir = std::env::temp_dir()
+            .join(format!("termstack-redirect-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let env = HashMap::new();
+        let child_id = manager
+            .spawn_command("", "echo one > a.log; echo two > b.log", &dir, &env, Some(parent_id))
+            .expect("spawn_command should succeed");
+        let files = vec![dir.join("a.log"), dir.join("b.log")];
+        manager.get_mut(child_id).unwrap().output_files = files.clone();
+
+        std::thread::sleep(Duration::from_millis(200));
+        manager.cleanup();
+
+        let child = manager.get(child_id).expect("child should still exist");
+        assert!(child.is_visible(), "redirected command should stay visible");
+        let grid = child.terminal.grid_content();
+        for file in &files {
+            let shown = format!("→ {}", file.display());
+            assert!(grid.iter().any(|line| line.contains(&shown)), "{shown:?} missing from {grid:?}");
+        }
+        assert_eq!(std::fs::read_to_string(&files[0]).unwrap(), "one\n");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     /// Test realistic scenario: command with echo prefix that fails immediately.
     ///
     /// This mimics what happens when termstack runs a command that fails:
@@ -2560,8 +2674,11 @@
 
         // Simulate the layout_node with height 0 (as the compositor would create it)
         let mut layout_nodes = vec![LayoutNode {
+            id: CellRegistry::default().insert(0),
             cell: StackWindow::Terminal(id),
             height: 0,
+            column: Default::default(),
+            tag: None,
         }];
 
         // Wait for the command to produce output and exit
@@ -2621,15 +2738,18 @@
         let launcher_height = manager.get(launcher_id).unwrap().height as i32;
         assert!(launcher_height > 0);
         let mut layout_nodes = vec![LayoutNode {
+            id: CellRegistry::default().insert(0),
             cell: StackWindow::Terminal(launcher_id),
             height: launcher_height,
+            column: Default::default(),
+            tag: None,
         }];
 
         // Hide the launcher terminal for foreground GUI
         manager.get_mut(launcher_id).unwrap().visibility.hide_for_gui();
         assert!(!manager.is_terminal_visible(launcher_id), "should be hidden after hide_for_gui");
 
-        // Recalculate heights (same logic as calculate_window_heights)
+        // Recalculate heights (same logic as frame_layout::estimate_heights)
         for node in &mut layout_nodes {
             if let StackWindow::Terminal(tid) = &node.cell {
                 if !manager.is_terminal_visible(*tid) {
@@ -2646,7 +2766,7 @@
         assert!(manager.is_terminal_visible(launcher_id), "should be visible after on_gui_exit");
 
         // Recalculate with fallback for cached height 0
-        // (same logic as calculate_window_heights)
+        // (same logic as frame_layout::estimate_heights)
         for node in &mut layout_nodes {
             if let StackWindow::Terminal(tid) = &node.cell {
                 if manager.is_terminal_visible(*tid) && node.height == 0 {
@@ -23,7 +23,17 @@ pub fn process_terminal_output(
     terminal_manager.flush_pending_writes();
 
     // Process PTY output and get sizing actions
-    let sizing_actions = terminal_manager.process_all();
+    let mut sizing_actions = terminal_manager.process_all();
+
+    // A cell held at the background growth cap catches up once focused
+    if let Some(FocusedWindow::Terminal(id)) = compositor.focused_window() {
+        if let Some(term) = terminal_manager.get_mut(*id) {
+            if std::mem::take(&mut term.growth_capped) {
+                let target_rows = term.content_rows().min(u16::MAX as u32) as u16;
+                sizing_actions.push((*id, terminal::sizing::SizingAction::RequestGrowth { target_rows }));
+            }
+        }
+    }
 
     // Handle sizing actions
     for (id, action) in sizing_actions {
@@ -34,34 +44,40 @@ pub fn process_terminal_output(
                 continue;
             }
 
+            // Unfocused cells grow at most to `background_max_rows`, so a
+            // chatty background job doesn't keep moving the layout
+            let is_focused = matches!(compositor.focused_window(), Some(FocusedWindow::Terminal(fid)) if *fid == id);
+            let target_rows = if is_focused {
+                target_rows
+            } else {
+                terminal_manager.cap_background_growth(id, target_rows)
+            };
+
             tracing::info!(id = id.0, target_rows, "processing growth request");
             terminal_manager.grow_terminal(id, target_rows);
 
             // If focused terminal grew, update cache and scroll (if bottom was visible)
-            let is_focused = matches!(compositor.focused_window.as_ref(), Some(FocusedWindow::Terminal(fid)) if *fid == id);
             if is_focused {
                 if let Some(idx) = find_terminal_window_index(compositor, id) {
                     // Check if bottom was visible before resize
                     let was_bottom_visible = is_window_bottom_visible(compositor, idx);
 
                     if let Some(term) = terminal_manager.get(id) {
-                        if let Some(node) = compositor.layout_nodes.get_mut(idx) {
-                            node.height = calculate_terminal_render_height(
-                                term.height as i32,
-                                term.show_title_bar,
-                                true, // visible since we're handling growth
-                            );
-                        }
+                        compositor.request_window_height(idx, calculate_terminal_render_height(
+                            term.height as i32,
+                            term.show_title_bar,
+                            true, // visible since we're handling growth
+                        ));
                     }
 
                     // Only autoscroll if bottom was already visible
                     // This allows users to scroll up while content flows in
                     if was_bottom_visible {
-                        compositor.scroll_to_show_window_bottom(idx);
+                        compositor.reveal_after_layout(idx);
                         tracing::debug!(
                             id = id.0,
                             idx,
-                            "autoscrolled after terminal growth (bottom was visible)"
+                            "autoscrolling after terminal growth (bottom was visible)"
                         );
                     } else {
                         tracing::debug!(
@@ -75,24 +91,6 @@ pub fn process_terminal_output(
         }
     }
 
-    // Fix up heights for terminals that just became visible
-    // (e.g., command terminals that received output this frame).
-    // Without this, a terminal transitioning from WaitingForOutput to HasOutput
-    // would keep height 0, causing validate_state() to panic.
-    for node in &mut compositor.layout_nodes {
-        if let StackWindow::Terminal(tid) = node.cell {
-            if node.height == 0 && terminal_manager.is_terminal_visible(tid) {
-                if let Some(term) = terminal_manager.get(tid) {
-                    node.height = calculate_terminal_render_height(
-                        term.height as i32,
-                        term.show_title_bar,
-                        true,
-                    );
-                }
-            }
-        }
-    }
-
     // Auto-resize terminals entering alternate screen mode
     auto_resize_alt_screen_terminals(compositor, terminal_manager);
 }
@@ -102,24 +100,22 @@ fn auto_resize_alt_screen_terminals(
     compositor: &mut TermStack,
     terminal_manager: &mut TerminalManager,
 ) {
-    let max_height = terminal_manager.max_rows as u32 * terminal_manager.cell_height;
     // Check ALL terminals, not just visible ones - TUI apps like fzf enter
     // alternate screen before producing content_rows, so they'd be hidden
     let all_ids = terminal_manager.ids();
 
     let mut ids_to_resize = Vec::new();
     for id in all_ids {
+        let (_, char_height, max_rows) = terminal_manager.cell_metrics(id);
         if let Some(term) = terminal_manager.get_mut(id) {
-            if term.check_alt_screen_resize_needed(max_height) {
+            if term.check_alt_screen_resize_needed(max_rows as u32 * char_height) {
                 ids_to_resize.push(id);
             }
         }
     }
 
-    let max_rows = terminal_manager.max_rows;
-    let char_height = terminal_manager.cell_height;
-
     for id in ids_to_resize {
+        let (_, char_height, max_rows) = terminal_manager.cell_metrics(id);
         if let Some(term) = terminal_manager.get_mut(id) {
             let old_height = term.height;
             term.resize(max_rows, char_height);
@@ -134,13 +130,11 @@ fn auto_resize_alt_screen_terminals(
 
             // Update cached height (note: new_height is content-only, need to add title bar)
             if let Some(idx) = find_terminal_window_index(compositor, id) {
-                if let Some(node) = compositor.layout_nodes.get_mut(idx) {
-                    node.height = calculate_terminal_render_height(
-                        new_height as i32,
-                        term.show_title_bar,
-                        true,
-                    );
-                }
+                compositor.request_window_height(idx, calculate_terminal_render_height(
+                    new_height as i32,
+                    term.show_title_bar,
+                    true,
+                ));
             }
         }
     }
@@ -160,26 +154,25 @@ pub fn find_terminal_window_index(compositor: &TermStack, id: TerminalId) -> Opt
 
 /// Check if a window's bottom edge is visible in the viewport.
 pub fn is_window_bottom_visible(compositor: &TermStack, window_idx: usize) -> bool {
-    let cell_top_y: i32 = compositor
-        .layout_nodes
-        .iter()
-        .take(window_idx)
-        .map(|n| n.height)
-        .sum();
+    let cell_top_y = compositor.column_content_y(window_idx);
     let window_height = compositor
         .layout_nodes
         .get(window_idx)
         .map(|n| n.height)
         .unwrap_or(0);
     let cell_bottom_y = cell_top_y + window_height;
-    let viewport_height = compositor.output_size.h;
+    let viewport_height = compositor.viewport_height();
 
     // Calculate minimum scroll needed to show cell bottom
     let min_scroll_for_bottom = (cell_bottom_y - viewport_height).max(0) as f64;
 
     // Cell bottom is visible if current scroll >= minimum needed
     // (allowing small epsilon for floating point comparison)
-    compositor.scroll_offset >= (min_scroll_for_bottom - 1.0)
+    let scroll_offset = compositor
+        .layout_nodes
+        .get(window_idx)
+        .map_or(compositor.scroll_offset, |node| compositor.column_scroll(node.column));
+    scroll_offset >= (min_scroll_for_bottom - 1.0)
 }
 
 /// Handle IPC resize request from termstack --resize.
@@ -194,7 +187,7 @@ pub fn handle_ipc_resize_request(
         return;
     };
 
-    let focused_id = match compositor.focused_window.as_ref() {
+    let focused_id = match compositor.focused_window() {
         Some(FocusedWindow::Terminal(id)) => *id,
         _ => {
             tracing::warn!("resize request but no focused terminal");
@@ -205,11 +198,11 @@ pub fn handle_ipc_resize_request(
         }
     };
 
-    let char_height = terminal_manager.cell_height;
+    let (_, char_height, max_rows) = terminal_manager.cell_metrics(focused_id);
     let new_rows = match resize_mode {
         crate::ipc::ResizeMode::Full => {
-            tracing::info!(id = focused_id.0, max_rows = terminal_manager.max_rows, "resize to full");
-            terminal_manager.max_rows
+            tracing::info!(id = focused_id.0, max_rows, "resize to full");
+            max_rows
         }
         crate::ipc::ResizeMode::Content => {
             // Process pending PTY output first
@@ -240,18 +233,13 @@ pub fn handle_ipc_resize_request(
             term.show_title_bar,
             true,
         );
-        for node in compositor.layout_nodes.iter_mut() {
-            if let StackWindow::Terminal(tid) = node.cell {
-                if tid == focused_id {
-                    node.height = total_height;
-                    break;
-                }
-            }
+        if let Some(idx) = find_terminal_window_index(compositor, focused_id) {
+            compositor.request_window_height(idx, total_height);
         }
 
         // Scroll to keep terminal visible
         if let Some(idx) = compositor.focused_index() {
-            compositor.scroll_to_show_window_bottom(idx);
+            compositor.reveal_after_layout(idx);
         }
     }
 
@@ -260,6 +248,29 @@ pub fn handle_ipc_resize_request(
     }
 }
 
+/// Answer IPC export requests from termstack export.
+///
+/// Sends `Ok(content)` for a terminal cell, `Err(message)` otherwise.
+pub fn handle_ipc_export_requests(
+    compositor: &mut TermStack,
+    terminal_manager: &TerminalManager,
+) {
+    for (index, format, stream) in std::mem::take(&mut compositor.pending_export_requests) {
+        let result = match compositor.layout_nodes.get(index).map(|node| &node.cell) {
+            Some(StackWindow::Terminal(id)) => match terminal_manager.get(*id) {
+                Some(term) => Ok(term.terminal.export(format.into(), term.display_title())),
+                None => Err(format!("cell {index} has no terminal")),
+            },
+            Some(StackWindow::External(_)) => Err(format!("cell {index} is a GUI window, not a terminal")),
+            None => Err(format!("no cell {index} ({} cells)", compositor.layout_nodes.len())),
+        };
+        tracing::info!(index, ?format, ok = result.is_ok(), "IPC export response");
+        if let Err(e) = crate::ipc::send_json_response(stream, &result) {
+            tracing::warn!(error = ?e, "Failed to send export response");
+        }
+    }
+}
+
 /// Promote output terminals that have content to standalone cells.
 ///
 /// Checks each external window's output terminal. If it has output and isn't
@@ -302,10 +313,7 @@ pub fn promote_output_terminals(
             .map(|t| calculate_terminal_render_height(t.height as i32, t.show_title_bar, true))
             .unwrap_or(0);
 
-        compositor.layout_nodes.insert(insert_idx, crate::state::LayoutNode {
-            cell: StackWindow::Terminal(term_id),
-            height,
-        });
+        compositor.insert_cell(insert_idx, StackWindow::Terminal(term_id), height);
         // Invalidate cache since layout_nodes changed
         compositor.invalidate_focused_index_cache();
 
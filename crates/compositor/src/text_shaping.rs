@@ -0,0 +1,325 @@
+//! Text shaping for title bars and overlay panels
+//!
+//! Lays a line of text out with rustybuzz so ligatures, combining marks and
+//! Arabic joining come out right, orders right-to-left runs with the Unicode
+//! bidi algorithm, and falls back to other system fonts for characters the
+//! primary font lacks (CJK, Arabic). Fallback fonts are only loaded when a
+//! character needs one. `TitleBarRenderer` rasterizes the shaped glyphs.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use unicode_bidi::BidiInfo;
+
+/// Fonts tried, in order, for characters the primary font has no glyph for
+pub const FALLBACK_FONT_PATHS: &[&str] = &[
+    // Proportional DejaVu covers Arabic, Hebrew and more symbols than the mono one
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/truetype/noto/NotoSansArabic-Regular.ttf",
+    "/usr/share/fonts/noto/NotoSansArabic-Regular.ttf",
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/wqy/wqy-zenhei.ttc",
+    "/usr/share/fonts/wenquanyi/wqy-zenhei/wqy-zenhei.ttc",
+    // macOS fonts
+    "/System/Library/Fonts/GeezaPro.ttc",
+    "/System/Library/Fonts/PingFang.ttc",
+    "/System/Library/Fonts/Hiragino Sans GB.ttc",
+];
+
+/// Appended to text cut short by `Shaper::shape_to_fit`
+pub const ELLIPSIS: char = '…';
+
+/// A font loaded for both shaping (rustybuzz) and rasterizing (fontdue)
+pub struct ShapingFont {
+    data: Vec<u8>,
+    raster: fontdue::Font,
+}
+
+impl ShapingFont {
+    /// Load the first face of a font or font collection file
+    pub fn from_bytes(data: Vec<u8>) -> Option<Self> {
+        // Make sure rustybuzz can read it too, so shaping never fails later
+        rustybuzz::Face::from_slice(&data, 0)?;
+        let raster = fontdue::Font::from_bytes(data.as_slice(), fontdue::FontSettings::default()).ok()?;
+        Some(Self { data, raster })
+    }
+
+    /// The font for rasterizing glyphs
+    pub fn raster(&self) -> &fontdue::Font {
+        &self.raster
+    }
+
+    fn has_glyph(&self, c: char) -> bool {
+        self.raster.lookup_glyph_index(c) != 0
+    }
+}
+
+/// A glyph placed on a line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionedGlyph {
+    /// Index of the font in the `Shaper`
+    pub font: usize,
+    pub glyph_id: u16,
+    /// Pen position from the start of the line (pixels)
+    pub x: f32,
+    /// Offset above the baseline (pixels)
+    pub y: f32,
+}
+
+/// A line of text laid out in visual order
+#[derive(Debug, Clone, Default)]
+pub struct ShapedLine {
+    pub glyphs: Vec<PositionedGlyph>,
+    /// Total advance (pixels)
+    pub width: f32,
+    /// Left edge of each character, in logical (string) order
+    pub char_positions: Vec<f32>,
+    /// Width of each character, in logical order
+    pub char_widths: Vec<f32>,
+}
+
+/// Shapes text with a primary font and lazily loaded fallbacks
+pub struct Shaper {
+    fonts: Vec<ShapingFont>,
+    /// Fallback font paths not tried yet
+    fallback_paths: std::slice::Iter<'static, &'static str>,
+}
+
+impl Shaper {
+    /// Shape with `primary`, falling back to `FALLBACK_FONT_PATHS`
+    pub fn new(primary: ShapingFont) -> Self {
+        Self::with_fallbacks(primary, FALLBACK_FONT_PATHS)
+    }
+
+    /// Shape with `primary`, falling back to the fonts at `fallback_paths`
+    pub fn with_fallbacks(primary: ShapingFont, fallback_paths: &'static [&'static str]) -> Self {
+        Self { fonts: vec![primary], fallback_paths: fallback_paths.iter() }
+    }
+
+    /// Font `index` of the glyphs this shaper returned (0 is the primary font)
+    pub fn font(&self, index: usize) -> &ShapingFont {
+        &self.fonts[index]
+    }
+
+    /// Index of a font with a glyph for `c`, preferring `current`, loading
+    /// fallbacks as needed (the primary font if none has one)
+    fn font_for(&mut self, c: char, current: Option<usize>) -> usize {
+        if let Some(current) = current.filter(|&i| self.fonts[i].has_glyph(c)) {
+            return current;
+        }
+        if let Some(index) = self.fonts.iter().position(|font| font.has_glyph(c)) {
+            return index;
+        }
+        for path in self.fallback_paths.by_ref() {
+            let Some(font) = std::fs::read(path).ok().and_then(ShapingFont::from_bytes) else {
+                continue;
+            };
+            tracing::debug!(path, "loaded fallback font");
+            self.fonts.push(font);
+            if self.fonts.last().is_some_and(|font| font.has_glyph(c)) {
+                return self.fonts.len() - 1;
+            }
+        }
+        current.unwrap_or(0)
+    }
+
+    /// Lay out `text` at `size` pixels
+    pub fn shape(&mut self, text: &str, size: f32) -> ShapedLine {
+        let mut line = ShapedLine::default();
+        if text.is_empty() {
+            return line;
+        }
+
+        // Left and right edge of every cluster, keyed by its first byte
+        let mut clusters: BTreeMap<usize, (f32, f32)> = BTreeMap::new();
+        let mut pen = 0.0;
+        for (run, rtl) in visual_runs(text) {
+            let mut font_runs = self.font_runs(text, run);
+            if rtl {
+                font_runs.reverse();
+            }
+            for (range, font) in font_runs {
+                pen = self.shape_run(text, range, font, rtl, size, pen, &mut line.glyphs, &mut clusters);
+            }
+        }
+        line.width = pen;
+
+        // Characters sharing a cluster (ligatures, marks) split its width
+        let starts: Vec<usize> = clusters.keys().copied().collect();
+        for (byte, _) in text.char_indices() {
+            let slot = starts.partition_point(|&start| start <= byte);
+            let Some(&start) = slot.checked_sub(1).and_then(|slot| starts.get(slot)) else {
+                line.char_positions.push(pen);
+                line.char_widths.push(0.0);
+                continue;
+            };
+            let end = starts.get(slot).copied().unwrap_or(text.len());
+            let (left, right) = clusters[&start];
+            let count = text[start..end].chars().count().max(1);
+            let first = text[start..byte].chars().count();
+            let width = (right - left) / count as f32;
+            line.char_positions.push(left + width * first as f32);
+            line.char_widths.push(width);
+        }
+        line
+    }
+
+    /// Lay out `text`, cutting it and appending `ELLIPSIS` if it is wider
+    /// than `max_width`. Returns the text actually shown with its layout.
+    pub fn shape_to_fit(&mut self, text: &str, size: f32, max_width: f32) -> (String, ShapedLine) {
+        let line = self.shape(text, size);
+        if line.width <= max_width {
+            return (text.to_string(), line);
+        }
+
+        // Longest prefix (in characters) that still fits with the ellipsis
+        let cut = |chars: usize| -> String {
+            let mut shown: String = text.chars().take(chars).collect::<String>().trim_end().to_string();
+            shown.push(ELLIPSIS);
+            shown
+        };
+        let (mut low, mut high) = (0, text.chars().count());
+        while low < high {
+            let mid = (low + high).div_ceil(2);
+            if self.shape(&cut(mid), size).width <= max_width {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+        let shown = cut(low);
+        let line = self.shape(&shown, size);
+        (shown, line)
+    }
+
+    /// Split `range` of `text` into runs of characters drawn with one font
+    fn font_runs(&mut self, text: &str, range: Range<usize>) -> Vec<(Range<usize>, usize)> {
+        let mut runs: Vec<(Range<usize>, usize)> = Vec::new();
+        for (offset, c) in text[range.clone()].char_indices() {
+            let start = range.start + offset;
+            let end = start + c.len_utf8();
+            let font = self.font_for(c, runs.last().map(|(_, font)| *font));
+            match runs.last_mut() {
+                Some((run, run_font)) if *run_font == font => run.end = end,
+                _ => runs.push((start..end, font)),
+            }
+        }
+        runs
+    }
+
+    /// Shape one single-font, single-direction run starting at `pen`,
+    /// returning the pen position after it
+    #[allow(clippy::too_many_arguments)]
+    fn shape_run(
+        &self,
+        text: &str,
+        range: Range<usize>,
+        font: usize,
+        rtl: bool,
+        size: f32,
+        mut pen: f32,
+        glyphs: &mut Vec<PositionedGlyph>,
+        clusters: &mut BTreeMap<usize, (f32, f32)>,
+    ) -> f32 {
+        let data = &self.fonts[font].data;
+        let Some(face) = rustybuzz::Face::from_slice(data, 0) else {
+            return pen;
+        };
+        let scale = size / face.units_per_em() as f32;
+
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(&text[range.clone()]);
+        buffer.set_direction(if rtl {
+            rustybuzz::Direction::RightToLeft
+        } else {
+            rustybuzz::Direction::LeftToRight
+        });
+        buffer.guess_segment_properties();
+        let shaped = rustybuzz::shape(&face, &[], buffer);
+
+        for (info, position) in shaped.glyph_infos().iter().zip(shaped.glyph_positions()) {
+            let advance = position.x_advance as f32 * scale;
+            glyphs.push(PositionedGlyph {
+                font,
+                glyph_id: info.glyph_id as u16,
+                x: pen + position.x_offset as f32 * scale,
+                y: position.y_offset as f32 * scale,
+            });
+            let cluster = clusters
+                .entry(range.start + info.cluster as usize)
+                .or_insert((pen, pen));
+            cluster.0 = cluster.0.min(pen);
+            cluster.1 = cluster.1.max(pen + advance);
+            pen += advance;
+        }
+        pen
+    }
+}
+
+/// Byte ranges of `text` in visual order, and whether each is right-to-left
+fn visual_runs(text: &str) -> Vec<(Range<usize>, bool)> {
+    let bidi = BidiInfo::new(text, None);
+    let mut runs = Vec::new();
+    for paragraph in &bidi.paragraphs {
+        let (levels, level_runs) = bidi.visual_runs(paragraph, paragraph.range.clone());
+        runs.extend(level_runs.into_iter().map(|run| {
+            let rtl = levels[run.start].is_rtl();
+            (run, rtl)
+        }));
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shaper() -> Option<Shaper> {
+        let data = crate::title_bar::TitleBarRenderer::FONT_SEARCH_PATHS
+            .iter()
+            .find_map(|path| std::fs::read(path).ok())?;
+        Some(Shaper::new(ShapingFont::from_bytes(data)?))
+    }
+
+    #[test]
+    fn bidi_runs_are_in_visual_order() {
+        assert_eq!(visual_runs("ls -la"), vec![(0..6, false)]);
+        // "cat שלום.txt": the Hebrew word is one right-to-left run after "cat "
+        let runs = visual_runs("cat \u{05E9}\u{05DC}\u{05D5}\u{05DD}.txt");
+        assert_eq!(runs.first(), Some(&(0..4, false)));
+        assert!(runs.iter().any(|&(ref run, rtl)| rtl && run.start == 4));
+    }
+
+    #[test]
+    fn every_char_gets_a_position() {
+        let Some(mut shaper) = shaper() else { return };
+        for text in ["cargo test", "e\u{0301}cole", "\u{0645}\u{0631}\u{062D}\u{0628}\u{0627} ls", "日本語 vim"] {
+            let line = shaper.shape(text, 14.0);
+            assert_eq!(line.char_positions.len(), text.chars().count(), "{text}");
+            assert_eq!(line.char_widths.len(), text.chars().count(), "{text}");
+            assert!(line.width > 0.0);
+        }
+
+        let line = shaper.shape("abc", 14.0);
+        assert!(line.char_positions.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn overlong_text_is_ellipsized() {
+        let Some(mut shaper) = shaper() else { return };
+        let text = "cargo test --workspace --all-features -- --nocapture";
+        let full = shaper.shape(text, 14.0).width;
+
+        let (shown, line) = shaper.shape_to_fit(text, 14.0, full / 2.0);
+        assert!(shown.ends_with(ELLIPSIS), "{shown}");
+        assert!(line.width <= full / 2.0);
+        assert!(text.starts_with(shown.trim_end_matches(ELLIPSIS)));
+
+        let (shown, _) = shaper.shape_to_fit(text, 14.0, full + 1.0);
+        assert_eq!(shown, text);
+    }
+}
@@ -0,0 +1,290 @@
+//! GPU texture reuse for terminal cells
+//!
+//! Spawning many small command cells used to allocate (and soon after free)
+//! a GL texture per cell, and every resize allocated a new one. The pool cuts
+//! that churn in three ways:
+//!
+//! - A texture whose size didn't change is updated in place.
+//! - Textures of removed or resized terminals are kept in a small free list
+//!   and handed out again to terminals of the same size.
+//! - Very short cells (up to `ATLAS_SLOT_HEIGHT` pixels) share one atlas
+//!   texture, divided into fixed-height slots.
+//!
+//! With very long stacks, textures of cells that stay off-screen would still
+//! add up, so those left off-screen and unchanged for `texture_evict_secs`
+//! are handed back (`is_evictable`) and rasterized again when their cell is
+//! scrolled near the viewport.
+
+use std::time::{Duration, Instant};
+
+use smithay::backend::allocator::Fourcc;
+use smithay::backend::renderer::gles::{GlesError, GlesRenderer, GlesTexture};
+use smithay::backend::renderer::{ImportMem, Texture};
+use smithay::utils::{Buffer, Rectangle, Size};
+
+/// Height of one atlas slot; cells up to this tall go into the atlas
+pub const ATLAS_SLOT_HEIGHT: i32 = 64;
+
+/// Number of slots in the atlas
+const ATLAS_SLOTS: usize = 32;
+
+/// Free textures kept around for reuse
+const MAX_POOLED_TEXTURES: usize = 16;
+
+/// Bytes per pixel of the Argb8888 staging format
+const BYTES_PER_PIXEL: usize = 4;
+
+/// A terminal's content texture, possibly a region of the shared atlas
+#[derive(Debug)]
+pub enum TerminalTexture {
+    /// A texture of exactly the content size
+    Owned(GlesTexture),
+    /// A slot in the shared atlas
+    Atlas {
+        texture: GlesTexture,
+        slot: usize,
+        size: Size<i32, Buffer>,
+    },
+}
+
+impl TerminalTexture {
+    /// The GL texture to sample from
+    pub fn texture(&self) -> &GlesTexture {
+        match self {
+            Self::Owned(texture) | Self::Atlas { texture, .. } => texture,
+        }
+    }
+
+    /// Size of the terminal content (not of the underlying texture)
+    pub fn size(&self) -> Size<i32, Buffer> {
+        match self {
+            Self::Owned(texture) => texture.size(),
+            Self::Atlas { size, .. } => *size,
+        }
+    }
+
+    /// Region of `texture()` holding the content
+    pub fn src(&self) -> Rectangle<f64, Buffer> {
+        let loc = match self {
+            Self::Owned(_) => (0, 0),
+            Self::Atlas { slot, .. } => (0, slot_y(*slot)),
+        };
+        Rectangle::new(loc.into(), self.size()).to_f64()
+    }
+}
+
+/// Whether a texture has gone unused long enough to release: its cell was
+/// last near the viewport and last changed more than `after` ago
+pub fn is_evictable(last_on_screen: Instant, last_change: Instant, now: Instant, after: Duration) -> bool {
+    now.saturating_duration_since(last_on_screen.max(last_change)) >= after
+}
+
+/// Top row of an atlas slot
+fn slot_y(slot: usize) -> i32 {
+    slot as i32 * ATLAS_SLOT_HEIGHT
+}
+
+/// Fixed-height slot bookkeeping for the atlas
+#[derive(Debug)]
+struct SlotAllocator {
+    in_use: Vec<bool>,
+}
+
+impl SlotAllocator {
+    fn new(slots: usize) -> Self {
+        Self { in_use: vec![false; slots] }
+    }
+
+    fn allocate(&mut self) -> Option<usize> {
+        let slot = self.in_use.iter().position(|used| !used)?;
+        self.in_use[slot] = true;
+        Some(slot)
+    }
+
+    fn free(&mut self, slot: usize) {
+        if let Some(used) = self.in_use.get_mut(slot) {
+            *used = false;
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.in_use.contains(&true)
+    }
+}
+
+/// The shared texture for short cells
+#[derive(Debug)]
+struct Atlas {
+    texture: GlesTexture,
+    slots: SlotAllocator,
+    /// Full-atlas upload buffer; `update_memory` reads the region at its
+    /// offset within a buffer laid out like the whole texture
+    scratch: Vec<u8>,
+}
+
+impl Atlas {
+    fn width(&self) -> i32 {
+        self.texture.size().w
+    }
+}
+
+/// Reusable terminal textures
+#[derive(Debug, Default)]
+pub struct TexturePool {
+    free: Vec<GlesTexture>,
+    atlas: Option<Atlas>,
+}
+
+impl TexturePool {
+    /// Upload BGRA `pixels` of the given size, reusing `previous` or a
+    /// pooled texture where possible
+    pub fn upload(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        pixels: &[u8],
+        size: Size<i32, Buffer>,
+        previous: Option<TerminalTexture>,
+    ) -> Result<TerminalTexture, GlesError> {
+        let fits_atlas = size.h <= ATLAS_SLOT_HEIGHT;
+
+        // Same size (or still fits its atlas slot): update in place
+        let previous = match previous {
+            Some(TerminalTexture::Owned(texture)) if !fits_atlas && texture.size() == size => {
+                renderer.update_memory(&texture, pixels, Rectangle::from_size(size))?;
+                return Ok(TerminalTexture::Owned(texture));
+            }
+            Some(TerminalTexture::Atlas { texture, slot, .. })
+                if fits_atlas && self.atlas.as_ref().is_some_and(|a| a.width() == size.w) =>
+            {
+                self.upload_to_slot(renderer, slot, pixels, size)?;
+                return Ok(TerminalTexture::Atlas { texture, slot, size });
+            }
+            other => other,
+        };
+        if let Some(previous) = previous {
+            self.release(previous);
+        }
+
+        if fits_atlas {
+            if let Some(slot) = self.allocate_slot(renderer, size.w)? {
+                self.upload_to_slot(renderer, slot, pixels, size)?;
+                let texture = self.atlas.as_ref().map(|a| a.texture.clone()).expect("atlas exists");
+                return Ok(TerminalTexture::Atlas { texture, slot, size });
+            }
+        }
+
+        if let Some(index) = self.free.iter().position(|t| t.size() == size) {
+            let texture = self.free.swap_remove(index);
+            renderer.update_memory(&texture, pixels, Rectangle::from_size(size))?;
+            return Ok(TerminalTexture::Owned(texture));
+        }
+
+        let texture = renderer.import_memory(pixels, Fourcc::Argb8888, size, false)?;
+        Ok(TerminalTexture::Owned(texture))
+    }
+
+    /// Return a texture that is no longer displayed
+    pub fn release(&mut self, texture: TerminalTexture) {
+        match texture {
+            TerminalTexture::Owned(texture) => {
+                if self.free.len() == MAX_POOLED_TEXTURES {
+                    self.free.remove(0);
+                }
+                self.free.push(texture);
+            }
+            TerminalTexture::Atlas { texture, slot, .. } => {
+                // Slots of a replaced atlas have nothing to free
+                if let Some(atlas) = self.atlas.as_mut().filter(|a| a.texture.tex_id() == texture.tex_id()) {
+                    atlas.slots.free(slot);
+                }
+            }
+        }
+    }
+
+    /// Find a free atlas slot, (re)creating the atlas for `width` if needed
+    fn allocate_slot(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        width: i32,
+    ) -> Result<Option<usize>, GlesError> {
+        let stale = self
+            .atlas
+            .as_ref()
+            .is_some_and(|a| a.width() != width && a.slots.is_empty());
+        if self.atlas.is_none() || stale {
+            let height = ATLAS_SLOT_HEIGHT * ATLAS_SLOTS as i32;
+            let scratch = vec![0u8; width as usize * height as usize * BYTES_PER_PIXEL];
+            let texture =
+                renderer.import_memory(&scratch, Fourcc::Argb8888, (width, height).into(), false)?;
+            tracing::debug!(width, height, "created terminal texture atlas");
+            self.atlas = Some(Atlas {
+                texture,
+                slots: SlotAllocator::new(ATLAS_SLOTS),
+                scratch,
+            });
+        }
+
+        // An atlas for another width still has live cells; fall back to owned textures
+        Ok(self
+            .atlas
+            .as_mut()
+            .filter(|a| a.width() == width)
+            .and_then(|a| a.slots.allocate()))
+    }
+
+    fn upload_to_slot(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        slot: usize,
+        pixels: &[u8],
+        size: Size<i32, Buffer>,
+    ) -> Result<(), GlesError> {
+        let atlas = self.atlas.as_mut().expect("slot belongs to the atlas");
+        let row_bytes = atlas.width() as usize * BYTES_PER_PIXEL;
+        let offset = slot_y(slot) as usize * row_bytes;
+        let len = (size.h as usize * row_bytes).min(pixels.len());
+        atlas.scratch[offset..offset + len].copy_from_slice(&pixels[..len]);
+
+        let region = Rectangle::new((0, slot_y(slot)).into(), size);
+        renderer.update_memory(&atlas.texture, &atlas.scratch, region)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slots_are_reused_after_free() {
+        let mut slots = SlotAllocator::new(2);
+        assert!(slots.is_empty());
+        assert_eq!(slots.allocate(), Some(0));
+        assert_eq!(slots.allocate(), Some(1));
+        assert_eq!(slots.allocate(), None);
+
+        slots.free(0);
+        assert_eq!(slots.allocate(), Some(0));
+        slots.free(0);
+        slots.free(1);
+        assert!(slots.is_empty());
+    }
+
+    #[test]
+    fn only_textures_unused_for_a_while_are_evictable() {
+        let after = Duration::from_secs(60);
+        let start = Instant::now();
+        let later = start + Duration::from_secs(90);
+        assert!(is_evictable(start, start, later, after));
+        // Seen recently
+        assert!(!is_evictable(start + Duration::from_secs(40), start, later, after));
+        // Changed recently, while off-screen
+        assert!(!is_evictable(start, start + Duration::from_secs(40), later, after));
+    }
+
+    #[test]
+    fn slots_do_not_overlap() {
+        assert_eq!(slot_y(0), 0);
+        assert_eq!(slot_y(1), ATLAS_SLOT_HEIGHT);
+        assert!(slot_y(ATLAS_SLOTS - 1) + ATLAS_SLOT_HEIGHT <= ATLAS_SLOT_HEIGHT * ATLAS_SLOTS as i32);
+    }
+}
@@ -4,7 +4,9 @@
 //! Also tracks character positions for text selection hit-testing.
 
 use std::collections::HashMap;
-use terminal::Theme;
+use terminal::{Progress, Theme};
+
+use crate::text_shaping::{ShapedLine, Shaper, ShapingFont};
 
 /// Title bar height in pixels
 pub const TITLE_BAR_HEIGHT: u32 = 24;
@@ -18,6 +20,124 @@ pub const GRADIENT_HEIGHT: u32 = 24;
 /// Left padding for title bar text (pixels)
 pub const TITLE_BAR_PADDING: u32 = 8;
 
+/// Rendered title bars kept by default in a `TitleBarCache`
+pub const TITLE_BAR_CACHE_CAPACITY: usize = 64;
+
+/// Everything that determines how a title bar looks
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TitleBarKey {
+    /// Displayed text (including any "(not responding)" suffix)
+    pub text: String,
+    /// Width of the title bar in pixels
+    pub width: u32,
+    /// Color theme
+    pub theme: Theme,
+    /// Whether the cell has keyboard focus
+    pub focused: bool,
+    /// Whether the cell's process is still running
+    pub running: bool,
+    /// Progress the cell's program reports (OSC 9;4), drawn along the bottom
+    pub progress: Option<Progress>,
+}
+
+struct CachedTitleBar<T> {
+    value: T,
+    /// Frame in which the entry was last looked up
+    last_used: u64,
+}
+
+/// Rendered title bars kept across frames, keyed by their content
+///
+/// Generic over what a backend keeps per title bar (a GPU texture, a pixel
+/// buffer). When the cache grows past its capacity, the least recently used
+/// entries are evicted, but never ones used in the last frame, so a screen
+/// with more title bars than the capacity doesn't re-render them every frame.
+pub struct TitleBarCache<T> {
+    entries: HashMap<TitleBarKey, CachedTitleBar<T>>,
+    frame: u64,
+    capacity: usize,
+}
+
+impl<T> TitleBarCache<T> {
+    /// Create a cache holding up to `TITLE_BAR_CACHE_CAPACITY` title bars
+    pub fn new() -> Self {
+        Self::with_capacity(TITLE_BAR_CACHE_CAPACITY)
+    }
+
+    /// Create a cache holding up to `capacity` title bars (beyond the
+    /// ones used in the current frame)
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            frame: 0,
+            capacity,
+        }
+    }
+
+    /// Start a new frame, evicting least recently used entries over capacity
+    /// that weren't used in the previous frame
+    pub fn begin_frame(&mut self) {
+        if self.entries.len() > self.capacity {
+            let mut by_age: Vec<_> = self
+                .entries
+                .iter()
+                .filter(|(_, entry)| entry.last_used < self.frame)
+                .map(|(key, entry)| (entry.last_used, key.clone()))
+                .collect();
+            by_age.sort_by_key(|(last_used, _)| *last_used);
+            let excess = self.entries.len() - self.capacity;
+            for (_, key) in by_age.into_iter().take(excess) {
+                self.entries.remove(&key);
+            }
+        }
+        self.frame += 1;
+    }
+
+    /// Look up a title bar, rendering it with `render` if it isn't cached
+    ///
+    /// Returns false if it wasn't cached and `render` failed.
+    pub fn ensure(&mut self, key: &TitleBarKey, render: impl FnOnce() -> Option<T>) -> bool {
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.last_used = self.frame;
+            return true;
+        }
+        match render() {
+            Some(value) => {
+                self.entries.insert(key.clone(), CachedTitleBar { value, last_used: self.frame });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get a cached title bar
+    pub fn get(&self, key: &TitleBarKey) -> Option<&T> {
+        self.entries.get(key).map(|entry| &entry.value)
+    }
+
+    /// Number of cached title bars
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<T> Default for TitleBarCache<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether a title bar at the top of a cell is on screen
+///
+/// `cell_top` is the cell's top edge in screen coordinates (Y down).
+pub fn title_bar_visible(cell_top: i32, title_bar_height: i32, screen_height: i32) -> bool {
+    cell_top < screen_height && cell_top + title_bar_height > 0
+}
+
 /// Character position information for text selection
 #[derive(Debug, Clone)]
 pub struct TitleBarCharInfo {
@@ -121,14 +241,14 @@ impl TitleBarColors {
 
 /// Title bar renderer
 pub struct TitleBarRenderer {
-    /// Font for rendering
-    font: fontdue::Font,
+    /// Primary font and fallbacks, for laying text out
+    shaper: Shaper,
 
     /// Font size
     font_size: f32,
 
-    /// Glyph cache
-    glyph_cache: HashMap<char, GlyphData>,
+    /// Rasterized glyphs by (font index, glyph id)
+    glyph_cache: HashMap<(usize, u16), GlyphData>,
 
     /// Color theme
     theme: Theme,
@@ -143,12 +263,11 @@ struct GlyphData {
     height: u32,
     x_offset: i32,
     y_offset: i32,
-    advance: f32,
 }
 
 impl TitleBarRenderer {
     /// Common font search paths
-    const FONT_SEARCH_PATHS: &'static [&'static str] = &[
+    pub(crate) const FONT_SEARCH_PATHS: &'static [&'static str] = &[
         "/usr/share/fonts/truetype/dejavu/DejaVuSansMono.ttf",
         "/usr/share/fonts/TTF/DejaVuSansMono.ttf",
         "/usr/share/fonts/dejavu/DejaVuSansMono.ttf",
@@ -164,14 +283,10 @@ impl TitleBarRenderer {
     ];
 
     /// Try to find and load a font from common system locations
-    fn find_font() -> Option<(fontdue::Font, &'static str)> {
+    fn find_font() -> Option<(ShapingFont, &'static str)> {
         Self::FONT_SEARCH_PATHS.iter().find_map(|&path| {
             let data = std::fs::read(path).ok()?;
-            let font = fontdue::Font::from_bytes(
-                data.as_slice(),
-                fontdue::FontSettings::default(),
-            ).ok()?;
-            Some((font, path))
+            Some((ShapingFont::from_bytes(data)?, path))
         })
     }
 
@@ -190,7 +305,7 @@ impl TitleBarRenderer {
             Some((font, path)) => {
                 tracing::info!("TitleBarRenderer: loaded font from {} (scale={})", path, scale);
                 Some(Self {
-                    font,
+                    shaper: Shaper::new(font),
                     font_size: 14.0 * scale,
                     glyph_cache: HashMap::new(),
                     theme,
@@ -204,6 +319,16 @@ impl TitleBarRenderer {
         }
     }
 
+    /// Color theme the title bars are rendered with
+    pub fn theme(&self) -> Theme {
+        self.theme
+    }
+
+    /// Switch the color theme (cached title bars are keyed by theme)
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
     /// Scaled title bar height in pixels
     pub fn title_bar_height(&self) -> u32 {
         (TITLE_BAR_HEIGHT as f32 * self.scale) as u32
@@ -252,87 +377,12 @@ impl TitleBarRenderer {
             }
         }
 
-        // Text colors from theme
-        let fg_r = colors.fg_r;
-        let fg_g = colors.fg_g;
-        let fg_b = colors.fg_b;
-
-        // Display text as-is - prompt already includes any prefix
-        let display_text = text;
-
-        // Starting position with padding
-        let mut x_pos = padding as f32;
+        // Shape the text, ellipsized to leave room for the close button
+        let max_text_width = width.saturating_sub(2 * padding + close_btn_width) as f32;
+        let (displayed_text, line) = self.shaper.shape_to_fit(text, self.font_size, max_text_width);
         let baseline_y = (height as f32 * 0.75) as i32; // Approximate baseline
-
-        // Track character positions for selection hit-testing
-        let mut char_positions: Vec<f32> = Vec::new();
-        let mut char_widths: Vec<f32> = Vec::new();
-        let mut displayed_text = String::new();
-
-        for c in display_text.chars() {
-            // Get or rasterize glyph
-            let glyph = self.glyph_cache.entry(c).or_insert_with(|| {
-                let (metrics, bitmap) = self.font.rasterize(c, self.font_size);
-                GlyphData {
-                    bitmap,
-                    width: metrics.width as u32,
-                    height: metrics.height as u32,
-                    x_offset: metrics.xmin,
-                    y_offset: metrics.ymin,
-                    advance: metrics.advance_width,
-                }
-            });
-
-            // Track character position (relative to padding start)
-            char_positions.push(x_pos - padding as f32);
-            char_widths.push(glyph.advance);
-            displayed_text.push(c);
-
-            // Calculate glyph position
-            let glyph_x = (x_pos as i32 + glyph.x_offset).max(0) as u32;
-            let glyph_y = (baseline_y - glyph.height as i32 - glyph.y_offset).max(0) as u32;
-
-            // Draw glyph with alpha blending
-            for gy in 0..glyph.height {
-                let py = glyph_y + gy;
-                if py >= height {
-                    break;
-                }
-
-                for gx in 0..glyph.width {
-                    let px = glyph_x + gx;
-                    if px >= width {
-                        break;
-                    }
-
-                    let alpha = glyph.bitmap[(gy * glyph.width + gx) as usize];
-                    if alpha == 0 {
-                        continue;
-                    }
-
-                    let idx = ((py * width + px) * 4) as usize;
-                    if idx + 3 >= buffer.len() {
-                        continue;
-                    }
-
-                    // Alpha blend
-                    let alpha_f = alpha as f32 / 255.0;
-                    let inv_alpha = 1.0 - alpha_f;
-
-                    buffer[idx] = (fg_b as f32 * alpha_f + buffer[idx] as f32 * inv_alpha) as u8;
-                    buffer[idx + 1] = (fg_g as f32 * alpha_f + buffer[idx + 1] as f32 * inv_alpha) as u8;
-                    buffer[idx + 2] = (fg_r as f32 * alpha_f + buffer[idx + 2] as f32 * inv_alpha) as u8;
-                    // Alpha channel stays at 0xFF
-                }
-            }
-
-            x_pos += glyph.advance;
-
-            // Stop if we're past the visible area (leave room for close button)
-            if x_pos >= (width - padding - close_btn_width) as f32 {
-                break;
-            }
-        }
+        let fg = (colors.fg_r, colors.fg_g, colors.fg_b);
+        self.draw_line(&mut buffer, width, height, &line, padding as f32, baseline_y, fg);
 
         // Draw close button on the right side
         self.render_close_button(&mut buffer, width, height);
@@ -369,15 +419,121 @@ impl TitleBarRenderer {
             }
         }
 
+        // Character positions are relative to the padding start
         let char_info = TitleBarCharInfo {
             text: displayed_text,
-            char_positions,
-            char_widths,
+            char_positions: line.char_positions,
+            char_widths: line.char_widths,
         };
 
         (buffer, width, height, char_info)
     }
 
+    /// Draw a progress bar along the bottom of a rendered title bar
+    pub fn draw_progress(&self, buffer: &mut [u8], width: u32, progress: Progress) {
+        let thickness = (PROGRESS_BAR_HEIGHT as f32 * self.scale).round().max(1.0) as u32;
+        let bar_width = width.saturating_sub(self.close_button_width());
+        draw_progress_bar(buffer, width, self.title_bar_height(), bar_width, thickness, progress);
+    }
+
+    /// Render lines of text on a plain panel (used by the frame-time HUD)
+    ///
+    /// The panel is sized to fit the longest line. Returns (pixels, width, height)
+    pub fn render_panel(&mut self, lines: &[String]) -> (Vec<u8>, u32, u32) {
+        let padding = self.title_bar_padding();
+        let line_height = (self.font_size * 1.4).ceil() as u32;
+        let colors = TitleBarColors::from_theme(self.theme);
+
+        // Shape lines up front so the panel width is known
+        let shaped: Vec<ShapedLine> = lines.iter().map(|line| self.shaper.shape(line, self.font_size)).collect();
+        let text_width = shaped.iter().map(|line| line.width).fold(0.0f32, f32::max).ceil() as u32;
+
+        let width = (text_width + 2 * padding).max(1);
+        let height = (line_height * lines.len() as u32 + padding).max(1);
+        let mut buffer = vec![0u8; (width * height * 4) as usize];
+
+        for pixel in buffer.chunks_exact_mut(4) {
+            pixel[0] = colors.bg_b;
+            pixel[1] = colors.bg_g;
+            pixel[2] = colors.bg_r;
+            pixel[3] = 0xFF;
+        }
+
+        let fg = (colors.fg_r, colors.fg_g, colors.fg_b);
+        for (row, line) in shaped.iter().enumerate() {
+            let baseline_y = (padding / 2 + line_height * row as u32) as i32
+                + (line_height as f32 * 0.75) as i32;
+            self.draw_line(&mut buffer, width, height, line, padding as f32, baseline_y, fg);
+        }
+
+        (buffer, width, height)
+    }
+
+    /// Rasterized glyph `glyph_id` of shaper font `font`
+    fn glyph(&mut self, font: usize, glyph_id: u16) -> &GlyphData {
+        let (shaper, font_size) = (&self.shaper, self.font_size);
+        self.glyph_cache.entry((font, glyph_id)).or_insert_with(|| {
+            let (metrics, bitmap) = shaper.font(font).raster().rasterize_indexed(glyph_id, font_size);
+            GlyphData {
+                bitmap,
+                width: metrics.width as u32,
+                height: metrics.height as u32,
+                x_offset: metrics.xmin,
+                y_offset: metrics.ymin,
+            }
+        })
+    }
+
+    /// Alpha-blend a shaped line into `buffer`, starting at `x` on `baseline_y`
+    #[allow(clippy::too_many_arguments)]
+    fn draw_line(
+        &mut self,
+        buffer: &mut [u8],
+        width: u32,
+        height: u32,
+        line: &ShapedLine,
+        x: f32,
+        baseline_y: i32,
+        (fg_r, fg_g, fg_b): (u8, u8, u8),
+    ) {
+        for placed in &line.glyphs {
+            let glyph = self.glyph(placed.font, placed.glyph_id);
+            let glyph_x = (x + placed.x) as i32 + glyph.x_offset;
+            let glyph_y = baseline_y - glyph.height as i32 - glyph.y_offset - placed.y.round() as i32;
+
+            for gy in 0..glyph.height {
+                let py = glyph_y + gy as i32;
+                if py < 0 {
+                    continue;
+                }
+                if py >= height as i32 {
+                    break;
+                }
+                for gx in 0..glyph.width {
+                    let px = glyph_x + gx as i32;
+                    if px < 0 {
+                        continue;
+                    }
+                    if px >= width as i32 {
+                        break;
+                    }
+                    let alpha = glyph.bitmap[(gy * glyph.width + gx) as usize];
+                    if alpha == 0 {
+                        continue;
+                    }
+
+                    // Alpha blend (alpha channel stays at 0xFF)
+                    let idx = ((py as u32 * width + px as u32) * 4) as usize;
+                    let alpha_f = alpha as f32 / 255.0;
+                    let inv_alpha = 1.0 - alpha_f;
+                    buffer[idx] = (fg_b as f32 * alpha_f + buffer[idx] as f32 * inv_alpha) as u8;
+                    buffer[idx + 1] = (fg_g as f32 * alpha_f + buffer[idx + 1] as f32 * inv_alpha) as u8;
+                    buffer[idx + 2] = (fg_r as f32 * alpha_f + buffer[idx + 2] as f32 * inv_alpha) as u8;
+                }
+            }
+        }
+    }
+
     /// Render the close button
     fn render_close_button(&mut self, buffer: &mut [u8], width: u32, height: u32) {
         let btn_width = self.close_button_width();
@@ -400,18 +556,8 @@ impl TitleBarRenderer {
         }
 
         // Draw "×" character centered in button
-        let close_char = '×';
-        let glyph = self.glyph_cache.entry(close_char).or_insert_with(|| {
-            let (metrics, bitmap) = self.font.rasterize(close_char, self.font_size);
-            GlyphData {
-                bitmap,
-                width: metrics.width as u32,
-                height: metrics.height as u32,
-                x_offset: metrics.xmin,
-                y_offset: metrics.ymin,
-                advance: metrics.advance_width,
-            }
-        });
+        let close_glyph = self.shaper.font(0).raster().lookup_glyph_index('×');
+        let glyph = self.glyph(0, close_glyph);
 
         // Center the glyph in the button
         let glyph_x = btn_x + (btn_width.saturating_sub(glyph.width)) / 2;
@@ -457,6 +603,44 @@ impl TitleBarRenderer {
     }
 }
 
+/// Height of the progress bar along the bottom of a title bar (pixels)
+pub const PROGRESS_BAR_HEIGHT: u32 = 3;
+
+/// Color of a progress bar (RGB), the same in both themes
+fn progress_color(progress: Progress) -> (u8, u8, u8) {
+    match progress {
+        Progress::Normal(_) => (0x4C, 0xAF, 0x50),        // Green
+        Progress::Error(_) => (0xE5, 0x39, 0x35),         // Red
+        Progress::Paused(_) => (0xF9, 0xA8, 0x25),        // Amber
+        Progress::Indeterminate => (0x42, 0x8B, 0xCA),    // Blue
+    }
+}
+
+/// Fill the bottom `thickness` rows of a `width` x `height` ARGB buffer with a
+/// bar over the first `bar_width` pixels, as far as `progress` got
+///
+/// Progress of unknown extent is drawn dashed across the whole bar.
+fn draw_progress_bar(buffer: &mut [u8], width: u32, height: u32, bar_width: u32, thickness: u32, progress: Progress) {
+    let (r, g, b) = progress_color(progress);
+    let dash = thickness * 4;
+    let filled = match progress.percent() {
+        Some(percent) => bar_width * percent as u32 / 100,
+        None => bar_width,
+    };
+    for y in height.saturating_sub(thickness)..height {
+        for x in 0..filled.min(width) {
+            if progress == Progress::Indeterminate && (x / dash) % 2 == 1 {
+                continue;
+            }
+            let idx = ((y * width + x) * 4) as usize;
+            buffer[idx] = b;
+            buffer[idx + 1] = g;
+            buffer[idx + 2] = r;
+            buffer[idx + 3] = 0xFF;
+        }
+    }
+}
+
 impl Default for TitleBarRenderer {
     fn default() -> Self {
         Self::new(Theme::default()).expect("Failed to create TitleBarRenderer - no font available")
@@ -525,6 +709,104 @@ mod tests {
         assert_eq!(info.text_range(0, 5), "");
     }
 
+    #[test]
+    fn progress_bar_fills_its_share_of_the_bottom_rows() {
+        let (width, height) = (10, 4);
+        let filled = |progress| {
+            let mut buffer = vec![0u8; (width * height * 4) as usize];
+            draw_progress_bar(&mut buffer, width, height, 8, 1, progress);
+            let bottom = &buffer[((height - 1) * width * 4) as usize..];
+            assert!(buffer[..((height - 1) * width * 4) as usize].iter().all(|&byte| byte == 0));
+            bottom.chunks_exact(4).map(|pixel| pixel[3] == 0xFF).collect::<Vec<_>>()
+        };
+
+        let half = filled(Progress::Normal(50));
+        assert_eq!(half.iter().filter(|&&on| on).count(), 4);
+        assert!(half[0] && !half[4]);
+        // The close button's part stays clear even when done
+        assert_eq!(filled(Progress::Error(100)).iter().filter(|&&on| on).count(), 8);
+        // Dashes of 4 pixels across the whole bar
+        assert_eq!(filled(Progress::Indeterminate), [true, true, true, true, false, false, false, false, false, false]);
+    }
+
+    fn key(text: &str) -> TitleBarKey {
+        TitleBarKey {
+            text: text.to_string(),
+            width: 800,
+            theme: Theme::Dark,
+            focused: false,
+            running: true,
+            progress: None,
+        }
+    }
+
+    #[test]
+    fn cache_renders_each_key_once() {
+        let mut cache = TitleBarCache::new();
+        let mut renders = 0;
+        for _ in 0..3 {
+            cache.begin_frame();
+            assert!(cache.ensure(&key("vim"), || {
+                renders += 1;
+                Some(renders)
+            }));
+        }
+        assert_eq!(renders, 1);
+        assert_eq!(cache.get(&key("vim")), Some(&1));
+
+        // Any change in appearance is a different entry
+        let focused = TitleBarKey { focused: true, ..key("vim") };
+        assert!(cache.get(&focused).is_none());
+    }
+
+    #[test]
+    fn cache_reports_failed_render() {
+        let mut cache: TitleBarCache<u32> = TitleBarCache::new();
+        assert!(!cache.ensure(&key("vim"), || None));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn cache_evicts_least_recently_used() {
+        let mut cache = TitleBarCache::with_capacity(2);
+        for text in ["a", "b", "c"] {
+            cache.begin_frame();
+            cache.ensure(&key(text), || Some(()));
+        }
+        // Over capacity, but every entry is kept until the next frame
+        assert_eq!(cache.len(), 3);
+
+        cache.begin_frame();
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&key("a")).is_none());
+        assert!(cache.get(&key("b")).is_some());
+        assert!(cache.get(&key("c")).is_some());
+    }
+
+    #[test]
+    fn cache_keeps_entries_used_this_frame() {
+        let mut cache = TitleBarCache::with_capacity(1);
+        cache.begin_frame();
+        for text in ["a", "b", "c"] {
+            cache.ensure(&key(text), || Some(()));
+        }
+        cache.begin_frame();
+        // All three were on screen in the previous frame
+        assert_eq!(cache.len(), 3);
+
+        cache.begin_frame();
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn title_bar_visibility() {
+        assert!(title_bar_visible(0, 24, 600));
+        assert!(title_bar_visible(-23, 24, 600));
+        assert!(!title_bar_visible(-24, 24, 600));
+        assert!(title_bar_visible(599, 24, 600));
+        assert!(!title_bar_visible(600, 24, 600));
+    }
+
     #[test]
     fn render_with_char_info_produces_positions() {
         // Skip this test if no font is available
@@ -547,4 +829,37 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn long_titles_are_ellipsized() {
+        // Skip this test if no font is available
+        let Some(mut renderer) = TitleBarRenderer::new(Theme::Dark) else {
+            return;
+        };
+
+        let text = "tail -f /var/log/some/very/long/path/to/a/log/file/that/does/not/fit.log";
+        let (_, _, _, char_info) = renderer.render_with_char_info(text, 200);
+
+        assert!(char_info.text.ends_with(crate::text_shaping::ELLIPSIS), "{}", char_info.text);
+        assert_eq!(char_info.char_positions.len(), char_info.text.chars().count());
+        let end = char_info.char_positions.last().unwrap() + char_info.char_widths.last().unwrap();
+        assert!(end <= (200 - 2 * TITLE_BAR_PADDING - CLOSE_BUTTON_WIDTH) as f32);
+    }
+
+    #[test]
+    fn render_panel_fits_longest_line() {
+        // Skip this test if no font is available
+        let Some(mut renderer) = TitleBarRenderer::new(Theme::Dark) else {
+            return;
+        };
+
+        let short = vec!["ab".to_string()];
+        let long = vec!["ab".to_string(), "abcdefgh".to_string()];
+        let (pixels, short_w, short_h) = renderer.render_panel(&short);
+        let (_, long_w, long_h) = renderer.render_panel(&long);
+
+        assert_eq!(pixels.len(), (short_w * short_h * 4) as usize);
+        assert!(long_w > short_w, "panel should widen for longer lines");
+        assert!(long_h > short_h, "panel should grow with line count");
+    }
 }
@@ -0,0 +1,65 @@
+//! Fast path for typing echo
+//!
+//! A key written to the focused terminal arms `TypingEcho` for
+//! `ECHO_WINDOW`. When the terminal's echo arrives within it, the X11
+//! backend draws a frame right away instead of waiting out the frame limiter
+//! and rasterizes only that terminal, leaving other dirty cells to the
+//! following frame. Typing then feels as quick as in a native terminal even
+//! with busy cells elsewhere in the stack.
+
+use std::time::{Duration, Instant};
+
+use crate::terminal_manager::{TerminalId, TerminalManager};
+
+/// How long after a key its echo still gets the fast path
+pub const ECHO_WINDOW: Duration = Duration::from_millis(100);
+
+/// Terminal waiting for the echo of a key, kept on `TermStack`
+#[derive(Debug, Default)]
+pub struct TypingEcho {
+    pending: Option<(TerminalId, Instant)>,
+}
+
+impl TypingEcho {
+    /// A key was written to `terminal`
+    pub fn key_sent(&mut self, terminal: TerminalId, now: Instant) {
+        self.pending = Some((terminal, now));
+    }
+
+    /// The terminal waiting for an echo, while the window lasts
+    pub fn waiting(&self, now: Instant) -> Option<TerminalId> {
+        self.pending
+            .filter(|(_, sent)| now.saturating_duration_since(*sent) < ECHO_WINDOW)
+            .map(|(terminal, _)| terminal)
+    }
+
+    /// The terminal to draw ahead of the frame cadence: its echo has arrived
+    pub fn due(&self, now: Instant, terminal_manager: &TerminalManager) -> Option<TerminalId> {
+        self.waiting(now)
+            .filter(|id| terminal_manager.get(*id).is_some_and(|terminal| terminal.is_dirty()))
+    }
+
+    /// The echo has been drawn; the next key arms the fast path again
+    pub fn drawn(&mut self) {
+        self.pending = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn echo_window_closes() {
+        let now = Instant::now();
+        let mut echo = TypingEcho::default();
+        assert_eq!(echo.waiting(now), None);
+
+        echo.key_sent(TerminalId(3), now);
+        assert_eq!(echo.waiting(now + Duration::from_millis(50)), Some(TerminalId(3)));
+        assert_eq!(echo.waiting(now + ECHO_WINDOW), None);
+
+        echo.drawn();
+        assert_eq!(echo.waiting(now), None);
+    }
+}
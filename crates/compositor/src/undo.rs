@@ -0,0 +1,202 @@
+//! Taking back closed cells
+//!
+//! Closing a terminal cell with its close button, absorbing it into its shell
+//! or `termstack all close-exited` keeps the terminal, frozen, for
+//! `undo_seconds`. Super+Shift+Z (or Ctrl+Shift+Z) puts the cells of the last
+//! close back where they were. Only the last `undo_depth` closes are kept;
+//! older ones are dropped for good. An absorbed cell comes back, but its
+//! output also stays in the shell's scrollback.
+//!
+//! `termstack all collapse` goes on the same stack, so `termstack all undo`
+//! takes back whichever came last.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::cell_id::CellId;
+use crate::state::{StackWindow, TermStack};
+use crate::terminal_manager::{ManagedTerminal, TerminalManager};
+
+/// How long a closed cell can be brought back
+pub const DEFAULT_UNDO_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many closes are kept
+pub const DEFAULT_UNDO_DEPTH: usize = 10;
+
+/// A closed cell, its terminal kept but no longer read from
+pub struct ClosedCell {
+    /// Index the cell had in the stack
+    pub index: usize,
+    pub terminal: ManagedTerminal,
+    pub height: i32,
+}
+
+/// One undoable change
+pub enum UndoEntry {
+    /// Cells closed together, in stack order
+    Closed(Vec<ClosedCell>),
+    /// Cells collapsed before the last `termstack all collapse`
+    Collapsed(Vec<CellId>),
+}
+
+/// Recent closes, oldest first. Kept on `TermStack`.
+pub struct UndoStack {
+    entries: VecDeque<(UndoEntry, Instant)>,
+    depth: usize,
+    timeout: Duration,
+    /// Set by the undo keybinding, handled once per frame
+    pub requested: bool,
+}
+
+impl Default for UndoStack {
+    fn default() -> Self {
+        Self::new(DEFAULT_UNDO_DEPTH, DEFAULT_UNDO_TIMEOUT)
+    }
+}
+
+impl UndoStack {
+    pub fn new(depth: usize, timeout: Duration) -> Self {
+        Self { entries: VecDeque::new(), depth, timeout, requested: false }
+    }
+
+    /// Settings from the config (`undo_depth = 0` turns undo off)
+    pub fn from_config(config: &crate::config::Config) -> Self {
+        Self::new(config.undo_depth, Duration::from_secs(config.undo_seconds))
+    }
+
+    /// Remember a change, dropping the oldest if the stack is full
+    pub fn push(&mut self, entry: UndoEntry, now: Instant) {
+        self.entries.push_back((entry, now));
+        while self.entries.len() > self.depth {
+            self.entries.pop_front();
+        }
+    }
+
+    /// Take the latest change
+    pub fn pop(&mut self) -> Option<UndoEntry> {
+        self.entries.pop_back().map(|(entry, _)| entry)
+    }
+
+    /// Drop changes older than the timeout, with their terminals
+    pub fn expire(&mut self, now: Instant) {
+        // Oldest first, so the stale ones are at the front
+        let stale = self
+            .entries
+            .iter()
+            .take_while(|(_, at)| now.saturating_duration_since(*at) > self.timeout)
+            .count();
+        if stale > 0 {
+            tracing::debug!(stale, "dropping expired undo entries");
+            self.entries.drain(..stale);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl TermStack {
+    /// Close the terminal cell at `index`, keeping its terminal for undo
+    pub fn close_terminal_cell(&mut self, index: usize, terminal_manager: &mut TerminalManager) {
+        let node = self.remove_cell(index);
+        self.invalidate_focused_index_cache();
+        if let Some(terminal) = node.cell.terminal_id().and_then(|id| terminal_manager.remove(id)) {
+            let now = Instant::now();
+            self.archive.add(&terminal, now);
+            let closed = ClosedCell { index, terminal, height: node.height };
+            self.undo_stack.push(UndoEntry::Closed(vec![closed]), now);
+        }
+        self.update_focus_after_removal(index);
+    }
+}
+
+/// Drop expired closes and take back the last one if asked to (once per frame)
+pub fn process(compositor: &mut TermStack, terminal_manager: &mut TerminalManager) {
+    compositor.undo_stack.expire(Instant::now());
+    if std::mem::take(&mut compositor.undo_stack.requested) {
+        let restored = undo(compositor, terminal_manager);
+        tracing::info!(restored, "undo requested");
+    }
+}
+
+/// Take back the latest change, returning how many cells came back
+pub fn undo(compositor: &mut TermStack, terminal_manager: &mut TerminalManager) -> usize {
+    match compositor.undo_stack.pop() {
+        Some(UndoEntry::Closed(cells)) => {
+            let count = cells.len();
+            let mut last = None;
+            for ClosedCell { index, mut terminal, height } in cells {
+                let id = terminal.id;
+                compositor.archive.forget(id);
+                terminal.mark_dirty();
+                terminal_manager.restore(terminal);
+                let index = index.min(compositor.layout_nodes.len());
+                compositor.insert_cell(index, StackWindow::Terminal(id), height);
+                last = Some(index);
+            }
+            compositor.invalidate_focused_index_cache();
+            compositor.recalculate_layout();
+            if let Some(index) = last {
+                compositor.set_focus_by_index(index);
+                compositor.update_keyboard_focus_for_focused_window();
+                compositor.scroll_to_show_window_bottom(index);
+            }
+            compositor.redraw_requested = true;
+            count
+        }
+        Some(UndoEntry::Collapsed(previous)) => {
+            compositor.redraw_requested = true;
+            compositor.bulk_actions.restore_collapsed(previous)
+        }
+        None => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collapsed(bits: u64) -> UndoEntry {
+        UndoEntry::Collapsed(vec![CellId::from_bits(bits)])
+    }
+
+    fn bits(entry: Option<UndoEntry>) -> Option<u64> {
+        match entry? {
+            UndoEntry::Collapsed(cells) => cells.first().map(|cell| cell.to_bits()),
+            UndoEntry::Closed(_) => None,
+        }
+    }
+
+    #[test]
+    fn full_stack_drops_the_oldest() {
+        let now = Instant::now();
+        let mut stack = UndoStack::new(2, DEFAULT_UNDO_TIMEOUT);
+        for cell in 1..=3 {
+            stack.push(collapsed(cell), now);
+        }
+        assert_eq!(stack.len(), 2);
+        assert_eq!(bits(stack.pop()), Some(3));
+        assert_eq!(bits(stack.pop()), Some(2));
+        assert!(stack.pop().is_none());
+
+        let mut off = UndoStack::new(0, DEFAULT_UNDO_TIMEOUT);
+        off.push(collapsed(1), now);
+        assert!(off.is_empty());
+    }
+
+    #[test]
+    fn old_entries_expire() {
+        let now = Instant::now();
+        let mut stack = UndoStack::new(DEFAULT_UNDO_DEPTH, Duration::from_secs(30));
+        stack.push(collapsed(1), now);
+        stack.push(collapsed(2), now + Duration::from_secs(20));
+        stack.expire(now + Duration::from_secs(31));
+        assert_eq!(stack.len(), 1);
+        assert_eq!(bits(stack.pop()), Some(2));
+    }
+}
@@ -0,0 +1,361 @@
+//! Watchdog for frozen clients and unresponsive cells
+//!
+//! A cell is "not responding" when it has been waiting on its owner for
+//! longer than `NOT_RESPONDING_TIMEOUT`:
+//!
+//! - External windows: an `xdg_wm_base` ping is sent every `PING_INTERVAL`
+//!   and has not been answered, or a configure was sent and the client has
+//!   not committed since. The stale-resize cancel only unblocks our own resize
+//!   state machine after `RESIZE_TIMEOUT_MS`; this keeps tracking the client
+//!   until it commits again.
+//! - Terminals: the foreground job is in uninterruptible sleep (`D` state),
+//!   e.g. blocked on a hung network mount.
+//!
+//! Unresponsive cells get a "(not responding)" suffix in their title bar,
+//! and clicking the close button of an unresponsive window kills the client
+//! process instead of sending a close request it would never handle.
+//! Windows whose client has disconnected without destroying them are
+//! removed from the stack.
+
+use std::time::{Duration, Instant};
+
+use smithay::reexports::wayland_server::Resource;
+use smithay::utils::SERIAL_COUNTER;
+use smithay::wayland::shell::xdg::ShellClient;
+use smithay::wayland::shell::PingError;
+use terminal::pty::ProcessTable;
+
+use crate::state::{SurfaceKind, StackWindow, TermStack};
+use crate::terminal_manager::TerminalManager;
+
+/// How long a cell may stall before it is marked as not responding
+pub const NOT_RESPONDING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often each external window's client is pinged
+pub const PING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often liveness is re-evaluated (terminal checks read `/proc`)
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Suffix appended to the title of unresponsive cells
+const NOT_RESPONDING_SUFFIX: &str = " (not responding)";
+
+/// Responsiveness tracking for a single cell
+#[derive(Debug, Clone, Default)]
+pub struct Liveness {
+    /// When the cell started waiting on its owner (configure, blocked job)
+    waiting_since: Option<Instant>,
+    /// When the outstanding ping was sent, if one is outstanding
+    ping_sent_at: Option<Instant>,
+    /// When the last ping was sent
+    last_ping: Option<Instant>,
+    /// Round-trip time of the last answered ping
+    pong_latency: Option<Duration>,
+    /// Whether the stall has exceeded `NOT_RESPONDING_TIMEOUT`
+    not_responding: bool,
+}
+
+impl Liveness {
+    /// Note that the cell is waiting on its owner (keeps the earliest start)
+    pub fn mark_waiting(&mut self, now: Instant) {
+        self.waiting_since.get_or_insert(now);
+    }
+
+    /// Note that the owner caught up with what the cell was waiting for
+    pub fn mark_alive(&mut self) {
+        self.waiting_since = None;
+    }
+
+    /// Whether a new ping should be sent
+    pub fn ping_due(&self, now: Instant) -> bool {
+        self.ping_sent_at.is_none()
+            && self
+                .last_ping
+                .map_or(true, |t| now.duration_since(t) >= PING_INTERVAL)
+    }
+
+    /// Note that a ping was sent to the owning client
+    pub fn ping_sent(&mut self, now: Instant) {
+        self.ping_sent_at.get_or_insert(now);
+        self.last_ping = Some(now);
+    }
+
+    /// Note that the owning client answered, returning the round-trip time
+    pub fn pong_received(&mut self, now: Instant) -> Option<Duration> {
+        let latency = now.duration_since(self.ping_sent_at.take()?);
+        self.pong_latency = Some(latency);
+        Some(latency)
+    }
+
+    /// Round-trip time of the last answered ping
+    pub fn pong_latency(&self) -> Option<Duration> {
+        self.pong_latency
+    }
+
+    /// Re-evaluate the stall
+    ///
+    /// Returns `Some(true)` if the cell just became unresponsive and
+    /// `Some(false)` if it just recovered.
+    pub fn update(&mut self, now: Instant) -> Option<bool> {
+        let stalled = self
+            .waiting_since
+            .into_iter()
+            .chain(self.ping_sent_at)
+            .min()
+            .is_some_and(|since| now.duration_since(since) >= NOT_RESPONDING_TIMEOUT);
+        if stalled == self.not_responding {
+            return None;
+        }
+        self.not_responding = stalled;
+        Some(stalled)
+    }
+
+    pub fn is_not_responding(&self) -> bool {
+        self.not_responding
+    }
+
+    /// Title to display for a cell with this liveness
+    pub fn decorate_title<'a>(&self, title: &'a str) -> std::borrow::Cow<'a, str> {
+        if self.not_responding {
+            format!("{}{}", title, NOT_RESPONDING_SUFFIX).into()
+        } else {
+            title.into()
+        }
+    }
+}
+
+/// Rate limiting for the periodic liveness check
+#[derive(Debug, Default)]
+pub struct Watchdog {
+    last_check: Option<Instant>,
+}
+
+impl Watchdog {
+    /// Whether a check is due, marking it done if so
+    fn check_due(&mut self, now: Instant) -> bool {
+        let due = self
+            .last_check
+            .map_or(true, |t| now.duration_since(t) >= CHECK_INTERVAL);
+        if due {
+            self.last_check = Some(now);
+        }
+        due
+    }
+}
+
+/// Ping clients, update the liveness of every cell and drop dead windows
+pub fn check_liveness(compositor: &mut TermStack, terminal_manager: &mut TerminalManager) {
+    let now = Instant::now();
+    if !compositor.watchdog.check_due(now) {
+        return;
+    }
+
+    let mut dead_windows = Vec::new();
+    let mut changed = false;
+    // Read once for all terminals, and only if one is still running
+    let mut processes = None;
+
+    for node in &mut compositor.layout_nodes {
+        match &mut node.cell {
+            StackWindow::Terminal(id) => {
+                let Some(terminal) = terminal_manager.get_mut(*id) else {
+                    continue;
+                };
+                if !terminal.has_exited()
+                    && terminal.terminal.foreground_blocked(processes.get_or_insert_with(ProcessTable::read))
+                {
+                    terminal.liveness.mark_waiting(now);
+                } else {
+                    terminal.liveness.mark_alive();
+                }
+                let update = terminal.liveness.update(now);
+                changed |= update.is_some();
+                match update {
+                    Some(true) => tracing::warn!(
+                        id = id.0,
+                        title = %terminal.title,
+                        "terminal not responding: foreground job in uninterruptible sleep"
+                    ),
+                    Some(false) => tracing::info!(id = id.0, "terminal responding again"),
+                    None => {}
+                }
+            }
+            StackWindow::External(entry) => {
+                if !entry.surface.alive() {
+                    dead_windows.push(entry.surface.wl_surface().clone());
+                    continue;
+                }
+                if entry.liveness.ping_due(now) {
+                    match entry.surface.client().send_ping(SERIAL_COUNTER.next_serial()) {
+                        // Windows of the same client share one outstanding ping
+                        Ok(()) | Err(PingError::PingAlreadyPending(_)) => {
+                            entry.liveness.ping_sent(now);
+                        }
+                        Err(PingError::DeadSurface) => {
+                            dead_windows.push(entry.surface.wl_surface().clone());
+                            continue;
+                        }
+                    }
+                }
+                let update = entry.liveness.update(now);
+                changed |= update.is_some();
+                match update {
+                    Some(true) => tracing::warn!(
+                        command = %entry.command,
+                        "window not responding: no pong or commit"
+                    ),
+                    Some(false) => tracing::info!(command = %entry.command, "window responding again"),
+                    None => {}
+                }
+            }
+        }
+    }
+
+    if changed || !dead_windows.is_empty() {
+        compositor.redraw_requested = true;
+    }
+
+    for surface in dead_windows {
+        tracing::warn!(surface_id = ?surface.id(), "removing window of disconnected client");
+        compositor.remove_window(&surface);
+    }
+}
+
+/// Record a pong for every window belonging to `client`
+pub fn handle_pong(compositor: &mut TermStack, client: &ShellClient) {
+    let now = Instant::now();
+    for node in &mut compositor.layout_nodes {
+        if let StackWindow::External(entry) = &mut node.cell {
+            if entry.surface.client() != *client {
+                continue;
+            }
+            if let Some(latency) = entry.liveness.pong_received(now) {
+                tracing::trace!(command = %entry.command, ?latency, "pong");
+            }
+        }
+    }
+}
+
+/// Kill the client owning an unresponsive window
+///
+/// X11 windows all belong to xwayland-satellite, so killing their client
+/// would take down every X11 app; those only get a regular close request.
+pub fn force_close(compositor: &TermStack, surface: &SurfaceKind) {
+    let client_pid = surface
+        .wl_surface()
+        .client()
+        .and_then(|client| client.get_credentials(&compositor.display_handle).ok())
+        .map(|credentials| credentials.pid);
+
+    let satellite_pid = compositor.xwayland.satellite_pid();
+
+    let pid = client_pid
+        .filter(|&pid| pid > 0 && Some(pid) != satellite_pid && pid != std::process::id() as i32)
+        .and_then(rustix::process::Pid::from_raw);
+
+    match pid {
+        Some(pid) => {
+            tracing::warn!(pid = pid.as_raw_nonzero().get(), "killing unresponsive client");
+            if let Err(e) = rustix::process::kill_process(pid, rustix::process::Signal::Kill) {
+                tracing::warn!(?e, "failed to kill unresponsive client");
+            }
+        }
+        None => {
+            tracing::info!("unresponsive window has no killable client, sending close");
+            surface.send_close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stall_becomes_not_responding_after_timeout() {
+        let mut liveness = Liveness::default();
+        let start = Instant::now();
+        liveness.mark_waiting(start);
+
+        assert_eq!(liveness.update(start + Duration::from_secs(1)), None);
+        assert!(!liveness.is_not_responding());
+
+        assert_eq!(liveness.update(start + NOT_RESPONDING_TIMEOUT), Some(true));
+        assert!(liveness.is_not_responding());
+        // Only reported once
+        assert_eq!(liveness.update(start + NOT_RESPONDING_TIMEOUT * 2), None);
+    }
+
+    #[test]
+    fn repeated_waits_keep_earliest_start() {
+        let mut liveness = Liveness::default();
+        let start = Instant::now();
+        liveness.mark_waiting(start);
+        liveness.mark_waiting(start + Duration::from_secs(4));
+
+        assert_eq!(liveness.update(start + NOT_RESPONDING_TIMEOUT), Some(true));
+    }
+
+    #[test]
+    fn response_clears_stall() {
+        let mut liveness = Liveness::default();
+        let start = Instant::now();
+        liveness.mark_waiting(start);
+        liveness.update(start + NOT_RESPONDING_TIMEOUT);
+
+        liveness.mark_alive();
+        assert_eq!(liveness.update(start + NOT_RESPONDING_TIMEOUT * 2), Some(false));
+        assert!(!liveness.is_not_responding());
+    }
+
+    #[test]
+    fn unanswered_ping_stalls_until_pong() {
+        let mut liveness = Liveness::default();
+        let start = Instant::now();
+        assert!(liveness.ping_due(start));
+        liveness.ping_sent(start);
+        assert!(!liveness.ping_due(start + PING_INTERVAL));
+
+        // A commit doesn't clear an outstanding ping
+        liveness.mark_alive();
+        assert_eq!(liveness.update(start + NOT_RESPONDING_TIMEOUT), Some(true));
+
+        let pong_at = start + NOT_RESPONDING_TIMEOUT + Duration::from_secs(1);
+        assert_eq!(liveness.pong_received(pong_at), Some(pong_at - start));
+        assert_eq!(liveness.pong_latency(), Some(pong_at - start));
+        assert_eq!(liveness.update(pong_at), Some(false));
+        // Stray pongs are ignored
+        assert_eq!(liveness.pong_received(pong_at), None);
+    }
+
+    #[test]
+    fn pings_are_spaced_by_interval() {
+        let mut liveness = Liveness::default();
+        let start = Instant::now();
+        liveness.ping_sent(start);
+        liveness.pong_received(start + Duration::from_millis(5));
+
+        assert!(!liveness.ping_due(start + Duration::from_secs(1)));
+        assert!(liveness.ping_due(start + PING_INTERVAL));
+    }
+
+    #[test]
+    fn title_gets_suffix_only_when_not_responding() {
+        let mut liveness = Liveness::default();
+        assert_eq!(liveness.decorate_title("vim"), "vim");
+
+        let start = Instant::now();
+        liveness.mark_waiting(start);
+        liveness.update(start + NOT_RESPONDING_TIMEOUT);
+        assert_eq!(liveness.decorate_title("vim"), "vim (not responding)");
+    }
+
+    #[test]
+    fn checks_are_rate_limited() {
+        let mut watchdog = Watchdog::default();
+        let now = Instant::now();
+        assert!(watchdog.check_due(now));
+        assert!(!watchdog.check_due(now + Duration::from_millis(100)));
+        assert!(watchdog.check_due(now + CHECK_INTERVAL));
+    }
+}
@@ -0,0 +1,109 @@
+//! First-run welcome cell
+//!
+//! When no config file exists yet, the first shell gets a static cell above it
+//! listing the main keybindings, whether the shell gets the integration, and
+//! `termstack init-config` to write a default config. Writing a config is what
+//! marks termstack as set up, so the cell stops appearing after that.
+
+use std::path::Path;
+
+use crate::config::Config;
+use crate::state::{StackWindow, TermStack};
+use crate::terminal_manager::TerminalManager;
+
+/// Title bar text of the welcome cell
+pub const WELCOME_TITLE: &str = "Welcome to termstack";
+
+/// Keybindings shown in the welcome cell
+const KEYS: &[(&str, &str)] = &[
+    ("Super+T", "new terminal"),
+    ("Super+J / Super+K", "focus next / previous cell"),
+    ("Super+Up / Super+Down", "scroll"),
+    ("Super+R", "resize the focused cell"),
+    ("Alt+Tab", "switch cells"),
+    ("Ctrl+Shift+C / V", "copy / paste"),
+    ("Super+Q", "quit"),
+];
+
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Body of the welcome cell for a session running `shell`
+///
+/// `config_path` is where `termstack init-config` will write the config.
+pub fn welcome_text(shell: &str, config_path: Option<&Path>) -> String {
+    let mut lines = vec![
+        "Each command you run gets its own cell, stacked in one scrolling column.".to_string(),
+        String::new(),
+        format!("{BOLD}Keys{RESET} (use Ctrl+Shift instead of Super when running nested)"),
+    ];
+    let width = KEYS.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+    lines.extend(KEYS.iter().map(|(key, action)| format!("  {key:<width$}  {action}")));
+
+    lines.push(String::new());
+    lines.push(format!("{BOLD}Shell integration{RESET}"));
+    if Path::new(shell).file_name().and_then(|n| n.to_str()) == Some("fish") {
+        lines.push("  Loaded automatically into fish. Try: ls, then gui <app> for a GUI app.".to_string());
+    } else {
+        lines.push(format!(
+            "  Only available for fish (your shell is {}). Start termstack with",
+            if shell.is_empty() { "not set" } else { shell }
+        ));
+        lines.push("  SHELL=$(command -v fish) for a cell per command.".to_string());
+    }
+
+    lines.push(String::new());
+    lines.push(format!("{BOLD}Setup{RESET}"));
+    match config_path {
+        Some(path) => lines.push(format!(
+            "  termstack init-config   write the default config to {} (hides this cell)",
+            path.display()
+        )),
+        None => lines.push("  termstack init-config   write the default config (hides this cell)".to_string()),
+    }
+    lines.push("  termstack doctor        check GPU, fonts, xwayland-satellite and config".to_string());
+
+    lines.iter().map(|line| format!("{line}\r\n")).collect()
+}
+
+/// Add the welcome cell above the current cells if termstack has no config yet
+///
+/// Call before spawning the initial shell so the shell ends up below it.
+pub fn show_if_first_run(compositor: &mut TermStack, terminal_manager: &mut TerminalManager) {
+    if !Config::is_first_run() {
+        return;
+    }
+    let shell = std::env::var("SHELL").unwrap_or_default();
+    let text = welcome_text(&shell, Config::user_config_path().as_deref());
+    match terminal_manager.create_builtin_terminal(WELCOME_TITLE, "", &text, true) {
+        Ok(id) => {
+            compositor.push_cell(StackWindow::Terminal(id), 0);
+            compositor.invalidate_focused_index_cache();
+            tracing::info!(id = id.0, "first run, showing welcome cell");
+        }
+        Err(e) => tracing::warn!(error = ?e, "failed to create welcome cell"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn welcome_text_lists_keys_and_setup() {
+        let text = welcome_text("/usr/bin/fish", Some(Path::new("/home/u/.config/termstack/config.toml")));
+        assert!(text.contains("Super+T"));
+        assert!(text.contains("Loaded automatically into fish"));
+        assert!(text.contains("/home/u/.config/termstack/config.toml"));
+        assert!(text.contains("termstack doctor"));
+        // Every line ends with CR LF so the injected text starts at column 0
+        assert!(text.split_inclusive('\n').all(|line| line.ends_with("\r\n")));
+    }
+
+    #[test]
+    fn welcome_text_explains_missing_integration() {
+        let text = welcome_text("/bin/bash", None);
+        assert!(text.contains("your shell is /bin/bash"));
+        assert!(!text.contains("Loaded automatically"));
+    }
+}
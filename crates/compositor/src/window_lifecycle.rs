@@ -3,6 +3,8 @@
 //! Handles window creation, cleanup of dead terminals and windows,
 //! output terminal management, and focus synchronization.
 
+use std::time::{Duration, Instant};
+
 use crate::layout::calculate_terminal_render_height;
 use crate::state::{StackWindow, TermStack};
 use crate::terminal_manager::{TerminalId, TerminalManager};
@@ -72,6 +74,61 @@ fn restore_launcher_only(
     }
 }
 
+/// How long a foreground GUI session may have neither a running command nor
+/// a live window before its launcher is restored. Covers apps that hand off
+/// to an already running instance or fork before mapping.
+pub const ORPHANED_GUI_GRACE: Duration = Duration::from_secs(2);
+
+/// Restore the launchers of foreground GUI sessions whose app is gone.
+///
+/// The output terminal of a GUI command stays open after it exits, so its
+/// exit never shows up in `cleanup_and_sync_focus`, and a client that dies
+/// may leave no close event behind. A session whose command has exited and
+/// that has no live window is orphaned; after `ORPHANED_GUI_GRACE` its
+/// launcher is shown and focused again.
+pub fn restore_orphaned_gui_launchers(
+    compositor: &mut TermStack,
+    terminal_manager: &mut TerminalManager,
+) {
+    if compositor.foreground_gui_sessions.is_empty() {
+        compositor.orphaned_gui_sessions.clear();
+        return;
+    }
+
+    let now = Instant::now();
+    let sessions: Vec<_> = compositor.foreground_gui_sessions.iter().map(|(o, (l, _))| (*o, *l)).collect();
+    compositor.orphaned_gui_sessions.retain(|id, _| compositor.foreground_gui_sessions.contains_key(id));
+
+    for (output_id, launcher_id) in sessions {
+        let command_running = terminal_manager.get(output_id).is_some_and(|t| !t.has_exited());
+        let window_alive = compositor.layout_nodes.iter().any(|node| match &node.cell {
+            StackWindow::External(entry) => {
+                entry.surface.alive()
+                    && (entry.output_terminal == Some(output_id) || entry.launcher_terminal == Some(launcher_id))
+            }
+            StackWindow::Terminal(_) => false,
+        });
+        if command_running || window_alive {
+            compositor.orphaned_gui_sessions.remove(&output_id);
+            continue;
+        }
+
+        let since = *compositor.orphaned_gui_sessions.entry(output_id).or_insert(now);
+        if now.duration_since(since) < ORPHANED_GUI_GRACE {
+            continue;
+        }
+        compositor.orphaned_gui_sessions.remove(&output_id);
+        compositor.foreground_gui_sessions.remove(&output_id);
+        restore_foreground_gui_launcher(
+            compositor,
+            terminal_manager,
+            launcher_id,
+            output_id,
+            "foreground GUI gone without closing its window",
+        );
+    }
+}
+
 /// Handle new external windows and window resize events.
 ///
 /// Processes new external window additions (with keyboard focus if needed)
@@ -79,11 +136,12 @@ fn restore_launcher_only(
 pub fn handle_external_window_events(compositor: &mut TermStack) {
     // Handle new external window - heights are already managed in add_window,
     // just need to scroll and set keyboard focus if needed
-    if let Some(window_idx) = compositor.new_external_window_index.take() {
+    if let Some(window) = compositor.new_external_window.take() {
+        let window_idx = compositor.cell_index(window);
         let needs_keyboard_focus = std::mem::take(&mut compositor.new_window_needs_keyboard_focus);
 
         tracing::info!(
-            window_idx,
+            ?window_idx,
             cells_count = compositor.layout_nodes.len(),
             focused_index = ?compositor.focused_index(),
             needs_keyboard_focus,
@@ -93,14 +151,14 @@ pub fn handle_external_window_events(compositor: &mut TermStack) {
         // If this is a foreground GUI window, give it keyboard focus
         if needs_keyboard_focus {
             compositor.update_keyboard_focus_for_focused_window();
-            tracing::info!(window_idx, "set keyboard focus to foreground GUI window");
+            tracing::info!(?window_idx, "set keyboard focus to foreground GUI window");
         }
 
         // Scroll to show the focused cell
         if let Some(focused_idx) = compositor.focused_index() {
             if let Some(new_scroll) = compositor.scroll_to_show_window_bottom(focused_idx) {
                 tracing::info!(
-                    window_idx,
+                    ?window_idx,
                     focused_idx,
                     new_scroll,
                     "scrolled to show focused cell after external window added"
@@ -110,15 +168,17 @@ pub fn handle_external_window_events(compositor: &mut TermStack) {
     }
 
     // Handle external window resize
-    if let Some((resized_idx, new_height)) = compositor.external_window_resized.take() {
+    let resized = compositor
+        .external_window_resized
+        .take()
+        .and_then(|(cell, new_height)| Some((cell, compositor.cell_index(cell)?, new_height)));
+    if let Some((resized_cell, resized_idx, new_height)) = resized {
         // Skip processing if this cell is currently being resized by the user
         // (don't let stale commits overwrite the drag updates)
-        let is_resizing = compositor.resizing.as_ref().map(|d| d.window_index);
-        if is_resizing == Some(resized_idx) {
+        if compositor.resizing.as_ref().is_some_and(|d| d.cell == resized_cell) {
             tracing::info!(
                 resized_idx,
                 new_height,
-                is_resizing = ?is_resizing,
                 "SKIPPING external_window_resized processing during active resize drag"
             );
         } else {
@@ -129,14 +189,12 @@ pub fn handle_external_window_events(compositor: &mut TermStack) {
                 false
             };
 
-            if let Some(node) = compositor.layout_nodes.get_mut(resized_idx) {
-                node.height = new_height;
-            }
+            compositor.request_window_height(resized_idx, new_height);
 
             // Only autoscroll if focused cell is at/below resized window AND bottom was visible
             if should_autoscroll {
                 if let Some(focused_idx) = compositor.focused_index() {
-                    compositor.scroll_to_show_window_bottom(focused_idx);
+                    compositor.reveal_after_layout(focused_idx);
                 }
             }
         }
@@ -150,7 +208,6 @@ pub fn handle_external_window_events(compositor: &mut TermStack) {
 pub fn handle_terminal_spawn(
     compositor: &mut TermStack,
     terminal_manager: &mut TerminalManager,
-    calculate_window_heights: impl Fn(&TermStack, &TerminalManager) -> Vec<i32>,
 ) {
     if !compositor.spawn_terminal_requested {
         return;
@@ -162,21 +219,15 @@ pub fn handle_terminal_spawn(
             compositor.add_terminal(id);
             compositor.enforce_terminal_limit(terminal_manager);
 
-            // Update cell heights
-            let new_heights = calculate_window_heights(compositor, terminal_manager);
-            compositor.update_layout_heights(new_heights);
-
-            // Scroll to show the new terminal
+            // Scroll to show the new terminal once it is laid out
             if let Some(focused_idx) = compositor.focused_index() {
-                if let Some(new_scroll) = compositor.scroll_to_show_window_bottom(focused_idx) {
-                    tracing::info!(
-                        id = id.0,
-                        window_count = compositor.layout_nodes.len(),
-                        focused_idx,
-                        new_scroll,
-                        "spawned terminal, scrolling to show"
-                    );
-                }
+                compositor.reveal_after_layout(focused_idx);
+                tracing::info!(
+                    id = id.0,
+                    window_count = compositor.layout_nodes.len(),
+                    focused_idx,
+                    "spawned terminal, scrolling to show"
+                );
             }
         }
         Err(e) => {
@@ -219,7 +270,7 @@ pub fn handle_output_terminal_cleanup(
             );
         } else {
             // Never had output - remove from layout and TerminalManager
-            compositor.layout_nodes.retain(|n| {
+            compositor.retain_cells(|n| {
                 !matches!(n.cell, StackWindow::Terminal(id) if id == term_id)
             });
             terminal_manager.remove(term_id);
@@ -325,25 +376,17 @@ pub fn cleanup_and_sync_focus(
             compositor.set_focus_by_index(idx);
             tracing::info!(id = new_focus_id.0, index = idx, "synced compositor focus to parent terminal");
 
-            // Update cached height for unhidden terminal (was 0 when hidden)
+            // Lay out the unhidden terminal at its height (was 0 when hidden)
             if let Some(term) = terminal_manager.get(new_focus_id) {
-                if let Some(node) = compositor.layout_nodes.get_mut(idx) {
-                    node.height = calculate_terminal_render_height(
-                        term.height as i32,
-                        term.show_title_bar,
-                        true, // now visible
-                    );
-                }
+                compositor.request_window_height(idx, calculate_terminal_render_height(
+                    term.height as i32,
+                    term.show_title_bar,
+                    true, // now visible
+                ));
             }
 
             // Scroll to show the unhidden parent terminal
-            if let Some(new_scroll) = compositor.scroll_to_show_window_bottom(idx) {
-                tracing::info!(
-                    id = new_focus_id.0,
-                    new_scroll,
-                    "scrolled to show unhidden parent terminal"
-                );
-            }
+            compositor.reveal_after_layout(idx);
         }
     }
 
@@ -356,6 +399,14 @@ pub fn cleanup_and_sync_focus(
     // Check if all cells are gone (this handles both natural terminal exits
     // and direct removal via close button)
     if compositor.layout_nodes.is_empty() {
+        if compositor.respawn_last_shell {
+            // Unless a shell is on its way already
+            if !compositor.spawn_terminal_requested && !compositor.spawn_initial_terminal {
+                tracing::info!("all cells removed, opening a fresh shell");
+                compositor.spawn_terminal_requested = true;
+            }
+            return false;
+        }
         tracing::info!("all cells removed, shutting down");
         return true;
     }
@@ -0,0 +1,240 @@
+//! Window switcher (Alt+Tab)
+//!
+//! Alt+Tab (or Super+Tab, for when the host grabs Alt+Tab) opens an overlay
+//! listing the cells by title, most recently focused first, with the
+//! previously focused cell selected. Tab and Shift+Tab (or Down and Up) move
+//! the selection; letting go of the modifier or pressing Enter focuses the
+//! selected cell, Esc closes the overlay without switching. Number keys 1-6
+//! show only the cells with that color tag (in `ColorTag::ALL` order), 0 all
+//! of them again.
+//!
+//! Above the list a row of thumbnails shows the selected cell and its
+//! neighbours in the list, scaled down from what the cells last drew:
+//! terminals keep the bottom of their output, windows are shown whole.
+//! Every backend draws the row (the GPU ones in `render`, winit in software)
+//! from the boxes laid out here.
+
+use crate::cell_id::CellId;
+use crate::color_tags::ColorTag;
+use crate::state::{StackWindow, TermStack};
+use crate::terminal_manager::TerminalManager;
+
+/// Longest title shown in the overlay (characters)
+const MAX_TITLE_CHARS: usize = 60;
+
+/// Size of a thumbnail box (physical pixels)
+pub const THUMBNAIL_SIZE: (i32, i32) = (240, 150);
+
+/// Space between thumbnails and between the row and the list
+pub const THUMBNAIL_GAP: i32 = 16;
+
+/// Width of the frame around the selected thumbnail
+pub const THUMBNAIL_BORDER: i32 = 3;
+
+/// Most thumbnails shown at once
+const MAX_THUMBNAILS: usize = 7;
+
+/// Switcher state, kept on `TermStack`
+#[derive(Debug, Default)]
+pub struct WindowSwitcher {
+    /// All cells in most recently focused order while open (empty when closed)
+    order: Vec<CellId>,
+    /// The cells of `order` with the `filter` tag
+    cells: Vec<CellId>,
+    selected: usize,
+    filter: Option<ColorTag>,
+}
+
+impl WindowSwitcher {
+    pub fn is_open(&self) -> bool {
+        !self.order.is_empty()
+    }
+
+    /// Move the selection by `delta`, wrapping around
+    fn step(&mut self, delta: isize) {
+        let len = self.cells.len() as isize;
+        if len > 0 {
+            self.selected = (self.selected as isize + delta).rem_euclid(len) as usize;
+        }
+    }
+
+    /// Overlay text: one line per cell still open, the selected one marked,
+    /// after a line naming the filter if there is one
+    pub fn lines(&self, compositor: &TermStack, terminal_manager: &TerminalManager) -> Vec<String> {
+        let cells = self.cells.iter().enumerate().filter_map(|(i, &cell)| {
+            let node = compositor.layout_nodes.get(compositor.cell_index(cell)?)?;
+            let title = match &node.cell {
+                StackWindow::Terminal(id) => {
+                    terminal_manager.get(*id).map(|t| t.display_title().to_string()).unwrap_or_default()
+                }
+                StackWindow::External(entry) => entry.title_bar_text().into_owned(),
+            };
+            let marker = if i == self.selected { '>' } else { ' ' };
+            let tag = node.tag.map(|tag| format!("[{}] ", tag.name())).unwrap_or_default();
+            Some(format!("{marker} {tag}{}", truncate(&title, MAX_TITLE_CHARS)))
+        });
+        match self.filter {
+            Some(tag) if self.cells.is_empty() => vec![format!("  no cells tagged {} (0 shows all)", tag.name())],
+            Some(tag) => std::iter::once(format!("  tagged {}:", tag.name())).chain(cells).collect(),
+            None => cells.collect(),
+        }
+    }
+
+    /// The cells shown as thumbnails on a screen `screen_width` wide: a run
+    /// of the listed cells around the selected one, each with whether it is
+    /// the selected one
+    pub fn thumbnail_cells(&self, screen_width: i32) -> Vec<(CellId, bool)> {
+        let range = thumbnail_range(self.cells.len(), self.selected, thumbnail_count(screen_width));
+        range.map(|i| (self.cells[i], i == self.selected)).collect()
+    }
+}
+
+/// How many thumbnails fit side by side on a screen `screen_width` wide
+fn thumbnail_count(screen_width: i32) -> usize {
+    let fit = (screen_width - THUMBNAIL_GAP) / (THUMBNAIL_SIZE.0 + THUMBNAIL_GAP);
+    (fit.max(1) as usize).min(MAX_THUMBNAILS)
+}
+
+/// Indices of `count` of `len` listed cells, `selected` as near the middle
+/// as the ends of the list allow
+fn thumbnail_range(len: usize, selected: usize, count: usize) -> std::ops::Range<usize> {
+    let count = count.min(len);
+    let start = selected.saturating_sub(count / 2).min(len - count);
+    start..start + count
+}
+
+/// Top-left corners (y counted down from the top of the screen) of `count`
+/// thumbnail boxes in a row centered above a centered panel `panel_height`
+/// tall
+pub fn thumbnail_boxes(count: usize, screen_size: (i32, i32), panel_height: i32) -> Vec<(i32, i32)> {
+    let (width, height) = THUMBNAIL_SIZE;
+    let row_width = count as i32 * (width + THUMBNAIL_GAP) - THUMBNAIL_GAP;
+    let left = (screen_size.0 - row_width) / 2;
+    let panel_top = ((screen_size.1 - panel_height) / 2).max(0);
+    let top = (panel_top - THUMBNAIL_GAP - height).max(0);
+    (0..count as i32).map(|i| (left + i * (width + THUMBNAIL_GAP), top)).collect()
+}
+
+/// Rows of a `width` x `height` terminal image shown in its thumbnail: the
+/// bottom part, cut to the box's shape so the latest output stays readable
+pub fn thumbnail_rows(width: i32, height: i32) -> i32 {
+    height.min(width * THUMBNAIL_SIZE.1 / THUMBNAIL_SIZE.0)
+}
+
+/// Scale that fits a `width` x `height` image into a thumbnail box (never
+/// more than 1)
+pub fn thumbnail_scale(width: i32, height: i32) -> f64 {
+    let (box_width, box_height) = THUMBNAIL_SIZE;
+    (box_width as f64 / width.max(1) as f64)
+        .min(box_height as f64 / height.max(1) as f64)
+        .min(1.0)
+}
+
+/// `text` cut to `max` characters, ending in "..." if anything was cut
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(max.saturating_sub(3)).collect();
+    cut.push_str("...");
+    cut
+}
+
+impl TermStack {
+    /// Open the switcher, selecting the cell `delta` away from the focused one
+    pub fn open_window_switcher(&mut self, delta: isize) {
+        let cells = self.cells_by_recent_focus();
+        if cells.is_empty() {
+            return;
+        }
+        self.window_switcher = WindowSwitcher { order: cells.clone(), cells, selected: 0, filter: None };
+        self.window_switcher.step(delta);
+        self.redraw_requested = true;
+    }
+
+    /// Move the switcher's selection
+    pub fn step_window_switcher(&mut self, delta: isize) {
+        self.window_switcher.step(delta);
+        self.redraw_requested = true;
+    }
+
+    /// Show only the cells tagged `filter` in the switcher (`None`: all),
+    /// selecting the most recently focused of them
+    pub fn filter_window_switcher(&mut self, filter: Option<ColorTag>) {
+        let cells = self
+            .window_switcher
+            .order
+            .iter()
+            .copied()
+            .filter(|&cell| {
+                filter.is_none() || self.cell_index(cell).and_then(|index| self.layout_nodes[index].tag) == filter
+            })
+            .collect();
+        let switcher = &mut self.window_switcher;
+        switcher.cells = cells;
+        switcher.filter = filter;
+        switcher.selected = 0;
+        self.redraw_requested = true;
+    }
+
+    /// Close the switcher, focusing the selected cell if `switch`
+    pub fn close_window_switcher(&mut self, switch: bool) {
+        let switcher = std::mem::take(&mut self.window_switcher);
+        self.redraw_requested = true;
+        if !switch {
+            return;
+        }
+        let Some(index) = switcher.cells.get(switcher.selected).and_then(|&cell| self.cell_index(cell)) else {
+            return;
+        };
+        tracing::info!(index, "window switcher focused cell");
+        self.set_focus_by_index(index);
+        self.scroll_to_show_window_bottom(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selection_wraps() {
+        let cells: Vec<CellId> = (1..=3).map(CellId::from_bits).collect();
+        let mut switcher = WindowSwitcher { order: cells.clone(), cells, selected: 0, filter: None };
+        switcher.step(1);
+        assert_eq!(switcher.selected, 1);
+        switcher.step(2);
+        assert_eq!(switcher.selected, 0);
+        switcher.step(-1);
+        assert_eq!(switcher.selected, 2);
+    }
+
+    #[test]
+    fn long_titles_are_cut() {
+        assert_eq!(truncate("cargo test", 20), "cargo test");
+        assert_eq!(truncate("cargo test --workspace", 10), "cargo t...");
+    }
+
+    #[test]
+    fn thumbnails_follow_the_selection() {
+        assert_eq!(thumbnail_range(10, 0, 5), 0..5);
+        assert_eq!(thumbnail_range(10, 6, 5), 4..9);
+        assert_eq!(thumbnail_range(10, 9, 5), 5..10);
+        assert_eq!(thumbnail_range(3, 2, 5), 0..3);
+        assert_eq!(thumbnail_count(100), 1);
+        assert_eq!(thumbnail_count(10_000), MAX_THUMBNAILS);
+    }
+
+    #[test]
+    fn thumbnails_fit_their_box() {
+        // A tall terminal keeps a box-shaped bottom part, shown at half size
+        assert_eq!(thumbnail_rows(480, 2000), 300);
+        assert_eq!(thumbnail_scale(480, 300), 0.5);
+        // Small images are not enlarged
+        assert_eq!(thumbnail_rows(100, 20), 20);
+        assert_eq!(thumbnail_scale(100, 20), 1.0);
+
+        let boxes = thumbnail_boxes(2, (1000, 800), 100);
+        assert_eq!(boxes, vec![(252, 184), (508, 184)]);
+    }
+}
@@ -11,7 +11,7 @@ use smithay::backend::renderer::utils::RendererSurfaceStateUserData;
 use smithay::output::Output;
 use smithay::reexports::calloop::EventLoop;
 use smithay::reexports::wayland_server::Display;
-use smithay::utils::Size;
+use smithay::utils::{Rectangle, Size};
 use smithay::wayland::compositor::{
     with_surface_tree_downward, SubsurfaceCachedState, TraversalAction,
 };
@@ -20,17 +20,17 @@ use softbuffer::Surface;
 use winit::application::ApplicationHandler;
 use winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
 use winit::event_loop::{ActiveEventLoop, EventLoop as WinitEventLoop};
-use winit::keyboard::{Key, ModifiersState, NamedKey};
+use winit::keyboard::{Key, KeyCode, ModifiersState, NamedKey, PhysicalKey};
 use winit::window::{Window, WindowAttributes, WindowId};
 
+use crate::background::Background;
+use crate::cell_style::CellStyle;
 use crate::config::Config;
 use crate::coords::ScreenY;
+use crate::frame_layout::FrameLayout;
 use crate::state::{StackWindow, TermStack};
 use crate::terminal_manager::TerminalManager;
-use crate::title_bar::TitleBarRenderer;
-
-/// Minimum time between frames (~120 FPS cap)
-const MIN_FRAME_TIME: Duration = Duration::from_millis(8);
+use crate::title_bar::{title_bar_visible, TitleBarCache, TitleBarKey, TitleBarRenderer};
 
 /// Run the compositor with the winit backend (macOS)
 pub fn run_compositor_winit() -> anyhow::Result<()> {
@@ -39,6 +39,7 @@ pub fn run_compositor_winit() -> anyhow::Result<()> {
     let event_loop = WinitEventLoop::new()?;
     event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
 
+    let config = Config::load();
     let mut app = App {
         window: None,
         surface: None,
@@ -47,15 +48,29 @@ pub fn run_compositor_winit() -> anyhow::Result<()> {
         calloop: None,
         terminal_manager: None,
         output: None,
-        config: Config::load(),
         modifiers: ModifiersState::empty(),
         cursor_position: (0.0, 0.0),
         title_bar_renderer: None,
+        title_bar_cache: TitleBarCache::new(),
         title_bar_height: crate::title_bar::TITLE_BAR_HEIGHT as i32,
         close_button_width: crate::title_bar::CLOSE_BUTTON_WIDTH as i32,
         last_render_time: Instant::now(),
+        background: None,
+        background_pixels: None,
+        switcher_panel: None,
+        cell_style: CellStyle::from_config(&config),
+        config,
+    };
+    app.background = match Background::load(&app.config.background) {
+        Ok(background) => background,
+        Err(e) => {
+            tracing::warn!(error = %e, "using flat background color");
+            None
+        }
     };
 
+    crate::crash::install_panic_hook(&app.config);
+
     event_loop.run_app(&mut app)?;
 
     Ok(())
@@ -74,6 +89,8 @@ struct App {
     output: Option<Output>,
     config: Config,
     title_bar_renderer: Option<TitleBarRenderer>,
+    /// Rendered title bars as (BGRA pixels, height)
+    title_bar_cache: TitleBarCache<(Vec<u8>, u32)>,
 
     // Input state
     modifiers: ModifiersState,
@@ -85,6 +102,17 @@ struct App {
 
     // Frame timing
     last_render_time: Instant,
+
+    /// Background image or gradient, and its pixels as (width, height, BGRA)
+    /// for the current window size
+    background: Option<Background>,
+    background_pixels: Option<(u32, u32, Vec<u8>)>,
+
+    /// Window switcher panel as (lines, BGRA pixels, width, height)
+    switcher_panel: Option<(Vec<String>, Vec<u8>, u32, u32)>,
+
+    /// Rounded corners and shadows, painted in software
+    cell_style: CellStyle,
 }
 
 impl ApplicationHandler for App {
@@ -119,6 +147,14 @@ impl ApplicationHandler for App {
         let output_width = inner_size.width;
         let output_height = inner_size.height;
 
+        // Pace frames to the monitor the window opened on
+        let refresh = window
+            .current_monitor()
+            .and_then(|monitor| monitor.refresh_rate_millihertz())
+            .and_then(|refresh| i32::try_from(refresh).ok())
+            .unwrap_or(crate::refresh::DEFAULT_REFRESH);
+        tracing::info!(refresh, "output refresh rate (mHz)");
+
         self.window = Some(window);
         self.surface = Some(surface);
 
@@ -133,7 +169,7 @@ impl ApplicationHandler for App {
 
         // Create output
         let (output, _mode, output_size) =
-            crate::setup::create_output("winit", output_width as i32, output_height as i32);
+            crate::setup::create_output("winit", output_width as i32, output_height as i32, refresh);
 
         // Create compositor state
         let (mut compositor, display) = TermStack::new(
@@ -143,6 +179,20 @@ impl ApplicationHandler for App {
             self.config.csd_apps.clone(),
             self.config.max_gui_windows,
         );
+        compositor.gui_env = self.config.gui_env.clone();
+        compositor.refresh = refresh;
+        compositor.hide_gui_launcher = self.config.hide_gui_launcher;
+        compositor.decoration_overrides = self.config.decorations.clone();
+        compositor.window_rules = self.config.window_rules.clone();
+        compositor.max_window_height = self.config.max_window_height;
+        compositor.quick_terminal = crate::quick_terminal::QuickTerminal::new(self.config.quick_terminal_height);
+        compositor.quick_terminal.set_reduce_motion(self.config.reduce_motion);
+        compositor.copy_options = crate::selection::CopyOptions::from_config(&self.config);
+        compositor.word_chars = self.config.word_chars.clone();
+        compositor.spawn_dedup = crate::spawn_handler::SpawnDedup::from_config(&self.config);
+        compositor.undo_stack = crate::undo::UndoStack::from_config(&self.config);
+        compositor.archive = crate::archive::Archive::from_config(&self.config);
+        compositor.respawn_last_shell = self.config.respawn_last_shell;
 
         // Add output to compositor
         compositor.space.map_output(&output, (0, 0));
@@ -153,11 +203,23 @@ impl ApplicationHandler for App {
             .expect("failed to set up Wayland socket");
         crate::setup::setup_ipc_socket(&calloop.handle())
             .expect("failed to set up IPC socket");
+        crate::child_reaper::setup_sigchld_source(&calloop.handle())
+            .expect("failed to set up SIGCHLD reaper");
 
         // Create terminal manager
         let mut terminal_manager =
             crate::setup::create_terminal_manager(&self.config, output_width, output_height);
 
+        // Recover the stack if the previous run crashed
+        if let Some(snapshot) = crate::crash::take_saved_session() {
+            crate::crash::restore_session(&mut compositor, &mut terminal_manager, &snapshot);
+        }
+
+        // Load the plugins enabled in the config
+        if let Some(dir) = crate::config::Config::plugin_dir() {
+            compositor.plugins.load(&dir, &self.config.plugins);
+        }
+
         // Create title bar renderer (scaled for HiDPI)
         let terminal_theme = self.config.theme.to_terminal_theme();
         self.title_bar_renderer = TitleBarRenderer::new_scaled(terminal_theme, scale_factor as f32);
@@ -169,7 +231,8 @@ impl ApplicationHandler for App {
             tracing::warn!("Title bar renderer unavailable - no font found");
         }
 
-        // Spawn initial terminal
+        // Spawn initial terminal, below the welcome cell on first run
+        crate::welcome::show_if_first_run(&mut compositor, &mut terminal_manager);
         match terminal_manager.spawn() {
             Ok(id) => {
                 compositor.add_terminal(id);
@@ -203,6 +266,12 @@ impl ApplicationHandler for App {
 
             WindowEvent::ModifiersChanged(mods) => {
                 self.modifiers = mods.state();
+
+                // Letting go of Alt/Super switches to the selected cell
+                let held = self.modifiers.alt_key() || self.modifiers.super_key();
+                if compositor.window_switcher.is_open() && !held {
+                    compositor.close_window_switcher(true);
+                }
             }
 
             WindowEvent::KeyboardInput { event, .. } => {
@@ -216,12 +285,58 @@ impl ApplicationHandler for App {
                     return;
                 }
 
-                // Check compositor keybindings first
+                if window_switcher_key(compositor, &self.modifiers, &event.logical_key, event.physical_key) {
+                    return;
+                }
+
+                // Keyboard resize mode takes every key but its own binding
+                if compositor.keyboard_resize.is_active() {
+                    use crate::keyboard_resize::ResizeKey;
+                    let key = match &event.logical_key {
+                        Key::Named(NamedKey::ArrowDown) => Some(ResizeKey::Grow),
+                        Key::Named(NamedKey::ArrowUp) => Some(ResizeKey::Shrink),
+                        Key::Named(NamedKey::Enter) => Some(ResizeKey::Commit),
+                        Key::Named(NamedKey::Escape) => Some(ResizeKey::Cancel),
+                        Key::Character(s) => match s.as_str() {
+                            "j" => Some(ResizeKey::Grow),
+                            "k" => Some(ResizeKey::Shrink),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    let binding = parse_winit_keybinding(&self.modifiers, &event.logical_key);
+                    match key {
+                        Some(key) => {
+                            compositor.keyboard_resize.press(key);
+                            return;
+                        }
+                        None if binding != Some(crate::compositor_actions::CompositorAction::ResizeMode) => return,
+                        None => {}
+                    }
+                }
+
+                // Plugin bindings take precedence over compositor bindings
+                let key_name = match &event.logical_key {
+                    Key::Character(s) => Some(s.to_string()),
+                    Key::Named(named) => Some(format!("{named:?}")),
+                    _ => None,
+                };
+                let mods = crate::plugins::KeyMods {
+                    ctrl,
+                    shift,
+                    alt,
+                    logo: self.modifiers.super_key(),
+                };
+                if key_name.is_some_and(|name| compositor.plugins.handle_key(mods, &name)) {
+                    return;
+                }
+
+                // Then compositor keybindings
                 if let Some(action) = parse_winit_keybinding(&self.modifiers, &event.logical_key) {
                     crate::compositor_actions::apply_compositor_action(compositor, action);
 
-                    // Ctrl+Shift+Q uses process::exit on macOS (no clean shutdown path)
-                    if action == crate::compositor_actions::CompositorAction::Quit {
+                    // A confirmed Ctrl+Shift+Q uses process::exit on macOS (no clean shutdown path)
+                    if action == crate::compositor_actions::CompositorAction::Quit && !compositor.running {
                         std::process::exit(0);
                     }
 
@@ -233,10 +348,10 @@ impl ApplicationHandler for App {
                     return;
                 }
 
-                // Send key to focused terminal
+                // Send key to focused terminal (or the quick terminal over it)
                 let bytes = winit_key_to_bytes(&event.logical_key, ctrl, alt);
                 if !bytes.is_empty() {
-                    if let Some(terminal) = terminal_manager.get_focused_mut(compositor.focused_window.as_ref()) {
+                    if let Some(terminal) = terminal_manager.get_focused_mut(compositor.keyboard_input_window().as_ref()) {
                         if !terminal.has_exited() {
                             if let Err(e) = terminal.write(&bytes) {
                                 tracing::error!(?e, "failed to write to terminal");
@@ -278,18 +393,25 @@ impl ApplicationHandler for App {
                 }
 
                 // Handle resize drag motion
-                crate::mouse_actions::update_resize_drag(
-                    compositor,
-                    terminal_manager,
-                    position.y as i32,
-                    title_bar_h,
-                );
+                if compositor.resizing.as_ref().is_some_and(|drag| !drag.keyboard) {
+                    crate::mouse_actions::update_resize_drag(
+                        compositor,
+                        terminal_manager,
+                        position.y as i32,
+                        title_bar_h,
+                    );
+                }
             }
 
             WindowEvent::MouseInput { state, button, .. } => {
                 let screen_x = self.cursor_position.0;
                 let screen_y = ScreenY::new(self.cursor_position.1);
 
+                // Clicks on the quick terminal don't reach the cells under it
+                if state == ElementState::Pressed && compositor.quick_terminal.covers(screen_y.value()) {
+                    return;
+                }
+
                 match state {
                     ElementState::Pressed => {
                         compositor.pointer_buttons_pressed =
@@ -308,29 +430,39 @@ impl ApplicationHandler for App {
                                 ClickResult::ResizeDragStarted => {}
                                 ClickResult::CloseButtonClicked { index } => {
                                     match compositor.layout_nodes[index].cell {
-                                        StackWindow::Terminal(tid) => {
-                                            compositor.layout_nodes.remove(index);
-                                            compositor.invalidate_focused_index_cache();
-                                            terminal_manager.remove(tid);
-                                            compositor.update_focus_after_removal(index);
+                                        StackWindow::Terminal(_) => {
+                                            compositor.close_terminal_cell(index, terminal_manager);
                                         }
                                         StackWindow::External(ref entry) => {
-                                            entry.surface.send_close();
+                                            if entry.liveness.is_not_responding() {
+                                                crate::watchdog::force_close(compositor, &entry.surface);
+                                            } else {
+                                                entry.surface.send_close();
+                                            }
                                         }
                                     }
                                 }
                                 ClickResult::WindowClicked { index } => {
-                                    // Start text selection
+                                    // Start text selection (double-click selects a word)
                                     let render_y = screen_y.to_render(compositor.output_size.h);
-                                    crate::selection::start_cross_selection(
-                                        compositor,
-                                        terminal_manager,
-                                        screen_x,
-                                        render_y,
-                                    );
+                                    let double_click = crate::selection::register_click(compositor, screen_x, render_y);
+                                    if !(double_click
+                                        && crate::selection::select_word_at(compositor, terminal_manager, screen_x, render_y))
+                                    {
+                                        crate::selection::start_cross_selection(
+                                            compositor,
+                                            terminal_manager,
+                                            screen_x,
+                                            render_y,
+                                            self.modifiers.alt_key(),
+                                        );
+                                    }
                                     // Scroll to show focused window
                                     compositor.scroll_to_show_window_bottom(index);
                                 }
+                                ClickResult::WindowExpanded { index } => {
+                                    compositor.scroll_to_show_window_bottom(index);
+                                }
                                 ClickResult::NoHit => {}
                             }
                         } else if button == MouseButton::Middle {
@@ -339,7 +471,7 @@ impl ApplicationHandler for App {
                                 match clipboard.get_text() {
                                     Ok(text) => {
                                         if let Some(terminal) =
-                                            terminal_manager.get_focused_mut(compositor.focused_window.as_ref())
+                                            terminal_manager.get_focused_mut(compositor.focused_window())
                                         {
                                             if !terminal.has_exited() {
                                                 if let Err(e) = terminal.write(text.as_bytes()) {
@@ -390,6 +522,7 @@ impl ApplicationHandler for App {
                     terminal_manager,
                     pixel_delta,
                     self.modifiers.shift_key(),
+                    self.cursor_position.0,
                     ScreenY::new(self.cursor_position.1),
                     scrollback_lines,
                 );
@@ -428,7 +561,6 @@ impl ApplicationHandler for App {
             display,
             terminal_manager,
             output,
-            crate::window_height::calculate_window_heights,
         );
         if result.all_terminals_exited {
             compositor.running = false;
@@ -438,9 +570,9 @@ impl ApplicationHandler for App {
         if compositor.pending_copy {
             compositor.pending_copy = false;
             if let Some(ref mut clipboard) = compositor.clipboard {
-                if let Some(terminal) = terminal_manager.get_focused_mut(compositor.focused_window.as_ref()) {
+                if let Some(terminal) = terminal_manager.get_focused_mut(compositor.focused_window()) {
                     let text = if let Some(selected) = terminal.terminal.selection_text() {
-                        selected
+                        compositor.copy_options.prepare(&selected)
                     } else {
                         terminal.terminal.grid_content().join("\n")
                     };
@@ -455,7 +587,7 @@ impl ApplicationHandler for App {
             if let Some(ref mut clipboard) = compositor.clipboard {
                 match clipboard.get_text() {
                     Ok(text) => {
-                        if let Some(terminal) = terminal_manager.get_focused_mut(compositor.focused_window.as_ref()) {
+                        if let Some(terminal) = terminal_manager.get_focused_mut(compositor.focused_window()) {
                             if !terminal.has_exited() {
                                 if let Err(e) = terminal.write(text.as_bytes()) {
                                     tracing::warn!(?e, "failed to paste from clipboard");
@@ -470,8 +602,8 @@ impl ApplicationHandler for App {
             }
         }
 
-        // 6. Request redraw (throttled to avoid burning CPU)
-        if self.last_render_time.elapsed() >= MIN_FRAME_TIME {
+        // 6. Request redraw (at most once per refresh of the monitor)
+        if self.last_render_time.elapsed() >= compositor.frame_time() {
             if let Some(window) = &self.window {
                 window.request_redraw();
             }
@@ -519,13 +651,30 @@ impl App {
             0x00FFFFFF
         };
         buffer.fill(bg_color);
+        if let Some(background) = &self.background {
+            if !matches!(&self.background_pixels, Some((w, h, _)) if (*w, *h) == (width, height)) {
+                self.background_pixels = Some((width, height, background.render(width, height)));
+            }
+            if let Some((_, _, pixels)) = &self.background_pixels {
+                blit_bgra_to_surface(pixels, width, height, &mut buffer, width, height, 0, 0);
+            }
+        }
 
         // Render each visible terminal
+        self.title_bar_cache.begin_frame();
         let focused_index = compositor.focused_index();
-        let mut content_y: i32 = -(compositor.scroll_offset as i32);
+        // Each cell's column x and screen top, laid out from the cached
+        // heights (nothing is measured here)
+        let node_heights: Vec<i32> = compositor.layout_nodes.iter().map(|node| node.height).collect();
+        let frame_layout = FrameLayout::compute(compositor, &node_heights);
+        let frame_layout = compositor.commit_frame_layout(frame_layout);
+        let origins = frame_layout.origins();
+        let column_width = frame_layout.column_width() as u32;
+        let mut cell_bounds = Vec::new();
 
         for (i, node) in compositor.layout_nodes.iter().enumerate() {
             let window_height = node.height;
+            let (cell_x, content_y) = origins[i];
             if window_height <= 0 {
                 continue;
             }
@@ -534,15 +683,14 @@ impl App {
 
             // Skip if entirely off-screen
             if content_y >= height as i32 || content_y + window_height <= 0 {
-                content_y += window_height;
                 continue;
             }
+            cell_bounds.push(Rectangle::new((cell_x, content_y).into(), (column_width as i32, window_height).into()));
 
             match &node.cell {
                 StackWindow::Terminal(tid) => {
                     if let Some(terminal) = terminal_manager.get_mut(*tid) {
                         if !terminal.is_visible() {
-                            content_y += window_height;
                             continue;
                         }
 
@@ -550,22 +698,38 @@ impl App {
                         let title_bar_y = content_y;
                         let mut terminal_content_y = content_y;
 
+                        // Off-screen title bars are skipped but still take up space
+                        let title_bar_on_screen =
+                            title_bar_visible(title_bar_y, title_bar_h, height as i32);
                         if terminal.show_title_bar {
-                            if let Some(ref mut tb_renderer) = self.title_bar_renderer {
-                                let title = &terminal.title;
-                                let (tb_pixels, _tb_w, tb_h) = tb_renderer.render(title, width);
-
-                                // Blit title bar (BGRA bytes → softbuffer u32 pixels)
-                                blit_bgra_to_surface(
-                                    &tb_pixels,
-                                    width,
-                                    tb_h,
-                                    &mut buffer,
-                                    width,
-                                    height,
-                                    0,
-                                    title_bar_y,
-                                );
+                            let tb_renderer = self.title_bar_renderer.as_mut().filter(|_| title_bar_on_screen);
+                            if let Some(tb_renderer) = tb_renderer {
+                                let key = TitleBarKey {
+                                    text: compositor
+                                        .plugins
+                                        .decorate_title(node.id, terminal.title_bar_text())
+                                        .into_owned(),
+                                    width: column_width,
+                                    theme: tb_renderer.theme(),
+                                    focused: is_focused,
+                                    running: !terminal.has_exited(),
+                                    progress: terminal.terminal.progress().filter(|_| !terminal.has_exited()),
+                                };
+                                if let Some((tb_pixels, tb_h)) =
+                                    cached_title_bar(&mut self.title_bar_cache, tb_renderer, &key)
+                                {
+                                    // Blit title bar (BGRA bytes → softbuffer u32 pixels)
+                                    blit_bgra_to_surface(
+                                        tb_pixels,
+                                        column_width,
+                                        *tb_h,
+                                        &mut buffer,
+                                        width,
+                                        height,
+                                        cell_x,
+                                        title_bar_y,
+                                    );
+                                }
                             }
                             terminal_content_y += title_bar_h;
                         }
@@ -588,7 +752,7 @@ impl App {
                                 &mut buffer,
                                 width,
                                 height,
-                                0,
+                                cell_x,
                                 terminal_content_y,
                             );
                         }
@@ -599,6 +763,7 @@ impl App {
                                 &mut buffer,
                                 width,
                                 height,
+                                cell_x,
                                 content_y,
                                 window_height,
                             );
@@ -610,40 +775,78 @@ impl App {
 
                     // Render title bar for SSD windows
                     let mut window_content_y = content_y;
-                    if !entry.uses_csd {
-                        if let Some(ref mut tb_renderer) = self.title_bar_renderer {
-                            let (tb_pixels, _tb_w, tb_h) =
-                                tb_renderer.render(&entry.command, width);
-                            blit_bgra_to_surface(
-                                &tb_pixels,
-                                width,
-                                tb_h,
+                    if entry.has_title_bar() {
+                        let title_bar_on_screen =
+                            title_bar_visible(content_y, title_bar_h, height as i32);
+                        let tb_renderer = self.title_bar_renderer.as_mut().filter(|_| title_bar_on_screen);
+                        if let Some(tb_renderer) = tb_renderer {
+                            let key = TitleBarKey {
+                                text: compositor
+                                    .plugins
+                                    .decorate_title(node.id, entry.title_bar_text())
+                                    .into_owned(),
+                                width: column_width,
+                                theme: tb_renderer.theme(),
+                                focused: is_focused,
+                                running: true,
+                                progress: None,
+                            };
+                            if let Some((tb_pixels, tb_h)) =
+                                cached_title_bar(&mut self.title_bar_cache, tb_renderer, &key)
+                            {
+                                blit_bgra_to_surface(
+                                    tb_pixels,
+                                    column_width,
+                                    *tb_h,
+                                    &mut buffer,
+                                    width,
+                                    height,
+                                    cell_x,
+                                    content_y,
+                                );
+                            }
+                        }
+                        window_content_y += title_bar_h;
+                    }
+
+                    // Blit the Wayland surface tree, through a full-size
+                    // copy if the window is shown scaled down
+                    let size = entry.window.geometry().size;
+                    match entry.display_scale {
+                        Some(scale) if size.w > 0 && size.h > 0 => {
+                            let (src_width, src_height) = (size.w as u32, size.h as u32);
+                            let mut pixels = vec![bg_color; (src_width * src_height) as usize];
+                            blit_surface_tree(wl_surface, &mut pixels, src_width, src_height, 0, 0);
+                            blit_scaled(
+                                &pixels,
+                                src_width,
+                                src_height,
+                                0,
+                                scale,
                                 &mut buffer,
                                 width,
                                 height,
-                                0,
-                                content_y,
+                                cell_x,
+                                window_content_y,
                             );
                         }
-                        window_content_y += title_bar_h;
+                        _ => blit_surface_tree(
+                            wl_surface,
+                            &mut buffer,
+                            width,
+                            height,
+                            cell_x,
+                            window_content_y,
+                        ),
                     }
 
-                    // Blit the Wayland surface tree
-                    blit_surface_tree(
-                        wl_surface,
-                        &mut buffer,
-                        width,
-                        height,
-                        0,
-                        window_content_y,
-                    );
-
                     // Draw focus indicator
                     if is_focused {
                         draw_focus_indicator(
                             &mut buffer,
                             width,
                             height,
+                            cell_x,
                             content_y,
                             window_height,
                         );
@@ -651,20 +854,23 @@ impl App {
                 }
             }
 
-            content_y += window_height;
+            if let Some(tag) = node.tag {
+                draw_tag_stripe(&mut buffer, width, height, cell_x + column_width as i32, content_y, window_height, tag);
+            }
+        }
+
+        if self.cell_style.is_enabled() {
+            self.cell_style.paint(&mut buffer, width, height, &cell_bounds, bg_color);
         }
 
         // Render popups on top of all windows
         {
             use smithay::desktop::{PopupKind, PopupManager};
-            let mut popup_content_y: i32 = -(compositor.scroll_offset as i32);
-
-            for node in compositor.layout_nodes.iter() {
-                let window_height = node.height;
+            for (node, &(cell_x, popup_content_y)) in compositor.layout_nodes.iter().zip(origins) {
                 if let StackWindow::External(entry) = &node.cell {
                     let wl_surface = entry.surface.wl_surface();
                     let parent_window_geo = entry.window.geometry();
-                    let title_bar_offset = if entry.uses_csd { 0 } else { title_bar_h };
+                    let title_bar_offset = if entry.has_title_bar() { title_bar_h } else { 0 };
                     let client_area_y = popup_content_y + title_bar_offset;
 
                     for (popup_kind, popup_offset) in PopupManager::popups_for_surface(wl_surface) {
@@ -687,8 +893,7 @@ impl App {
                         };
 
                         // In screen coords (Y=0 at top), popup is below parent's top
-                        let popup_x =
-                            popup_position.x - parent_window_geo.loc.x - popup_window_geo.loc.x;
+                        let popup_x = cell_x + popup_position.x - parent_window_geo.loc.x - popup_window_geo.loc.x;
                         let popup_y = client_area_y + popup_position.y
                             - parent_window_geo.loc.y
                             - popup_window_geo.loc.y;
@@ -703,19 +908,71 @@ impl App {
                         );
                     }
                 }
-                popup_content_y += window_height;
             }
         }
 
+        // Quick terminal slides over the cells and popups
+        if let Some((id, progress)) = compositor.quick_terminal.visible() {
+            if let Some(terminal) = terminal_manager.get_mut(id) {
+                draw_quick_terminal(terminal, progress, bg_color, &mut buffer, width, height);
+            }
+        }
+
+        // Window switcher over everything
+        if compositor.window_switcher.is_open() {
+            let lines = compositor.window_switcher.lines(compositor, terminal_manager);
+            if !matches!(&self.switcher_panel, Some((cached, ..)) if *cached == lines) {
+                self.switcher_panel = self.title_bar_renderer.as_mut().map(|tb_renderer| {
+                    let (pixels, panel_width, panel_height) = tb_renderer.render_panel(&lines);
+                    (lines, pixels, panel_width, panel_height)
+                });
+            }
+            if let Some((_, pixels, panel_width, panel_height)) = &self.switcher_panel {
+                draw_switcher_thumbnails(compositor, terminal_manager, &mut buffer, width, height, *panel_height as i32);
+                blit_bgra_to_surface(
+                    pixels,
+                    *panel_width,
+                    *panel_height,
+                    &mut buffer,
+                    width,
+                    height,
+                    ((width as i32 - *panel_width as i32) / 2).max(0),
+                    ((height as i32 - *panel_height as i32) / 2).max(0),
+                );
+            }
+        } else {
+            self.switcher_panel = None;
+        }
+
         // Present the frame
         if let Err(e) = buffer.present() {
             tracing::warn!(error = ?e, "failed to present softbuffer frame");
+        } else if let Some(output) = &self.output {
+            crate::presentation::frame_submitted(compositor, output);
         }
 
         self.last_render_time = Instant::now();
     }
 }
 
+/// Look up a title bar, rendering it on a cache miss
+///
+/// Returns the BGRA pixels and the height of the title bar.
+fn cached_title_bar<'a>(
+    cache: &'a mut TitleBarCache<(Vec<u8>, u32)>,
+    renderer: &mut TitleBarRenderer,
+    key: &TitleBarKey,
+) -> Option<&'a (Vec<u8>, u32)> {
+    cache.ensure(key, || {
+        let (mut pixels, width, height) = renderer.render(&key.text, key.width);
+        if let Some(progress) = key.progress {
+            renderer.draw_progress(&mut pixels, width, progress);
+        }
+        Some((pixels, height))
+    });
+    cache.get(key)
+}
+
 /// Blit BGRA byte buffer onto softbuffer surface at given position
 #[allow(clippy::too_many_arguments)]
 fn blit_bgra_to_surface(
@@ -796,25 +1053,199 @@ fn draw_focus_indicator(
     buffer: &mut [u32],
     buf_width: u32,
     buf_height: u32,
+    x: i32,
     y: i32,
     height: i32,
 ) {
-    let indicator_width = crate::layout::FOCUS_INDICATOR_WIDTH;
     let green = 0x0000CC00; // Green, no alpha
+    fill_strip(buffer, buf_width, buf_height, x, y, crate::layout::FOCUS_INDICATOR_WIDTH, height, green);
+}
+
+/// Draw a color tag's stripe along the right edge of a cell ending at `right`
+fn draw_tag_stripe(
+    buffer: &mut [u32],
+    buf_width: u32,
+    buf_height: u32,
+    right: i32,
+    y: i32,
+    height: i32,
+    tag: crate::color_tags::ColorTag,
+) {
+    let [r, g, b, _] = tag.color().components();
+    let color = ((r * 255.0) as u32) << 16 | ((g * 255.0) as u32) << 8 | (b * 255.0) as u32;
+    let stripe_width = crate::color_tags::TAG_STRIPE_WIDTH;
+    fill_strip(buffer, buf_width, buf_height, right - stripe_width, y, stripe_width, height, color);
+}
 
+/// Fill a `strip_width` x `height` rectangle at (`x`, `y`), clipped to the buffer
+#[allow(clippy::too_many_arguments)]
+fn fill_strip(
+    buffer: &mut [u32],
+    buf_width: u32,
+    buf_height: u32,
+    x: i32,
+    y: i32,
+    strip_width: i32,
+    height: i32,
+    color: u32,
+) {
     for row in 0..height {
         let screen_y = y + row;
         if screen_y < 0 || screen_y >= buf_height as i32 {
             continue;
         }
-        for col in 0..indicator_width {
+        for col in x.max(0)..x + strip_width {
             if col >= buf_width as i32 {
                 break;
             }
             let idx = screen_y as usize * buf_width as usize + col as usize;
             if idx < buffer.len() {
-                buffer[idx] = green;
+                buffer[idx] = color;
+            }
+        }
+    }
+}
+
+/// Draw the quick terminal sliding down from the top edge, `progress` of
+/// the way in
+fn draw_quick_terminal(
+    terminal: &mut crate::terminal_manager::ManagedTerminal,
+    progress: f32,
+    background: u32,
+    buffer: &mut [u32],
+    buf_width: u32,
+    buf_height: u32,
+) {
+    use crate::quick_terminal::BORDER_HEIGHT;
+
+    let content_height = terminal.height as i32;
+    let total = content_height + BORDER_HEIGHT;
+    let top = (total as f32 * progress).round() as i32 - total;
+    fill_strip(buffer, buf_width, buf_height, 0, top, buf_width as i32, content_height, background);
+    fill_strip(buffer, buf_width, buf_height, 0, top + content_height, buf_width as i32, BORDER_HEIGHT, 0x0000CC00);
+
+    terminal.terminal.render(terminal.width, terminal.height, !terminal.has_exited());
+    blit_argb_to_surface(
+        terminal.terminal.buffer(),
+        terminal.width,
+        terminal.height,
+        buffer,
+        buf_width,
+        buf_height,
+        0,
+        top,
+    );
+}
+
+/// Draw the window switcher's thumbnails in a row above its panel, scaled
+/// down from the terminals' last rendered buffers and the windows' surfaces
+fn draw_switcher_thumbnails(
+    compositor: &TermStack,
+    terminal_manager: &TerminalManager,
+    buffer: &mut [u32],
+    buf_width: u32,
+    buf_height: u32,
+    panel_height: i32,
+) {
+    use crate::window_switcher::{thumbnail_boxes, thumbnail_rows, THUMBNAIL_BORDER, THUMBNAIL_SIZE};
+
+    let (box_width, box_height) = THUMBNAIL_SIZE;
+    let cells = compositor.window_switcher.thumbnail_cells(buf_width as i32);
+    let boxes = thumbnail_boxes(cells.len(), (buf_width as i32, buf_height as i32), panel_height);
+    for ((cell, selected), (x, y)) in cells.into_iter().zip(boxes) {
+        let Some(node) = compositor.cell_index(cell).and_then(|index| compositor.layout_nodes.get(index)) else {
+            continue;
+        };
+        if selected {
+            let (frame_width, frame_height) = (box_width + 2 * THUMBNAIL_BORDER, box_height + 2 * THUMBNAIL_BORDER);
+            let (frame_x, frame_y) = (x - THUMBNAIL_BORDER, y - THUMBNAIL_BORDER);
+            fill_strip(buffer, buf_width, buf_height, frame_x, frame_y, frame_width, frame_height, 0x0000CC00);
+        }
+        fill_strip(buffer, buf_width, buf_height, x, y, box_width, box_height, 0x001A1A1A);
+
+        match &node.cell {
+            StackWindow::Terminal(id) => {
+                let Some(terminal) = terminal_manager.get(*id) else { continue };
+                let (src_width, src_height) = terminal.terminal.buffer_dimensions();
+                let rows = thumbnail_rows(src_width as i32, src_height as i32);
+                let src = terminal.terminal.buffer();
+                blit_thumbnail(src, src_width, src_height, rows, buffer, buf_width, buf_height, x, y);
+            }
+            StackWindow::External(entry) => {
+                let size = entry.window.geometry().size;
+                if size.w <= 0 || size.h <= 0 {
+                    continue;
+                }
+                let (src_width, src_height) = (size.w as u32, size.h as u32);
+                let mut pixels = vec![0; (src_width * src_height) as usize];
+                blit_surface_tree(entry.surface.wl_surface(), &mut pixels, src_width, src_height, 0, 0);
+                blit_thumbnail(&pixels, src_width, src_height, size.h, buffer, buf_width, buf_height, x, y);
+            }
+        }
+    }
+}
+
+/// Blit the bottom `rows` rows of an ARGB u32 buffer into the thumbnail box
+/// at (`x`, `y`), scaled down to fit (nearest neighbour), centered and
+/// top-aligned
+#[allow(clippy::too_many_arguments)]
+fn blit_thumbnail(
+    src: &[u32],
+    src_width: u32,
+    src_height: u32,
+    rows: i32,
+    dst: &mut [u32],
+    dst_width: u32,
+    dst_height: u32,
+    x: i32,
+    y: i32,
+) {
+    // Never rendered yet
+    if src_width == 0 || rows <= 0 || src.len() < (src_width * src_height) as usize {
+        return;
+    }
+    let scale = crate::window_switcher::thumbnail_scale(src_width as i32, rows);
+    let width = (src_width as f64 * scale) as i32;
+    let left = x + (crate::window_switcher::THUMBNAIL_SIZE.0 - width) / 2;
+    let first_row = src_height - rows as u32;
+    blit_scaled(src, src_width, src_height, first_row, scale, dst, dst_width, dst_height, left, y);
+}
+
+/// Blit an ARGB u32 buffer from row `first_row` on, scaled by `scale`
+/// (nearest neighbour), with its top-left corner at (`x`, `y`)
+#[allow(clippy::too_many_arguments)]
+fn blit_scaled(
+    src: &[u32],
+    src_width: u32,
+    src_height: u32,
+    first_row: u32,
+    scale: f64,
+    dst: &mut [u32],
+    dst_width: u32,
+    dst_height: u32,
+    x: i32,
+    y: i32,
+) {
+    if src_width == 0 || first_row >= src_height || src.len() < (src_width * src_height) as usize {
+        return;
+    }
+    let width = (src_width as f64 * scale) as i32;
+    let height = ((src_height - first_row) as f64 * scale) as i32;
+
+    for row in 0..height {
+        let screen_y = y + row;
+        if screen_y < 0 || screen_y >= dst_height as i32 {
+            continue;
+        }
+        let src_row = (first_row + (row as f64 / scale) as u32).min(src_height - 1);
+        for col in 0..width {
+            let screen_x = x + col;
+            if screen_x < 0 || screen_x >= dst_width as i32 {
+                continue;
             }
+            let src_col = ((col as f64 / scale) as u32).min(src_width - 1);
+            let pixel = src[(src_row * src_width + src_col) as usize];
+            dst[screen_y as usize * dst_width as usize + screen_x as usize] = pixel & 0x00FFFFFF;
         }
     }
 }
@@ -921,6 +1352,49 @@ fn blit_surface_tree(
     );
 }
 
+/// Open the window switcher on Alt+Tab / Super+Tab and take all key presses
+/// while it is open (letting go of the modifier is seen in
+/// `ModifiersChanged`). Returns true if the key was taken.
+fn window_switcher_key(
+    compositor: &mut TermStack,
+    modifiers: &ModifiersState,
+    key: &Key,
+    physical_key: PhysicalKey,
+) -> bool {
+    let shift = modifiers.shift_key();
+    if !compositor.window_switcher.is_open() {
+        let held = (modifiers.alt_key() || modifiers.super_key()) && !modifiers.control_key();
+        if !(held && *key == Key::Named(NamedKey::Tab)) {
+            return false;
+        }
+        compositor.open_window_switcher(if shift { -1 } else { 1 });
+        return true;
+    }
+
+    // Tags by physical key: Alt+digit types other characters on macOS
+    let digit = match physical_key {
+        PhysicalKey::Code(KeyCode::Digit0) => Some(0),
+        PhysicalKey::Code(KeyCode::Digit1) => Some(1),
+        PhysicalKey::Code(KeyCode::Digit2) => Some(2),
+        PhysicalKey::Code(KeyCode::Digit3) => Some(3),
+        PhysicalKey::Code(KeyCode::Digit4) => Some(4),
+        PhysicalKey::Code(KeyCode::Digit5) => Some(5),
+        PhysicalKey::Code(KeyCode::Digit6) => Some(6),
+        _ => None,
+    };
+    match (key, digit) {
+        (_, Some(0)) => compositor.filter_window_switcher(None),
+        (_, Some(n)) => compositor.filter_window_switcher(Some(crate::color_tags::ColorTag::ALL[n - 1])),
+        (Key::Named(NamedKey::Tab), _) if shift => compositor.step_window_switcher(-1),
+        (Key::Named(NamedKey::Tab | NamedKey::ArrowDown), _) => compositor.step_window_switcher(1),
+        (Key::Named(NamedKey::ArrowUp), _) => compositor.step_window_switcher(-1),
+        (Key::Named(NamedKey::Enter), _) => compositor.close_window_switcher(true),
+        (Key::Named(NamedKey::Escape), _) => compositor.close_window_switcher(false),
+        _ => {}
+    }
+    true
+}
+
 /// Parse compositor keybindings from winit modifiers and key.
 ///
 /// Maps Cmd+C/V, Ctrl+Shift+... to `CompositorAction`.
@@ -955,9 +1429,31 @@ fn parse_winit_keybinding(
                 "k" | "K" => return Some(CompositorAction::FocusPrev),
                 "+" | "=" => return Some(CompositorAction::FontSizeUp),
                 "-" | "_" => return Some(CompositorAction::FontSizeDown),
+                "d" | "D" => return Some(CompositorAction::ToggleDecorations),
+                "n" | "N" => return Some(CompositorAction::ToggleDoNotDisturb),
+                "r" | "R" => return Some(CompositorAction::ResizeMode),
+                "o" | "O" => return Some(CompositorAction::FocusBack),
+                "a" | "A" => return Some(CompositorAction::Absorb),
+                "z" | "Z" => return Some(CompositorAction::Undo),
+                "h" | "H" => return Some(CompositorAction::FocusColumnLeft),
+                "l" | "L" => return Some(CompositorAction::FocusColumnRight),
+                "g" | "G" => return Some(CompositorAction::CycleColorTag),
+                "e" | "E" => return Some(CompositorAction::ToggleSmartView),
+                "`" | "~" => return Some(CompositorAction::ToggleQuickTerminal),
                 _ => {}
             },
             Key::Named(NamedKey::Enter) => return Some(CompositorAction::SpawnTerminal),
+            Key::Named(NamedKey::ArrowLeft) => return Some(CompositorAction::FocusParent),
+            Key::Named(NamedKey::ArrowRight) => return Some(CompositorAction::FocusNextChild),
+            _ => {}
+        }
+    }
+
+    // Scrollback paging in the focused terminal (Shift+Page Up/Down)
+    if shift && !ctrl && !super_key {
+        match key {
+            Key::Named(NamedKey::PageUp) => return Some(CompositorAction::ScrollbackPageUp),
+            Key::Named(NamedKey::PageDown) => return Some(CompositorAction::ScrollbackPageDown),
             _ => {}
         }
     }
@@ -1,25 +1,28 @@
 //! XWayland lifecycle management
 //!
 //! Handles XWayland initialization, xwayland-satellite spawning, health monitoring,
-//! and automatic crash recovery with exponential backoff.
+//! and automatic crash recovery with exponential backoff. The states these move
+//! through live in `xwayland_supervisor`.
 
-use std::time::{Duration, Instant};
+use std::time::Instant;
 use smithay::reexports::calloop::LoopHandle;
 use smithay::reexports::wayland_server::Display;
-use crate::state::{TermStack, XWaylandSatelliteMonitor};
+use crate::state::TermStack;
+use crate::xwayland_supervisor::{XWaylandSatelliteMonitor, XWaylandState};
 
 /// Initialize XWayland support with xwayland-satellite for running X11 applications
 ///
 /// xwayland-satellite acts as the X11 window manager and presents X11 windows as
 /// normal Wayland toplevels to the compositor.
 pub fn initialize_xwayland(
-    _compositor: &mut TermStack,
+    compositor: &mut TermStack,
     display: &mut Display<TermStack>,
     loop_handle: LoopHandle<'static, TermStack>,
 ) {
     // Spawn XWayland without a window manager (xwayland-satellite will be the WM)
     use smithay::xwayland::{XWayland, XWaylandEvent};
 
+    compositor.xwayland.starting();
     let (xwayland, _client) = match XWayland::spawn(
         &display.handle(),
         None, // Let XWayland pick display number
@@ -32,19 +35,21 @@ pub fn initialize_xwayland(
         Ok(result) => result,
         Err(e) => {
             tracing::warn!(?e, "Failed to spawn XWayland - X11 apps will not work");
+            compositor.xwayland.unavailable(format!("failed to spawn XWayland: {e}"));
             return;
         }
     };
 
     // Insert XWayland event source to handle Ready/Error events
     if let Err(e) = loop_handle.insert_source(xwayland, move |event, _, compositor| {
+        compositor.redraw_requested = true;
         match event {
             XWaylandEvent::Ready { display_number, .. } => {
                 tracing::info!(display_number, "XWayland ready, spawning xwayland-satellite");
 
                 // Set DISPLAY for child processes
                 std::env::set_var("DISPLAY", format!(":{}", display_number));
-                compositor.x11_display_number = Some(display_number);
+                compositor.xwayland.display_ready(display_number);
 
                 // Set up X authorization for GTK apps.
                 // XWayland accepts unauthenticated local connections (no -auth flag),
@@ -54,9 +59,9 @@ pub fn initialize_xwayland(
 
                 // Spawn xwayland-satellite (acts as X11 WM, presents windows as Wayland toplevels)
                 match spawn_xwayland_satellite(display_number) {
-                    Ok(child) => {
+                    Ok(monitor) => {
                         tracing::info!("xwayland-satellite launched successfully");
-                        compositor.xwayland_satellite = Some(child);
+                        compositor.xwayland.satellite_started(monitor);
                     }
                     Err(e) => {
                         // Soft dependency: warn but continue
@@ -71,6 +76,7 @@ pub fn initialize_xwayland(
                             ?e,
                             "Failed to spawn xwayland-satellite - continuing in Wayland-only mode"
                         );
+                        compositor.xwayland.unavailable(format!("xwayland-satellite not found: {e}"));
                     }
                 }
 
@@ -79,6 +85,7 @@ pub fn initialize_xwayland(
             }
             XWaylandEvent::Error => {
                 tracing::error!("XWayland failed");
+                compositor.xwayland.unavailable("XWayland failed to start");
             }
         }
     }) {
@@ -90,75 +97,44 @@ pub fn initialize_xwayland(
 ///
 /// Returns true if the compositor should continue running, false if shutdown is requested.
 pub fn monitor_xwayland_satellite_health(compositor: &mut TermStack) -> bool {
-    if let Some(mut monitor) = compositor.xwayland_satellite.take() {
-        match monitor.child.try_wait() {
-            Ok(Some(status)) => {
-                // xwayland-satellite crashed! Try to read stderr to see why
-                let stderr_output = if let Some(ref mut stderr) = monitor.child.stderr {
-                    use std::io::Read;
-                    let mut buf = String::new();
-                    stderr.read_to_string(&mut buf).ok();
-                    buf
-                } else {
-                    String::new()
-                };
-
-                if !stderr_output.is_empty() {
-                    tracing::error!(?status, stderr = %stderr_output, "xwayland-satellite crashed");
-                } else {
-                    tracing::warn!(?status, "xwayland-satellite exited");
-                }
-
-                // Determine if this is a rapid crash (within 10s of last crash)
-                let now = Instant::now();
-                let time_since_last = monitor.last_crash_time.map(|t| now.duration_since(t));
-                let is_rapid_crash = time_since_last.is_some_and(|d| d < Duration::from_secs(10));
-
-                if is_rapid_crash {
-                    monitor.crash_count += 1;
-                } else {
-                    // Ran for a while before crashing, reset counter
-                    monitor.crash_count = 1;
-                }
+    let now = Instant::now();
+
+    // Exit status is collected by the SIGCHLD reaper (see child_reaper)
+    let exited = compositor.xwayland.satellite.as_ref().and_then(|m| m.exit_status);
+    if let Some((status, mut monitor)) = exited.zip(compositor.xwayland.satellite.take()) {
+        // xwayland-satellite crashed! Try to read stderr to see why
+        let stderr_output = if let Some(ref mut stderr) = monitor.child.stderr {
+            use std::io::Read;
+            let mut buf = String::new();
+            stderr.read_to_string(&mut buf).ok();
+            buf
+        } else {
+            String::new()
+        };
+
+        let exit = if !stderr_output.is_empty() {
+            tracing::error!(?status, stderr = %stderr_output, "xwayland-satellite crashed");
+            format!("{status}: {}", stderr_output.trim())
+        } else {
+            tracing::warn!(?status, "xwayland-satellite exited");
+            status.to_string()
+        };
+        compositor.xwayland.satellite_exited(exit, now);
+        if compositor.xwayland.state() == XWaylandState::Crashed {
+            tracing::warn!("X11 apps will not work for the rest of this session");
+        }
+    }
 
-                monitor.last_crash_time = Some(now);
-
-                if monitor.crash_count <= 3 {
-                    tracing::warn!(
-                        attempt = monitor.crash_count,
-                        "xwayland-satellite crashed, restarting (attempt {}/3)",
-                        monitor.crash_count
-                    );
-
-                    // Attempt restart
-                    match spawn_xwayland_satellite(compositor.x11_display_number.unwrap()) {
-                        Ok(mut new_monitor) => {
-                            // Preserve crash tracking from old monitor
-                            new_monitor.crash_count = monitor.crash_count;
-                            new_monitor.last_crash_time = monitor.last_crash_time;
-                            compositor.xwayland_satellite = Some(new_monitor);
-                        }
-                        Err(e) => {
-                            tracing::error!(?e, "Failed to restart xwayland-satellite");
-                        }
-                    }
-                } else {
-                    tracing::error!(
-                        "xwayland-satellite crashed {} times in rapid succession, giving up",
-                        monitor.crash_count
-                    );
-                    tracing::warn!("X11 apps will not work for the rest of this session");
-                    // Don't put monitor back - X11 support disabled for session
-                }
-            }
-            Ok(None) => {
-                // Still running, put monitor back
-                compositor.xwayland_satellite = Some(monitor);
+    if let Some(display_number) = compositor.xwayland.restart_due(now) {
+        match spawn_xwayland_satellite(display_number) {
+            Ok(monitor) => {
+                tracing::info!("xwayland-satellite restarted");
+                compositor.xwayland.satellite_started(monitor);
+                compositor.redraw_requested = true;
             }
             Err(e) => {
-                tracing::debug!(?e, "Error checking xwayland-satellite status");
-                // Put monitor back even on error
-                compositor.xwayland_satellite = Some(monitor);
+                tracing::error!(?e, "Failed to restart xwayland-satellite");
+                compositor.xwayland.unavailable(format!("failed to restart xwayland-satellite: {e}"));
             }
         }
     }
@@ -218,11 +194,7 @@ fn spawn_xwayland_satellite(display_number: u32) -> std::io::Result<XWaylandSate
         .stderr(std::process::Stdio::piped()) // Capture stderr for logging
         .spawn()?;
 
-    Ok(XWaylandSatelliteMonitor {
-        child,
-        last_crash_time: None,
-        crash_count: 0,
-    })
+    Ok(XWaylandSatelliteMonitor { child, exit_status: None })
 }
 
 /// Find xwayland-satellite binary in common installation locations
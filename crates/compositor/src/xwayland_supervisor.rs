@@ -0,0 +1,308 @@
+//! Supervision of XWayland and xwayland-satellite
+//!
+//! X11 apps reach termstack through XWayland, with xwayland-satellite as
+//! their window manager presenting X11 windows as Wayland toplevels. The
+//! supervisor follows that pair through explicit states:
+//!
+//! - `starting`: XWayland is launching, or xwayland-satellite is about to be
+//! - `ready`: `DISPLAY` is set and xwayland-satellite is running
+//! - `backoff`: xwayland-satellite exited and is restarted after a delay that
+//!   doubles with each crash in quick succession
+//! - `crashed`: it kept crashing; X11 apps won't work for the rest of the session
+//! - `unavailable`: XWayland or xwayland-satellite could not be started
+//!
+//! `termstack gui` launches made while X11 is still coming up (`starting` or
+//! `backoff`) wait for `ready` instead of starting without a display, for at
+//! most `SPAWN_WAIT`; so do commands that have no other display. Shells
+//! started before that pick up `DISPLAY` through the `env` IPC query (see
+//! `EnvUpdate`). Plugins get an `xwayland_ready` event each time `DISPLAY`
+//! becomes usable, and `termstack doctor` reports the state over IPC.
+
+use std::collections::BTreeMap;
+use std::process::{Child, ExitStatus};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Crashes this close together count as a crash loop
+pub const RAPID_CRASH_WINDOW: Duration = Duration::from_secs(10);
+
+/// Restarts in a crash loop before giving up for the session
+pub const MAX_RAPID_RESTARTS: u32 = 3;
+
+/// Delay before the first restart; doubles with each rapid crash
+pub const BACKOFF_BASE: Duration = Duration::from_secs(1);
+
+/// How long X11 app launches wait for X11 to come up
+pub const SPAWN_WAIT: Duration = Duration::from_secs(15);
+
+/// Where X11 support is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum XWaylandState {
+    Starting,
+    Ready,
+    Backoff,
+    Crashed,
+    Unavailable,
+}
+
+/// X11 support as reported over IPC (answer to `xwayland`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct XWaylandStatus {
+    pub state: XWaylandState,
+    /// `DISPLAY` for X11 apps, once XWayland is up
+    pub display: Option<String>,
+    /// Restarts of xwayland-satellite this session
+    pub restarts: u32,
+    /// How xwayland-satellite last exited, with its error output
+    pub last_exit: Option<String>,
+    /// Milliseconds until the next restart while backing off
+    pub retry_in_ms: Option<u64>,
+    /// Why X11 support is unavailable
+    pub detail: Option<String>,
+}
+
+/// Variables for a shell started before X11 was up (answer to `env`)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvUpdate {
+    /// Variables to set; empty until X11 apps can start
+    pub set: BTreeMap<String, String>,
+    /// Whether asking again later may give more
+    pub pending: bool,
+}
+
+/// The running xwayland-satellite process
+pub struct XWaylandSatelliteMonitor {
+    /// The xwayland-satellite process handle
+    pub child: Child,
+    /// Exit status, set by the child reaper once the process has exited
+    pub exit_status: Option<ExitStatus>,
+}
+
+/// XWayland and xwayland-satellite state, kept on `TermStack`
+pub struct XWaylandSupervisor {
+    state: XWaylandState,
+    /// X11 display number (e.g., 0 for :0)
+    display_number: Option<u32>,
+    pub satellite: Option<XWaylandSatelliteMonitor>,
+    /// Crashes in the current crash loop
+    crash_count: u32,
+    last_crash_time: Option<Instant>,
+    restarts: u32,
+    last_exit: Option<String>,
+    retry_at: Option<Instant>,
+    detail: Option<String>,
+    /// When the first X11 launch started waiting for `ready`
+    deferring_since: Option<Instant>,
+    /// `DISPLAY` that became usable since plugins were last told
+    ready_event: Option<String>,
+}
+
+impl Default for XWaylandSupervisor {
+    fn default() -> Self {
+        Self {
+            state: XWaylandState::Unavailable,
+            display_number: None,
+            satellite: None,
+            crash_count: 0,
+            last_crash_time: None,
+            restarts: 0,
+            last_exit: None,
+            retry_at: None,
+            detail: Some("XWayland not started".to_string()),
+            deferring_since: None,
+            ready_event: None,
+        }
+    }
+}
+
+impl XWaylandSupervisor {
+    pub fn state(&self) -> XWaylandState {
+        self.state
+    }
+
+    pub fn display_number(&self) -> Option<u32> {
+        self.display_number
+    }
+
+    /// PID of xwayland-satellite, which owns every X11 window
+    pub fn satellite_pid(&self) -> Option<i32> {
+        self.satellite.as_ref().map(|monitor| monitor.child.id() as i32)
+    }
+
+    /// XWayland is being launched
+    pub fn starting(&mut self) {
+        self.state = XWaylandState::Starting;
+        self.detail = None;
+    }
+
+    /// XWayland is up on `display_number`; xwayland-satellite starts next
+    pub fn display_ready(&mut self, display_number: u32) {
+        self.display_number = Some(display_number);
+    }
+
+    /// xwayland-satellite is running: X11 apps can start
+    pub fn satellite_started(&mut self, monitor: XWaylandSatelliteMonitor) {
+        self.satellite = Some(monitor);
+        self.ready();
+    }
+
+    fn ready(&mut self) {
+        self.state = XWaylandState::Ready;
+        self.retry_at = None;
+        self.deferring_since = None;
+        if let Some(x11_display) = self.display() {
+            tracing::info!(display = %x11_display, "X11 apps can start");
+            self.ready_event = Some(x11_display);
+        }
+    }
+
+    /// X11 support can't be brought up
+    pub fn unavailable(&mut self, detail: impl Into<String>) {
+        self.state = XWaylandState::Unavailable;
+        self.detail = Some(detail.into());
+        self.retry_at = None;
+    }
+
+    /// xwayland-satellite exited: back off before restarting, or give up
+    /// after `MAX_RAPID_RESTARTS` crashes in quick succession
+    pub fn satellite_exited(&mut self, exit: String, now: Instant) {
+        self.satellite = None;
+        self.last_exit = Some(exit);
+
+        let rapid = self.last_crash_time.is_some_and(|t| now.duration_since(t) < RAPID_CRASH_WINDOW);
+        // Ran for a while before crashing: a fresh crash loop
+        self.crash_count = if rapid { self.crash_count + 1 } else { 1 };
+        self.last_crash_time = Some(now);
+
+        if self.crash_count > MAX_RAPID_RESTARTS {
+            tracing::error!(crashes = self.crash_count, "xwayland-satellite keeps crashing, giving up");
+            self.state = XWaylandState::Crashed;
+            self.retry_at = None;
+            return;
+        }
+        let delay = BACKOFF_BASE * 2u32.pow(self.crash_count - 1);
+        tracing::warn!(
+            attempt = self.crash_count,
+            delay_ms = delay.as_millis() as u64,
+            "restarting xwayland-satellite after backoff"
+        );
+        self.state = XWaylandState::Backoff;
+        self.retry_at = Some(now + delay);
+    }
+
+    /// Display to restart xwayland-satellite on, once the backoff is over
+    pub fn restart_due(&mut self, now: Instant) -> Option<u32> {
+        if self.state != XWaylandState::Backoff || self.retry_at.is_some_and(|at| now < at) {
+            return None;
+        }
+        self.restarts += 1;
+        self.display_number
+    }
+
+    /// When the next restart is due, for the event loop's timeout
+    pub fn restart_deadline(&self) -> Option<Instant> {
+        self.retry_at.filter(|_| self.state == XWaylandState::Backoff)
+    }
+
+    /// Whether an X11 app launch should wait for X11 to come up
+    pub fn defer_x11_spawn(&mut self, now: Instant) -> bool {
+        if !matches!(self.state, XWaylandState::Starting | XWaylandState::Backoff) {
+            self.deferring_since = None;
+            return false;
+        }
+        let since = *self.deferring_since.get_or_insert(now);
+        if now.duration_since(since) < SPAWN_WAIT {
+            return true;
+        }
+        tracing::warn!(state = ?self.state, "X11 still not ready, launching anyway");
+        false
+    }
+
+    /// `DISPLAY` that became usable since the last call
+    pub fn take_ready_event(&mut self) -> Option<String> {
+        self.ready_event.take()
+    }
+
+    /// What X11 apps need in their environment, once they can start
+    pub fn env_update(&self) -> EnvUpdate {
+        let pending = matches!(self.state, XWaylandState::Starting | XWaylandState::Backoff);
+        let mut set = BTreeMap::new();
+        if let Some(x11_display) = self.display().filter(|_| self.state == XWaylandState::Ready) {
+            set.insert("DISPLAY".to_string(), x11_display);
+            // Written by setup_xauthority once XWayland is up
+            if let Ok(xauthority) = std::env::var("XAUTHORITY") {
+                set.insert("XAUTHORITY".to_string(), xauthority);
+            }
+        }
+        EnvUpdate { set, pending }
+    }
+
+    fn display(&self) -> Option<String> {
+        self.display_number.map(|number| format!(":{number}"))
+    }
+
+    pub fn status(&self, now: Instant) -> XWaylandStatus {
+        XWaylandStatus {
+            state: self.state,
+            display: self.display(),
+            restarts: self.restarts,
+            last_exit: self.last_exit.clone(),
+            retry_in_ms: self
+                .restart_deadline()
+                .map(|at| at.saturating_duration_since(now).as_millis() as u64),
+            detail: self.detail.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crashes_back_off_and_then_give_up() {
+        let now = Instant::now();
+        let mut xwayland = XWaylandSupervisor::default();
+        xwayland.starting();
+        xwayland.display_ready(1);
+        xwayland.ready();
+        assert_eq!(xwayland.take_ready_event().as_deref(), Some(":1"));
+        let env = xwayland.env_update();
+        assert_eq!(env.set.get("DISPLAY").map(String::as_str), Some(":1"));
+        assert!(!env.pending);
+
+        xwayland.satellite_exited("signal 11".to_string(), now);
+        assert_eq!(xwayland.state(), XWaylandState::Backoff);
+        assert_eq!(xwayland.restart_due(now), None);
+        assert_eq!(xwayland.restart_due(now + BACKOFF_BASE), Some(1));
+
+        // Each rapid crash doubles the delay
+        xwayland.satellite_exited("signal 11".to_string(), now + Duration::from_secs(2));
+        assert_eq!(xwayland.status(now + Duration::from_secs(2)).retry_in_ms, Some(2000));
+        xwayland.satellite_exited("signal 11".to_string(), now + Duration::from_secs(5));
+        xwayland.satellite_exited("signal 11".to_string(), now + Duration::from_secs(9));
+        assert_eq!(xwayland.state(), XWaylandState::Crashed);
+        assert_eq!(xwayland.restart_due(now + Duration::from_secs(60)), None);
+    }
+
+    #[test]
+    fn x11_launches_wait_while_starting() {
+        let now = Instant::now();
+        let mut xwayland = XWaylandSupervisor::default();
+        assert!(!xwayland.defer_x11_spawn(now));
+
+        xwayland.starting();
+        assert!(xwayland.env_update().pending);
+        assert!(xwayland.defer_x11_spawn(now));
+        assert!(xwayland.defer_x11_spawn(now + Duration::from_secs(1)));
+        // Not forever
+        assert!(!xwayland.defer_x11_spawn(now + SPAWN_WAIT));
+
+        xwayland.unavailable("xwayland-satellite not found");
+        assert!(!xwayland.defer_x11_spawn(now));
+        assert_eq!(xwayland.env_update(), EnvUpdate::default());
+        assert_eq!(xwayland.status(now).state, XWaylandState::Unavailable);
+    }
+}
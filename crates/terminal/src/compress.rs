@@ -0,0 +1,72 @@
+//! Compressed scrollback for idle terminals
+//!
+//! Every terminal keeps a 1000-row grid plus its history, however little it
+//! printed, which adds up with hundreds of finished command cells. An idle
+//! terminal can swap its grid for a zstd-compressed ANSI dump
+//! ([`Terminal::compress_scrollback`](crate::Terminal::compress_scrollback));
+//! the grid is rebuilt from the dump the next time anything reads it.
+//!
+//! Text, colors, attributes and the cursor position survive the round trip.
+//! Terminal modes and the selection don't, so only the primary screen of a
+//! terminal whose program has exited should be compressed.
+
+use alacritty_terminal::event::EventListener;
+use alacritty_terminal::grid::Dimensions;
+use alacritty_terminal::index::Point;
+use alacritty_terminal::term::{Config as TermConfig, Term};
+use alacritty_terminal::vte::ansi;
+
+use crate::render::TerminalRenderer;
+use crate::state::Size;
+
+/// zstd level: terminal output compresses well even at the fast levels
+const LEVEL: i32 = 3;
+
+/// A grid as a compressed ANSI dump
+pub struct CompressedGrid {
+    /// Every line from the top of the history to the last screen line,
+    /// `\r\n` separated
+    data: Vec<u8>,
+    columns: usize,
+    screen_lines: usize,
+    cursor: Point,
+}
+
+impl CompressedGrid {
+    /// Dump and compress the grid of `term`
+    pub fn new<T: EventListener>(term: &Term<T>, renderer: &TerminalRenderer) -> std::io::Result<Self> {
+        // Blank lines are kept so that lines end up on the same grid rows
+        let text = (term.topmost_line().0..term.screen_lines() as i32)
+            .map(|line| crate::export::line_ansi(term, renderer, line))
+            .collect::<Vec<_>>()
+            .join("\r\n");
+        Ok(Self {
+            data: zstd::encode_all(text.as_bytes(), LEVEL)?,
+            columns: term.columns(),
+            screen_lines: term.screen_lines(),
+            cursor: term.grid().cursor.point,
+        })
+    }
+
+    /// Compressed size in bytes
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Rebuild the grid in a new terminal sending its events to `listener`
+    pub fn restore<T: EventListener>(&self, listener: T) -> std::io::Result<Term<T>> {
+        let text = zstd::decode_all(self.data.as_slice())?;
+        let size = Size { cols: self.columns, rows: self.screen_lines };
+        let mut term = Term::new(TermConfig::default(), &size, listener);
+        let mut parser: ansi::Processor = ansi::Processor::new();
+        let cursor = format!("\x1b[{};{}H", self.cursor.line.0 + 1, self.cursor.column.0 + 1);
+        for byte in text.iter().chain(cursor.as_bytes()) {
+            parser.advance(&mut term, *byte);
+        }
+        Ok(term)
+    }
+}
@@ -0,0 +1,269 @@
+//! Exporting terminal content with its colors
+//!
+//! Turns the grid and scrollback into plain text, text with ANSI escape
+//! sequences, or a standalone HTML page, e.g. for sharing a build log.
+
+use std::fmt::Write;
+
+use alacritty_terminal::event::EventListener;
+use alacritty_terminal::grid::Dimensions;
+use alacritty_terminal::index::Line;
+use alacritty_terminal::term::cell::Flags;
+use alacritty_terminal::term::Term;
+use alacritty_terminal::vte::ansi::{Color, NamedColor, Rgb};
+
+use crate::render::TerminalRenderer;
+
+/// Output format of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Standalone HTML page with inline styles
+    Html,
+    /// Text with SGR escape sequences
+    Ansi,
+    /// Plain text
+    Text,
+}
+
+/// One character with its attributes
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct StyledChar {
+    c: char,
+    style: Style,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Style {
+    fg: Color,
+    bg: Color,
+    flags: Flags,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            fg: Color::Named(NamedColor::Foreground),
+            bg: Color::Named(NamedColor::Background),
+            flags: Flags::empty(),
+        }
+    }
+}
+
+/// Attributes that matter for export
+const STYLE_FLAGS: Flags = Flags::BOLD
+    .union(Flags::DIM)
+    .union(Flags::ITALIC)
+    .union(Flags::UNDERLINE)
+    .union(Flags::INVERSE)
+    .union(Flags::STRIKEOUT);
+
+/// Export scrollback and grid of `term` down to the last non-empty line
+pub fn export<T: EventListener>(
+    term: &Term<T>,
+    renderer: &TerminalRenderer,
+    format: ExportFormat,
+    title: &str,
+) -> String {
+    let lines = styled_lines(term);
+    match format {
+        ExportFormat::Text => to_text(&lines),
+        ExportFormat::Ansi => to_ansi(&lines, renderer),
+        ExportFormat::Html => to_html(&lines, renderer, title),
+    }
+}
+
+/// Line `line` of `term` (negative: scrollback) as text with SGR escape sequences
+pub fn line_ansi<T: EventListener>(term: &Term<T>, renderer: &TerminalRenderer, line: i32) -> String {
+    line_to_ansi(&styled_line(term, line), renderer)
+}
+
+fn styled_lines<T: EventListener>(term: &Term<T>) -> Vec<Vec<StyledChar>> {
+    let mut lines: Vec<Vec<StyledChar>> = (term.topmost_line().0..term.screen_lines() as i32)
+        .map(|line| styled_line(term, line))
+        .collect();
+    while lines.last().is_some_and(Vec::is_empty) {
+        lines.pop();
+    }
+    lines
+}
+
+/// One line without trailing blanks
+fn styled_line<T: EventListener>(term: &Term<T>, line: i32) -> Vec<StyledChar> {
+    let blank = |ch: &StyledChar| ch.c == ' ' && ch.style.bg == Style::default().bg;
+    let mut chars: Vec<StyledChar> = term.grid()[Line(line)]
+        .into_iter()
+        .filter(|cell| !cell.flags.contains(Flags::WIDE_CHAR_SPACER))
+        .map(|cell| StyledChar {
+            c: if cell.c == '\0' { ' ' } else { cell.c },
+            style: Style { fg: cell.fg, bg: cell.bg, flags: cell.flags & STYLE_FLAGS },
+        })
+        .collect();
+    while chars.last().is_some_and(blank) {
+        chars.pop();
+    }
+    chars
+}
+
+fn to_text(lines: &[Vec<StyledChar>]) -> String {
+    let mut out = String::new();
+    for line in lines {
+        out.extend(line.iter().map(|ch| ch.c));
+        out.push('\n');
+    }
+    out
+}
+
+/// Runs of characters in `line` sharing a style
+fn runs(line: &[StyledChar]) -> Vec<(Style, String)> {
+    let mut runs: Vec<(Style, String)> = Vec::new();
+    for ch in line {
+        match runs.last_mut() {
+            Some((style, text)) if *style == ch.style => text.push(ch.c),
+            _ => runs.push((ch.style, ch.c.to_string())),
+        }
+    }
+    runs
+}
+
+fn to_ansi(lines: &[Vec<StyledChar>], renderer: &TerminalRenderer) -> String {
+    let mut out = String::new();
+    for line in lines {
+        out.push_str(&line_to_ansi(line, renderer));
+        out.push('\n');
+    }
+    out
+}
+
+fn line_to_ansi(line: &[StyledChar], renderer: &TerminalRenderer) -> String {
+    let mut out = String::new();
+    let mut styled = false;
+    for (style, text) in runs(line) {
+        if style != Style::default() {
+            let _ = write!(out, "\x1b[{}m", sgr(&style, renderer));
+            styled = true;
+        } else if styled {
+            out.push_str("\x1b[0m");
+            styled = false;
+        }
+        out.push_str(&text);
+    }
+    if styled {
+        out.push_str("\x1b[0m");
+    }
+    out
+}
+
+/// SGR parameters for `style`, starting from a reset
+fn sgr(style: &Style, renderer: &TerminalRenderer) -> String {
+    let mut params = vec!["0".to_string()];
+    for (flag, param) in [
+        (Flags::BOLD, "1"),
+        (Flags::DIM, "2"),
+        (Flags::ITALIC, "3"),
+        (Flags::UNDERLINE, "4"),
+        (Flags::INVERSE, "7"),
+        (Flags::STRIKEOUT, "9"),
+    ] {
+        if style.flags.contains(flag) {
+            params.push(param.to_string());
+        }
+    }
+    params.extend(sgr_color(&style.fg, 30, renderer));
+    params.extend(sgr_color(&style.bg, 40, renderer));
+    params.join(";")
+}
+
+/// SGR color parameter; `base` is 30 for foreground, 40 for background
+fn sgr_color(color: &Color, base: u8, renderer: &TerminalRenderer) -> Option<String> {
+    match color {
+        Color::Named(NamedColor::Foreground | NamedColor::Background) => None,
+        Color::Named(named) if (*named as usize) < 8 => Some((base + *named as u8).to_string()),
+        Color::Named(named) if (*named as usize) < 16 => Some((base + 60 + *named as u8 - 8).to_string()),
+        Color::Indexed(index) => Some(format!("{};5;{}", base + 8, index)),
+        color => {
+            let Rgb { r, g, b } = renderer.color_rgb(color);
+            Some(format!("{};2;{};{};{}", base + 8, r, g, b))
+        }
+    }
+}
+
+fn to_html(lines: &[Vec<StyledChar>], renderer: &TerminalRenderer, title: &str) -> String {
+    let default = Style::default();
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n\
+         body {{ margin: 0; background: {}; }}\n\
+         pre {{ margin: 0; padding: 1em; color: {}; font-family: monospace; }}\n\
+         </style>\n</head>\n<body>\n<pre>",
+        escape_html(title),
+        css_color(renderer.color_rgb(&default.bg)),
+        css_color(renderer.color_rgb(&default.fg)),
+    );
+    for line in lines {
+        for (style, text) in runs(line) {
+            let css = css_style(&style, renderer);
+            if css.is_empty() {
+                out.push_str(&escape_html(&text));
+            } else {
+                let _ = write!(out, "<span style=\"{}\">{}</span>", css, escape_html(&text));
+            }
+        }
+        out.push('\n');
+    }
+    out.push_str("</pre>\n</body>\n</html>\n");
+    out
+}
+
+/// Inline CSS for `style`, empty for the default style
+fn css_style(style: &Style, renderer: &TerminalRenderer) -> String {
+    let default = Style::default();
+    let (fg, bg) = if style.flags.contains(Flags::INVERSE) {
+        (style.bg, style.fg)
+    } else {
+        (style.fg, style.bg)
+    };
+    let mut css = Vec::new();
+    if fg != default.fg || style.flags.contains(Flags::INVERSE) {
+        css.push(format!("color: {}", css_color(renderer.color_rgb(&fg))));
+    }
+    if bg != default.bg || style.flags.contains(Flags::INVERSE) {
+        css.push(format!("background: {}", css_color(renderer.color_rgb(&bg))));
+    }
+    if style.flags.contains(Flags::BOLD) {
+        css.push("font-weight: bold".to_string());
+    }
+    if style.flags.contains(Flags::DIM) {
+        css.push("opacity: 0.7".to_string());
+    }
+    if style.flags.contains(Flags::ITALIC) {
+        css.push("font-style: italic".to_string());
+    }
+    let decorations: Vec<&str> = [(Flags::UNDERLINE, "underline"), (Flags::STRIKEOUT, "line-through")]
+        .into_iter()
+        .filter(|(flag, _)| style.flags.contains(*flag))
+        .map(|(_, decoration)| decoration)
+        .collect();
+    if !decorations.is_empty() {
+        css.push(format!("text-decoration: {}", decorations.join(" ")));
+    }
+    css.join("; ")
+}
+
+fn css_color(Rgb { r, g, b }: Rgb) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,149 @@
+//! Folding of repeated output lines
+//!
+//! Noisy commands often print the same line over and over (retry loops,
+//! polling, progress spam). Runs of at least [`MIN_RUN`] identical non-empty
+//! lines are shown as a single row followed by a `×N` marker. A run is keyed
+//! by its first grid line, which is what gets expanded again on click.
+
+use std::collections::HashSet;
+
+use alacritty_terminal::event::EventListener;
+use alacritty_terminal::index::Line;
+use alacritty_terminal::term::Term;
+
+/// Shortest run of identical lines that gets folded
+pub const MIN_RUN: usize = 3;
+
+/// One displayed row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FoldedRow {
+    /// Grid line shown in this row
+    pub line: usize,
+    /// How many identical lines the row stands for (1 = not folded)
+    pub repeat: usize,
+    /// Width of the line's text in columns, for placing the marker
+    pub width: usize,
+}
+
+/// Mapping between grid lines and displayed rows
+///
+/// Lines past the folded range map one-to-one (shifted by the hidden lines),
+/// so the default map is the identity.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FoldMap {
+    rows: Vec<FoldedRow>,
+    lines: usize,
+}
+
+impl FoldMap {
+    /// Fold runs in `lines`, except runs starting at a line in `expanded`
+    pub fn new(lines: &[String], expanded: &HashSet<usize>) -> Self {
+        let mut rows = Vec::with_capacity(lines.len());
+        let mut start = 0;
+        while start < lines.len() {
+            let text = &lines[start];
+            let run = lines[start..].iter().take_while(|l| *l == text).count();
+            let width = text.chars().count();
+            if run >= MIN_RUN && !text.is_empty() && !expanded.contains(&start) {
+                rows.push(FoldedRow { line: start, repeat: run, width });
+            } else {
+                rows.extend((start..start + run).map(|line| FoldedRow { line, repeat: 1, width }));
+            }
+            start += run;
+        }
+        Self { rows, lines: lines.len() }
+    }
+
+    /// Fold the lines above the cursor of `term`
+    ///
+    /// The cursor line is left out since it may still be being written.
+    pub fn for_term<T: EventListener>(term: &Term<T>, expanded: &HashSet<usize>) -> Self {
+        let grid = term.grid();
+        let cursor_line = grid.cursor.point.line.0.max(0) as usize;
+        let lines: Vec<String> = (0..cursor_line)
+            .map(|line| {
+                let text: String = grid[Line(line as i32)]
+                    .into_iter()
+                    .map(|cell| if cell.c == '\0' { ' ' } else { cell.c })
+                    .collect();
+                text.trim_end().to_string()
+            })
+            .collect();
+        Self::new(&lines, expanded)
+    }
+
+    /// Rows covering the folded range
+    pub fn rows(&self) -> &[FoldedRow] {
+        &self.rows
+    }
+
+    /// Number of grid lines hidden by folding
+    pub fn hidden(&self) -> usize {
+        self.lines - self.rows.len()
+    }
+
+    /// Row showing grid `line`, `None` if it's folded away
+    pub fn row_of(&self, line: usize) -> Option<usize> {
+        if line >= self.lines {
+            return Some(line - self.hidden());
+        }
+        let row = self.rows.partition_point(|r| r.line <= line).checked_sub(1)?;
+        (self.rows[row].line == line).then_some(row)
+    }
+
+    /// Row showing grid `line`, or the folded run containing it
+    pub fn row_containing(&self, line: usize) -> usize {
+        if line >= self.lines {
+            return line - self.hidden();
+        }
+        self.rows.partition_point(|r| r.line <= line).saturating_sub(1)
+    }
+
+    /// Grid line shown in `row`
+    pub fn line_at(&self, row: usize) -> usize {
+        self.rows.get(row).map_or(row + self.hidden(), |r| r.line)
+    }
+
+    /// First line of the folded run shown in `row`, if it is one
+    pub fn run_at(&self, row: usize) -> Option<usize> {
+        self.rows.get(row).filter(|r| r.repeat > 1).map(|r| r.line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn runs_are_folded_into_one_row() {
+        let folds = FoldMap::new(&lines(&["start", "retry", "retry", "retry", "retry", "done"]), &HashSet::new());
+        assert_eq!(folds.rows().len(), 3);
+        assert_eq!(folds.rows()[1], FoldedRow { line: 1, repeat: 4, width: 5 });
+        assert_eq!(folds.hidden(), 3);
+        assert_eq!(folds.row_of(1), Some(1));
+        assert_eq!(folds.row_of(3), None);
+        assert_eq!(folds.row_containing(3), 1);
+        assert_eq!(folds.row_of(5), Some(2));
+        assert_eq!(folds.line_at(2), 5);
+        assert_eq!(folds.run_at(1), Some(1));
+        assert_eq!(folds.run_at(2), None);
+        // Past the folded range lines map one-to-one
+        assert_eq!(folds.row_of(6), Some(3));
+        assert_eq!(folds.line_at(3), 6);
+    }
+
+    #[test]
+    fn short_runs_blank_lines_and_expanded_runs_stay() {
+        let short = FoldMap::new(&lines(&["a", "a", "b"]), &HashSet::new());
+        assert_eq!(short.hidden(), 0);
+        let blank = FoldMap::new(&lines(&["", "", "", "x"]), &HashSet::new());
+        assert_eq!(blank.hidden(), 0);
+        let expanded = FoldMap::new(&lines(&["x", "y", "y", "y"]), &HashSet::from([1]));
+        assert_eq!(expanded.hidden(), 0);
+        assert_eq!(FoldMap::default().line_at(7), 7);
+    }
+}
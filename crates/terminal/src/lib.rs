@@ -3,14 +3,17 @@
 //! This crate provides content-aware terminal windows that can report
 //! their content height and request dynamic resizing.
 
+pub mod compress;
+pub mod export;
+pub mod fold;
 pub mod pty;
 pub mod render;
 pub mod sizing;
 pub mod state;
 
-pub use render::Theme;
+pub use render::{Contrast, Theme};
 pub use sizing::TerminalSizingState;
-pub use state::Terminal;
+pub use state::{Progress, Terminal};
 
 // Re-export Side for selection handling
 pub use alacritty_terminal::index::Side;
@@ -8,7 +8,7 @@ use std::io::{Read, Write};
 use std::os::fd::{AsFd, AsRawFd, FromRawFd, IntoRawFd, RawFd};
 use std::os::unix::process::CommandExt;
 use std::path::Path;
-use std::process::{Child, Command, Stdio};
+use std::process::{Child, Command, ExitStatus, Stdio};
 
 use rustix::termios::{tcsetwinsize, Winsize};
 
@@ -49,11 +49,20 @@ pub struct Pty {
 
     /// Whether we've already detected the child exited
     exited: bool,
+
+    /// Exit status of the child, once it has been reaped
+    exit_status: Option<ExitStatus>,
 }
 
 impl Pty {
     /// Spawn a new PTY with the given shell
     pub fn spawn(shell: &str, cols: u16, rows: u16) -> Result<Self, PtyError> {
+        Self::spawn_in(shell, cols, rows, None)
+    }
+
+    /// Spawn a new PTY with the given shell, started in `working_dir`
+    /// (the compositor's working directory if `None`)
+    pub fn spawn_in(shell: &str, cols: u16, rows: u16, working_dir: Option<&Path>) -> Result<Self, PtyError> {
         let winsize = Winsize {
             ws_row: rows,
             ws_col: cols,
@@ -114,6 +123,9 @@ impl Pty {
         // Also set TERM so shell knows it's in a terminal
         let mut cmd = Command::new(shell);
         cmd.arg("-l").env("TERM", "xterm-256color");
+        if let Some(dir) = working_dir {
+            cmd.current_dir(dir);
+        }
 
         // Inject integration script for fish
         // The script self-guards with TERMSTACK_SOCKET check
@@ -145,6 +157,7 @@ impl Pty {
             child,
             winsize,
             exited: false,
+            exit_status: None,
         })
     }
 
@@ -262,6 +275,7 @@ impl Pty {
             child,
             winsize,
             exited: false,
+            exit_status: None,
         })
     }
 
@@ -349,6 +363,7 @@ impl Pty {
             Ok(None) => true,
             Ok(Some(status)) => {
                 self.exited = true;
+                self.exit_status = Some(status);
                 tracing::debug!("shell exited with status: {:?}", status);
                 false
             }
@@ -366,38 +381,230 @@ impl Pty {
     }
 }
 
-impl Drop for Pty {
-    fn drop(&mut self) {
-        if self.exited {
-            return;
-        }
+/// Grace period after SIGHUP before escalating to SIGTERM
+const HANGUP_GRACE: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Grace period after SIGTERM before escalating to SIGKILL
+const TERM_GRACE: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Time to wait for SIGKILL to take effect
+const KILL_GRACE: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Parse `(pgrp, session, state)` from the contents of `/proc/<pid>/stat`
+///
+/// The command name (field 2) is wrapped in parentheses and may itself contain
+/// spaces or parentheses, so fields are read after the last `)`.
+#[cfg(target_os = "linux")]
+fn parse_proc_stat(stat: &str) -> Option<(i32, i32, char)> {
+    let rest = &stat[stat.rfind(')')? + 1..];
+    let mut fields = rest.split_whitespace();
+    let state = fields.next()?.chars().next()?;
+    let _ppid = fields.next()?;
+    let pgrp = fields.next()?.parse().ok()?;
+    let session = fields.next()?.parse().ok()?;
+    Some((pgrp, session, state))
+}
 
-        let pid = self.child.id() as i32;
+/// `(pgrp, session, state)` of every process visible in `/proc`
+#[cfg(target_os = "linux")]
+fn all_proc_stats() -> Vec<(i32, i32, char)> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_str().is_some_and(|n| n.bytes().all(|b| b.is_ascii_digit())))
+        .filter_map(|entry| std::fs::read_to_string(entry.path().join("stat")).ok())
+        .filter_map(|stat| parse_proc_stat(&stat))
+        .collect()
+}
+
+/// Collect the process groups of all live (non-zombie) processes in a session
+///
+/// Job-control shells move background jobs into their own process groups, so
+/// signalling only the session leader's group would miss them.
+#[cfg(target_os = "linux")]
+fn session_process_groups(sid: i32) -> Vec<i32> {
+    let mut groups: Vec<i32> = all_proc_stats()
+        .into_iter()
+        .filter(|&(_, session, state)| session == sid && state != 'Z')
+        .map(|(pgrp, _, _)| pgrp)
+        .collect();
+    groups.sort_unstable();
+    groups.dedup();
+    groups
+}
+
+/// Collect the process groups of all live processes in a session
+///
+/// Without procfs we can only see the session leader's own group.
+#[cfg(not(target_os = "linux"))]
+fn session_process_groups(sid: i32) -> Vec<i32> {
+    if unsafe { libc::kill(-sid, 0) } == 0 {
+        vec![sid]
+    } else {
+        Vec::new()
+    }
+}
 
-        // Try to terminate gracefully with SIGHUP (hangup)
-        // This allows shells to save history
+/// The state of every process, read once for checks on many terminals
+///
+/// Reading `/proc` costs a file per process, so a periodic check over all
+/// cells takes one snapshot and passes it to each.
+#[derive(Debug, Default)]
+pub struct ProcessTable(Vec<(i32, i32, char)>);
+
+impl ProcessTable {
+    /// Snapshot the running processes (empty without procfs)
+    pub fn read() -> Self {
+        #[cfg(target_os = "linux")]
+        {
+            Self(all_proc_stats())
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            Self::default()
+        }
+    }
+}
+
+/// Send a signal to every process group in a session
+///
+/// Returns false if no live process was left to signal.
+fn signal_session(sid: i32, signal: i32) -> bool {
+    let groups = session_process_groups(sid);
+    for pgid in &groups {
         unsafe {
-            libc::kill(pid, libc::SIGHUP);
+            libc::kill(-pgid, signal);
         }
+    }
+    !groups.is_empty()
+}
 
-        // Wait a bit for the process to exit
-        let start = std::time::Instant::now();
-        loop {
-            match self.child.try_wait() {
-                Ok(Some(_)) => return, // Exited
-                Ok(None) => {
-                    if start.elapsed() > std::time::Duration::from_millis(500) {
-                        break; // Timeout
-                    }
-                    std::thread::sleep(std::time::Duration::from_millis(10));
-                }
-                Err(_) => break, // Error waiting
-            }
+impl Pty {
+    /// Process ID of the child (also its session and process group ID, via `setsid`)
+    pub fn pid(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Whether the foreground job is stuck in uninterruptible sleep (`D` state)
+    ///
+    /// Such processes ignore every signal, including SIGKILL, until the kernel
+    /// call they are blocked in returns (hung network mounts, failing disks).
+    #[cfg(target_os = "linux")]
+    pub fn foreground_blocked(&self, processes: &ProcessTable) -> bool {
+        let Ok(pgrp) = rustix::termios::tcgetpgrp(self.master.as_fd()) else {
+            return false;
+        };
+        let pgrp = pgrp.as_raw_nonzero().get();
+        processes.0.iter().any(|&(group, _, state)| group == pgrp && state == 'D')
+    }
+
+    /// Whether the foreground job is stuck in uninterruptible sleep
+    ///
+    /// Process states aren't available without procfs.
+    #[cfg(not(target_os = "linux"))]
+    pub fn foreground_blocked(&self, _processes: &ProcessTable) -> bool {
+        false
+    }
+
+    /// Exit status of the child, if it has exited and been reaped
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        self.exit_status
+    }
+
+    /// Record an exit status collected by an external reaper
+    ///
+    /// Once the child has been reaped elsewhere (e.g. by a SIGCHLD handler
+    /// calling `waitpid`), `Child::try_wait` can no longer observe it, so the
+    /// reaper hands the status over here instead.
+    pub fn record_exit(&mut self, status: ExitStatus) {
+        self.exited = true;
+        self.exit_status = Some(status);
+    }
+
+    /// Ask everything in the child's session to quit, without waiting
+    ///
+    /// Sends SIGHUP and SIGTERM (`nohup`'d jobs ignore the former). The exit
+    /// is picked up by the usual reaping. Returns false if nothing was running.
+    pub fn hang_up(&self) -> bool {
+        let sid = self.child.id() as i32;
+        signal_session(sid, libc::SIGHUP) | signal_session(sid, libc::SIGTERM)
+    }
+
+    /// Terminate the child and everything else running in its session
+    ///
+    /// Sends SIGHUP (lets shells save history) and leaves escalating to
+    /// SIGTERM and SIGKILL to a thread, so background jobs and grandchildren
+    /// don't outlive the cell but closing it doesn't wait out their grace
+    /// periods. Returns that thread, or `None` if nothing was left running.
+    pub fn terminate(&mut self) -> Option<std::thread::JoinHandle<()>> {
+        // The child called setsid(), so its PID is the session ID
+        let sid = self.child.id() as i32;
+        // Reap an exited child first: the session ID is free for reuse once
+        // the session is empty, so an empty session must not be signalled
+        self.is_running();
+        if !signal_session(sid, libc::SIGHUP) {
+            return None;
         }
 
-        // Force kill if still running
-        let _ = self.child.kill();
-        let _ = self.child.wait();
+        // From here on the thread reaps the child
+        let reap = !self.exited;
+        self.exited = true;
+        std::thread::Builder::new()
+            .name("pty-terminate".to_string())
+            .spawn(move || escalate_termination(sid, reap))
+            .map_err(|e| tracing::warn!(sid, error = %e, "failed to start the thread ending the session"))
+            .ok()
+    }
+}
+
+/// Wait until every process in the session has exited (or is a zombie)
+///
+/// Returns true if the session emptied before the timeout.
+fn wait_for_session_exit(sid: i32, timeout: std::time::Duration) -> bool {
+    let start = std::time::Instant::now();
+    loop {
+        if session_process_groups(sid).is_empty() {
+            return true;
+        }
+        if start.elapsed() > timeout {
+            return false;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+}
+
+/// Escalate a hung-up session to SIGTERM, then SIGKILL, reaping its leader
+/// if `reap`
+///
+/// While the leader is unreaped its PID can't be reused, so neither can the
+/// session ID that is signalled here.
+fn escalate_termination(sid: i32, reap: bool) {
+    let exited = wait_for_session_exit(sid, HANGUP_GRACE)
+        || !signal_session(sid, libc::SIGTERM)
+        || wait_for_session_exit(sid, TERM_GRACE);
+    if !exited && signal_session(sid, libc::SIGKILL) {
+        tracing::debug!(sid, "session did not exit after SIGTERM, sent SIGKILL");
+        // SIGKILL can't be caught, but delivery is still asynchronous
+        if !wait_for_session_exit(sid, KILL_GRACE) {
+            tracing::warn!(sid, "session still running after SIGKILL");
+        }
+    }
+    if reap {
+        // Blocks only while the leader is stuck in the kernel (D state)
+        unsafe {
+            libc::waitpid(sid, std::ptr::null_mut(), 0);
+        }
+    }
+}
+
+impl Drop for Pty {
+    fn drop(&mut self) {
+        // Even if the shell itself already exited, background jobs it started
+        // may still be running in its session
+        let _ = self.terminate();
     }
 }
 
@@ -581,6 +788,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn spawn_in_starts_in_the_directory() {
+        if std::env::var("CI").is_ok() {
+            return;
+        }
+        let dir = std::env::temp_dir().canonicalize().unwrap();
+        let before = std::env::current_dir().unwrap();
+        let mut pty = Pty::spawn_in("/bin/sh", 80, 24, Some(&dir)).unwrap();
+        pty.write(b"echo dir=$(pwd -P)\n").unwrap();
+
+        let mut output = String::new();
+        let mut buf = [0u8; 1024];
+        let expected = format!("dir={}", dir.display());
+        for _ in 0..20 {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            if let Ok(n) = pty.read(&mut buf) {
+                output.push_str(&String::from_utf8_lossy(&buf[..n]));
+                if output.contains(&expected) {
+                    break;
+                }
+            }
+        }
+
+        assert!(output.contains(&expected), "shell should start in {}, got: {}", dir.display(), output);
+        // The compositor's own directory is left alone
+        assert_eq!(std::env::current_dir().unwrap(), before);
+    }
+
     #[test]
     fn read_after_child_exit_returns_zero_or_error() {
         // After the child exits, reads should return 0 (EOF) or error gracefully
@@ -758,4 +993,145 @@ mod tests {
         // We don't assert success/failure, just that it doesn't panic
         eprintln!("Resize after exit: {:?}", result);
     }
+
+    /// Read child output until `pred` matches or the timeout expires
+    fn read_until(pty: &mut Pty, pred: impl Fn(&str) -> bool) -> String {
+        let mut output = String::new();
+        let mut buf = [0u8; 256];
+        let start = std::time::Instant::now();
+        while start.elapsed() < std::time::Duration::from_secs(2) && !pred(&output) {
+            match pty.read(&mut buf) {
+                Ok(0) => std::thread::sleep(std::time::Duration::from_millis(10)),
+                Ok(n) => output.push_str(&String::from_utf8_lossy(&buf[..n])),
+                Err(_) => break,
+            }
+        }
+        output
+    }
+
+    /// Wait up to a second past the grace periods for a process to go
+    #[cfg(target_os = "linux")]
+    fn wait_for_exit(pid: i32) -> bool {
+        let deadline = std::time::Instant::now() + HANGUP_GRACE + TERM_GRACE + KILL_GRACE + std::time::Duration::from_secs(1);
+        while process_alive(pid) && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        !process_alive(pid)
+    }
+
+    /// Whether a process exists and is not a zombie
+    #[cfg(target_os = "linux")]
+    fn process_alive(pid: i32) -> bool {
+        std::fs::read_to_string(format!("/proc/{}/stat", pid))
+            .ok()
+            .and_then(|stat| parse_proc_stat(&stat))
+            .is_some_and(|(_, _, state)| state != 'Z')
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn parse_proc_stat_handles_parens_in_command_name() {
+        let stat = "1234 (weird) name)) S 1 1234 1200 34816 1234 4194560";
+        assert_eq!(parse_proc_stat(stat), Some((1234, 1200, 'S')));
+        assert_eq!(parse_proc_stat("garbage"), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn sleeping_foreground_job_is_not_blocked() {
+        let mut env = HashMap::new();
+        env.insert("TERM".to_string(), "xterm".to_string());
+        env.insert("SHELL".to_string(), "/bin/sh".to_string());
+
+        // `sleep` waits interruptibly (state S), which must not count as hung
+        let pty = Pty::spawn_command("sleep 5", Path::new("/tmp"), &env, 80, 24).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert!(!pty.foreground_blocked(&ProcessTable::read()));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn drop_kills_background_grandchildren() {
+        if std::env::var("CI").is_ok() {
+            return;
+        }
+
+        let mut env = HashMap::new();
+        env.insert("TERM".to_string(), "xterm".to_string());
+        env.insert("SHELL".to_string(), "/bin/sh".to_string());
+
+        // The background sleep ignores SIGHUP, so hanging up the session
+        // alone would leak it
+        let mut pty = Pty::spawn_command(
+            "nohup sleep 60 >/dev/null 2>&1 & echo pid=$!; sleep 60",
+            Path::new("/tmp"),
+            &env,
+            80,
+            24,
+        ).unwrap();
+
+        let output = read_until(&mut pty, |out| out.contains('\n'));
+        let grandchild: i32 = output
+            .trim()
+            .strip_prefix("pid=")
+            .and_then(|pid| pid.parse().ok())
+            .unwrap_or_else(|| panic!("unexpected output: {:?}", output));
+        assert!(process_alive(grandchild), "background sleep should be running");
+
+        drop(pty);
+
+        assert!(wait_for_exit(grandchild), "background sleep should be killed with the cell");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn terminate_escalates_to_sigkill() {
+        if std::env::var("CI").is_ok() {
+            return;
+        }
+
+        let mut env = HashMap::new();
+        env.insert("TERM".to_string(), "xterm".to_string());
+        env.insert("SHELL".to_string(), "/bin/sh".to_string());
+
+        // Ignored signals are inherited across exec, so sleep ignores them too
+        let mut pty = Pty::spawn_command(
+            "trap '' HUP TERM; echo ready; sleep 60",
+            Path::new("/tmp"),
+            &env,
+            80,
+            24,
+        ).unwrap();
+        read_until(&mut pty, |out| out.contains("ready"));
+        let pid = pty.pid() as i32;
+
+        let start = std::time::Instant::now();
+        let escalation = pty.terminate().expect("the shell is running");
+        assert!(start.elapsed() < HANGUP_GRACE, "terminate should not wait out the grace periods");
+        escalation.join().unwrap();
+
+        assert!(!process_alive(pid), "shell should be killed after escalation");
+        assert!(session_process_groups(pid).is_empty(), "no process should remain in the session");
+        assert!(
+            start.elapsed() < HANGUP_GRACE + TERM_GRACE + std::time::Duration::from_secs(1),
+            "termination should be bounded by the grace periods"
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn terminate_leaves_an_empty_session_alone() {
+        let mut env = HashMap::new();
+        env.insert("TERM".to_string(), "xterm".to_string());
+        env.insert("SHELL".to_string(), "/bin/sh".to_string());
+
+        let mut pty = Pty::spawn_command("true", Path::new("/tmp"), &env, 80, 24).unwrap();
+        let start = std::time::Instant::now();
+        while pty.is_running() && start.elapsed() < std::time::Duration::from_secs(2) {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        assert!(pty.terminate().is_none(), "nothing should be signalled");
+        assert!(pty.exit_status().is_some_and(|status| status.success()));
+    }
 }
@@ -3,15 +3,18 @@
 //! Renders alacritty_terminal grid to an ARGB pixel buffer using fontdue.
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use alacritty_terminal::event::EventListener;
 use alacritty_terminal::index::Point;
-use alacritty_terminal::term::cell::Flags;
+use alacritty_terminal::term::cell::{Cell, Flags};
 use alacritty_terminal::term::Term;
-use alacritty_terminal::vte::ansi::{Color, NamedColor};
+use alacritty_terminal::vte::ansi::{Color, NamedColor, Rgb};
+
+use crate::fold::FoldMap;
 
 /// Color theme for the terminal
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 pub enum Theme {
     #[default]
     Dark,
@@ -50,6 +53,92 @@ impl Theme {
             Theme::Light => 0xFF1A1A1A,
         }
     }
+
+    /// Color scheme report for this theme (`CSI ? 997 ; 1 n` dark, `2` light)
+    pub fn color_scheme_report(&self) -> &'static str {
+        match self {
+            Theme::Dark => "\x1b[?997;1n",
+            Theme::Light => "\x1b[?997;2n",
+        }
+    }
+}
+
+/// Palette adjustments for low vision
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contrast {
+    /// Pure black and white for the theme's background and foreground, and
+    /// text kept at WCAG AA contrast (4.5) or more
+    pub high: bool,
+    /// Minimum WCAG contrast ratio of text against its background (1.0 = any)
+    pub minimum: f32,
+}
+
+impl Default for Contrast {
+    fn default() -> Self {
+        Self { high: false, minimum: 1.0 }
+    }
+}
+
+impl Contrast {
+    /// Contrast ratio text is held to
+    fn effective_minimum(&self) -> f32 {
+        if self.high {
+            self.minimum.max(4.5)
+        } else {
+            self.minimum
+        }
+    }
+}
+
+/// Relative luminance of an ARGB color (WCAG 2)
+fn luminance(color: u32) -> f32 {
+    let channel = |shift: u32| {
+        let c = ((color >> shift) & 0xFF) as f32 / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * channel(16) + 0.7152 * channel(8) + 0.0722 * channel(0)
+}
+
+/// Contrast ratio of two ARGB colors (1.0 to 21.0, WCAG 2)
+pub fn contrast_ratio(a: u32, b: u32) -> f32 {
+    let (a, b) = (luminance(a), luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// `fg` moved toward black or white, whichever stands out more against `bg`,
+/// just far enough to reach `minimum` contrast
+pub fn ensure_contrast(fg: u32, bg: u32, minimum: f32) -> u32 {
+    if contrast_ratio(fg, bg) >= minimum {
+        return fg;
+    }
+    let target: u32 = if contrast_ratio(0xFFFFFFFF, bg) >= contrast_ratio(0xFF000000, bg) {
+        0xFFFFFFFF
+    } else {
+        0xFF000000
+    };
+    let mix = |t: f32| {
+        let channel = |shift: u32| {
+            let from = ((fg >> shift) & 0xFF) as f32;
+            let to = ((target >> shift) & 0xFF) as f32;
+            ((from + (to - from) * t).round() as u32) << shift
+        };
+        0xFF000000 | channel(16) | channel(8) | channel(0)
+    };
+    // Smallest blend that is enough (the target itself if nothing is)
+    let (mut low, mut high) = (0.0f32, 1.0f32);
+    for _ in 0..12 {
+        let mid = (low + high) / 2.0;
+        if contrast_ratio(mix(mid), bg) >= minimum {
+            high = mid;
+        } else {
+            low = mid;
+        }
+    }
+    mix(high)
 }
 
 /// Font configuration
@@ -65,8 +154,37 @@ pub struct FontConfig {
 
     /// Cell height in pixels
     pub cell_height: u32,
+
+    /// The font file, for shaping ligatures
+    pub data: Arc<[u8]>,
 }
 
+/// Monospace fonts tried for terminal text, in order
+pub const MONOSPACE_FONT_PATHS: &[&str] = &[
+    // DejaVu Sans Mono
+    "/usr/share/fonts/truetype/dejavu/DejaVuSansMono.ttf",
+    "/usr/share/fonts/TTF/DejaVuSansMono.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSansMono.ttf",
+    // Liberation Mono
+    "/usr/share/fonts/truetype/liberation/LiberationMono-Regular.ttf",
+    "/usr/share/fonts/liberation-mono/LiberationMono-Regular.ttf",
+    // Noto Mono
+    "/usr/share/fonts/truetype/noto/NotoMono-Regular.ttf",
+    "/usr/share/fonts/noto/NotoMono-Regular.ttf",
+    // Hack
+    "/usr/share/fonts/truetype/hack/Hack-Regular.ttf",
+    // Ubuntu Mono
+    "/usr/share/fonts/truetype/ubuntu/UbuntuMono-R.ttf",
+    // Fira Code / Fira Mono
+    "/usr/share/fonts/truetype/firacode/FiraCode-Regular.ttf",
+    "/usr/share/fonts/opentype/firacode/FiraCode-Regular.otf",
+    // macOS fonts
+    "/System/Library/Fonts/Supplemental/Courier New.ttf",
+    "/System/Library/Fonts/Supplemental/Andale Mono.ttf",
+    "/System/Library/Fonts/Menlo.ttc",
+    "/Library/Fonts/SF-Mono-Regular.otf",
+];
+
 impl FontConfig {
     /// Create font config from a TTF font
     pub fn from_bytes(font_data: &[u8], size: f32) -> Option<Self> {
@@ -82,39 +200,14 @@ impl FontConfig {
             size,
             cell_width,
             cell_height,
+            data: font_data.into(),
         })
     }
 
     /// Create default font config with the given size
     /// Returns None if no font is available (graceful degradation)
     pub fn try_default_font_with_size(size: f32) -> Option<Self> {
-        // Try to load a monospace font from common locations
-        let font_paths = [
-            // DejaVu Sans Mono
-            "/usr/share/fonts/truetype/dejavu/DejaVuSansMono.ttf",
-            "/usr/share/fonts/TTF/DejaVuSansMono.ttf",
-            "/usr/share/fonts/dejavu/DejaVuSansMono.ttf",
-            // Liberation Mono
-            "/usr/share/fonts/truetype/liberation/LiberationMono-Regular.ttf",
-            "/usr/share/fonts/liberation-mono/LiberationMono-Regular.ttf",
-            // Noto Mono
-            "/usr/share/fonts/truetype/noto/NotoMono-Regular.ttf",
-            "/usr/share/fonts/noto/NotoMono-Regular.ttf",
-            // Hack
-            "/usr/share/fonts/truetype/hack/Hack-Regular.ttf",
-            // Ubuntu Mono
-            "/usr/share/fonts/truetype/ubuntu/UbuntuMono-R.ttf",
-            // Fira Code / Fira Mono
-            "/usr/share/fonts/truetype/firacode/FiraCode-Regular.ttf",
-            "/usr/share/fonts/opentype/firacode/FiraCode-Regular.otf",
-            // macOS fonts
-            "/System/Library/Fonts/Supplemental/Courier New.ttf",
-            "/System/Library/Fonts/Supplemental/Andale Mono.ttf",
-            "/System/Library/Fonts/Menlo.ttc",
-            "/Library/Fonts/SF-Mono-Regular.otf",
-        ];
-
-        for path in &font_paths {
+        for path in MONOSPACE_FONT_PATHS {
             if let Ok(data) = std::fs::read(path) {
                 if let Some(config) = Self::from_bytes(&data, size) {
                     tracing::info!("Loaded font from: {} (size {})", path, size);
@@ -163,12 +256,27 @@ pub struct TerminalRenderer {
     /// Glyph cache
     glyph_cache: HashMap<(char, u32), GlyphData>,
 
+    /// Shape programming ligatures (`->`, `!=`, ...) in runs of punctuation
+    ligatures: bool,
+
+    /// Glyphs of ligature runs by text, `None` when the font joins nothing
+    ligature_cache: HashMap<String, Option<Vec<LigatureGlyph>>>,
+
+    /// Glyph cache for glyphs from shaping, by glyph id
+    indexed_glyph_cache: HashMap<(u16, u32), GlyphData>,
+
     /// Cell dimensions
     cell_width: u32,
     cell_height: u32,
 
     /// Color theme
     theme: Theme,
+
+    /// Palette adjustments for low vision
+    contrast: Contrast,
+
+    /// Foreground colors already adjusted for `contrast`, by (fg, bg)
+    contrast_cache: HashMap<(u32, u32), u32>,
 }
 
 struct GlyphData {
@@ -177,6 +285,138 @@ struct GlyphData {
     height: u32,
     x_offset: i32,
     y_offset: i32,
+    advance: f32,
+}
+
+/// Zero width joiner, gluing emoji into one sequence
+const ZWJ: char = '\u{200D}';
+
+/// Zero-width characters of a cell that are drawn over its base glyph
+///
+/// Combining marks are. Variation selectors and the emoji following a zero
+/// width joiner only change how the base is presented, and without a color
+/// emoji font the base glyph stands for the whole sequence.
+fn combining_marks(zerowidth: &[char]) -> impl Iterator<Item = char> + '_ {
+    zerowidth.iter().enumerate().filter_map(move |(i, &c)| {
+        let after_zwj = i > 0 && zerowidth[i - 1] == ZWJ;
+        let selector = matches!(c, '\u{FE00}'..='\u{FE0F}' | '\u{E0100}'..='\u{E01EF}');
+        (c != ZWJ && !selector && !after_zwj).then_some(c)
+    })
+}
+
+/// Alpha-blend `glyph` onto a `width` x `height` ARGB buffer, its origin
+/// at `x` on the baseline `baseline_y`
+fn blend_glyph(buffer: &mut [u32], width: u32, height: u32, glyph: &GlyphData, x: i32, baseline_y: i32, fg: u32) {
+    let glyph_x = (x + glyph.x_offset).max(0) as u32;
+    let glyph_y = (baseline_y - glyph.height as i32 - glyph.y_offset).max(0) as u32;
+
+    // Draw glyph bitmap
+    let fg_r = (fg >> 16) & 0xFF;
+    let fg_g = (fg >> 8) & 0xFF;
+    let fg_b = fg & 0xFF;
+
+    for gy in 0..glyph.height {
+        let py = glyph_y + gy;
+        if py >= height {
+            break;
+        }
+
+        for gx in 0..glyph.width {
+            let px = glyph_x + gx;
+            if px >= width {
+                break;
+            }
+
+            let alpha = glyph.bitmap[(gy * glyph.width + gx) as usize] as u32;
+            if alpha == 0 {
+                continue;
+            }
+
+            let idx = (py * width + px) as usize;
+            if idx >= buffer.len() {
+                continue;
+            }
+
+            // Alpha blend
+            let bg = buffer[idx];
+            let bg_r = (bg >> 16) & 0xFF;
+            let bg_g = (bg >> 8) & 0xFF;
+            let bg_b = bg & 0xFF;
+
+            let r = (fg_r * alpha + bg_r * (255 - alpha)) / 255;
+            let g = (fg_g * alpha + bg_g * (255 - alpha)) / 255;
+            let b = (fg_b * alpha + bg_b * (255 - alpha)) / 255;
+
+            buffer[idx] = 0xFF000000 | (r << 16) | (g << 8) | b;
+        }
+    }
+}
+
+/// Most ligature runs kept shaped before the cache starts over
+const LIGATURE_CACHE_LIMIT: usize = 1024;
+
+/// A glyph of a shaped ligature run
+#[derive(Debug, Clone, PartialEq)]
+struct LigatureGlyph {
+    /// Character (cell) of the run the glyph belongs to
+    cell: usize,
+    glyph_id: u16,
+    /// Pixel offset from that cell
+    x: i32,
+    y: i32,
+}
+
+/// A cell of a ligature run
+#[derive(Debug, Clone, Copy)]
+struct RunCell {
+    x: u32,
+    c: char,
+    fg: u32,
+}
+
+/// Whether a cell can be part of a ligature run
+///
+/// Programming ligatures join punctuation, so runs stay short and repeat a lot.
+fn is_ligature_candidate(cell: &Cell) -> bool {
+    cell.c.is_ascii_punctuation()
+        && cell.zerowidth().is_none()
+        && !cell.flags.intersects(Flags::WIDE_CHAR | Flags::WIDE_CHAR_SPACER)
+}
+
+/// Shape a run of one-cell ASCII characters with the font's default features
+///
+/// Glyphs are positioned relative to the cell of the character they came from,
+/// so they stay on the grid. `None` if shaping gave each character its own glyph.
+fn shape_ligatures(data: &[u8], text: &str, size: f32) -> Option<Vec<LigatureGlyph>> {
+    let face = rustybuzz::Face::from_slice(data, 0)?;
+    let nominal: Vec<u32> = text.chars().map(|c| face.glyph_index(c).map_or(0, |id| id.0 as u32)).collect();
+    let mut buffer = rustybuzz::UnicodeBuffer::new();
+    buffer.push_str(text);
+    let shaped = rustybuzz::shape(&face, &[], buffer);
+    let infos = shaped.glyph_infos();
+    if infos.len() == nominal.len() && infos.iter().zip(&nominal).all(|(info, &id)| info.glyph_id == id) {
+        return None;
+    }
+
+    let scale = size / face.units_per_em() as f32;
+    let mut glyphs = Vec::with_capacity(infos.len());
+    let (mut pen, mut cluster_pen, mut cluster) = (0, 0, None);
+    for (info, position) in infos.iter().zip(shaped.glyph_positions()) {
+        // ASCII, so the byte offset is the character index
+        let cell = info.cluster as usize;
+        if cluster != Some(cell) {
+            cluster = Some(cell);
+            cluster_pen = pen;
+        }
+        glyphs.push(LigatureGlyph {
+            cell,
+            glyph_id: info.glyph_id as u16,
+            x: ((pen - cluster_pen + position.x_offset) as f32 * scale).round() as i32,
+            y: (position.y_offset as f32 * scale).round() as i32,
+        });
+        pen += position.x_advance;
+    }
+    Some(glyphs)
 }
 
 impl TerminalRenderer {
@@ -193,9 +433,14 @@ impl TerminalRenderer {
             width: 0,
             height: 0,
             glyph_cache: HashMap::new(),
+            ligatures: false,
+            ligature_cache: HashMap::new(),
+            indexed_glyph_cache: HashMap::new(),
             cell_width: 8,
             cell_height: 16,
             theme,
+            contrast: Contrast::default(),
+            contrast_cache: HashMap::new(),
         }
     }
 
@@ -214,9 +459,14 @@ impl TerminalRenderer {
             width: 0,
             height: 0,
             glyph_cache: HashMap::new(),
+            ligatures: false,
+            ligature_cache: HashMap::new(),
+            indexed_glyph_cache: HashMap::new(),
             cell_width,
             cell_height,
             theme,
+            contrast: Contrast::default(),
+            contrast_cache: HashMap::new(),
         }
     }
 
@@ -225,11 +475,81 @@ impl TerminalRenderer {
         (self.cell_width, self.cell_height)
     }
 
+    /// Get the color theme
+    pub fn theme(&self) -> Theme {
+        self.theme
+    }
+
+    /// Switch the color theme (takes effect on the next render)
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.contrast_cache.clear();
+    }
+
+    /// Set the low-vision palette adjustments (takes effect on the next render)
+    pub fn set_contrast(&mut self, contrast: Contrast) {
+        self.contrast = contrast;
+        self.contrast_cache.clear();
+    }
+
+    /// Default text color
+    fn foreground(&self) -> u32 {
+        match (self.contrast.high, self.theme) {
+            (true, Theme::Dark) => 0xFFFFFFFF,
+            (true, Theme::Light) => 0xFF000000,
+            (false, theme) => theme.foreground(),
+        }
+    }
+
+    /// Default background color
+    fn background(&self) -> u32 {
+        match (self.contrast.high, self.theme) {
+            (true, Theme::Dark) => 0xFF000000,
+            (true, Theme::Light) => 0xFFFFFFFF,
+            (false, theme) => theme.background(),
+        }
+    }
+
+    fn cursor_color(&self) -> u32 {
+        if self.contrast.high {
+            self.foreground()
+        } else {
+            self.theme.cursor_color()
+        }
+    }
+
+    /// Color of palette entry `index`, numbered like alacritty's color
+    /// requests (0-255 indexed, 256 foreground, 257 background, 258 cursor)
+    pub fn palette_rgb(&self, index: usize) -> Rgb {
+        let argb = match index {
+            0..=255 => self.indexed_color_to_argb(index as u8),
+            256 => self.foreground(),
+            257 => self.background(),
+            258 => self.cursor_color(),
+            _ => self.foreground(),
+        };
+        Rgb { r: (argb >> 16) as u8, g: (argb >> 8) as u8, b: argb as u8 }
+    }
+
+    /// RGB value `color` is drawn with
+    pub fn color_rgb(&self, color: &Color) -> Rgb {
+        let argb = self.color_to_argb(color);
+        Rgb { r: (argb >> 16) as u8, g: (argb >> 8) as u8, b: argb as u8 }
+    }
+
+    /// Shape programming ligatures like `->` and `!=` if the font has them
+    /// (takes effect on the next render)
+    pub fn set_ligatures(&mut self, ligatures: bool) {
+        self.ligatures = ligatures;
+    }
+
     /// Replace font configuration and clear glyph cache
     pub fn set_font_size(&mut self, font_config: FontConfig) {
         self.cell_width = font_config.cell_width;
         self.cell_height = font_config.cell_height;
         self.glyph_cache.clear();
+        self.ligature_cache.clear();
+        self.indexed_glyph_cache.clear();
         self.font = Some(font_config);
     }
 
@@ -246,6 +566,22 @@ impl TerminalRenderer {
         show_cursor: bool,
         viewport_offset: usize,
     ) {
+        self.render_folded(term, width, height, show_cursor, viewport_offset, &FoldMap::default());
+    }
+
+    /// Render terminal to buffer with repeated lines folded as in `folds`
+    ///
+    /// Rows and the viewport are counted in displayed rows. Returns the first
+    /// displayed row at the top of the buffer.
+    pub fn render_folded<T: EventListener>(
+        &mut self,
+        term: &Term<T>,
+        width: u32,
+        height: u32,
+        show_cursor: bool,
+        viewport_offset: usize,
+        folds: &FoldMap,
+    ) -> usize {
         // Resize buffer if needed
         if self.width != width || self.height != height {
             self.width = width;
@@ -276,7 +612,7 @@ impl TerminalRenderer {
                     last = line;
                 }
             }
-            last
+            folds.row_containing(last as usize) as u32
         };
 
         // Calculate first visible line based on viewport offset
@@ -296,10 +632,23 @@ impl TerminalRenderer {
         // Get selection range for highlighting
         let selection = content.selection.as_ref();
 
+        // Ligature runs by row; the cursor cell never joins one, so the cursor
+        // doesn't cover half a ligature
+        let ligatures = self.ligatures && self.font.is_some();
+        let cursor_point = (show_cursor && viewport_offset == 0).then_some(content.cursor.point);
+        let mut runs: Vec<(u32, Vec<RunCell>)> = Vec::new();
+
         // Render each cell, offsetting by viewport position
         for cell in cells {
+            // A wide char covers its spacer cell as well
+            if cell.flags.contains(Flags::WIDE_CHAR_SPACER) {
+                continue;
+            }
             let col = cell.point.column.0 as u32;
-            let line = cell.point.line.0 as u32;
+            let Some(line) = usize::try_from(cell.point.line.0).ok().and_then(|line| folds.row_of(line)) else {
+                continue;
+            };
+            let line = line as u32;
 
             // Skip lines outside viewport
             if line < first_visible_line {
@@ -314,12 +663,56 @@ impl TerminalRenderer {
                 continue;
             }
 
-            // Check if this cell is selected
+            // Check if this cell is selected (either half, for wide chars)
             let is_selected = selection
-                .map(|sel| sel.contains(Point::new(cell.point.line, cell.point.column)))
+                .map(|sel| {
+                    sel.contains(cell.point)
+                        || (cell.flags.contains(Flags::WIDE_CHAR)
+                            && sel.contains(Point::new(cell.point.line, cell.point.column + 1)))
+                })
                 .unwrap_or(false);
 
-            self.render_cell(x, y, cell.cell, is_selected);
+            if ligatures && is_ligature_candidate(cell.cell) && cursor_point != Some(cell.point) {
+                let Some(fg) = self.render_cell_background(x, y, cell.cell, is_selected) else {
+                    continue;
+                };
+                match runs.last_mut() {
+                    // Selection changes the color, so runs also break at its edges
+                    Some((run_y, run))
+                        if *run_y == y
+                            && run.last().is_some_and(|last| last.x + self.cell_width == x && last.fg == fg) =>
+                    {
+                        run.push(RunCell { x, c: cell.c, fg })
+                    }
+                    _ => runs.push((y, vec![RunCell { x, c: cell.c, fg }])),
+                }
+            } else {
+                self.render_cell(x, y, cell.cell, is_selected);
+            }
+        }
+
+        for (y, run) in runs {
+            self.draw_ligature_run(y, &run);
+        }
+
+        // `×N` after each folded row
+        let marker_color = self.indexed_color_to_argb(8);
+        for (row, folded) in folds.rows().iter().enumerate().skip(first_visible_line as usize) {
+            if folded.repeat < 2 {
+                continue;
+            }
+            let y = (row as u32 - first_visible_line) * self.cell_height;
+            if y >= height {
+                break;
+            }
+            let marker = format!("\u{d7}{}", folded.repeat);
+            for (i, c) in marker.chars().enumerate() {
+                let x = (folded.width + 1 + i) as u32 * self.cell_width;
+                if x >= width {
+                    break;
+                }
+                self.draw_glyph(x, y, c, marker_color, Flags::empty());
+            }
         }
 
         // Render cursor (only if process is running and at live view)
@@ -328,27 +721,48 @@ impl TerminalRenderer {
             let cursor = content.cursor;
             let x = cursor.point.column.0 as u32 * self.cell_width;
             // Adjust cursor Y to viewport
-            let y = (cursor.point.line.0 as u32).saturating_sub(first_visible_line) * self.cell_height;
+            let row = folds.row_containing(cursor.point.line.0.max(0) as usize) as u32;
+            let y = row.saturating_sub(first_visible_line) * self.cell_height;
 
+            let columns = if term.grid()[cursor.point].flags.contains(Flags::WIDE_CHAR) { 2 } else { 1 };
             if x < width && y < height {
-                self.render_cursor(x, y);
+                self.render_cursor(x, y, columns);
+            }
+        }
+
+        first_visible_line as usize
+    }
+
+    fn render_cell(&mut self, x: u32, y: u32, cell: &Cell, is_selected: bool) {
+        let Some(fg) = self.render_cell_background(x, y, cell, is_selected) else {
+            return;
+        };
+        self.draw_glyph(x, y, cell.c, fg, cell.flags);
+        if let Some(zerowidth) = cell.zerowidth() {
+            for mark in combining_marks(zerowidth) {
+                // Skip marks the font lacks rather than drawing a box over the base
+                if self.font.as_ref().is_some_and(|font| font.font.lookup_glyph_index(mark) != 0) {
+                    self.draw_glyph(x, y, mark, fg, cell.flags);
+                }
             }
         }
     }
 
-    fn render_cell(&mut self, x: u32, y: u32, cell: &alacritty_terminal::term::cell::Cell, is_selected: bool) {
+    /// Fill a cell's background, returning the color of its glyph if it has one
+    fn render_cell_background(&mut self, x: u32, y: u32, cell: &Cell, is_selected: bool) -> Option<u32> {
         // Background - use selection color if selected, otherwise cell's background
         let bg = if is_selected {
             self.theme.selection_bg()
         } else {
             self.color_to_argb(&cell.bg)
         };
-        self.fill_rect(x, y, self.cell_width, self.cell_height, bg);
+        let columns = if cell.flags.contains(Flags::WIDE_CHAR) { 2 } else { 1 };
+        self.fill_rect(x, y, columns * self.cell_width, self.cell_height, bg);
 
         // Don't render space characters
         let c = cell.c;
         if c == ' ' || c == '\0' {
-            return;
+            return None;
         }
 
         // Debug: log all rendered characters
@@ -363,15 +777,51 @@ impl TerminalRenderer {
                 Theme::Light => 0xFF000000,
             }
         } else {
-            self.color_to_argb(&cell.fg)
+            let fg = self.color_to_argb(&cell.fg);
+            let minimum = self.contrast.effective_minimum();
+            if minimum > 1.0 {
+                *self.contrast_cache.entry((fg, bg)).or_insert_with(|| ensure_contrast(fg, bg, minimum))
+            } else {
+                fg
+            }
         };
-        self.draw_glyph(x, y, c, fg, cell.flags);
+        Some(fg)
     }
 
-    fn render_cursor(&mut self, x: u32, y: u32) {
-        let cursor_color = self.theme.cursor_color();
+    /// Draw a run of punctuation cells, joined into ligatures where the font has them
+    fn draw_ligature_run(&mut self, y: u32, run: &[RunCell]) {
+        let glyphs = match (run.len(), &self.font) {
+            (2.., Some(font)) => {
+                let text: String = run.iter().map(|cell| cell.c).collect();
+                if self.ligature_cache.len() >= LIGATURE_CACHE_LIMIT {
+                    self.ligature_cache.clear();
+                }
+                self.ligature_cache
+                    .entry(text)
+                    .or_insert_with_key(|text| shape_ligatures(&font.data, text, font.size))
+                    .clone()
+            }
+            _ => None,
+        };
+        match glyphs {
+            Some(glyphs) => {
+                for glyph in glyphs {
+                    let cell = run[glyph.cell];
+                    self.draw_glyph_id(cell.x as i32 + glyph.x, y as i32 - glyph.y, glyph.glyph_id, cell.fg);
+                }
+            }
+            None => {
+                for cell in run {
+                    self.draw_glyph(cell.x, y, cell.c, cell.fg, Flags::empty());
+                }
+            }
+        }
+    }
+
+    fn render_cursor(&mut self, x: u32, y: u32, columns: u32) {
+        let cursor_color = self.cursor_color();
         // Draw block cursor
-        self.fill_rect(x, y, self.cell_width, self.cell_height, cursor_color);
+        self.fill_rect(x, y, columns * self.cell_width, self.cell_height, cursor_color);
     }
 
     fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: u32) {
@@ -395,7 +845,8 @@ impl TerminalRenderer {
         }
     }
 
-    fn draw_glyph(&mut self, x: u32, y: u32, c: char, fg: u32, _flags: Flags) {
+    /// Draw `c` into the cell at `x`, `y`, centered across both cells of a wide char
+    fn draw_glyph(&mut self, x: u32, y: u32, c: char, fg: u32, flags: Flags) {
         let Some(font) = &self.font else {
             return;
         };
@@ -419,56 +870,45 @@ impl TerminalRenderer {
                 height: metrics.height as u32,
                 x_offset: metrics.xmin,
                 y_offset: metrics.ymin,
+                advance: metrics.advance_width,
             }
         });
 
         let glyph = &self.glyph_cache[&cache_key];
 
-        // Calculate position with offset
-        let baseline_y = y + (self.cell_height as i32 - 4) as u32; // Approximate baseline
-        let glyph_x = (x as i32 + glyph.x_offset).max(0) as u32;
-        let glyph_y = (baseline_y as i32 - glyph.height as i32 - glyph.y_offset).max(0) as u32;
-
-        // Draw glyph bitmap
-        let fg_r = (fg >> 16) & 0xFF;
-        let fg_g = (fg >> 8) & 0xFF;
-        let fg_b = fg & 0xFF;
-
-        for gy in 0..glyph.height {
-            let py = glyph_y + gy;
-            if py >= self.height {
-                break;
-            }
-
-            for gx in 0..glyph.width {
-                let px = glyph_x + gx;
-                if px >= self.width {
-                    break;
-                }
-
-                let alpha = glyph.bitmap[(gy * glyph.width + gx) as usize] as u32;
-                if alpha == 0 {
-                    continue;
-                }
-
-                let idx = (py * self.width + px) as usize;
-                if idx >= self.buffer.len() {
-                    continue;
-                }
-
-                // Alpha blend
-                let bg = self.buffer[idx];
-                let bg_r = (bg >> 16) & 0xFF;
-                let bg_g = (bg >> 8) & 0xFF;
-                let bg_b = bg & 0xFF;
+        // Monospace fonts draw wide chars one cell wide, if at all
+        let x = if flags.contains(Flags::WIDE_CHAR) {
+            x + ((2 * self.cell_width) as f32 - glyph.advance).max(0.0) as u32 / 2
+        } else {
+            x
+        };
 
-                let r = (fg_r * alpha + bg_r * (255 - alpha)) / 255;
-                let g = (fg_g * alpha + bg_g * (255 - alpha)) / 255;
-                let b = (fg_b * alpha + bg_b * (255 - alpha)) / 255;
+        let baseline_y = (y + self.cell_height) as i32 - 4; // Approximate baseline
+        let (width, height) = (self.width, self.height);
+        blend_glyph(&mut self.buffer, width, height, glyph, x as i32, baseline_y, fg);
+    }
 
-                self.buffer[idx] = 0xFF000000 | (r << 16) | (g << 8) | b;
+    /// Draw glyph `glyph_id` of the font (from shaping) into the cell at `x`, `y`
+    fn draw_glyph_id(&mut self, x: i32, y: i32, glyph_id: u16, fg: u32) {
+        let Some(font) = &self.font else {
+            return;
+        };
+        let cache_key = (glyph_id, (font.size * 10.0) as u32);
+        self.indexed_glyph_cache.entry(cache_key).or_insert_with(|| {
+            let (metrics, bitmap) = font.font.rasterize_indexed(glyph_id, font.size);
+            GlyphData {
+                bitmap,
+                width: metrics.width as u32,
+                height: metrics.height as u32,
+                x_offset: metrics.xmin,
+                y_offset: metrics.ymin,
+                advance: metrics.advance_width,
             }
-        }
+        });
+        let glyph = &self.indexed_glyph_cache[&cache_key];
+        let baseline_y = y + self.cell_height as i32 - 4;
+        let (width, height) = (self.width, self.height);
+        blend_glyph(&mut self.buffer, width, height, glyph, x, baseline_y, fg);
     }
 
     fn color_to_argb(&self, color: &Color) -> u32 {
@@ -500,10 +940,10 @@ impl TerminalRenderer {
             NamedColor::BrightMagenta => 0xFFFF00FF,
             NamedColor::BrightCyan => 0xFF00FFFF,
             NamedColor::BrightWhite => 0xFFFFFFFF,
-            NamedColor::Foreground => self.theme.foreground(),
-            NamedColor::Background => self.theme.background(),
-            NamedColor::Cursor => self.theme.cursor_color(),
-            _ => self.theme.foreground(),
+            NamedColor::Foreground => self.foreground(),
+            NamedColor::Background => self.background(),
+            NamedColor::Cursor => self.cursor_color(),
+            _ => self.foreground(),
         }
     }
 
@@ -564,6 +1004,41 @@ impl Default for TerminalRenderer {
 mod tests {
     use crate::Terminal;
 
+    #[test]
+    fn palette_follows_theme() {
+        let mut renderer = super::TerminalRenderer::with_theme(super::Theme::Dark);
+        assert_eq!(renderer.palette_rgb(257), super::Rgb { r: 0x1A, g: 0x1A, b: 0x1A });
+        renderer.set_theme(super::Theme::Light);
+        assert_eq!(renderer.palette_rgb(257), super::Rgb { r: 0xFF, g: 0xFF, b: 0xFF });
+        // Indexed colors don't depend on the theme
+        assert_eq!(renderer.palette_rgb(1), super::Rgb { r: 0xCC, g: 0, b: 0 });
+    }
+
+    #[test]
+    fn high_contrast_uses_black_and_white() {
+        let mut renderer = super::TerminalRenderer::with_theme(super::Theme::Dark);
+        renderer.set_contrast(super::Contrast { high: true, minimum: 1.0 });
+        assert_eq!(renderer.palette_rgb(256), super::Rgb { r: 0xFF, g: 0xFF, b: 0xFF });
+        assert_eq!(renderer.palette_rgb(257), super::Rgb { r: 0, g: 0, b: 0 });
+        renderer.set_theme(super::Theme::Light);
+        assert_eq!(renderer.palette_rgb(256), super::Rgb { r: 0, g: 0, b: 0 });
+    }
+
+    #[test]
+    fn minimum_contrast_is_enforced() {
+        use super::{contrast_ratio, ensure_contrast};
+        assert!((contrast_ratio(0xFF000000, 0xFFFFFFFF) - 21.0).abs() < 0.01);
+        // Dark blue on a dark background is lifted toward white, just enough
+        let (fg, bg) = (0xFF0000AA, 0xFF1A1A1A);
+        let adjusted = ensure_contrast(fg, bg, 4.5);
+        assert!(contrast_ratio(adjusted, bg) >= 4.5);
+        assert!(contrast_ratio(adjusted, bg) < 5.0);
+        // Already readable colors are left alone
+        assert_eq!(ensure_contrast(0xFFFFFFFF, bg, 4.5), 0xFFFFFFFF);
+        // Impossible minimums give the best available
+        assert_eq!(ensure_contrast(0xFF808080, 0xFF808080, 22.0), 0xFF000000);
+    }
+
     /// Default theme's selection background color (for tests)
     const TEST_SELECTION_BG: u32 = 0xFF264F78;
 
@@ -645,6 +1120,102 @@ mod tests {
         }
     }
 
+    /// Pixels of the cell at `col`, `row` (80 columns rendered)
+    fn cell_pixels(terminal: &Terminal, col: u32, row: u32) -> Vec<u32> {
+        let (cell_width, cell_height) = terminal.cell_size();
+        let width = 80 * cell_width;
+        let buffer = terminal.buffer();
+        (row * cell_height..(row + 1) * cell_height)
+            .flat_map(|y| (col * cell_width..(col + 1) * cell_width).map(move |x| (y * width + x) as usize))
+            .map(|idx| buffer[idx])
+            .collect()
+    }
+
+    fn render_text(text: &str) -> Terminal {
+        let mut terminal = Terminal::new(80, 24).expect("terminal creation");
+        terminal.inject_bytes(text.as_bytes());
+        let (cell_width, cell_height) = terminal.cell_size();
+        terminal.render(80 * cell_width, 24 * cell_height, false);
+        terminal
+    }
+
+    #[test]
+    fn wide_char_is_not_painted_over_by_its_spacer() {
+        let terminal = render_text("日x\r\n");
+        let bg = get_bg_color_at(&terminal, 3, 0);
+        // The glyph is centered across both cells, so the right cell has ink
+        assert!(cell_pixels(&terminal, 1, 0).iter().any(|&pixel| pixel != bg));
+        // and `x` sits in column 2, after the wide char
+        assert!(cell_pixels(&terminal, 2, 0).iter().any(|&pixel| pixel != bg));
+    }
+
+    #[test]
+    fn selecting_half_a_wide_char_highlights_both_cells() {
+        let mut terminal = Terminal::new(80, 24).expect("terminal creation");
+        terminal.inject_bytes("日本\r\n".as_bytes());
+        terminal.start_selection(0, 0);
+        terminal.update_selection(0, 0, 0, 0);
+
+        let (cell_width, cell_height) = terminal.cell_size();
+        terminal.render(80 * cell_width, 24 * cell_height, true);
+        assert_eq!(get_bg_color_at(&terminal, 0, 0), TEST_SELECTION_BG);
+        assert_eq!(get_bg_color_at(&terminal, 1, 0), TEST_SELECTION_BG);
+        assert_ne!(get_bg_color_at(&terminal, 2, 0), TEST_SELECTION_BG);
+    }
+
+    #[test]
+    fn combining_marks_are_drawn_over_their_base() {
+        let plain = render_text("e\r\n");
+        let accented = render_text("e\u{301}x\r\n");
+        assert_ne!(cell_pixels(&plain, 0, 0), cell_pixels(&accented, 0, 0));
+        // The mark takes no column of its own
+        assert_eq!(cell_pixels(&plain, 1, 0), cell_pixels(&render_text(" \r\n"), 1, 0));
+        assert_ne!(cell_pixels(&accented, 1, 0), cell_pixels(&plain, 1, 0));
+    }
+
+    #[test]
+    fn emoji_sequences_draw_only_their_base() {
+        use super::combining_marks;
+        // 👨‍👩‍👧 is one wide cell holding the joiners and the other emoji
+        let family = ['\u{200D}', '\u{1F469}', '\u{200D}', '\u{1F467}'];
+        assert_eq!(combining_marks(&family).count(), 0);
+        assert_eq!(combining_marks(&['\u{FE0F}']).count(), 0);
+        assert_eq!(combining_marks(&['\u{301}', '\u{323}']).collect::<Vec<_>>(), ['\u{301}', '\u{323}']);
+
+        let terminal = render_text("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}x\r\n");
+        let bg = get_bg_color_at(&terminal, 3, 0);
+        assert!(cell_pixels(&terminal, 2, 0).iter().any(|&pixel| pixel != bg), "x follows the two cells");
+    }
+
+    #[test]
+    fn ligatures_keep_fonts_without_them_unchanged() {
+        let mut terminal = Terminal::new(80, 24).expect("terminal creation");
+        terminal.inject_bytes(b"a -> b != c :: d\r\n");
+        let (cell_width, cell_height) = terminal.cell_size();
+        let (width, height) = (80 * cell_width, 24 * cell_height);
+        terminal.render(width, height, true);
+        let plain = terminal.buffer().to_vec();
+        terminal.set_ligatures(true);
+        terminal.render(width, height, true);
+        assert_eq!(terminal.buffer(), &plain[..]);
+    }
+
+    #[test]
+    fn ligature_glyphs_are_placed_on_their_cells() {
+        use super::shape_ligatures;
+        let Ok(mono) = std::fs::read("/usr/share/fonts/truetype/dejavu/DejaVuSansMono.ttf") else {
+            return;
+        };
+        assert_eq!(shape_ligatures(&mono, "->", 14.0), None);
+
+        // DejaVu Sans joins "fi" into one glyph, drawn from the cell of the f
+        let Ok(sans) = std::fs::read("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf") else {
+            return;
+        };
+        let glyphs = shape_ligatures(&sans, "xfi", 14.0).expect("fi ligature");
+        assert_eq!(glyphs.iter().map(|glyph| (glyph.cell, glyph.x)).collect::<Vec<_>>(), [(0, 0), (1, 0)]);
+    }
+
     #[test]
     fn cleared_selection_restores_normal_background() {
         let mut terminal = Terminal::new(80, 24).expect("terminal creation");
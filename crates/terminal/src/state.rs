@@ -2,7 +2,7 @@
 //!
 //! Wraps alacritty_terminal with PTY and sizing state machine.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use std::sync::Arc;
 
@@ -10,13 +10,17 @@ use alacritty_terminal::event::{Event, EventListener};
 use alacritty_terminal::grid::Dimensions;
 use alacritty_terminal::index::{Column, Line, Point, Side};
 use alacritty_terminal::selection::{Selection, SelectionType};
+use alacritty_terminal::term::cell::Flags;
 use alacritty_terminal::term::Config as TermConfig;
 use alacritty_terminal::term::{Term, TermMode};
 use alacritty_terminal::sync::FairMutex;
 use alacritty_terminal::vte::ansi;
 
-use crate::pty::{Pty, PtyError};
-use crate::render::{Theme, TerminalRenderer};
+use crate::compress::CompressedGrid;
+use crate::export::ExportFormat;
+use crate::fold::FoldMap;
+use crate::pty::{ProcessTable, Pty, PtyError};
+use crate::render::{Contrast, Theme, TerminalRenderer};
 use crate::sizing::{SizingAction, TerminalSizingState};
 
 use thiserror::Error;
@@ -55,10 +59,173 @@ pub enum TerminalEvent {
     Exited,
 }
 
+/// Color scheme requests found in PTY output
+///
+/// alacritty_terminal doesn't know the color palette update protocol (mode
+/// 2031), so its sequences are picked out of the output before parsing.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ColorSchemeRequests {
+    /// Reports turned on (`CSI ? 2031 h`) or off (`CSI ? 2031 l`), last one wins
+    reports: Option<bool>,
+    /// The current scheme was queried (`CSI ? 996 n`)
+    query: bool,
+}
+
+fn scan_color_scheme_requests(data: &[u8]) -> ColorSchemeRequests {
+    let mut requests = ColorSchemeRequests::default();
+    for (i, window) in data.windows(3).enumerate() {
+        if window != b"\x1b[?" {
+            continue;
+        }
+        let rest = &data[i + 3..];
+        if rest.starts_with(b"2031h") {
+            requests.reports = Some(true);
+        } else if rest.starts_with(b"2031l") {
+            requests.reports = Some(false);
+        } else if rest.starts_with(b"996n") {
+            requests.query = true;
+        }
+    }
+    requests
+}
+
+/// Start of the sequence a program sends to name its cell:
+/// `OSC 777 ; termstack-title ; <text>`, ended by BEL or ST
+const TITLE_SEQUENCE: &[u8] = b"\x1b]777;termstack-title;";
+
+/// The last cell title requested in PTY output, empty to clear it
+///
+/// alacritty_terminal ignores OSC 777, so like the color scheme requests the
+/// sequence is picked out of the output before parsing. One split across
+/// reads is missed.
+fn scan_title_request(data: &[u8]) -> Option<String> {
+    let mut title = None;
+    let mut rest = data;
+    while let Some(start) = rest.windows(TITLE_SEQUENCE.len()).position(|window| window == TITLE_SEQUENCE) {
+        rest = &rest[start + TITLE_SEQUENCE.len()..];
+        let Some(end) = rest.iter().position(|&byte| byte == 0x07 || byte == 0x1b) else {
+            break;
+        };
+        title = Some(String::from_utf8_lossy(&rest[..end]).into_owned());
+        rest = &rest[end..];
+    }
+    title
+}
+
+/// Progress a program reports with `OSC 9 ; 4 ; <state> ; <percent>`
+///
+/// The ConEmu sequence Windows Terminal adopted, sent by systemd, winget and
+/// some build tool wrappers. Percentages are clamped to 0-100.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Progress {
+    /// State 1: under way
+    Normal(u8),
+    /// State 2: failing, at this point
+    Error(u8),
+    /// State 3: busy, with no idea how far along
+    Indeterminate,
+    /// State 4: paused or warning, at this point
+    Paused(u8),
+}
+
+impl Progress {
+    /// How far along, if known
+    pub fn percent(self) -> Option<u8> {
+        match self {
+            Self::Normal(percent) | Self::Error(percent) | Self::Paused(percent) => Some(percent),
+            Self::Indeterminate => None,
+        }
+    }
+}
+
+/// Start of the progress sequence: `OSC 9 ; 4 ;`
+const PROGRESS_SEQUENCE: &[u8] = b"\x1b]9;4;";
+
+/// The last progress report in PTY output: `Some(None)` when the program
+/// cleared it (state 0), `None` when there was no report
+///
+/// Picked out of the output like the title requests, and like them one
+/// split across reads is missed. Unknown states are ignored.
+fn scan_progress_report(data: &[u8]) -> Option<Option<Progress>> {
+    let mut report = None;
+    let mut rest = data;
+    while let Some(start) = rest.windows(PROGRESS_SEQUENCE.len()).position(|window| window == PROGRESS_SEQUENCE) {
+        rest = &rest[start + PROGRESS_SEQUENCE.len()..];
+        let Some(end) = rest.iter().position(|&byte| byte == 0x07 || byte == 0x1b) else {
+            break;
+        };
+        let params = String::from_utf8_lossy(&rest[..end]);
+        rest = &rest[end..];
+
+        let mut params = params.split(';');
+        let state = params.next().unwrap_or("");
+        let percent = params
+            .next()
+            .and_then(|percent| percent.trim().parse::<u32>().ok())
+            .map_or(0, |percent| percent.min(100) as u8);
+        report = match state.trim() {
+            "0" => Some(None),
+            "1" => Some(Some(Progress::Normal(percent))),
+            "2" => Some(Some(Progress::Error(percent))),
+            "3" => Some(Some(Progress::Indeterminate)),
+            "4" => Some(Some(Progress::Paused(percent))),
+            _ => report,
+        };
+    }
+    report
+}
+
+/// Shell integration marks (FinalTerm's `OSC 133`): `A` where a prompt
+/// starts, `C` where the command line was accepted and its output begins
+const PROMPT_START_SEQUENCE: &[u8] = b"\x1b]133;A";
+const COMMAND_START_SEQUENCE: &[u8] = b"\x1b]133;C";
+
+/// Feed `data` through the parser, keeping `prompt_start` at the line (counted
+/// from the top of the scrollback) where the shell last began a prompt
+///
+/// The line is only known while the bytes are parsed, so unlike the other
+/// sequences these marks aren't picked out beforehand. A command starting
+/// forgets the mark, so a nested shell without integration doesn't inherit it.
+fn advance_marking_prompts(
+    parser: &mut ansi::Processor,
+    term: &mut Term<TerminalEventProxy>,
+    data: &[u8],
+    prompt_start: &mut Option<usize>,
+) {
+    for (i, byte) in data.iter().enumerate() {
+        if *byte == 0x1b {
+            let rest = &data[i..];
+            if rest.starts_with(PROMPT_START_SEQUENCE) {
+                let grid = term.grid();
+                let line = grid.history_size() as i32 + grid.cursor.point.line.0;
+                *prompt_start = Some(line.max(0) as usize);
+            } else if rest.starts_with(COMMAND_START_SEQUENCE) {
+                *prompt_start = None;
+            }
+        }
+        parser.advance(&mut *term, *byte);
+    }
+}
+
+/// Move a point on the right half of a wide char to the char itself
+///
+/// Clicks use plain cell math, so the second column of a double-width
+/// character lands on its spacer cell.
+fn wide_char_start<T>(term: &Term<T>, point: Point) -> Point {
+    let in_grid = point.column.0 < term.columns()
+        && point.line >= term.topmost_line()
+        && point.line <= term.bottommost_line();
+    if in_grid && point.column.0 > 0 && term.grid()[point].flags.contains(Flags::WIDE_CHAR_SPACER) {
+        Point::new(point.line, point.column - 1)
+    } else {
+        point
+    }
+}
+
 /// Simple size struct implementing Dimensions
-struct Size {
-    cols: usize,
-    rows: usize,
+pub(crate) struct Size {
+    pub(crate) cols: usize,
+    pub(crate) rows: usize,
 }
 
 impl Dimensions for Size {
@@ -80,6 +247,12 @@ pub struct Terminal {
     /// Terminal state from alacritty
     term: Arc<FairMutex<Term<TerminalEventProxy>>>,
 
+    /// The grid while it is compressed (see [`crate::compress`])
+    compressed: FairMutex<Option<CompressedGrid>>,
+
+    /// Sender of the terminal's event channel, for rebuilt grids
+    event_sender: std::sync::mpsc::Sender<TerminalEvent>,
+
     /// VTE parser
     parser: ansi::Processor,
 
@@ -116,6 +289,37 @@ pub struct Terminal {
     /// This is updated during render() and used by scroll_display()
     /// to properly clamp the viewport offset to the visual maximum.
     last_visual_rows: usize,
+
+    /// The program asked to be told about theme changes (mode 2031)
+    color_scheme_reports: bool,
+
+    /// Cell title the program asked for since the last `take_title_request()`
+    title_request: Option<String>,
+
+    /// Progress the program last reported (OSC 9;4)
+    progress: Option<Progress>,
+
+    /// The output's grid while another text is shown in its place
+    set_aside: Option<Term<TerminalEventProxy>>,
+
+    /// Collapse runs of identical output lines (see [`crate::fold`])
+    fold_repeated_lines: bool,
+
+    /// Folded runs the user expanded, by first grid line
+    expanded_folds: HashSet<usize>,
+
+    /// Folding as of the last render, for mapping clicks to lines
+    folds: FoldMap,
+
+    /// First displayed row of the last render
+    first_visible_row: usize,
+
+    /// PTY output read since the last `take_recorded_output`, when recording
+    recorded_output: Option<Vec<u8>>,
+
+    /// Line the shell's current prompt starts on, counted from the top of the
+    /// scrollback, if the shell marks prompts (`OSC 133 ; A`)
+    prompt_start: Option<usize>,
 }
 
 impl Terminal {
@@ -131,6 +335,17 @@ impl Terminal {
 
     /// Create a new terminal running an interactive shell with theme and font size
     pub fn new_with_options(cols: u16, rows: u16, theme: Theme, font_size: f32) -> Result<Self, TerminalError> {
+        Self::new_with_options_in(cols, rows, theme, font_size, None)
+    }
+
+    /// Like [`Self::new_with_options`], with the shell started in `working_dir`
+    pub fn new_with_options_in(
+        cols: u16,
+        rows: u16,
+        theme: Theme,
+        font_size: f32,
+        working_dir: Option<&Path>,
+    ) -> Result<Self, TerminalError> {
         let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
 
         // Use 1000 rows for PTY and grid to prevent internal scrolling
@@ -138,11 +353,11 @@ impl Terminal {
         let pty_rows = 1000u16;
 
         // Create PTY with large size so shell doesn't scroll internally
-        let pty = Pty::spawn(&shell, cols, pty_rows)?;
+        let pty = Pty::spawn_in(&shell, cols, pty_rows, working_dir)?;
 
         // Create event channel
         let (sender, receiver) = std::sync::mpsc::channel();
-        let event_proxy = TerminalEventProxy { sender };
+        let event_proxy = TerminalEventProxy { sender: sender.clone() };
 
         // Create terminal grid with large size to store all output
         let config = TermConfig::default();
@@ -169,6 +384,8 @@ impl Terminal {
 
         Ok(Self {
             term,
+            compressed: FairMutex::new(None),
+            event_sender: sender,
             parser,
             pty,
             sizing,
@@ -179,6 +396,16 @@ impl Terminal {
             pty_rows,
             viewport_offset: 0,
             last_visual_rows: rows as usize,
+            color_scheme_reports: false,
+            title_request: None,
+            progress: None,
+            set_aside: None,
+            fold_repeated_lines: false,
+            expanded_folds: HashSet::new(),
+            folds: FoldMap::default(),
+            first_visible_row: 0,
+            recorded_output: None,
+            prompt_start: None,
         })
     }
 
@@ -233,7 +460,7 @@ impl Terminal {
 
         // Create event channel
         let (sender, receiver) = std::sync::mpsc::channel();
-        let event_proxy = TerminalEventProxy { sender };
+        let event_proxy = TerminalEventProxy { sender: sender.clone() };
 
         // Create terminal grid with large size to store all output
         let config = TermConfig::default();
@@ -260,6 +487,8 @@ impl Terminal {
 
         Ok(Self {
             term,
+            compressed: FairMutex::new(None),
+            event_sender: sender,
             parser,
             pty,
             sizing,
@@ -270,6 +499,16 @@ impl Terminal {
             pty_rows,
             viewport_offset: 0,
             last_visual_rows: visual_rows as usize,
+            color_scheme_reports: false,
+            title_request: None,
+            progress: None,
+            set_aside: None,
+            fold_repeated_lines: false,
+            expanded_folds: HashSet::new(),
+            folds: FoldMap::default(),
+            first_visible_row: 0,
+            recorded_output: None,
+            prompt_start: None,
         })
     }
 
@@ -289,60 +528,10 @@ impl Terminal {
                 Ok(0) => break,
                 Ok(n) => {
                     total_read += n;
-                    let mut term = self.term.lock();
-
-                    // Check if in alternate screen BEFORE processing (for logging)
-                    let was_alt = term.mode().contains(TermMode::ALT_SCREEN);
-
-                    // Process bytes through VTE parser
-                    for byte in &buf[..n] {
-                        self.parser.advance(&mut *term, *byte);
+                    if let Some(recorded) = &mut self.recorded_output {
+                        recorded.extend_from_slice(&buf[..n]);
                     }
-
-                    // Check if in alternate screen AFTER processing
-                    let is_alt = term.mode().contains(TermMode::ALT_SCREEN);
-
-                    // Use last non-empty line for growth decisions
-                    // This avoids showing empty rows when cursor is on an empty line
-                    if !is_alt && !was_alt {
-                        let cursor_line = term.grid().cursor.point.line.0 as u16;
-                        let visual_rows = self.sizing.current_rows();
-
-                        // Find last non-empty line for content-based sizing
-                        let last_content = {
-                            let grid = term.grid();
-                            let mut last = 0u16;
-                            for line_idx in (0..=cursor_line).rev() {
-                                let line = &grid[alacritty_terminal::index::Line(line_idx as i32)];
-                                let has_content = line.into_iter().any(|cell| {
-                                    let c = cell.c;
-                                    c != ' ' && c != '\0'
-                                });
-                                if has_content {
-                                    last = line_idx;
-                                    break;
-                                }
-                            }
-                            last
-                        };
-
-                        // Update content_rows to last content line + 1 (0-indexed)
-                        let content_line = (last_content + 1) as u32;
-                        if content_line > self.sizing.content_rows() {
-                            while self.sizing.content_rows() < content_line {
-                                self.sizing.on_new_line();
-                            }
-                        }
-
-                        // Request growth if content exceeds visual rows
-                        if last_content >= visual_rows {
-                            let target_rows = last_content + 1;
-                            tracing::debug!(cursor_line, last_content, visual_rows, target_rows, "content exceeded visual size, requesting growth");
-                            actions.push(SizingAction::RequestGrowth { target_rows });
-                        }
-                    }
-
-                    drop(term);
+                    actions.extend(self.process_output(&buf[..n]));
                 }
                 Err(_) => break,
             }
@@ -350,17 +539,104 @@ impl Terminal {
 
         // Process terminal events (e.g., PtyWrite for terminal query responses)
         for event in self.events.try_iter() {
-            if let TerminalEvent::Alacritty(Event::PtyWrite(text)) = event {
-                tracing::debug!(len = text.len(), "writing terminal response to PTY");
-                if let Err(e) = self.pty.write(text.as_bytes()) {
-                    tracing::warn!("failed to write terminal response: {:?}", e);
+            let text = match event {
+                TerminalEvent::Alacritty(Event::PtyWrite(text)) => text,
+                // OSC 4/10/11/12 queries, answered from the current theme
+                TerminalEvent::Alacritty(Event::ColorRequest(index, format)) => {
+                    format(self.renderer.palette_rgb(index))
                 }
+                _ => continue,
+            };
+            tracing::debug!(len = text.len(), "writing terminal response to PTY");
+            if let Err(e) = self.pty.write(text.as_bytes()) {
+                tracing::warn!("failed to write terminal response: {:?}", e);
             }
         }
 
         (actions, total_read)
     }
 
+    /// Feed output read from the PTY through the parser and the sizing logic
+    ///
+    /// This is what `process_pty` does with every chunk it reads; the fuzz
+    /// targets call it directly.
+    pub fn process_output(&mut self, data: &[u8]) -> Vec<SizingAction> {
+        let mut actions = Vec::new();
+        let requests = scan_color_scheme_requests(data);
+        if let Some(reports) = requests.reports {
+            self.color_scheme_reports = reports;
+        }
+        if requests.query {
+            self.report_color_scheme();
+        }
+        if let Some(title) = scan_title_request(data) {
+            self.title_request = Some(title);
+        }
+        if let Some(progress) = scan_progress_report(data) {
+            self.progress = progress;
+        }
+
+        self.restore_scrollback();
+        let mut term = self.term.lock();
+
+        // Check if in alternate screen BEFORE processing (for logging)
+        let was_alt = term.mode().contains(TermMode::ALT_SCREEN);
+
+        // Process bytes through VTE parser
+        advance_marking_prompts(&mut self.parser, &mut term, data, &mut self.prompt_start);
+
+        // Check if in alternate screen AFTER processing
+        let is_alt = term.mode().contains(TermMode::ALT_SCREEN);
+
+        // Use last non-empty line for growth decisions
+        // This avoids showing empty rows when cursor is on an empty line
+        if !is_alt && !was_alt {
+            let cursor_line = term.grid().cursor.point.line.0 as u16;
+            let visual_rows = self.sizing.current_rows();
+
+            // Find last non-empty line for content-based sizing
+            let last_content = {
+                let grid = term.grid();
+                let mut last = 0u16;
+                for line_idx in (0..=cursor_line).rev() {
+                    let line = &grid[alacritty_terminal::index::Line(line_idx as i32)];
+                    let has_content = line.into_iter().any(|cell| {
+                        let c = cell.c;
+                        c != ' ' && c != '\0'
+                    });
+                    if has_content {
+                        last = line_idx;
+                        break;
+                    }
+                }
+                if self.fold_repeated_lines {
+                    let folds = FoldMap::for_term(&term, &self.expanded_folds);
+                    folds.row_containing(last as usize) as u16
+                } else {
+                    last
+                }
+            };
+
+            // Update content_rows to last content line + 1 (0-indexed)
+            let content_line = (last_content + 1) as u32;
+            if content_line > self.sizing.content_rows() {
+                while self.sizing.content_rows() < content_line {
+                    self.sizing.on_new_line();
+                }
+            }
+
+            // Request growth if content exceeds visual rows
+            if last_content >= visual_rows {
+                let target_rows = last_content + 1;
+                tracing::debug!(cursor_line, last_content, visual_rows, target_rows, "content exceeded visual size, requesting growth");
+                actions.push(SizingAction::RequestGrowth { target_rows });
+            }
+        }
+
+        drop(term);
+        actions
+    }
+
     /// Write input to terminal (non-blocking)
     ///
     /// Returns the number of bytes written. May return less than `data.len()`
@@ -369,21 +645,31 @@ impl Terminal {
         Ok(self.pty.write(data)?)
     }
 
+    /// Cell title the program set with `OSC 777 ; termstack-title ; <text>`
+    /// since the last call (empty to go back to the default title)
+    pub fn take_title_request(&mut self) -> Option<String> {
+        self.title_request.take()
+    }
+
+    /// Progress the program last reported with `OSC 9 ; 4`, until it clears it
+    pub fn progress(&self) -> Option<Progress> {
+        self.progress
+    }
+
     /// Directly process bytes through terminal emulator (for testing)
     ///
     /// Unlike process_pty, this doesn't read from PTY but directly feeds
     /// the given bytes to the VTE parser. Useful for simulating terminal
     /// output in tests.
     pub fn inject_bytes(&mut self, data: &[u8]) {
+        self.restore_scrollback();
         let mut term = self.term.lock();
         let was_alt = term.mode().contains(TermMode::ALT_SCREEN);
 
         // Count only \n for line endings - \r is cursor control
         let newlines = data.iter().filter(|&&b| b == b'\n').count();
 
-        for byte in data {
-            self.parser.advance(&mut *term, *byte);
-        }
+        advance_marking_prompts(&mut self.parser, &mut term, data, &mut self.prompt_start);
 
         let is_alt = term.mode().contains(TermMode::ALT_SCREEN);
         drop(term);
@@ -418,7 +704,7 @@ impl Terminal {
                     cols: self.cols as usize,
                     rows: rows as usize,
                 };
-                let mut term = self.term.lock();
+                let mut term = self.lock_term();
                 term.resize(size);
             }
         }
@@ -449,7 +735,7 @@ impl Terminal {
             cols: cols as usize,
             rows: self.grid_rows() as usize,
         };
-        let mut term = self.term.lock();
+        let mut term = self.lock_term();
         term.resize(size);
     }
 
@@ -464,8 +750,231 @@ impl Terminal {
         let (_, cell_height) = self.renderer.cell_size();
         self.last_visual_rows = (height / cell_height).max(1) as usize;
 
+        self.restore_scrollback();
         let term = self.term.lock();
-        self.renderer.render(&term, width, height, show_cursor, self.viewport_offset);
+        let folding = self.fold_repeated_lines && self.set_aside.is_none();
+        self.folds = if folding && !term.mode().contains(TermMode::ALT_SCREEN) {
+            FoldMap::for_term(&term, &self.expanded_folds)
+        } else {
+            FoldMap::default()
+        };
+        self.first_visible_row =
+            self.renderer.render_folded(&term, width, height, show_cursor, self.viewport_offset, &self.folds);
+    }
+
+    /// Scrollback and grid as text, ANSI text or an HTML page titled `title`
+    ///
+    /// Exports the output even while a view stands in for it.
+    pub fn export(&self, format: ExportFormat, title: &str) -> String {
+        if let Some(output) = &self.set_aside {
+            return crate::export::export(output, &self.renderer, format, title);
+        }
+        let term = self.lock_term();
+        crate::export::export(&term, &self.renderer, format, title)
+    }
+
+    /// Swap the grid for a compressed copy until it is next read
+    ///
+    /// Returns the compressed size, or `None` if the grid stays as it is:
+    /// already compressed, or showing the alternate screen.
+    pub fn compress_scrollback(&mut self) -> Option<usize> {
+        let mut term = self.term.lock();
+        let mut compressed = self.compressed.lock();
+        if compressed.is_some() || term.mode().contains(TermMode::ALT_SCREEN) {
+            return None;
+        }
+        let grid = match CompressedGrid::new(&term, &self.renderer) {
+            Ok(grid) => grid,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to compress scrollback");
+                return None;
+            }
+        };
+        // A one-line grid stands in until the real one is rebuilt
+        let size = Size { cols: term.columns(), rows: 1 };
+        let proxy = TerminalEventProxy { sender: self.event_sender.clone() };
+        *term = Term::new(TermConfig::default(), &size, proxy);
+        let len = grid.len();
+        *compressed = Some(grid);
+        Some(len)
+    }
+
+    /// Whether the grid is compressed right now
+    pub fn is_scrollback_compressed(&self) -> bool {
+        self.compressed.lock().is_some()
+    }
+
+    /// Rebuild the grid if it was compressed
+    fn restore_scrollback(&self) {
+        let mut term = self.term.lock();
+        if let Some(grid) = self.compressed.lock().take() {
+            let proxy = TerminalEventProxy { sender: self.event_sender.clone() };
+            match grid.restore(proxy) {
+                Ok(restored) => *term = restored,
+                Err(e) => tracing::warn!(error = %e, "failed to restore compressed scrollback"),
+            }
+        }
+    }
+
+    /// Lock the grid, rebuilding it first if it was compressed
+    fn lock_term(&self) -> impl std::ops::DerefMut<Target = Term<TerminalEventProxy>> + '_ {
+        self.restore_scrollback();
+        self.term.lock()
+    }
+
+    /// Write `text` (ANSI text, `\n` line endings) above the prompt the cursor
+    /// is in, as if it had been printed before it
+    ///
+    /// The prompt starts where the shell marked it (`OSC 133 ; A`), or without
+    /// shell integration at the first row of the cursor's wrapped line. Rows
+    /// are inserted there, so the prompt and what was typed move down intact
+    /// (multi-line, wrapped and right prompts included) and the cursor keeps
+    /// its place in them, where the shell expects it. Lines of `text` wider
+    /// than the terminal are cut off.
+    pub fn insert_above_cursor_line(&mut self, text: &str) {
+        let lines: Vec<&str> = text.lines().collect();
+        if lines.is_empty() {
+            return;
+        }
+        let count = lines.len() as i32;
+
+        let (mut start, cursor, screen_lines, autowrap, mark_moves) = {
+            let term = self.lock_term();
+            let grid = term.grid();
+            let cursor = grid.cursor.point.line.0;
+            let last_column = Column(term.columns() - 1);
+            let mut start = cursor;
+            while start > 0 && grid[Line(start - 1)][last_column].flags.contains(Flags::WRAPLINE) {
+                start -= 1;
+            }
+            let mark = self.prompt_start.map(|mark| mark as i32 - grid.history_size() as i32);
+            if let Some(mark) = mark.filter(|&mark| mark <= cursor) {
+                start = start.min(mark.max(0));
+            }
+            let mark_moves = mark.is_some_and(|mark| (start..=cursor).contains(&mark));
+            (start, cursor, term.screen_lines() as i32, term.mode().contains(TermMode::LINE_WRAP), mark_moves)
+        };
+
+        // Save the cursor with its colors, and scroll if the rows below it
+        // can't take the inserted lines
+        let scroll = (count - (screen_lines - 1 - cursor)).max(0);
+        let mut bytes = String::from("\x1b7");
+        if scroll > 0 {
+            bytes.push_str(&format!("\x1b[{scroll}S"));
+            start = (start - scroll).max(0);
+        }
+        bytes.push_str(&format!("\x1b[{};1H\x1b[{count}L", start + 1));
+        if autowrap {
+            bytes.push_str("\x1b[?7l");
+        }
+        for (i, line) in lines.iter().enumerate() {
+            bytes.push_str(&format!("\x1b[{};1H{line}\x1b[0m", start + 1 + i as i32));
+        }
+        if autowrap {
+            bytes.push_str("\x1b[?7h");
+        }
+        // Back to the cursor, which moved down with the prompt
+        bytes.push_str("\x1b8");
+        let shift = count - scroll;
+        if shift > 0 {
+            bytes.push_str(&format!("\x1b[{shift}B"));
+        } else if shift < 0 {
+            bytes.push_str(&format!("\x1b[{}A", -shift));
+        }
+        self.inject_bytes(bytes.as_bytes());
+
+        if let Some(mark) = self.prompt_start.as_mut().filter(|_| mark_moves) {
+            *mark += count as usize;
+        }
+        for _ in 0..count {
+            self.sizing.on_new_line();
+        }
+    }
+
+    /// Show `text` (ANSI text, `\n` line endings) in place of the output
+    ///
+    /// The output's grid is set aside until [`Self::hide_view`]; showing
+    /// another text replaces the one shown. Repeated lines aren't folded in
+    /// the view, so its rows are its lines. Returns how many lines it has.
+    pub fn show_view(&mut self, text: &str) -> usize {
+        let mut bytes = String::new();
+        for (i, line) in text.lines().enumerate() {
+            if i > 0 {
+                bytes.push_str("\r\n");
+            }
+            bytes.push_str(line);
+            bytes.push_str("\x1b[0m");
+        }
+
+        let mut term = self.lock_term();
+        let size = Size { cols: term.columns(), rows: term.screen_lines() };
+        let proxy = TerminalEventProxy { sender: self.event_sender.clone() };
+        let mut view = Term::new(TermConfig::default(), &size, proxy);
+        let mut parser: ansi::Processor = ansi::Processor::new();
+        for byte in bytes.bytes() {
+            parser.advance(&mut view, byte);
+        }
+        let output = std::mem::replace(&mut *term, view);
+        drop(term);
+
+        self.set_aside.get_or_insert(output);
+        self.viewport_offset = 0;
+        text.lines().count()
+    }
+
+    /// Bring the output back after [`Self::show_view`]
+    ///
+    /// Returns false if no view was shown.
+    pub fn hide_view(&mut self) -> bool {
+        let Some(mut output) = self.set_aside.take() else {
+            return false;
+        };
+        let mut term = self.lock_term();
+        // The columns may have changed while the view was shown
+        output.resize(Size { cols: term.columns(), rows: term.screen_lines() });
+        *term = output;
+        drop(term);
+        self.viewport_offset = 0;
+        true
+    }
+
+    /// Whether a view is shown in place of the output
+    pub fn is_showing_view(&self) -> bool {
+        self.set_aside.is_some()
+    }
+
+    /// The output as plain text, with lines the terminal wrapped joined again
+    pub fn output_text(&self) -> String {
+        let term = self.lock_term();
+        let start = Point::new(term.topmost_line(), Column(0));
+        let end = Point::new(term.bottommost_line(), term.last_column());
+        let text = term.bounds_to_string(start, end);
+        text.trim_end().to_string()
+    }
+
+    /// Keep the raw PTY output for `take_recorded_output`
+    pub fn set_recording(&mut self, recording: bool) {
+        self.recorded_output = recording.then(Vec::new);
+    }
+
+    /// PTY output read since the last call (empty unless recording)
+    pub fn take_recorded_output(&mut self) -> Vec<u8> {
+        self.recorded_output.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Collapse runs of identical output lines into one `line ×N` row
+    pub fn set_fold_repeated_lines(&mut self, fold: bool) {
+        self.fold_repeated_lines = fold;
+    }
+
+    /// Expand the folded run shown in viewport `row` (0 = top)
+    ///
+    /// Returns false if the row isn't a folded run.
+    pub fn expand_fold_at_row(&mut self, row: usize) -> bool {
+        match self.folds.run_at(self.first_visible_row + row) {
+            Some(line) => self.expanded_folds.insert(line),
+            None => false,
+        }
     }
 
     /// Get rendered pixel buffer
@@ -473,11 +982,50 @@ impl Terminal {
         self.renderer.buffer()
     }
 
+    /// Get the size of the rendered pixel buffer (width, height)
+    pub fn buffer_dimensions(&self) -> (u32, u32) {
+        self.renderer.dimensions()
+    }
+
     /// Get cell size
     pub fn cell_size(&self) -> (u32, u32) {
         self.renderer.cell_size()
     }
 
+    /// Get the color theme
+    pub fn theme(&self) -> Theme {
+        self.renderer.theme()
+    }
+
+    /// Switch the color theme, telling the program if it asked for reports
+    pub fn set_theme(&mut self, theme: Theme) {
+        if theme == self.renderer.theme() {
+            return;
+        }
+        self.renderer.set_theme(theme);
+        if self.color_scheme_reports {
+            self.report_color_scheme();
+        }
+    }
+
+    /// Set the low-vision palette adjustments
+    pub fn set_contrast(&mut self, contrast: Contrast) {
+        self.renderer.set_contrast(contrast);
+    }
+
+    /// Draw programming ligatures (`->`, `!=`, ...) if the font has them
+    pub fn set_ligatures(&mut self, ligatures: bool) {
+        self.renderer.set_ligatures(ligatures);
+    }
+
+    /// Write the current color scheme report to the PTY
+    fn report_color_scheme(&mut self) {
+        let report = self.renderer.theme().color_scheme_report();
+        if let Err(e) = self.pty.write(report.as_bytes()) {
+            tracing::warn!("failed to write color scheme report: {:?}", e);
+        }
+    }
+
     /// Change font size at runtime, returning the new (cell_width, cell_height)
     pub fn set_font_size(&mut self, font_size: f32) -> Option<(u32, u32)> {
         let font_config = crate::render::FontConfig::default_font_with_size(font_size)?;
@@ -493,20 +1041,30 @@ impl Terminal {
     /// Get actual grid rows from alacritty terminal
     /// This is the number of rows the terminal grid can display
     pub fn grid_rows(&self) -> u16 {
-        let term = self.term.lock();
+        let term = self.lock_term();
         term.screen_lines() as u16
     }
 
+    /// Grid size as (columns, screen lines, scrollback lines)
+    pub fn grid_size(&self) -> (usize, usize, usize) {
+        let term = self.lock_term();
+        (term.columns(), term.screen_lines(), term.grid().history_size())
+    }
+
     /// Check if terminal is in alternate screen mode (used by TUI apps like vim, fzf, mc)
     pub fn is_alternate_screen(&self) -> bool {
-        let term = self.term.lock();
+        // Only the primary screen is ever compressed
+        if self.is_scrollback_compressed() {
+            return false;
+        }
+        let term = self.lock_term();
         term.mode().contains(TermMode::ALT_SCREEN)
     }
 
     /// Get cursor line (0-indexed row where cursor is)
     /// This reflects actual content position in the primary screen
     pub fn cursor_line(&self) -> u16 {
-        let term = self.term.lock();
+        let term = self.lock_term();
         term.grid().cursor.point.line.0 as u16
     }
 
@@ -515,7 +1073,7 @@ impl Terminal {
     /// non-empty line, not necessarily where the cursor is.
     /// Returns cursor_line if the cursor line has content.
     pub fn last_content_line(&self) -> u16 {
-        let term = self.term.lock();
+        let term = self.lock_term();
         let grid = term.grid();
         let cursor_line = grid.cursor.point.line.0 as u16;
 
@@ -542,7 +1100,7 @@ impl Terminal {
     /// Used for visibility decisions: we only want to show output terminals
     /// that have actual content to display.
     pub fn has_meaningful_content(&self) -> bool {
-        let term = self.term.lock();
+        let term = self.lock_term();
         let grid = term.grid();
         let cursor_line = grid.cursor.point.line.0 as u16;
 
@@ -571,6 +1129,31 @@ impl Terminal {
         self.pty.is_running()
     }
 
+    /// Process ID of the terminal's child process
+    pub fn pid(&self) -> u32 {
+        self.pty.pid()
+    }
+
+    /// Ask the child process and its session to quit (see `Pty::hang_up`)
+    pub fn hang_up(&self) -> bool {
+        self.pty.hang_up()
+    }
+
+    /// Whether the foreground job is stuck in uninterruptible sleep
+    pub fn foreground_blocked(&self, processes: &ProcessTable) -> bool {
+        self.pty.foreground_blocked(processes)
+    }
+
+    /// Exit status of the child process, once it has been reaped
+    pub fn exit_status(&self) -> Option<std::process::ExitStatus> {
+        self.pty.exit_status()
+    }
+
+    /// Record an exit status collected by an external reaper
+    pub fn record_exit(&mut self, status: std::process::ExitStatus) {
+        self.pty.record_exit(status);
+    }
+
     /// Get PTY fd for polling
     pub fn pty_fd(&self) -> std::os::fd::RawFd {
         self.pty.as_raw_fd()
@@ -585,7 +1168,7 @@ impl Terminal {
     ///
     /// Returns the content of each line in the grid (all 1000 lines).
     pub fn grid_content(&self) -> Vec<String> {
-        let term = self.term.lock();
+        let term = self.lock_term();
         let grid = term.grid();
         let mut lines = Vec::new();
 
@@ -613,7 +1196,7 @@ impl Terminal {
     /// Returns the `num_rows` lines that would be visible if we render.
     /// Shows from line 0 if content fits, otherwise shows ending at last content line.
     pub fn visible_content(&self, num_rows: usize) -> Vec<String> {
-        let term = self.term.lock();
+        let term = self.lock_term();
         let grid = term.grid();
         let cursor_line = term.grid().cursor.point.line.0 as usize;
 
@@ -679,10 +1262,56 @@ impl Terminal {
     /// This creates a zero-width selection at the starting point. Call `update_selection`
     /// with the same start coordinates and end coordinates to define the selection range.
     pub fn start_selection(&self, col: usize, row: usize) {
-        let mut term = self.term.lock();
-        let point = Point::new(Line(row as i32), Column(col));
+        self.start_selection_of(SelectionType::Simple, col, row);
+    }
+
+    /// Start a rectangular selection at the given grid coordinates
+    ///
+    /// Like `start_selection`, but `update_selection` then selects the block of
+    /// columns between the two points on every row (Alt+drag).
+    pub fn start_block_selection(&self, col: usize, row: usize) {
+        self.start_selection_of(SelectionType::Block, col, row);
+    }
+
+    /// Select the word at the given grid coordinates (double-click)
+    ///
+    /// A word is a run of alphanumeric characters and characters in
+    /// `word_chars`. Returns false if there is no word there.
+    pub fn select_word(&self, col: usize, row: usize, word_chars: &str) -> bool {
+        let line = Line(self.folds.line_at(row) as i32);
+        let mut term = self.lock_term();
+        if col >= term.columns() || line < term.topmost_line() || line > term.bottommost_line() {
+            return false;
+        }
+        let grid = term.grid();
+        let in_word = |col: usize| {
+            let cell = &grid[line][Column(col)];
+            // The spacer after a wide char belongs to it
+            let c = if cell.flags.contains(Flags::WIDE_CHAR_SPACER) && col > 0 {
+                grid[line][Column(col - 1)].c
+            } else {
+                cell.c
+            };
+            c.is_alphanumeric() || word_chars.contains(c)
+        };
+        if !in_word(col) {
+            return false;
+        }
+        let start = (0..col).rev().take_while(|&c| in_word(c)).last().unwrap_or(col);
+        let end = (col + 1..term.columns()).take_while(|&c| in_word(c)).last().unwrap_or(col);
+
+        let mut selection = Selection::new(SelectionType::Simple, Point::new(line, Column(start)), Side::Left);
+        selection.update(Point::new(line, Column(end)), Side::Right);
+        term.selection = Some(selection);
+        true
+    }
+
+    fn start_selection_of(&self, ty: SelectionType, col: usize, row: usize) {
+        let row = self.folds.line_at(row);
+        let mut term = self.lock_term();
+        let point = wide_char_start(&term, Point::new(Line(row as i32), Column(col)));
         // Initial side doesn't matter - it will be set correctly in update_selection
-        term.selection = Some(Selection::new(SelectionType::Simple, point, Side::Left));
+        term.selection = Some(Selection::new(ty, point, Side::Left));
     }
 
     /// Update the selection from start point to end point
@@ -693,14 +1322,25 @@ impl Terminal {
     ///
     /// This ensures both endpoint cells are always included regardless of selection direction.
     pub fn update_selection(&self, start_col: usize, start_row: usize, end_col: usize, end_row: usize) {
-        let mut term = self.term.lock();
+        let (start_row, end_row) = (self.folds.line_at(start_row), self.folds.line_at(end_row));
+        let mut term = self.lock_term();
 
-        let start_point = Point::new(Line(start_row as i32), Column(start_col));
-        let end_point = Point::new(Line(end_row as i32), Column(end_col));
+        let start_point = wide_char_start(&term, Point::new(Line(start_row as i32), Column(start_col)));
+        let end_point = wide_char_start(&term, Point::new(Line(end_row as i32), Column(end_col)));
+
+        // Keep the kind of selection that was started
+        let ty = term.selection.as_ref().map_or(SelectionType::Simple, |selection| selection.ty);
 
         // Determine sides based on selection direction
         // This mirrors alacritty's include_all() logic to ensure both endpoints are included
-        let (start_side, end_side) = if start_point > end_point {
+        let (start_side, end_side) = if ty == SelectionType::Block {
+            // Blocks are ordered by column: the left edge is the Left side
+            if start_point.column > end_point.column {
+                (Side::Right, Side::Left)
+            } else {
+                (Side::Left, Side::Right)
+            }
+        } else if start_point > end_point {
             // Right-to-left: after swap in to_range, start becomes end and vice versa
             // We want post-swap: start.side = Left, end.side = Right
             // So pre-swap: start.side = Right (becomes end.side), end.side = Left (becomes start.side)
@@ -711,7 +1351,7 @@ impl Terminal {
         };
 
         // Recreate selection with correct sides
-        term.selection = Some(Selection::new(SelectionType::Simple, start_point, start_side));
+        term.selection = Some(Selection::new(ty, start_point, start_side));
         if let Some(ref mut selection) = term.selection {
             selection.update(end_point, end_side);
         }
@@ -719,19 +1359,23 @@ impl Terminal {
 
     /// Clear the current selection
     pub fn clear_selection(&self) {
-        let mut term = self.term.lock();
+        let mut term = self.lock_term();
         term.selection = None;
     }
 
     /// Get the selected text, if any
     pub fn selection_text(&self) -> Option<String> {
-        let term = self.term.lock();
+        let term = self.lock_term();
         term.selection_to_string()
     }
 
     /// Check if there's an active selection
     pub fn has_selection(&self) -> bool {
-        let term = self.term.lock();
+        // Compressing drops the selection
+        if self.is_scrollback_compressed() {
+            return false;
+        }
+        let term = self.lock_term();
         term.selection.is_some()
     }
 
@@ -740,7 +1384,7 @@ impl Terminal {
     /// Positive lines = scroll up (back in history)
     /// Negative lines = scroll down (toward live output)
     pub fn scroll_display(&mut self, lines: i32) {
-        let term = self.term.lock();
+        let term = self.lock_term();
         let cursor_line = term.grid().cursor.point.line.0 as usize;
         drop(term);
 
@@ -764,10 +1408,19 @@ impl Terminal {
         self.viewport_offset = new_offset.min(max_visual_offset);
     }
 
+    /// Scroll the viewport by whole pages of the last rendered height
+    ///
+    /// A page keeps one line of the previous one on screen for context.
+    /// Positive pages scroll up (back in history), like [`Self::scroll_display`].
+    pub fn scroll_display_pages(&mut self, pages: i32) {
+        let page = self.last_visual_rows.saturating_sub(1).max(1) as i32;
+        self.scroll_display(pages.saturating_mul(page));
+    }
+
     /// Returns true if terminal has scrollback history available
     /// (content above the current viewport)
     pub fn has_scrollback(&self) -> bool {
-        let term = self.term.lock();
+        let term = self.lock_term();
         let cursor_line = term.grid().cursor.point.line.0 as usize;
         // We have scrollback if cursor is past line 0
         cursor_line > 0
@@ -778,6 +1431,14 @@ impl Terminal {
         self.viewport_offset
     }
 
+    /// Displayed rows shown by the last render, counted from the first line
+    ///
+    /// Selections take rows in these terms, so a click on viewport row `r`
+    /// is row `visible_rows().start + r`.
+    pub fn visible_rows(&self) -> std::ops::Range<usize> {
+        self.first_visible_row..self.first_visible_row + self.last_visual_rows
+    }
+
     /// Reset viewport to show live output (scroll to bottom)
     pub fn scroll_to_bottom(&mut self) {
         self.viewport_offset = 0;
@@ -788,6 +1449,127 @@ impl Terminal {
 mod tests {
     use super::*;
 
+    #[test]
+    fn color_scheme_requests_are_found_in_output() {
+        let requests = scan_color_scheme_requests(b"hi\x1b[?2031h\x1b[?996n\x1b[?25l");
+        assert_eq!(requests, ColorSchemeRequests { reports: Some(true), query: true });
+
+        let requests = scan_color_scheme_requests(b"\x1b[?2031h\x1b[?2031l");
+        assert_eq!(requests.reports, Some(false));
+        assert_eq!(scan_color_scheme_requests(b"\x1b[?2004h"), ColorSchemeRequests::default());
+    }
+
+    #[test]
+    fn title_requests_are_found_in_output() {
+        let title = scan_title_request(b"a\x1b]777;termstack-title;db shell\x07b");
+        assert_eq!(title.as_deref(), Some("db shell"));
+
+        let titles = b"\x1b]777;termstack-title;one\x1b\\\x1b]777;termstack-title;two\x07";
+        assert_eq!(scan_title_request(titles).as_deref(), Some("two"));
+        assert_eq!(scan_title_request(b"\x1b]777;termstack-title;\x07").as_deref(), Some(""));
+        assert_eq!(scan_title_request(b"\x1b]777;termstack-title;unterminated"), None);
+        assert_eq!(scan_title_request(b"\x1b]2;plain title\x07"), None);
+    }
+
+    #[test]
+    fn progress_reports_are_found_in_output() {
+        assert_eq!(scan_progress_report(b"a\x1b]9;4;1;42\x07b"), Some(Some(Progress::Normal(42))));
+        assert_eq!(scan_progress_report(b"\x1b]9;4;1;10\x1b\\\x1b]9;4;2;150\x1b\\"), Some(Some(Progress::Error(100))));
+        assert_eq!(scan_progress_report(b"\x1b]9;4;3\x07"), Some(Some(Progress::Indeterminate)));
+        assert_eq!(scan_progress_report(b"\x1b]9;4;4;7\x07"), Some(Some(Progress::Paused(7))));
+        assert_eq!(scan_progress_report(b"\x1b]9;4;1;50\x07\x1b]9;4;0;0\x07"), Some(None));
+        // A bad state leaves the last good one
+        assert_eq!(scan_progress_report(b"\x1b]9;4;1;5\x07\x1b]9;4;9;5\x07"), Some(Some(Progress::Normal(5))));
+        assert_eq!(scan_progress_report(b"\x1b]9;notification\x07"), None);
+        assert_eq!(scan_progress_report(b"\x1b]9;4;1;60"), None);
+    }
+
+    #[test]
+    fn repeated_lines_fold_and_expand_on_click() {
+        let mut terminal = Terminal::new(80, 24).expect("terminal creation");
+        terminal.set_fold_repeated_lines(true);
+        terminal.inject_bytes(b"start\r\nretry\r\nretry\r\nretry\r\ndone\r\n");
+        terminal.render(640, 24 * 17, false);
+
+        // Rows are start, retry x3, done: selecting row 2 selects "done"
+        terminal.start_selection(0, 2);
+        terminal.update_selection(0, 2, 3, 2);
+        assert_eq!(terminal.selection_text().as_deref(), Some("done"));
+
+        assert!(!terminal.expand_fold_at_row(0));
+        assert!(terminal.expand_fold_at_row(1));
+        terminal.render(640, 24 * 17, false);
+        assert!(!terminal.expand_fold_at_row(1));
+    }
+
+    #[test]
+    fn export_keeps_colors() {
+        let mut terminal = Terminal::new(80, 24).expect("terminal creation");
+        terminal.inject_bytes(b"\x1b[1;31merror\x1b[0m: a < b\r\n\x1b[38;5;208mwarn\x1b[0m\r\n");
+
+        assert_eq!(terminal.export(ExportFormat::Text, "log"), "error: a < b\nwarn\n");
+        assert_eq!(
+            terminal.export(ExportFormat::Ansi, "log"),
+            "\x1b[0;1;31merror\x1b[0m: a < b\n\x1b[0;38;5;208mwarn\x1b[0m\n"
+        );
+        let html = terminal.export(ExportFormat::Html, "cargo <build>");
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>cargo &lt;build&gt;</title>"));
+        assert!(html.contains("<span style=\"color: #cc0000; font-weight: bold\">error</span>: a &lt; b\n"));
+    }
+
+    #[test]
+    fn compressed_scrollback_comes_back_on_access() {
+        let mut terminal = Terminal::new(80, 24).expect("terminal creation");
+        // More lines than the grid has rows, so some are in the history
+        for i in 0..1100 {
+            terminal.inject_bytes(format!("line {i}\r\n").as_bytes());
+        }
+        terminal.inject_bytes(b"\x1b[1;31merror\x1b[0m: done\r\n$ ");
+        let export = terminal.export(ExportFormat::Ansi, "");
+        let (grid_size, cursor_line) = (terminal.grid_size(), terminal.cursor_line());
+
+        assert!(terminal.compress_scrollback().is_some_and(|len| len < export.len() / 2));
+        assert!(terminal.is_scrollback_compressed());
+        assert!(terminal.compress_scrollback().is_none());
+        assert!(!terminal.is_alternate_screen());
+        assert!(terminal.is_scrollback_compressed());
+
+        assert_eq!(terminal.export(ExportFormat::Ansi, ""), export);
+        assert!(!terminal.is_scrollback_compressed());
+        assert_eq!((terminal.grid_size(), terminal.cursor_line()), (grid_size, cursor_line));
+        terminal.inject_bytes(b"ls");
+        assert!(terminal.export(ExportFormat::Text, "").ends_with("error: done\n$ ls\n"));
+    }
+
+    #[test]
+    fn text_is_inserted_above_the_prompt() {
+        let mut terminal = Terminal::new(80, 24).expect("terminal creation");
+        terminal.inject_bytes(b"$ ls\r\nfoo\r\n$ \x1b[32mech");
+        terminal.insert_above_cursor_line("-- make --\n\x1b[31mok\x1b[0m\n");
+        // The cursor is back after the typed text
+        terminal.inject_bytes(b"o");
+
+        assert_eq!(terminal.export(ExportFormat::Text, ""), "$ ls\nfoo\n-- make --\nok\n$ echo\n");
+    }
+
+    #[test]
+    fn text_is_inserted_above_the_whole_prompt() {
+        // A two-line prompt with a right prompt, marked by the shell
+        let mut terminal = Terminal::new(20, 24).expect("terminal creation");
+        terminal.inject_bytes(b"out\r\n\x1b]133;A\x07~/src\x1b[17Gmain\r\n> ");
+        terminal.inject_bytes(b"git status --short --branch");
+        terminal.insert_above_cursor_line("-- make --\n");
+        terminal.inject_bytes(b"!");
+        assert_eq!(terminal.output_text(), "out\n-- make --\n~/src           main\n> git status --short --branch!");
+
+        // Without the mark, a command line wrapped over several rows moves as a whole
+        let mut terminal = Terminal::new(20, 24).expect("terminal creation");
+        terminal.inject_bytes(b"$ git status --short --branch");
+        terminal.insert_above_cursor_line("ok\n");
+        assert_eq!(terminal.output_text(), "ok\n$ git status --short --branch");
+    }
+
     #[test]
     fn selection_starts_and_clears() {
         let terminal = Terminal::new(80, 24).expect("terminal creation");
@@ -867,6 +1649,87 @@ mod tests {
         assert_eq!(text, "ABCDEF", "should select columns 0-5");
     }
 
+    #[test]
+    fn selection_snaps_to_wide_char_start() {
+        let mut terminal = Terminal::new(80, 24).expect("terminal creation");
+        // a=0, 日=1-2, 本=3-4, b=5
+        terminal.inject_bytes("a日本b\r\n".as_bytes());
+
+        // Clicking the right half of 日 selects 日 itself
+        terminal.start_selection(2, 0);
+        terminal.update_selection(2, 0, 2, 0);
+        let range = {
+            let term = terminal.term.lock();
+            term.selection.as_ref().and_then(|selection| selection.to_range(&*term)).unwrap()
+        };
+        assert_eq!((range.start.column, range.end.column), (Column(1), Column(1)));
+
+        // Dragging right-to-left from the right half of 本
+        terminal.update_selection(4, 0, 2, 0);
+        assert_eq!(terminal.selection_text().as_deref(), Some("日本"));
+        terminal.update_selection(2, 0, 5, 0);
+        assert_eq!(terminal.selection_text().as_deref(), Some("日本b"));
+    }
+
+    #[test]
+    fn block_selection_copies_a_column() {
+        let mut terminal = Terminal::new(80, 24).expect("terminal creation");
+        terminal.inject_bytes(b"abcdef\r\nghijkl\r\nmnopqr\r\n");
+
+        // Every drag direction selects the same block of columns 1-3
+        for (start, end) in [((1, 0), (3, 2)), ((3, 2), (1, 0)), ((3, 0), (1, 2)), ((1, 2), (3, 0))] {
+            terminal.start_block_selection(start.0, start.1);
+            terminal.update_selection(start.0, start.1, end.0, end.1);
+            assert_eq!(terminal.selection_text().as_deref(), Some("bcd\nhij\nnop"), "{start:?} -> {end:?}");
+        }
+
+        // A plain selection over the same points still runs line to line
+        terminal.start_selection(1, 0);
+        terminal.update_selection(1, 0, 3, 2);
+        assert_eq!(terminal.selection_text().as_deref(), Some("bcdef\nghijkl\nmnop"));
+    }
+
+    #[test]
+    fn selection_rows_follow_the_scrolled_viewport() {
+        let mut terminal = Terminal::new(80, 24).expect("terminal creation");
+        for i in 1..=20 {
+            terminal.inject_bytes(format!("{i}\r\n").as_bytes());
+        }
+        let (cell_width, cell_height) = terminal.cell_size();
+        terminal.render(80 * cell_width, 10 * cell_height, false);
+        assert_eq!(terminal.visible_rows(), 10..20);
+
+        // The top viewport row is displayed row 10, holding "11"
+        let top = terminal.visible_rows().start;
+        terminal.start_selection(0, top);
+        terminal.update_selection(0, top, 1, top);
+        assert_eq!(terminal.selection_text().as_deref(), Some("11"));
+
+        // Scrolling back three lines shows three more rows above
+        terminal.scroll_display(3);
+        terminal.render(80 * cell_width, 10 * cell_height, false);
+        assert_eq!(terminal.visible_rows(), 7..17);
+        terminal.update_selection(1, top, 0, terminal.visible_rows().start);
+        assert_eq!(terminal.selection_text().as_deref(), Some("8\n9\n10\n11"));
+    }
+
+    #[test]
+    fn double_click_selects_a_word() {
+        let mut terminal = Terminal::new(80, 24).expect("terminal creation");
+        terminal.inject_bytes(b"cat /usr/lib/os-release | less\r\n");
+
+        assert!(terminal.select_word(8, 0, "-./_~"));
+        assert_eq!(terminal.selection_text().as_deref(), Some("/usr/lib/os-release"));
+        // Without the punctuation, only the path component is a word
+        assert!(terminal.select_word(10, 0, ""));
+        assert_eq!(terminal.selection_text().as_deref(), Some("lib"));
+        assert!(terminal.select_word(0, 0, ""));
+        assert_eq!(terminal.selection_text().as_deref(), Some("cat"));
+        // Spaces and pipes are never words
+        assert!(!terminal.select_word(3, 0, "-./_~"));
+        assert!(!terminal.select_word(24, 0, "-./_~"));
+    }
+
     #[test]
     fn selection_survives_new_output() {
         let mut terminal = Terminal::new(80, 24).expect("terminal creation");
@@ -913,6 +1776,43 @@ mod tests {
         assert!(offset_after_down < offset_after_up, "scroll down should decrease offset");
     }
 
+    #[test]
+    fn views_stand_in_for_the_output_until_hidden() {
+        let mut terminal = Terminal::new(10, 10).expect("terminal creation");
+        // Longer than a row: wrapped, but one line of output
+        terminal.inject_bytes(b"0123456789abcde\r\nsecond\r\n");
+        assert_eq!(terminal.output_text(), "0123456789abcde\nsecond");
+
+        assert_eq!(terminal.show_view("\x1b[1mview\x1b[0m\nlines"), 2);
+        assert!(terminal.is_showing_view());
+        assert_eq!(terminal.output_text(), "view\nlines");
+        terminal.show_view("other");
+        assert_eq!(terminal.output_text(), "other");
+        let exported = terminal.export(ExportFormat::Text, "");
+        assert!(exported.contains("second") && !exported.contains("other"));
+
+        assert!(terminal.hide_view());
+        assert!(!terminal.hide_view());
+        assert_eq!(terminal.output_text(), "0123456789abcde\nsecond");
+    }
+
+    #[test]
+    fn scroll_display_pages_keeps_a_line_of_context() {
+        let mut terminal = Terminal::new(80, 10).expect("terminal creation");
+        for i in 1..=100 {
+            terminal.inject_bytes(format!("{}\r\n", i).as_bytes());
+        }
+
+        terminal.scroll_display_pages(2);
+        assert_eq!(terminal.display_offset(), 18);
+        terminal.scroll_display_pages(-1);
+        assert_eq!(terminal.display_offset(), 9);
+
+        // Paging down past live output stops there
+        terminal.scroll_display_pages(-3);
+        assert_eq!(terminal.display_offset(), 0);
+    }
+
     #[test]
     fn terminal_shows_latest_output_not_first() {
         // Bug: seq 500 shows lines 1-47 instead of latest output
@@ -0,0 +1,136 @@
+//! Debug bundle for bug reports
+//!
+//! `termstack debug-bundle [output.tar.gz]` collects recent logs, the config
+//! file, version/environment info and (when run inside a session) the
+//! `termstack ls --json` layout snapshot into a single compressed archive
+//! that can be attached to a bug report.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+
+/// Environment variables worth including in version info
+const ENV_VARS: &[&str] = &[
+    "WAYLAND_DISPLAY",
+    "DISPLAY",
+    "HOST_WAYLAND_DISPLAY",
+    "HOST_DISPLAY",
+    "XDG_SESSION_TYPE",
+    "XDG_CURRENT_DESKTOP",
+    "SHELL",
+    "TERMSTACK_SOCKET",
+    "RUST_LOG",
+];
+
+/// Create a debug bundle archive and print its path
+pub fn create(args: &[String]) -> Result<()> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let name = format!("termstack-debug-{}", timestamp);
+    let output = match args.first() {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(format!("{}.tar.gz", name)),
+    };
+
+    let staging_root = std::env::temp_dir().join(format!("termstack-bundle-{}", std::process::id()));
+    let staging = staging_root.join(&name);
+    fs::create_dir_all(&staging)
+        .with_context(|| format!("failed to create {}", staging.display()))?;
+
+    let result = collect(&staging).and_then(|()| archive(&staging_root, &name, &output));
+    let _ = fs::remove_dir_all(&staging_root);
+    result?;
+
+    println!("Wrote debug bundle: {}", output.display());
+    println!("Please review it for private information before attaching it to a bug report.");
+    Ok(())
+}
+
+/// Write all bundle contents into `dir`
+fn collect(dir: &Path) -> Result<()> {
+    fs::write(dir.join("version.txt"), version_info())?;
+
+    // Config files (user config and system-wide fallback)
+    let config_dir = dir.join("config");
+    fs::create_dir_all(&config_dir)?;
+    for (i, path) in compositor::config::Config::config_paths().into_iter().flatten().enumerate() {
+        if path.exists() {
+            let dest = config_dir.join(format!("{}-config.toml", i));
+            fs::copy(&path, &dest)
+                .with_context(|| format!("failed to copy {}", path.display()))?;
+            println!("Included config: {}", path.display());
+        }
+    }
+
+    // Log files, newest first
+    let logs_dir = dir.join("logs");
+    fs::create_dir_all(&logs_dir)?;
+    match compositor::logging::log_dir() {
+        Some(log_dir) => {
+            let files = compositor::logging::log_files(&log_dir);
+            if files.is_empty() {
+                println!("No log files found in {}", log_dir.display());
+            }
+            for path in files {
+                if let Some(file_name) = path.file_name() {
+                    fs::copy(&path, logs_dir.join(file_name))
+                        .with_context(|| format!("failed to copy {}", path.display()))?;
+                }
+            }
+        }
+        None => println!("No log directory (neither XDG_STATE_HOME nor HOME is set)"),
+    }
+
+    // Layout snapshot, only available inside a running session
+    if std::env::var("TERMSTACK_SOCKET").is_ok() {
+        let snapshot = crate::cli::fetch_layout()
+            .and_then(|layout| Ok(serde_json::to_string_pretty(&layout)? + "\n"))
+            .unwrap_or_else(|e| format!("failed to fetch the layout: {:#}\n", e));
+        fs::write(dir.join("ls.json"), snapshot)?;
+    }
+
+    Ok(())
+}
+
+/// Version, platform and relevant environment
+fn version_info() -> String {
+    let mut info = format!(
+        "termstack {}\nos: {}\narch: {}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+    );
+
+    if let Ok(output) = Command::new("uname").arg("-a").output() {
+        info.push_str(&format!("uname: {}", String::from_utf8_lossy(&output.stdout)));
+    }
+
+    info.push_str("\nenvironment:\n");
+    for var in ENV_VARS {
+        let value = std::env::var(var).unwrap_or_else(|_| "(not set)".to_string());
+        info.push_str(&format!("  {}={}\n", var, value));
+    }
+    info
+}
+
+/// Compress `root/name` into `output`
+fn archive(root: &Path, name: &str, output: &Path) -> Result<()> {
+    let status = Command::new("tar")
+        .arg("-czf")
+        .arg(output)
+        .arg("-C")
+        .arg(root)
+        .arg(name)
+        .status()
+        .context("failed to run tar")?;
+
+    if !status.success() {
+        bail!("tar exited with {}", status);
+    }
+    Ok(())
+}
@@ -6,7 +6,10 @@
 //!
 //! Command classification (shell builtins vs regular commands) is handled
 //! entirely in the shell integration script (scripts/integration.fish).
-//! When called with `-c`, the CLI always spawns in a new terminal.
+//! When called with `-c`, the CLI spawns in a new terminal, unless the
+//! command starts with the configured `inline_prefix`: then it prints the
+//! command without the prefix and exits with [`INLINE_EXIT_CODE`], and the
+//! shell runs it itself.
 //!
 //! TUI apps (vim, mc, etc.) are auto-detected via alternate screen mode
 //! and automatically resized to full viewport height.
@@ -14,14 +17,20 @@
 //! GUI apps automatically get an output terminal that appears below the
 //! window when stderr/stdout is produced.
 
+use std::collections::BTreeMap;
 use std::env;
 use std::io::Write;
 use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
 
 use anyhow::{bail, Context, Result};
 
 use crate::util::debug_enabled;
 
+/// Exit status of `termstack -c` for a command the calling shell should run
+/// itself (it starts with `inline_prefix`)
+pub const INLINE_EXIT_CODE: i32 = 2;
+
 
 pub fn run() -> Result<()> {
     let args: Vec<String> = env::args().collect();
@@ -33,28 +42,9 @@ pub fn run() -> Result<()> {
         eprintln!("[termstack] TERMSTACK_SOCKET={:?}", env::var("TERMSTACK_SOCKET"));
     }
 
-    // Handle --status flag for diagnostics
+    // Handle --status flag for diagnostics (alias of `termstack doctor`)
     if args.len() >= 2 && args[1] == "--status" {
-        let socket = env::var("TERMSTACK_SOCKET");
-        let shell = env::var("SHELL").unwrap_or_else(|_| "(not set)".to_string());
-
-        println!("termstack status:");
-        println!("  TERMSTACK_SOCKET: {}", match &socket {
-            Ok(path) => format!("{} (exists: {})", path, std::path::Path::new(path).exists()),
-            Err(_) => "NOT SET - shell integration will not activate".to_string(),
-        });
-        println!("  SHELL: {}", shell);
-        println!();
-
-        if socket.is_ok() {
-            println!("Shell integration should be active.");
-            println!("If 'gui' command is not found, make sure to source the integration script:");
-            println!("  fish: source scripts/integration.fish");
-        } else {
-            println!("You are NOT inside termstack.");
-            println!("Start the compositor first, then the shell integration will activate.");
-        }
-        return Ok(());
+        return crate::doctor::run();
     }
 
     // Handle diagnose subcommand for X11/Wayland diagnostics
@@ -72,6 +62,88 @@ pub fn run() -> Result<()> {
         return query_windows();
     }
 
+    // Handle status subcommand for status bars
+    if args.len() >= 2 && args[1] == "status" {
+        return show_status(&args[2..]);
+    }
+
+    // Handle a11y subcommand for screen readers
+    // Usage: termstack a11y [--watch] [--json] | termstack a11y read <index> [--lines N]
+    if args.len() >= 2 && args[1] == "a11y" {
+        return accessibility(&args[2..]);
+    }
+
+    // Handle dnd subcommand for do-not-disturb
+    // Usage: termstack dnd [on|off|toggle] [--focus|--no-focus]
+    if args.len() >= 2 && args[1] == "dnd" {
+        return set_do_not_disturb(&args[2..]);
+    }
+
+    // Handle ls subcommand: list the cells (--json: the whole layout)
+    if args.len() >= 2 && args[1] == "ls" {
+        return list_cells(&args[2..]);
+    }
+
+    // Handle title subcommand: name a cell (no text clears the name)
+    // Usage: termstack title <index> [text ...]
+    if args.len() >= 2 && args[1] == "title" {
+        return set_cell_title(&args[2..]);
+    }
+
+    // Handle tag subcommand: color-tag a cell (no color removes the tag)
+    // Usage: termstack tag <index> [red|orange|yellow|green|blue|purple]
+    if args.len() >= 2 && args[1] == "tag" {
+        return set_cell_tag(&args[2..]);
+    }
+
+    // Handle filter subcommand: show only the cells of a command or tag
+    // Usage: termstack filter <text ...> | --tag <color> | --clear
+    if args.len() >= 2 && args[1] == "filter" {
+        return filter_cells(&args[2..]);
+    }
+
+    // Handle layout subcommand: save the stack's layout to a file or add the
+    // cells of one
+    // Usage: termstack layout export <file.toml> | termstack layout import [--yes] <file.toml>
+    if args.len() >= 2 && args[1] == "layout" {
+        return layout_file(&args[2..]);
+    }
+
+    // Handle hit-test subcommand: report what is under screen points
+    // Usage: termstack hit-test <x> <y> [<x> <y> ...]
+    if args.len() >= 2 && args[1] == "hit-test" {
+        return hit_test(&args[2..]);
+    }
+
+    // Handle back subcommand: focus the previously focused cell (like `cd -`)
+    if args.len() >= 2 && args[1] == "back" {
+        return focus_back();
+    }
+
+    // Handle all subcommand: act on the whole stack
+    // Usage: termstack all close-exited|collapse|kill|undo
+    if args.len() >= 2 && args[1] == "all" {
+        return bulk_action(&args[2..]);
+    }
+
+    // Handle archive subcommand: browse and restore closed command cells
+    // Usage: termstack archive [show|restore <id>]
+    if args.len() >= 2 && args[1] == "archive" {
+        return archive(&args[2..]);
+    }
+
+    // Handle env subcommand: variables X11 apps need, for shells started
+    // before XWayland was ready
+    if args.len() >= 2 && args[1] == "env" {
+        return print_x11_env();
+    }
+
+    // Handle quit subcommand: shut termstack down after asking
+    // Usage: termstack quit [--yes]
+    if args.len() >= 2 && args[1] == "quit" {
+        return quit(&args[2..]);
+    }
+
     // Handle --resize flag first (before any command parsing)
     if args.len() >= 2 && args[1] == "--resize" {
         let mode = args.get(2).map(|s| s.as_str()).unwrap_or("full");
@@ -84,6 +156,12 @@ pub fn run() -> Result<()> {
         return send_builtin_notification(&args[2..]);
     }
 
+    // Handle --calc flag for arithmetic typed at the prompt
+    // Usage: termstack --calc "prompt" "expression" (fails if it isn't one)
+    if args.len() >= 2 && args[1] == "--calc" {
+        return send_calc_result(&args[2..]);
+    }
+
     // Handle 'gui' subcommand for launching GUI apps with foreground/background mode
     // Usage: termstack gui <command>
     // Background mode: TERMSTACK_GUI_BACKGROUND=1 termstack gui <command>
@@ -103,9 +181,28 @@ pub fn run() -> Result<()> {
         return spawn_gui_app(&command, foreground);
     }
 
+    // Handle 'export' subcommand for saving a cell's content
+    // Usage: termstack export <index> [--format html|ansi|txt] [-o file]
+    if args.len() >= 2 && args[1] == "export" {
+        return export_cell(&args[2..]);
+    }
+
+    // Handle 'replay' subcommand for recorded sessions
+    // Usage: termstack replay [session-dir] [--speed N]
+    if args.len() >= 2 && args[1] == "replay" {
+        return replay_session(&args[2..]);
+    }
+
+    // Handle 'tpl' subcommand for named command templates from the config
+    // Usage: termstack tpl [name [args...]]
+    if args.len() >= 2 && args[1] == "tpl" {
+        return spawn_template(&args[2..]);
+    }
+
     // Parse arguments
-    let command = parse_command(&args)?;
-    if debug { eprintln!("[termstack] command: {:?}", command); }
+    let (options, command_args) = parse_spawn_options(&args[1..])?;
+    let command = parse_command(command_args)?;
+    if debug { eprintln!("[termstack] command: {:?} options: {:?}", command, options); }
 
     // Get prompt from environment (set by fish integration)
     let prompt = env::var("TERMSTACK_PROMPT").unwrap_or_default();
@@ -114,7 +211,15 @@ pub fn run() -> Result<()> {
     // Empty command = interactive shell, always use terminal
     if command.is_empty() {
         if debug { eprintln!("[termstack] empty command, spawning shell"); }
-        return spawn_in_terminal(&command, &prompt);
+        return spawn_in_terminal(&command, &prompt, &options);
+    }
+
+    // Opted out of a new cell: hand the command back to the shell
+    let inline_prefix = compositor::config::Config::load().inline_prefix;
+    if let Some(inline) = inline_command(&command, &inline_prefix) {
+        if debug { eprintln!("[termstack] inline prefix {:?}, running in the shell: {:?}", inline_prefix, inline); }
+        println!("{}", inline);
+        std::process::exit(INLINE_EXIT_CODE);
     }
 
     // Check if command is a termstack subcommand - execute it directly
@@ -127,7 +232,7 @@ pub fn run() -> Result<()> {
 
     // Spawn in new terminal — classification is handled by the shell integration script
     if debug { eprintln!("[termstack] spawning in terminal"); }
-    spawn_in_terminal(&command, &prompt)
+    spawn_in_terminal(&command, &prompt, &options)
 }
 
 /// Send a resize request to the compositor and wait for acknowledgement
@@ -223,10 +328,46 @@ fn send_builtin_notification(args: &[String]) -> Result<()> {
     Ok(())
 }
 
+/// Evaluate a calculation and show the answer in the stack
+///
+/// The shell integration tries this for command lines that look like
+/// arithmetic or a unit conversion. An error means it isn't one, and the
+/// line runs as a normal command instead.
+///
+/// Usage: termstack --calc "prompt" "expression"
+fn send_calc_result(args: &[String]) -> Result<()> {
+    let [prompt, expression] = args else {
+        bail!("usage: termstack --calc <prompt> <expression>");
+    };
+    let result = compositor::calc::evaluate(expression)?;
+    if debug_enabled() { eprintln!("[termstack] calc: {:?} = {:?}", expression, result); }
+
+    let socket_path = env::var("TERMSTACK_SOCKET")
+        .context("TERMSTACK_SOCKET not set - are you running inside termstack?")?;
+    let msg = serde_json::json!({
+        "type": "calc",
+        "prompt": prompt,
+        "command": expression,
+        "result": result,
+    });
+    let mut stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("failed to connect to {}", socket_path))?;
+    writeln!(stream, "{}", msg).context("failed to send calc message")?;
+    stream.flush().context("failed to flush calc message")?;
+    Ok(())
+}
+
 /// Query current window state from the compositor
 ///
 /// Outputs JSON array of window information. Useful for testing and debugging.
 fn query_windows() -> Result<()> {
+    // Output the JSON response directly
+    print!("{}", fetch_windows_json()?);
+    Ok(())
+}
+
+/// Fetch the window state JSON from the compositor
+pub(crate) fn fetch_windows_json() -> Result<String> {
     use std::io::{BufRead, BufReader};
 
     let socket_path = env::var("TERMSTACK_SOCKET")
@@ -253,242 +394,1334 @@ fn query_windows() -> Result<()> {
     let mut response = String::new();
     reader.read_line(&mut response).context("failed to read query_windows response")?;
 
-    // Output the JSON response directly
-    print!("{}", response);
-
-    Ok(())
+    Ok(response)
 }
 
-/// Spawn command in a new termstack terminal
+/// Print the variables X11 apps need as `KEY=VALUE` lines
 ///
-/// The terminal starts small and grows with content. TUI apps are
-/// auto-detected via alternate screen mode and resized to full viewport.
-fn spawn_in_terminal(command: &str, prompt: &str) -> Result<()> {
-    let debug = debug_enabled();
+/// Usage: termstack env
+///
+/// Shells started before XWayland was ready have no `DISPLAY`; the fish
+/// integration runs this until it succeeds and sets what it prints. Fails
+/// while X11 is still coming up, so the caller asks again later; prints
+/// nothing when X11 apps won't work in this session.
+fn print_x11_env() -> Result<()> {
+    use std::io::{BufRead, BufReader};
 
-    // Get socket path from environment
     let socket_path = env::var("TERMSTACK_SOCKET")
         .context("TERMSTACK_SOCKET not set - are you running inside termstack?")?;
-    if debug { eprintln!("[termstack] socket path: {}", socket_path); }
-
-    // Collect current environment
-    let env_vars: std::collections::HashMap<String, String> = env::vars().collect();
-
-    // Get current working directory
-    let cwd = env::current_dir()
-        .context("failed to get current directory")?
-        .to_string_lossy()
-        .to_string();
-    if debug { eprintln!("[termstack] cwd: {}", cwd); }
 
-    // Build JSON message
-    let msg = serde_json::json!({
-        "type": "spawn",
-        "prompt": prompt,
-        "command": command,
-        "cwd": cwd,
-        "env": env_vars,
-    });
+    let msg = serde_json::json!({ "type": "env" });
 
-    if debug { eprintln!("[termstack] connecting to socket..."); }
-    // Connect to compositor and send message
-    let mut stream = UnixStream::connect(&socket_path)
+    let stream = UnixStream::connect(&socket_path)
         .with_context(|| format!("failed to connect to {}", socket_path))?;
-    if debug { eprintln!("[termstack] connected, sending message..."); }
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(2)))
+        .context("failed to set read timeout")?;
 
-    writeln!(stream, "{}", msg).context("failed to send message")?;
-    stream.flush().context("failed to flush message")?;
-    if debug { eprintln!("[termstack] message sent successfully"); }
+    let mut stream_write = stream.try_clone().context("failed to clone stream")?;
+    writeln!(stream_write, "{}", msg).context("failed to send env message")?;
+    stream_write.flush().context("failed to flush env message")?;
 
-    // Clear the command line from the invoking terminal
-    if !command.is_empty() {
-        print!("\x1b[A\x1b[2K");
-        std::io::stdout().flush().ok();
+    let mut reader = BufReader::new(stream);
+    let mut response = String::new();
+    reader.read_line(&mut response).context("failed to read env response")?;
+    let update: compositor::xwayland_supervisor::EnvUpdate =
+        serde_json::from_str(&response).context("invalid env response")?;
+    if update.set.is_empty() && update.pending {
+        bail!("X11 is not ready yet");
+    }
+    for (key, value) in &update.set {
+        println!("{key}={value}");
     }
-
     Ok(())
 }
 
-/// Spawn a GUI app with foreground/background mode
-///
-/// In foreground mode, the launching terminal is hidden until the GUI app exits.
-/// In background mode, the launching terminal stays visible and usable.
-fn spawn_gui_app(command: &str, foreground: bool) -> Result<()> {
-    let debug = debug_enabled();
+/// Ask the compositor how X11 support is doing
+pub(crate) fn fetch_xwayland_status() -> Result<compositor::xwayland_supervisor::XWaylandStatus> {
+    use std::io::{BufRead, BufReader};
 
-    // Get socket path from environment
     let socket_path = env::var("TERMSTACK_SOCKET")
         .context("TERMSTACK_SOCKET not set - are you running inside termstack?")?;
-    if debug { eprintln!("[termstack] socket path: {}", socket_path); }
-
-    // Collect current environment
-    let env_vars: std::collections::HashMap<String, String> = env::vars().collect();
-
-    // Get current working directory
-    let cwd = env::current_dir()
-        .context("failed to get current directory")?
-        .to_string_lossy()
-        .to_string();
-    if debug { eprintln!("[termstack] cwd: {}", cwd); }
 
-    // Build JSON message for GUI spawn (unified with terminal spawn)
     let msg = serde_json::json!({
-        "type": "spawn",
-        "command": command,
-        "cwd": cwd,
-        "env": env_vars,
-        "foreground": foreground,
+        "type": "xwayland",
     });
 
-    if debug { eprintln!("[termstack] connecting to socket for GUI spawn..."); }
-    // Connect to compositor and send message
-    let mut stream = UnixStream::connect(&socket_path)
+    let stream = UnixStream::connect(&socket_path)
         .with_context(|| format!("failed to connect to {}", socket_path))?;
-    if debug { eprintln!("[termstack] connected, sending gui_spawn message..."); }
 
-    writeln!(stream, "{}", msg).context("failed to send message")?;
-    stream.flush().context("failed to flush message")?;
-    if debug { eprintln!("[termstack] gui_spawn message sent successfully"); }
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(2)))
+        .context("failed to set read timeout")?;
 
-    // Clear the command line from the invoking terminal
-    print!("\x1b[A\x1b[2K");
-    std::io::stdout().flush().ok();
+    let mut stream_write = stream.try_clone().context("failed to clone stream")?;
 
-    Ok(())
+    writeln!(stream_write, "{}", msg).context("failed to send xwayland message")?;
+    stream_write.flush().context("failed to flush xwayland message")?;
+
+    let mut reader = BufReader::new(stream);
+    let mut response = String::new();
+    reader.read_line(&mut response).context("failed to read xwayland response")?;
+    serde_json::from_str(&response).context("invalid xwayland response")
 }
 
-/// Extract termstack subcommand from a command string
+/// Print what the focused cell is running
 ///
-/// When fish integration intercepts "termstack test-x11", it calls
-/// "termstack -c 'termstack test-x11'". We detect this and extract
-/// the subcommand args to execute directly (avoiding PATH lookup issues).
+/// Usage: termstack status [--watch] [--json]
 ///
-/// Returns the full subcommand string (e.g., "test-x11" or "gui firefox")
-fn extract_termstack_subcommand(command: &str) -> Option<String> {
-    let parts: Vec<&str> = command.split_whitespace().collect();
-    if parts.is_empty() {
-        return None;
-    }
+/// Prints e.g. `cargo test (2m13s)`, or an empty line when nothing is
+/// focused. With `--watch` the connection stays open and a new line is
+/// printed whenever the status changes or the displayed runtime ticks, for
+/// status bars that follow a command's output. `--json` prints the raw
+/// status lines from the compositor instead.
+fn show_status(args: &[String]) -> Result<()> {
+    use std::io::{BufRead, BufReader, ErrorKind};
+    use std::time::{Duration, SystemTime};
 
-    // Check if first word is "termstack" (or path ending in termstack)
-    let first = parts[0];
-    let is_termstack = first == "termstack"
-        || first.ends_with("/termstack")
-        || first == "$TERMSTACK_BIN";
+    use compositor::now_running::NowRunning;
 
-    if !is_termstack {
-        return None;
-    }
+    let watch = args.iter().any(|arg| arg == "--watch");
+    let json = args.iter().any(|arg| arg == "--json");
 
-    // Check if second word is a known subcommand
-    if parts.len() < 2 {
-        return None;
-    }
+    let socket_path = env::var("TERMSTACK_SOCKET")
+        .context("TERMSTACK_SOCKET not set - are you running inside termstack?")?;
 
-    let subcommands = ["diagnose", "test-x11", "query-windows", "gui", "--status", "--resize", "--builtin", "--help", "-h"];
-    if subcommands.contains(&parts[1]) {
-        // Return everything after "termstack"
-        Some(parts[1..].join(" "))
-    } else {
-        None
-    }
-}
+    let msg = serde_json::json!({
+        "type": "status",
+        "watch": watch,
+    });
 
-/// Execute a termstack subcommand directly
-fn execute_subcommand(subcommand: &str) -> Result<()> {
-    let parts: Vec<&str> = subcommand.split_whitespace().collect();
-    if parts.is_empty() {
-        bail!("empty subcommand");
-    }
+    let stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("failed to connect to {}", socket_path))?;
 
-    match parts[0] {
-        "diagnose" => run_diagnostics(),
-        "test-x11" => test_x11_connectivity(),
-        "query-windows" => query_windows(),
-        "--status" => {
-            // Inline the status output
-            let socket = env::var("TERMSTACK_SOCKET");
-            let shell = env::var("SHELL").unwrap_or_else(|_| "(not set)".to_string());
-
-            println!("termstack status:");
-            println!("  TERMSTACK_SOCKET: {}", match &socket {
-                Ok(path) => format!("{} (exists: {})", path, std::path::Path::new(path).exists()),
-                Err(_) => "NOT SET - shell integration will not activate".to_string(),
-            });
-            println!("  SHELL: {}", shell);
-            println!();
+    // Without --watch the answer comes on the next frame; with it, wake up
+    // every second to advance the runtime
+    let timeout = if watch { Duration::from_secs(1) } else { Duration::from_secs(2) };
+    stream.set_read_timeout(Some(timeout))
+        .context("failed to set read timeout")?;
 
-            if socket.is_ok() {
-                println!("Shell integration should be active.");
-            } else {
-                println!("You are NOT inside termstack.");
+    let mut stream_write = stream.try_clone().context("failed to clone stream")?;
+
+    writeln!(stream_write, "{}", msg).context("failed to send status message")?;
+    stream_write.flush().context("failed to flush status message")?;
+
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    let mut status: Option<NowRunning> = None;
+    let mut printed: Option<String> = None;
+    let mut done = false;
+    while !done {
+        match reader.read_line(&mut line) {
+            // Compositor went away
+            Ok(0) => return Ok(()),
+            Ok(_) if line.ends_with('\n') => {
+                status = serde_json::from_str(&line).context("invalid status response")?;
+                if json {
+                    print!("{}", line);
+                }
+                line.clear();
+                done = !watch;
             }
-            Ok(())
-        }
-        "--resize" => {
-            let mode = parts.get(1).copied().unwrap_or("full");
-            send_resize_request(mode)
-        }
-        "--builtin" => {
-            // Convert &str parts back to String for send_builtin_notification
-            let builtin_args: Vec<String> = parts[1..].iter().map(|s| s.to_string()).collect();
-            send_builtin_notification(&builtin_args)
+            Ok(_) => {}
+            Err(e) if watch && matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(e) => return Err(e).context("failed to read status response"),
         }
-        "gui" => {
-            if parts.len() < 2 {
-                bail!("usage: termstack gui <command>");
+
+        if !json {
+            let text = status
+                .as_ref()
+                .map(|status| status.summary(SystemTime::now()))
+                .unwrap_or_default();
+            if printed.as_ref() != Some(&text) {
+                println!("{}", text);
+                std::io::stdout().flush().context("failed to write status")?;
+                printed = Some(text);
             }
-            let gui_command = parts[1..].join(" ");
-            let foreground = env::var("TERMSTACK_GUI_BACKGROUND").is_err();
-            spawn_gui_app(&gui_command, foreground)
-        }
-        "--help" | "-h" => {
-            println!("termstack - Terminal compositor CLI");
-            println!();
-            println!("Subcommands:");
-            println!("  diagnose       Run X11/Wayland diagnostics");
-            println!("  test-x11       Test X11 connectivity");
-            println!("  query-windows  Query current window state (JSON output)");
-            println!("  gui <cmd>      Launch GUI app inside termstack");
-            println!("  --status       Show termstack status");
-            println!("  --resize       Resize focused terminal");
-            Ok(())
         }
-        _ => bail!("unknown subcommand: {}", parts[0]),
     }
+    Ok(())
 }
 
-/// Parse command from arguments
+/// Turn do-not-disturb on or off and print the new state
 ///
-/// Supports:
-/// - `termstack -c "command"` - run command
-/// - `termstack` - run interactive shell (empty command)
-fn parse_command(args: &[String]) -> Result<String> {
-    if args.len() == 1 {
-        // No arguments - spawn interactive shell
-        return Ok(String::new());
-    }
+/// Usage: termstack dnd [on|off|toggle] [--focus|--no-focus]
+///
+/// The mode defaults to `toggle`. `--focus` also hides every cell except the
+/// focused one and its parent; without either flag the `dnd_focus_mode`
+/// config setting decides.
+fn set_do_not_disturb(args: &[String]) -> Result<()> {
+    use std::io::{BufRead, BufReader};
 
-    if args.len() == 3 && args[1] == "-c" {
-        // -c "command"
-        return Ok(args[2].clone());
-    }
+    use compositor::do_not_disturb::DndStatus;
 
-    if args.len() == 2 && args[1] == "-c" {
-        bail!("missing command after -c");
-    }
+    const USAGE: &str = "usage: termstack dnd [on|off|toggle] [--focus|--no-focus]";
 
-    // Treat all remaining args as the command
-    if args.len() >= 2 {
-        if args[1] == "-c" {
-            // -c with multiple args: join them
-            return Ok(args[2..].join(" "));
+    let mut mode = "toggle";
+    let mut focus = None;
+    for arg in args {
+        match arg.as_str() {
+            "on" | "off" | "toggle" => mode = arg,
+            "--focus" => focus = Some(true),
+            "--no-focus" => focus = Some(false),
+            _ => bail!(USAGE),
         }
-        // No -c: join all args as command
-        return Ok(args[1..].join(" "));
     }
 
-    bail!("usage: termstack [-c command]");
+    let socket_path = env::var("TERMSTACK_SOCKET")
+        .context("TERMSTACK_SOCKET not set - are you running inside termstack?")?;
+
+    let msg = serde_json::json!({
+        "type": "dnd",
+        "mode": mode,
+        "focus": focus,
+    });
+
+    let stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("failed to connect to {}", socket_path))?;
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(2)))
+        .context("failed to set read timeout")?;
+
+    let mut stream_write = stream.try_clone().context("failed to clone stream")?;
+
+    writeln!(stream_write, "{}", msg).context("failed to send dnd message")?;
+    stream_write.flush().context("failed to flush dnd message")?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).context("failed to read dnd response")?;
+    let status: DndStatus = serde_json::from_str(&response).context("invalid dnd response")?;
+    match (status.enabled, status.focus_mode) {
+        (false, _) => println!("do-not-disturb off"),
+        (true, false) => println!("do-not-disturb on"),
+        (true, true) => println!("do-not-disturb on (focus mode)"),
+    }
+    Ok(())
+}
+
+/// Focus the previously focused cell, like `cd -`
+///
+/// Usage: termstack back
+fn focus_back() -> Result<()> {
+    use std::io::{BufRead, BufReader};
+
+    let socket_path = env::var("TERMSTACK_SOCKET")
+        .context("TERMSTACK_SOCKET not set - are you running inside termstack?")?;
+
+    let msg = serde_json::json!({ "type": "focus_back" });
+
+    let stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("failed to connect to {}", socket_path))?;
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(2)))
+        .context("failed to set read timeout")?;
+
+    let mut stream_write = stream.try_clone().context("failed to clone stream")?;
+
+    writeln!(stream_write, "{}", msg).context("failed to send focus_back message")?;
+    stream_write.flush().context("failed to flush focus_back message")?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).context("failed to read focus_back response")?;
+    let switched: bool = serde_json::from_str(&response).context("invalid focus_back response")?;
+    if !switched {
+        bail!("no previously focused cell");
+    }
+    Ok(())
+}
+
+/// Name a cell, shown instead of its command in title bars, `ls` and the
+/// window switcher
+///
+/// Usage: termstack title <index> [text ...] (no text goes back to the command)
+fn set_cell_title(args: &[String]) -> Result<()> {
+    use std::io::{BufRead, BufReader};
+
+    const USAGE: &str = "usage: termstack title <index> [text ...]";
+
+    let index: usize = args
+        .first()
+        .context(USAGE)?
+        .parse()
+        .with_context(|| format!("invalid cell index\n{}", USAGE))?;
+    let title = args[1..].join(" ");
+
+    let socket_path = env::var("TERMSTACK_SOCKET")
+        .context("TERMSTACK_SOCKET not set - are you running inside termstack?")?;
+
+    let msg = serde_json::json!({ "type": "set_title", "index": index, "title": title });
+
+    let stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("failed to connect to {}", socket_path))?;
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(2)))
+        .context("failed to set read timeout")?;
+
+    let mut stream_write = stream.try_clone().context("failed to clone stream")?;
+
+    writeln!(stream_write, "{}", msg).context("failed to send set_title message")?;
+    stream_write.flush().context("failed to flush set_title message")?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).context("failed to read set_title response")?;
+    let result: Result<(), String> = serde_json::from_str(&response).context("invalid set_title response")?;
+    result.map_err(|e| anyhow::anyhow!(e))
+}
+
+/// Tag a cell with a color, drawn as a stripe on its right edge
+///
+/// Usage: termstack tag <index> [red|orange|yellow|green|blue|purple]
+/// (no color removes the tag)
+fn set_cell_tag(args: &[String]) -> Result<()> {
+    use std::io::{BufRead, BufReader};
+
+    use compositor::color_tags::ColorTag;
+
+    const USAGE: &str = "usage: termstack tag <index> [red|orange|yellow|green|blue|purple]";
+
+    let (index, tag) = match args {
+        [index] => (index, None),
+        [index, color] => (index, Some(ColorTag::from_name(color).with_context(|| format!("unknown color {color:?}\n{USAGE}"))?)),
+        _ => bail!(USAGE),
+    };
+    let index: usize = index.parse().with_context(|| format!("invalid cell index\n{}", USAGE))?;
+
+    let socket_path = env::var("TERMSTACK_SOCKET")
+        .context("TERMSTACK_SOCKET not set - are you running inside termstack?")?;
+
+    let msg = serde_json::json!({ "type": "set_tag", "index": index, "tag": tag });
+
+    let stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("failed to connect to {}", socket_path))?;
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(2)))
+        .context("failed to set read timeout")?;
+
+    let mut stream_write = stream.try_clone().context("failed to clone stream")?;
+
+    writeln!(stream_write, "{}", msg).context("failed to send set_tag message")?;
+    stream_write.flush().context("failed to flush set_tag message")?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).context("failed to read set_tag response")?;
+    let result: Result<(), String> = serde_json::from_str(&response).context("invalid set_tag response")?;
+    result.map_err(|e| anyhow::anyhow!(e))
+}
+
+/// Show only the cells whose title or command contains `text` (ignoring
+/// case) or that have a color tag, plus the shells that launched them
+///
+/// Usage: termstack filter <text ...> | --tag <color> | --clear
+fn filter_cells(args: &[String]) -> Result<()> {
+    use std::io::{BufRead, BufReader};
+
+    use compositor::cell_filter::CellFilter;
+    use compositor::color_tags::ColorTag;
+
+    const USAGE: &str = "usage: termstack filter <text ...> | --tag <color> | --clear";
+
+    let filter = match args {
+        [] => bail!(USAGE),
+        [flag] if flag == "--clear" => None,
+        [flag, color] if flag == "--tag" => Some(CellFilter::Tag(
+            ColorTag::from_name(color).with_context(|| format!("unknown color {color:?}\n{USAGE}"))?,
+        )),
+        [flag, ..] if flag.starts_with("--") => bail!(USAGE),
+        words => Some(CellFilter::Command(words.join(" "))),
+    };
+
+    let socket_path = env::var("TERMSTACK_SOCKET")
+        .context("TERMSTACK_SOCKET not set - are you running inside termstack?")?;
+
+    let msg = serde_json::json!({ "type": "filter", "filter": filter });
+
+    let stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("failed to connect to {}", socket_path))?;
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(2)))
+        .context("failed to set read timeout")?;
+
+    let mut stream_write = stream.try_clone().context("failed to clone stream")?;
+
+    writeln!(stream_write, "{}", msg).context("failed to send filter message")?;
+    stream_write.flush().context("failed to flush filter message")?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).context("failed to read filter response")?;
+    let result: Result<usize, String> = serde_json::from_str(&response).context("invalid filter response")?;
+    let matched = result.map_err(|e| anyhow::anyhow!(e))?;
+    if filter.is_some() {
+        println!("showing {matched} matching cell{}", if matched == 1 { "" } else { "s" });
+    }
+    Ok(())
+}
+
+/// Print what is under each screen point (Y=0 at top): the cell a click
+/// would land on, title bar or terminal row and column, the cell whose edge
+/// a press would resize, and the cell drawn there in the last frame
+///
+/// Usage: termstack hit-test <x> <y> [<x> <y> ...]
+fn hit_test(args: &[String]) -> Result<()> {
+    use std::io::{BufRead, BufReader};
+
+    use compositor::hit_test::HitTest;
+
+    const USAGE: &str = "usage: termstack hit-test <x> <y> [<x> <y> ...]";
+
+    if args.is_empty() || args.len() % 2 != 0 {
+        bail!(USAGE);
+    }
+    let coords = args
+        .iter()
+        .map(|arg| arg.parse::<f64>().with_context(|| format!("invalid coordinate {arg:?}\n{USAGE}")))
+        .collect::<Result<Vec<f64>>>()?;
+    let points: Vec<(f64, f64)> = coords.chunks(2).map(|pair| (pair[0], pair[1])).collect();
+
+    let socket_path = env::var("TERMSTACK_SOCKET")
+        .context("TERMSTACK_SOCKET not set - are you running inside termstack?")?;
+
+    let msg = serde_json::json!({ "type": "hit_test", "points": points });
+
+    let stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("failed to connect to {}", socket_path))?;
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(2)))
+        .context("failed to set read timeout")?;
+
+    let mut stream_write = stream.try_clone().context("failed to clone stream")?;
+
+    writeln!(stream_write, "{}", msg).context("failed to send hit_test message")?;
+    stream_write.flush().context("failed to flush hit_test message")?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).context("failed to read hit_test response")?;
+    let hits: Vec<HitTest> = serde_json::from_str(&response).context("invalid hit_test response")?;
+    for hit in hits {
+        println!("{}", serde_json::to_string(&hit)?);
+    }
+    Ok(())
+}
+
+/// Save the stack's commands, order, columns and hand-set heights to a TOML
+/// file, or add the cells of such a file to the stack (running its commands)
+///
+/// Usage: termstack layout export <file.toml> | termstack layout import [--yes] <file.toml>
+///
+/// A layout file may come from someone else, so `import` lists what it would
+/// run and asks first unless `--yes` (or `-y`) is given.
+fn layout_file(args: &[String]) -> Result<()> {
+    use std::io::{BufRead, BufReader};
+    use std::path::Path;
+
+    use compositor::crash::CellKind;
+    use compositor::layout_file::LayoutFile;
+
+    const USAGE: &str = "usage: termstack layout export <file.toml> | termstack layout import [--yes] <file.toml>";
+
+    let (action, path, confirmed) = match args {
+        [action, path] if action == "export" || action == "import" => (action.as_str(), Path::new(path), false),
+        [action, flag, path] | [action, path, flag] if action == "import" && (flag == "--yes" || flag == "-y") => {
+            (action.as_str(), Path::new(path), true)
+        }
+        _ => bail!(USAGE),
+    };
+
+    let msg = if action == "import" {
+        let layout = LayoutFile::load(path)?;
+        if !confirmed {
+            println!("{} would start:", path.display());
+            for cell in &layout.cells {
+                let cwd = cell.cwd.as_deref().map(|cwd| format!(" (in {cwd})")).unwrap_or_default();
+                match cell.kind {
+                    CellKind::Shell => println!("  shell{cwd}"),
+                    CellKind::Command if cell.command.trim().is_empty() => {}
+                    CellKind::Command => println!("  command: {}{cwd}", cell.command),
+                    CellKind::Window => println!("  GUI app: {}{cwd}", cell.command),
+                }
+            }
+            print!("Run these? [y/N] ");
+            std::io::stdout().flush().ok();
+            let mut answer = String::new();
+            std::io::stdin().read_line(&mut answer).context("failed to read answer")?;
+            if !matches!(answer.trim(), "y" | "Y" | "yes") {
+                println!("not importing");
+                return Ok(());
+            }
+        }
+        serde_json::json!({ "type": "layout_import", "layout": layout })
+    } else {
+        serde_json::json!({ "type": "layout_export" })
+    };
+
+    let socket_path = env::var("TERMSTACK_SOCKET")
+        .context("TERMSTACK_SOCKET not set - are you running inside termstack?")?;
+
+    let stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("failed to connect to {}", socket_path))?;
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(5)))
+        .context("failed to set read timeout")?;
+
+    let mut stream_write = stream.try_clone().context("failed to clone stream")?;
+
+    writeln!(stream_write, "{}", msg).context("failed to send layout message")?;
+    stream_write.flush().context("failed to flush layout message")?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).context("failed to read layout response")?;
+    if action == "import" {
+        let result: Result<usize, String> = serde_json::from_str(&response).context("invalid layout response")?;
+        let added = result.map_err(|e| anyhow::anyhow!(e))?;
+        println!("added {added} cell{}", if added == 1 { "" } else { "s" });
+    } else {
+        let layout: LayoutFile = serde_json::from_str(&response).context("invalid layout response")?;
+        layout.save(path)?;
+        println!("saved {} cells to {}", layout.cells.len(), path.display());
+    }
+    Ok(())
+}
+
+/// Act on every cell of the stack at once
+///
+/// Usage: termstack all close-exited|collapse|kill|undo
+///
+/// `close-exited` closes the cells of finished commands, `collapse` hides
+/// every cell but the focused one, `kill` hangs up all running commands and
+/// `undo` takes back the last close or collapse.
+fn bulk_action(args: &[String]) -> Result<()> {
+    use std::io::{BufRead, BufReader};
+
+    use compositor::bulk_actions::{BulkAction, BulkResult};
+
+    const USAGE: &str = "usage: termstack all close-exited|collapse|kill|undo";
+
+    let action = match args {
+        [action] => match action.as_str() {
+            "close-exited" => BulkAction::CloseExited,
+            "collapse" => BulkAction::Collapse,
+            "kill" => BulkAction::KillChildren,
+            "undo" => BulkAction::Undo,
+            _ => bail!(USAGE),
+        },
+        _ => bail!(USAGE),
+    };
+
+    let socket_path = env::var("TERMSTACK_SOCKET")
+        .context("TERMSTACK_SOCKET not set - are you running inside termstack?")?;
+
+    let msg = serde_json::json!({ "type": "bulk", "action": action });
+
+    let stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("failed to connect to {}", socket_path))?;
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(2)))
+        .context("failed to set read timeout")?;
+
+    let mut stream_write = stream.try_clone().context("failed to clone stream")?;
+
+    writeln!(stream_write, "{}", msg).context("failed to send bulk message")?;
+    stream_write.flush().context("failed to flush bulk message")?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).context("failed to read bulk response")?;
+    let result: BulkResult = serde_json::from_str(&response).context("invalid bulk response")?;
+    let done = match action {
+        BulkAction::CloseExited => "closed",
+        BulkAction::Collapse => "collapsed",
+        BulkAction::KillChildren => "hung up",
+        BulkAction::Undo => "restored",
+    };
+    println!("{done} {} cell{}", result.affected, if result.affected == 1 { "" } else { "s" });
+    Ok(())
+}
+
+/// Browse and restore closed command cells
+///
+/// Usage: termstack archive [show|restore <id>]
+///
+/// Without arguments lists the archived cells, newest first. `show` prints a
+/// cell's output and `restore` brings it back as a read-only cell.
+fn archive(args: &[String]) -> Result<()> {
+    use std::io::{BufRead, BufReader};
+
+    use compositor::archive::{ArchiveAction, ArchiveSummary};
+
+    const USAGE: &str = "usage: termstack archive [show|restore <id>]";
+
+    let (action, id) = match args {
+        [] => (ArchiveAction::List, None),
+        [action, id] => {
+            let action = match action.as_str() {
+                "show" => ArchiveAction::Show,
+                "restore" => ArchiveAction::Restore,
+                _ => bail!(USAGE),
+            };
+            let id: u64 = id.parse().with_context(|| format!("invalid archive id: {}", id))?;
+            (action, Some(id))
+        }
+        _ => bail!(USAGE),
+    };
+
+    let socket_path = env::var("TERMSTACK_SOCKET")
+        .context("TERMSTACK_SOCKET not set - are you running inside termstack?")?;
+
+    let msg = serde_json::json!({ "type": "archive", "action": action, "id": id });
+
+    let stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("failed to connect to {}", socket_path))?;
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(2)))
+        .context("failed to set read timeout")?;
+
+    let mut stream_write = stream.try_clone().context("failed to clone stream")?;
+
+    writeln!(stream_write, "{}", msg).context("failed to send archive message")?;
+    stream_write.flush().context("failed to flush archive message")?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).context("failed to read archive response")?;
+    match action {
+        ArchiveAction::List => {
+            let cells = serde_json::from_str::<Result<Vec<ArchiveSummary>, String>>(&response)
+                .context("invalid archive response")?
+                .map_err(|e| anyhow::anyhow!(e))?;
+            if cells.is_empty() {
+                println!("no closed cells archived");
+            }
+            for cell in cells {
+                let status = match cell.exit_code {
+                    Some(0) | None => String::new(),
+                    Some(code) => format!(" (exit {code})"),
+                };
+                let age = match cell.age_secs {
+                    secs if secs < 60 => format!("{secs}s"),
+                    secs => format!("{}m", secs / 60),
+                };
+                println!("{:>4}  {age:>4} ago  {:>5} lines  {}{status}", cell.id, cell.lines, cell.title);
+            }
+        }
+        ArchiveAction::Show => {
+            let text = serde_json::from_str::<Result<String, String>>(&response)
+                .context("invalid archive response")?
+                .map_err(|e| anyhow::anyhow!(e))?;
+            print!("{text}");
+        }
+        ArchiveAction::Restore => {
+            let title = serde_json::from_str::<Result<String, String>>(&response)
+                .context("invalid archive response")?
+                .map_err(|e| anyhow::anyhow!(e))?;
+            println!("restored {title}");
+        }
+    }
+    Ok(())
+}
+
+/// Shut termstack down, closing every cell
+///
+/// Usage: termstack quit [--yes]
+///
+/// Asks for confirmation first unless `--yes` (or `-y`) is given.
+fn quit(args: &[String]) -> Result<()> {
+    use std::io::{BufRead, BufReader};
+
+    let confirmed = match args {
+        [] => false,
+        [flag] if flag == "--yes" || flag == "-y" => true,
+        _ => bail!("usage: termstack quit [--yes]"),
+    };
+    if !confirmed {
+        print!("Quit termstack and close every cell? [y/N] ");
+        std::io::stdout().flush().ok();
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer).context("failed to read answer")?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            println!("not quitting");
+            return Ok(());
+        }
+    }
+
+    let socket_path = env::var("TERMSTACK_SOCKET")
+        .context("TERMSTACK_SOCKET not set - are you running inside termstack?")?;
+
+    let msg = serde_json::json!({ "type": "quit" });
+
+    let stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("failed to connect to {}", socket_path))?;
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(2)))
+        .context("failed to set read timeout")?;
+
+    let mut stream_write = stream.try_clone().context("failed to clone stream")?;
+
+    writeln!(stream_write, "{}", msg).context("failed to send quit message")?;
+    stream_write.flush().context("failed to flush quit message")?;
+
+    // The compositor may be gone before the answer arrives
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).ok();
+    Ok(())
+}
+
+/// List the cells of the stack, or with `--json` print the layout snapshot
+///
+/// Usage: termstack ls [--json] [--tag <color>]
+///
+/// `--debug` is the flag's older name.
+fn list_cells(args: &[String]) -> Result<()> {
+    use compositor::color_tags::ColorTag;
+    use compositor::layout_dump::CellKind;
+
+    const USAGE: &str = "usage: termstack ls [--json] [--tag <color>]";
+
+    let mut json = false;
+    let mut tag = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" | "--debug" => json = true,
+            "--tag" => {
+                let color = args.next().context(USAGE)?;
+                tag = Some(ColorTag::from_name(color).with_context(|| format!("unknown color {color:?}"))?);
+            }
+            _ => bail!(USAGE),
+        }
+    }
+
+    let layout = fetch_layout()?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&layout)?);
+        return Ok(());
+    }
+    for (index, cell) in layout.cells.iter().enumerate() {
+        if tag.is_some() && cell.tag != tag {
+            continue;
+        }
+        let marker = if layout.focused == Some(index) { '*' } else { ' ' };
+        let kind = match cell.kind {
+            CellKind::Terminal => "term",
+            CellKind::External => "gui",
+        };
+        let hidden = if cell.hidden { " (hidden)" } else { "" };
+        let color = cell.tag.map(|tag| format!("[{}] ", tag.name())).unwrap_or_default();
+        println!("{marker}{index:>3}  {kind:<4}  {color}{}{hidden}", cell.title);
+    }
+    Ok(())
+}
+
+/// Fetch the compositor's layout snapshot (what `termstack ls --json` prints)
+pub(crate) fn fetch_layout() -> Result<compositor::layout_dump::LayoutDump> {
+    use std::io::{BufRead, BufReader};
+
+    let socket_path = env::var("TERMSTACK_SOCKET")
+        .context("TERMSTACK_SOCKET not set - are you running inside termstack?")?;
+
+    let msg = serde_json::json!({ "type": "layout" });
+
+    let stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("failed to connect to {}", socket_path))?;
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(2)))
+        .context("failed to set read timeout")?;
+
+    let mut stream_write = stream.try_clone().context("failed to clone stream")?;
+
+    writeln!(stream_write, "{}", msg).context("failed to send layout message")?;
+    stream_write.flush().context("failed to flush layout message")?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).context("failed to read layout response")?;
+    serde_json::from_str(&response).context("invalid layout response")
+}
+
+/// Spawn command in a new termstack terminal
+///
+/// The terminal starts small and grows with content. TUI apps are
+/// auto-detected via alternate screen mode and resized to full viewport.
+fn spawn_in_terminal(command: &str, prompt: &str, options: &SpawnOptions) -> Result<()> {
+    send_spawn_request(command, prompt, options)?;
+
+    // Clear the command line from the invoking terminal
+    if !command.is_empty() {
+        print!("\x1b[A\x1b[2K");
+        std::io::stdout().flush().ok();
+    }
+
+    Ok(())
+}
+
+/// Ask the compositor to spawn `command` in a new terminal
+fn send_spawn_request(command: &str, prompt: &str, options: &SpawnOptions) -> Result<()> {
+    let debug = debug_enabled();
+
+    // Get socket path from environment
+    let socket_path = env::var("TERMSTACK_SOCKET")
+        .context("TERMSTACK_SOCKET not set - are you running inside termstack?")?;
+    if debug { eprintln!("[termstack] socket path: {}", socket_path); }
+
+    // Collect current environment
+    let env_vars: std::collections::HashMap<String, String> = env::vars().collect();
+
+    // Get working directory (current one unless overridden with --cwd)
+    let cwd = match &options.cwd {
+        Some(cwd) => cwd.clone(),
+        None => env::current_dir().context("failed to get current directory")?,
+    };
+    let cwd = cwd.to_string_lossy().to_string();
+    if debug { eprintln!("[termstack] cwd: {}", cwd); }
+
+    // Build JSON message
+    let mut msg = serde_json::json!({
+        "type": "spawn",
+        "prompt": prompt,
+        "command": command,
+        "cwd": cwd,
+        "env": env_vars,
+    });
+    if let Some(shell) = &options.shell {
+        msg["shell"] = shell.clone().into();
+    }
+    if !options.env.is_empty() {
+        msg["env_overrides"] = serde_json::json!(options.env);
+    }
+
+    if debug { eprintln!("[termstack] connecting to socket..."); }
+    // Connect to compositor and send message
+    let mut stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("failed to connect to {}", socket_path))?;
+    if debug { eprintln!("[termstack] connected, sending message..."); }
+
+    writeln!(stream, "{}", msg).context("failed to send message")?;
+    stream.flush().context("failed to flush message")?;
+    if debug { eprintln!("[termstack] message sent successfully"); }
+
+    Ok(())
+}
+
+/// Recreate the cells of a recorded session and play back their output
+///
+/// Usage: termstack replay [session-dir] [--speed N]
+///
+/// Without a directory the most recent recording is replayed. Cells are
+/// spawned with the same delays as in the recording, divided by `--speed`
+/// like the output itself (see `play_recording`).
+fn replay_session(args: &[String]) -> Result<()> {
+    use compositor::recording;
+    use std::time::Duration;
+
+    const USAGE: &str = "usage: termstack replay [session-dir] [--speed N]";
+
+    let (dir, speed) = parse_replay_args(args, USAGE)?;
+    let dir = match dir {
+        Some(dir) => dir,
+        None => recording::latest_session()
+            .context("no recorded sessions (set record_sessions = true in the config)")?,
+    };
+    let casts = recording::session_casts(&dir)?;
+    if casts.is_empty() {
+        bail!("no recordings in {}", dir.display());
+    }
+
+    let exe = env::current_exe().context("failed to find the termstack binary")?;
+    let exe = compositor::command_templates::shell_quote(&exe.to_string_lossy());
+    let first = casts[0].1.timestamp;
+    let mut elapsed = 0;
+    for (path, header) in &casts {
+        let offset = header.timestamp - first;
+        std::thread::sleep(Duration::from_secs_f64((offset - elapsed) as f64 / speed));
+        elapsed = offset;
+
+        let command = format!(
+            "{} play --speed {} {}",
+            exe,
+            speed,
+            compositor::command_templates::shell_quote(&path.to_string_lossy())
+        );
+        println!("{}", header.title);
+        send_spawn_request(&command, "", &SpawnOptions::default())?;
+    }
+    Ok(())
+}
+
+/// Write the default config file, so termstack counts as set up
+///
+/// Usage: termstack init-config [--force]
+pub(crate) fn init_config(args: &[String]) -> Result<()> {
+    let force = match args {
+        [] => false,
+        [flag] if flag == "--force" => true,
+        _ => bail!("usage: termstack init-config [--force]"),
+    };
+    let path = compositor::config::Config::user_config_path()
+        .context("no config directory (neither XDG_CONFIG_HOME nor HOME is set)")?;
+    compositor::config::Config::write_default(&path, force)?;
+    println!("Wrote {}", path.display());
+    println!("Changes take effect the next time termstack starts.");
+    Ok(())
+}
+
+/// Write a recording to stdout with its original timing
+///
+/// Usage: termstack play <file.cast> [--speed N]
+pub(crate) fn play_recording(args: &[String]) -> Result<()> {
+    use std::time::{Duration, Instant};
+
+    const USAGE: &str = "usage: termstack play <file.cast> [--speed N]";
+
+    let (path, speed) = parse_replay_args(args, USAGE)?;
+    let path = path.context(USAGE)?;
+    let (_, events) = compositor::recording::read_cast(&path)?;
+
+    let start = Instant::now();
+    let mut stdout = std::io::stdout();
+    for event in events {
+        let due = Duration::from_secs_f64(event.time / speed);
+        if let Some(wait) = due.checked_sub(start.elapsed()) {
+            std::thread::sleep(wait);
+        }
+        stdout.write_all(event.data.as_bytes()).context("failed to write to stdout")?;
+        stdout.flush().context("failed to write to stdout")?;
+    }
+    Ok(())
+}
+
+/// A path and `--speed N` (default 1)
+pub(crate) fn parse_replay_args(args: &[String], usage: &str) -> Result<(Option<PathBuf>, f64)> {
+    let mut path = None;
+    let mut speed = 1.0;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--speed" => {
+                let value = args.next().context(usage.to_string())?;
+                speed = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|speed| *speed > 0.0 && speed.is_finite())
+                    .with_context(|| format!("invalid speed: {value}"))?;
+            }
+            arg if path.is_none() => path = Some(PathBuf::from(arg)),
+            _ => bail!("{}", usage),
+        }
+    }
+    Ok((path, speed))
+}
+
+/// Export a terminal cell's scrollback and grid
+///
+/// Usage: termstack export <index> [--format html|ansi|txt] [-o file]
+///
+/// `index` is the cell's position in the stack as printed by
+/// `query-windows`. HTML (the default) is a standalone page with colors
+/// preserved; the content goes to stdout unless `-o` is given.
+fn export_cell(args: &[String]) -> Result<()> {
+    const USAGE: &str = "usage: termstack export <index> [--format html|ansi|txt] [-o file]";
+
+    let mut index = None;
+    let mut format = "html";
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" | "-f" => format = args.next().context(USAGE)?,
+            "--output" | "-o" => output = Some(PathBuf::from(args.next().context(USAGE)?)),
+            arg if index.is_none() => {
+                index = Some(arg.parse::<usize>().with_context(|| format!("invalid cell index: {arg}"))?);
+            }
+            _ => bail!(USAGE),
+        }
+    }
+    let index = index.context(USAGE)?;
+    if !matches!(format, "html" | "ansi" | "txt") {
+        bail!("unknown export format: {} (expected html, ansi or txt)", format);
+    }
+
+    let content = request_export(index, format)?;
+    match output {
+        Some(path) => std::fs::write(&path, content)
+            .with_context(|| format!("failed to write {}", path.display()))?,
+        None => std::io::stdout().write_all(content.as_bytes()).context("failed to write to stdout")?,
+    }
+    Ok(())
+}
+
+/// Ask the compositor for the content of the cell at `index`
+fn request_export(index: usize, format: &str) -> Result<String> {
+    use std::io::{BufRead, BufReader};
+
+    let socket_path = env::var("TERMSTACK_SOCKET")
+        .context("TERMSTACK_SOCKET not set - are you running inside termstack?")?;
+
+    let msg = serde_json::json!({
+        "type": "export",
+        "index": index,
+        "format": format,
+    });
+
+    let stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("failed to connect to {}", socket_path))?;
+
+    // The answer comes on the next frame
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(5)))
+        .context("failed to set read timeout")?;
+
+    let mut stream_write = stream.try_clone().context("failed to clone stream")?;
+
+    writeln!(stream_write, "{}", msg).context("failed to send export message")?;
+    stream_write.flush().context("failed to flush export message")?;
+
+    let mut reader = BufReader::new(stream);
+    let mut response = String::new();
+    reader.read_line(&mut response).context("failed to read export response")?;
+    serde_json::from_str::<Result<String, String>>(&response)
+        .context("invalid export response")?
+        .map_err(|e| anyhow::anyhow!(e))
+}
+
+/// Follow the stack for screen readers
+///
+/// Usage: termstack a11y [--watch] [--json] | termstack a11y read <index> [--lines N]
+///
+/// Prints one announcement per line ("cargo test, terminal, 3 of 5"); with
+/// `--watch` it keeps announcing focus changes and cells opening, finishing
+/// and closing, e.g. for piping into `spd-say`. `--json` prints the raw tree
+/// lines instead. `read` prints the last N (default 20) lines of a terminal
+/// cell, numbered as in `query-windows`.
+fn accessibility(args: &[String]) -> Result<()> {
+    use std::io::{BufRead, BufReader};
+
+    use compositor::accessibility::{announcements, AccessTree};
+
+    const USAGE: &str = "usage: termstack a11y [--watch] [--json] | termstack a11y read <index> [--lines N]";
+
+    if args.first().is_some_and(|arg| arg == "read") {
+        let mut index = None;
+        let mut lines = 20;
+        let mut args = args[1..].iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--lines" | "-n" => {
+                    let count = args.next().context(USAGE)?;
+                    lines = count.parse().with_context(|| format!("invalid line count: {count}"))?;
+                }
+                arg if index.is_none() => {
+                    index = Some(arg.parse::<usize>().with_context(|| format!("invalid cell index: {arg}"))?);
+                }
+                _ => bail!(USAGE),
+            }
+        }
+        let content = request_export(index.context(USAGE)?, "txt")?;
+        let content: Vec<&str> = content.trim_end().lines().collect();
+        for line in &content[content.len().saturating_sub(lines)..] {
+            println!("{}", line.trim_end());
+        }
+        return Ok(());
+    }
+
+    let mut watch = false;
+    let mut json = false;
+    for arg in args {
+        match arg.as_str() {
+            "--watch" => watch = true,
+            "--json" => json = true,
+            _ => bail!(USAGE),
+        }
+    }
+
+    let socket_path = env::var("TERMSTACK_SOCKET")
+        .context("TERMSTACK_SOCKET not set - are you running inside termstack?")?;
+
+    let msg = serde_json::json!({
+        "type": "accessibility",
+        "watch": watch,
+    });
+
+    let stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("failed to connect to {}", socket_path))?;
+    if !watch {
+        stream.set_read_timeout(Some(std::time::Duration::from_secs(2)))
+            .context("failed to set read timeout")?;
+    }
+
+    let mut stream_write = stream.try_clone().context("failed to clone stream")?;
+
+    writeln!(stream_write, "{}", msg).context("failed to send accessibility message")?;
+    stream_write.flush().context("failed to flush accessibility message")?;
+
+    let mut previous: Option<AccessTree> = None;
+    for line in BufReader::new(stream).lines() {
+        let line = line.context("failed to read accessibility response")?;
+        if json {
+            println!("{}", line);
+        } else {
+            let tree: AccessTree = serde_json::from_str(&line).context("invalid accessibility response")?;
+            for announcement in announcements(previous.as_ref(), &tree) {
+                println!("{}", announcement);
+            }
+            previous = Some(tree);
+        }
+        std::io::stdout().flush().context("failed to write announcement")?;
+        if !watch {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Spawn a command template from the config's `[commands]` table, or list
+/// the templates when no name is given
+fn spawn_template(args: &[String]) -> Result<()> {
+    let config = compositor::config::Config::load();
+    let Some((name, template_args)) = args.split_first() else {
+        if config.commands.is_empty() {
+            println!("No command templates; add a [commands] table to the config.");
+        }
+        for (name, template) in &config.commands {
+            println!("{}\t{}", name, template);
+        }
+        return Ok(());
+    };
+
+    let Some(template) = config.commands.get(name) else {
+        bail!("unknown command template: {} (see `termstack tpl`)", name);
+    };
+    let command = compositor::command_templates::expand(template, template_args)
+        .with_context(|| format!("template {}: {}", name, template))?;
+    if debug_enabled() { eprintln!("[termstack] template {}: {:?}", name, command); }
+
+    let prompt = env::var("TERMSTACK_PROMPT").unwrap_or_default();
+    spawn_in_terminal(&command, &prompt, &SpawnOptions::default())
+}
+
+/// Spawn a GUI app with foreground/background mode
+///
+/// In foreground mode, the launching terminal is hidden until the GUI app exits.
+/// In background mode, the launching terminal stays visible and usable.
+fn spawn_gui_app(command: &str, foreground: bool) -> Result<()> {
+    let debug = debug_enabled();
+
+    // Get socket path from environment
+    let socket_path = env::var("TERMSTACK_SOCKET")
+        .context("TERMSTACK_SOCKET not set - are you running inside termstack?")?;
+    if debug { eprintln!("[termstack] socket path: {}", socket_path); }
+
+    // Collect current environment
+    let env_vars: std::collections::HashMap<String, String> = env::vars().collect();
+
+    // Get current working directory
+    let cwd = env::current_dir()
+        .context("failed to get current directory")?
+        .to_string_lossy()
+        .to_string();
+    if debug { eprintln!("[termstack] cwd: {}", cwd); }
+
+    // Build JSON message for GUI spawn (unified with terminal spawn)
+    let msg = serde_json::json!({
+        "type": "spawn",
+        "command": command,
+        "cwd": cwd,
+        "env": env_vars,
+        "foreground": foreground,
+    });
+
+    if debug { eprintln!("[termstack] connecting to socket for GUI spawn..."); }
+    // Connect to compositor and send message
+    let mut stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("failed to connect to {}", socket_path))?;
+    if debug { eprintln!("[termstack] connected, sending gui_spawn message..."); }
+
+    writeln!(stream, "{}", msg).context("failed to send message")?;
+    stream.flush().context("failed to flush message")?;
+    if debug { eprintln!("[termstack] gui_spawn message sent successfully"); }
+
+    // Clear the command line from the invoking terminal
+    print!("\x1b[A\x1b[2K");
+    std::io::stdout().flush().ok();
+
+    Ok(())
+}
+
+/// Extract termstack subcommand from a command string
+///
+/// When fish integration intercepts "termstack test-x11", it calls
+/// "termstack -c 'termstack test-x11'". We detect this and extract
+/// the subcommand args to execute directly (avoiding PATH lookup issues).
+///
+/// Returns the full subcommand string (e.g., "test-x11" or "gui firefox")
+fn extract_termstack_subcommand(command: &str) -> Option<String> {
+    let parts: Vec<&str> = command.split_whitespace().collect();
+    if parts.is_empty() {
+        return None;
+    }
+
+    // Check if first word is "termstack" (or path ending in termstack)
+    let first = parts[0];
+    let is_termstack = first == "termstack"
+        || first.ends_with("/termstack")
+        || first == "$TERMSTACK_BIN";
+
+    if !is_termstack {
+        return None;
+    }
+
+    // Check if second word is a known subcommand
+    if parts.len() < 2 {
+        return None;
+    }
+
+    let subcommands = ["diagnose", "test-x11", "query-windows", "debug-bundle", "doctor", "gui", "--status", "--resize", "--builtin", "--help", "-h"];
+    if subcommands.contains(&parts[1]) {
+        // Return everything after "termstack"
+        Some(parts[1..].join(" "))
+    } else {
+        None
+    }
+}
+
+/// Execute a termstack subcommand directly
+fn execute_subcommand(subcommand: &str) -> Result<()> {
+    let parts: Vec<&str> = subcommand.split_whitespace().collect();
+    if parts.is_empty() {
+        bail!("empty subcommand");
+    }
+
+    match parts[0] {
+        "diagnose" => run_diagnostics(),
+        "test-x11" => test_x11_connectivity(),
+        "query-windows" => query_windows(),
+        "status" => {
+            let status_args: Vec<String> = parts[1..].iter().map(|s| s.to_string()).collect();
+            show_status(&status_args)
+        }
+        "debug-bundle" => {
+            let bundle_args: Vec<String> = parts[1..].iter().map(|s| s.to_string()).collect();
+            crate::bundle::create(&bundle_args)
+        }
+        "doctor" | "--status" => crate::doctor::run(),
+        "--resize" => {
+            let mode = parts.get(1).copied().unwrap_or("full");
+            send_resize_request(mode)
+        }
+        "--builtin" => {
+            // Convert &str parts back to String for send_builtin_notification
+            let builtin_args: Vec<String> = parts[1..].iter().map(|s| s.to_string()).collect();
+            send_builtin_notification(&builtin_args)
+        }
+        "gui" => {
+            if parts.len() < 2 {
+                bail!("usage: termstack gui <command>");
+            }
+            let gui_command = parts[1..].join(" ");
+            let foreground = env::var("TERMSTACK_GUI_BACKGROUND").is_err();
+            spawn_gui_app(&gui_command, foreground)
+        }
+        "--help" | "-h" => {
+            println!("termstack - Terminal compositor CLI");
+            println!();
+            println!("Subcommands:");
+            println!("  diagnose       Run X11/Wayland diagnostics");
+            println!("  test-x11       Test X11 connectivity");
+            println!("  query-windows  Query current window state (JSON output)");
+            println!("  status         Show what the focused cell is running (--watch, --json)");
+            println!("  dnd [on|off]   Toggle do-not-disturb (--focus hides other cells)");
+            println!("  title <index>  Name a cell (no text goes back to the command)");
+            println!("  tag <index>    Color-tag a cell (red, orange, yellow, green, blue, purple; none removes it)");
+            println!("  filter <text>  Show only cells whose command contains text (--tag <color>, --clear)");
+            println!("  layout export  Save commands, columns and heights to a TOML file (import <file> adds them after asking)");
+            println!("  hit-test <x> <y>  Print the cell, row and column a click at a screen point reaches (JSON)");
+            println!("  back           Focus the previously focused cell (like cd -)");
+            println!("  all <action>   close-exited, collapse, kill or undo across the stack");
+            println!("  archive        List closed command cells (show|restore <id>)");
+            println!("  quit           Shut termstack down after asking (--yes skips the question)");
+            println!("  ls             List the cells, * marks the focused one (--tag <color>, --json: layout as JSON)");
+            println!("  a11y           Announce focus and cell changes for screen readers (--watch, read <index>)");
+            println!("  debug-bundle   Collect logs, config and state for a bug report");
+            println!("  doctor         Check GPU, fonts, xwayland-satellite, config and shell integration");
+            println!("  env            Print DISPLAY and XAUTHORITY for X11 apps (fails until XWayland is ready)");
+            println!("  init-config    Write the default config file (--force overwrites)");
+            println!("  gui <cmd>      Launch GUI app inside termstack");
+            println!("  tpl [name ...] Spawn a command template (lists them without a name)");
+            println!("  export <index> Save a cell as HTML, ANSI or text (--format, -o)");
+            println!("  replay [dir]   Replay a recorded session (--speed N)");
+            println!("  play <file>    Play one cell's recording (--speed N)");
+            println!("  --status       Same as doctor");
+            println!("  --resize       Resize focused terminal");
+            println!();
+            println!("Spawn options (before the command): --shell <shell>, --env KEY=VAL, --cwd <dir>");
+            Ok(())
+        }
+        _ => bail!("unknown subcommand: {}", parts[0]),
+    }
+}
+
+/// Per-cell overrides given before the command
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct SpawnOptions {
+    /// Shell for the new cell (absolute path)
+    pub shell: Option<String>,
+    /// Variables to set in the new cell
+    pub env: BTreeMap<String, String>,
+    /// Working directory for the new cell
+    pub cwd: Option<PathBuf>,
+}
+
+/// Split `--shell SHELL`, `--env KEY=VAL` and `--cwd PATH` off the front of
+/// `args` (without the program name), returning the rest
+pub(crate) fn parse_spawn_options(mut args: &[String]) -> Result<(SpawnOptions, &[String])> {
+    let mut options = SpawnOptions::default();
+    while let [flag, value, rest @ ..] = args {
+        match flag.as_str() {
+            "--shell" => options.shell = Some(resolve_shell(value)?),
+            "--env" => {
+                let Some((key, val)) = value.split_once('=').filter(|(key, _)| !key.is_empty()) else {
+                    bail!("invalid --env {:?} (expected KEY=VALUE)", value);
+                };
+                options.env.insert(key.to_string(), val.to_string());
+            }
+            "--cwd" => {
+                let cwd = std::fs::canonicalize(value)
+                    .with_context(|| format!("invalid --cwd {}", value))?;
+                if !cwd.is_dir() {
+                    bail!("--cwd {} is not a directory", value);
+                }
+                options.cwd = Some(cwd);
+            }
+            _ => break,
+        }
+        args = rest;
+    }
+    if let [flag] = args {
+        if matches!(flag.as_str(), "--shell" | "--env" | "--cwd") {
+            bail!("missing value after {}", flag);
+        }
+    }
+    Ok((options, args))
+}
+
+/// Absolute path of a shell given by name (`zsh`) or path
+fn resolve_shell(shell: &str) -> Result<String> {
+    let path = if shell.contains('/') {
+        std::fs::canonicalize(shell).ok()
+    } else {
+        env::var_os("PATH")
+            .and_then(|paths| env::split_paths(&paths).map(|dir| dir.join(shell)).find(|path| path.is_file()))
+    };
+    match path {
+        Some(path) => Ok(path.to_string_lossy().into_owned()),
+        None => bail!("shell not found: {}", shell),
+    }
+}
+
+/// `command` without the inline prefix, if it starts with it (an empty
+/// prefix turns the opt-out off)
+pub(crate) fn inline_command<'a>(command: &'a str, prefix: &str) -> Option<&'a str> {
+    if prefix.is_empty() {
+        return None;
+    }
+    command.strip_prefix(prefix).filter(|rest| !rest.trim().is_empty())
+}
+
+/// Parse command from the arguments after the spawn options
+///
+/// Supports:
+/// - `termstack -c "command"` - run command
+/// - `termstack` - run interactive shell (empty command)
+fn parse_command(args: &[String]) -> Result<String> {
+    match args {
+        // No arguments - spawn interactive shell
+        [] => Ok(String::new()),
+        [flag] if flag == "-c" => bail!("missing command after -c"),
+        // -c "command", or -c with multiple args: join them
+        [flag, rest @ ..] if flag == "-c" => Ok(rest.join(" ")),
+        // No -c: join all args as command
+        _ => Ok(args.join(" ")),
+    }
 }
 
 /// Run X11/Wayland diagnostics to help debug GUI app issues
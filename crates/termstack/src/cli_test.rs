@@ -247,6 +247,109 @@ mod tests {
         }
     }
 
+    #[test]
+    fn fish_integration_classifies_functions_by_their_body() {
+        // Functions and aliases calling a state-affecting command run in the
+        // current shell; ones running other programs spawn a terminal
+        use std::process::Command;
+
+        let script_path = match find_integration_script("integration.fish") {
+            Some(p) => p,
+            None => {
+                eprintln!("Skipping test: can't find integration.fish");
+                return;
+            }
+        };
+
+        let output = Command::new("fish")
+            .args(["-c", &format!(
+                "source {}; \
+                 alias up 'cd ..'; alias ll 'ls -l'; \
+                 function mkcd; mkdir -p $argv; and cd $argv; end; \
+                 function outer; up; end; \
+                 function cached; set -g __cache 1; set -l x 2; end; \
+                 function setenv; set -gx FOO $argv; end; \
+                 for c in up ll mkcd outer cached setenv cd ls; \
+                     __termstack_runs_in_shell $c; and echo \"$c=shell\"; or echo \"$c=cell\"; \
+                 end",
+                script_path
+            )])
+            .env("TERMSTACK_SOCKET", "/tmp/fake")
+            .output();
+
+        match output {
+            Ok(o) => {
+                let stdout = String::from_utf8_lossy(&o.stdout);
+                for expected in [
+                    "up=shell", "ll=cell", "mkcd=shell", "outer=shell",
+                    "cached=cell", "setenv=shell", "cd=shell", "ls=cell",
+                ] {
+                    assert!(
+                        stdout.lines().any(|line| line == expected),
+                        "expected {expected}. stdout: {}, stderr: {}",
+                        stdout,
+                        String::from_utf8_lossy(&o.stderr)
+                    );
+                }
+            }
+            Err(e) => {
+                eprintln!("Skipping test: fish not available: {}", e);
+            }
+        }
+    }
+
+    #[test]
+    fn fish_integration_expands_aliases_before_spawning() {
+        // The spawned cell doesn't have the shell's functions, so aliases are
+        // replaced by what they stand for; other functions are sent as typed
+        use std::process::Command;
+
+        let script_path = match find_integration_script("integration.fish") {
+            Some(p) => p,
+            None => {
+                eprintln!("Skipping test: can't find integration.fish");
+                return;
+            }
+        };
+
+        let output = Command::new("fish")
+            .args(["-c", &format!(
+                "source {}; \
+                 alias ll 'ls -l'; alias lla 'll -a'; alias grep 'grep --color'; \
+                 function mine; ls $argv; end; \
+                 for c in 'll' 'll /tmp' 'lla x' 'grep foo' 'mine -a' 'make all'; \
+                     echo \"[$c] -> [\"(__termstack_expand_alias $c)\"]\"; \
+                 end",
+                script_path
+            )])
+            .env("TERMSTACK_SOCKET", "/tmp/fake")
+            .output();
+
+        match output {
+            Ok(o) => {
+                let stdout = String::from_utf8_lossy(&o.stdout);
+                for expected in [
+                    "[ll] -> [ls -l]",
+                    "[ll /tmp] -> [ls -l /tmp]",
+                    "[lla x] -> [ls -l -a x]",
+                    "[grep foo] -> [grep --color foo]",
+                    "[mine -a] -> [mine -a]",
+                    "[make all] -> [make all]",
+                ] {
+                    assert!(
+                        stdout.lines().any(|line| line == expected),
+                        "expected {expected}. stdout: {}, stderr: {}",
+                        stdout,
+                        String::from_utf8_lossy(&o.stderr)
+                    );
+                }
+            }
+            Err(e) => {
+                eprintln!("Skipping test: fish not available: {}", e);
+            }
+        }
+    }
+
     #[test]
     fn fish_integration_not_active_without_socket() {
         // Verify the fish integration script does NOT define termstack_exec when socket is unset
@@ -285,6 +388,106 @@ mod tests {
         }
     }
 
+    #[test]
+    fn debug_bundle_archives_logs() {
+        // debug-bundle works outside a session and includes rotated log files
+        use std::process::Command;
+
+        let bin_path = match find_column_term_binary() {
+            Some(p) => p,
+            None => {
+                eprintln!("Skipping test: can't find termstack binary");
+                return;
+            }
+        };
+
+        let dir = std::env::temp_dir().join(format!("termstack-bundle-test-{}", std::process::id()));
+        let log_dir = dir.join("state/termstack/log");
+        std::fs::create_dir_all(&log_dir).unwrap();
+        std::fs::write(log_dir.join("termstack.log"), "current\n").unwrap();
+        std::fs::write(log_dir.join("termstack.log.1"), "older\n").unwrap();
+        let archive = dir.join("bundle.tar.gz");
+
+        let output = Command::new(&bin_path)
+            .arg("debug-bundle")
+            .arg(&archive)
+            .env("XDG_STATE_HOME", dir.join("state"))
+            .env("XDG_CONFIG_HOME", dir.join("config"))
+            .env_remove("TERMSTACK_SOCKET")
+            .output()
+            .expect("failed to run termstack");
+        assert!(
+            output.status.success(),
+            "debug-bundle failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let listing = Command::new("tar").arg("-tzf").arg(&archive).output().unwrap();
+        let listing = String::from_utf8_lossy(&listing.stdout);
+        assert!(listing.contains("version.txt"), "missing version.txt: {}", listing);
+        assert!(listing.contains("logs/termstack.log.1"), "missing rotated log: {}", listing);
+        assert!(!listing.contains("ls.json"), "no snapshot outside a session: {}", listing);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn inline_prefix_opts_out_of_a_new_cell() {
+        use crate::cli::inline_command;
+
+        assert_eq!(inline_command("!make install", "!"), Some("make install"));
+        assert_eq!(inline_command(" vim notes", " "), Some("vim notes"));
+        assert_eq!(inline_command(",,top", ",,"), Some("top"));
+        // Only at the very start, and not the prefix alone
+        assert_eq!(inline_command("echo !x", "!"), None);
+        assert_eq!(inline_command("! ", "!"), None);
+        // No prefix configured: everything spawns
+        assert_eq!(inline_command("!make", ""), None);
+    }
+
+    #[test]
+    fn spawn_options_come_before_the_command() {
+        use crate::cli::parse_spawn_options;
+
+        let args: Vec<String> = ["--env", "A=1", "--env", "B=x=y", "--cwd", "/", "-c", "make", "--env", "C=2"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let (options, rest) = parse_spawn_options(&args).unwrap();
+        assert_eq!(options.env.get("A").map(String::as_str), Some("1"));
+        assert_eq!(options.env.get("B").map(String::as_str), Some("x=y"));
+        assert_eq!(options.cwd, Some(std::path::PathBuf::from("/")));
+        assert!(options.shell.is_none());
+        // Everything after the command belongs to it
+        assert_eq!(rest, &args[6..]);
+
+        let args = vec!["--shell".to_string(), "sh".to_string()];
+        let (options, rest) = parse_spawn_options(&args).unwrap();
+        assert!(options.shell.is_some_and(|shell| shell.starts_with('/') && shell.ends_with("/sh")));
+        assert!(rest.is_empty());
+
+        for bad in [&["--env", "=1"][..], &["--env"], &["--cwd", "/nonexistent-dir-12345"], &["--shell", "no-such-shell-12345"]] {
+            let args: Vec<String> = bad.iter().map(|s| s.to_string()).collect();
+            assert!(parse_spawn_options(&args).is_err(), "{:?} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn replay_args_take_a_path_and_speed() {
+        use crate::cli::parse_replay_args;
+
+        let args: Vec<String> = ["--speed", "4", "/tmp/rec"].iter().map(|s| s.to_string()).collect();
+        let (path, speed) = parse_replay_args(&args, "usage").unwrap();
+        assert_eq!(path, Some(std::path::PathBuf::from("/tmp/rec")));
+        assert!((speed - 4.0).abs() < f64::EPSILON);
+
+        assert_eq!(parse_replay_args(&[], "usage").unwrap(), (None, 1.0));
+        for bad in [&["--speed", "0"][..], &["--speed", "fast"], &["--speed"], &["a", "b"]] {
+            let args: Vec<String> = bad.iter().map(|s| s.to_string()).collect();
+            assert!(parse_replay_args(&args, "usage").is_err(), "{:?} should be rejected", bad);
+        }
+    }
+
     // Helper functions for finding binaries and scripts
 
     fn find_column_term_binary() -> Option<String> {
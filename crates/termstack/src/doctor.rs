@@ -0,0 +1,411 @@
+//! Environment and dependency diagnosis
+//!
+//! `termstack doctor` (also `termstack --status`) checks what termstack needs
+//! to show anything: a GPU with EGL, xwayland-satellite for X11 apps (inside
+//! a session, whether the compositor has it running), a monospace font, a
+//! healthy IPC socket, a shell with integration (fish) and a valid config
+//! file. Each problem comes with the command or edit that fixes it. Works
+//! inside and outside a session.
+
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Result};
+use compositor::xwayland_supervisor::{XWaylandState, XWaylandStatus};
+
+/// How a check went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Ok,
+    /// Works, but something is missing or odd
+    Warn,
+    /// termstack will not work (or not show anything)
+    Fail,
+}
+
+/// Result of one check
+#[derive(Debug, Clone)]
+struct Check {
+    name: &'static str,
+    outcome: Outcome,
+    detail: String,
+    /// What to do about a warning or failure
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self { name, outcome: Outcome::Ok, detail: detail.into(), fix: None }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self { name, outcome: Outcome::Warn, detail: detail.into(), fix: Some(fix.into()) }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self { name, outcome: Outcome::Fail, detail: detail.into(), fix: Some(fix.into()) }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mark = match self.outcome {
+            Outcome::Ok => "ok  ",
+            Outcome::Warn => "warn",
+            Outcome::Fail => "FAIL",
+        };
+        write!(f, "[{mark}] {}: {}", self.name, self.detail)?;
+        if let Some(fix) = &self.fix {
+            for line in fix.lines() {
+                write!(f, "\n       fix: {line}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run all checks and print them; fails if any check failed
+pub fn run() -> Result<()> {
+    println!("termstack {} doctor\n", env!("CARGO_PKG_VERSION"));
+
+    let checks = [
+        check_session(),
+        check_gpu(),
+        check_xwayland_satellite(),
+        check_font(),
+        check_shell_integration(),
+        check_config(),
+    ];
+    for check in &checks {
+        println!("{check}");
+    }
+
+    let failed = checks.iter().filter(|c| c.outcome == Outcome::Fail).count();
+    let warned = checks.iter().filter(|c| c.outcome == Outcome::Warn).count();
+    println!();
+    if failed > 0 {
+        bail!("{failed} check(s) failed, {warned} warning(s)");
+    }
+    if warned > 0 {
+        println!("No failures, {warned} warning(s).");
+    } else {
+        println!("Everything looks good.");
+    }
+    Ok(())
+}
+
+/// Whether this shell is in a session and the compositor answers on its socket
+fn check_session() -> Check {
+    const NAME: &str = "session";
+    if let Ok(socket) = env::var("TERMSTACK_SOCKET") {
+        return match crate::cli::fetch_windows_json() {
+            Ok(json) => {
+                let cells = serde_json::from_str::<Vec<serde_json::Value>>(&json).map_or(0, |w| w.len());
+                Check::ok(NAME, format!("inside termstack, compositor answers on {socket} ({cells} cells)"))
+            }
+            Err(e) => Check::fail(
+                NAME,
+                format!("TERMSTACK_SOCKET={socket} but the compositor does not answer: {e:#}"),
+                "the compositor may have crashed; see `termstack debug-bundle` and restart it with `termstack`",
+            ),
+        };
+    }
+
+    let socket = compositor::ipc::socket_path();
+    if !socket.exists() {
+        return Check::ok(NAME, "not inside termstack and no session running (start one with `termstack`)");
+    }
+    match std::os::unix::net::UnixStream::connect(&socket) {
+        Ok(_) => Check::ok(
+            NAME,
+            format!("a session is running ({}), but this shell is not inside it", socket.display()),
+        ),
+        Err(e) => Check::warn(
+            NAME,
+            format!("stale socket {} ({e})", socket.display()),
+            format!("no compositor is listening; remove it with `rm {}`", socket.display()),
+        ),
+    }
+}
+
+/// Library directories searched for libEGL
+const LIB_DIRS: &[&str] = &[
+    "/usr/lib",
+    "/usr/lib64",
+    "/usr/lib/x86_64-linux-gnu",
+    "/usr/lib/aarch64-linux-gnu",
+    "/lib/x86_64-linux-gnu",
+    "/lib/aarch64-linux-gnu",
+];
+
+/// A DRM render node to draw with, and EGL to talk to it
+fn check_gpu() -> Check {
+    const NAME: &str = "GPU/EGL";
+    if cfg!(target_os = "macos") {
+        return Check::ok(NAME, "macOS uses a software renderer");
+    }
+    if env::var("TERMSTACK_BACKEND").is_ok_and(|b| b == "headless") {
+        return Check::ok(NAME, "headless backend, no GPU needed");
+    }
+
+    let mut nodes: Vec<PathBuf> = std::fs::read_dir("/dev/dri")
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("renderD")))
+                .collect()
+        })
+        .unwrap_or_default();
+    nodes.sort();
+    let Some(node) = nodes.first() else {
+        return Check::fail(
+            NAME,
+            "no GPU render node in /dev/dri",
+            "install and load a GPU driver (Mesa for Intel/AMD, the vendor driver for NVIDIA)",
+        );
+    };
+    if let Err(e) = std::fs::OpenOptions::new().read(true).write(true).open(node) {
+        return Check::fail(
+            NAME,
+            format!("cannot open {} ({e})", node.display()),
+            "add yourself to the render group: sudo usermod -aG render $USER (then log in again)",
+        );
+    }
+
+    let in_lib_dirs = LIB_DIRS.iter().any(|dir| Path::new(dir).join("libEGL.so.1").exists());
+    let in_ld_cache = || {
+        Command::new("ldconfig")
+            .arg("-p")
+            .output()
+            .is_ok_and(|out| String::from_utf8_lossy(&out.stdout).contains("libEGL.so.1"))
+    };
+    if !in_lib_dirs && !in_ld_cache() {
+        return Check::fail(
+            NAME,
+            format!("{} is usable, but libEGL.so.1 is not installed", node.display()),
+            "Debian/Ubuntu: sudo apt install libegl1\nFedora: sudo dnf install mesa-libEGL\nArch: sudo pacman -S mesa",
+        );
+    }
+    Check::ok(NAME, format!("{} with libEGL", node.display()))
+}
+
+/// `program` in the directories of a PATH-style list
+fn find_in_path(program: &str, path_var: &str) -> Option<PathBuf> {
+    env::split_paths(path_var).map(|dir| dir.join(program)).find(|path| path.is_file())
+}
+
+/// xwayland-satellite, which X11 apps need
+fn check_xwayland_satellite() -> Check {
+    const NAME: &str = "xwayland-satellite";
+    if cfg!(target_os = "macos") {
+        return Check::ok(NAME, "not used on macOS");
+    }
+    // Inside a session the compositor knows best whether it is running
+    if env::var_os("TERMSTACK_SOCKET").is_some() {
+        if let Some(check) = crate::cli::fetch_xwayland_status().ok().and_then(|status| xwayland_status_check(&status)) {
+            return check;
+        }
+    }
+    let Some(path) = find_in_path("xwayland-satellite", &env::var("PATH").unwrap_or_default()) else {
+        return Check::warn(
+            NAME,
+            "not found in PATH, X11 apps will not start",
+            "cargo install xwayland-satellite (or your distribution's package)",
+        );
+    };
+    let version = Command::new(&path)
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .and_then(|out| String::from_utf8_lossy(&out.stdout).lines().next().map(str::to_string))
+        .unwrap_or_else(|| "version unknown".to_string());
+    Check::ok(NAME, format!("{} ({})", path.display(), version.trim()))
+}
+
+/// How X11 support is doing in the running session (`None`: it never came
+/// up, which the PATH check explains better)
+fn xwayland_status_check(status: &XWaylandStatus) -> Option<Check> {
+    const NAME: &str = "xwayland-satellite";
+    let last_exit = status.last_exit.as_deref().unwrap_or("unknown exit");
+    Some(match status.state {
+        XWaylandState::Ready => {
+            let display = status.display.as_deref().unwrap_or("?");
+            let restarts = match status.restarts {
+                0 => String::new(),
+                1 => ", restarted once".to_string(),
+                n => format!(", restarted {n} times"),
+            };
+            Check::ok(NAME, format!("running, DISPLAY={display}{restarts}"))
+        }
+        XWaylandState::Starting => Check::warn(
+            NAME,
+            "still starting",
+            "X11 apps launched meanwhile wait for it; run `termstack doctor` again in a moment",
+        ),
+        XWaylandState::Backoff => Check::warn(
+            NAME,
+            format!(
+                "crashed ({last_exit}), restarting in {:.1}s",
+                status.retry_in_ms.unwrap_or(0) as f64 / 1000.0
+            ),
+            "if it keeps crashing, see `termstack debug-bundle`",
+        ),
+        XWaylandState::Crashed => Check::warn(
+            NAME,
+            format!("kept crashing ({last_exit}), X11 apps will not start this session"),
+            "restart termstack; if it keeps crashing, update xwayland-satellite",
+        ),
+        XWaylandState::Unavailable => return None,
+    })
+}
+
+/// A monospace font for terminal text
+fn check_font() -> Check {
+    const NAME: &str = "font";
+    match terminal::render::MONOSPACE_FONT_PATHS.iter().find(|path| Path::new(path).exists()) {
+        Some(path) => Check::ok(NAME, *path),
+        None => Check::fail(
+            NAME,
+            "no supported monospace font found, terminal text will not render",
+            "Debian/Ubuntu: sudo apt install fonts-dejavu\n\
+             Fedora: sudo dnf install dejavu-sans-mono-fonts\n\
+             Arch: sudo pacman -S ttf-dejavu",
+        ),
+    }
+}
+
+/// The fish integration, which runs commands in their own cells
+///
+/// termstack injects it into every fish shell it starts, so all that can go
+/// wrong is the shell not being fish.
+fn check_shell_integration() -> Check {
+    let shell = env::var("SHELL").unwrap_or_default();
+    shell_integration_check(&shell, find_in_path("fish", &env::var("PATH").unwrap_or_default()))
+}
+
+fn shell_integration_check(shell: &str, fish: Option<PathBuf>) -> Check {
+    const NAME: &str = "shell integration";
+    if Path::new(shell).file_name().and_then(|n| n.to_str()) == Some("fish") {
+        return Check::ok(NAME, format!("{shell}, integration is loaded automatically"));
+    }
+    let detail = format!(
+        "shell is {}, integration is only available for fish; commands run inside the shell's cell",
+        if shell.is_empty() { "not set" } else { shell }
+    );
+    match fish {
+        Some(fish) => Check::warn(
+            NAME,
+            detail,
+            format!(
+                "start termstack with SHELL={0}, or make fish your login shell: chsh -s {0}",
+                fish.display()
+            ),
+        ),
+        None => Check::warn(NAME, detail, "install fish and make it your login shell"),
+    }
+}
+
+/// The config file, as the compositor would load it
+fn check_config() -> Check {
+    const NAME: &str = "config";
+    let Some(path) = compositor::config::Config::config_paths().into_iter().flatten().find(|p| p.exists()) else {
+        return Check::ok(NAME, "no config file, using defaults");
+    };
+    config_file_check(&path)
+}
+
+fn config_file_check(path: &Path) -> Check {
+    const NAME: &str = "config";
+    match compositor::config::Config::from_file(path) {
+        Ok(_) => Check::ok(NAME, path.display().to_string()),
+        Err(e @ compositor::config::ConfigFileError::Invalid(_)) => Check::fail(
+            NAME,
+            format!("{}: {e}", path.display()),
+            "the compositor ignores the whole file while a value is out of range; fix or remove it",
+        ),
+        Err(e) => Check::fail(
+            NAME,
+            format!("{}: {e}", path.display()),
+            "the compositor skips this file; fix the syntax or remove the file",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixes_are_printed_under_the_check() {
+        let check = Check::fail("font", "none found", "install one\nor another");
+        assert_eq!(
+            check.to_string(),
+            "[FAIL] font: none found\n       fix: install one\n       fix: or another"
+        );
+        assert_eq!(Check::ok("config", "defaults").to_string(), "[ok  ] config: defaults");
+    }
+
+    #[test]
+    fn only_fish_gets_the_integration() {
+        let fish = Some(PathBuf::from("/usr/bin/fish"));
+        assert_eq!(shell_integration_check("/usr/bin/fish", None).outcome, Outcome::Ok);
+
+        let check = shell_integration_check("/bin/bash", fish);
+        assert_eq!(check.outcome, Outcome::Warn);
+        assert!(check.fix.unwrap().contains("SHELL=/usr/bin/fish"));
+
+        let check = shell_integration_check("", None);
+        assert!(check.detail.contains("not set"));
+        assert_eq!(check.fix.as_deref(), Some("install fish and make it your login shell"));
+    }
+
+    #[test]
+    fn programs_are_found_in_path() {
+        let dir = env::temp_dir().join(format!("termstack-doctor-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("fake-tool"), "").unwrap();
+        let path_var = format!("/nonexistent:{}", dir.display());
+        assert_eq!(find_in_path("fake-tool", &path_var), Some(dir.join("fake-tool")));
+        assert_eq!(find_in_path("missing-tool", &path_var), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn xwayland_state_comes_from_the_session() {
+        let mut status = XWaylandStatus {
+            state: XWaylandState::Ready,
+            display: Some(":1".to_string()),
+            restarts: 2,
+            last_exit: Some("signal: 11 (SIGSEGV)".to_string()),
+            retry_in_ms: None,
+            detail: None,
+        };
+        let check = xwayland_status_check(&status).unwrap();
+        assert_eq!(check.to_string(), "[ok  ] xwayland-satellite: running, DISPLAY=:1, restarted 2 times");
+
+        status.state = XWaylandState::Backoff;
+        status.retry_in_ms = Some(2000);
+        let check = xwayland_status_check(&status).unwrap();
+        assert_eq!(check.outcome, Outcome::Warn);
+        assert!(check.detail.contains("SIGSEGV"), "{}", check.detail);
+
+        status.state = XWaylandState::Unavailable;
+        assert!(xwayland_status_check(&status).is_none());
+    }
+
+    #[test]
+    fn bad_config_files_fail() {
+        let path = env::temp_dir().join(format!("termstack-doctor-test-{}.toml", std::process::id()));
+        std::fs::write(&path, "font_size = 14.0\n").unwrap();
+        assert_eq!(config_file_check(&path).outcome, Outcome::Ok);
+        std::fs::write(&path, "font_size = 200.0\n").unwrap();
+        assert_eq!(config_file_check(&path).outcome, Outcome::Fail);
+        std::fs::write(&path, "font_size = \n").unwrap();
+        assert_eq!(config_file_check(&path).outcome, Outcome::Fail);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -16,7 +16,23 @@
 //!
 //! ```bash
 //! termstack -c "git status"  # Spawn command in new terminal
+//! termstack --shell zsh --env FOO=1 --cwd /tmp -c make  # ...with per-cell overrides
 //! termstack gui pqiv img.png # Launch GUI app
+//! termstack tpl deploy prod  # Spawn the `deploy` command template from the config
+//! termstack export 2 -o build.html  # Save cell 2 as HTML with its colors
+//! termstack replay --speed 4  # Replay the last recorded session, 4x faster
+//! termstack dnd on --focus   # Do-not-disturb, hiding all but the focused cell
+//! termstack a11y --watch    # Announce focus and cell changes for screen readers
+//! termstack title 2 prod logs # Name cell 2 (`termstack title 2` clears it)
+//! termstack tag 2 red         # Mark cell 2 with a red stripe (`termstack ls --tag red`)
+//! termstack filter cargo      # Show only cells running cargo (`--clear` shows all)
+//! termstack layout export dev.toml # Save commands, order, columns and heights (`import` adds them, after asking)
+//! termstack hit-test 640 400 # What a click there reaches, and what was drawn there
+//! termstack back             # Focus the previously focused cell (like `cd -`)
+//! termstack all close-exited # Close every finished command's cell (undo: `all undo`)
+//! termstack archive restore 3 # Bring back closed cell 3 from the archive, read-only
+//! termstack quit             # Shut termstack down (asks first; --yes doesn't)
+//! termstack ls --json        # Print the layout (cells, heights, scroll, focus) as JSON
 //! termstack --resize full    # Resize focused terminal
 //! ```
 //!
@@ -26,13 +42,26 @@
 //! termstack uninstall  # Remove desktop integration files
 //! ```
 //!
+//! ## Bug Reports
+//! ```bash
+//! termstack debug-bundle  # Archive logs, config and version info
+//! termstack doctor        # Check GPU, fonts, config and shell integration
+//! ```
+//!
+//! ## Setup
+//! ```bash
+//! termstack init-config   # Write the default config file
+//! ```
+//!
 //! The mode is detected automatically based on environment context,
 //! providing a seamless user experience with a single binary.
 
 use std::env;
 
+mod bundle;
 mod cli;
 mod desktop;
+mod doctor;
 mod util;
 
 #[cfg(test)]
@@ -41,18 +70,22 @@ mod cli_test;
 fn main() -> anyhow::Result<()> {
     let args: Vec<String> = env::args().collect();
 
-    // Handle install/uninstall commands first (work in any context)
+    // Handle install/uninstall/debug-bundle/doctor commands first (work in any context)
     if args.len() >= 2 {
         match args[1].as_str() {
             "install" => return desktop::install(),
             "uninstall" => return desktop::uninstall(),
+            "debug-bundle" => return bundle::create(&args[2..]),
+            "doctor" | "--status" => return doctor::run(),
+            "init-config" => return cli::init_config(&args[2..]),
+            "play" => return cli::play_recording(&args[2..]),
             _ => {}
         }
     }
 
     // Check for CLI-specific subcommands (gui, --resize, etc.)
     // These require TERMSTACK_SOCKET and should error immediately if missing
-    let is_cli_command = args.len() >= 2 && matches!(args[1].as_str(), "gui" | "--resize");
+    let is_cli_command = args.len() >= 2 && matches!(args[1].as_str(), "gui" | "tpl" | "export" | "replay" | "dnd" | "a11y" | "title" | "tag" | "filter" | "layout" | "hit-test" | "back" | "all" | "archive" | "quit" | "ls" | "--resize");
 
     // Smart mode detection based on TERMSTACK_SOCKET environment variable
     if env::var("TERMSTACK_SOCKET").is_ok() {
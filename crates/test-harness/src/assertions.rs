@@ -1,7 +1,22 @@
 //! Test assertions for compositor state
 
+use compositor::layout_dump::LayoutDump;
+
 use crate::headless::{CompositorSnapshot, TestCompositor};
 
+/// Assert that a layout snapshot matches its golden text form
+/// (see `LayoutDump`'s `Display`; surrounding whitespace is ignored)
+pub fn assert_layout_matches(dump: &LayoutDump, golden: &str) {
+    let actual = dump.to_string();
+    assert_eq!(
+        actual.trim(),
+        golden.trim(),
+        "layout differs from golden state\n--- actual ---\n{}\n--- golden ---\n{}",
+        actual.trim(),
+        golden.trim()
+    );
+}
+
 /// Assert that windows don't overlap
 pub fn assert_windows_dont_overlap(snapshot: &CompositorSnapshot) {
     // With column layout, windows are stacked vertically
@@ -5,6 +5,11 @@
 //! - Test real terminals with real shell commands
 //! - Use the actual HeadlessBackend for event injection
 //!
+//! `HeadlessCompositor` goes one step further: it runs the termstack binary on
+//! the headless backend and lets real Wayland clients (the bundled
+//! test-wayland-client, weston-terminal, GTK demos) connect to it, so the
+//! xdg-shell handlers are exercised by actual protocol traffic.
+//!
 //! # Example
 //!
 //! ```ignore
@@ -22,6 +27,9 @@
 #[cfg(feature = "headless-backend")]
 pub use headless_harness::*;
 
+#[cfg(feature = "headless-backend")]
+pub use headless_process::*;
+
 #[cfg(feature = "headless-backend")]
 mod headless_harness {
     use compositor::backend::headless::{HeadlessBackend, HeadlessEvent, HeadlessInputBackend};
@@ -310,3 +318,351 @@ mod headless_harness {
         }
     }
 }
+
+#[cfg(feature = "headless-backend")]
+mod headless_process {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::fs::DirBuilderExt;
+    use std::os::unix::net::UnixStream;
+    use std::path::{Path, PathBuf};
+    use std::process::{Child, Command, ExitStatus, Stdio};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{mpsc, OnceLock};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use anyhow::{anyhow, bail, Context};
+    use compositor::hit_test::HitTest;
+    use compositor::layout_dump::{CellKind, LayoutDump};
+
+    use crate::live::find_workspace_root;
+
+    /// Output size of the headless backend
+    pub const HEADLESS_OUTPUT_SIZE: (i32, i32) = (1280, 800);
+
+    /// How long the compositor gets to open its sockets
+    const STARTUP_TIMEOUT: Duration = Duration::from_secs(20);
+
+    /// Directory holding the `termstack` and `test-wayland-client` binaries,
+    /// built once per test binary
+    fn binary_dir() -> anyhow::Result<&'static Path> {
+        static DIR: OnceLock<Result<PathBuf, String>> = OnceLock::new();
+        let dir = DIR.get_or_init(|| {
+            let workspace_root = find_workspace_root();
+            let status = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()))
+                .args([
+                    "build",
+                    "-p",
+                    "termstack",
+                    "--features",
+                    "termstack/headless-backend",
+                    "-p",
+                    "test-wayland-client",
+                ])
+                .current_dir(&workspace_root)
+                .status()
+                .map_err(|e| format!("failed to run cargo build: {e}"))?;
+            if !status.success() {
+                return Err("building termstack and test-wayland-client failed".into());
+            }
+            let target_dir = std::env::var_os("CARGO_TARGET_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| workspace_root.join("target"));
+            Ok(target_dir.join("debug"))
+        });
+        dir.as_deref().map_err(|e| anyhow!("{e}"))
+    }
+
+    /// Path of the bundled test client
+    pub fn test_client_path() -> anyhow::Result<PathBuf> {
+        Ok(binary_dir()?.join("test-wayland-client"))
+    }
+
+    /// Whether `program` is on PATH (tests skip clients that aren't installed)
+    pub fn program_available(program: &str) -> bool {
+        Command::new("which")
+            .arg(program)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false)
+    }
+
+    /// A termstack process on the headless backend
+    ///
+    /// Each instance gets its own runtime, config and state directories, so
+    /// tests can run in parallel and never see the user's config, sockets or
+    /// crash snapshots. The process is killed and the directories removed on
+    /// drop.
+    pub struct HeadlessCompositor {
+        child: Child,
+        root: PathBuf,
+        runtime_dir: PathBuf,
+        wayland_display: String,
+    }
+
+    impl HeadlessCompositor {
+        /// Start termstack and wait until its Wayland and IPC sockets accept connections
+        pub fn start() -> anyhow::Result<Self> {
+            static NEXT: AtomicU32 = AtomicU32::new(0);
+
+            let binary = binary_dir()?.join("termstack");
+            let root = std::env::temp_dir().join(format!(
+                "termstack-e2e-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            let runtime_dir = root.join("runtime");
+            std::fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(&runtime_dir)
+                .with_context(|| format!("failed to create {}", runtime_dir.display()))?;
+            // An empty config, so the first-run welcome cell stays out of the layout
+            let config_dir = root.join("config/termstack");
+            std::fs::create_dir_all(&config_dir)
+                .and_then(|()| std::fs::write(config_dir.join("config.toml"), ""))
+                .context("failed to write config")?;
+            // A file rather than a pipe, so a chatty compositor never blocks on it
+            let log = std::fs::File::create(root.join("termstack.log")).context("failed to create log file")?;
+
+            let child = Command::new(binary)
+                .env("TERMSTACK_BACKEND", "headless")
+                .env("XDG_RUNTIME_DIR", &runtime_dir)
+                .env("XDG_CONFIG_HOME", root.join("config"))
+                .env("XDG_STATE_HOME", root.join("state"))
+                .env("XDG_DATA_HOME", root.join("data"))
+                .env("HOME", &root)
+                .env("SHELL", "/bin/sh")
+                .env("RUST_LOG", "compositor=info")
+                .env_remove("WAYLAND_DISPLAY")
+                .env_remove("DISPLAY")
+                .env_remove("TERMSTACK_IPC_SOCKET")
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(log)
+                .spawn()
+                .context("failed to start termstack")?;
+
+            let mut compositor = Self { child, root, runtime_dir, wayland_display: String::new() };
+            compositor.wait_for_sockets()?;
+            Ok(compositor)
+        }
+
+        fn wait_for_sockets(&mut self) -> anyhow::Result<()> {
+            let deadline = Instant::now() + STARTUP_TIMEOUT;
+            while Instant::now() < deadline {
+                if !self.is_running() {
+                    bail!("termstack exited during startup:\n{}", self.log());
+                }
+                let display = self.find_wayland_socket();
+                if let Some(display) = display {
+                    if UnixStream::connect(self.ipc_socket()).is_ok() {
+                        self.wayland_display = display;
+                        return Ok(());
+                    }
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+            bail!("termstack sockets not ready after {STARTUP_TIMEOUT:?}:\n{}", self.log())
+        }
+
+        /// The `wayland-N` socket in the private runtime directory
+        fn find_wayland_socket(&self) -> Option<String> {
+            std::fs::read_dir(&self.runtime_dir)
+                .ok()?
+                .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                .find(|name| name.starts_with("wayland-") && !name.ends_with(".lock"))
+        }
+
+        /// What the compositor logged so far
+        pub fn log(&self) -> String {
+            std::fs::read_to_string(self.root.join("termstack.log")).unwrap_or_default()
+        }
+
+        pub fn wayland_display(&self) -> &str {
+            &self.wayland_display
+        }
+
+        pub fn ipc_socket(&self) -> PathBuf {
+            self.runtime_dir.join("termstack.sock")
+        }
+
+        /// Whether the compositor process is still alive
+        pub fn is_running(&mut self) -> bool {
+            matches!(self.child.try_wait(), Ok(None))
+        }
+
+        /// Current layout, fetched over IPC like `termstack ls --json`
+        pub fn layout(&self) -> anyhow::Result<LayoutDump> {
+            let stream = UnixStream::connect(self.ipc_socket()).context("failed to connect to IPC socket")?;
+            stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+            writeln!(&stream, r#"{{"type":"layout"}}"#)?;
+            let mut response = String::new();
+            BufReader::new(stream).read_line(&mut response).context("no layout response")?;
+            serde_json::from_str(&response).context("invalid layout response")
+        }
+
+        /// Launch `command` like `termstack gui` does from the focused terminal
+        ///
+        /// In the foreground, the focused terminal is hidden until the app is gone.
+        pub fn launch_gui(&self, command: &str, foreground: bool) -> anyhow::Result<()> {
+            let stream = UnixStream::connect(self.ipc_socket()).context("failed to connect to IPC socket")?;
+            let request = serde_json::json!({
+                "type": "spawn",
+                "prompt": "$ ",
+                "command": command,
+                "cwd": self.root,
+                "env": { "PATH": std::env::var("PATH").unwrap_or_default() },
+                "foreground": foreground,
+            });
+            writeln!(&stream, "{request}")?;
+            Ok(())
+        }
+
+        /// Run `command` in a new terminal cell, like `termstack -c` from the focused terminal
+        pub fn spawn_command(&self, command: &str) -> anyhow::Result<()> {
+            let stream = UnixStream::connect(self.ipc_socket()).context("failed to connect to IPC socket")?;
+            let request = serde_json::json!({
+                "type": "spawn",
+                "prompt": "$ ",
+                "command": command,
+                "cwd": self.root,
+                "env": { "PATH": std::env::var("PATH").unwrap_or_default() },
+            });
+            writeln!(&stream, "{request}")?;
+            Ok(())
+        }
+
+        /// What is under each screen point (Y=0 at top), like `termstack hit-test`
+        pub fn hit_test(&self, points: &[(f64, f64)]) -> anyhow::Result<Vec<HitTest>> {
+            let stream = UnixStream::connect(self.ipc_socket()).context("failed to connect to IPC socket")?;
+            stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+            let request = serde_json::json!({ "type": "hit_test", "points": points });
+            writeln!(&stream, "{request}")?;
+            let mut response = String::new();
+            BufReader::new(stream).read_line(&mut response).context("no hit_test response")?;
+            serde_json::from_str(&response).context("invalid hit_test response")
+        }
+
+        /// Poll the layout until `done` holds, returning the layout that satisfied it
+        pub fn wait_for_layout(
+            &self,
+            timeout: Duration,
+            done: impl Fn(&LayoutDump) -> bool,
+        ) -> anyhow::Result<LayoutDump> {
+            let deadline = Instant::now() + timeout;
+            loop {
+                let layout = self.layout()?;
+                if done(&layout) {
+                    return Ok(layout);
+                }
+                if Instant::now() >= deadline {
+                    bail!("layout condition not met after {timeout:?}, last layout:\n{layout}");
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+        }
+
+        /// Start a Wayland client connected to this compositor
+        ///
+        /// The client's stdout is captured line by line (see `WaylandClient::wait_for_line`).
+        pub fn spawn_client(&self, program: impl AsRef<std::ffi::OsStr>, args: &[&str]) -> anyhow::Result<WaylandClient> {
+            let mut child = Command::new(program)
+                .args(args)
+                .env("WAYLAND_DISPLAY", &self.wayland_display)
+                .env("XDG_RUNTIME_DIR", &self.runtime_dir)
+                .env("GDK_BACKEND", "wayland")
+                .env("QT_QPA_PLATFORM", "wayland")
+                .env_remove("DISPLAY")
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .context("failed to start Wayland client")?;
+
+            let (sender, lines) = mpsc::channel();
+            let stdout = child.stdout.take().expect("stdout is piped");
+            thread::spawn(move || {
+                for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+            });
+            Ok(WaylandClient { child, lines, seen: Vec::new() })
+        }
+    }
+
+    impl Drop for HeadlessCompositor {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    /// External cells in a layout
+    pub fn external_cells(layout: &LayoutDump) -> usize {
+        layout.cells.iter().filter(|cell| cell.kind == CellKind::External).count()
+    }
+
+    /// A client process started by `HeadlessCompositor::spawn_client`, killed on drop
+    pub struct WaylandClient {
+        child: Child,
+        lines: mpsc::Receiver<String>,
+        /// Every stdout line received so far
+        seen: Vec<String>,
+    }
+
+    impl WaylandClient {
+        /// Wait for a stdout line matching `matches` (earlier lines count too)
+        pub fn wait_for_line(&mut self, timeout: Duration, matches: impl Fn(&str) -> bool) -> Option<String> {
+            if let Some(line) = self.seen.iter().find(|line| matches(line)) {
+                return Some(line.clone());
+            }
+            let deadline = Instant::now() + timeout;
+            while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+                let line = self.lines.recv_timeout(left).ok()?;
+                self.seen.push(line.clone());
+                if matches(&line) {
+                    return Some(line);
+                }
+            }
+            None
+        }
+
+        /// Stdout lines received so far
+        pub fn lines(&mut self) -> &[String] {
+            self.seen.extend(self.lines.try_iter());
+            &self.seen
+        }
+
+        /// Wait for the client to exit by itself
+        pub fn wait_for_exit(&mut self, timeout: Duration) -> Option<ExitStatus> {
+            let deadline = Instant::now() + timeout;
+            while Instant::now() < deadline {
+                if let Ok(Some(status)) = self.child.try_wait() {
+                    return Some(status);
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+            None
+        }
+
+        /// Ask the client to quit with SIGTERM
+        pub fn terminate(&mut self) {
+            if let Some(pid) = rustix::process::Pid::from_raw(self.child.id() as i32) {
+                let _ = rustix::process::kill_process(pid, rustix::process::Signal::Term);
+            }
+        }
+    }
+
+    impl Drop for WaylandClient {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
@@ -1,5 +1,7 @@
 //! Test fixtures for common test scenarios
 
+use std::time::{Duration, Instant};
+
 use crate::headless::{TestCompositor, TerminalHandle};
 
 /// Standard test output dimensions
@@ -100,6 +102,142 @@ pub fn rapid_output_command(count: u32, delay_ms: u32) -> String {
     )
 }
 
+// ===== Stress scenarios =====
+
+/// Time one frame may take at 60 fps
+pub const FRAME_BUDGET: Duration = Duration::from_micros(16_667);
+
+/// One scripted step of a stress scenario
+#[derive(Debug, Clone, PartialEq)]
+pub enum StressStep {
+    /// A terminal prints `lines` lines of synthetic output
+    Output { index: usize, lines: u32 },
+    /// Scroll by a delta (positive moves down the stack)
+    Scroll(f64),
+    /// Click at a screen Y, focusing whatever is there
+    Focus(f64),
+    /// Resize a cell to `height` pixels
+    Resize { index: usize, height: u32 },
+}
+
+/// A stack of many terminals and the steps to run against it
+pub struct StressScenario {
+    pub compositor: TestCompositor,
+    pub terminals: Vec<TerminalHandle>,
+    pub steps: Vec<StressStep>,
+}
+
+/// Frame times measured by `StressScenario::run`
+#[derive(Debug, Clone)]
+pub struct StressReport {
+    /// One entry per step, in step order
+    pub frame_times: Vec<Duration>,
+}
+
+impl StressReport {
+    pub fn worst(&self) -> Duration {
+        self.frame_times.iter().copied().max().unwrap_or_default()
+    }
+
+    /// Frame time that `fraction` (0.0-1.0) of the frames stay within
+    pub fn percentile(&self, fraction: f64) -> Duration {
+        let mut sorted = self.frame_times.clone();
+        sorted.sort();
+        let rank = ((sorted.len() as f64 * fraction).ceil() as usize).clamp(1, sorted.len().max(1));
+        sorted.get(rank - 1).copied().unwrap_or_default()
+    }
+}
+
+/// Create a stack of `cells` terminals and `steps` scripted output, scroll,
+/// focus and resize steps, all derived from `seed`
+///
+/// The same seed always gives the same scenario, so a slow run can be replayed.
+pub fn stress(cells: usize, steps: usize, seed: u64) -> StressScenario {
+    let mut compositor = TestCompositor::new_headless(TEST_WIDTH, TEST_HEIGHT);
+    let terminals: Vec<_> = (0..cells).map(|_| compositor.spawn_terminal()).collect();
+
+    let mut rng = XorShift::new(seed);
+    let steps = (0..steps)
+        .map(|_| match rng.below(4) {
+            0 => StressStep::Output {
+                index: rng.below(cells as u64) as usize,
+                lines: 1 + rng.below(40) as u32,
+            },
+            1 => StressStep::Scroll(rng.below(4000) as f64 - 2000.0),
+            2 => StressStep::Focus(rng.below(TEST_HEIGHT as u64) as f64),
+            _ => StressStep::Resize {
+                index: rng.below(cells as u64) as usize,
+                height: 50 + rng.below(800) as u32,
+            },
+        })
+        .collect();
+
+    StressScenario { compositor, terminals, steps }
+}
+
+/// Synthetic terminal output: `lines` numbered lines of varying length
+pub fn synthetic_output(index: usize, lines: u32) -> String {
+    (0..lines)
+        .map(|line| format!("cell {index} line {line} {}\n", "#".repeat((line as usize * 7) % 120)))
+        .collect()
+}
+
+impl StressScenario {
+    /// Apply every step, building a frame after each and timing the build
+    pub fn run(&mut self) -> StressReport {
+        let steps = std::mem::take(&mut self.steps);
+        let frame_times = steps
+            .iter()
+            .map(|step| {
+                self.apply(step);
+                let start = Instant::now();
+                build_frame(&self.compositor);
+                start.elapsed()
+            })
+            .collect();
+        self.steps = steps;
+        StressReport { frame_times }
+    }
+
+    fn apply(&mut self, step: &StressStep) {
+        match *step {
+            StressStep::Output { index, lines } => {
+                let terminal = self.terminals[index];
+                self.compositor.send_input(&terminal, &synthetic_output(index, lines));
+            }
+            StressStep::Scroll(delta) => self.compositor.simulate_scroll(delta),
+            StressStep::Focus(screen_y) => self.compositor.simulate_click(100.0, screen_y),
+            StressStep::Resize { index, height } => self.compositor.set_window_height(index, height),
+        }
+    }
+}
+
+/// What the renderer computes per frame: every cell's position, every
+/// element's position, and the layout snapshot the frame-state log prints
+fn build_frame(compositor: &TestCompositor) {
+    std::hint::black_box(compositor.render_positions());
+    std::hint::black_box(compositor.rendered_elements());
+    std::hint::black_box(compositor.dump_layout());
+}
+
+/// Small deterministic PRNG, so scenarios don't depend on proptest's runner
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        // Zero is a fixed point of xorshift
+        Self(seed.max(1))
+    }
+
+    /// Uniform-ish value in `0..bound`
+    fn below(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % bound.max(1)
+    }
+}
+
 trait PadToWidth {
     fn pad_to_width(&self, width: usize) -> String;
 }
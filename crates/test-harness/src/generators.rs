@@ -0,0 +1,52 @@
+//! proptest strategies for random stacks
+//!
+//! `stack()` picks an output size, cell heights and a scroll offset;
+//! `StackSpec::build` turns that into a `TestCompositor`, so property tests
+//! can check coordinate invariants over stacks no one thought to write down.
+
+use proptest::prelude::*;
+
+use crate::headless::TestCompositor;
+
+/// A random stack, before it is built
+#[derive(Debug, Clone)]
+pub struct StackSpec {
+    /// Output (width, height)
+    pub output_size: (u32, u32),
+    /// Cell heights, top to bottom
+    pub heights: Vec<u32>,
+    /// Requested scroll offset, possibly out of range (`build` clamps it)
+    pub scroll: f64,
+}
+
+impl StackSpec {
+    /// Test compositor with one window per height, scrolled as requested
+    pub fn build(&self) -> TestCompositor {
+        let mut tc = TestCompositor::new_headless(self.output_size.0, self.output_size.1);
+        for &height in &self.heights {
+            tc.add_external_window(height);
+        }
+        tc.set_scroll(self.scroll);
+        tc
+    }
+}
+
+/// Output sizes from small laptop panels to 4K
+pub fn output_size() -> impl Strategy<Value = (u32, u32)> {
+    (320u32..=3840, 240u32..=2160)
+}
+
+/// Up to a dozen cells, from one pixel to taller than most screens
+pub fn heights() -> impl Strategy<Value = Vec<u32>> {
+    prop::collection::vec(1u32..2500, 0..12)
+}
+
+/// Random stacks; scroll offsets are whole pixels, as `render_positions()`
+/// works in integer pixels
+pub fn stack() -> impl Strategy<Value = StackSpec> {
+    (output_size(), heights(), -1000i32..30_000).prop_map(|(output_size, heights, scroll)| StackSpec {
+        output_size,
+        heights,
+        scroll: scroll as f64,
+    })
+}
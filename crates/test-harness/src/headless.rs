@@ -3,6 +3,7 @@
 use std::time::{Duration, Instant};
 
 use compositor::coords::{content_to_render_y, RenderPoint, ScreenPoint};
+use compositor::layout_dump::{CellKind, CellLayout, LayoutDump};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -68,6 +69,8 @@ pub struct TestCompositor {
 }
 
 struct MockWindow {
+    /// Whether this stands for an external window rather than a terminal
+    external: bool,
     /// The "cached" height - what bbox() would return
     /// This is used by click detection in the real compositor
     cached_height: u32,
@@ -230,6 +233,7 @@ impl TestCompositor {
         let index = self.windows.len();
 
         self.windows.push(MockWindow {
+            external: false,
             cached_height: 200,
             content: String::new(),
             elements: vec![(0, 200)], // Single element spanning the window
@@ -247,6 +251,7 @@ impl TestCompositor {
         let index = self.windows.len();
 
         self.windows.push(MockWindow {
+            external: true,
             cached_height: height,
             content: String::new(),
             elements: vec![(0, height as i32)], // Single element spanning the window
@@ -268,6 +273,7 @@ impl TestCompositor {
         let index = self.windows.len();
 
         self.windows.push(MockWindow {
+            external: true,
             cached_height,
             content: String::new(),
             elements: vec![(0, actual_height as i32)], // Actual rendered height differs!
@@ -286,6 +292,7 @@ impl TestCompositor {
         let index = self.windows.len();
 
         self.windows.push(MockWindow {
+            external: true,
             cached_height: total_height,
             content: String::new(),
             elements,
@@ -452,6 +459,37 @@ impl TestCompositor {
         }
     }
 
+    /// Layout snapshot in the real compositor's format (for golden tests)
+    ///
+    /// Mock windows have no titles; they start below the terminals' total height.
+    pub fn dump_layout(&self) -> LayoutDump {
+        let mut y = self.terminal_total_height;
+        let cells = self
+            .windows
+            .iter()
+            .enumerate()
+            .map(|(i, w)| {
+                let cell = CellLayout {
+                    cell: i as u64,
+                    kind: if w.external { CellKind::External } else { CellKind::Terminal },
+                    title: String::new(),
+                    y,
+                    height: w.cached_height as i32,
+                    hidden: false,
+                    tag: None,
+                };
+                y += w.cached_height as i32;
+                cell
+            })
+            .collect();
+        LayoutDump {
+            cells,
+            scroll_offset: self.scroll_offset,
+            focused: self.focused_index,
+            output_height: self.output_size.1 as i32,
+        }
+    }
+
     /// Get terminal content
     pub fn get_terminal_content(&self, handle: &TerminalHandle) -> String {
         self.windows
@@ -8,10 +8,12 @@
 //! - `live`: Live compositor testing helpers (requires display, Linux only)
 //! - `assertions`: Common test assertions
 //! - `fixtures`: Test fixture helpers
+//! - `generators`: proptest strategies for random stacks
 //! - `e2e`: E2E test infrastructure using the real HeadlessBackend (Linux only)
 
 pub mod assertions;
 pub mod fixtures;
+pub mod generators;
 pub mod headless;
 
 #[cfg(target_os = "linux")]
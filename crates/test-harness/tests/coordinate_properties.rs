@@ -0,0 +1,111 @@
+//! Property-based tests for coordinate math over random stacks
+//!
+//! Click detection, rendering and scrolling each compute cell positions with
+//! the Y-flip; these tests check they agree for stacks from
+//! `test_harness::generators`.
+
+use compositor::coords::{content_to_render_y, render_to_content_y, RenderY, ScreenPoint, ScreenY};
+use proptest::prelude::*;
+use test_harness::generators::{self, StackSpec};
+
+/// Index of the rendered window covering render Y `y`
+fn rendered_window_at(positions: &[(i32, i32)], y: f64) -> Option<usize> {
+    positions
+        .iter()
+        .position(|&(render_y, height)| y >= render_y as f64 && y < (render_y + height) as f64)
+}
+
+proptest! {
+    /// Every point of the screen hits the window rendered there
+    #[test]
+    fn window_at_matches_render_positions(
+        spec in generators::stack(),
+        probes in prop::collection::vec(0f64..1.0, 1..20),
+    ) {
+        let tc = spec.build();
+        let positions = tc.render_positions();
+        let screen_height = spec.output_size.1 as f64;
+
+        for probe in probes {
+            let y = (probe * screen_height).floor();
+            prop_assert_eq!(tc.window_at(y), rendered_window_at(&positions, y), "render Y {}", y);
+        }
+    }
+
+    /// A window's bottom edge is inside it, its top edge belongs to the one above
+    #[test]
+    fn window_edges_hit_the_right_window(spec in generators::stack()) {
+        let tc = spec.build();
+
+        for (i, (render_y, height)) in tc.render_positions().into_iter().enumerate() {
+            prop_assert_eq!(tc.window_at(render_y as f64), Some(i));
+            let top = (render_y + height) as f64;
+            prop_assert_ne!(tc.window_at(top), Some(i), "window {} top edge at {}", i, top);
+        }
+    }
+
+    /// Once the content fills the screen there are no gaps to click into
+    #[test]
+    fn full_screen_has_no_gaps(spec in generators::stack(), probe in 0f64..1.0) {
+        let tc = spec.build();
+        let (_, screen_height) = tc.output_size();
+        prop_assume!(tc.total_content_height() >= screen_height as i32);
+
+        let y = (probe * screen_height as f64).floor();
+        prop_assert!(tc.window_at(y).is_some(), "render Y {} hits nothing", y);
+    }
+
+    /// Scrolling never leaves the range 0..=max_scroll, whatever the output size
+    #[test]
+    fn scroll_stays_within_max_scroll(
+        spec in generators::stack(),
+        deltas in prop::collection::vec(-5000f64..5000.0, 1..10),
+    ) {
+        let mut tc = spec.build();
+        let max_scroll = (tc.total_content_height() - spec.output_size.1 as i32).max(0) as f64;
+
+        for delta in deltas {
+            tc.scroll(delta);
+            prop_assert!(tc.scroll_offset() >= 0.0);
+            prop_assert!(tc.scroll_offset() <= max_scroll, "{} > {}", tc.scroll_offset(), max_scroll);
+        }
+    }
+
+    /// Screen -> render -> screen gives the point back
+    #[test]
+    fn y_flip_round_trips(y in -5000f64..5000.0, x in -5000f64..5000.0, output_height in 1i32..4320) {
+        let back = ScreenY::new(y).to_render(output_height).to_screen(output_height);
+        prop_assert!((back.value() - y).abs() < 1e-9);
+
+        let render = RenderY::new(y).to_screen(output_height).to_render(output_height);
+        prop_assert!((render.value() - y).abs() < 1e-9);
+
+        let point = ScreenPoint::new(x, y).to_render(output_height).to_screen(output_height);
+        prop_assert_eq!(point.x, x);
+        prop_assert!((point.y.value() - y).abs() < 1e-9);
+    }
+
+    /// `render_to_content_y` undoes `content_to_render_y`, and an object's top
+    /// edge lands where the point flip puts it
+    #[test]
+    fn object_flip_round_trips(
+        content_y in -20_000f64..20_000.0,
+        height in 0f64..3000.0,
+        screen_height in 1i32..4320,
+    ) {
+        let render_y = content_to_render_y(content_y, height, screen_height as f64);
+        let back = render_to_content_y(render_y, height, screen_height as f64);
+        prop_assert!((back - content_y).abs() < 1e-9);
+
+        let top = ScreenY::new(content_y).to_render(screen_height);
+        prop_assert!((render_y + height - top.value()).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn empty_stack_hits_nothing() {
+    let spec = StackSpec { output_size: (1280, 720), heights: Vec::new(), scroll: 100.0 };
+    let tc = spec.build();
+    assert_eq!(tc.scroll_offset(), 0.0);
+    assert_eq!(tc.window_at(360.0), None);
+}
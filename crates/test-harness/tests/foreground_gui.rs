@@ -0,0 +1,86 @@
+//! Foreground GUI launches with apps that fail or crash
+//!
+//! A foreground `gui` launch hides the terminal it came from until the app is
+//! done. These tests launch apps over IPC on the headless backend
+//! (`HeadlessCompositor`) and check that the launcher comes back, focused,
+//! when the app dies before mapping a window or crashes while mapped.
+//!
+//! Run with:
+//! ```bash
+//! cargo test -p test-harness --features headless-backend --test foreground_gui
+//! ```
+
+#![cfg(feature = "headless-backend")]
+
+use std::time::Duration;
+
+use compositor::layout_dump::{CellKind, LayoutDump};
+use test_harness::e2e::{external_cells, test_client_path, HeadlessCompositor};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Start a compositor and return it with the cell id of its shell terminal
+fn start() -> (HeadlessCompositor, u64) {
+    let compositor = HeadlessCompositor::start().unwrap();
+    let layout = compositor
+        .wait_for_layout(TIMEOUT, |layout| layout.cells.iter().any(|cell| cell.kind == CellKind::Terminal))
+        .unwrap();
+    let launcher = layout.cells.iter().find(|cell| cell.kind == CellKind::Terminal).unwrap().cell;
+    (compositor, launcher)
+}
+
+fn launcher_hidden(layout: &LayoutDump, launcher: u64) -> bool {
+    layout.cells.iter().any(|cell| cell.cell == launcher && cell.hidden)
+}
+
+/// The launcher is visible again and has focus
+fn launcher_restored(layout: &LayoutDump, launcher: u64) -> bool {
+    let index = layout.cells.iter().position(|cell| cell.cell == launcher);
+    index.is_some_and(|index| !layout.cells[index].hidden && layout.focused == Some(index))
+}
+
+#[test]
+fn gui_that_dies_before_mapping_restores_launcher() {
+    let (mut compositor, launcher) = start();
+
+    // Prints an error (so its output cell stays) and never opens a window
+    compositor.launch_gui("echo cannot open display; exit 1", true).unwrap();
+    compositor
+        .wait_for_layout(TIMEOUT, |layout| layout.cells.len() >= 2)
+        .unwrap();
+    compositor
+        .wait_for_layout(TIMEOUT, |layout| launcher_restored(layout, launcher))
+        .unwrap();
+
+    assert!(compositor.is_running(), "compositor died:\n{}", compositor.log());
+}
+
+#[test]
+fn gui_that_crashes_after_mapping_restores_launcher() {
+    let (mut compositor, launcher) = start();
+
+    // Maps a window, then drops its connection without closing the toplevel
+    let client = test_client_path().unwrap();
+    compositor
+        .launch_gui(&format!("{} --frames 60", client.display()), true)
+        .unwrap();
+    compositor
+        .wait_for_layout(TIMEOUT, |layout| external_cells(layout) == 1 && launcher_hidden(layout, launcher))
+        .unwrap();
+    compositor
+        .wait_for_layout(TIMEOUT, |layout| external_cells(layout) == 0 && launcher_restored(layout, launcher))
+        .unwrap();
+
+    assert!(compositor.is_running(), "compositor died:\n{}", compositor.log());
+}
+
+#[test]
+fn background_gui_leaves_launcher_alone() {
+    let (compositor, launcher) = start();
+
+    compositor.launch_gui("exit 1", false).unwrap();
+    let layout = compositor
+        .wait_for_layout(TIMEOUT, |layout| layout.cells.len() >= 2)
+        .unwrap();
+    assert!(!launcher_hidden(&layout, launcher));
+}
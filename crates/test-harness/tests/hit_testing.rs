@@ -0,0 +1,155 @@
+//! Click-to-focus accuracy across the whole stack
+//!
+//! Clicks find their cell through the layout's cached heights while the
+//! renderer places cells by the heights of the frame; when the two drift
+//! apart, clicks land on the wrong cell. Each test builds a stack of
+//! command cells (and a Wayland client), sweeps a grid of points over the
+//! whole output with the `hit_test` IPC request and checks every answer
+//! against the cell drawn there and the layout's own positions.
+//!
+//! Run with:
+//! ```bash
+//! cargo test -p test-harness --features headless-backend --test hit_testing
+//! ```
+
+#![cfg(feature = "headless-backend")]
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use compositor::hit_test::{HitPart, HitTest};
+use compositor::layout_dump::LayoutDump;
+use test_harness::e2e::{external_cells, test_client_path, HeadlessCompositor, HEADLESS_OUTPUT_SIZE};
+
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Grid spacing of the sweep (pixels)
+const STEP_X: usize = 40;
+const STEP_Y: usize = 3;
+
+/// Spawn a command cell per line count, each printing that many lines and
+/// staying alive, and wait until the stack has them and `external` windows
+/// and stops changing
+fn build_stack(compositor: &HeadlessCompositor, line_counts: &[usize], external: usize) -> LayoutDump {
+    let initial = compositor.wait_for_layout(TIMEOUT, |layout| !layout.cells.is_empty()).unwrap();
+    for lines in line_counts {
+        compositor.spawn_command(&format!("seq 1 {lines}; sleep 60")).unwrap();
+    }
+    let cells = initial.cells.len() - external_cells(&initial) + line_counts.len() + external;
+    compositor
+        .wait_for_layout(TIMEOUT, |layout| layout.cells.len() == cells && external_cells(layout) == external)
+        .unwrap();
+    settled_layout(compositor)
+}
+
+/// The layout once two polls in a row agree (output and resizes done)
+fn settled_layout(compositor: &HeadlessCompositor) -> LayoutDump {
+    let mut previous = compositor.layout().unwrap();
+    for _ in 0..100 {
+        std::thread::sleep(Duration::from_millis(200));
+        let layout = compositor.layout().unwrap();
+        if layout == previous {
+            return layout;
+        }
+        previous = layout;
+    }
+    panic!("layout never settled:\n{previous}");
+}
+
+/// Hit-test a grid of points over the whole output, one request per row
+fn sweep(compositor: &HeadlessCompositor) -> Vec<HitTest> {
+    let (width, height) = HEADLESS_OUTPUT_SIZE;
+    (0..height as usize)
+        .step_by(STEP_Y)
+        .flat_map(|y| {
+            let points: Vec<(f64, f64)> =
+                (STEP_X / 2..width as usize).step_by(STEP_X).map(|x| (x as f64, y as f64 + 0.5)).collect();
+            compositor.hit_test(&points).unwrap()
+        })
+        .collect()
+}
+
+/// Index of the cell the layout puts at screen `y`, if `y` is at least a
+/// pixel away from the cell's edges
+fn layout_cell_at(layout: &LayoutDump, y: f64) -> Option<Option<usize>> {
+    let content_y = y + layout.scroll_offset;
+    for (index, cell) in layout.cells.iter().enumerate().filter(|(_, cell)| !cell.hidden && cell.height > 0) {
+        let (top, bottom) = (cell.y as f64, (cell.y + cell.height) as f64);
+        if (content_y - top).abs() < 1.0 || (content_y - bottom).abs() < 1.0 {
+            return None;
+        }
+        if content_y > top && content_y < bottom {
+            return Some(Some(index));
+        }
+    }
+    Some(None)
+}
+
+/// Check every hit against rendering and the layout, and that clicks within
+/// a cell move through its title bar and rows in screen order
+fn assert_hits_match(layout: &LayoutDump, hits: &[HitTest]) {
+    for hit in hits {
+        assert_eq!(hit.cell, hit.drawn, "click and frame disagree at ({}, {}): {hit:?}\n{layout}", hit.x, hit.y);
+        if let Some(expected) = layout_cell_at(layout, hit.y) {
+            assert_eq!(hit.cell, expected, "click at ({}, {}) misses the layout's cell: {hit:?}\n{layout}", hit.x, hit.y);
+        }
+        assert_eq!(hit.column, 0, "single-column stack reported column {}", hit.column);
+    }
+
+    // Going down a vertical line of points, a cell's title bar comes first
+    // and its rows never go back up
+    let mut last_row: HashMap<(usize, u64), Option<usize>> = HashMap::new();
+    for hit in hits {
+        let Some(cell) = hit.cell else { continue };
+        let key = (cell, hit.x as u64);
+        let row = match hit.part {
+            Some(HitPart::TitleBar) => None,
+            Some(HitPart::Terminal { row, .. }) => Some(row),
+            Some(HitPart::Window) | None => continue,
+        };
+        if let Some(previous) = last_row.insert(key, row) {
+            assert!(row >= previous, "cell {cell} at x={} goes from row {previous:?} back to {row:?}", hit.x);
+        }
+    }
+
+    // Every cell on screen is reachable
+    let output_h = layout.output_height as f64;
+    for (index, cell) in layout.cells.iter().enumerate() {
+        let top = cell.y as f64 - layout.scroll_offset;
+        let bottom = top + cell.height as f64;
+        if cell.hidden || bottom - top < STEP_Y as f64 * 2.0 || bottom <= 0.0 || top >= output_h {
+            continue;
+        }
+        assert!(hits.iter().any(|hit| hit.cell == Some(index)), "cell {index} is on screen but never hit\n{layout}");
+    }
+}
+
+#[test]
+fn clicks_land_on_the_drawn_cell_across_the_stack() {
+    let compositor = HeadlessCompositor::start().unwrap();
+    let layout = build_stack(&compositor, &[1, 4, 9, 2], 0);
+    assert_hits_match(&layout, &sweep(&compositor));
+}
+
+#[test]
+fn clicks_land_on_the_drawn_cell_when_the_stack_is_scrolled() {
+    let compositor = HeadlessCompositor::start().unwrap();
+    // Taller than the output, so the stack is scrolled to its bottom
+    let layout = build_stack(&compositor, &[30, 3, 45, 12, 25], 0);
+    assert!(layout.scroll_offset > 0.0, "stack should overflow the output:\n{layout}");
+    assert_hits_match(&layout, &sweep(&compositor));
+}
+
+#[test]
+fn clicks_land_on_the_drawn_cell_with_windows_in_the_stack() {
+    let compositor = HeadlessCompositor::start().unwrap();
+    let _client = compositor.spawn_client(test_client_path().unwrap(), &[]).unwrap();
+    let layout = build_stack(&compositor, &[6, 2], 1);
+
+    let hits = sweep(&compositor);
+    assert_hits_match(&layout, &hits);
+    assert!(
+        hits.iter().any(|hit| hit.part == Some(HitPart::Window)),
+        "no point reached the window's surface\n{layout}"
+    );
+}
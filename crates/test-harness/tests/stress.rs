@@ -0,0 +1,58 @@
+//! Stress scenarios: hundreds of cells under scripted output, scroll, focus
+//! and resize, checking that the stack stays consistent and (run with
+//! `--ignored` on a release build) that frame building stays within the
+//! frame budget
+
+use test_harness::assertions;
+use test_harness::fixtures::{self, StressStep, FRAME_BUDGET};
+
+#[test]
+fn hundreds_of_cells_stay_consistent() {
+    let mut scenario = fixtures::stress(300, 1000, 0x5eed);
+    let report = scenario.run();
+
+    assert_eq!(report.frame_times.len(), 1000);
+    assertions::assert_no_element_overlaps(&scenario.compositor);
+    assertions::assert_render_matches_click_detection(&scenario.compositor);
+    assert_eq!(scenario.compositor.snapshot().window_count, 300);
+}
+
+#[test]
+#[ignore] // Wall-clock timing; run with: cargo test --release -p test-harness --test stress -- --ignored
+fn hundreds_of_cells_build_frames_within_budget() {
+    let mut scenario = fixtures::stress(300, 1000, 0x5eed);
+    let report = scenario.run();
+
+    let p99 = report.percentile(0.99);
+    assert!(
+        p99 < FRAME_BUDGET,
+        "99th percentile frame took {p99:?} (worst {:?}), budget is {FRAME_BUDGET:?}",
+        report.worst()
+    );
+}
+
+#[test]
+fn scenarios_are_reproducible_from_the_seed() {
+    let a = fixtures::stress(50, 200, 7);
+    let b = fixtures::stress(50, 200, 7);
+    let c = fixtures::stress(50, 200, 8);
+    assert_eq!(a.steps, b.steps);
+    assert_ne!(a.steps, c.steps);
+}
+
+#[test]
+fn scenarios_mix_every_kind_of_step() {
+    let scenario = fixtures::stress(10, 200, 1);
+    let has = |kind: fn(&StressStep) -> bool| scenario.steps.iter().any(kind);
+    assert!(has(|s| matches!(s, StressStep::Output { .. })));
+    assert!(has(|s| matches!(s, StressStep::Scroll(_))));
+    assert!(has(|s| matches!(s, StressStep::Focus(_))));
+    assert!(has(|s| matches!(s, StressStep::Resize { .. })));
+}
+
+#[test]
+fn synthetic_output_has_the_requested_lines() {
+    let output = fixtures::synthetic_output(3, 5);
+    assert_eq!(output.lines().count(), 5);
+    assert!(output.starts_with("cell 3 line 0"));
+}
@@ -0,0 +1,151 @@
+//! Wayland protocol conformance tests with real clients
+//!
+//! Each test starts termstack on the headless backend (`HeadlessCompositor`)
+//! and connects actual Wayland clients to it, then checks that windows map as
+//! external cells, get configured to the column width, receive frame
+//! callbacks and go away cleanly when the client closes. Clients other than
+//! the bundled test-wayland-client are skipped when not installed.
+//!
+//! Run with:
+//! ```bash
+//! cargo test -p test-harness --features headless-backend --test wayland_conformance
+//! ```
+
+#![cfg(feature = "headless-backend")]
+
+use std::time::Duration;
+
+use compositor::layout_dump::CellKind;
+use test_harness::e2e::{
+    external_cells, program_available, test_client_path, HeadlessCompositor, HEADLESS_OUTPUT_SIZE,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Height of the test client's buffer
+const TEST_CLIENT_HEIGHT: i32 = 240;
+
+#[test]
+fn test_client_maps_at_column_width() {
+    let compositor = HeadlessCompositor::start().unwrap();
+    let mut client = compositor.spawn_client(test_client_path().unwrap(), &["--report"]).unwrap();
+
+    // Width is constrained to the column, height left to the client
+    let configure = client
+        .wait_for_line(TIMEOUT, |line| line.starts_with("configure"))
+        .expect("client never got a toplevel configure");
+    assert_eq!(configure, format!("configure {} 0", HEADLESS_OUTPUT_SIZE.0));
+
+    let layout = compositor
+        .wait_for_layout(TIMEOUT, |layout| external_cells(layout) == 1)
+        .unwrap();
+    let cell = layout.cells.iter().find(|cell| cell.kind == CellKind::External).unwrap();
+    assert!(cell.title.contains("test-wayland-client"), "unexpected title {:?}", cell.title);
+    assert!(
+        cell.height >= TEST_CLIENT_HEIGHT,
+        "cell height {} doesn't fit the {TEST_CLIENT_HEIGHT}px buffer",
+        cell.height
+    );
+}
+
+#[test]
+fn test_client_receives_frame_callbacks() {
+    let compositor = HeadlessCompositor::start().unwrap();
+    let mut client = compositor
+        .spawn_client(test_client_path().unwrap(), &["--report", "--frames", "5"])
+        .unwrap();
+
+    let status = client.wait_for_exit(TIMEOUT).expect("client never got 5 frame callbacks");
+    assert!(status.success());
+    let frames = client.lines().iter().filter(|line| *line == "frame").count();
+    assert_eq!(frames, 5);
+}
+
+#[test]
+fn closed_clients_leave_no_cells_behind() {
+    let mut compositor = HeadlessCompositor::start().unwrap();
+    let baseline = compositor.wait_for_layout(TIMEOUT, |layout| !layout.cells.is_empty()).unwrap();
+
+    // Map and close a few times in a row; each close must remove its cell
+    for _ in 0..3 {
+        let mut client = compositor
+            .spawn_client(test_client_path().unwrap(), &["--frames", "2"])
+            .unwrap();
+        assert!(client.wait_for_exit(TIMEOUT).expect("client hung").success());
+        compositor
+            .wait_for_layout(TIMEOUT, |layout| external_cells(layout) == 0)
+            .unwrap();
+    }
+
+    assert!(compositor.is_running(), "compositor died:\n{}", compositor.log());
+    let layout = compositor.layout().unwrap();
+    assert_eq!(layout.cells.len(), baseline.cells.len());
+}
+
+#[test]
+fn killed_client_is_unmapped() {
+    let mut compositor = HeadlessCompositor::start().unwrap();
+    let mut client = compositor.spawn_client(test_client_path().unwrap(), &["--report"]).unwrap();
+    client.wait_for_line(TIMEOUT, |line| line == "frame").expect("client never drew");
+
+    // No xdg_toplevel.destroy, just a dropped connection
+    drop(client);
+    compositor
+        .wait_for_layout(TIMEOUT, |layout| external_cells(layout) == 0)
+        .unwrap();
+    assert!(compositor.is_running(), "compositor died:\n{}", compositor.log());
+}
+
+#[test]
+fn concurrent_clients_each_get_their_height() {
+    let compositor = HeadlessCompositor::start().unwrap();
+    let _clients: Vec<_> = (0..3)
+        .map(|_| compositor.spawn_client(test_client_path().unwrap(), &[]).unwrap())
+        .collect();
+
+    let layout = compositor
+        .wait_for_layout(TIMEOUT, |layout| external_cells(layout) == 3)
+        .unwrap();
+    for cell in layout.cells.iter().filter(|cell| cell.kind == CellKind::External) {
+        assert!(cell.height >= TEST_CLIENT_HEIGHT, "window squeezed by its neighbours:\n{layout}");
+    }
+}
+
+/// Start `program`, wait for it to map, then SIGTERM it and wait for the cell to go
+fn maps_and_closes(program: &str, args: &[&str]) {
+    if !program_available(program) {
+        eprintln!("Skipping: {program} not installed");
+        return;
+    }
+    let mut compositor = HeadlessCompositor::start().unwrap();
+    let mut client = compositor.spawn_client(program, args).unwrap();
+
+    let layout = compositor
+        .wait_for_layout(TIMEOUT, |layout| external_cells(layout) >= 1)
+        .unwrap_or_else(|e| panic!("{program} never mapped: {e}\n{}", compositor.log()));
+    for cell in layout.cells.iter().filter(|cell| cell.kind == CellKind::External) {
+        assert!(cell.height > 0, "{program} mapped with zero height:\n{layout}");
+    }
+
+    client.terminate();
+    client.wait_for_exit(TIMEOUT).unwrap_or_else(|| panic!("{program} ignored SIGTERM"));
+    compositor
+        .wait_for_layout(TIMEOUT, |layout| external_cells(layout) == 0)
+        .unwrap();
+    assert!(compositor.is_running(), "compositor died:\n{}", compositor.log());
+}
+
+#[test]
+fn weston_terminal_maps_and_closes() {
+    maps_and_closes("weston-terminal", &[]);
+}
+
+#[test]
+fn gtk4_demo_maps_and_closes() {
+    maps_and_closes("gtk4-demo", &[]);
+}
+
+#[test]
+fn gtk3_demo_maps_and_closes() {
+    maps_and_closes("gtk3-demo", &[]);
+}
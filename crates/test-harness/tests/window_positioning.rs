@@ -2194,3 +2194,20 @@ fn all_windows_offscreen_after_scroll() {
     assert_eq!(positions[1].0, 500, "window 1 partially visible at top");
     assert_eq!(positions[2].0, 0, "window 2 at bottom of screen");
 }
+
+#[test]
+fn layout_after_scrolling_matches_golden_state() {
+    let mut tc = TestCompositor::new_headless(fixtures::TEST_WIDTH, fixtures::TEST_HEIGHT);
+
+    tc.spawn_terminal();
+    tc.add_external_window(400);
+    tc.add_external_window(300);
+    tc.scroll(100.0);
+
+    assertions::assert_layout_matches(&tc.dump_layout(), "
+scroll=100 focused=2 output_h=720
+[0] term y=0 h=200
+[1] ext y=200 h=400
+[2] ext y=600 h=300
+");
+}
@@ -5,6 +5,10 @@
 //!     cargo run -p test-wayland-client
 //!
 //! Press ESC to close.
+//!
+//! Conformance tests run it with `--report`, which prints each protocol
+//! event it gets to stdout, one per line (`configure <w> <h>`, `frame`,
+//! `close`), and `--frames <n>`, which makes it exit after `n` frame callbacks.
 
 use std::cmp::min;
 use std::fs::File;
@@ -12,7 +16,7 @@ use std::io::{BufWriter, Write};
 use std::os::unix::io::AsFd;
 
 use wayland_client::protocol::{
-    wl_buffer, wl_compositor, wl_keyboard, wl_registry, wl_seat, wl_shm, wl_shm_pool, wl_surface,
+    wl_buffer, wl_callback, wl_compositor, wl_keyboard, wl_registry, wl_seat, wl_shm, wl_shm_pool, wl_surface,
 };
 use wayland_client::{delegate_noop, Connection, Dispatch, QueueHandle, WEnum};
 
@@ -21,7 +25,33 @@ use wayland_protocols::xdg::shell::client::{xdg_surface, xdg_toplevel, xdg_wm_ba
 const WIDTH: u32 = 320;
 const HEIGHT: u32 = 240;
 
+/// Command line options
+#[derive(Default)]
+struct Options {
+    /// Print protocol events to stdout
+    report: bool,
+    /// Exit after this many frame callbacks
+    exit_after_frames: Option<u32>,
+}
+
+fn parse_args() -> Options {
+    let mut options = Options::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--report" => options.report = true,
+            "--frames" => {
+                let n = args.next().and_then(|n| n.parse().ok());
+                options.exit_after_frames = Some(n.expect("--frames needs a number"));
+            }
+            other => panic!("unknown argument: {other}"),
+        }
+    }
+    options
+}
+
 fn main() {
+    let options = parse_args();
     let conn = Connection::connect_to_env().expect(
         "failed to connect to Wayland display — \
          make sure WAYLAND_DISPLAY is set (run inside termstack)",
@@ -40,6 +70,8 @@ fn main() {
         wm_base: None,
         xdg_surface: None,
         configured: false,
+        frames: 0,
+        options,
     };
 
     eprintln!("test-wayland-client: {WIDTH}x{HEIGHT} gradient window, press ESC to quit");
@@ -47,6 +79,13 @@ fn main() {
     while state.running {
         event_queue.blocking_dispatch(&mut state).unwrap();
     }
+
+    // Unmap before disconnecting so the compositor sees a clean close
+    if let Some((xdg_surface, toplevel)) = state.xdg_surface.take() {
+        toplevel.destroy();
+        xdg_surface.destroy();
+    }
+    event_queue.roundtrip(&mut state).ok();
 }
 
 struct State {
@@ -56,6 +95,9 @@ struct State {
     wm_base: Option<xdg_wm_base::XdgWmBase>,
     xdg_surface: Option<(xdg_surface::XdgSurface, xdg_toplevel::XdgToplevel)>,
     configured: bool,
+    /// Frame callbacks received
+    frames: u32,
+    options: Options,
 }
 
 impl State {
@@ -70,6 +112,25 @@ impl State {
         base_surface.commit();
         self.xdg_surface = Some((xdg_surface, toplevel));
     }
+
+    /// Attach the buffer and commit, asking for a frame callback
+    fn present(&self, qh: &QueueHandle<State>) {
+        let surface = self.base_surface.as_ref().unwrap();
+        if let Some(ref buffer) = self.buffer {
+            surface.attach(Some(buffer), 0, 0);
+        }
+        surface.frame(qh, ());
+        surface.commit();
+    }
+
+    /// Print a protocol event in `--report` mode
+    fn report(&self, event: std::fmt::Arguments) {
+        if self.options.report {
+            let mut stdout = std::io::stdout().lock();
+            writeln!(stdout, "{event}").unwrap();
+            stdout.flush().unwrap();
+        }
+    }
 }
 
 /// Draw an ARGB8888 color gradient into the temp file.
@@ -129,9 +190,7 @@ impl Dispatch<wl_registry::WlRegistry, ()> for State {
                     state.buffer = Some(buffer.clone());
 
                     if state.configured {
-                        let surface = state.base_surface.as_ref().unwrap();
-                        surface.attach(Some(&buffer), 0, 0);
-                        surface.commit();
+                        state.present(qh);
                     }
                 }
                 "wl_seat" => {
@@ -174,16 +233,12 @@ impl Dispatch<xdg_surface::XdgSurface, ()> for State {
         event: xdg_surface::Event,
         _: &(),
         _: &Connection,
-        _: &QueueHandle<Self>,
+        qh: &QueueHandle<Self>,
     ) {
         if let xdg_surface::Event::Configure { serial, .. } = event {
             xdg_surface.ack_configure(serial);
             state.configured = true;
-            let surface = state.base_surface.as_ref().unwrap();
-            if let Some(ref buffer) = state.buffer {
-                surface.attach(Some(buffer), 0, 0);
-                surface.commit();
-            }
+            state.present(qh);
         }
     }
 }
@@ -197,8 +252,36 @@ impl Dispatch<xdg_toplevel::XdgToplevel, ()> for State {
         _: &Connection,
         _: &QueueHandle<Self>,
     ) {
-        if let xdg_toplevel::Event::Close = event {
-            state.running = false;
+        match event {
+            xdg_toplevel::Event::Configure { width, height, .. } => {
+                state.report(format_args!("configure {width} {height}"));
+            }
+            xdg_toplevel::Event::Close => {
+                state.report(format_args!("close"));
+                state.running = false;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<wl_callback::WlCallback, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &wl_callback::WlCallback,
+        event: wl_callback::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_callback::Event::Done { .. } = event {
+            state.frames += 1;
+            state.report(format_args!("frame"));
+            if state.options.exit_after_frames.is_some_and(|n| state.frames >= n) {
+                state.running = false;
+            } else {
+                state.present(qh);
+            }
         }
     }
 }
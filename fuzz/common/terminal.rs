@@ -0,0 +1,33 @@
+//! The terminal shared by the terminal fuzz targets
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
+
+use terminal::Terminal;
+
+/// Scrollback kept by the grid (alacritty's default `scrolling_history`)
+pub const SCROLLBACK_LIMIT: usize = 10_000;
+
+thread_local! {
+    /// One terminal for all inputs: spawning a PTY per input is far too slow.
+    /// The command exits at once; only the grid is exercised.
+    static TERMINAL: RefCell<Option<Terminal>> = const { RefCell::new(None) };
+}
+
+/// Run `f` on the shared terminal, reset to a blank 80 column grid
+pub fn with_terminal(f: impl FnOnce(&mut Terminal)) {
+    TERMINAL.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        if cell.is_none() {
+            *cell = Terminal::new_with_command(80, 40, 5, "true", Path::new("/"), &HashMap::new()).ok();
+        }
+        let Some(terminal) = cell.as_mut() else {
+            return;
+        };
+        // Full reset (RIS): clears the grid, scrollback and modes
+        let _ = terminal.process_output(b"\x1bc");
+        terminal.resize_cols(80);
+        f(terminal);
+    });
+}
@@ -0,0 +1,32 @@
+#![no_main]
+//! Fuzz target for reading IPC requests off the socket
+//!
+//! Sends random bytes through `read_ipc_request` on a real socket pair,
+//! covering truncated lines, missing newlines, invalid UTF-8 and request
+//! validation on top of JSON parsing (see `fuzz_ipc`). Reading must never
+//! panic, and must always come back: a client can't wedge the compositor.
+
+use std::io::Write;
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::time::{Duration, Instant};
+
+use libfuzzer_sys::fuzz_target;
+
+use compositor::ipc::read_ipc_request;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok((mut client, server)) = UnixStream::pair() else {
+        return;
+    };
+    // Inputs are far smaller than the socket buffer, so this doesn't block
+    // (and the client is done before the reader can close its end)
+    if client.write_all(data).is_err() {
+        return;
+    }
+    let _ = client.shutdown(Shutdown::Write);
+
+    let start = Instant::now();
+    let _ = read_ipc_request(server);
+    assert!(start.elapsed() < Duration::from_secs(3), "read outlived its deadline");
+});
@@ -0,0 +1,31 @@
+#![no_main]
+//! Fuzz target for the terminal's output processing
+//!
+//! Feeds random bytes through the same path as PTY output (escape-sequence
+//! parser, grid updates and sizing) to find panics, runaway memory use, or a
+//! grid that no longer matches the terminal's dimensions. Programs run in a
+//! cell can print anything, so none of it may be trusted.
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../common/terminal.rs"]
+mod common;
+
+use common::{with_terminal, SCROLLBACK_LIMIT};
+
+fuzz_target!(|data: &[u8]| {
+    with_terminal(|terminal| {
+        // Same chunk size as PTY reads
+        for chunk in data.chunks(4096) {
+            let _ = terminal.process_output(chunk);
+        }
+        // Answer queries the input made (device attributes, colors)
+        let _ = terminal.process_pty();
+
+        let (cols, rows) = terminal.dimensions();
+        let (grid_cols, grid_rows, history) = terminal.grid_size();
+        assert_eq!((grid_cols, grid_rows), (cols as usize, rows as usize));
+        assert!(history <= SCROLLBACK_LIMIT, "scrollback grew to {history} lines");
+        assert!(terminal.cursor_line() < rows);
+    });
+});
@@ -0,0 +1,83 @@
+#![no_main]
+//! Fuzz target for escape sequences and resizes
+//!
+//! Random bytes rarely form valid escape sequences, so this target builds
+//! output from well-formed pieces (text, CSI, OSC and ESC sequences, line
+//! endings) and mixes in column changes and configures, checking after each
+//! step that the grid still matches the terminal's width and holds the cursor.
+
+use libfuzzer_sys::arbitrary::{Result, Unstructured};
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../common/terminal.rs"]
+mod common;
+
+use common::{with_terminal, SCROLLBACK_LIMIT};
+
+/// CSI final bytes alacritty handles (cursor movement, erasing, scrolling
+/// regions, modes, SGR, ...)
+const CSI_FINALS: &[u8] = b"@ABCDEFGHIJKLMPSTXZ`abcdefghlmnpqrstu";
+
+/// Take one step from the input: output to process, or a resize
+enum Step {
+    Output(Vec<u8>),
+    ResizeCols(u16),
+    Configure(u16),
+}
+
+fn step(u: &mut Unstructured) -> Result<Step> {
+    Ok(match u.int_in_range(0..=7)? {
+        0 => Step::Output(u.arbitrary::<String>()?.into_bytes()),
+        1 => {
+            let count = u.int_in_range(0..=4)?;
+            let mut params = Vec::new();
+            for _ in 0..count {
+                params.push(u.int_in_range(0..=1200u16)?.to_string());
+            }
+            let private = if u.arbitrary()? { "?" } else { "" };
+            let end = *u.choose(CSI_FINALS)? as char;
+            Step::Output(format!("\x1b[{private}{}{end}", params.join(";")).into_bytes())
+        }
+        2 => {
+            let number = u.int_in_range(0..=133u16)?;
+            let text: String = u.arbitrary()?;
+            let end = if u.arbitrary()? { "\x07" } else { "\x1b\\" };
+            Step::Output(format!("\x1b]{number};{text}{end}").into_bytes())
+        }
+        3 => Step::Output(vec![0x1b, u.int_in_range(0x20..=0x7e)?]),
+        4 => Step::Output(b"\r\n".repeat(u.int_in_range(1..=200)?)),
+        5 => Step::Output(u.arbitrary::<Vec<u8>>()?),
+        6 => Step::ResizeCols(u.int_in_range(1..=300)?),
+        _ => Step::Configure(u.int_in_range(1..=200)?),
+    })
+}
+
+fuzz_target!(|data: &[u8]| {
+    with_terminal(|terminal| {
+        let mut u = Unstructured::new(data);
+        while !u.is_empty() {
+            let Ok(step) = step(&mut u) else {
+                break;
+            };
+            match step {
+                Step::Output(bytes) => {
+                    let _ = terminal.process_output(&bytes);
+                }
+                Step::ResizeCols(cols) => terminal.resize_cols(cols),
+                Step::Configure(rows) => {
+                    let _ = terminal.configure(rows);
+                    let _ = terminal.complete_resize();
+                }
+            }
+
+            // The primary screen's grid keeps its rows when the PTY shrinks,
+            // but columns always follow
+            let (cols, _) = terminal.dimensions();
+            let (grid_cols, grid_rows, history) = terminal.grid_size();
+            assert_eq!(grid_cols, cols as usize, "grid columns out of sync");
+            assert!((terminal.cursor_line() as usize) < grid_rows, "cursor below the grid");
+            assert!(history <= SCROLLBACK_LIMIT, "scrollback grew to {history} lines");
+        }
+        let _ = terminal.process_pty();
+    });
+});
@@ -7,6 +7,7 @@ if set -q TERMSTACK_SOCKET
     end
 
     # Shell commands that modify launcher shell state — run in current shell.
+    # Functions and aliases calling one of them run there too (see below).
     # Users can extend via: set -g __termstack_shell_commands ... in config.fish
     if not set -q __termstack_shell_commands
         set -g __termstack_shell_commands \
@@ -18,6 +19,98 @@ if set -q TERMSTACK_SOCKET
             eval
     end
 
+    # Answers for functions, cached until their definition changes
+    set -g __termstack_function_names
+    set -g __termstack_function_definitions
+    set -g __termstack_function_results
+
+    # Whether a command runs in the current shell: the commands listed above,
+    # and functions (fish aliases included) whose body calls one of them, so
+    # `alias up 'cd ..'` changes this shell's directory while `alias ll 'ls -l'`
+    # still spawns a cell. `type` tells functions from builtins and programs.
+    function __termstack_runs_in_shell --argument-names name depth
+        if contains -- $name $__termstack_shell_commands
+            return 0
+        end
+        test -n "$depth"; or set depth 0
+        set -l kind (type --type -- $name 2>/dev/null)
+        if test "$kind" = function; and test $depth -lt 3
+            __termstack_function_runs_in_shell $name $depth
+            return
+        end
+        return 1
+    end
+
+    # Read a function's body with `functions` and check every command it
+    # calls. `set` only counts when it exports: functions keep caches and
+    # locals in variables without changing what later commands see.
+    function __termstack_function_runs_in_shell --argument-names name depth
+        set -l definition (functions --no-details -- $name | string collect)
+        set -l index (contains --index -- $name $__termstack_function_names)
+        if test -n "$index"; and test "$__termstack_function_definitions[$index]" = "$definition"
+            return $__termstack_function_results[$index]
+        end
+
+        set -l result 1
+        # Skip the `function` and `end` lines
+        for line in (string split \n -- $definition)[2..-2]
+            # One statement per `;`, `&&`, `||` or pipe
+            set -l statements (string replace -ra -- '&&|\|\||\|' ';' $line)
+            for statement in (string split ';' -- $statements)
+                set statement (string trim -- $statement | string replace -r -- '^((and|or|not|builtin)\s+)+' '')
+                set -l word (string split ' ' -- $statement)[1]
+                if test -z "$word"; or test "$word" = $name; or string match -q -- '#*' $word
+                    continue
+                end
+                if test "$word" = set
+                    if string match -qr -- '^set(\s+-\S+)*\s+(-[a-zA-Z]*x[a-zA-Z]*|--export)(\s|$)' $statement
+                        set result 0
+                    end
+                else if __termstack_runs_in_shell $word (math $depth + 1)
+                    set result 0
+                end
+                test $result -eq 0; and break
+            end
+            test $result -eq 0; and break
+        end
+
+        if test -n "$index"
+            set -g __termstack_function_definitions[$index] $definition
+            set -g __termstack_function_results[$index] $result
+        else
+            set -ga __termstack_function_names $name
+            set -ga __termstack_function_definitions $definition
+            set -ga __termstack_function_results $result
+        end
+        return $result
+    end
+
+    # The command line with an alias in command position replaced by what it
+    # stands for, so a spawned cell (which doesn't have this shell's
+    # functions) runs it and its title bar shows it: after `alias ll 'ls -l'`,
+    # `ll -a` becomes `ls -l -a`. Aliases are the functions whose description
+    # `alias` wrote; aliases of aliases are followed up to three levels deep.
+    function __termstack_expand_alias --argument-names cmd
+        # Leading blanks stay: they can be the inline prefix
+        set -l indent (string match -r -- '^\s*' $cmd)
+        set -l cmd (string trim -- $cmd | string collect)
+        for i in 1 2 3
+            set -l words (string split -m 1 ' ' -- $cmd)
+            set -l kind (type --type -- $words[1] 2>/dev/null)
+            test "$kind" = function; or break
+            set -l description (functions --details --verbose -- $words[1])[5]
+            set -l expansion (string replace -rf -- '^alias \Q'$words[1]'\E[= ]' '' $description)
+            or break
+            set expansion (string replace -r -- '^command\s+' '' $expansion)
+            set cmd (string join ' ' -- $expansion $words[2] | string collect)
+            # `alias ls 'ls -F'` runs the program it shadows: expanding that
+            # again would repeat the options
+            set -l program (string split ' ' -- $expansion)[1]
+            test "$program" = $words[1]; and break
+        end
+        printf '%s%s\n' "$indent" $cmd
+    end
+
     # Define 'gui' as a function for launching GUI apps
     # Usage: gui <command>           # foreground mode (launcher hidden until GUI exits)
     # Usage: gui -b <command>        # background mode (launcher stays visible)
@@ -57,7 +150,23 @@ if set -q TERMSTACK_SOCKET
         end
     end
 
+    # Shells started before XWayland was ready have no DISPLAY; ask for it
+    # until the compositor has a final answer (`termstack env` fails meanwhile)
+    function __termstack_update_env
+        if set -q DISPLAY; or set -q __termstack_env_settled
+            return
+        end
+        set -l vars ($TERMSTACK_BIN env 2>/dev/null)
+        or return
+        set -g __termstack_env_settled 1
+        for var in $vars
+            set -l kv (string split -m 1 = -- $var)
+            set -gx $kv[1] $kv[2]
+        end
+    end
+
     function termstack_exec
+        __termstack_update_env
         set -l cmd (commandline)
 
         # Capture prompt BEFORE any command execution (shows state at command entry time)
@@ -92,6 +201,17 @@ if set -q TERMSTACK_SOCKET
             return
         end
 
+        # Arithmetic and unit conversions (`2^10`, `5 km to mi`) are answered
+        # in a short-lived entry; anything termstack can't evaluate runs as usual
+        if string match -qr -- '^[-+(.0-9][-+*/%^().0-9A-Za-z_ \t]*$' $trimmed
+            if $TERMSTACK_BIN --calc "$prompt_str" "$trimmed" 2>/dev/null
+                history append -- "$cmd"
+                commandline ""
+                commandline -f repaint
+                return
+            end
+        end
+
         # Syntax check (fish 3.4+): 0 = valid, 1 = error, 2 = incomplete
         commandline --is-valid
         if test $status -ne 0
@@ -100,7 +220,7 @@ if set -q TERMSTACK_SOCKET
         end
 
         # State-affecting commands — run in current shell, record in stack
-        if contains -- $first_word $__termstack_shell_commands
+        if __termstack_runs_in_shell $first_word
             set -l tmpfile (mktemp)
 
             # eval runs in current shell context, so cd/export/etc affect this shell
@@ -126,8 +246,15 @@ if set -q TERMSTACK_SOCKET
             commandline ""
             commandline -f repaint
         else
-            # Regular command — spawn in new terminal
-            TERMSTACK_PROMPT="$prompt_str" $TERMSTACK_BIN -c "$cmd"
+            # Regular command — spawn in new terminal, aliases expanded
+            set -l output (TERMSTACK_PROMPT="$prompt_str" $TERMSTACK_BIN -c (__termstack_expand_alias $cmd | string collect))
+            if test $status -eq 2
+                # It starts with `inline_prefix`: run the rest right here
+                commandline -r -- (string join \n -- $output)
+                commandline -f execute
+                return
+            end
+            printf '%s' $output
 
             history append -- "$cmd"
             commandline ""
@@ -135,8 +262,9 @@ if set -q TERMSTACK_SOCKET
         end
     end
 
-    bind \r termstack_exec
-    bind \n termstack_exec
+    # Expand an abbreviation under the cursor first, as `execute` would
+    bind \r expand-abbr termstack_exec
+    bind \n expand-abbr termstack_exec
 else
     # Not inside compositor - only show message if sourced interactively (not from config.fish)
     if status --is-interactive; and test -z "$__termstack_integration_sourced"